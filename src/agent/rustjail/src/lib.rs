@@ -447,6 +447,7 @@ fn linux_grpc_to_oci(l: &grpc::Linux) -> oci::Linux {
 
         Some(oci::LinuxIntelRdt {
             l3_cache_schema: rdt.L3CacheSchema.clone(),
+            ..Default::default()
         })
     } else {
         None
@@ -10,6 +10,7 @@ use nix::fcntl::{self, OFlag};
 use nix::mount;
 use nix::mount::{MntFlags, MsFlags};
 use nix::sys::stat::{self, Mode, SFlag};
+use nix::sys::statvfs::{statvfs, FsFlags};
 use nix::unistd::{self, Gid, Uid};
 use nix::NixPath;
 use oci::{LinuxDevice, Mount, Process, Spec};
@@ -1056,6 +1057,8 @@ pub fn finish_rootfs(cfd_log: RawFd, spec: &Spec, process: &Process) -> Result<(
     Ok(())
 }
 
+// mask_path hides the path from the container the same way as runc: files are
+// masked with /dev/null and directories with an empty read-only tmpfs.
 fn mask_path(path: &str) -> Result<()> {
     check_paths(path)?;
 
@@ -1067,13 +1070,26 @@ fn mask_path(path: &str) -> Result<()> {
         None::<&str>,
     ) {
         Err(e) => match e {
-            nix::Error::ENOENT | nix::Error::ENOTDIR => Ok(()),
+            nix::Error::ENOENT => Ok(()),
+            nix::Error::ENOTDIR => {
+                mount(
+                    Some("tmpfs"),
+                    path,
+                    Some("tmpfs"),
+                    MsFlags::MS_RDONLY,
+                    None::<&str>,
+                )?;
+                Ok(())
+            }
             _ => Err(e.into()),
         },
         Ok(_) => Ok(()),
     }
 }
 
+// readonly_path remounts the path read-only, keeping the nosuid, nodev and
+// noexec flags of the mount it's in as runc does, since they can't be cleared
+// in a user namespace.
 fn readonly_path(path: &str) -> Result<()> {
     check_paths(path)?;
 
@@ -1090,17 +1106,36 @@ fn readonly_path(path: &str) -> Result<()> {
         };
     }
 
+    let flags = locked_mount_flags(path)?;
     mount(
         Some(&path[1..]),
         &path[1..],
         None::<&str>,
-        MsFlags::MS_BIND | MsFlags::MS_REC | MsFlags::MS_RDONLY | MsFlags::MS_REMOUNT,
+        flags | MsFlags::MS_BIND | MsFlags::MS_REMOUNT | MsFlags::MS_RDONLY,
         None::<&str>,
     )?;
 
     Ok(())
 }
 
+// locked_mount_flags gets the flags of the mount the path is in that a bind
+// remount must keep.
+fn locked_mount_flags(path: &str) -> Result<MsFlags> {
+    let stat = statvfs(path).context(format!("statvfs {}", path))?;
+    let mut flags = MsFlags::empty();
+    for (fs_flag, ms_flag) in [
+        (FsFlags::ST_NOSUID, MsFlags::MS_NOSUID),
+        (FsFlags::ST_NODEV, MsFlags::MS_NODEV),
+        (FsFlags::ST_NOEXEC, MsFlags::MS_NOEXEC),
+    ] {
+        if stat.flags().contains(fs_flag) {
+            flags |= ms_flag;
+        }
+    }
+
+    Ok(flags)
+}
+
 fn check_paths(path: &str) -> Result<()> {
     if !path.starts_with('/') || path.contains("..") {
         return Err(anyhow!(
@@ -1329,6 +1364,17 @@ mod tests {
         assert!(ret.is_ok(), "Should pass. Got: {:?}", ret);
     }
 
+    #[test]
+    fn test_locked_mount_flags() {
+        // procfs is mounted with nosuid, nodev and noexec, these flags must
+        // be kept when the readonly paths under /proc are remounted.
+        let flags = locked_mount_flags("/proc").unwrap();
+        assert!(flags.contains(MsFlags::MS_NOSUID | MsFlags::MS_NODEV | MsFlags::MS_NOEXEC));
+
+        let ret = locked_mount_flags("/does-not-exist");
+        assert!(ret.is_err(), "Should fail. Got: {:?}", ret);
+    }
+
     #[test]
     #[serial(chdir)]
     fn test_mknod_dev() {
@@ -0,0 +1,195 @@
+// Copyright (c) 2023 Kata Containers community
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+// Checkpoint of the containers by CRIU, so that the long running workloads
+// could be migrated off the node. The image is written in the guest, and read
+// by the runtime with ReadFile.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use anyhow::{anyhow, Context, Result};
+use oci::Spec;
+
+const CRIU_PATH: &str = "criu";
+// the image dir of a container, in the dir of the container
+const CHECKPOINT_DIR: &str = "checkpoint";
+// written in the image dir
+const DUMP_LOG_FILE: &str = "dump.log";
+// the lines of the end of the dump log reported when criu fails
+const DUMP_LOG_TAIL_LINES: usize = 20;
+
+const BIND_MOUNT_TYPE: &str = "bind";
+const BIND_MOUNT_OPTIONS: [&str; 2] = ["bind", "rbind"];
+const DEV_NULL: &str = "/dev/null";
+
+/// Checkpoint the container `cid` whose init process is `pid` into its image
+/// dir in `base`, which replaces the image of the previous checkpoint. The
+/// image dir and the names of its files are returned.
+pub fn checkpoint_container(
+    base: &str,
+    cid: &str,
+    pid: i32,
+    spec: &Spec,
+    leave_running: bool,
+) -> Result<(PathBuf, Vec<String>)> {
+    let image_dir = Path::new(base).join(cid).join(CHECKPOINT_DIR);
+    if image_dir.exists() {
+        fs::remove_dir_all(&image_dir)
+            .with_context(|| format!("remove image dir {}", image_dir.display()))?;
+    }
+    fs::create_dir_all(&image_dir)
+        .with_context(|| format!("create image dir {}", image_dir.display()))?;
+
+    let args = criu_dump_args(pid, spec, &image_dir, leave_running, |path| {
+        is_file_in_container(pid, path)
+    });
+    let output = Command::new(CRIU_PATH)
+        .args(&args)
+        .output()
+        .context("run criu")?;
+    if !output.status.success() {
+        let log = fs::read_to_string(image_dir.join(DUMP_LOG_FILE)).unwrap_or_default();
+        let mut tail: Vec<&str> = log.lines().rev().take(DUMP_LOG_TAIL_LINES).collect();
+        tail.reverse();
+        return Err(anyhow!(
+            "criu dump failed: {}{}",
+            String::from_utf8_lossy(&output.stderr),
+            tail.join("\n")
+        ));
+    }
+
+    let mut files = vec![];
+    for entry in fs::read_dir(&image_dir)? {
+        let entry = entry?;
+        if entry.file_type()?.is_file() {
+            files.push(entry.file_name().to_string_lossy().into_owned());
+        }
+    }
+    files.sort();
+    Ok((image_dir, files))
+}
+
+// criu_dump_args returns the arguments of criu dump. The bind mounts of the
+// container are external to its mount namespace, they're bound again from the
+// same paths by the restore, and the masked files are bind mounts of
+// /dev/null like runc does.
+fn criu_dump_args(
+    pid: i32,
+    spec: &Spec,
+    image_dir: &Path,
+    leave_running: bool,
+    is_file: impl Fn(&str) -> bool,
+) -> Vec<String> {
+    let mut args: Vec<String> = vec![
+        "dump".to_string(),
+        "--tree".to_string(),
+        pid.to_string(),
+        "--images-dir".to_string(),
+        image_dir.display().to_string(),
+        "--log-file".to_string(),
+        DUMP_LOG_FILE.to_string(),
+        "--manage-cgroups".to_string(),
+        "--tcp-established".to_string(),
+        "--ext-unix-sk".to_string(),
+        "--file-locks".to_string(),
+    ];
+    if let Some(root) = spec.root.as_ref() {
+        args.push("--root".to_string());
+        args.push(root.path.clone());
+    }
+    if leave_running {
+        args.push("--leave-running".to_string());
+    }
+
+    let bind_mounts = spec.mounts.iter().filter(|m| {
+        m.r#type == BIND_MOUNT_TYPE
+            || m.options
+                .iter()
+                .any(|o| BIND_MOUNT_OPTIONS.contains(&o.as_str()))
+    });
+    for m in bind_mounts {
+        args.push("--external".to_string());
+        args.push(format!("mnt[{}]:{}", m.destination, m.destination));
+    }
+    if let Some(linux) = spec.linux.as_ref() {
+        for path in linux.masked_paths.iter().filter(|p| is_file(p)) {
+            args.push("--external".to_string());
+            args.push(format!("mnt[{}]:{}", path, DEV_NULL));
+        }
+    }
+
+    args
+}
+
+// the masked paths which don't exist aren't mounted, and the masked dirs are
+// read-only tmpfs mounts which criu handles
+fn is_file_in_container(pid: i32, path: &str) -> bool {
+    let path = format!("/proc/{}/root/{}", pid, path.trim_start_matches('/'));
+    fs::metadata(path).map_or(false, |m| !m.is_dir())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_criu_dump_args() {
+        let spec = Spec {
+            root: Some(oci::Root {
+                path: "/run/kata-containers/c1/rootfs".to_string(),
+                ..Default::default()
+            }),
+            mounts: vec![
+                oci::Mount {
+                    destination: "/proc".to_string(),
+                    r#type: "proc".to_string(),
+                    ..Default::default()
+                },
+                oci::Mount {
+                    destination: "/etc/hosts".to_string(),
+                    r#type: "bind".to_string(),
+                    ..Default::default()
+                },
+                oci::Mount {
+                    destination: "/data".to_string(),
+                    r#type: "none".to_string(),
+                    options: vec!["rbind".to_string(), "ro".to_string()],
+                    ..Default::default()
+                },
+            ],
+            linux: Some(oci::Linux {
+                masked_paths: vec!["/proc/kcore".to_string(), "/proc/acpi".to_string()],
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let args = criu_dump_args(1234, &spec, Path::new("/run/c1/checkpoint"), false, |p| {
+            p == "/proc/kcore"
+        });
+        let args = args.join(" ");
+
+        assert!(args.starts_with("dump --tree 1234 --images-dir /run/c1/checkpoint "));
+        assert!(args.contains(" --root /run/kata-containers/c1/rootfs"));
+        assert!(!args.contains("--leave-running"));
+        assert!(args.contains(" --external mnt[/etc/hosts]:/etc/hosts"));
+        assert!(args.contains(" --external mnt[/data]:/data"));
+        assert!(!args.contains("mnt[/proc]"));
+        assert!(args.ends_with(" --external mnt[/proc/kcore]:/dev/null"));
+
+        let args = criu_dump_args(1234, &Spec::default(), Path::new("/c"), true, |_| true);
+        assert!(args.contains(&"--leave-running".to_string()));
+        assert!(!args.contains(&"--root".to_string()));
+    }
+
+    #[test]
+    fn test_is_file_in_container() {
+        let pid = std::process::id() as i32;
+        assert!(is_file_in_container(pid, "/proc/self/status"));
+        assert!(!is_file_in_container(pid, "/proc"));
+        assert!(!is_file_in_container(pid, "/nonexistent"));
+    }
+}
@@ -22,6 +22,7 @@ const SERVER_ADDR_OPTION: &str = "agent.server_addr";
 const HOTPLUG_TIMOUT_OPTION: &str = "agent.hotplug_timeout";
 const DEBUG_CONSOLE_VPORT_OPTION: &str = "agent.debug_console_vport";
 const LOG_VPORT_OPTION: &str = "agent.log_vport";
+const GUEST_METRICS_PORTS_OPTION: &str = "agent.guest_metrics_ports";
 const CONTAINER_PIPE_SIZE_OPTION: &str = "agent.container_pipe_size";
 const UNIFIED_CGROUP_HIERARCHY_OPTION: &str = "agent.unified_cgroup_hierarchy";
 const CONFIG_FILE: &str = "agent.config_file";
@@ -71,6 +72,7 @@ pub struct AgentConfig {
     pub hotplug_timeout: time::Duration,
     pub debug_console_vport: i32,
     pub log_vport: i32,
+    pub guest_metrics_ports: Vec<u32>,
     pub container_pipe_size: i32,
     pub server_addr: String,
     pub unified_cgroup_hierarchy: bool,
@@ -87,6 +89,7 @@ pub struct AgentConfigBuilder {
     pub hotplug_timeout: Option<time::Duration>,
     pub debug_console_vport: Option<i32>,
     pub log_vport: Option<i32>,
+    pub guest_metrics_ports: Option<Vec<u32>>,
     pub container_pipe_size: Option<i32>,
     pub server_addr: Option<String>,
     pub unified_cgroup_hierarchy: Option<bool>,
@@ -147,6 +150,7 @@ impl Default for AgentConfig {
             hotplug_timeout: DEFAULT_HOTPLUG_TIMEOUT,
             debug_console_vport: 0,
             log_vport: 0,
+            guest_metrics_ports: vec![],
             container_pipe_size: DEFAULT_CONTAINER_PIPE_SIZE,
             server_addr: format!("{}:{}", VSOCK_ADDR, DEFAULT_AGENT_VSOCK_PORT),
             unified_cgroup_hierarchy: false,
@@ -177,6 +181,7 @@ impl FromStr for AgentConfig {
         config_override!(agent_config_builder, agent_config, hotplug_timeout);
         config_override!(agent_config_builder, agent_config, debug_console_vport);
         config_override!(agent_config_builder, agent_config, log_vport);
+        config_override!(agent_config_builder, agent_config, guest_metrics_ports);
         config_override!(agent_config_builder, agent_config, container_pipe_size);
         config_override!(agent_config_builder, agent_config, server_addr);
         config_override!(agent_config_builder, agent_config, unified_cgroup_hierarchy);
@@ -267,6 +272,12 @@ impl AgentConfig {
                 get_vsock_port,
                 |port| port > 0
             );
+            parse_cmdline_param!(
+                param,
+                GUEST_METRICS_PORTS_OPTION,
+                config.guest_metrics_ports,
+                get_guest_metrics_ports
+            );
 
             parse_cmdline_param!(
                 param,
@@ -414,6 +425,23 @@ fn get_string_value(param: &str) -> Result<String> {
     Ok(value)
 }
 
+// The ports of the in-guest metrics exporters, e.g. "9400,9100", each one is
+// proxied from the vsock port of the same number.
+#[instrument]
+fn get_guest_metrics_ports(param: &str) -> Result<Vec<u32>> {
+    let value = get_string_value(param)?;
+    value
+        .split(',')
+        .map(|port| {
+            let port = port
+                .parse::<u16>()
+                .with_context(|| format!("invalid guest metrics port {:?}", port))?;
+            ensure!(port > 0, "guest metrics port should be positive");
+            Ok(port as u32)
+        })
+        .collect()
+}
+
 #[instrument]
 fn get_container_pipe_size(param: &str) -> Result<i32> {
     let fields: Vec<&str> = param.split('=').collect();
@@ -1372,12 +1400,29 @@ Caused by:
         }
     }
 
+    #[test]
+    fn test_get_guest_metrics_ports() {
+        assert_eq!(
+            get_guest_metrics_ports("agent.guest_metrics_ports=9400").unwrap(),
+            vec![9400]
+        );
+        assert_eq!(
+            get_guest_metrics_ports("agent.guest_metrics_ports=9400,9100").unwrap(),
+            vec![9400, 9100]
+        );
+        assert!(get_guest_metrics_ports("agent.guest_metrics_ports=").is_err());
+        assert!(get_guest_metrics_ports("agent.guest_metrics_ports=0").is_err());
+        assert!(get_guest_metrics_ports("agent.guest_metrics_ports=70000").is_err());
+        assert!(get_guest_metrics_ports("agent.guest_metrics_ports=9400,foo").is_err());
+    }
+
     #[test]
     fn test_config_builder_from_string() {
         let config = AgentConfig::from_str(
             r#"
                dev_mode = true
                server_addr = 'vsock://8:2048'
+               guest_metrics_ports = [9400]
 
                [endpoints]
                allowed = ["CreateContainer", "StartContainer"]
@@ -1391,6 +1436,7 @@ Caused by:
         // Verify that the override worked
         assert!(config.dev_mode);
         assert_eq!(config.server_addr, "vsock://8:2048");
+        assert_eq!(config.guest_metrics_ports, vec![9400]);
         assert_eq!(
             config.endpoints.allowed,
             ["CreateContainer".to_string(), "StartContainer".to_string()]
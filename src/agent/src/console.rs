@@ -25,7 +25,8 @@ use std::sync::Mutex as SyncMutex;
 use futures::StreamExt;
 use tokio::io::{AsyncRead, AsyncWrite};
 use tokio::select;
-use tokio::sync::watch::Receiver;
+use tokio::sync::watch::{channel, Receiver, Sender};
+use tokio::task::JoinHandle;
 
 const CONSOLE_PATH: &str = "/dev/console";
 
@@ -46,6 +47,39 @@ pub fn initialize() {
     lazy_static::initialize(&SHELLS);
 }
 
+/// A running debug console, which could be stopped without shutting down the agent.
+#[derive(Debug)]
+pub struct DebugConsole {
+    port: u32,
+    shutdown: Sender<bool>,
+    handle: JoinHandle<Result<()>>,
+}
+
+impl DebugConsole {
+    pub fn start(logger: Logger, port: u32) -> Self {
+        let (shutdown, shutdown_rx) = channel(true);
+        let handle = tokio::spawn(debug_console_handler(logger, port, shutdown_rx));
+
+        DebugConsole {
+            port,
+            shutdown,
+            handle,
+        }
+    }
+
+    pub fn port(&self) -> u32 {
+        self.port
+    }
+
+    /// Stop listening for new debug console connections, the sessions
+    /// already established are kept until they exit.
+    pub async fn stop(self) -> Result<()> {
+        // The handler may have exited on its own, e.g. no shell found.
+        let _ = self.shutdown.send(true);
+        self.handle.await?
+    }
+}
+
 pub async fn debug_console_handler(
     logger: Logger,
     port: u32,
@@ -0,0 +1,488 @@
+// Copyright (c) 2023 Kata Containers community
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+// A lightweight DNS forwarder of the sandbox, which caches the lookups of the
+// pod and falls back across the nameservers of the pod, so that the pods with
+// a distant cluster DNS don't pay the round trip on every lookup.
+
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, Context, Result};
+use slog::Logger;
+use tokio::net::{TcpListener, TcpStream, UdpSocket};
+use tokio::sync::Mutex;
+use tokio::time::timeout;
+
+// The forwarder listens on the loopback of the guest, which is shared by all
+// the containers of the sandbox.
+const DNS_FORWARDER_IP: Ipv4Addr = Ipv4Addr::LOCALHOST;
+const DNS_PORT: u16 = 53;
+
+const UPSTREAM_TIMEOUT: Duration = Duration::from_secs(2);
+const MAX_CACHE_ENTRIES: usize = 1024;
+// The TTLs of the cached responses are not decreased, so keep them short.
+const MAX_CACHE_TTL_SECS: u32 = 300;
+// EDNS0 allows responses larger than 512 bytes over UDP.
+const MAX_UDP_MSG_SIZE: usize = 4096;
+
+const DNS_HEADER_LEN: usize = 12;
+const DNS_FLAG_TC: u16 = 0x0200;
+const DNS_RCODE_MASK: u16 = 0x000f;
+const DNS_RCODE_NOERROR: u16 = 0;
+const DNS_RCODE_NXDOMAIN: u16 = 3;
+
+struct CacheEntry {
+    response: Vec<u8>,
+    expire: Instant,
+}
+
+// Cache of the DNS responses, keyed by the question of the query.
+#[derive(Default)]
+struct DnsCache {
+    entries: HashMap<Vec<u8>, CacheEntry>,
+}
+
+impl DnsCache {
+    fn get(&mut self, key: &[u8], now: Instant) -> Option<Vec<u8>> {
+        match self.entries.get(key) {
+            Some(e) if e.expire > now => Some(e.response.clone()),
+            Some(_) => {
+                self.entries.remove(key);
+                None
+            }
+            None => None,
+        }
+    }
+
+    fn insert(&mut self, key: Vec<u8>, response: Vec<u8>, ttl: u32, now: Instant) {
+        if ttl == 0 {
+            return;
+        }
+
+        if self.entries.len() >= MAX_CACHE_ENTRIES && !self.entries.contains_key(&key) {
+            self.entries.retain(|_, e| e.expire > now);
+        }
+        if self.entries.len() >= MAX_CACHE_ENTRIES && !self.entries.contains_key(&key) {
+            // evict the one expiring first
+            if let Some(k) = self
+                .entries
+                .iter()
+                .min_by_key(|(_, e)| e.expire)
+                .map(|(k, _)| k.clone())
+            {
+                self.entries.remove(&k);
+            }
+        }
+
+        let ttl = ttl.min(MAX_CACHE_TTL_SECS);
+        self.entries.insert(
+            key,
+            CacheEntry {
+                response,
+                expire: now + Duration::from_secs(ttl as u64),
+            },
+        );
+    }
+}
+
+fn read_u16(msg: &[u8], pos: usize) -> Option<u16> {
+    Some(u16::from_be_bytes([*msg.get(pos)?, *msg.get(pos + 1)?]))
+}
+
+fn read_u32(msg: &[u8], pos: usize) -> Option<u32> {
+    Some(u32::from_be_bytes([
+        *msg.get(pos)?,
+        *msg.get(pos + 1)?,
+        *msg.get(pos + 2)?,
+        *msg.get(pos + 3)?,
+    ]))
+}
+
+// Skip the domain name at pos, returns the position right after it.
+fn skip_name(msg: &[u8], mut pos: usize) -> Option<usize> {
+    loop {
+        let len = *msg.get(pos)? as usize;
+        if len == 0 {
+            return Some(pos + 1);
+        }
+        // compression pointer
+        if len & 0xc0 == 0xc0 {
+            msg.get(pos + 1)?;
+            return Some(pos + 2);
+        }
+        pos += 1 + len;
+    }
+}
+
+// Key of a query for the cache, made of its only question with the name in
+// lowercase, as the names are case insensitive.
+fn question_key(msg: &[u8]) -> Option<Vec<u8>> {
+    if read_u16(msg, 4)? != 1 {
+        return None;
+    }
+
+    let name_end = skip_name(msg, DNS_HEADER_LEN)?;
+    let end = name_end + 4;
+    if end > msg.len() {
+        return None;
+    }
+
+    let mut key = msg[DNS_HEADER_LEN..name_end].to_ascii_lowercase();
+    key.extend_from_slice(&msg[name_end..end]);
+    Some(key)
+}
+
+// The time the response could be cached for, which is the minimum TTL of the
+// answer and authority records. None if the response shouldn't be cached.
+fn cache_ttl(msg: &[u8]) -> Option<u32> {
+    let flags = read_u16(msg, 2)?;
+    let rcode = flags & DNS_RCODE_MASK;
+    if flags & DNS_FLAG_TC != 0 || (rcode != DNS_RCODE_NOERROR && rcode != DNS_RCODE_NXDOMAIN) {
+        return None;
+    }
+
+    let qdcount = read_u16(msg, 4)?;
+    let records = read_u16(msg, 6)? as usize + read_u16(msg, 8)? as usize;
+
+    let mut pos = DNS_HEADER_LEN;
+    for _ in 0..qdcount {
+        pos = skip_name(msg, pos)? + 4;
+    }
+
+    let mut ttl: Option<u32> = None;
+    for _ in 0..records {
+        pos = skip_name(msg, pos)?;
+        let record_ttl = read_u32(msg, pos + 4)?;
+        let rdlen = read_u16(msg, pos + 8)? as usize;
+        pos += 10 + rdlen;
+        if pos > msg.len() {
+            return None;
+        }
+        ttl = Some(ttl.map_or(record_ttl, |t| t.min(record_ttl)));
+    }
+
+    ttl
+}
+
+/// Get the nameservers of the resolv.conf lines, the loopback ones are
+/// skipped as the forwarder takes over the loopback.
+pub fn nameservers(dns: &[String]) -> Vec<SocketAddr> {
+    dns.iter()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            if fields.next() != Some("nameserver") {
+                return None;
+            }
+            fields.next()?.parse::<IpAddr>().ok()
+        })
+        .filter(|ip| !ip.is_loopback())
+        .map(|ip| SocketAddr::new(ip, DNS_PORT))
+        .collect()
+}
+
+/// Rewrite the resolv.conf lines to resolve through the forwarder, the other
+/// settings, e.g. search domains and options, are kept.
+pub fn forwarder_resolv_conf(dns: &[String]) -> Vec<String> {
+    let mut lines: Vec<String> = dns
+        .iter()
+        .filter(|line| !line.trim_start().starts_with("nameserver"))
+        .cloned()
+        .collect();
+    lines.insert(0, format!("nameserver {}", DNS_FORWARDER_IP));
+    lines
+}
+
+struct DnsForwarder {
+    logger: Logger,
+    upstreams: Vec<SocketAddr>,
+    cache: Mutex<DnsCache>,
+}
+
+impl DnsForwarder {
+    async fn resolve(&self, query: &[u8]) -> Result<Vec<u8>> {
+        let key = question_key(query);
+        if let Some(key) = key.as_ref() {
+            if let Some(mut response) = self.cache.lock().await.get(key, Instant::now()) {
+                response[..2].copy_from_slice(&query[..2]);
+                return Ok(response);
+            }
+        }
+
+        let mut last_err = anyhow!("no upstream nameserver");
+        for upstream in &self.upstreams {
+            match query_upstream(*upstream, query).await {
+                Ok(response) => {
+                    if let (Some(key), Some(ttl)) = (key, cache_ttl(&response)) {
+                        self.cache
+                            .lock()
+                            .await
+                            .insert(key, response.clone(), ttl, Instant::now());
+                    }
+                    return Ok(response);
+                }
+                Err(e) => {
+                    debug!(self.logger, "nameserver {} failed: {:?}", upstream, e);
+                    last_err = e;
+                }
+            }
+        }
+
+        Err(last_err)
+    }
+
+    async fn serve_udp(self: Arc<Self>, socket: UdpSocket) {
+        let socket = Arc::new(socket);
+        let mut buf = vec![0u8; MAX_UDP_MSG_SIZE];
+        loop {
+            let (len, peer) = match socket.recv_from(&mut buf).await {
+                Ok(r) => r,
+                Err(e) => {
+                    warn!(self.logger, "failed to receive dns query: {:?}", e);
+                    continue;
+                }
+            };
+            if len < DNS_HEADER_LEN {
+                continue;
+            }
+
+            let query = buf[..len].to_vec();
+            let forwarder = self.clone();
+            let socket = socket.clone();
+            tokio::spawn(async move {
+                match forwarder.resolve(&query).await {
+                    Ok(response) => {
+                        let _ = socket.send_to(&response, peer).await;
+                    }
+                    Err(e) => warn!(forwarder.logger, "failed to resolve dns query: {:?}", e),
+                }
+            });
+        }
+    }
+
+    // The queries over TCP, e.g. the retries of the truncated responses, are
+    // passed through to the first reachable nameserver without caching.
+    async fn serve_tcp(self: Arc<Self>, listener: TcpListener) {
+        loop {
+            let mut client = match listener.accept().await {
+                Ok((s, _)) => s,
+                Err(e) => {
+                    warn!(self.logger, "failed to accept dns connection: {:?}", e);
+                    continue;
+                }
+            };
+
+            let forwarder = self.clone();
+            tokio::spawn(async move {
+                for upstream in &forwarder.upstreams {
+                    if let Ok(Ok(mut server)) =
+                        timeout(UPSTREAM_TIMEOUT, TcpStream::connect(upstream)).await
+                    {
+                        let _ = tokio::io::copy_bidirectional(&mut client, &mut server).await;
+                        return;
+                    }
+                }
+                warn!(forwarder.logger, "no nameserver reachable over tcp");
+            });
+        }
+    }
+}
+
+async fn query_upstream(upstream: SocketAddr, query: &[u8]) -> Result<Vec<u8>> {
+    let local: SocketAddr = if upstream.is_ipv4() {
+        (Ipv4Addr::UNSPECIFIED, 0).into()
+    } else {
+        (Ipv6Addr::UNSPECIFIED, 0).into()
+    };
+    let socket = UdpSocket::bind(local).await.context("bind")?;
+    socket.connect(upstream).await.context("connect")?;
+    socket.send(query).await.context("send")?;
+
+    let mut buf = vec![0u8; MAX_UDP_MSG_SIZE];
+    loop {
+        let len = timeout(UPSTREAM_TIMEOUT, socket.recv(&mut buf))
+            .await
+            .map_err(|_| anyhow!("timeout"))?
+            .context("recv")?;
+        // drop the stale responses of other queries
+        if len >= DNS_HEADER_LEN && buf[..2] == query[..2] {
+            return Ok(buf[..len].to_vec());
+        }
+    }
+}
+
+/// Start the DNS forwarder of the sandbox with the resolv.conf lines of the
+/// pod, returns the resolv.conf lines to resolve through the forwarder.
+pub async fn start(logger: Logger, dns: &[String]) -> Result<Vec<String>> {
+    let logger = logger.new(o!("subsystem" => "dns-forwarder"));
+    let upstreams = nameservers(dns);
+    if upstreams.is_empty() {
+        info!(
+            logger,
+            "no nameserver to forward to, skip the dns forwarder"
+        );
+        return Ok(dns.to_vec());
+    }
+
+    let addr = SocketAddr::new(IpAddr::V4(DNS_FORWARDER_IP), DNS_PORT);
+    let udp = UdpSocket::bind(addr)
+        .await
+        .context("bind dns forwarder udp socket")?;
+    let tcp = TcpListener::bind(addr)
+        .await
+        .context("bind dns forwarder tcp socket")?;
+
+    info!(logger, "start dns forwarder"; "upstreams" => format!("{:?}", upstreams));
+    let forwarder = Arc::new(DnsForwarder {
+        logger,
+        upstreams,
+        cache: Mutex::new(DnsCache::default()),
+    });
+    tokio::spawn(forwarder.clone().serve_udp(udp));
+    tokio::spawn(forwarder.serve_tcp(tcp));
+
+    Ok(forwarder_resolv_conf(dns))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // query of "Example.com. IN A"
+    fn query(id: u16) -> Vec<u8> {
+        let mut msg = id.to_be_bytes().to_vec();
+        msg.extend_from_slice(&[0x01, 0x00, 0, 1, 0, 0, 0, 0, 0, 0]);
+        msg.extend_from_slice(b"\x07Example\x03com\x00");
+        msg.extend_from_slice(&[0, 1, 0, 1]);
+        msg
+    }
+
+    fn response(query: &[u8], ttls: &[u32]) -> Vec<u8> {
+        let mut msg = query.to_vec();
+        msg[2] = 0x81;
+        msg[3] = 0x80;
+        msg[6..8].copy_from_slice(&(ttls.len() as u16).to_be_bytes());
+        for ttl in ttls {
+            // pointer to the name of the question
+            msg.extend_from_slice(&[0xc0, 0x0c, 0, 1, 0, 1]);
+            msg.extend_from_slice(&ttl.to_be_bytes());
+            msg.extend_from_slice(&[0, 4, 93, 184, 216, 34]);
+        }
+        msg
+    }
+
+    #[test]
+    fn test_question_key() {
+        let key = question_key(&query(1)).unwrap();
+        assert_eq!(key, question_key(&query(2)).unwrap());
+        assert_eq!(&key[..13], b"\x07example\x03com\x00");
+
+        // truncated query
+        assert!(question_key(&query(1)[..20]).is_none());
+    }
+
+    #[test]
+    fn test_cache_ttl() {
+        let q = query(1);
+        assert_eq!(cache_ttl(&response(&q, &[60, 30])), Some(30));
+        assert_eq!(cache_ttl(&response(&q, &[])), None);
+
+        // SERVFAIL isn't cached
+        let mut r = response(&q, &[60]);
+        r[3] = 0x82;
+        assert_eq!(cache_ttl(&r), None);
+
+        // truncated
+        let mut r = response(&q, &[60]);
+        r[2] |= 0x02;
+        assert_eq!(cache_ttl(&r), None);
+
+        let r = response(&q, &[60]);
+        assert_eq!(cache_ttl(&r[..r.len() - 1]), None);
+    }
+
+    #[test]
+    fn test_dns_cache() {
+        let now = Instant::now();
+        let mut cache = DnsCache::default();
+        cache.insert(b"k1".to_vec(), b"r1".to_vec(), 10, now);
+        cache.insert(b"k2".to_vec(), b"r2".to_vec(), 0, now);
+        assert_eq!(cache.get(b"k1", now), Some(b"r1".to_vec()));
+        assert_eq!(cache.get(b"k2", now), None);
+        assert_eq!(cache.get(b"k1", now + Duration::from_secs(10)), None);
+
+        // the ttl is capped
+        cache.insert(b"k3".to_vec(), b"r3".to_vec(), u32::MAX, now);
+        let expire = now + Duration::from_secs(MAX_CACHE_TTL_SECS as u64);
+        assert!(cache.get(b"k3", expire).is_none());
+
+        for i in 0..MAX_CACHE_ENTRIES + 1 {
+            cache.insert(i.to_string().into_bytes(), vec![], 20 + i as u32, now);
+        }
+        assert_eq!(cache.entries.len(), MAX_CACHE_ENTRIES);
+        // the one expiring first is evicted
+        assert!(cache.get(b"0", now).is_none());
+    }
+
+    #[test]
+    fn test_resolv_conf() {
+        let dns = vec![
+            "nameserver 10.96.0.10".to_string(),
+            "nameserver 127.0.0.53".to_string(),
+            "nameserver fd00::10".to_string(),
+            "search default.svc.cluster.local svc.cluster.local".to_string(),
+            "options ndots:5".to_string(),
+        ];
+
+        assert_eq!(
+            nameservers(&dns),
+            vec![
+                "10.96.0.10:53".parse::<SocketAddr>().unwrap(),
+                "[fd00::10]:53".parse::<SocketAddr>().unwrap(),
+            ]
+        );
+        assert_eq!(
+            forwarder_resolv_conf(&dns),
+            vec![
+                "nameserver 127.0.0.1",
+                "search default.svc.cluster.local svc.cluster.local",
+                "options ndots:5",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_resolve_fallback_and_cache() {
+        let upstream = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let upstream_addr = upstream.local_addr().unwrap();
+        // nobody answers on the first nameserver
+        let dead = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+
+        let forwarder = DnsForwarder {
+            logger: slog_scope::logger(),
+            upstreams: vec![dead.local_addr().unwrap(), upstream_addr],
+            cache: Mutex::new(DnsCache::default()),
+        };
+
+        let server = tokio::spawn(async move {
+            let mut buf = vec![0u8; MAX_UDP_MSG_SIZE];
+            let (len, peer) = upstream.recv_from(&mut buf).await.unwrap();
+            let r = response(&buf[..len], &[60]);
+            upstream.send_to(&r, peer).await.unwrap();
+        });
+
+        let q = query(1);
+        let r = forwarder.resolve(&q).await.unwrap();
+        assert_eq!(r, response(&q, &[60]));
+        server.await.unwrap();
+
+        // answered by the cache with the id of the query, the upstream is gone
+        let q = query(2);
+        let r = forwarder.resolve(&q).await.unwrap();
+        assert_eq!(r, response(&q, &[60]));
+    }
+}
@@ -0,0 +1,109 @@
+// Copyright (c) 2023 Kata Containers community
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+// The hooks run in the guest on behalf of the runtime, out of the containers,
+// e.g. the pre-stop hooks flushing the workloads of the guest before the VM
+// is stopped.
+
+use std::process::Stdio;
+use std::time::Duration;
+
+use anyhow::{anyhow, Context, Result};
+use tokio::process::Command;
+
+// the bytes of the end of the output reported when a hook fails
+const OUTPUT_TAIL_BYTES: usize = 1024;
+
+/// Run the hook `args`, the absolute path of the command and its arguments,
+/// it's killed if it doesn't exit in `timeout` seconds, 0 for no timeout.
+pub async fn exec_guest_hook(args: &[String], timeout: u32) -> Result<()> {
+    let path = args.first().ok_or_else(|| anyhow!("empty hook command"))?;
+    if !path.starts_with('/') {
+        return Err(anyhow!("hook command {} isn't an absolute path", path));
+    }
+
+    let child = Command::new(path)
+        .args(&args[1..])
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+        .with_context(|| format!("spawn {}", path))?;
+    // the hook is killed once its output future is dropped on timeout
+    let output = if timeout > 0 {
+        tokio::time::timeout(
+            Duration::from_secs(timeout as u64),
+            child.wait_with_output(),
+        )
+        .await
+        .map_err(|_| anyhow!("{} didn't exit in {} seconds", path, timeout))?
+    } else {
+        child.wait_with_output().await
+    }
+    .with_context(|| format!("wait {}", path))?;
+
+    if !output.status.success() {
+        return Err(anyhow!(
+            "{} failed with {}: {}",
+            path,
+            output.status,
+            output_tail(&output.stderr, &output.stdout)
+        ));
+    }
+    Ok(())
+}
+
+// output_tail returns the end of the error output of a hook, or of its
+// standard output if it wrote no error
+fn output_tail(stderr: &[u8], stdout: &[u8]) -> String {
+    let output = if stderr.is_empty() { stdout } else { stderr };
+    let start = output.len().saturating_sub(OUTPUT_TAIL_BYTES);
+    String::from_utf8_lossy(&output[start..]).trim().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|a| a.to_string()).collect()
+    }
+
+    #[tokio::test]
+    async fn test_exec_guest_hook() {
+        exec_guest_hook(&args(&["/bin/sh", "-c", "exit 0"]), 0)
+            .await
+            .unwrap();
+
+        let err = exec_guest_hook(
+            &args(&["/bin/sh", "-c", "echo flush failed >&2; exit 3"]),
+            5,
+        )
+        .await
+        .unwrap_err();
+        assert!(err.to_string().contains("flush failed"));
+
+        let err = exec_guest_hook(&args(&["/bin/sh", "-c", "sleep 10"]), 1)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("didn't exit in 1 seconds"));
+
+        // the commands aren't looked up in the PATH
+        assert!(exec_guest_hook(&args(&["sh", "-c", "exit 0"]), 0)
+            .await
+            .is_err());
+        assert!(exec_guest_hook(&[], 0).await.is_err());
+    }
+
+    #[test]
+    fn test_output_tail() {
+        assert_eq!(output_tail(b"", b"out\n"), "out");
+        assert_eq!(output_tail(b"err\n", b"out\n"), "err");
+
+        let long = vec![b'a'; OUTPUT_TAIL_BYTES * 2];
+        assert_eq!(output_tail(&long, b"").len(), OUTPUT_TAIL_BYTES);
+    }
+}
@@ -35,11 +35,15 @@ use std::process::exit;
 use std::sync::Arc;
 use tracing::{instrument, span};
 
+mod checkpoint;
 mod config;
 mod console;
 mod device;
+mod dns_forwarder;
+mod guest_hook;
 mod linux_abi;
 mod metrics;
+mod metrics_proxy;
 mod mount;
 mod namespace;
 mod netlink;
@@ -317,20 +321,17 @@ async fn start_sandbox(
     tasks: &mut Vec<JoinHandle<Result<()>>>,
     shutdown: Receiver<bool>,
 ) -> Result<()> {
-    let debug_console_vport = config.debug_console_vport as u32;
+    // Initialize unique sandbox structure.
+    let mut s = Sandbox::new(logger).context("Failed to create sandbox")?;
 
+    // The debug console is owned by the sandbox, so that it could also be
+    // enabled or disabled at runtime.
     if config.debug_console {
-        let debug_console_task = tokio::task::spawn(console::debug_console_handler(
-            logger.clone(),
-            debug_console_vport,
-            shutdown.clone(),
-        ));
-
-        tasks.push(debug_console_task);
+        s.start_debug_console(config.debug_console_vport as u32)
+            .await
+            .context("start debug console")?;
     }
 
-    // Initialize unique sandbox structure.
-    let s = Sandbox::new(logger).context("Failed to create sandbox")?;
     if init_mode {
         s.rtnl.handle_localhost().await?;
     }
@@ -350,6 +351,17 @@ async fn start_sandbox(
 
     let sandbox = Arc::new(Mutex::new(s));
 
+    // The exporters are proxied for the host, a failing proxy doesn't stop
+    // the agent.
+    for port in config.guest_metrics_ports.iter() {
+        let proxy_task = tokio::spawn(metrics_proxy::proxy_metrics_exporter(
+            logger.clone(),
+            *port,
+            shutdown.clone(),
+        ));
+        tasks.push(proxy_task);
+    }
+
     let signal_handler_task = tokio::spawn(setup_signal_handler(
         logger.clone(),
         sandbox.clone(),
@@ -372,6 +384,8 @@ async fn start_sandbox(
     rx.await?;
     server.shutdown().await?;
 
+    sandbox.lock().await.stop_debug_console().await?;
+
     Ok(())
 }
 
@@ -5,10 +5,13 @@
 
 extern crate procfs;
 
-use prometheus::{Encoder, Gauge, GaugeVec, IntCounter, Opts, Registry, TextEncoder};
+use prometheus::{
+    Encoder, Gauge, GaugeVec, IntCounter, IntCounterVec, Opts, Registry, TextEncoder,
+};
 
 use anyhow::{anyhow, Result};
 use slog::warn;
+use std::fmt::Debug;
 use std::sync::Mutex;
 use tracing::instrument;
 
@@ -30,6 +33,12 @@ lazy_static! {
     static ref AGENT_SCRAPE_COUNT: IntCounter =
     IntCounter::new(format!("{}_{}",NAMESPACE_KATA_AGENT,"scrape_count"), "Metrics scrape count").unwrap();
 
+    static ref AGENT_SCRAPE_FAILURES: IntCounterVec =
+    IntCounterVec::new(Opts::new(format!("{}_{}",NAMESPACE_KATA_AGENT,"scrape_collector_failures"), "Metrics collectors failures count"), &["collector"]).unwrap();
+
+    static ref AGENT_SCRAPE_SUCCESS: GaugeVec =
+    GaugeVec::new(Opts::new(format!("{}_{}",NAMESPACE_KATA_AGENT,"scrape_collector_success"), "Whether the metrics collector succeeded in the last scrape, the metrics of a failed one are stale."), &["collector"]).unwrap();
+
     // agent metrics
     static ref AGENT_THREADS: Gauge =
     Gauge::new(format!("{}_{}",NAMESPACE_KATA_AGENT,"threads"), "Agent process threads").unwrap();
@@ -73,6 +82,9 @@ lazy_static! {
 
     static ref GUEST_MEMINFO: GaugeVec =
     GaugeVec::new(Opts::new(format!("{}_{}",NAMESPACE_KATA_GUEST,"meminfo"), "Statistics about memory usage in the system."), &["item"]).unwrap();
+
+    static ref GUEST_STORAGE_QUOTA: GaugeVec =
+    GaugeVec::new(Opts::new(format!("{}_{}",NAMESPACE_KATA_GUEST,"storage_quota"), "Usage of quota limited storages in bytes."), &["mount_point","item"]).unwrap();
 }
 
 #[instrument]
@@ -88,11 +100,22 @@ pub fn get_metrics(_: &protocols::agent::GetMetricsRequest) -> Result<String> {
 
     AGENT_SCRAPE_COUNT.inc();
 
+    // the failed collectors are reported by the scrape metrics, and the
+    // metrics of the others are still returned
+    let mut scrape = Scrape::default();
+
     // update agent process metrics
-    update_agent_metrics()?;
+    update_agent_metrics(&mut scrape);
 
     // update guest os metrics
-    update_guest_metrics();
+    update_guest_metrics(&mut scrape);
+
+    if scrape.failures > 0 {
+        warn!(
+            sl(),
+            "{} metrics collectors failed, return partial metrics", scrape.failures
+        );
+    }
 
     // gather all metrics and return as a String
     let metric_families = REGISTRY.gather();
@@ -107,6 +130,8 @@ pub fn get_metrics(_: &protocols::agent::GetMetricsRequest) -> Result<String> {
 #[instrument]
 fn register_metrics() -> Result<()> {
     REGISTRY.register(Box::new(AGENT_SCRAPE_COUNT.clone()))?;
+    REGISTRY.register(Box::new(AGENT_SCRAPE_FAILURES.clone()))?;
+    REGISTRY.register(Box::new(AGENT_SCRAPE_SUCCESS.clone()))?;
 
     // agent metrics
     REGISTRY.register(Box::new(AGENT_THREADS.clone()))?;
@@ -125,143 +150,141 @@ fn register_metrics() -> Result<()> {
     REGISTRY.register(Box::new(GUEST_NETDEV_STAT.clone()))?;
     REGISTRY.register(Box::new(GUEST_DISKSTAT.clone()))?;
     REGISTRY.register(Box::new(GUEST_MEMINFO.clone()))?;
+    REGISTRY.register(Box::new(GUEST_STORAGE_QUOTA.clone()))?;
 
     Ok(())
 }
 
-#[instrument]
-fn update_agent_metrics() -> Result<()> {
-    let me = procfs::process::Process::myself();
-
-    let me = match me {
-        Ok(p) => p,
-        Err(e) => {
-            // FIXME: return Ok for all errors?
-            warn!(sl(), "failed to create process instance: {:?}", e);
+// Scrape records the result of the collectors of a scrape.
+#[derive(Debug, Default)]
+struct Scrape {
+    failures: usize,
+}
 
-            return Ok(());
+impl Scrape {
+    // collect records the result of the collector, the value is returned on
+    // success, and the failure is counted otherwise.
+    fn collect<T, E: Debug>(&mut self, collector: &str, result: Result<T, E>) -> Option<T> {
+        match result {
+            Ok(value) => {
+                AGENT_SCRAPE_SUCCESS
+                    .with_label_values(&[collector])
+                    .set(1.0);
+                Some(value)
+            }
+            Err(err) => {
+                warn!(sl(), "metrics collector {} failed: {:?}", collector, err);
+                AGENT_SCRAPE_FAILURES.with_label_values(&[collector]).inc();
+                AGENT_SCRAPE_SUCCESS
+                    .with_label_values(&[collector])
+                    .set(0.0);
+                self.failures += 1;
+                None
+            }
         }
-    };
+    }
+}
 
-    let tps = procfs::ticks_per_second()?;
+#[instrument]
+fn update_agent_metrics(scrape: &mut Scrape) {
+    let me = match scrape.collect("agent_process", procfs::process::Process::myself()) {
+        Some(me) => me,
+        None => return,
+    };
 
     // process total time
-    AGENT_TOTAL_TIME.set((me.stat.utime + me.stat.stime) as f64 / (tps as f64));
+    if let Some(tps) = scrape.collect("agent_total_time", procfs::ticks_per_second()) {
+        AGENT_TOTAL_TIME.set((me.stat.utime + me.stat.stime) as f64 / (tps as f64));
+    }
 
     // Total virtual memory used
     AGENT_TOTAL_VM.set(me.stat.vsize as f64);
 
     // Total resident set
-    let page_size = procfs::page_size()? as f64;
-    AGENT_TOTAL_RSS.set(me.stat.rss as f64 * page_size);
+    if let Some(page_size) = scrape.collect("agent_total_rss", procfs::page_size()) {
+        AGENT_TOTAL_RSS.set(me.stat.rss as f64 * page_size as f64);
+    }
 
     // io
-    match me.io() {
-        Err(err) => {
-            info!(sl(), "failed to get process io stat: {:?}", err);
-        }
-        Ok(io) => {
-            set_gauge_vec_proc_io(&AGENT_IO_STAT, &io);
-        }
+    if let Some(io) = scrape.collect("agent_io_stat", me.io()) {
+        set_gauge_vec_proc_io(&AGENT_IO_STAT, &io);
     }
 
-    match me.stat() {
-        Err(err) => {
-            info!(sl(), "failed to get process stat: {:?}", err);
-        }
-        Ok(stat) => {
-            set_gauge_vec_proc_stat(&AGENT_PROC_STAT, &stat);
-        }
+    if let Some(stat) = scrape.collect("agent_proc_stat", me.stat()) {
+        set_gauge_vec_proc_stat(&AGENT_PROC_STAT, &stat);
     }
 
-    match me.status() {
-        Err(err) => error!(sl(), "failed to get process status: {:?}", err),
-        Ok(status) => set_gauge_vec_proc_status(&AGENT_PROC_STATUS, &status),
+    if let Some(status) = scrape.collect("agent_proc_status", me.status()) {
+        set_gauge_vec_proc_status(&AGENT_PROC_STATUS, &status);
     }
-
-    Ok(())
 }
 
 #[instrument]
-fn update_guest_metrics() {
-    // try get load and task info
-    match procfs::LoadAverage::new() {
-        Err(err) => {
-            info!(sl(), "failed to get guest LoadAverage: {:?}", err);
-        }
-        Ok(load) => {
-            GUEST_LOAD
-                .with_label_values(&["load1"])
-                .set(load.one as f64);
-            GUEST_LOAD
-                .with_label_values(&["load5"])
-                .set(load.five as f64);
-            GUEST_LOAD
-                .with_label_values(&["load15"])
-                .set(load.fifteen as f64);
-            GUEST_TASKS.with_label_values(&["cur"]).set(load.cur as f64);
-            GUEST_TASKS.with_label_values(&["max"]).set(load.max as f64);
+fn update_guest_metrics(scrape: &mut Scrape) {
+    // quota limited storages, e.g. the writable layer of block rootfs
+    if let Some(usages) = scrape.collect(
+        "guest_storage_quota",
+        crate::storage::quota::get_storage_quota_usages(),
+    ) {
+        GUEST_STORAGE_QUOTA.reset();
+        for (mount_point, usage) in usages {
+            GUEST_STORAGE_QUOTA
+                .with_label_values(&[mount_point.as_str(), "used"])
+                .set(usage.used as f64);
+            GUEST_STORAGE_QUOTA
+                .with_label_values(&[mount_point.as_str(), "limit"])
+                .set(usage.limit as f64);
         }
     }
 
+    // try get load and task info
+    if let Some(load) = scrape.collect("guest_load", procfs::LoadAverage::new()) {
+        GUEST_LOAD
+            .with_label_values(&["load1"])
+            .set(load.one as f64);
+        GUEST_LOAD
+            .with_label_values(&["load5"])
+            .set(load.five as f64);
+        GUEST_LOAD
+            .with_label_values(&["load15"])
+            .set(load.fifteen as f64);
+        GUEST_TASKS.with_label_values(&["cur"]).set(load.cur as f64);
+        GUEST_TASKS.with_label_values(&["max"]).set(load.max as f64);
+    }
+
     // try to get disk stats
-    match procfs::diskstats() {
-        Err(err) => {
-            info!(sl(), "failed to get guest diskstats: {:?}", err);
-        }
-        Ok(diskstats) => {
-            for diskstat in diskstats {
-                set_gauge_vec_diskstat(&GUEST_DISKSTAT, &diskstat);
-            }
+    if let Some(diskstats) = scrape.collect("guest_diskstat", procfs::diskstats()) {
+        for diskstat in diskstats {
+            set_gauge_vec_diskstat(&GUEST_DISKSTAT, &diskstat);
         }
     }
 
     // try to get vm stats
-    match procfs::vmstat() {
-        Err(err) => {
-            info!(sl(), "failed to get guest vmstat: {:?}", err);
-        }
-        Ok(vmstat) => {
-            for (k, v) in vmstat {
-                GUEST_VM_STAT.with_label_values(&[k.as_str()]).set(v as f64);
-            }
+    if let Some(vmstat) = scrape.collect("guest_vm_stat", procfs::vmstat()) {
+        for (k, v) in vmstat {
+            GUEST_VM_STAT.with_label_values(&[k.as_str()]).set(v as f64);
         }
     }
 
     // cpu stat
-    match procfs::KernelStats::new() {
-        Err(err) => {
-            info!(sl(), "failed to get guest KernelStats: {:?}", err);
-        }
-        Ok(kernel_stats) => {
-            set_gauge_vec_cpu_time(&GUEST_CPU_TIME, "total", &kernel_stats.total);
-            for (i, cpu_time) in kernel_stats.cpu_time.iter().enumerate() {
-                set_gauge_vec_cpu_time(&GUEST_CPU_TIME, format!("{}", i).as_str(), cpu_time);
-            }
+    if let Some(kernel_stats) = scrape.collect("guest_cpu_time", procfs::KernelStats::new()) {
+        set_gauge_vec_cpu_time(&GUEST_CPU_TIME, "total", &kernel_stats.total);
+        for (i, cpu_time) in kernel_stats.cpu_time.iter().enumerate() {
+            set_gauge_vec_cpu_time(&GUEST_CPU_TIME, format!("{}", i).as_str(), cpu_time);
         }
     }
 
     // try to get net device stats
-    match procfs::net::dev_status() {
-        Err(err) => {
-            info!(sl(), "failed to get guest net::dev_status: {:?}", err);
-        }
-        Ok(devs) => {
-            // netdev: map[string]procfs::net::DeviceStatus
-            for (_, status) in devs {
-                set_gauge_vec_netdev(&GUEST_NETDEV_STAT, &status);
-            }
+    if let Some(devs) = scrape.collect("guest_netdev_stat", procfs::net::dev_status()) {
+        // netdev: map[string]procfs::net::DeviceStatus
+        for (_, status) in devs {
+            set_gauge_vec_netdev(&GUEST_NETDEV_STAT, &status);
         }
     }
 
     // get statistics about memory from /proc/meminfo
-    match procfs::Meminfo::new() {
-        Err(err) => {
-            info!(sl(), "failed to get guest Meminfo: {:?}", err);
-        }
-        Ok(meminfo) => {
-            set_gauge_vec_meminfo(&GUEST_MEMINFO, &meminfo);
-        }
+    if let Some(meminfo) = scrape.collect("guest_meminfo", procfs::Meminfo::new()) {
+        set_gauge_vec_meminfo(&GUEST_MEMINFO, &meminfo);
     }
 }
 
@@ -543,3 +566,33 @@ fn set_gauge_vec_proc_stat(gv: &prometheus::GaugeVec, stat: &procfs::process::St
     gv.with_label_values(&["cutime"]).set(stat.cutime as f64);
     gv.with_label_values(&["cstime"]).set(stat.cstime as f64);
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scrape_collect() {
+        let mut scrape = Scrape::default();
+
+        assert_eq!(scrape.collect("test_ok", Ok::<_, String>(1)), Some(1));
+        assert_eq!(
+            AGENT_SCRAPE_SUCCESS.with_label_values(&["test_ok"]).get(),
+            1.0
+        );
+
+        // the failure is counted, and the other collectors still run
+        assert!(scrape
+            .collect("test_err", Err::<(), _>("no such file".to_string()))
+            .is_none());
+        assert_eq!(scrape.failures, 1);
+        assert_eq!(
+            AGENT_SCRAPE_SUCCESS.with_label_values(&["test_err"]).get(),
+            0.0
+        );
+        assert_eq!(
+            AGENT_SCRAPE_FAILURES.with_label_values(&["test_err"]).get(),
+            1
+        );
+    }
+}
@@ -0,0 +1,114 @@
+// Copyright (c) 2023 Kata Containers community
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+// The proxy of the in-guest metrics exporters, e.g. the DCGM exporter of the
+// GPUs, which listen on a TCP port of the guest loopback. Each exporter port
+// is proxied from the vsock port of the same number, so that the host scrapes
+// the exporters through the vsock of the sandbox, without a route to the
+// guest network.
+
+use std::convert::TryFrom;
+use std::net::{Ipv4Addr, SocketAddr};
+
+use anyhow::{Context, Result};
+use futures::StreamExt;
+use nix::sys::socket::{self, AddressFamily, SockFlag, SockType, VsockAddr};
+use slog::Logger;
+use tokio::net::TcpStream;
+use tokio::select;
+use tokio::sync::watch::Receiver;
+use tokio_vsock::VsockStream;
+
+use crate::util;
+
+// the exporters are scraped by a single host client at a time
+const LISTEN_BACKLOG: usize = 4;
+
+/// Proxy the vsock `port` to the exporter listening on the TCP `port` of the
+/// guest loopback, until the agent is shut down.
+pub async fn proxy_metrics_exporter(
+    logger: Logger,
+    port: u32,
+    shutdown: Receiver<bool>,
+) -> Result<()> {
+    let logger = logger.new(o!("subsystem" => "metrics-proxy", "port" => port));
+
+    let result = run_proxy(&logger, port, shutdown).await;
+    if let Err(e) = result.as_ref() {
+        error!(logger, "metrics proxy failed: {:?}", e);
+    }
+    result
+}
+
+async fn run_proxy(logger: &Logger, port: u32, mut shutdown: Receiver<bool>) -> Result<()> {
+    let exporter = exporter_addr(port)?;
+
+    let listenfd = socket::socket(
+        AddressFamily::Vsock,
+        SockType::Stream,
+        SockFlag::SOCK_CLOEXEC,
+        None,
+    )?;
+    let addr = VsockAddr::new(libc::VMADDR_CID_ANY, port);
+    socket::bind(listenfd, &addr).with_context(|| format!("bind vsock port {}", port))?;
+    socket::listen(listenfd, LISTEN_BACKLOG)?;
+
+    let mut incoming = util::get_vsock_incoming(listenfd);
+    info!(logger, "proxy metrics exporter"; "exporter" => exporter.to_string());
+
+    loop {
+        select! {
+            _ = shutdown.changed() => {
+                info!(logger, "metrics proxy got shutdown request");
+                break;
+            }
+
+            conn = incoming.next() => {
+                match conn {
+                    Some(Ok(stream)) => {
+                        let logger = logger.clone();
+                        // Do not block(await) here, or we'll never receive the shutdown signal
+                        tokio::spawn(async move {
+                            if let Err(e) = forward(stream, exporter).await {
+                                warn!(logger, "failed to proxy metrics exporter: {:?}", e);
+                            }
+                        });
+                    }
+                    Some(Err(e)) => error!(logger, "{:?}", e),
+                    None => break,
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+async fn forward(mut stream: VsockStream, exporter: SocketAddr) -> Result<()> {
+    let mut upstream = TcpStream::connect(exporter)
+        .await
+        .with_context(|| format!("connect to exporter {}", exporter))?;
+    tokio::io::copy_bidirectional(&mut stream, &mut upstream)
+        .await
+        .context("copy")?;
+
+    Ok(())
+}
+
+fn exporter_addr(port: u32) -> Result<SocketAddr> {
+    let port = u16::try_from(port).with_context(|| format!("invalid exporter port {}", port))?;
+    Ok(SocketAddr::from((Ipv4Addr::LOCALHOST, port)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_exporter_addr() {
+        assert_eq!(exporter_addr(9400).unwrap().to_string(), "127.0.0.1:9400");
+        assert!(exporter_addr(70000).is_err());
+    }
+}
@@ -7,7 +7,7 @@ use anyhow::{anyhow, Context, Result};
 use futures::{future, StreamExt, TryStreamExt};
 use ipnetwork::{IpNetwork, Ipv4Network, Ipv6Network};
 use nix::errno::Errno;
-use protocols::types::{ARPNeighbor, IPAddress, IPFamily, Interface, Route};
+use protocols::types::{ARPNeighbor, IPAddress, IPFamily, Interface, Route, Rule};
 use rtnetlink::{new_connection, packet, IpVersion};
 use std::convert::{TryFrom, TryInto};
 use std::fmt;
@@ -287,16 +287,34 @@ impl Handle {
         let mut result = Vec::new();
 
         for msg in self.query_routes(None).await? {
-            // Ignore non-main tables
-            if msg.header.table != packet::constants::RT_TABLE_MAIN {
+            use packet::nlas::route::Nla;
+
+            // Ignore the local table maintained by the kernel
+            let table = msg
+                .nlas
+                .iter()
+                .find_map(|nla| match nla {
+                    Nla::Table(t) => Some(*t),
+                    _ => None,
+                })
+                .unwrap_or(msg.header.table as u32);
+            if table == packet::constants::RT_TABLE_LOCAL as u32 {
                 continue;
             }
 
             let mut route = Route {
                 scope: msg.header.scope as _,
+                table: from_route_table(table),
                 ..Default::default()
             };
 
+            if let Some(metric) = msg.nlas.iter().find_map(|nla| match nla {
+                Nla::Priority(p) => Some(*p),
+                _ => None,
+            }) {
+                route.metric = metric;
+            }
+
             if let Some((ip, mask)) = msg.destination_prefix() {
                 route.dest = format!("{}/{}", ip, mask);
             }
@@ -342,24 +360,31 @@ impl Handle {
         for route in list {
             let link = self.find_link(LinkFilter::Name(&route.device)).await?;
 
-            const MAIN_TABLE: u8 = packet::constants::RT_TABLE_MAIN;
+            const UNSPEC_TABLE: u8 = packet::constants::RT_TABLE_UNSPEC;
             const UNICAST: u8 = packet::constants::RTN_UNICAST;
             const BOOT_PROT: u8 = packet::constants::RTPROT_BOOT;
 
             let scope = route.scope as u8;
+            let table = to_route_table(route.table);
 
             use packet::nlas::route::Nla;
 
             // Build a common indeterminate ip request
-            let request = self
+            let mut request = self
                 .handle
                 .route()
                 .add()
-                .table(MAIN_TABLE)
+                .table(u8::try_from(table).unwrap_or(UNSPEC_TABLE))
                 .kind(UNICAST)
                 .protocol(BOOT_PROT)
                 .scope(scope);
 
+            // The table id larger than 255 can only be carried by the attribute
+            request.message_mut().nlas.push(Nla::Table(table));
+            if route.metric > 0 {
+                request.message_mut().nlas.push(Nla::Priority(route.metric));
+            }
+
             // `rtnetlink` offers a separate request builders for different IP versions (IP v4 and v6).
             // This if branch is a bit clumsy because it does almost the same.
             if route.family() == IPFamily::v6 {
@@ -478,6 +503,107 @@ impl Handle {
         Ok(())
     }
 
+    /// Replace the policy routing rules with the ones of `list`, the rules
+    /// created by the kernel are kept.
+    pub async fn update_rules<I>(&mut self, list: I) -> Result<()>
+    where
+        I: IntoIterator<Item = Rule>,
+    {
+        let old_rules = self
+            .query_rules()
+            .await
+            .with_context(|| "Failed to query old rules")?;
+
+        for rule in old_rules {
+            if is_default_rule(&rule) {
+                continue;
+            }
+            self.handle.rule().del(rule).execute().await?;
+        }
+
+        for rule in list {
+            self.add_rule(&rule).await.with_context(|| {
+                format!(
+                    "Failed to add rule (src: {}, dst: {}, table: {})",
+                    rule.source, rule.dest, rule.table
+                )
+            })?;
+        }
+
+        Ok(())
+    }
+
+    async fn query_rules(&self) -> Result<Vec<packet::RuleMessage>> {
+        // These queries must be executed sequentially, the same as the routes
+        let rules4 = self
+            .handle
+            .rule()
+            .get(IpVersion::V4)
+            .execute()
+            .try_collect::<Vec<_>>()
+            .await
+            .with_context(|| "Failed to query IP v4 rules")?;
+
+        let rules6 = self
+            .handle
+            .rule()
+            .get(IpVersion::V6)
+            .execute()
+            .try_collect::<Vec<_>>()
+            .await
+            .with_context(|| "Failed to query IP v6 rules")?;
+
+        Ok([rules4, rules6].concat())
+    }
+
+    async fn add_rule(&mut self, rule: &Rule) -> Result<()> {
+        use packet::nlas::rule::Nla;
+
+        let table = to_route_table(rule.table);
+        let mut request = self.handle.rule().add();
+        let message = request.message_mut();
+
+        message.header.family = if rule.family() == IPFamily::v6 {
+            libc::AF_INET6 as u8
+        } else {
+            libc::AF_INET as u8
+        };
+        message.header.action = FR_ACT_TO_TBL;
+        message.header.table = u8::try_from(table).unwrap_or(packet::constants::RT_TABLE_UNSPEC);
+        message.nlas.push(Nla::Table(table));
+
+        if !rule.source.is_empty() {
+            let network = IpNetwork::from_str(&rule.source)?;
+            message.header.src_len = network.prefix();
+            message.nlas.push(Nla::Source(ip_octets(network.ip())));
+        }
+        if !rule.dest.is_empty() {
+            let network = IpNetwork::from_str(&rule.dest)?;
+            message.header.dst_len = network.prefix();
+            message.nlas.push(Nla::Destination(ip_octets(network.ip())));
+        }
+        if !rule.iif.is_empty() {
+            message.nlas.push(Nla::Iifname(rule.iif.clone()));
+        }
+        if !rule.oif.is_empty() {
+            message.nlas.push(Nla::OifName(rule.oif.clone()));
+        }
+        if rule.fwmark > 0 {
+            message.nlas.push(Nla::FwMark(rule.fwmark));
+        }
+        if rule.priority > 0 {
+            message.nlas.push(Nla::Priority(rule.priority));
+        }
+
+        if let Err(rtnetlink::Error::NetlinkError(message)) = request.execute().await {
+            if Errno::from_i32(message.code.abs()) != Errno::EEXIST {
+                return Err(anyhow!("Failed to add rule: {}", message));
+            }
+        }
+
+        Ok(())
+    }
+
     async fn list_addresses<F>(&self, filter: F) -> Result<Vec<Address>>
     where
         F: Into<Option<AddressFilter>>,
@@ -664,6 +790,53 @@ fn parse_mac_address(addr: &str) -> Result<[u8; 6]> {
     Ok(arr)
 }
 
+// Action of the rules looking up a routing table, from <linux/fib_rules.h>
+const FR_ACT_TO_TBL: u8 = 1;
+
+// Priorities of the rules created by the kernel for the local, main and default tables.
+const DEFAULT_RULE_PRIORITIES: [u32; 3] = [0, 32766, 32767];
+
+/// The main routing table is denoted by 0 in the requests.
+fn to_route_table(table: u32) -> u32 {
+    if table == 0 {
+        packet::constants::RT_TABLE_MAIN as u32
+    } else {
+        table
+    }
+}
+
+fn from_route_table(table: u32) -> u32 {
+    if table == packet::constants::RT_TABLE_MAIN as u32 {
+        0
+    } else {
+        table
+    }
+}
+
+fn ip_octets(ip: IpAddr) -> Vec<u8> {
+    match ip {
+        IpAddr::V4(ip) => ip.octets().to_vec(),
+        IpAddr::V6(ip) => ip.octets().to_vec(),
+    }
+}
+
+fn is_default_rule(rule: &packet::RuleMessage) -> bool {
+    use packet::constants::{RT_TABLE_DEFAULT, RT_TABLE_LOCAL, RT_TABLE_MAIN};
+    use packet::nlas::rule::Nla;
+
+    let priority = rule
+        .nlas
+        .iter()
+        .find_map(|nla| match nla {
+            Nla::Priority(p) => Some(*p),
+            _ => None,
+        })
+        .unwrap_or(0);
+
+    [RT_TABLE_LOCAL, RT_TABLE_MAIN, RT_TABLE_DEFAULT].contains(&rule.header.table)
+        && DEFAULT_RULE_PRIORITIES.contains(&priority)
+}
+
 /// Wraps external type with the local one, so we can implement various extensions and type conversions.
 struct Link(packet::LinkMessage);
 
@@ -883,6 +1056,34 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_route_table() {
+        let main = packet::constants::RT_TABLE_MAIN as u32;
+        assert_eq!(to_route_table(0), main);
+        assert_eq!(to_route_table(1000), 1000);
+        assert_eq!(from_route_table(main), 0);
+        assert_eq!(from_route_table(100), 100);
+    }
+
+    #[test]
+    fn test_is_default_rule() {
+        use packet::nlas::rule::Nla;
+
+        let mut rule = packet::RuleMessage::default();
+        rule.header.table = packet::constants::RT_TABLE_MAIN;
+        rule.nlas.push(Nla::Priority(32766));
+        assert!(is_default_rule(&rule));
+
+        // the local table rule has no priority attribute
+        rule.header.table = packet::constants::RT_TABLE_LOCAL;
+        rule.nlas.clear();
+        assert!(is_default_rule(&rule));
+
+        rule.header.table = 100;
+        rule.nlas.push(Nla::Priority(1000));
+        assert!(!is_default_rule(&rule));
+    }
+
     #[tokio::test]
     async fn list_addresses() {
         let list = Handle::new()
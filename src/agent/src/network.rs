@@ -3,14 +3,17 @@
 // SPDX-License-Identifier: Apache-2.0
 //
 
-use anyhow::{anyhow, Result};
+use anyhow::{anyhow, Context, Result};
 use nix::mount::{self, MsFlags};
+use protocols::agent::NetworkStats;
 use slog::Logger;
 use std::fs;
 use std::path;
 
 const KATA_GUEST_SANDBOX_DNS_FILE: &str = "/run/kata-containers/sandbox/resolv.conf";
 const GUEST_DNS_FILE: &str = "/etc/resolv.conf";
+const SYSFS_NET_PATH: &str = "/sys/class/net";
+const LOOPBACK_INTERFACE: &str = "lo";
 
 // Network describes a sandbox network, includings its dns
 // related information.
@@ -29,6 +32,47 @@ impl Network {
     }
 }
 
+// get_network_stats returns the counters of the interfaces of the sandbox
+// network, which is shared by all the containers, the loopback excepted.
+pub fn get_network_stats() -> Result<Vec<NetworkStats>> {
+    do_get_network_stats(path::Path::new(SYSFS_NET_PATH))
+}
+
+fn do_get_network_stats(sysfs_net: &path::Path) -> Result<Vec<NetworkStats>> {
+    let mut stats = Vec::new();
+    for entry in fs::read_dir(sysfs_net).context(format!("read {:?}", sysfs_net))? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if name == LOOPBACK_INTERFACE {
+            continue;
+        }
+
+        // the interface may be removed meanwhile, its counters are zero then
+        let counters = entry.path().join("statistics");
+        let read = |counter: &str| -> u64 {
+            fs::read_to_string(counters.join(counter))
+                .ok()
+                .and_then(|v| v.trim().parse().ok())
+                .unwrap_or_default()
+        };
+        stats.push(NetworkStats {
+            rx_bytes: read("rx_bytes"),
+            rx_packets: read("rx_packets"),
+            rx_errors: read("rx_errors"),
+            rx_dropped: read("rx_dropped"),
+            tx_bytes: read("tx_bytes"),
+            tx_packets: read("tx_packets"),
+            tx_errors: read("tx_errors"),
+            tx_dropped: read("tx_dropped"),
+            name,
+            ..Default::default()
+        });
+    }
+    stats.sort_by(|a, b| a.name.cmp(&b.name));
+
+    Ok(stats)
+}
+
 pub fn setup_guest_dns(logger: Logger, dns_list: &[String]) -> Result<()> {
     do_setup_guest_dns(
         logger,
@@ -89,6 +133,27 @@ mod tests {
     use tempfile::tempdir;
     use test_utils::skip_if_not_root;
 
+    #[test]
+    fn test_get_network_stats() {
+        let sysfs_net = tempdir().unwrap();
+        for (iface, rx_bytes) in [("lo", "100"), ("eth1", "300"), ("eth0", "200\n")] {
+            let counters = sysfs_net.path().join(iface).join("statistics");
+            fs::create_dir_all(&counters).unwrap();
+            fs::write(counters.join("rx_bytes"), rx_bytes).unwrap();
+            fs::write(counters.join("tx_packets"), "7").unwrap();
+        }
+
+        let stats = do_get_network_stats(sysfs_net.path()).unwrap();
+        let names: Vec<&str> = stats.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(names, vec!["eth0", "eth1"]);
+        assert_eq!(stats[0].rx_bytes, 200);
+        assert_eq!(stats[1].rx_bytes, 300);
+        assert_eq!(stats[0].tx_packets, 7);
+        assert_eq!(stats[0].tx_bytes, 0);
+
+        assert!(do_get_network_stats(&sysfs_net.path().join("none")).is_err());
+    }
+
     #[test]
     fn test_setup_guest_dns() {
         skip_if_not_root!();
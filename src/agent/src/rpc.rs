@@ -8,6 +8,7 @@ use rustjail::{pipestream::PipeStream, process::StreamType};
 use tokio::io::{AsyncReadExt, AsyncWriteExt, ReadHalf};
 use tokio::sync::Mutex;
 
+use std::cmp;
 use std::ffi::{CString, OsStr};
 use std::fmt::Debug;
 use std::io;
@@ -22,13 +23,14 @@ use ttrpc::{
 
 use anyhow::{anyhow, Context, Result};
 use cgroups::freezer::FreezerState;
-use oci::{LinuxNamespace, Root, Spec};
+use oci::{ContainerState, LinuxNamespace, Root, Spec};
 use protobuf::{MessageDyn, MessageField};
 use protocols::agent::{
-    AddSwapRequest, AgentDetails, CopyFileRequest, GetIPTablesRequest, GetIPTablesResponse,
-    GuestDetailsResponse, Interfaces, Metrics, OOMEvent, ReadStreamResponse, Routes,
-    SetIPTablesRequest, SetIPTablesResponse, StatsContainerResponse, VolumeStatsRequest,
-    WaitProcessResponse, WriteStreamResponse,
+    AddSwapRequest, AgentDetails, CheckpointContainerResponse, CopyFileRequest, GetIPTablesRequest,
+    GetIPTablesResponse, GuestDetailsResponse, Interfaces, Metrics, OOMEvent, ReadFileRequest,
+    ReadFileResponse, ReadStreamResponse, Routes, SetIPTablesRequest, SetIPTablesResponse,
+    StatsContainerResponse, StorageEvent, VolumeStatsRequest, WaitProcessResponse,
+    WriteStreamResponse,
 };
 use protocols::csi::{
     volume_usage::Unit as VolumeUsage_Unit, VolumeCondition, VolumeStatsResponse, VolumeUsage,
@@ -52,17 +54,22 @@ use nix::sys::{stat, statfs};
 use nix::unistd::{self, Pid};
 use rustjail::process::ProcessOperations;
 
+use crate::checkpoint::checkpoint_container;
 use crate::device::{
     add_devices, get_virtio_blk_pci_device_name, update_device_cgroup, update_env_pci,
 };
+use crate::dns_forwarder;
+use crate::guest_hook::exec_guest_hook;
 use crate::linux_abi::*;
 use crate::metrics::get_metrics;
 use crate::mount::baremount;
 use crate::namespace::{NSTYPEIPC, NSTYPEPID, NSTYPEUTS};
-use crate::network::setup_guest_dns;
+use crate::network::{get_network_stats, setup_guest_dns};
 use crate::pci;
 use crate::random;
 use crate::sandbox::Sandbox;
+use crate::storage::capacity_monitor::run_capacity_monitor;
+use crate::storage::writable_layer::get_writable_layer_usage;
 use crate::storage::{add_storages, update_ephemeral_mounts, STORAGE_HANDLERS};
 use crate::version::{AGENT_VERSION, API_VERSION};
 use crate::AGENT_CONFIG;
@@ -72,6 +79,7 @@ use crate::tracer::extract_carrier_from_ttrpc;
 
 #[cfg(feature = "agent-policy")]
 use crate::AGENT_POLICY;
+use logging::audit::{audit, AuditRecord};
 
 use opentelemetry::global;
 use tracing::span;
@@ -104,7 +112,7 @@ const USR_IP6TABLES_SAVE: &str = "/usr/sbin/ip6tables-save";
 const IP6TABLES_SAVE: &str = "/sbin/ip6tables-save";
 const USR_IP6TABLES_RESTORE: &str = "/usr/sbin/ip6tables-save";
 const IP6TABLES_RESTORE: &str = "/sbin/ip6tables-restore";
-const KATA_GUEST_SHARE_DIR: &str = "/run/kata-containers/shared/containers/";
+pub(crate) const KATA_GUEST_SHARE_DIR: &str = "/run/kata-containers/shared/containers/";
 
 const ERR_CANNOT_GET_WRITER: &str = "Cannot get writer";
 const ERR_INVALID_BLOCK_SIZE: &str = "Invalid block size";
@@ -117,6 +125,14 @@ const ERR_NO_SANDBOX_PIDNS: &str = "Sandbox does not have sandbox_pidns";
 // not available.
 const IPTABLES_RESTORE_WAIT_SEC: u64 = 5;
 
+// Maximum number of bytes returned by a single ReadStdout/ReadStderr call, the
+// output beyond is read by the next calls.
+const MAX_READ_STREAM_LEN: usize = 1024 * 1024;
+
+// Maximum number of bytes returned by a single ReadFile call, keeps the
+// response well below the ttrpc message size limit.
+const MAX_READ_FILE_LEN: u32 = 1024 * 1024;
+
 // Convenience function to obtain the scope logger.
 fn sl() -> slog::Logger {
     slog_scope::logger()
@@ -758,7 +774,36 @@ impl agent_ttrpc::AgentService for AgentService {
         let ctr = sandbox
             .get_container(&req.container_id)
             .map_ttrpc_err(ttrpc::Code::INVALID_ARGUMENT, "invalid container id")?;
-        ctr.stats().map_ttrpc_err(same)
+        let mut resp = ctr.stats().map_ttrpc_err(same)?;
+        let rootfs = ctr
+            .config
+            .spec
+            .as_ref()
+            .and_then(|spec| spec.root.as_ref())
+            .map(|root| root.path.clone());
+        // the writable layer is walked without blocking the other requests
+        drop(sandbox);
+
+        // the containers share the network of the sandbox
+        match get_network_stats() {
+            Ok(stats) => resp.network_stats = stats,
+            Err(e) => warn!(sl(), "failed to get network stats: {:?}", e),
+        }
+
+        if let Some(rootfs) = rootfs {
+            let cid = req.container_id.clone();
+            let usage =
+                tokio::task::spawn_blocking(move || get_writable_layer_usage(&cid, &rootfs))
+                    .await
+                    .map_err(anyhow::Error::from)
+                    .and_then(|usage| usage);
+            match usage {
+                Ok(usage) => resp.writable_layer = MessageField::from_option(usage),
+                Err(e) => warn!(sl(), "failed to get writable layer usage: {:?}", e),
+            }
+        }
+
+        Ok(resp)
     }
 
     async fn pause_container(
@@ -793,6 +838,47 @@ impl agent_ttrpc::AgentService for AgentService {
         Ok(Empty::new())
     }
 
+    async fn checkpoint_container(
+        &self,
+        ctx: &TtrpcContext,
+        req: protocols::agent::CheckpointContainerRequest,
+    ) -> ttrpc::Result<CheckpointContainerResponse> {
+        trace_rpc_call!(ctx, "checkpoint_container", req);
+        is_allowed(&req).await?;
+
+        let mut sandbox = self.sandbox.lock().await;
+        let ctr = sandbox
+            .get_container(&req.container_id)
+            .map_ttrpc_err(ttrpc::Code::INVALID_ARGUMENT, "invalid container id")?;
+        if ctr.status() != ContainerState::Running {
+            return Err(ttrpc_error(
+                ttrpc::Code::FAILED_PRECONDITION,
+                format!("container {} is not running", req.container_id),
+            ));
+        }
+        let pid = ctr.init_process_pid;
+        let spec = ctr.config.spec.clone().unwrap_or_default();
+        // the memory of the container is dumped without blocking the other requests
+        drop(sandbox);
+
+        let cid = req.container_id.clone();
+        let leave_running = req.leave_running;
+        let (image_dir, files) = tokio::task::spawn_blocking(move || {
+            checkpoint_container(CONTAINER_BASE, &cid, pid, &spec, leave_running)
+        })
+        .await
+        .map_err(anyhow::Error::from)
+        .and_then(|result| result)
+        .map_ttrpc_err(same)?;
+        info!(sl(), "checkpointed container {}", req.container_id; "files" => files.len());
+
+        Ok(CheckpointContainerResponse {
+            image_dir: image_dir.display().to_string(),
+            files,
+            ..Default::default()
+        })
+    }
+
     async fn remove_stale_virtiofs_share_mounts(
         &self,
         ctx: &TtrpcContext,
@@ -936,6 +1022,7 @@ impl agent_ttrpc::AgentService for AgentService {
         trace_rpc_call!(ctx, "update_routes", req);
         is_allowed(&req).await?;
 
+        let new_rules = req.rules;
         let new_routes = req
             .routes
             .into_option()
@@ -950,6 +1037,13 @@ impl agent_ttrpc::AgentService for AgentService {
             .await
             .map_ttrpc_err(|e| format!("Failed to update routes: {:?}", e))?;
 
+        // the rules refer to the routing tables populated above
+        sandbox
+            .rtnl
+            .update_rules(new_rules)
+            .await
+            .map_ttrpc_err(|e| format!("Failed to update rules: {:?}", e))?;
+
         let list = sandbox
             .rtnl
             .list_routes()
@@ -1195,8 +1289,16 @@ impl agent_ttrpc::AgentService for AgentService {
             .await
             .map_ttrpc_err(same)?;
         self.sandbox.lock().await.mounts = m;
+        tokio::spawn(run_capacity_monitor(sl(), self.sandbox.clone()));
 
-        setup_guest_dns(sl(), &req.dns).map_ttrpc_err(same)?;
+        let dns = if req.dns_cache {
+            dns_forwarder::start(sl(), &req.dns)
+                .await
+                .map_ttrpc_err(same)?
+        } else {
+            req.dns.clone()
+        };
+        setup_guest_dns(sl(), &dns).map_ttrpc_err(same)?;
         {
             let mut s = self.sandbox.lock().await;
             for dns in req.dns {
@@ -1218,9 +1320,10 @@ impl agent_ttrpc::AgentService for AgentService {
         let mut sandbox = self.sandbox.lock().await;
         // destroy all containers, clean up, notify agent to exit etc.
         sandbox.destroy().await.map_ttrpc_err(same)?;
-        // Close get_oom_event connection,
-        // otherwise it will block the shutdown of ttrpc.
+        // Close get_oom_event and get_storage_event connections,
+        // otherwise they will block the shutdown of ttrpc.
         drop(sandbox.event_tx.take());
+        drop(sandbox.storage_event_tx.take());
 
         sandbox
             .sender
@@ -1358,6 +1461,17 @@ impl agent_ttrpc::AgentService for AgentService {
         Ok(Empty::new())
     }
 
+    async fn read_file(
+        &self,
+        ctx: &TtrpcContext,
+        req: protocols::agent::ReadFileRequest,
+    ) -> ttrpc::Result<ReadFileResponse> {
+        trace_rpc_call!(ctx, "read_file", req);
+        is_allowed(&req).await?;
+
+        do_read_file(CONTAINER_BASE, &req).map_ttrpc_err(same)
+    }
+
     async fn get_metrics(
         &self,
         ctx: &TtrpcContext,
@@ -1395,6 +1509,26 @@ impl agent_ttrpc::AgentService for AgentService {
         Ok(resp)
     }
 
+    async fn get_storage_event(
+        &self,
+        _ctx: &TtrpcContext,
+        req: protocols::agent::GetStorageEventRequest,
+    ) -> ttrpc::Result<StorageEvent> {
+        is_allowed(&req).await?;
+        let s = self.sandbox.lock().await;
+        let event_rx = s.storage_event_rx.clone();
+        let mut event_rx = event_rx.lock().await;
+        drop(s);
+
+        let event = event_rx
+            .recv()
+            .await
+            .map_ttrpc_err(ttrpc::Code::INTERNAL, "")?;
+
+        info!(sl(), "get_storage_event return {:?}", &event);
+        Ok(event)
+    }
+
     async fn get_volume_stats(
         &self,
         ctx: &TtrpcContext,
@@ -1443,6 +1577,51 @@ impl agent_ttrpc::AgentService for AgentService {
         Ok(Empty::new())
     }
 
+    async fn set_debug_console(
+        &self,
+        ctx: &TtrpcContext,
+        req: protocols::agent::SetDebugConsoleRequest,
+    ) -> ttrpc::Result<Empty> {
+        trace_rpc_call!(ctx, "set_debug_console", req);
+        is_allowed(&req).await?;
+
+        let mut sandbox = self.sandbox.lock().await;
+        if req.enable {
+            let port = if req.vport > 0 {
+                req.vport
+            } else {
+                AGENT_CONFIG.debug_console_vport as u32
+            };
+            sandbox
+                .start_debug_console(port)
+                .await
+                .map_ttrpc_err(same)?;
+        } else {
+            sandbox.stop_debug_console().await.map_ttrpc_err(same)?;
+        }
+
+        Ok(Empty::new())
+    }
+
+    async fn exec_guest_hook(
+        &self,
+        ctx: &TtrpcContext,
+        req: protocols::agent::ExecGuestHookRequest,
+    ) -> ttrpc::Result<Empty> {
+        trace_rpc_call!(ctx, "exec_guest_hook", req);
+        is_allowed(&req).await?;
+
+        let result = exec_guest_hook(&req.args, req.timeout).await;
+        // the hooks are only ever run by the runtime through the agent API
+        audit(
+            &sl(),
+            &AuditRecord::new("exec_guest_hook", "runtime", &req.args.join(" "), &result),
+        );
+        result.map_ttrpc_err(same)?;
+
+        Ok(Empty::new())
+    }
+
     #[cfg(feature = "agent-policy")]
     async fn set_policy(
         &self,
@@ -1452,12 +1631,13 @@ impl agent_ttrpc::AgentService for AgentService {
         trace_rpc_call!(ctx, "set_policy", req);
         is_allowed(&req).await?;
 
-        AGENT_POLICY
-            .lock()
-            .await
-            .set_policy(&req.policy)
-            .await
-            .map_ttrpc_err(same)?;
+        let result = AGENT_POLICY.lock().await.set_policy(&req.policy).await;
+        // the policy is only ever set by the runtime through the agent API
+        audit(
+            &sl(),
+            &AuditRecord::new("set_policy", "runtime", "", &result),
+        );
+        result.map_ttrpc_err(same)?;
 
         Ok(Empty::new())
     }
@@ -1586,7 +1766,8 @@ fn get_agent_details() -> AgentDetails {
 }
 
 async fn read_stream(reader: &Mutex<ReadHalf<PipeStream>>, l: usize) -> Result<Vec<u8>> {
-    let mut content = vec![0u8; l];
+    // the length is given by the runtime, the buffer is bounded whatever it asks for
+    let mut content = vec![0u8; l.min(MAX_READ_STREAM_LEN)];
 
     let mut reader = reader.lock().await;
     let len = reader.read(&mut content).await?;
@@ -1875,6 +2056,35 @@ fn do_copy_file(req: &CopyFileRequest) -> Result<()> {
     Ok(())
 }
 
+fn do_read_file(base: &str, req: &ReadFileRequest) -> Result<ReadFileResponse> {
+    // resolve the symlinks, they must not lead out of the base directory
+    let base = fs::canonicalize(base)?;
+    let path = fs::canonicalize(&req.path).context(format!("canonicalize {}", req.path))?;
+    if !path.starts_with(&base) {
+        return Err(anyhow!("Path {:?} does not start with {:?}", path, base));
+    }
+
+    if req.offset < 0 {
+        return Err(anyhow!("Invalid offset {}", req.offset));
+    }
+
+    let file = File::open(&path)?;
+    let metadata = file.metadata()?;
+    if !metadata.is_file() {
+        return Err(anyhow!("Path {:?} is not a regular file", path));
+    }
+
+    let mut data = vec![0u8; cmp::min(req.len, MAX_READ_FILE_LEN) as usize];
+    let n = file.read_at(&mut data, req.offset as u64)?;
+    data.truncate(n);
+
+    let mut resp = ReadFileResponse::new();
+    resp.file_size = metadata.len() as i64;
+    resp.data = data;
+
+    Ok(resp)
+}
+
 async fn do_add_swap(sandbox: &Arc<Mutex<Sandbox>>, req: &AddSwapRequest) -> Result<()> {
     let mut slots = Vec::new();
     for slot in &req.PCIPath {
@@ -2903,4 +3113,53 @@ COMMIT
             "We should see the resulting rule"
         );
     }
+
+    #[test]
+    fn test_do_read_file() {
+        let base = tempdir().expect("failed to make tempdir");
+        let outside = tempdir().expect("failed to make tempdir");
+
+        let file = base.path().join("file");
+        fs::write(&file, "foobar").unwrap();
+        let secret = outside.path().join("secret");
+        fs::write(&secret, "secret").unwrap();
+        let link = base.path().join("link");
+        std::os::unix::fs::symlink(&secret, &link).unwrap();
+
+        let base_str = base.path().to_str().unwrap();
+        let mut req = ReadFileRequest {
+            path: file.to_str().unwrap().to_string(),
+            offset: 0,
+            len: 4,
+            ..Default::default()
+        };
+
+        // read the file in chunks
+        let resp = do_read_file(base_str, &req).unwrap();
+        assert_eq!(resp.file_size, 6);
+        assert_eq!(resp.data, b"foob");
+
+        req.offset = 4;
+        let resp = do_read_file(base_str, &req).unwrap();
+        assert_eq!(resp.data, b"ar");
+
+        req.offset = 6;
+        let resp = do_read_file(base_str, &req).unwrap();
+        assert!(resp.data.is_empty());
+
+        req.offset = -1;
+        assert!(do_read_file(base_str, &req).is_err());
+
+        // the files out of the base directory can't be read, even through a symlink
+        req.offset = 0;
+        req.path = secret.to_str().unwrap().to_string();
+        assert!(do_read_file(base_str, &req).is_err());
+
+        req.path = link.to_str().unwrap().to_string();
+        assert!(do_read_file(base_str, &req).is_err());
+
+        // only the regular files can be read
+        req.path = base_str.to_string();
+        assert!(do_read_file(base_str, &req).is_err());
+    }
 }
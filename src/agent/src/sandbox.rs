@@ -19,7 +19,7 @@ use kata_types::cpu::CpuSet;
 use kata_types::mount::StorageDevice;
 use libc::pid_t;
 use oci::{Hook, Hooks};
-use protocols::agent::OnlineCPUMemRequest;
+use protocols::agent::{OnlineCPUMemRequest, StorageEvent};
 use regex::Regex;
 use rustjail::cgroups as rustjail_cgroups;
 use rustjail::container::BaseContainer;
@@ -31,6 +31,7 @@ use tokio::sync::oneshot;
 use tokio::sync::Mutex;
 use tracing::instrument;
 
+use crate::console::DebugConsole;
 use crate::linux_abi::*;
 use crate::mount::{get_mount_fs_type, TYPE_ROOTFS};
 use crate::namespace::Namespace;
@@ -42,6 +43,8 @@ use crate::uevent::{Uevent, UeventMatcher};
 use crate::watcher::BindWatcher;
 
 pub const ERR_INVALID_CONTAINER_ID: &str = "Invalid container id";
+// the storage events waiting for the runtime to get them
+const STORAGE_EVENT_QUEUE_SIZE: usize = 100;
 
 type UeventWatcher = (Box<dyn UeventMatcher>, oneshot::Sender<Uevent>);
 
@@ -111,8 +114,11 @@ pub struct Sandbox {
     pub hooks: Option<Hooks>,
     pub event_rx: Arc<Mutex<Receiver<String>>>,
     pub event_tx: Option<Sender<String>>,
+    pub storage_event_rx: Arc<Mutex<Receiver<StorageEvent>>>,
+    pub storage_event_tx: Option<Sender<StorageEvent>>,
     pub bind_watcher: BindWatcher,
     pub pcimap: HashMap<pci::Address, pci::Address>,
+    pub debug_console: Option<DebugConsole>,
 }
 
 impl Sandbox {
@@ -122,6 +128,8 @@ impl Sandbox {
         let logger = logger.new(o!("subsystem" => "sandbox"));
         let (tx, rx) = channel::<String>(100);
         let event_rx = Arc::new(Mutex::new(rx));
+        let (storage_event_tx, storage_event_rx) =
+            channel::<StorageEvent>(STORAGE_EVENT_QUEUE_SIZE);
 
         Ok(Sandbox {
             logger: logger.clone(),
@@ -144,11 +152,41 @@ impl Sandbox {
             hooks: None,
             event_rx,
             event_tx: Some(tx),
+            storage_event_rx: Arc::new(Mutex::new(storage_event_rx)),
+            storage_event_tx: Some(storage_event_tx),
             bind_watcher: BindWatcher::new(),
             pcimap: HashMap::new(),
+            debug_console: None,
         })
     }
 
+    /// Start the debug console listening on the vsock port, or on the serial
+    /// console if the port is 0. A running console listening on another port
+    /// is stopped first.
+    #[instrument]
+    pub async fn start_debug_console(&mut self, port: u32) -> Result<()> {
+        match self.debug_console.as_ref() {
+            Some(console) if console.port() == port => return Ok(()),
+            Some(_) => self.stop_debug_console().await?,
+            None => {}
+        }
+
+        info!(self.logger, "start debug console"; "port" => port);
+        self.debug_console = Some(DebugConsole::start(self.logger.clone(), port));
+
+        Ok(())
+    }
+
+    #[instrument]
+    pub async fn stop_debug_console(&mut self) -> Result<()> {
+        if let Some(console) = self.debug_console.take() {
+            info!(self.logger, "stop debug console"; "port" => console.port());
+            console.stop().await.context("stop debug console")?;
+        }
+
+        Ok(())
+    }
+
     /// Add a new storage object or increase reference count of existing one.
     /// The caller may detect new storage object by checking `StorageState.refcount == 1`.
     #[instrument]
@@ -375,6 +413,19 @@ impl Sandbox {
         Ok(hooks)
     }
 
+    /// Queue the storage event for the runtime, it's dropped if the runtime
+    /// doesn't keep up, rather than blocking the storage operations.
+    pub fn send_storage_event(&self, event: StorageEvent) {
+        let tx = match self.storage_event_tx.as_ref() {
+            Some(tx) => tx,
+            None => return,
+        };
+        info!(self.logger, "storage event {:?}", event);
+        if let Err(e) = tx.try_send(event) {
+            warn!(self.logger, "failed to send storage event: {}", e);
+        }
+    }
+
     #[instrument]
     pub async fn run_oom_event_monitor(&self, mut rx: Receiver<String>, container_id: String) {
         let logger = self.logger.clone();
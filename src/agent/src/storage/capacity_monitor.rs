@@ -0,0 +1,189 @@
+// Copyright (c) 2023 Kata Containers community
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+// The storage events reported to the runtime, so that the users find out the
+// nearly full filesystems and the failed mounts before the write failures of
+// their workloads.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::Arc;
+use std::time::Duration;
+
+use nix::sys::statfs;
+use protocols::agent::{storage_event::Type as StorageEvent_Type, StorageEvent};
+use slog::Logger;
+use tokio::sync::Mutex;
+
+use crate::sandbox::Sandbox;
+
+// the usage of a filesystem in percent from which it's reported near full
+const NEAR_FULL_PERCENT: u64 = 90;
+const CHECK_INTERVAL: Duration = Duration::from_secs(30);
+
+/// The event of the storage of the container `cid` failed to be mounted at
+/// `mount_point`, `cid` is `None` for the storages of the sandbox.
+pub fn mount_failed_event(
+    cid: Option<&str>,
+    mount_point: &str,
+    err: &anyhow::Error,
+) -> StorageEvent {
+    StorageEvent {
+        type_: StorageEvent_Type::MOUNT_FAILED.into(),
+        container_id: cid.unwrap_or_default().to_string(),
+        mount_point: mount_point.to_string(),
+        message: format!("{:?}", err),
+        ..Default::default()
+    }
+}
+
+/// Check the usage of the storages of the sandbox and of its containers
+/// periodically, and report the ones becoming near full, until the sandbox is
+/// destroyed.
+pub async fn run_capacity_monitor(logger: Logger, sandbox: Arc<Mutex<Sandbox>>) {
+    // the mount points reported near full, they're reported again once they
+    // went below the threshold
+    let mut near_full = HashSet::new();
+    let mut interval = tokio::time::interval(CHECK_INTERVAL);
+    loop {
+        interval.tick().await;
+        let mounts = {
+            let s = sandbox.lock().await;
+            if s.storage_event_tx.is_none() {
+                return;
+            }
+            storage_mounts(&s.mounts, &s.container_mounts)
+        };
+
+        let usages = match tokio::task::spawn_blocking(move || filesystem_usages(mounts)).await {
+            Ok(usages) => usages,
+            Err(e) => {
+                warn!(logger, "failed to get filesystem usages: {:?}", e);
+                continue;
+            }
+        };
+        let events = update_near_full(&mut near_full, usages);
+        if !events.is_empty() {
+            let s = sandbox.lock().await;
+            for event in events {
+                s.send_storage_event(event);
+            }
+        }
+    }
+}
+
+// storage_mounts maps the mount points of the storages to the container using
+// them, empty for the sandbox, the shared storages are checked once.
+fn storage_mounts(
+    sandbox_mounts: &[String],
+    container_mounts: &HashMap<String, Vec<String>>,
+) -> BTreeMap<String, String> {
+    let mut mounts = BTreeMap::new();
+    for (cid, paths) in container_mounts {
+        for path in paths.iter().filter(|p| !p.is_empty()) {
+            mounts.entry(path.clone()).or_insert_with(|| cid.clone());
+        }
+    }
+    for path in sandbox_mounts.iter().filter(|p| !p.is_empty()) {
+        mounts.insert(path.clone(), String::new());
+    }
+    mounts
+}
+
+// filesystem_usages gets the used and total bytes of the filesystems of the
+// mount points, the ones failing statfs are skipped.
+fn filesystem_usages(mounts: BTreeMap<String, String>) -> Vec<(String, String, u64, u64)> {
+    mounts
+        .into_iter()
+        .filter_map(|(mount_point, cid)| {
+            let stat = statfs::statfs(mount_point.as_str()).ok()?;
+            let block_size = stat.block_size() as u64;
+            let capacity = stat.blocks() * block_size;
+            let used = capacity.saturating_sub(stat.blocks_free() * block_size);
+            Some((cid, mount_point, used, capacity))
+        })
+        .collect()
+}
+
+// update_near_full returns the events of the filesystems which became near
+// full, and remembers the ones near full.
+fn update_near_full(
+    near_full: &mut HashSet<String>,
+    usages: Vec<(String, String, u64, u64)>,
+) -> Vec<StorageEvent> {
+    let mut events = vec![];
+    let mut now_near_full = HashSet::new();
+    for (cid, mount_point, used, capacity) in usages {
+        if capacity == 0 || used * 100 < capacity * NEAR_FULL_PERCENT {
+            continue;
+        }
+        if !near_full.contains(&mount_point) {
+            events.push(StorageEvent {
+                type_: StorageEvent_Type::NEAR_FULL.into(),
+                container_id: cid,
+                message: format!("filesystem is {}% full", used * 100 / capacity),
+                mount_point: mount_point.clone(),
+                used_bytes: used,
+                capacity_bytes: capacity,
+                ..Default::default()
+            });
+        }
+        now_near_full.insert(mount_point);
+    }
+    *near_full = now_near_full;
+    events
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_storage_mounts() {
+        let mut container_mounts = HashMap::new();
+        container_mounts.insert("c1".to_string(), vec!["/run/a".to_string(), "".to_string()]);
+        container_mounts.insert("c2".to_string(), vec!["/run/b".to_string()]);
+        let mounts = storage_mounts(&["/run/b".to_string()], &container_mounts);
+
+        assert_eq!(mounts.len(), 2);
+        assert_eq!(mounts["/run/a"], "c1");
+        // the storages of the sandbox are reported for it
+        assert_eq!(mounts["/run/b"], "");
+    }
+
+    #[test]
+    fn test_update_near_full() {
+        let mut near_full = HashSet::new();
+        let usage = |used| vec![("c1".to_string(), "/run/a".to_string(), used, 100)];
+
+        assert!(update_near_full(&mut near_full, usage(50)).is_empty());
+
+        let events = update_near_full(&mut near_full, usage(95));
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].type_, StorageEvent_Type::NEAR_FULL.into());
+        assert_eq!(events[0].container_id, "c1");
+        assert_eq!(events[0].mount_point, "/run/a");
+        assert_eq!(events[0].used_bytes, 95);
+        assert_eq!(events[0].capacity_bytes, 100);
+
+        // reported once while it's near full
+        assert!(update_near_full(&mut near_full, usage(99)).is_empty());
+        assert!(update_near_full(&mut near_full, usage(10)).is_empty());
+        assert_eq!(update_near_full(&mut near_full, usage(90)).len(), 1);
+
+        // the filesystems without capacity are skipped
+        let empty = vec![("c1".to_string(), "/run/a".to_string(), 0, 0)];
+        assert!(update_near_full(&mut near_full, empty).is_empty());
+        assert!(near_full.is_empty());
+    }
+
+    #[test]
+    fn test_mount_failed_event() {
+        let event = mount_failed_event(None, "/run/a", &anyhow::anyhow!("no device"));
+        assert_eq!(event.type_, StorageEvent_Type::MOUNT_FAILED.into());
+        assert!(event.container_id.is_empty());
+        assert_eq!(event.mount_point, "/run/a");
+        assert!(event.message.contains("no device"));
+    }
+}
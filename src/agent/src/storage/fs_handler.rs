@@ -9,11 +9,21 @@ use std::path::Path;
 use std::sync::Arc;
 
 use anyhow::{anyhow, Context, Result};
-use kata_types::mount::StorageDevice;
+use kata_types::mount::{
+    StorageDevice, KATA_OVERLAY_RW_OPTION, KATA_STORAGE_DRIVER_OPTION_TMPFS_SIZE,
+};
+use nix::mount::MsFlags;
 use protocols::agent::Storage;
 use tracing::instrument;
 
-use crate::storage::{common_storage_handler, new_device, StorageContext, StorageHandler};
+use crate::mount::{baremount, remove_mounts};
+use crate::storage::{
+    common_storage_handler, new_device, parse_options, StorageContext, StorageDeviceGeneric,
+    StorageHandler,
+};
+
+// the tmpfs holding the upper and work dirs of a memory backed overlay
+const OVERLAY_TMPFS_DIR: &str = "scratch";
 
 #[derive(Debug)]
 pub struct OverlayfsHandler {}
@@ -26,22 +36,35 @@ impl StorageHandler for OverlayfsHandler {
         mut storage: Storage,
         ctx: &mut StorageContext,
     ) -> Result<Arc<dyn StorageDevice>> {
-        if storage
-            .options
-            .iter()
-            .any(|e| e == "io.katacontainers.fs-opt.overlay-rw")
-        {
+        let mut tmpfs = None;
+        if storage.options.iter().any(|e| e == KATA_OVERLAY_RW_OPTION) {
             let cid = ctx
                 .cid
                 .clone()
                 .ok_or_else(|| anyhow!("No container id in rw overlay"))?;
-            let cpath = Path::new(crate::rpc::CONTAINER_BASE).join(cid);
+            let mut cpath = Path::new(crate::rpc::CONTAINER_BASE).join(cid);
+            if let Some(size) = tmpfs_size(&storage)? {
+                cpath = cpath.join(OVERLAY_TMPFS_DIR);
+                fs::create_dir_all(&cpath).context("Creating overlay tmpfs directory")?;
+                baremount(
+                    Path::new("tmpfs"),
+                    &cpath,
+                    "tmpfs",
+                    MsFlags::MS_NOSUID | MsFlags::MS_NODEV,
+                    &format!("size={}", size),
+                    ctx.logger,
+                )
+                .context("Mounting overlay tmpfs")?;
+                tmpfs = Some(cpath.to_string_lossy().to_string());
+            }
             let work = cpath.join("work");
             let upper = cpath.join("upper");
 
             fs::create_dir_all(&work).context("Creating overlay work directory")?;
             fs::create_dir_all(&upper).context("Creating overlay upper directory")?;
 
+            // the option is for the agent, not the kernel
+            storage.options.retain(|o| o != KATA_OVERLAY_RW_OPTION);
             storage.fstype = "overlay".into();
             storage
                 .options
@@ -51,8 +74,69 @@ impl StorageHandler for OverlayfsHandler {
                 .push(format!("workdir={}", work.to_string_lossy()));
         }
 
-        let path = common_storage_handler(ctx.logger, &storage)?;
-        new_device(path)
+        let tmpfs = match tmpfs {
+            Some(tmpfs) => tmpfs,
+            None => {
+                let path = common_storage_handler(ctx.logger, &storage)?;
+                return new_device(path);
+            }
+        };
+        match common_storage_handler(ctx.logger, &storage) {
+            Ok(path) => Ok(Arc::new(OverlayTmpfsDevice {
+                overlay: StorageDeviceGeneric::new(path),
+                tmpfs,
+            })),
+            Err(e) => {
+                if let Err(e) = remove_tmpfs(&tmpfs) {
+                    warn!(
+                        ctx.logger,
+                        "failed to remove overlay tmpfs {}: {:?}", tmpfs, e
+                    );
+                }
+                Err(e)
+            }
+        }
+    }
+}
+
+// tmpfs_size gets the size in bytes of the tmpfs backing the upper dir of the
+// overlay from the driver options.
+fn tmpfs_size(storage: &Storage) -> Result<Option<u64>> {
+    let opts = parse_options(&storage.driver_options);
+    match opts.get(KATA_STORAGE_DRIVER_OPTION_TMPFS_SIZE) {
+        Some(size) => {
+            let size = size
+                .parse::<u64>()
+                .context(format!("invalid overlay tmpfs size {}", size))?;
+            if size == 0 {
+                return Err(anyhow!("overlay tmpfs size must not be 0"));
+            }
+            Ok(Some(size))
+        }
+        None => Ok(None),
+    }
+}
+
+fn remove_tmpfs(tmpfs: &str) -> Result<()> {
+    remove_mounts(&[tmpfs])?;
+    fs::remove_dir(tmpfs).context("Removing overlay tmpfs directory")
+}
+
+/// An overlay with its upper dir in a tmpfs, which is removed after the overlay.
+#[derive(Debug)]
+pub struct OverlayTmpfsDevice {
+    overlay: StorageDeviceGeneric,
+    tmpfs: String,
+}
+
+impl StorageDevice for OverlayTmpfsDevice {
+    fn path(&self) -> Option<&str> {
+        self.overlay.path()
+    }
+
+    fn cleanup(&self) -> Result<()> {
+        self.overlay.cleanup()?;
+        remove_tmpfs(&self.tmpfs)
     }
 }
 
@@ -87,3 +171,23 @@ impl StorageHandler for VirtioFsHandler {
         new_device(path)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tmpfs_size() {
+        let mut storage = Storage::default();
+        assert_eq!(tmpfs_size(&storage).unwrap(), None);
+
+        storage.driver_options = vec![format!("{}=1048576", KATA_STORAGE_DRIVER_OPTION_TMPFS_SIZE)];
+        assert_eq!(tmpfs_size(&storage).unwrap(), Some(1048576));
+
+        storage.driver_options = vec![format!("{}=0", KATA_STORAGE_DRIVER_OPTION_TMPFS_SIZE)];
+        assert!(tmpfs_size(&storage).is_err());
+
+        storage.driver_options = vec![format!("{}=1g", KATA_STORAGE_DRIVER_OPTION_TMPFS_SIZE)];
+        assert!(tmpfs_size(&storage).is_err());
+    }
+}
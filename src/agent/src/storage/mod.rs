@@ -12,7 +12,10 @@ use std::sync::Arc;
 
 use anyhow::{anyhow, Context, Result};
 use kata_sys_util::mount::{create_mount_destination, parse_mount_options};
-use kata_types::mount::{StorageDevice, StorageHandlerManager, KATA_SHAREDFS_GUEST_PREMOUNT_TAG};
+use kata_types::mount::{
+    StorageDevice, StorageHandlerManager, KATA_SHAREDFS_GUEST_PREMOUNT_TAG,
+    KATA_STORAGE_DRIVER_OPTION_QUOTA,
+};
 use nix::unistd::{Gid, Uid};
 use protocols::agent::Storage;
 use protocols::types::FSGroupChangePolicy;
@@ -22,6 +25,7 @@ use tracing::instrument;
 
 use self::bind_watcher_handler::BindWatcherHandler;
 use self::block_handler::{PmemHandler, ScsiHandler, VirtioBlkMmioHandler, VirtioBlkPciHandler};
+use self::capacity_monitor::mount_failed_event;
 use self::ephemeral_handler::EphemeralHandler;
 use self::fs_handler::{OverlayfsHandler, Virtio9pHandler, VirtioFsHandler};
 use self::local_handler::LocalHandler;
@@ -37,9 +41,12 @@ pub use self::ephemeral_handler::update_ephemeral_mounts;
 
 mod bind_watcher_handler;
 mod block_handler;
+pub mod capacity_monitor;
 mod ephemeral_handler;
 mod fs_handler;
 mod local_handler;
+pub mod quota;
+pub mod writable_layer;
 
 const RW_MASK: u32 = 0o660;
 const RO_MASK: u32 = 0o440;
@@ -87,6 +94,7 @@ impl StorageDevice for StorageDeviceGeneric {
             return Ok(());
         }
 
+        quota::remove_storage_quota(path);
         if matches!(is_mounted(path), Ok(true)) {
             let mounts = vec![path.to_string()];
             remove_mounts(&mounts)?;
@@ -217,9 +225,11 @@ pub async fn add_storages(
                 }
                 Err(e) => {
                     error!(logger, "failed to create device for storage, error: {e:?}");
-                    if let Err(e) = sandbox.lock().await.remove_sandbox_storage(&path).await {
+                    let mut s = sandbox.lock().await;
+                    if let Err(e) = s.remove_sandbox_storage(&path).await {
                         warn!(logger, "failed to remove dummy sandbox storage {e:?}");
                     }
+                    s.send_storage_event(mount_failed_event(cid.as_deref(), &path, &e));
                     return Err(e);
                 }
             }
@@ -243,9 +253,29 @@ pub(crate) fn new_device(path: String) -> Result<Arc<dyn StorageDevice>> {
 pub(crate) fn common_storage_handler(logger: &Logger, storage: &Storage) -> Result<String> {
     mount_storage(logger, storage)?;
     set_ownership(logger, storage)?;
+    set_quota(logger, storage)?;
     Ok(storage.mount_point.clone())
 }
 
+// set_quota limits the size of the storage if a quota is requested by the
+// driver options.
+#[instrument]
+fn set_quota(logger: &Logger, storage: &Storage) -> Result<()> {
+    let opts = parse_options(&storage.driver_options);
+    let limit = match opts.get(KATA_STORAGE_DRIVER_OPTION_QUOTA) {
+        Some(limit) => limit
+            .parse::<u64>()
+            .context(format!("invalid storage quota {}", limit))?,
+        None => return Ok(()),
+    };
+
+    info!(logger, "set storage quota";
+        "mount-point" => storage.mount_point.as_str(),
+        "quota-bytes" => limit,
+    );
+    quota::set_storage_quota(&storage.source, &storage.mount_point, limit)
+}
+
 // mount_storage performs the mount described by the storage structure.
 #[instrument]
 fn mount_storage(logger: &Logger, storage: &Storage) -> Result<()> {
@@ -0,0 +1,211 @@
+// Copyright (c) 2023 Kata Containers community
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+// Project quota based size limit for the writable layer of containers whose
+// rootfs lives in the guest, e.g. block device backed rootfs.
+
+use std::collections::HashMap;
+use std::ffi::CString;
+use std::fs::File;
+use std::os::unix::io::AsRawFd;
+use std::sync::Mutex;
+
+use anyhow::{anyhow, Context, Result};
+use nix::errno::Errno;
+use tracing::instrument;
+
+// Project id assigned to the quota limited directory, every quota limited
+// storage is a standalone filesystem so a single project id is enough.
+const KATA_QUOTA_PROJECT_ID: u32 = 1;
+
+// From <linux/quota.h>
+const PRJQUOTA: libc::c_int = 2;
+const Q_GETQUOTA: libc::c_int = 0x800007;
+const Q_SETQUOTA: libc::c_int = 0x800008;
+const QIF_BLIMITS: u32 = 1;
+const QIF_SPACE: u32 = 4;
+// Block limits of quotactl(2) are in units of 1 KiB.
+const QIF_DQBLKSIZE: u64 = 1024;
+
+// From <linux/fs.h>
+const FS_IOC_FSGETXATTR: libc::c_ulong = 0x801c581f;
+const FS_IOC_FSSETXATTR: libc::c_ulong = 0x401c5820;
+const FS_XFLAG_PROJINHERIT: u32 = 0x200;
+
+// Handle the differing ioctl(2) request types for different targets
+#[cfg(target_env = "musl")]
+type IoctlRequestType = libc::c_int;
+#[cfg(target_env = "gnu")]
+type IoctlRequestType = libc::c_ulong;
+
+#[repr(C)]
+#[derive(Debug, Default)]
+struct FsXattr {
+    fsx_xflags: u32,
+    fsx_extsize: u32,
+    fsx_nextents: u32,
+    fsx_projid: u32,
+    fsx_cowextsize: u32,
+    fsx_pad: [u8; 8],
+}
+
+#[repr(C)]
+#[derive(Debug, Default)]
+struct IfDqblk {
+    dqb_bhardlimit: u64,
+    dqb_bsoftlimit: u64,
+    dqb_curspace: u64,
+    dqb_ihardlimit: u64,
+    dqb_isoftlimit: u64,
+    dqb_curinodes: u64,
+    dqb_btime: u64,
+    dqb_itime: u64,
+    dqb_valid: u32,
+}
+
+/// Usage of a quota limited storage, in bytes.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct QuotaUsage {
+    pub used: u64,
+    pub limit: u64,
+}
+
+lazy_static! {
+    // quota limited storages, mount point -> backing device
+    static ref QUOTA_STORAGES: Mutex<HashMap<String, String>> = Mutex::new(HashMap::new());
+}
+
+fn qcmd(cmd: libc::c_int, quota_type: libc::c_int) -> libc::c_int {
+    (cmd << 8) | (quota_type & 0x00ff)
+}
+
+fn quotactl(cmd: libc::c_int, device: &str, dqblk: &mut IfDqblk) -> Result<()> {
+    let device = CString::new(device).context("invalid device path")?;
+    let ret = unsafe {
+        libc::quotactl(
+            qcmd(cmd, PRJQUOTA),
+            device.as_ptr(),
+            KATA_QUOTA_PROJECT_ID as libc::c_int,
+            dqblk as *mut IfDqblk as *mut libc::c_char,
+        )
+    };
+    Errno::result(ret).map(drop)?;
+
+    Ok(())
+}
+
+// Tag the directory with the quota project id, all the files created under
+// it afterwards inherit the project id and are accounted to the quota.
+fn set_project_id(path: &str) -> Result<()> {
+    let dir = File::open(path).context(format!("open {}", path))?;
+    let mut attr = FsXattr::default();
+
+    let ret = unsafe {
+        libc::ioctl(
+            dir.as_raw_fd(),
+            FS_IOC_FSGETXATTR as IoctlRequestType,
+            &mut attr as *mut FsXattr,
+        )
+    };
+    Errno::result(ret).map(drop)?;
+
+    attr.fsx_projid = KATA_QUOTA_PROJECT_ID;
+    attr.fsx_xflags |= FS_XFLAG_PROJINHERIT;
+    let ret = unsafe {
+        libc::ioctl(
+            dir.as_raw_fd(),
+            FS_IOC_FSSETXATTR as IoctlRequestType,
+            &attr as *const FsXattr,
+        )
+    };
+    Errno::result(ret).map(drop)?;
+
+    Ok(())
+}
+
+fn limit_to_blocks(limit: u64) -> u64 {
+    (limit + QIF_DQBLKSIZE - 1) / QIF_DQBLKSIZE
+}
+
+/// Limit the size of the storage mounted at `mount_point` to `limit` bytes.
+///
+/// The filesystem must be mounted with project quota enabled (`prjquota`).
+#[instrument]
+pub fn set_storage_quota(device: &str, mount_point: &str, limit: u64) -> Result<()> {
+    if limit == 0 {
+        return Err(anyhow!("invalid quota 0 for storage {}", mount_point));
+    }
+
+    set_project_id(mount_point).context("set project id")?;
+
+    let mut dqblk = IfDqblk {
+        dqb_bhardlimit: limit_to_blocks(limit),
+        dqb_bsoftlimit: limit_to_blocks(limit),
+        dqb_valid: QIF_BLIMITS,
+        ..Default::default()
+    };
+    quotactl(Q_SETQUOTA, device, &mut dqblk).context("set project quota")?;
+
+    QUOTA_STORAGES
+        .lock()
+        .map_err(|e| anyhow!("failed to lock quota storages {:?}", e))?
+        .insert(mount_point.to_string(), device.to_string());
+
+    Ok(())
+}
+
+/// Stop tracking the quota of the storage mounted at `mount_point`.
+pub fn remove_storage_quota(mount_point: &str) {
+    if let Ok(mut storages) = QUOTA_STORAGES.lock() {
+        storages.remove(mount_point);
+    }
+}
+
+/// Get the usage of all the quota limited storages, keyed by mount point.
+#[instrument]
+pub fn get_storage_quota_usages() -> Result<HashMap<String, QuotaUsage>> {
+    let storages = QUOTA_STORAGES
+        .lock()
+        .map_err(|e| anyhow!("failed to lock quota storages {:?}", e))?
+        .clone();
+
+    let mut usages = HashMap::new();
+    for (mount_point, device) in storages {
+        let mut dqblk = IfDqblk::default();
+        quotactl(Q_GETQUOTA, &device, &mut dqblk)
+            .context(format!("get project quota of {}", mount_point))?;
+        if dqblk.dqb_valid & QIF_SPACE == 0 {
+            continue;
+        }
+        usages.insert(
+            mount_point,
+            QuotaUsage {
+                used: dqblk.dqb_curspace,
+                limit: dqblk.dqb_bhardlimit * QIF_DQBLKSIZE,
+            },
+        );
+    }
+
+    Ok(usages)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_limit_to_blocks() {
+        assert_eq!(limit_to_blocks(1), 1);
+        assert_eq!(limit_to_blocks(1024), 1);
+        assert_eq!(limit_to_blocks(1025), 2);
+        assert_eq!(limit_to_blocks(1024 * 1024 * 1024), 1024 * 1024);
+    }
+
+    #[test]
+    fn test_qcmd() {
+        assert_eq!(qcmd(Q_SETQUOTA, PRJQUOTA) as u32, 0x80000802);
+        assert_eq!(qcmd(Q_GETQUOTA, PRJQUOTA) as u32, 0x80000702);
+    }
+}
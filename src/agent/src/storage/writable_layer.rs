@@ -0,0 +1,150 @@
+// Copyright (c) 2023 Kata Containers community
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+// Usage of the writable layer of containers, the changes they made to their
+// image, reported with their stats for the disk usage accounting of the CRI.
+
+use std::collections::HashSet;
+use std::fs;
+use std::os::unix::fs::MetadataExt;
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use protocols::agent::FilesystemUsage;
+use tracing::instrument;
+
+const MOUNTINFO_PATH: &str = "/proc/self/mountinfo";
+const OVERLAY_FS_TYPE: &str = "overlay";
+const UPPERDIR_OPTION: &str = "upperdir=";
+// runtime-rs shares the upper dir of the rootfs overlay of the host read-only
+// next to the shared rootfs, in its passthrough dir
+const PASSTHROUGH_FS_DIR: &str = "passthrough";
+const SHARED_ROOTFS_UPPER: &str = "rootfs-upper";
+// st_blocks is in units of 512 bytes
+const STAT_BLOCK_SIZE: u64 = 512;
+
+/// Get the usage of the writable layer of the container `cid` with its
+/// rootfs at `rootfs`, `None` if the layer can't be told from the image.
+#[instrument]
+pub fn get_writable_layer_usage(cid: &str, rootfs: &str) -> Result<Option<FilesystemUsage>> {
+    let mountinfo =
+        fs::read_to_string(MOUNTINFO_PATH).with_context(|| format!("read {}", MOUNTINFO_PATH))?;
+    let shared_upper = Path::new(crate::rpc::KATA_GUEST_SHARE_DIR)
+        .join(PASSTHROUGH_FS_DIR)
+        .join(cid)
+        .join(SHARED_ROOTFS_UPPER);
+    let path = match find_writable_layer(&mountinfo, rootfs, &shared_upper) {
+        Some(path) => path,
+        None => return Ok(None),
+    };
+
+    let mut inodes = HashSet::new();
+    let used_bytes = dir_usage(Path::new(&path), &mut inodes)
+        .with_context(|| format!("get usage of {}", path))?;
+    Ok(Some(FilesystemUsage {
+        path,
+        used_bytes,
+        inodes_used: inodes.len() as u64,
+        ..Default::default()
+    }))
+}
+
+// find_writable_layer returns the upper dir of the rootfs overlay of the
+// guest, or the one shared by the host, the mountinfo records are
+// `id parent major:minor root mount_point options [optional...] - fs_type source super_options`
+fn find_writable_layer(mountinfo: &str, rootfs: &str, shared_upper: &Path) -> Option<String> {
+    // the mounts on top hide the ones below
+    let record = mountinfo.lines().rev().find_map(|line| {
+        let (mount, fs) = line.split_once(" - ")?;
+        (mount.split(' ').nth(4)? == rootfs).then(|| fs)
+    });
+
+    if let Some(fs) = record {
+        let fields: Vec<&str> = fs.split(' ').collect();
+        if fields.first() == Some(&OVERLAY_FS_TYPE) {
+            return fields
+                .get(2)?
+                .split(',')
+                .find_map(|o| o.strip_prefix(UPPERDIR_OPTION))
+                .map(str::to_string);
+        }
+    }
+
+    shared_upper
+        .is_dir()
+        .then(|| shared_upper.display().to_string())
+}
+
+// dir_usage returns the bytes allocated to the content of dir, the hard links
+// are counted once, the symlinks aren't followed
+fn dir_usage(dir: &Path, inodes: &mut HashSet<(u64, u64)>) -> Result<u64> {
+    let mut size = 0;
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let metadata = entry.metadata()?;
+        if !inodes.insert((metadata.dev(), metadata.ino())) {
+            continue;
+        }
+        size += metadata.blocks() * STAT_BLOCK_SIZE;
+        if metadata.is_dir() {
+            size += dir_usage(&entry.path(), inodes)?;
+        }
+    }
+    Ok(size)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_writable_layer() {
+        let mountinfo = "22 1 253:1 / / rw,relatime shared:1 - ext4 /dev/vda1 rw\n\
+             30 22 0:40 / /run/kata-containers/c1/rootfs rw,relatime - overlay overlay rw,lowerdir=/l1:/l2,upperdir=/run/kata-containers/c1/upper,workdir=/run/kata-containers/c1/work\n\
+             31 22 0:41 /c2/rootfs /run/kata-containers/c2/rootfs rw,relatime - virtiofs kataShared rw";
+        let dir = tempfile::tempdir().unwrap();
+        let missing = dir.path().join("missing");
+
+        assert_eq!(
+            find_writable_layer(mountinfo, "/run/kata-containers/c1/rootfs", &missing),
+            Some("/run/kata-containers/c1/upper".to_string())
+        );
+        assert_eq!(
+            find_writable_layer(mountinfo, "/run/kata-containers/c2/rootfs", &missing),
+            None
+        );
+        assert_eq!(
+            find_writable_layer(mountinfo, "/run/kata-containers/c2/rootfs", dir.path()),
+            Some(dir.path().display().to_string())
+        );
+        assert_eq!(
+            find_writable_layer(mountinfo, "/run/kata-containers/c3/rootfs", &missing),
+            None
+        );
+    }
+
+    #[test]
+    fn test_dir_usage() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut inodes = HashSet::new();
+        assert_eq!(dir_usage(dir.path(), &mut inodes).unwrap(), 0);
+        assert!(inodes.is_empty());
+
+        fs::write(dir.path().join("a"), vec![1u8; 8192]).unwrap();
+        fs::hard_link(dir.path().join("a"), dir.path().join("b")).unwrap();
+        fs::create_dir(dir.path().join("sub")).unwrap();
+        std::os::unix::fs::symlink("/nonexistent", dir.path().join("sub").join("l")).unwrap();
+
+        let mut inodes = HashSet::new();
+        let size = dir_usage(dir.path(), &mut inodes).unwrap();
+        // a and b are the same inode
+        assert_eq!(inodes.len(), 3);
+        let blocks = |p: &str| fs::symlink_metadata(dir.path().join(p)).unwrap().blocks();
+        assert_eq!(
+            size,
+            (blocks("a") + blocks("sub") + blocks("sub/l")) * STAT_BLOCK_SIZE
+        );
+    }
+}
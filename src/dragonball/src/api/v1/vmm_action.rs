@@ -7,13 +7,14 @@
 // found in the THIRD-PARTY file.
 
 use std::fs::File;
+use std::path::PathBuf;
 
 use crossbeam_channel::{Receiver, Sender, TryRecvError};
 use log::{debug, error, info, warn};
 
 use crate::error::{Result, StartMicroVmError, StopMicrovmError};
 use crate::event_manager::EventManager;
-use crate::vm::{CpuTopology, KernelConfigInfo, VmConfigInfo};
+use crate::vm::{CpuTopology, DumpGuestMemoryError, KernelConfigInfo, VmConfigInfo};
 use crate::vmm::Vmm;
 
 use crate::hypervisor_metrics::get_hypervisor_metrics;
@@ -121,6 +122,10 @@ pub enum VmmActionError {
     /// Balloon device related errors.
     #[error("virtio-balloon device error: {0}")]
     Balloon(#[source] BalloonDeviceError),
+
+    /// The guest memory dump failed.
+    #[error("failed to dump guest memory: {0}")]
+    DumpGuestMemory(#[source] DumpGuestMemoryError),
 }
 
 /// This enum represents the public interface of the VMM. Each action contains various
@@ -208,6 +213,10 @@ pub enum VmmAction {
     /// Add a new balloon device or update one that already exists using the `BalloonDeviceConfig`
     /// as input.
     InsertBalloonDevice(BalloonDeviceConfigInfo),
+
+    /// Dump the guest memory to a new file at the path as an ELF core file, for the forensic
+    /// analysis. This action can only be called after the microVM has booted.
+    DumpGuestMemory(PathBuf),
 }
 
 /// The enum represents the response sent by the VMM in case of success. The response is either
@@ -315,6 +324,7 @@ impl VmmService {
             VmmAction::ResizeVcpu(vcpu_resize_cfg) => self.resize_vcpu(vmm, vcpu_resize_cfg),
             #[cfg(feature = "virtio-mem")]
             VmmAction::InsertMemDevice(mem_cfg) => self.add_mem_device(vmm, event_mgr, mem_cfg),
+            VmmAction::DumpGuestMemory(path) => self.dump_guest_memory(vmm, path),
             #[cfg(feature = "virtio-balloon")]
             VmmAction::InsertBalloonDevice(balloon_cfg) => {
                 self.add_balloon_device(vmm, event_mgr, balloon_cfg)
@@ -395,6 +405,19 @@ impl VmmService {
         Ok(VmmData::Empty)
     }
 
+    fn dump_guest_memory(&mut self, vmm: &mut Vmm, path: PathBuf) -> VmmRequestResult {
+        let vm = vmm.get_vm_mut().ok_or(VmmActionError::VmNotExist)?;
+        if !vm.is_vm_initialized() {
+            return Err(VmmActionError::DumpGuestMemory(
+                DumpGuestMemoryError::MemoryNotInitialized,
+            ));
+        }
+
+        vm.dump_guest_memory(&path)
+            .map(|_| VmmData::Empty)
+            .map_err(VmmActionError::DumpGuestMemory)
+    }
+
     /// Get prometheus metrics.
     fn get_hypervisor_metrics(&self) -> VmmRequestResult {
         get_hypervisor_metrics()
@@ -0,0 +1,223 @@
+// Copyright (c) 2023 Kata Containers community
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! Dump of the guest memory as an ELF core file, for the forensic analysis.
+//!
+//! Every guest memory region is a `PT_LOAD` segment at its guest physical address, the same
+//! layout as the dumps of `dump-guest-memory` of QEMU without paging, so that the usual tools
+//! can read it.
+
+use std::fs::OpenOptions;
+use std::io::{self, BufWriter, Write};
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::Path;
+
+use vm_memory::{Bytes, GuestMemory, GuestMemoryRegion};
+
+use crate::vcpu::VcpuManagerError;
+
+const ELF_HEADER_SIZE: u64 = 64;
+const ELF_PROGRAM_HEADER_SIZE: u64 = 56;
+const ELF_CLASS_64: u8 = 2;
+const ELF_DATA_LSB: u8 = 1;
+const ELF_VERSION_CURRENT: u8 = 1;
+const ELF_TYPE_CORE: u16 = 4;
+#[cfg(target_arch = "x86_64")]
+const ELF_MACHINE: u16 = 62; // EM_X86_64
+#[cfg(target_arch = "aarch64")]
+const ELF_MACHINE: u16 = 183; // EM_AARCH64
+const PT_LOAD: u32 = 1;
+const PF_RWX: u32 = 0x7;
+// the segments start on a page boundary
+const SEGMENT_ALIGN: u64 = 4096;
+
+/// Errors associated with the guest memory dump.
+#[derive(Debug, thiserror::Error)]
+pub enum DumpGuestMemoryError {
+    /// The guest memory isn't initialized yet.
+    #[error("the guest memory isn't initialized")]
+    MemoryNotInitialized,
+
+    /// The dump file exists already, it's never overwritten.
+    #[error("the dump file {0} exists already")]
+    FileExists(String),
+
+    /// Not enough space for the dump on the target filesystem.
+    #[error("{0} bytes are needed for the dump, only {1} bytes are available")]
+    NoSpace(u64, u64),
+
+    /// Failed to pause or resume the vcpus.
+    #[error("failed to pause or resume the vcpus: {0}")]
+    Vcpu(#[source] VcpuManagerError),
+
+    /// Failed to read the guest memory.
+    #[error("failed to read the guest memory: {0}")]
+    Memory(#[source] vm_memory::GuestMemoryError),
+
+    /// Failed to write the dump.
+    #[error("failed to write the dump: {0}")]
+    Io(#[source] io::Error),
+}
+
+type Result<T> = std::result::Result<T, DumpGuestMemoryError>;
+
+fn data_offset(regions: u64) -> u64 {
+    let headers = ELF_HEADER_SIZE + ELF_PROGRAM_HEADER_SIZE * regions;
+    (headers + SEGMENT_ALIGN - 1) / SEGMENT_ALIGN * SEGMENT_ALIGN
+}
+
+/// Size of the dump of the guest memory `mem`.
+pub fn dump_size<M: GuestMemory>(mem: &M) -> u64 {
+    data_offset(mem.num_regions() as u64) + mem.iter().map(|r| r.len()).sum::<u64>()
+}
+
+/// Check there is enough space for a dump of `size` bytes at `path`.
+pub fn check_space(path: &Path, size: u64) -> Result<()> {
+    if path.exists() {
+        return Err(DumpGuestMemoryError::FileExists(path.display().to_string()));
+    }
+
+    let dir = path
+        .parent()
+        .filter(|p| !p.as_os_str().is_empty())
+        .unwrap_or_else(|| Path::new("."));
+    let stat = nix::sys::statvfs::statvfs(dir)
+        .map_err(|e| DumpGuestMemoryError::Io(io::Error::from_raw_os_error(e as i32)))?;
+    let available = stat.blocks_available() as u64 * stat.fragment_size() as u64;
+    if available < size {
+        return Err(DumpGuestMemoryError::NoSpace(size, available));
+    }
+
+    Ok(())
+}
+
+fn write_elf_header<W: Write>(w: &mut W, regions: u16) -> io::Result<()> {
+    let mut ident = [0u8; 16];
+    ident[..4].copy_from_slice(b"\x7fELF");
+    ident[4] = ELF_CLASS_64;
+    ident[5] = ELF_DATA_LSB;
+    ident[6] = ELF_VERSION_CURRENT;
+    w.write_all(&ident)?;
+    w.write_all(&ELF_TYPE_CORE.to_le_bytes())?;
+    w.write_all(&ELF_MACHINE.to_le_bytes())?;
+    w.write_all(&(ELF_VERSION_CURRENT as u32).to_le_bytes())?;
+    // entry, program header offset and section header offset
+    w.write_all(&0u64.to_le_bytes())?;
+    w.write_all(&ELF_HEADER_SIZE.to_le_bytes())?;
+    w.write_all(&0u64.to_le_bytes())?;
+    // flags
+    w.write_all(&0u32.to_le_bytes())?;
+    w.write_all(&(ELF_HEADER_SIZE as u16).to_le_bytes())?;
+    w.write_all(&(ELF_PROGRAM_HEADER_SIZE as u16).to_le_bytes())?;
+    w.write_all(&regions.to_le_bytes())?;
+    // no section headers
+    w.write_all(&[0u8; 6])
+}
+
+fn write_program_header<W: Write>(w: &mut W, offset: u64, addr: u64, len: u64) -> io::Result<()> {
+    w.write_all(&PT_LOAD.to_le_bytes())?;
+    w.write_all(&PF_RWX.to_le_bytes())?;
+    w.write_all(&offset.to_le_bytes())?;
+    // virtual and physical address
+    w.write_all(&addr.to_le_bytes())?;
+    w.write_all(&addr.to_le_bytes())?;
+    // size in file and in memory
+    w.write_all(&len.to_le_bytes())?;
+    w.write_all(&len.to_le_bytes())?;
+    // alignment
+    w.write_all(&0u64.to_le_bytes())
+}
+
+/// Write the dump of the guest memory `mem` to `w`, the size of the dump is returned.
+pub fn write_dump<M: GuestMemory, W: Write>(mem: &M, w: &mut W) -> Result<u64> {
+    let regions = mem.num_regions() as u64;
+    write_elf_header(w, regions as u16).map_err(DumpGuestMemoryError::Io)?;
+
+    let mut offset = data_offset(regions);
+    for region in mem.iter() {
+        write_program_header(w, offset, region.start_addr().0, region.len())
+            .map_err(DumpGuestMemoryError::Io)?;
+        offset += region.len();
+    }
+
+    let padding = data_offset(regions) - ELF_HEADER_SIZE - ELF_PROGRAM_HEADER_SIZE * regions;
+    w.write_all(&vec![0u8; padding as usize])
+        .map_err(DumpGuestMemoryError::Io)?;
+    for region in mem.iter() {
+        mem.write_all_to(region.start_addr(), w, region.len() as usize)
+            .map_err(DumpGuestMemoryError::Memory)?;
+    }
+    w.flush().map_err(DumpGuestMemoryError::Io)?;
+
+    Ok(offset)
+}
+
+/// Dump the guest memory `mem` to a new file at `path`, which is only accessible to its owner.
+pub fn dump_to_file<M: GuestMemory>(mem: &M, path: &Path) -> Result<u64> {
+    let file = OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(0o600)
+        .open(path)
+        .map_err(DumpGuestMemoryError::Io)?;
+    let mut writer = BufWriter::new(file);
+    write_dump(mem, &mut writer)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use vm_memory::{GuestAddress, GuestMemoryMmap};
+    use vmm_sys_util::tempdir::TempDir;
+
+    #[test]
+    fn test_write_dump() {
+        let mem = GuestMemoryMmap::<()>::from_ranges(&[
+            (GuestAddress(0), 0x1000),
+            (GuestAddress(0x10000), 0x2000),
+        ])
+        .unwrap();
+        mem.write_slice(b"kata", GuestAddress(0x10000)).unwrap();
+
+        let mut dump = vec![];
+        let size = write_dump(&mem, &mut dump).unwrap();
+        assert_eq!(size, dump.len() as u64);
+        assert_eq!(size, dump_size(&mem));
+        assert_eq!(size, SEGMENT_ALIGN + 0x3000);
+
+        assert_eq!(&dump[..4], b"\x7fELF");
+        assert_eq!(u16::from_le_bytes([dump[16], dump[17]]), ELF_TYPE_CORE);
+        assert_eq!(u16::from_le_bytes([dump[56], dump[57]]), 2);
+
+        // the second segment is at its guest physical address
+        let phdr = &dump[(ELF_HEADER_SIZE + ELF_PROGRAM_HEADER_SIZE) as usize..];
+        let field = |i: usize| {
+            let mut b = [0u8; 8];
+            b.copy_from_slice(&phdr[i..i + 8]);
+            u64::from_le_bytes(b)
+        };
+        let offset = field(8);
+        assert_eq!(offset, SEGMENT_ALIGN + 0x1000);
+        assert_eq!(field(24), 0x10000);
+        assert_eq!(field(32), 0x2000);
+        assert_eq!(&dump[offset as usize..offset as usize + 4], b"kata");
+    }
+
+    #[test]
+    fn test_check_space() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.as_path().join("dump");
+        assert!(check_space(&path, 0).is_ok());
+        assert!(matches!(
+            check_space(&path, u64::MAX),
+            Err(DumpGuestMemoryError::NoSpace(..))
+        ));
+
+        std::fs::write(&path, b"").unwrap();
+        assert!(matches!(
+            check_space(&path, 0),
+            Err(DumpGuestMemoryError::FileExists(_))
+        ));
+    }
+}
@@ -4,6 +4,7 @@
 use std::io::{self, Read, Seek, SeekFrom};
 use std::ops::Deref;
 use std::os::unix::io::RawFd;
+use std::path::Path;
 
 use std::sync::{Arc, Mutex, RwLock};
 
@@ -48,6 +49,9 @@ use dbs_arch::gic::Error as GICError;
 mod kernel_config;
 pub use self::kernel_config::KernelConfigInfo;
 
+mod memory_dump;
+pub use self::memory_dump::DumpGuestMemoryError;
+
 #[cfg(target_arch = "aarch64")]
 #[path = "aarch64.rs"]
 mod aarch64;
@@ -442,6 +446,43 @@ impl Vm {
         Ok(())
     }
 
+    /// Dump the guest memory to a new file at `path` as an ELF core file, the size of the dump
+    /// is returned. The vcpus are paused during the dump so that the memory is consistent.
+    pub fn dump_guest_memory(
+        &mut self,
+        path: &Path,
+    ) -> std::result::Result<u64, DumpGuestMemoryError> {
+        let vm_as = self
+            .vm_as()
+            .ok_or(DumpGuestMemoryError::MemoryNotInitialized)?
+            .clone();
+        let mem = vm_as.memory();
+        memory_dump::check_space(path, memory_dump::dump_size(mem.deref()))?;
+
+        let running = self.is_vm_running();
+        if running {
+            self.vcpu_manager()
+                .and_then(|mut mgr| mgr.pause_all_vcpus())
+                .map_err(DumpGuestMemoryError::Vcpu)?;
+        }
+        let result = memory_dump::dump_to_file(mem.deref(), path);
+        if running {
+            self.vcpu_manager()
+                .and_then(|mut mgr| mgr.resume_all_vcpus())
+                .map_err(DumpGuestMemoryError::Vcpu)?;
+        }
+        match &result {
+            Ok(size) => info!(
+                self.logger,
+                "VM: dumped {} bytes of guest memory to {:?}", size, path
+            ),
+            Err(_) => {
+                let _ = std::fs::remove_file(path);
+            }
+        }
+        result
+    }
+
     /// Resume all vcpus and calc the intance downtime
     pub fn resume_all_vcpus_with_downtime(&mut self) -> std::result::Result<(), VcpuManagerError> {
         self.vcpu_manager()?.resume_all_vcpus()?;
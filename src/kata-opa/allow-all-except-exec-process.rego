@@ -2,6 +2,7 @@ package agent_policy
 
 default AddARPNeighborsRequest := true
 default AddSwapRequest := true
+default CheckpointContainerRequest := true
 default CloseStdinRequest := true
 default CopyFileRequest := true
 default CreateContainerRequest := true
@@ -9,6 +10,7 @@ default CreateSandboxRequest := true
 default DestroySandboxRequest := true
 default GetMetricsRequest := true
 default GetOOMEventRequest := true
+default GetStorageEventRequest := true
 default GuestDetailsRequest := true
 default ListInterfacesRequest := true
 default ListRoutesRequest := true
@@ -16,11 +18,13 @@ default MemHotplugByProbeRequest := true
 default OnlineCPUMemRequest := true
 default PauseContainerRequest := true
 default PullImageRequest := true
+default ReadFileRequest := true
 default ReadStreamRequest := true
 default RemoveContainerRequest := true
 default RemoveStaleVirtiofsShareMountsRequest := true
 default ReseedRandomDevRequest := false
 default ResumeContainerRequest := true
+default SetDebugConsoleRequest := false
 default SetGuestDateTimeRequest := true
 default SetPolicyRequest := true
 default SignalProcessRequest := true
@@ -36,4 +40,5 @@ default UpdateRoutesRequest := true
 default WaitProcessRequest := true
 default WriteStreamRequest := true
 
+default ExecGuestHookRequest := true
 default ExecProcessRequest := false
@@ -2,14 +2,17 @@ package agent_policy
 
 default AddARPNeighborsRequest := true
 default AddSwapRequest := true
+default CheckpointContainerRequest := true
 default CloseStdinRequest := true
 default CopyFileRequest := true
 default CreateContainerRequest := true
 default CreateSandboxRequest := true
 default DestroySandboxRequest := true
+default ExecGuestHookRequest := true
 default ExecProcessRequest := true
 default GetMetricsRequest := true
 default GetOOMEventRequest := true
+default GetStorageEventRequest := true
 default GuestDetailsRequest := true
 default ListInterfacesRequest := true
 default ListRoutesRequest := true
@@ -17,11 +20,13 @@ default MemHotplugByProbeRequest := true
 default OnlineCPUMemRequest := true
 default PauseContainerRequest := true
 default PullImageRequest := true
+default ReadFileRequest := true
 default ReadStreamRequest := true
 default RemoveContainerRequest := true
 default RemoveStaleVirtiofsShareMountsRequest := true
 default ReseedRandomDevRequest := false
 default ResumeContainerRequest := true
+default SetDebugConsoleRequest := true
 default SetGuestDateTimeRequest := true
 default SetPolicyRequest := true
 default SignalProcessRequest := true
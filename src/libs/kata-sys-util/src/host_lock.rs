@@ -0,0 +1,212 @@
+// Copyright (c) 2023 Kata Containers community
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+//! Locks of the host resources shared by the shims of a node.
+//!
+//! Every sandbox has its own shim process, so the resources of the host which are used by more
+//! than one sandbox, e.g. the direct-volume directory, the hugepage pool, the network devices or
+//! the vsock context IDs, are protected by file locks (flock) in a common directory. The lock is released when the
+//! guard is dropped, or by the kernel when the shim dies while holding it.
+
+use std::fs::{self, File, OpenOptions};
+use std::io;
+use std::os::unix::io::AsRawFd;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use nix::errno::Errno;
+use nix::fcntl::{flock, FlockArg};
+
+use crate::sl;
+
+/// Directory of the lock files shared by the shims.
+pub const HOST_LOCK_DIR: &str = "/run/kata-containers/locks";
+
+/// Default time to wait for a host lock.
+pub const DEFAULT_LOCK_TIMEOUT: Duration = Duration::from_secs(30);
+
+// interval between two attempts to take a lock
+const LOCK_RETRY_INTERVAL: Duration = Duration::from_millis(10);
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("Can not create lock directory {0}: {1}")]
+    CreateDirectory(PathBuf, #[source] io::Error),
+    #[error("Can not open lock file {0}: {1}")]
+    OpenFile(PathBuf, #[source] io::Error),
+    #[error("Can not lock {0}: {1}")]
+    Lock(PathBuf, #[source] nix::Error),
+    #[error("Timed out after {1:?} waiting for lock {0}")]
+    Timeout(PathBuf, Duration),
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// Host resources shared by the sandboxes of a node.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HostResource {
+    /// The mount info of the direct volumes.
+    DirectVolume,
+    /// The hugepage pool.
+    Hugepages,
+    /// The network devices passed through to the VMs.
+    NetworkDevice,
+    /// The vsock context IDs of the VMs.
+    VsockCid,
+}
+
+impl HostResource {
+    pub fn name(&self) -> &'static str {
+        match self {
+            HostResource::DirectVolume => "direct-volume",
+            HostResource::Hugepages => "hugepages",
+            HostResource::NetworkDevice => "network-device",
+            HostResource::VsockCid => "vsock-cid",
+        }
+    }
+}
+
+/// Guard of a host lock, the lock is released when it's dropped.
+#[derive(Debug)]
+pub struct HostLockGuard {
+    file: File,
+    path: PathBuf,
+}
+
+impl HostLockGuard {
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for HostLockGuard {
+    fn drop(&mut self) {
+        if let Err(e) = flock(self.file.as_raw_fd(), FlockArg::Unlock) {
+            warn!(sl!(), "failed to unlock {}: {}", self.path.display(), e);
+        }
+    }
+}
+
+/// Lock the host resource `resource`, or the item `key` of it, e.g. a device or a volume.
+///
+/// The lock is waited for `timeout` at most. It must not be held across an `.await`, the other
+/// tasks of the shim would be blocked on it.
+pub fn lock(resource: HostResource, key: Option<&str>, timeout: Duration) -> Result<HostLockGuard> {
+    lock_at(lock_path(HOST_LOCK_DIR, resource, key), timeout)
+}
+
+/// Path of the lock file of `resource`, or of the item `key` of it, in `dir`.
+pub fn lock_path<P: AsRef<Path>>(dir: P, resource: HostResource, key: Option<&str>) -> PathBuf {
+    let name = match key {
+        Some(key) => format!("{}-{}.lock", resource.name(), sanitize(key)),
+        None => format!("{}.lock", resource.name()),
+    };
+    dir.as_ref().join(name)
+}
+
+/// Take the exclusive lock of the file at `path`, created if it doesn't exist, waiting for
+/// `timeout` at most.
+pub fn lock_at<P: AsRef<Path>>(path: P, timeout: Duration) -> Result<HostLockGuard> {
+    let path = path.as_ref().to_path_buf();
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|e| Error::CreateDirectory(dir.to_path_buf(), e))?;
+    }
+    let file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .open(&path)
+        .map_err(|e| Error::OpenFile(path.clone(), e))?;
+
+    let deadline = Instant::now() + timeout;
+    loop {
+        match flock(file.as_raw_fd(), FlockArg::LockExclusiveNonblock) {
+            Ok(()) => return Ok(HostLockGuard { file, path }),
+            Err(Errno::EWOULDBLOCK) | Err(Errno::EINTR) => {
+                if Instant::now() >= deadline {
+                    return Err(Error::Timeout(path, timeout));
+                }
+                thread::sleep(LOCK_RETRY_INTERVAL);
+            }
+            Err(e) => return Err(Error::Lock(path, e)),
+        }
+    }
+}
+
+// the key is a path or a PCI address, keep it a single file name
+fn sanitize(key: &str) -> String {
+    key.trim_matches('/')
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '.' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lock_path() {
+        assert_eq!(
+            lock_path("/locks", HostResource::Hugepages, None),
+            PathBuf::from("/locks/hugepages.lock")
+        );
+        assert_eq!(
+            lock_path("/locks", HostResource::NetworkDevice, Some("0000:00:03.0")),
+            PathBuf::from("/locks/network-device-0000_00_03.0.lock")
+        );
+        assert_eq!(
+            lock_path("/locks", HostResource::VsockCid, Some("3")),
+            PathBuf::from("/locks/vsock-cid-3.lock")
+        );
+        assert_eq!(
+            lock_path(
+                "/locks",
+                HostResource::DirectVolume,
+                Some("/run/volumes/../vol")
+            ),
+            PathBuf::from("/locks/direct-volume-run_volumes_.._vol.lock")
+        );
+    }
+
+    #[test]
+    fn test_lock_exclusive() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = lock_path(dir.path().join("locks"), HostResource::Hugepages, None);
+
+        let guard = lock_at(&path, Duration::from_millis(50)).unwrap();
+        assert_eq!(guard.path(), path);
+
+        // flock is per open file, so the second lock is refused in the same process too
+        let err = lock_at(&path, Duration::from_millis(50)).unwrap_err();
+        assert!(matches!(err, Error::Timeout(..)));
+
+        drop(guard);
+        lock_at(&path, Duration::from_millis(50)).unwrap();
+    }
+
+    #[test]
+    fn test_lock_wait() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = lock_path(dir.path(), HostResource::DirectVolume, Some("vol"));
+
+        let guard = lock_at(&path, Duration::from_millis(50)).unwrap();
+        let handle = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(100));
+            drop(guard);
+        });
+
+        // the lock is taken once the first holder releases it
+        lock_at(&path, Duration::from_secs(5)).unwrap();
+        handle.join().unwrap();
+    }
+}
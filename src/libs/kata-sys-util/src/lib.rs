@@ -10,11 +10,14 @@ pub mod cpu;
 pub mod device;
 pub mod fs;
 pub mod hooks;
+pub mod host_lock;
 pub mod k8s;
 pub mod mount;
+pub mod naming;
 pub mod numa;
 pub mod protection;
 pub mod rand;
+pub mod retry;
 pub mod spec;
 pub mod validate;
 
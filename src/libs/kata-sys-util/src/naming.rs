@@ -0,0 +1,167 @@
+// Copyright (c) 2023 Kata Containers community
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+//! Deterministic names of the host resources of a sandbox.
+//!
+//! The taps, the sandbox directory and the sockets in it are named from the sandbox id or the
+//! index of the interface, so that a restarted shim finds the resources of its sandbox again.
+//! The ids may exceed the limits of the names, e.g. IFNAMSIZ or the length of the path of a unix
+//! socket, and truncating them would make the ids sharing a prefix clash. The names too long keep
+//! a prefix of the id followed by a hash of the whole id, which is stable across the releases.
+
+use std::fs;
+use std::io::{ErrorKind, Result};
+use std::path::Path;
+
+use crate::eother;
+
+/// Maximum length of the name of a network interface, without the trailing NUL.
+pub const MAX_IFNAME_LEN: usize = libc::IFNAMSIZ - 1;
+/// Maximum length of the path of a unix socket, without the trailing NUL.
+pub const MAX_SOCKET_PATH_LEN: usize = 107;
+/// Maximum length of the name of the directory of a sandbox.
+pub const MAX_SANDBOX_DIR_NAME_LEN: usize = 64;
+
+// hex digits of the hash of the ids too long
+const HASH_LEN: usize = 8;
+// the file recording the id owning a directory
+const OWNER_FILE: &str = ".owner";
+
+// FNV-1a, unlike the hasher of std its output is guaranteed not to change
+fn fnv1a(data: &[u8]) -> u64 {
+    const OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+    const PRIME: u64 = 0x0100_0000_01b3;
+    data.iter().fold(OFFSET_BASIS, |hash, b| {
+        (hash ^ *b as u64).wrapping_mul(PRIME)
+    })
+}
+
+/// Get the name `prefix` + `id` + `suffix` if it's at most `max_len` bytes, otherwise the id is
+/// truncated and followed by its hash to fit.
+pub fn bounded_name(prefix: &str, id: &str, suffix: &str, max_len: usize) -> Result<String> {
+    let name = format!("{}{}{}", prefix, id, suffix);
+    if name.len() <= max_len {
+        return Ok(name);
+    }
+
+    let fixed_len = prefix.len() + HASH_LEN + suffix.len();
+    if fixed_len > max_len {
+        return Err(eother!(
+            "name {}...{} exceeds {} bytes",
+            prefix,
+            suffix,
+            max_len
+        ));
+    }
+    // cut the id on a char boundary
+    let mut keep = max_len - fixed_len;
+    while !id.is_char_boundary(keep) {
+        keep -= 1;
+    }
+    let hash = format!("{:016x}", fnv1a(id.as_bytes()));
+
+    Ok(format!(
+        "{}{}{}{}",
+        prefix,
+        &id[..keep],
+        &hash[..HASH_LEN],
+        suffix
+    ))
+}
+
+/// Get the name of the directory of the sandbox `sid`.
+pub fn sandbox_dir_name(sid: &str) -> String {
+    // the hash fits the limit whatever the id
+    bounded_name("", sid, "", MAX_SANDBOX_DIR_NAME_LEN).unwrap_or_default()
+}
+
+/// Get the name of a network interface, `prefix` + `id` + `suffix` bounded by IFNAMSIZ.
+pub fn ifname(prefix: &str, id: &str, suffix: &str) -> Result<String> {
+    bounded_name(prefix, id, suffix, MAX_IFNAME_LEN)
+}
+
+/// Check the path of a unix socket fits the address of the socket.
+pub fn check_socket_path(path: &str) -> Result<()> {
+    if path.len() > MAX_SOCKET_PATH_LEN {
+        return Err(eother!(
+            "socket path {} exceeds {} bytes",
+            path,
+            MAX_SOCKET_PATH_LEN
+        ));
+    }
+    Ok(())
+}
+
+/// Create the directory `dir` named from `id`, and record it's owned by `id`. It fails if it's
+/// owned by another id whose name clashes, the owner is kept across the restarts.
+pub fn claim_dir(dir: &Path, id: &str) -> Result<()> {
+    fs::create_dir_all(dir)?;
+
+    let owner_file = dir.join(OWNER_FILE);
+    match fs::read_to_string(&owner_file) {
+        Ok(owner) if owner == id => Ok(()),
+        Ok(owner) => Err(eother!("{} of {} is owned by {}", dir.display(), id, owner)),
+        Err(e) if e.kind() == ErrorKind::NotFound => fs::write(&owner_file, id),
+        Err(e) => Err(e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bounded_name() {
+        assert_eq!(
+            bounded_name("tap", "0", "_kata", MAX_IFNAME_LEN).unwrap(),
+            "tap0_kata"
+        );
+
+        // the ids sharing a prefix don't clash
+        let name1 = ifname("tap", "1234567890123", "_kata").unwrap();
+        let name2 = ifname("tap", "1234567890124", "_kata").unwrap();
+        assert_eq!(name1.len(), MAX_IFNAME_LEN);
+        assert_ne!(name1, name2);
+        assert!(name1.starts_with("tap") && name1.ends_with("_kata"));
+        // and stay the same
+        assert_eq!(name1, ifname("tap", "1234567890123", "_kata").unwrap());
+
+        // no room for the hash
+        assert!(bounded_name("tap", "1234567890123", "_kata", 10).is_err());
+
+        // multi-bytes chars aren't split
+        let name = bounded_name("", "ééééé", "", 11).unwrap();
+        assert!(name.starts_with('é'));
+        assert_eq!(name.len(), 2 + HASH_LEN);
+    }
+
+    #[test]
+    fn test_sandbox_dir_name() {
+        let sid = "a".repeat(MAX_SANDBOX_DIR_NAME_LEN);
+        assert_eq!(sandbox_dir_name(&sid), sid);
+
+        let long1 = format!("{}1", sid);
+        let long2 = format!("{}2", sid);
+        assert_eq!(sandbox_dir_name(&long1).len(), MAX_SANDBOX_DIR_NAME_LEN);
+        assert_ne!(sandbox_dir_name(&long1), sandbox_dir_name(&long2));
+    }
+
+    #[test]
+    fn test_check_socket_path() {
+        assert!(check_socket_path("/run/kata/sid/root/kata.hvsock").is_ok());
+        assert!(check_socket_path(&format!("/run/kata/{}", "a".repeat(100))).is_err());
+    }
+
+    #[test]
+    fn test_claim_dir() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path().join("sandbox");
+
+        claim_dir(&dir, "sid1").unwrap();
+        // claimed again after a restart
+        claim_dir(&dir, "sid1").unwrap();
+        assert!(claim_dir(&dir, "sid2").is_err());
+    }
+}
@@ -0,0 +1,186 @@
+// Copyright (c) 2023 Kata Containers community
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+//! Retry of the operations failing with transient errors.
+//!
+//! The requests to the agent go through a vsock connection and the device hotplug through the
+//! API of the VMM, either may fail with a transient error, e.g. when the connection resets or the
+//! device is busy. Those operations are retried with an exponential backoff instead of failing
+//! the pod, the other errors are returned at once.
+
+use std::future::Future;
+use std::io;
+use std::time::Duration;
+
+use anyhow::Result;
+
+use crate::sl;
+
+// the errnos of the failures which could go away once retried
+const TRANSIENT_ERRNOS: [i32; 8] = [
+    libc::EAGAIN,
+    libc::EINTR,
+    libc::EBUSY,
+    libc::ETIMEDOUT,
+    libc::ECONNRESET,
+    libc::ECONNREFUSED,
+    libc::EPIPE,
+    libc::ENOTCONN,
+];
+
+/// Retry policy with an exponential backoff.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Attempts of the operation, the first one included.
+    pub max_attempts: u32,
+    /// Backoff before the first retry, doubled before each of the next ones.
+    pub initial_backoff: Duration,
+    /// Upper bound of the backoff.
+    pub max_backoff: Duration,
+}
+
+impl RetryPolicy {
+    /// Create a new retry policy.
+    pub const fn new(max_attempts: u32, initial_backoff: Duration, max_backoff: Duration) -> Self {
+        Self {
+            max_attempts,
+            initial_backoff,
+            max_backoff,
+        }
+    }
+
+    /// The policy attempting the operation once.
+    pub const fn once() -> Self {
+        Self::new(1, Duration::ZERO, Duration::ZERO)
+    }
+
+    /// Get the backoff before the `retry`th retry, starting from 1.
+    pub fn backoff(&self, retry: u32) -> Duration {
+        let factor = 1u32
+            .checked_shl(retry.saturating_sub(1))
+            .unwrap_or(u32::MAX);
+        self.initial_backoff
+            .saturating_mul(factor)
+            .min(self.max_backoff)
+    }
+
+    /// Run the operation `name` until it succeeds, fails with an error `is_retryable` rejects, or
+    /// the attempts are exhausted. The error of the last attempt is returned.
+    pub async fn run<T, F, Fut>(
+        &self,
+        name: &str,
+        is_retryable: impl Fn(&anyhow::Error) -> bool,
+        mut op: F,
+    ) -> Result<T>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        let mut attempt = 1;
+        loop {
+            match op().await {
+                Err(e) if attempt < self.max_attempts && is_retryable(&e) => {
+                    let backoff = self.backoff(attempt);
+                    warn!(
+                        sl!(),
+                        "{} failed on attempt {}, retry in {:?}: {:?}", name, attempt, backoff, e
+                    );
+                    tokio::time::sleep(backoff).await;
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+/// Check whether the error is caused by a transient failure of the system, like a reset
+/// connection or a busy resource.
+pub fn is_transient_error(err: &anyhow::Error) -> bool {
+    err.chain().any(|cause| {
+        let errno = if let Some(e) = cause.downcast_ref::<io::Error>() {
+            e.raw_os_error()
+        } else {
+            cause.downcast_ref::<nix::Error>().map(|e| *e as i32)
+        };
+        errno.map_or(false, |errno| TRANSIENT_ERRNOS.contains(&errno))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::{anyhow, Context};
+    use std::cell::Cell;
+
+    #[test]
+    fn test_backoff() {
+        let policy = RetryPolicy::new(5, Duration::from_millis(100), Duration::from_millis(500));
+        assert_eq!(policy.backoff(1), Duration::from_millis(100));
+        assert_eq!(policy.backoff(2), Duration::from_millis(200));
+        assert_eq!(policy.backoff(3), Duration::from_millis(400));
+        assert_eq!(policy.backoff(4), Duration::from_millis(500));
+        assert_eq!(policy.backoff(40), Duration::from_millis(500));
+        assert_eq!(RetryPolicy::once().backoff(1), Duration::ZERO);
+    }
+
+    #[test]
+    fn test_is_transient_error() {
+        let err = anyhow!(io::Error::from_raw_os_error(libc::ECONNRESET)).context("send request");
+        assert!(is_transient_error(&err));
+        let err = Err::<(), _>(nix::Error::EBUSY)
+            .context("hotplug device")
+            .unwrap_err();
+        assert!(is_transient_error(&err));
+
+        assert!(!is_transient_error(&anyhow!(io::Error::from_raw_os_error(
+            libc::ENOENT
+        ))));
+        assert!(!is_transient_error(&anyhow!("invalid device")));
+    }
+
+    #[tokio::test]
+    async fn test_run() {
+        let policy = RetryPolicy::new(3, Duration::from_millis(1), Duration::from_millis(2));
+        let transient = || anyhow!(io::Error::from_raw_os_error(libc::EAGAIN));
+
+        // retried until it succeeds
+        let attempts = Cell::new(0);
+        let result = policy
+            .run("op", is_transient_error, || {
+                attempts.set(attempts.get() + 1);
+                let result = if attempts.get() < 3 {
+                    Err(transient())
+                } else {
+                    Ok(attempts.get())
+                };
+                async move { result }
+            })
+            .await;
+        assert_eq!(result.unwrap(), 3);
+
+        // the attempts are exhausted
+        attempts.set(0);
+        let result: Result<()> = policy
+            .run("op", is_transient_error, || {
+                attempts.set(attempts.get() + 1);
+                async { Err(transient()) }
+            })
+            .await;
+        assert!(result.is_err());
+        assert_eq!(attempts.get(), 3);
+
+        // the other errors are returned at once
+        attempts.set(0);
+        let result: Result<()> = policy
+            .run("op", is_transient_error, || {
+                attempts.set(attempts.get() + 1);
+                async { Err(anyhow!("invalid argument")) }
+            })
+            .await;
+        assert!(result.is_err());
+        assert_eq!(attempts.get(), 1);
+    }
+}
@@ -11,3 +11,9 @@ pub const SANDBOX: &str = "sandbox";
 pub const CONTAINER: &str = "container";
 
 pub const SANDBOX_ID_LABEL_KEY: &str = "io.kubernetes.cri-o.SandboxID";
+
+// CRI-O passes the kubelet labels of the pod and container as annotations.
+pub const SANDBOX_NAME_LABEL_KEY: &str = "io.kubernetes.pod.name";
+pub const SANDBOX_NAMESPACE_LABEL_KEY: &str = "io.kubernetes.pod.namespace";
+pub const SANDBOX_UID_LABEL_KEY: &str = "io.kubernetes.pod.uid";
+pub const CONTAINER_NAME_LABEL_KEY: &str = "io.kubernetes.container.name";
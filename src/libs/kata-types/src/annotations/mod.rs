@@ -20,7 +20,9 @@ use crate::config::hypervisor::{get_hypervisor_plugin, HugePageType};
 use crate::config::TomlConfig;
 use crate::sl;
 
-use self::cri_containerd::{SANDBOX_CPU_PERIOD_KEY, SANDBOX_CPU_QUOTA_KEY, SANDBOX_MEM_KEY};
+use self::cri_containerd::{
+    SANDBOX_CPU_PERIOD_KEY, SANDBOX_CPU_QUOTA_KEY, SANDBOX_CPU_SHARE_KEY, SANDBOX_MEM_KEY,
+};
 
 /// CRI-containerd specific annotations.
 pub mod cri_containerd;
@@ -59,6 +61,45 @@ pub const KATA_ANNO_CONTAINER_RES_SWAPPINESS: &str =
 /// A container annotation to specify the Resources.Memory.Swap.
 pub const KATA_ANNO_CONTAINER_RES_SWAP_IN_BYTES: &str =
     "io.katacontainers.container.resource.swap_in_bytes";
+/// A container annotation to limit the size of the container writable layer living in the guest,
+/// e.g. block device backed rootfs, in bytes.
+pub const KATA_ANNO_CONTAINER_RES_DISK_QUOTA_IN_BYTES: &str =
+    "io.katacontainers.container.resource.disk_quota_in_bytes";
+
+// Container rootfs related annotations
+/// A container annotation to place the container writable layer in a guest tmpfs of the given
+/// size in bytes, instead of the rootfs shared from the host.
+///
+/// The pages of the tmpfs are charged to the memory cgroup of the container, so its content
+/// counts against the memory limit of the container, which must not be smaller than the size.
+pub const KATA_ANNO_CONTAINER_ROOTFS_TMPFS_SIZE_IN_BYTES: &str =
+    "io.katacontainers.container.rootfs.tmpfs_size_in_bytes";
+
+// Container volume related annotations
+/// A container annotation to specify the host cache mode of hot-attached block volumes.
+///
+/// Comma separated list of `<mount destination>=<cache mode>`, where cache mode is one of
+/// `writeback` (use the host page cache) or `none` (bypass the host page cache).
+pub const KATA_ANNO_CONTAINER_VOLUME_BLOCK_CACHE_MODE: &str =
+    "io.katacontainers.container.volume.block_cache_mode";
+/// A container annotation to attach hot-attached block volumes as read-only devices.
+///
+/// Comma separated list of mount destinations.
+pub const KATA_ANNO_CONTAINER_VOLUME_BLOCK_READONLY: &str =
+    "io.katacontainers.container.volume.block_readonly";
+/// A container annotation to bypass the shared file system for volumes of host directories.
+///
+/// Comma separated list of mount destinations. The directory is converted into a disk image
+/// attached as a block device, so the volume must be read-only.
+pub const KATA_ANNO_CONTAINER_VOLUME_SHARE_FS_BYPASS: &str =
+    "io.katacontainers.container.volume.share_fs_bypass";
+
+// Container exec related annotations
+/// A container annotation to limit the run time of the exec processes of the container, in seconds.
+///
+/// The exec processes still running at the deadline are killed and reported as deadline exceeded.
+pub const KATA_ANNO_CONTAINER_EXEC_TIMEOUT_SECS: &str =
+    "io.katacontainers.container.exec.timeout_secs";
 
 // Agent related annotations
 /// Prefix for Agent configurations.
@@ -80,6 +121,16 @@ pub const KATA_ANNO_CFG_AGENT_TRACE: &str = "io.katacontainers.config.agent.enab
 /// An annotation to specify the size of the pipes created for containers.
 pub const KATA_ANNO_CFG_AGENT_CONTAINER_PIPE_SIZE: &str =
     "io.katacontainers.config.agent.container_pipe_size";
+/// A sandbox annotation to enable image signature verification in guest.
+pub const KATA_ANNO_CFG_AGENT_ENABLE_SIGNATURE_VERIFICATION: &str =
+    "io.katacontainers.config.agent.enable_signature_verification";
+/// A sandbox annotation to specify the image security policy used for signature verification.
+pub const KATA_ANNO_CFG_AGENT_IMAGE_POLICY_FILE: &str =
+    "io.katacontainers.config.agent.image_policy_file";
+/// A sandbox annotation to resolve the DNS lookups of the sandbox through a caching forwarder in
+/// guest.
+pub const KATA_ANNO_CFG_AGENT_ENABLE_DNS_CACHE: &str =
+    "io.katacontainers.config.agent.enable_dns_cache";
 /// An annotation key to specify the size of the pipes created for containers.
 pub const CONTAINER_PIPE_SIZE_KERNEL_PARAM: &str = "agent.container_pipe_size";
 
@@ -118,6 +169,9 @@ pub const SHA512: &str = "sha512";
 /// Specify the driver to be used for block device either VirtioSCSI or VirtioBlock
 pub const KATA_ANNO_CFG_HYPERVISOR_BLOCK_DEV_DRIVER: &str =
     "io.katacontainers.config.hypervisor.block_device_driver";
+/// A sandbox annotation that specifies the asynchronous IO mode of block devices: threads, native or io_uring.
+pub const KATA_ANNO_CFG_HYPERVISOR_BLOCK_DEV_AIO: &str =
+    "io.katacontainers.config.hypervisor.block_device_aio";
 /// A sandbox annotation that disallows a block device from being used.
 pub const KATA_ANNO_CFG_HYPERVISOR_DISABLE_BLOCK_DEV_USE: &str =
     "io.katacontainers.config.hypervisor.disable_block_device_use";
@@ -166,6 +220,10 @@ pub const KATA_ANNO_CFG_HYPERVISOR_IMAGE_HASH: &str =
 /// A sandbox annotation for passing a per container path pointing at the initrd that will run
 /// in the container VM.
 pub const KATA_ANNO_CFG_HYPERVISOR_INITRD_PATH: &str = "io.katacontainers.config.hypervisor.initrd";
+/// A sandbox annotation for selecting one of the guest kernel variants configured for the
+/// hypervisor, e.g. "rt" or "nvidia".
+pub const KATA_ANNO_CFG_HYPERVISOR_KERNEL_VARIANT: &str =
+    "io.katacontainers.config.hypervisor.kernel_variant";
 /// A sandbox annotation for passing a container guest initrd SHA-512 hash value.
 pub const KATA_ANNO_CFG_HYPERVISOR_INITRD_HASH: &str =
     "io.katacontainers.config.hypervisor.initrd_hash";
@@ -181,12 +239,30 @@ pub const KATA_ANNO_CFG_HYPERVISOR_FIRMWARE_HASH: &str =
 /// A sandbox annotation to specify cpu specific features.
 pub const KATA_ANNO_CFG_HYPERVISOR_CPU_FEATURES: &str =
     "io.katacontainers.config.hypervisor.cpu_features";
+/// A sandbox annotation to specify the cpu model of the guest.
+pub const KATA_ANNO_CFG_HYPERVISOR_CPU_MODEL: &str =
+    "io.katacontainers.config.hypervisor.cpu_model";
 /// A sandbox annotation for passing the default vCPUs assigned for a VM by the hypervisor.
 pub const KATA_ANNO_CFG_HYPERVISOR_DEFAULT_VCPUS: &str =
     "io.katacontainers.config.hypervisor.default_vcpus";
 /// A sandbox annotation that specifies the maximum number of vCPUs allocated for the VM by the hypervisor.
 pub const KATA_ANNO_CFG_HYPERVISOR_DEFAULT_MAX_VCPUS: &str =
     "io.katacontainers.config.hypervisor.default_max_vcpus";
+/// A sandbox annotation to specify the real-time SCHED_FIFO priority of the vCPU threads.
+pub const KATA_ANNO_CFG_HYPERVISOR_VCPU_RT_PRIORITY: &str =
+    "io.katacontainers.config.hypervisor.vcpu_rt_priority";
+/// A sandbox annotation to specify the host CPUs dedicated to the real-time vCPU threads.
+pub const KATA_ANNO_CFG_HYPERVISOR_VCPU_RT_CPUS: &str =
+    "io.katacontainers.config.hypervisor.vcpu_rt_cpus";
+/// A sandbox annotation to specify the placement policy of the vCPU threads on the host CPUs.
+pub const KATA_ANNO_CFG_HYPERVISOR_VCPU_AFFINITY_POLICY: &str =
+    "io.katacontainers.config.hypervisor.vcpu_affinity_policy";
+/// A sandbox annotation to specify the host CPUs of the vCPU threads.
+pub const KATA_ANNO_CFG_HYPERVISOR_VCPU_AFFINITY_CPUS: &str =
+    "io.katacontainers.config.hypervisor.vcpu_affinity_cpus";
+/// A sandbox annotation to specify the host NUMA nodes of the vCPU threads.
+pub const KATA_ANNO_CFG_HYPERVISOR_VCPU_AFFINITY_NUMA_NODES: &str =
+    "io.katacontainers.config.hypervisor.vcpu_affinity_numa_nodes";
 
 // Hypervisor Device related annotations
 /// A sandbox annotation used to indicate if devices need to be hotplugged on the root bus instead
@@ -275,12 +351,35 @@ pub const KATA_ANNO_CFG_HYPERVISOR_VIRTIO_FS_CACHE: &str =
 /// A sandbox annotation to specify the DAX cache size in MiB.
 pub const KATA_ANNO_CFG_HYPERVISOR_VIRTIO_FS_CACHE_SIZE: &str =
     "io.katacontainers.config.hypervisor.virtio_fs_cache_size";
+/// A sandbox annotation to enable the virtio-fs DAX window, of the default size unless set by
+/// the DAX cache size annotation.
+pub const KATA_ANNO_CFG_HYPERVISOR_VIRTIO_FS_IS_DAX: &str =
+    "io.katacontainers.config.hypervisor.virtio_fs_is_dax";
+/// A sandbox annotation to specify the size of the thread pool of the virtio-fs daemon.
+pub const KATA_ANNO_CFG_HYPERVISOR_VIRTIO_FS_THREAD_POOL_SIZE: &str =
+    "io.katacontainers.config.hypervisor.virtio_fs_thread_pool_size";
 /// A sandbox annotation to pass options to virtiofsd daemon.
 pub const KATA_ANNO_CFG_HYPERVISOR_VIRTIO_FS_EXTRA_ARGS: &str =
     "io.katacontainers.config.hypervisor.virtio_fs_extra_args";
 /// A sandbox annotation to specify as the msize for 9p shares.
 pub const KATA_ANNO_CFG_HYPERVISOR_MSIZE_9P: &str = "io.katacontainers.config.hypervisor.msize_9p";
 
+// Hypervisor sizing related annotations
+/// A sandbox annotation to select a VM sizing profile, e.g. "small", "medium" or "large", which
+/// sets the vcpus, the memory, the virtio-fs cache and the queues together.
+pub const KATA_ANNO_CFG_HYPERVISOR_SIZING_PROFILE: &str =
+    "io.katacontainers.config.hypervisor.sizing_profile";
+
+// Hypervisor hardening related annotations
+/// A sandbox annotation to apply the hardened guest preset, the preset enabled by the
+/// configuration can't be disabled by the annotation.
+pub const KATA_ANNO_CFG_HYPERVISOR_HARDENED_GUEST: &str =
+    "io.katacontainers.config.hypervisor.hardened_guest";
+/// A sandbox annotation for a comma separated list of the kernel modules which can't be loaded
+/// in the guest, on top of the ones of the configuration.
+pub const KATA_ANNO_CFG_HYPERVISOR_KERNEL_MODULES_BLACKLIST: &str =
+    "io.katacontainers.config.hypervisor.kernel_modules_blacklist";
+
 // Runtime related annotations
 /// Prefix for Runtime configurations.
 pub const KATA_ANNO_CFG_RUNTIME_PREFIX: &str = "io.katacontainers.config.runtime.";
@@ -323,6 +422,10 @@ pub const KATA_ANNO_CFG_HYPERVISOR_PREFETCH_FILES_LIST: &str =
 pub const KATA_ANNO_CFG_SANDBOX_BIND_MOUNTS: &str =
     "io.katacontainers.config.runtime.sandbox_bind_mounts";
 
+/// A sandbox annotation to limit the cache and the memory bandwidth of the vCPUs by Intel RDT,
+/// the resctrl schemata lines are separated by `\n`, e.g. "L3:0=ff;1=ff\nMB:0=50".
+pub const KATA_ANNO_CFG_RDT_SCHEMATA: &str = "io.katacontainers.config.runtime.rdt_schemata";
+
 /// A helper structure to query configuration information by check annotations.
 #[derive(Debug, Default, Deserialize)]
 pub struct Annotation {
@@ -413,6 +516,14 @@ impl Annotation {
         value.unwrap_or(0)
     }
 
+    /// Get the annotation of cpu shares for sandbox
+    pub fn get_sandbox_cpu_shares(&self) -> u64 {
+        let value = self
+            .get_value::<u64>(SANDBOX_CPU_SHARE_KEY)
+            .unwrap_or(Some(0));
+        value.unwrap_or(0)
+    }
+
     /// Get the annotation of memory for sandbox
     pub fn get_sandbox_mem(&self) -> i64 {
         let value = self.get_value::<i64>(SANDBOX_MEM_KEY).unwrap_or(Some(0));
@@ -479,6 +590,21 @@ impl Annotation {
         let i32_err = io::Error::new(io::ErrorKind::InvalidData, "parse i32 error".to_string());
         let hv = config.hypervisor.get_mut(hypervisor_name).unwrap();
         let ag = config.agent.get_mut(agent_name).unwrap();
+
+        // the sizing profile is applied first, so that the annotations of the single knobs
+        // override it
+        if let Some(profile) = self.get(KATA_ANNO_CFG_HYPERVISOR_SIZING_PROFILE) {
+            if hv
+                .security_info
+                .is_annotation_enabled(KATA_ANNO_CFG_HYPERVISOR_SIZING_PROFILE)
+            {
+                let min_memory = get_hypervisor_plugin(hypervisor_name)
+                    .map(|plugin| plugin.get_min_memory())
+                    .unwrap_or_default();
+                hv.apply_sizing_profile(&profile, min_memory)?;
+            }
+        }
+
         for (key, value) in &self.annotations {
             if hv.security_info.is_annotation_enabled(key) {
                 match key.as_str() {
@@ -510,6 +636,9 @@ impl Annotation {
                     KATA_ANNO_CFG_HYPERVISOR_BLOCK_DEV_DRIVER => {
                         hv.blockdev_info.block_device_driver = value.to_string();
                     }
+                    KATA_ANNO_CFG_HYPERVISOR_BLOCK_DEV_AIO => {
+                        hv.blockdev_info.block_device_aio = value.to_string();
+                    }
                     KATA_ANNO_CFG_HYPERVISOR_DISABLE_BLOCK_DEV_USE => {
                         match self.get_value::<bool>(key) {
                             Ok(r) => {
@@ -602,10 +731,16 @@ impl Annotation {
                         hv.boot_info.validate_boot_path(value)?;
                         hv.boot_info.firmware = value.to_string();
                     }
+                    KATA_ANNO_CFG_HYPERVISOR_KERNEL_VARIANT => {
+                        hv.boot_info.select_kernel_variant(value)?;
+                    }
                     // Hypervisor CPU related annotations
                     KATA_ANNO_CFG_HYPERVISOR_CPU_FEATURES => {
                         hv.cpu_info.cpu_features = value.to_string();
                     }
+                    KATA_ANNO_CFG_HYPERVISOR_CPU_MODEL => {
+                        hv.cpu_info.cpu_model = value.to_string();
+                    }
                     KATA_ANNO_CFG_HYPERVISOR_DEFAULT_VCPUS => match self.get_value::<i32>(key) {
                         Ok(num_cpus) => {
                             let num_cpus = num_cpus.unwrap_or_default();
@@ -642,6 +777,26 @@ impl Annotation {
                             }
                         }
                     }
+                    KATA_ANNO_CFG_HYPERVISOR_VCPU_RT_PRIORITY => match self.get_value::<u32>(key) {
+                        Ok(r) => {
+                            hv.cpu_info.vcpu_rt_priority = r.unwrap_or_default();
+                        }
+                        Err(_e) => {
+                            return Err(u32_err);
+                        }
+                    },
+                    KATA_ANNO_CFG_HYPERVISOR_VCPU_RT_CPUS => {
+                        hv.cpu_info.vcpu_rt_cpus = value.to_string();
+                    }
+                    KATA_ANNO_CFG_HYPERVISOR_VCPU_AFFINITY_POLICY => {
+                        hv.vcpu_affinity.policy = value.trim().to_string();
+                    }
+                    KATA_ANNO_CFG_HYPERVISOR_VCPU_AFFINITY_CPUS => {
+                        hv.vcpu_affinity.cpus = value.trim().to_string();
+                    }
+                    KATA_ANNO_CFG_HYPERVISOR_VCPU_AFFINITY_NUMA_NODES => {
+                        hv.vcpu_affinity.numa_nodes = value.trim().to_string();
+                    }
                     // Hypervisor Device related annotations
                     KATA_ANNO_CFG_HYPERVISOR_HOTPLUG_VFIO_ON_ROOT_BUS => {
                         match self.get_value::<bool>(key) {
@@ -857,6 +1012,32 @@ impl Annotation {
                         match self.get_value::<u32>(key) {
                             Ok(r) => {
                                 hv.shared_fs.virtio_fs_cache_size = r.unwrap_or_default();
+                                // a window of size 0 is disabled
+                                hv.shared_fs.virtio_fs_is_dax =
+                                    hv.shared_fs.virtio_fs_cache_size != 0;
+                            }
+                            Err(_e) => {
+                                return Err(u32_err);
+                            }
+                        }
+                    }
+                    KATA_ANNO_CFG_HYPERVISOR_VIRTIO_FS_IS_DAX => {
+                        match self.get_value::<bool>(key) {
+                            Ok(r) => {
+                                hv.shared_fs.virtio_fs_is_dax = r.unwrap_or_default();
+                                if !hv.shared_fs.virtio_fs_is_dax {
+                                    hv.shared_fs.virtio_fs_cache_size = 0;
+                                }
+                            }
+                            Err(_e) => {
+                                return Err(bool_err);
+                            }
+                        }
+                    }
+                    KATA_ANNO_CFG_HYPERVISOR_VIRTIO_FS_THREAD_POOL_SIZE => {
+                        match self.get_value::<u32>(key) {
+                            Ok(r) => {
+                                hv.shared_fs.virtio_fs_thread_pool_size = r.unwrap_or_default();
                             }
                             Err(_e) => {
                                 return Err(u32_err);
@@ -878,6 +1059,25 @@ impl Annotation {
                             return Err(u32_err);
                         }
                     },
+                    // applied before the other annotations
+                    KATA_ANNO_CFG_HYPERVISOR_SIZING_PROFILE => {}
+                    KATA_ANNO_CFG_HYPERVISOR_HARDENED_GUEST => match self.get_value::<bool>(key) {
+                        Ok(r) => {
+                            if r.unwrap_or_default() {
+                                hv.security_info.hardened_guest = true;
+                            }
+                        }
+                        Err(_e) => {
+                            return Err(bool_err);
+                        }
+                    },
+                    KATA_ANNO_CFG_HYPERVISOR_KERNEL_MODULES_BLACKLIST => {
+                        for module in value.split(',').map(str::trim).filter(|m| !m.is_empty()) {
+                            hv.security_info
+                                .kernel_modules_blacklist
+                                .push(module.to_string());
+                        }
+                    }
 
                     _ => {
                         return Err(io::Error::new(
@@ -912,6 +1112,27 @@ impl Annotation {
                             return Err(u32_err);
                         }
                     },
+                    KATA_ANNO_CFG_AGENT_ENABLE_SIGNATURE_VERIFICATION => {
+                        match self.get_value::<bool>(key) {
+                            Ok(r) => {
+                                ag.enable_signature_verification = r.unwrap_or_default();
+                            }
+                            Err(_e) => {
+                                return Err(bool_err);
+                            }
+                        }
+                    }
+                    KATA_ANNO_CFG_AGENT_IMAGE_POLICY_FILE => {
+                        ag.image_policy_file = value.to_string();
+                    }
+                    KATA_ANNO_CFG_AGENT_ENABLE_DNS_CACHE => match self.get_value::<bool>(key) {
+                        Ok(r) => {
+                            ag.enable_dns_cache = r.unwrap_or_default();
+                        }
+                        Err(_e) => {
+                            return Err(bool_err);
+                        }
+                    },
                     // update runtime config
                     KATA_ANNO_CFG_RUNTIME_NAME => {
                         let runtime = vec!["virt-container", "linux-container", "wasm-container"];
@@ -982,12 +1203,25 @@ impl Annotation {
                             config.runtime.sandbox_bind_mounts.push(arg.to_string());
                         }
                     }
+                    KATA_ANNO_CFG_RDT_SCHEMATA => {
+                        config.runtime.rdt_schemata = value.replace("\\n", "\n");
+                    }
                     _ => {
                         warn!(sl!(), "Annotation {} not enabled", key);
                     }
                 }
             }
         }
+
+        // the DAX window enabled by the annotation gets the default size
+        hv.shared_fs.adjust_dax();
+
+        // the hardened guest preset is applied last, so that the annotations can't loosen it
+        if hv.security_info.hardened_guest {
+            hv.apply_hardened_guest();
+            ag.debug_console_enabled = false;
+            config.runtime.disable_guest_seccomp = false;
+        }
         Ok(())
     }
 }
@@ -17,6 +17,10 @@ pub enum CapabilityBits {
     MultiQueueSupport,
     /// hypervisor supports filesystem share
     FsSharingSupport,
+    /// hypervisor supports virtio-scsi block device
+    BlockDeviceScsiSupport,
+    /// hypervisor supports nvme block device
+    BlockDeviceNvmeSupport,
 }
 
 /// Capabilities describe a virtcontainers hypervisor capabilities through a bit mask.
@@ -64,6 +68,16 @@ impl Capabilities {
     pub fn is_fs_sharing_supported(&self) -> bool {
         self.flags.and(CapabilityBits::FsSharingSupport) != 0
     }
+
+    /// is_scsi_block_device_supported tells if an hypervisor supports virtio-scsi block devices.
+    pub fn is_scsi_block_device_supported(&self) -> bool {
+        self.flags.and(CapabilityBits::BlockDeviceScsiSupport) != 0
+    }
+
+    /// is_nvme_block_device_supported tells if an hypervisor supports nvme block devices.
+    pub fn is_nvme_block_device_supported(&self) -> bool {
+        self.flags.and(CapabilityBits::BlockDeviceNvmeSupport) != 0
+    }
 }
 
 #[cfg(test)]
@@ -102,6 +116,18 @@ mod tests {
                 | CapabilityBits::MultiQueueSupport
                 | CapabilityBits::FsSharingSupport,
         );
-        assert!(cap.is_fs_sharing_supported())
+        assert!(cap.is_fs_sharing_supported());
+        assert!(!cap.is_scsi_block_device_supported());
+        assert!(!cap.is_nvme_block_device_supported());
+
+        // test set scsi and nvme block device support
+        cap.set(
+            CapabilityBits::BlockDeviceSupport
+                | CapabilityBits::BlockDeviceScsiSupport
+                | CapabilityBits::BlockDeviceNvmeSupport,
+        );
+        assert!(cap.is_scsi_block_device_supported());
+        assert!(cap.is_nvme_block_device_supported());
+        assert!(!cap.is_fs_sharing_supported());
     }
 }
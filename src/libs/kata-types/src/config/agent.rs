@@ -10,7 +10,8 @@ use crate::config::{ConfigOps, TomlConfig};
 pub use vendor::AgentVendor;
 
 use super::default::{
-    DEFAULT_AGENT_DIAL_TIMEOUT_MS, DEFAULT_AGENT_LOG_PORT, DEFAULT_AGENT_VSOCK_PORT,
+    DEFAULT_AGENT_DBG_CONSOLE_PORT, DEFAULT_AGENT_DIAL_TIMEOUT_MS, DEFAULT_AGENT_LOG_PORT,
+    DEFAULT_AGENT_VSOCK_PORT,
 };
 use crate::eother;
 
@@ -82,6 +83,45 @@ pub struct Agent {
     /// container pipe size
     #[serde(default)]
     pub container_pipe_size: u32,
+
+    /// If enabled, the guest image stack verifies the signatures of pulled images against the
+    /// image security policy, and refuses to create containers from images failing verification.
+    #[serde(default)]
+    pub enable_signature_verification: bool,
+
+    /// URI of the image security policy used for signature verification, e.g. a file path in
+    /// the guest or `kbs:///default/security-policy/test`.
+    #[serde(default)]
+    pub image_policy_file: String,
+
+    /// If enabled, the DNS lookups of the sandbox are resolved through a caching forwarder in the
+    /// guest, which falls back across the nameservers of the pod. It reduces the resolution
+    /// latency of the pods with a distant cluster DNS.
+    #[serde(default)]
+    pub enable_dns_cache: bool,
+
+    /// TCP ports of the metrics exporters listening on the loopback of the guest, e.g. 9400 for
+    /// the DCGM exporter of the GPUs. The agent proxies each one from the vsock port of the same
+    /// number, so that the host scrapes them through the shim management socket.
+    #[serde(default)]
+    pub guest_metrics_ports: Vec<u32>,
+
+    /// Commands run in the guest, out of the containers, before the VM is stopped, e.g. to
+    /// flush the databases or to deregister from the service mesh running in the VM. They're run
+    /// in order, and their failures are logged without failing the stop.
+    #[serde(default)]
+    pub guest_pre_stop_hooks: Vec<GuestHook>,
+}
+
+/// A command run in the guest by the agent.
+#[derive(Debug, Default, Deserialize, Serialize, Clone, PartialEq)]
+pub struct GuestHook {
+    /// The absolute path of the command in the guest, and its arguments.
+    pub args: Vec<String>,
+
+    /// Timeout in seconds after which the command is killed, 0 for no timeout.
+    #[serde(default)]
+    pub timeout: u32,
 }
 
 impl std::default::Default for Agent {
@@ -98,6 +138,11 @@ impl std::default::Default for Agent {
             health_check_request_timeout_ms: 90_000,
             kernel_modules: Default::default(),
             container_pipe_size: 0,
+            enable_signature_verification: false,
+            image_policy_file: String::new(),
+            enable_dns_cache: false,
+            guest_metrics_ports: vec![],
+            guest_pre_stop_hooks: vec![],
         }
     }
 }
@@ -136,6 +181,54 @@ impl Agent {
             return Err(eother!("dial_timeout_ms couldn't be 0."));
         }
 
+        // all the vsock ports are served by the agent on the same guest cid
+        if self.server_port == self.log_port
+            || self.server_port == DEFAULT_AGENT_DBG_CONSOLE_PORT
+            || self.log_port == DEFAULT_AGENT_DBG_CONSOLE_PORT
+        {
+            return Err(eother!(
+                "server_port {} and log_port {} couldn't be the same or {}.",
+                self.server_port,
+                self.log_port,
+                DEFAULT_AGENT_DBG_CONSOLE_PORT
+            ));
+        }
+
+        // the exporter ports are proxied from the vsock ports of the same number
+        for (i, port) in self.guest_metrics_ports.iter().enumerate() {
+            if *port == 0 || *port > u16::MAX as u32 {
+                return Err(eother!("guest_metrics_ports {} is not a TCP port.", port));
+            }
+            if *port == self.server_port
+                || *port == self.log_port
+                || *port == DEFAULT_AGENT_DBG_CONSOLE_PORT
+                || self.guest_metrics_ports[..i].contains(port)
+            {
+                return Err(eother!(
+                    "guest_metrics_ports {} is already used by the agent.",
+                    port
+                ));
+            }
+        }
+
+        // the policy is passed by kernel command line, which is split by whitespace
+        if self.image_policy_file.contains(char::is_whitespace) {
+            return Err(eother!(
+                "image_policy_file `{}` couldn't contain whitespace.",
+                self.image_policy_file
+            ));
+        }
+
+        // the commands are run without a shell nor a PATH lookup
+        for hook in self.guest_pre_stop_hooks.iter() {
+            if !hook.args.first().map_or(false, |p| p.starts_with('/')) {
+                return Err(eother!(
+                    "guest_pre_stop_hooks {:?} must start with an absolute path.",
+                    hook.args
+                ));
+            }
+        }
+
         Ok(())
     }
 }
@@ -169,3 +262,61 @@ mod vendor {
 #[cfg(feature = "enable-vendor")]
 #[path = "agent_vendor.rs"]
 mod vendor;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_guest_pre_stop_hooks() {
+        let content = r#"
+[agent.kata]
+[[agent.kata.guest_pre_stop_hooks]]
+args = ["/usr/bin/pg_ctl", "stop", "-m", "fast"]
+timeout = 20
+[[agent.kata.guest_pre_stop_hooks]]
+args = ["/usr/local/bin/deregister"]
+"#;
+        let config = TomlConfig::load(content).unwrap();
+        config.validate().unwrap();
+        let hooks = &config.agent[AGENT_NAME_KATA].guest_pre_stop_hooks;
+        assert_eq!(hooks.len(), 2);
+        assert_eq!(hooks[0].args[0], "/usr/bin/pg_ctl");
+        assert_eq!(hooks[0].timeout, 20);
+        assert_eq!(hooks[1].timeout, 0);
+
+        // the commands aren't looked up in the PATH
+        let content = r#"
+[agent.kata]
+[[agent.kata.guest_pre_stop_hooks]]
+args = ["sync"]
+"#;
+        let config = TomlConfig::load(content).unwrap();
+        config.validate().unwrap_err();
+    }
+
+    #[test]
+    fn test_guest_metrics_ports() {
+        let agent = Agent {
+            guest_metrics_ports: vec![9400, 9100],
+            ..Default::default()
+        };
+        agent.validate().unwrap();
+
+        for ports in [
+            vec![0],
+            vec![70000],
+            vec![9400, 9400],
+            vec![DEFAULT_AGENT_VSOCK_PORT],
+            vec![DEFAULT_AGENT_DBG_CONSOLE_PORT],
+        ]
+        .iter()
+        {
+            let agent = Agent {
+                guest_metrics_ports: ports.clone(),
+                ..Default::default()
+            };
+            agent.validate().unwrap_err();
+        }
+    }
+}
@@ -32,7 +32,14 @@ pub const DEFAULT_HYPERVISOR: &str = HYPERVISOR_NAME_DRAGONBALL;
 
 pub const DEFAULT_INTERNETWORKING_MODEL: &str = "tcfilter";
 
+pub const DEFAULT_GUEST_IMAGE_CACHE_DISK_SIZE_MB: u32 = 10 * 1024;
+
+pub const DEFAULT_PROCESS_OUTPUT_BUFFER_KB: u32 = 1024;
+
+pub const DEFAULT_SENSITIVE_HOST_PATHS: &[&str] = &["/dev", "/sys", "/proc", "/boot"];
+
 pub const DEFAULT_BLOCK_DEVICE_TYPE: &str = "virtio-blk-pci";
+pub const DEFAULT_BLOCK_DEVICE_AIO: &str = "threads";
 pub const DEFAULT_VHOST_USER_STORE_PATH: &str = "/var/run/vhost-user";
 pub const DEFAULT_BLOCK_NVDIMM_MEM_OFFSET: u64 = 0;
 
@@ -85,3 +92,24 @@ pub const DEFAULT_CH_PCI_BRIDGES: u32 = 2;
 pub const MAX_CH_PCI_BRIDGES: u32 = 5;
 pub const MAX_CH_VCPUS: u32 = 256;
 pub const MIN_CH_MEMORY_SIZE_MB: u32 = 64;
+
+// Default configuration for Firecracker
+pub const DEFAULT_FIRECRACKER_BINARY_PATH: &str = "/usr/bin/firecracker";
+pub const DEFAULT_FIRECRACKER_ROOTFS_TYPE: &str = "ext4";
+pub const DEFAULT_FIRECRACKER_ENTROPY_SOURCE: &str = "/dev/urandom";
+pub const DEFAULT_FIRECRACKER_GUEST_KERNEL_IMAGE: &str = "vmlinux";
+pub const DEFAULT_FIRECRACKER_GUEST_KERNEL_PARAMS: &str = "";
+pub const DEFAULT_FIRECRACKER_MEMORY_SIZE_MB: u32 = 128;
+pub const MAX_FIRECRACKER_VCPUS: u32 = 32;
+pub const MIN_FIRECRACKER_MEMORY_SIZE_MB: u32 = 64;
+
+// Default configuration for StratoVirt
+pub const DEFAULT_STRATOVIRT_BINARY_PATH: &str = "/usr/bin/stratovirt";
+pub const DEFAULT_STRATOVIRT_MACHINE_TYPE: &str = "microvm";
+pub const DEFAULT_STRATOVIRT_ROOTFS_TYPE: &str = "ext4";
+pub const DEFAULT_STRATOVIRT_ENTROPY_SOURCE: &str = "/dev/urandom";
+pub const DEFAULT_STRATOVIRT_GUEST_KERNEL_IMAGE: &str = "vmlinux";
+pub const DEFAULT_STRATOVIRT_GUEST_KERNEL_PARAMS: &str = "";
+pub const DEFAULT_STRATOVIRT_MEMORY_SIZE_MB: u32 = 128;
+pub const MAX_STRATOVIRT_VCPUS: u32 = 254;
+pub const MIN_STRATOVIRT_MEMORY_SIZE_MB: u32 = 64;
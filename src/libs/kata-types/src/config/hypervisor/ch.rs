@@ -13,7 +13,7 @@ use super::{default, register_hypervisor_plugin};
 use crate::config::default::MAX_CH_VCPUS;
 use crate::config::default::MIN_CH_MEMORY_SIZE_MB;
 
-use crate::config::hypervisor::VIRTIO_BLK_MMIO;
+use crate::config::hypervisor::{NVME, VIRTIO_BLK_MMIO, VIRTIO_SCSI};
 use crate::config::{ConfigPlugin, TomlConfig};
 use crate::{eother, resolve_path, validate_path};
 
@@ -109,6 +109,16 @@ impl ConfigPlugin for CloudHypervisorConfig {
                 return Err(eother!("CH doesn't support virtio-blk-mmio"));
             }
 
+            if !ch.blockdev_info.disable_block_device_use
+                && (ch.blockdev_info.block_device_driver == VIRTIO_SCSI
+                    || ch.blockdev_info.block_device_driver == NVME)
+            {
+                return Err(eother!(
+                    "CH doesn't support {}",
+                    ch.blockdev_info.block_device_driver
+                ));
+            }
+
             if ch.boot_info.kernel.is_empty() {
                 return Err(eother!("Guest kernel image for CH is empty"));
             }
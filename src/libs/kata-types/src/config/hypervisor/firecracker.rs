@@ -0,0 +1,179 @@
+// Copyright (c) 2019-2021 Alibaba Cloud
+// Copyright (c) 2023 Kata Containers community
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+use std::io::Result;
+use std::path::Path;
+use std::sync::Arc;
+
+use super::{default, register_hypervisor_plugin};
+
+use crate::config::default::MAX_FIRECRACKER_VCPUS;
+use crate::config::default::MIN_FIRECRACKER_MEMORY_SIZE_MB;
+
+use crate::config::hypervisor::VIRTIO_BLK_MMIO;
+use crate::config::{ConfigPlugin, TomlConfig};
+use crate::{eother, resolve_path, validate_path};
+
+/// Hypervisor name for Firecracker, used to index `TomlConfig::hypervisor`.
+pub const HYPERVISOR_NAME_FIRECRACKER: &str = "firecracker";
+
+/// Configuration information for Firecracker.
+#[derive(Default, Debug)]
+pub struct FirecrackerConfig {}
+
+impl FirecrackerConfig {
+    /// Create a new instance of `FirecrackerConfig`.
+    pub fn new() -> Self {
+        FirecrackerConfig {}
+    }
+
+    /// Register the Firecracker plugin.
+    pub fn register(self) {
+        let plugin = Arc::new(self);
+        register_hypervisor_plugin(HYPERVISOR_NAME_FIRECRACKER, plugin);
+    }
+}
+
+impl ConfigPlugin for FirecrackerConfig {
+    fn get_max_cpus(&self) -> u32 {
+        MAX_FIRECRACKER_VCPUS
+    }
+
+    fn get_min_memory(&self) -> u32 {
+        MIN_FIRECRACKER_MEMORY_SIZE_MB
+    }
+
+    fn name(&self) -> &str {
+        HYPERVISOR_NAME_FIRECRACKER
+    }
+
+    /// Adjust the configuration information after loading from configuration file.
+    fn adjust_config(&self, conf: &mut TomlConfig) -> Result<()> {
+        if let Some(fc) = conf.hypervisor.get_mut(HYPERVISOR_NAME_FIRECRACKER) {
+            if fc.path.is_empty() {
+                fc.path = default::DEFAULT_FIRECRACKER_BINARY_PATH.to_string();
+            }
+            resolve_path!(fc.path, "Firecracker binary path `{}` is invalid: {}")?;
+            resolve_path!(
+                fc.jailer_path,
+                "Firecracker jailer path `{}` is invalid: {}"
+            )?;
+
+            if fc.boot_info.kernel.is_empty() {
+                fc.boot_info.kernel = default::DEFAULT_FIRECRACKER_GUEST_KERNEL_IMAGE.to_string();
+            }
+            if fc.boot_info.kernel_params.is_empty() {
+                fc.boot_info.kernel_params =
+                    default::DEFAULT_FIRECRACKER_GUEST_KERNEL_PARAMS.to_string();
+            }
+            if fc.boot_info.rootfs_type.is_empty() {
+                fc.boot_info.rootfs_type = default::DEFAULT_FIRECRACKER_ROOTFS_TYPE.to_string();
+            }
+
+            // the devices are virtio-mmio ones, the block devices too
+            if fc.blockdev_info.block_device_driver.is_empty() {
+                fc.blockdev_info.block_device_driver = VIRTIO_BLK_MMIO.to_string();
+            }
+
+            // Firecracker can't hot plug vCPUs, the VM is booted with all of them
+            if fc.cpu_info.default_maxvcpus > MAX_FIRECRACKER_VCPUS {
+                fc.cpu_info.default_maxvcpus = MAX_FIRECRACKER_VCPUS;
+            }
+            if fc.cpu_info.default_vcpus as u32 > fc.cpu_info.default_maxvcpus {
+                fc.cpu_info.default_vcpus = fc.cpu_info.default_maxvcpus as i32;
+            }
+
+            if fc.machine_info.entropy_source.is_empty() {
+                fc.machine_info.entropy_source =
+                    default::DEFAULT_FIRECRACKER_ENTROPY_SOURCE.to_string();
+            }
+
+            if fc.memory_info.default_memory == 0 {
+                fc.memory_info.default_memory = default::DEFAULT_FIRECRACKER_MEMORY_SIZE_MB;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Validate the configuration information.
+    fn validate(&self, conf: &TomlConfig) -> Result<()> {
+        if let Some(fc) = conf.hypervisor.get(HYPERVISOR_NAME_FIRECRACKER) {
+            validate_path!(fc.path, "Firecracker binary path `{}` is invalid: {}")?;
+            validate_path!(
+                fc.jailer_path,
+                "Firecracker jailer path `{}` is invalid: {}"
+            )?;
+            if !fc.ctlpath.is_empty() {
+                return Err(eother!("CtlPath for Firecracker should be empty"));
+            }
+
+            if !fc.blockdev_info.disable_block_device_use
+                && fc.blockdev_info.block_device_driver != VIRTIO_BLK_MMIO
+            {
+                return Err(eother!(
+                    "Firecracker only supports {}, not {}",
+                    VIRTIO_BLK_MMIO,
+                    fc.blockdev_info.block_device_driver
+                ));
+            }
+
+            if fc.boot_info.kernel.is_empty() {
+                return Err(eother!("Guest kernel image for Firecracker is empty"));
+            }
+            if fc.boot_info.image.is_empty() && fc.boot_info.initrd.is_empty() {
+                return Err(eother!(
+                    "Both guest boot image and initrd for Firecracker are empty"
+                ));
+            }
+            if !fc.boot_info.firmware.is_empty() {
+                return Err(eother!("Firmware for Firecracker should be empty"));
+            }
+
+            if (fc.cpu_info.default_vcpus > 0
+                && fc.cpu_info.default_vcpus as u32 > MAX_FIRECRACKER_VCPUS)
+                || fc.cpu_info.default_maxvcpus > MAX_FIRECRACKER_VCPUS
+            {
+                return Err(eother!(
+                    "Firecracker cannot support {} vCPUs",
+                    fc.cpu_info.default_maxvcpus
+                ));
+            }
+
+            if fc.device_info.enable_iommu || fc.device_info.enable_iommu_platform {
+                return Err(eother!("Firecracker does not support vIOMMU"));
+            }
+            if fc.device_info.hotplug_vfio_on_root_bus
+                || fc.device_info.default_bridges > 0
+                || fc.device_info.pcie_root_port > 0
+            {
+                return Err(eother!("Firecracker does not support PCI hotplug options"));
+            }
+
+            if !fc.machine_info.machine_type.is_empty() {
+                return Err(eother!("Firecracker does not support machine_type"));
+            }
+
+            if fc.memory_info.enable_virtio_mem {
+                return Err(eother!("Firecracker does not support virtio-mem"));
+            }
+            if fc.memory_info.default_memory < MIN_FIRECRACKER_MEMORY_SIZE_MB {
+                return Err(eother!(
+                    "Firecracker has minimal memory limitation {}",
+                    MIN_FIRECRACKER_MEMORY_SIZE_MB
+                ));
+            }
+
+            // there is no shared filesystem device, the rootfs of the
+            // containers are block devices, e.g. by the devmapper snapshotter
+            if let Some(v) = fc.shared_fs.shared_fs.as_ref() {
+                return Err(eother!("Firecracker doesn't support {}", v));
+            }
+        }
+
+        Ok(())
+    }
+}
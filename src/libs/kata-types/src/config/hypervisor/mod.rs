@@ -25,6 +25,7 @@
 use std::collections::HashMap;
 use std::io::{self, Result};
 use std::path::Path;
+use std::str::FromStr;
 use std::sync::{Arc, Mutex};
 
 use lazy_static::lazy_static;
@@ -33,6 +34,7 @@ use serde_enum_str::{Deserialize_enum_str, Serialize_enum_str};
 
 use super::{default, ConfigOps, ConfigPlugin, TomlConfig};
 use crate::annotations::KATA_ANNO_CFG_HYPERVISOR_PREFIX;
+use crate::cpu::{CpuSet, NumaNodeSet};
 use crate::{eother, resolve_path, sl, validate_path};
 
 mod dragonball;
@@ -44,15 +46,39 @@ pub use self::qemu::{QemuConfig, HYPERVISOR_NAME_QEMU};
 mod ch;
 pub use self::ch::{CloudHypervisorConfig, HYPERVISOR_NAME_CH};
 
+mod firecracker;
+pub use self::firecracker::{FirecrackerConfig, HYPERVISOR_NAME_FIRECRACKER};
+
+mod stratovirt;
+pub use self::stratovirt::{StratoVirtConfig, HYPERVISOR_NAME_STRATOVIRT};
+
 const VIRTIO_BLK_PCI: &str = "virtio-blk-pci";
 const VIRTIO_BLK_MMIO: &str = "virtio-blk-mmio";
 const VIRTIO_BLK_CCW: &str = "virtio-blk-ccw";
 const VIRTIO_SCSI: &str = "virtio-scsi";
 const VIRTIO_PMEM: &str = "virtio-pmem";
+const NVME: &str = "nvme";
 const VIRTIO_9P: &str = "virtio-9p";
 const VIRTIO_FS: &str = "virtio-fs";
 const VIRTIO_FS_INLINE: &str = "inline-virtio-fs";
 const MAX_BRIDGE_SIZE: u32 = 5;
+// Highest priority of the SCHED_FIFO scheduling policy.
+const MAX_VCPU_RT_PRIORITY: u32 = 99;
+
+/// Each vCPU thread is pinned to one of the host CPUs of the vCPU affinity.
+pub const VCPU_AFFINITY_PINNED: &str = "pinned";
+/// The vCPU threads run on any of the host CPUs of the vCPU affinity.
+pub const VCPU_AFFINITY_SHARED: &str = "shared";
+
+/// CPU model passing the host CPU through to the guest.
+pub const CPU_MODEL_HOST: &str = "host";
+
+/// Thread pool based asynchronous IO for block devices.
+pub const BLOCK_DEVICE_AIO_THREADS: &str = "threads";
+/// Linux native asynchronous IO for block devices.
+pub const BLOCK_DEVICE_AIO_NATIVE: &str = "native";
+/// io_uring based asynchronous IO for block devices.
+pub const BLOCK_DEVICE_AIO_IO_URING: &str = "io_uring";
 
 const KERNEL_PARAM_DELIMITER: &str = " ";
 
@@ -86,10 +112,22 @@ pub struct BlockDeviceInfo {
     pub disable_block_device_use: bool,
 
     /// Block storage driver to be used for the hypervisor in case the container rootfs is backed
-    /// by a block device. This is virtio-scsi, virtio-blk or nvdimm.
+    /// by a block device. This is virtio-scsi, virtio-blk-pci, virtio-blk-mmio, virtio-blk-ccw,
+    /// virtio-pmem or nvme.
+    ///
+    /// Not all the drivers are supported by every hypervisor, the runtime refuses to attach
+    /// block devices with a driver the hypervisor doesn't support.
     #[serde(default)]
     pub block_device_driver: String,
 
+    /// Asynchronous IO mechanism used by the block device backends: threads, native or io_uring.
+    ///
+    /// - threads: pthread based disk IO, works everywhere.
+    /// - native: Linux native AIO, requires `block_device_cache_direct` to be enabled.
+    /// - io_uring: requires Linux kernel 5.1 and above, usually the fastest one.
+    #[serde(default)]
+    pub block_device_aio: String,
+
     /// Specifies cache-related options will be set to block devices or not.
     #[serde(default)]
     pub block_device_cache_set: bool,
@@ -151,6 +189,9 @@ impl BlockDeviceInfo {
         if self.block_device_driver.is_empty() {
             self.block_device_driver = default::DEFAULT_BLOCK_DEVICE_TYPE.to_string();
         }
+        if self.block_device_aio.is_empty() {
+            self.block_device_aio = default::DEFAULT_BLOCK_DEVICE_AIO.to_string();
+        }
         if self.memory_offset == 0 {
             self.memory_offset = default::DEFAULT_BLOCK_NVDIMM_MEM_OFFSET;
         }
@@ -178,6 +219,7 @@ impl BlockDeviceInfo {
             VIRTIO_BLK_MMIO,
             VIRTIO_PMEM,
             VIRTIO_SCSI,
+            NVME,
         ];
         if !l.contains(&self.block_device_driver.as_str()) {
             return Err(eother!(
@@ -185,6 +227,22 @@ impl BlockDeviceInfo {
                 self.block_device_driver
             ));
         }
+        let aio = [
+            BLOCK_DEVICE_AIO_THREADS,
+            BLOCK_DEVICE_AIO_NATIVE,
+            BLOCK_DEVICE_AIO_IO_URING,
+        ];
+        if !self.block_device_aio.is_empty() && !aio.contains(&self.block_device_aio.as_str()) {
+            return Err(eother!(
+                "{} is unsupported block device aio mode.",
+                self.block_device_aio
+            ));
+        }
+        if self.block_device_aio == BLOCK_DEVICE_AIO_NATIVE && !self.block_device_cache_direct {
+            return Err(eother!(
+                "block device aio mode native requires block_device_cache_direct."
+            ));
+        }
         validate_path!(
             self.vhost_user_store_path,
             "Invalid vhost-user-store-path {}: {}"
@@ -199,6 +257,23 @@ impl BlockDeviceInfo {
     }
 }
 
+/// A named guest kernel flavor, e.g. a real-time or GPU enabled kernel.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct KernelVariant {
+    /// Path to guest kernel file on host
+    #[serde(default)]
+    pub kernel: String,
+    /// Guest kernel commandline, appended to the kernel parameters of the hypervisor.
+    #[serde(default)]
+    pub kernel_params: String,
+    /// Path to initrd file on host
+    #[serde(default)]
+    pub initrd: String,
+    /// Path to root device on host
+    #[serde(default)]
+    pub image: String,
+}
+
 /// Guest kernel boot information.
 #[derive(Clone, Debug, Default, Deserialize, Serialize)]
 pub struct BootInfo {
@@ -226,6 +301,20 @@ pub struct BootInfo {
     /// by a block device. This is virtio-pmem, virtio-blk-pci or virtio-blk-mmio
     #[serde(default)]
     pub vm_rootfs_driver: String,
+    /// Named guest kernels which could be selected per sandbox by annotation, for example:
+    ///
+    /// ```toml
+    /// [hypervisor.qemu.kernel_variants.rt]
+    /// kernel = "/opt/kata/share/kata-containers/vmlinux-rt.container"
+    /// kernel_params = "nohz_full=1-3"
+    /// ```
+    #[serde(default)]
+    pub kernel_variants: HashMap<String, KernelVariant>,
+    /// List of kernel variants allowed to be selected by annotation.
+    ///
+    /// The default if not set is empty (all kernel variants rejected.)
+    #[serde(default)]
+    pub valid_kernel_variants: Vec<String>,
 }
 
 impl BootInfo {
@@ -235,6 +324,11 @@ impl BootInfo {
         resolve_path!(self.image, "guest boot image file {} is invalid: {}")?;
         resolve_path!(self.initrd, "guest initrd image file {} is invalid: {}")?;
         resolve_path!(self.firmware, "firmware image file {} is invalid: {}")?;
+        for variant in self.kernel_variants.values_mut() {
+            resolve_path!(variant.kernel, "guest kernel image file {} is invalid: {}")?;
+            resolve_path!(variant.image, "guest boot image file {} is invalid: {}")?;
+            resolve_path!(variant.initrd, "guest initrd image file {} is invalid: {}")?;
+        }
 
         if self.vm_rootfs_driver.is_empty() {
             self.vm_rootfs_driver = default::DEFAULT_BLOCK_DEVICE_TYPE.to_string();
@@ -252,6 +346,22 @@ impl BootInfo {
         if !self.image.is_empty() && !self.initrd.is_empty() {
             return Err(eother!("Can not configure both initrd and image for boot"));
         }
+        for (name, variant) in self.kernel_variants.iter() {
+            validate_path!(variant.kernel, "guest kernel image file {} is invalid: {}")?;
+            validate_path!(variant.image, "guest boot image file {} is invalid: {}")?;
+            validate_path!(variant.initrd, "guest initrd image file {} is invalid: {}")?;
+            if !variant.image.is_empty() && !variant.initrd.is_empty() {
+                return Err(eother!(
+                    "Can not configure both initrd and image for kernel variant {}",
+                    name
+                ));
+            }
+        }
+        for name in self.valid_kernel_variants.iter() {
+            if !self.kernel_variants.contains_key(name) {
+                return Err(eother!("kernel variant {} is not configured", name));
+            }
+        }
 
         let l = [
             VIRTIO_BLK_PCI,
@@ -285,13 +395,57 @@ impl BootInfo {
         validate_path!(path, "path {} is invalid{}")?;
         Ok(())
     }
+
+    /// Boot the guest with the kernel variant `name`, which must be in the allow-list.
+    pub fn select_kernel_variant(&mut self, name: &str) -> Result<()> {
+        if !self.valid_kernel_variants.iter().any(|v| v == name) {
+            return Err(eother!("kernel variant {} is not allowed", name));
+        }
+        let variant = self
+            .kernel_variants
+            .get(name)
+            .cloned()
+            .ok_or_else(|| eother!("kernel variant {} is not configured", name))?;
+
+        if !variant.kernel.is_empty() {
+            self.kernel = variant.kernel;
+        }
+        // the rootfs of the variant replaces the default one, either image or initrd
+        if !variant.image.is_empty() {
+            self.image = variant.image;
+            self.initrd.clear();
+        } else if !variant.initrd.is_empty() {
+            self.initrd = variant.initrd;
+            self.image.clear();
+        }
+        if !variant.kernel_params.is_empty() {
+            if self.kernel_params.is_empty() {
+                self.kernel_params = variant.kernel_params;
+            } else {
+                self.kernel_params = [self.kernel_params.as_str(), &variant.kernel_params]
+                    .join(KERNEL_PARAM_DELIMITER);
+            }
+        }
+
+        Ok(())
+    }
 }
 
 /// Virtual CPU configuration information.
 #[derive(Clone, Debug, Default, Deserialize, Serialize)]
 pub struct CpuInfo {
+    /// CPU model of the guest, empty or `host` to pass the host CPU through.
+    ///
+    /// A model named by the hypervisor, e.g. `Cascadelake-Server-v4` for QEMU, gives the guest
+    /// the same CPU flags on all the hosts supporting it, e.g. for live migration.
+    #[serde(default)]
+    pub cpu_model: String,
+
     /// CPU features, comma-separated list of cpu features to pass to the cpu.
     /// For example, `cpu_features = "pmu=off,vmx=off"
+    ///
+    /// A flag is added to the CPU model by `+flag` or `flag=on`, and removed by `-flag` or
+    /// `flag=off`, e.g. `cpu_features = "+avx512f,-hle"`.
     #[serde(default)]
     pub cpu_features: String,
 
@@ -325,6 +479,25 @@ pub struct CpuInfo {
     /// NOTICE: on arm platform with gicv2 interrupt controller, set it to 8.
     #[serde(default)]
     pub default_maxvcpus: u32,
+
+    /// Real-time scheduling priority of the vCPU threads.
+    ///
+    /// If set to a value within [1, 99], the vCPU threads run with the SCHED_FIFO policy at
+    /// that priority, 0 disables real-time scheduling. It's intended to be used together with
+    /// a PREEMPT_RT guest kernel for latency-sensitive workloads.
+    ///
+    /// WARNING: a real-time vCPU starves any other thread running on the same host CPU, so
+    /// `vcpu_rt_cpus` must be set to host CPUs dedicated to the vCPUs.
+    #[serde(default)]
+    pub vcpu_rt_priority: u32,
+
+    /// Host CPUs dedicated to the real-time vCPU threads, e.g. "4-7,10".
+    ///
+    /// Each vCPU thread is pinned to one of these CPUs, so there must be at least as many CPUs as
+    /// `default_maxvcpus`. The CPUs must be isolated from the host scheduler with the `isolcpus`
+    /// kernel parameter.
+    #[serde(default)]
+    pub vcpu_rt_cpus: String,
 }
 
 impl CpuInfo {
@@ -332,6 +505,7 @@ impl CpuInfo {
     pub fn adjust_config(&mut self) -> Result<()> {
         let features: Vec<&str> = self.cpu_features.split(',').map(|v| v.trim()).collect();
         self.cpu_features = features.join(",");
+        self.cpu_model = self.cpu_model.trim().to_string();
 
         let cpus = num_cpus::get() as u32;
 
@@ -351,6 +525,8 @@ impl CpuInfo {
             self.default_vcpus = self.default_maxvcpus as i32;
         }
 
+        self.vcpu_rt_cpus = self.vcpu_rt_cpus.trim().to_string();
+
         Ok(())
     }
 
@@ -363,8 +539,65 @@ impl CpuInfo {
                 self.default_maxvcpus
             ));
         }
+
+        if !self.cpu_model.is_empty() && !is_cpu_flag_name(&self.cpu_model) {
+            return Err(eother!("Invalid cpu_model {}", self.cpu_model));
+        }
+        for feature in self.cpu_features.split(',').filter(|f| !f.is_empty()) {
+            let flag = match feature.split_once('=') {
+                Some((flag, value)) if is_cpu_flag_name(value) => flag,
+                Some(_) => "",
+                None => feature.trim_start_matches(|c| c == '+' || c == '-'),
+            };
+            if !is_cpu_flag_name(flag) {
+                return Err(eother!("Invalid cpu feature {}", feature));
+            }
+        }
+
+        if self.vcpu_rt_priority > MAX_VCPU_RT_PRIORITY {
+            return Err(eother!(
+                "The vcpu_rt_priority({}) is greater than {}",
+                self.vcpu_rt_priority,
+                MAX_VCPU_RT_PRIORITY
+            ));
+        }
+        if self.vcpu_rt_priority > 0 {
+            let cpus = self.get_vcpu_rt_cpus()?;
+            if cpus.is_empty() {
+                return Err(eother!(
+                    "The vcpu_rt_cpus must be set when vcpu_rt_priority is enabled"
+                ));
+            }
+            if cpus.len() < self.default_maxvcpus as usize {
+                return Err(eother!(
+                    "The vcpu_rt_cpus({}) has fewer CPUs than default_maxvcpus({})",
+                    self.vcpu_rt_cpus,
+                    self.default_maxvcpus
+                ));
+            }
+        }
+
         Ok(())
     }
+
+    /// Whether the guest CPU is the host one.
+    pub fn is_host_cpu_model(&self) -> bool {
+        self.cpu_model.is_empty() || self.cpu_model == CPU_MODEL_HOST
+    }
+
+    /// Get the host CPUs dedicated to the real-time vCPU threads.
+    pub fn get_vcpu_rt_cpus(&self) -> Result<CpuSet> {
+        CpuSet::from_str(&self.vcpu_rt_cpus)
+            .map_err(|e| eother!("Invalid vcpu_rt_cpus {}: {}", self.vcpu_rt_cpus, e))
+    }
+}
+
+// the names of the CPU models and flags, and the values of the flags, as accepted by QEMU
+fn is_cpu_flag_name(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.')
 }
 
 /// Configuration information for debug
@@ -616,6 +849,16 @@ pub struct MemoryInfo {
     #[serde(default)]
     pub enable_virtio_mem: bool,
 
+    /// Maximum memory size in MiB the VM could be resized to by virtio-mem, or by ACPI hotplug for
+    /// cloud-hypervisor without virtio-mem, 0 to never grow it beyond `default_memory`.
+    #[serde(default)]
+    pub default_maxmemory: u32,
+
+    /// Add a virtio-balloon device to the VM, so that its memory could be shrunk below
+    /// `default_memory` when the containers need less.
+    #[serde(default)]
+    pub enable_balloon: bool,
+
     /// Enable swap of vm memory. Default false.
     ///
     /// The behaviour is undefined if mem_prealloc is also set to true
@@ -658,6 +901,13 @@ impl MemoryInfo {
         if self.memory_slots == 0 {
             return Err(eother!("Configured memory slots for guest VM are zero"));
         }
+        if self.default_maxmemory != 0 && self.default_maxmemory < self.default_memory {
+            return Err(eother!(
+                "Configured maximum memory {} MiB is less than the default memory {} MiB",
+                self.default_maxmemory,
+                self.default_memory
+            ));
+        }
 
         Ok(())
     }
@@ -710,6 +960,55 @@ impl NetworkInfo {
     }
 }
 
+/// Sysctls of the hardened guest preset, restricting the kernel information leaks and the
+/// kernel attack surface reachable by the workloads.
+pub const HARDENED_GUEST_SYSCTLS: &[(&str, &str)] = &[
+    ("kernel.kptr_restrict", "2"),
+    ("kernel.dmesg_restrict", "1"),
+    ("kernel.perf_event_paranoid", "3"),
+    ("kernel.unprivileged_bpf_disabled", "1"),
+    ("net.core.bpf_jit_harden", "2"),
+    ("kernel.kexec_load_disabled", "1"),
+    ("kernel.sysrq", "0"),
+    ("kernel.yama.ptrace_scope", "2"),
+    ("fs.protected_symlinks", "1"),
+    ("fs.protected_hardlinks", "1"),
+    ("fs.protected_fifos", "2"),
+    ("fs.protected_regular", "2"),
+    ("fs.suid_dumpable", "0"),
+];
+
+/// Kernel modules of the hardened guest preset which can't be loaded in the guest, the network
+/// protocols and the file systems which aren't needed by the containers.
+pub const HARDENED_GUEST_KERNEL_MODULES_BLACKLIST: &[&str] = &[
+    "dccp",
+    "sctp",
+    "rds",
+    "tipc",
+    "n_hdlc",
+    "ax25",
+    "netrom",
+    "x25",
+    "rose",
+    "decnet",
+    "econet",
+    "af_802154",
+    "ipx",
+    "appletalk",
+    "psnap",
+    "p8022",
+    "can",
+    "atm",
+    "bluetooth",
+    "firewire_core",
+    "cramfs",
+    "freevxfs",
+    "jffs2",
+    "hfs",
+    "hfsplus",
+    "udf",
+];
+
 /// Configuration information for security.
 #[derive(Clone, Debug, Default, Deserialize, Serialize)]
 pub struct SecurityInfo {
@@ -760,6 +1059,19 @@ pub struct SecurityInfo {
     /// e.g. "path" for io.katacontainers.config.hypervisor.path"
     #[serde(default)]
     pub enable_annotations: Vec<String>,
+
+    /// Apply the hardened guest preset, for the security sensitive tenants.
+    ///
+    /// The guest kernel is booted with a curated set of sysctls and with the kernel modules of
+    /// rarely used network protocols and file systems blacklisted, the device model of the VM is
+    /// reduced and the debug facilities of the guest are disabled.
+    #[serde(default)]
+    pub hardened_guest: bool,
+
+    /// Names of the kernel modules which can't be loaded in the guest, on top of the ones of the
+    /// hardened guest preset.
+    #[serde(default)]
+    pub kernel_modules_blacklist: Vec<String>,
 }
 
 impl SecurityInfo {
@@ -773,9 +1085,46 @@ impl SecurityInfo {
 
     /// Validate the configuration information.
     pub fn validate(&self) -> Result<()> {
+        for module in self.kernel_modules_blacklist.iter() {
+            if module.is_empty() || module.contains(|c: char| c == ',' || c.is_whitespace()) {
+                return Err(eother!(
+                    "Invalid kernel module name {:?} to blacklist",
+                    module
+                ));
+            }
+        }
         Ok(())
     }
 
+    /// Get the kernel modules which can't be loaded in the guest, with the `-` of their names
+    /// replaced by `_` as the kernel does.
+    pub fn guest_kernel_modules_blacklist(&self) -> Vec<String> {
+        let mut modules: Vec<String> = if self.hardened_guest {
+            HARDENED_GUEST_KERNEL_MODULES_BLACKLIST
+                .iter()
+                .map(|m| m.to_string())
+                .collect()
+        } else {
+            vec![]
+        };
+        for module in self.kernel_modules_blacklist.iter() {
+            let module = module.replace('-', "_");
+            if !modules.contains(&module) {
+                modules.push(module);
+            }
+        }
+        modules
+    }
+
+    /// Get the sysctls set by the guest kernel at boot.
+    pub fn guest_sysctls(&self) -> Vec<(&'static str, &'static str)> {
+        if self.hardened_guest {
+            HARDENED_GUEST_SYSCTLS.to_vec()
+        } else {
+            vec![]
+        }
+    }
+
     /// Check whether annotation key is enabled or not.
     pub fn is_annotation_enabled(&self, path: &str) -> bool {
         if !path.starts_with(KATA_ANNO_CFG_HYPERVISOR_PREFIX) {
@@ -843,6 +1192,11 @@ pub struct SharedFsInfo {
     #[serde(default)]
     pub virtio_fs_is_dax: bool,
 
+    /// Size of the thread pool of the virtio-fs daemon handling the requests of the guest, the
+    /// default of the daemon if 0.
+    #[serde(default)]
+    pub virtio_fs_thread_pool_size: u32,
+
     /// This is the msize used for 9p shares. It is the number of bytes used for 9p packet payload.
     #[serde(default)]
     pub msize_9p: u32,
@@ -910,13 +1264,19 @@ impl SharedFsInfo {
             warn!(sl!(), "virtio-fs cache mode `none` is deprecated since Kata Containers 2.5.0 and will be removed in the future release, please use `never` instead. For more details please refer to https://github.com/kata-containers/kata-containers/issues/4234.");
             self.virtio_fs_cache = default::DEFAULT_VIRTIO_FS_CACHE_MODE.to_string();
         }
+        self.adjust_dax();
+        Ok(())
+    }
+
+    /// Make the DAX window consistent with its size, a window enabled without a size gets the
+    /// default size, and a window with a size is enabled.
+    pub fn adjust_dax(&mut self) {
         if self.virtio_fs_is_dax && self.virtio_fs_cache_size == 0 {
             self.virtio_fs_cache_size = default::DEFAULT_VIRTIO_FS_DAX_SIZE_MB;
         }
         if !self.virtio_fs_is_dax && self.virtio_fs_cache_size != 0 {
             self.virtio_fs_is_dax = true;
         }
-        Ok(())
     }
 
     fn validate_virtio_fs(&self, inline: bool) -> Result<()> {
@@ -946,6 +1306,125 @@ impl SharedFsInfo {
     }
 }
 
+/// Placement of the vCPU threads on the host CPUs, for the latency-sensitive workloads which need
+/// a deterministic placement.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct VcpuAffinityInfo {
+    /// Placement policy of the vCPU threads:
+    /// - empty: the vCPU threads run on the CPUs of the sandbox cgroup, as scheduled by the host
+    /// - "pinned": each vCPU thread is pinned to one host CPU, in the order of the CPUs
+    /// - "shared": the vCPU threads run on any of the host CPUs
+    #[serde(default)]
+    pub policy: String,
+
+    /// Host CPUs of the vCPU threads, e.g. "2-5,8".
+    #[serde(default)]
+    pub cpus: String,
+
+    /// Host NUMA nodes whose CPUs are added to `cpus`, e.g. "0".
+    #[serde(default)]
+    pub numa_nodes: String,
+}
+
+impl VcpuAffinityInfo {
+    /// Adjust the configuration information after loading from configuration file.
+    pub fn adjust_config(&mut self) -> Result<()> {
+        self.policy = self.policy.trim().to_string();
+        self.cpus = self.cpus.trim().to_string();
+        self.numa_nodes = self.numa_nodes.trim().to_string();
+        Ok(())
+    }
+
+    /// Validate the configuration information.
+    pub fn validate(&self) -> Result<()> {
+        match self.policy.as_str() {
+            "" | VCPU_AFFINITY_PINNED | VCPU_AFFINITY_SHARED => {}
+            policy => return Err(eother!("Invalid vcpu affinity policy {}", policy)),
+        }
+        let cpus = self.get_cpus()?;
+        let nodes = self.get_numa_nodes()?;
+        if !self.policy.is_empty() && cpus.is_empty() && nodes.is_empty() {
+            return Err(eother!(
+                "The cpus or the numa_nodes of the vcpu affinity must be set with the {} policy",
+                self.policy
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Get the host CPUs of the vCPU threads.
+    pub fn get_cpus(&self) -> Result<CpuSet> {
+        CpuSet::from_str(&self.cpus)
+            .map_err(|e| eother!("Invalid vcpu affinity cpus {}: {}", self.cpus, e))
+    }
+
+    /// Get the host NUMA nodes of the vCPU threads.
+    pub fn get_numa_nodes(&self) -> Result<NumaNodeSet> {
+        NumaNodeSet::from_str(&self.numa_nodes).map_err(|e| {
+            eother!(
+                "Invalid vcpu affinity numa nodes {}: {}",
+                self.numa_nodes,
+                e
+            )
+        })
+    }
+}
+
+/// Named shape of the VM, selected by the sizing profile annotation, so that the platform teams
+/// offer consistent VM shapes without enabling the annotation of every knob.
+///
+/// The fields left to 0 or empty keep the value of the hypervisor configuration. The pod
+/// overhead of the RuntimeClass should account for the memory and the vcpus of the profile.
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+pub struct SizingProfile {
+    /// Number of vcpus of the VM at boot.
+    #[serde(default)]
+    pub default_vcpus: i32,
+
+    /// Memory of the VM at boot, in MiB.
+    #[serde(default)]
+    pub default_memory: u32,
+
+    /// Cache mode of virtio-fs, see `virtio_fs_cache`.
+    #[serde(default)]
+    pub virtio_fs_cache: String,
+
+    /// Size of the virtio-fs DAX cache in MiB.
+    #[serde(default)]
+    pub virtio_fs_cache_size: u32,
+
+    /// Size of the virtio-fs virtqueues.
+    #[serde(default)]
+    pub virtio_fs_queue_size: u32,
+
+    /// Number of queues of the network devices.
+    #[serde(default)]
+    pub network_queues: u32,
+}
+
+/// Sizing profiles available unless they are redefined in the configuration file.
+pub fn default_sizing_profiles() -> HashMap<String, SizingProfile> {
+    [
+        ("small", 1, 1024, 1),
+        ("medium", 2, 2048, 2),
+        ("large", 4, 4096, 4),
+    ]
+    .iter()
+    .map(|(name, vcpus, memory, queues)| {
+        (
+            name.to_string(),
+            SizingProfile {
+                default_vcpus: *vcpus,
+                default_memory: *memory,
+                network_queues: *queues,
+                ..Default::default()
+            },
+        )
+    })
+    .collect()
+}
+
 /// Common configuration information for hypervisors.
 #[derive(Clone, Debug, Default, Deserialize, Serialize)]
 pub struct Hypervisor {
@@ -1036,6 +1515,15 @@ pub struct Hypervisor {
     #[serde(default)]
     pub prefetch_list_path: String,
 
+    /// VM sizing profiles by name, e.g. `[hypervisor.dragonball.sizing_profiles.small]`, the
+    /// built-in "small", "medium" and "large" ones are used if none is defined.
+    #[serde(default)]
+    pub sizing_profiles: HashMap<String, SizingProfile>,
+
+    /// Placement of the vCPU threads on the host, `[hypervisor.dragonball.vcpu_affinity]`.
+    #[serde(default)]
+    pub vcpu_affinity: VcpuAffinityInfo,
+
     /// Vendor customized runtime configuration.
     #[serde(default, flatten)]
     pub vendor: HypervisorVendor,
@@ -1056,6 +1544,69 @@ impl Hypervisor {
     pub fn validate_jailer_path<P: AsRef<Path>>(&self, path: P) -> Result<()> {
         validate_path_pattern(&self.valid_jailer_paths, path)
     }
+
+    /// Apply the sizing profile `name`, the memory of the profile must be at least `min_memory`.
+    pub fn apply_sizing_profile(&mut self, name: &str, min_memory: u32) -> Result<()> {
+        let profile = self
+            .sizing_profiles
+            .get(name)
+            .cloned()
+            .ok_or_else(|| eother!("sizing profile {} is not defined", name))?;
+
+        if profile.default_vcpus < 0 {
+            return Err(eother!(
+                "default_vcpus({}) of sizing profile {} is invalid",
+                profile.default_vcpus,
+                name
+            ));
+        }
+        if profile.default_vcpus > 0 {
+            // capped like the default_vcpus of the configuration file
+            self.cpu_info.default_vcpus = profile
+                .default_vcpus
+                .min(self.cpu_info.default_maxvcpus as i32);
+        }
+        if profile.default_memory > 0 {
+            if profile.default_memory < min_memory {
+                return Err(eother!(
+                    "memory({}) of sizing profile {} is less than minimum limitation {}",
+                    profile.default_memory,
+                    name,
+                    min_memory
+                ));
+            }
+            self.memory_info.default_memory = profile.default_memory;
+        }
+        if !profile.virtio_fs_cache.is_empty() {
+            self.shared_fs.virtio_fs_cache = profile.virtio_fs_cache;
+        }
+        if profile.virtio_fs_cache_size > 0 {
+            self.shared_fs.virtio_fs_cache_size = profile.virtio_fs_cache_size;
+        }
+        if profile.virtio_fs_queue_size > 0 {
+            self.shared_fs.virtio_fs_queue_size = profile.virtio_fs_queue_size;
+        }
+        if profile.network_queues > 0 {
+            self.network_info.network_queues = profile.network_queues;
+        }
+        info!(sl!(), "apply sizing profile {}", name);
+
+        Ok(())
+    }
+
+    /// Reduce the VM to the devices and the facilities needed by the containers if the hardened
+    /// guest preset is enabled, overriding the configuration and the annotations.
+    pub fn apply_hardened_guest(&mut self) {
+        if !self.security_info.hardened_guest {
+            return;
+        }
+        // no device is hot plugged on the root bus or on the reserved root ports
+        self.device_info.pcie_root_port = 0;
+        self.device_info.hotplug_vfio_on_root_bus = false;
+        self.security_info.disable_seccomp = false;
+        self.debug_info.guest_memory_dump_path = String::new();
+        info!(sl!(), "apply hardened guest preset");
+    }
 }
 
 impl ConfigOps for Hypervisor {
@@ -1079,10 +1630,14 @@ impl ConfigOps for Hypervisor {
                 hv.network_info.adjust_config()?;
                 hv.security_info.adjust_config()?;
                 hv.shared_fs.adjust_config()?;
+                hv.vcpu_affinity.adjust_config()?;
                 resolve_path!(
                     hv.prefetch_list_path,
                     "prefetch_list_path `{}` is invalid: {}"
                 )?;
+                if hv.sizing_profiles.is_empty() {
+                    hv.sizing_profiles = default_sizing_profiles();
+                }
             } else {
                 return Err(eother!("Can not find plugin for hypervisor {}", hypervisor));
             }
@@ -1111,6 +1666,13 @@ impl ConfigOps for Hypervisor {
                 hv.network_info.validate()?;
                 hv.security_info.validate()?;
                 hv.shared_fs.validate()?;
+                hv.vcpu_affinity.validate()?;
+                // the real-time vCPUs are pinned to their dedicated CPUs
+                if hv.cpu_info.vcpu_rt_priority > 0 && !hv.vcpu_affinity.policy.is_empty() {
+                    return Err(eother!(
+                        "The vcpu affinity policy can't be set with vcpu_rt_priority"
+                    ));
+                }
                 validate_path!(hv.path, "Hypervisor binary path `{}` is invalid: {}")?;
                 validate_path!(
                     hv.ctlpath,
@@ -1190,6 +1752,96 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_select_kernel_variant() {
+        let mut boot_info = BootInfo {
+            kernel: "/opt/kata/vmlinux".to_string(),
+            kernel_params: "foo".to_string(),
+            image: "/opt/kata/kata.img".to_string(),
+            kernel_variants: HashMap::from([
+                (
+                    "rt".to_string(),
+                    KernelVariant {
+                        kernel: "/opt/kata/vmlinux-rt".to_string(),
+                        kernel_params: "nohz_full=1".to_string(),
+                        ..Default::default()
+                    },
+                ),
+                (
+                    "nvidia".to_string(),
+                    KernelVariant {
+                        kernel: "/opt/kata/vmlinux-nvidia".to_string(),
+                        initrd: "/opt/kata/kata-nvidia.initrd".to_string(),
+                        ..Default::default()
+                    },
+                ),
+            ]),
+            valid_kernel_variants: vec!["rt".to_string()],
+            ..Default::default()
+        };
+
+        // not in the allow-list
+        assert!(boot_info.clone().select_kernel_variant("nvidia").is_err());
+        assert!(boot_info.clone().select_kernel_variant("unknown").is_err());
+
+        let mut rt = boot_info.clone();
+        rt.select_kernel_variant("rt").unwrap();
+        assert_eq!(rt.kernel, "/opt/kata/vmlinux-rt");
+        assert_eq!(rt.kernel_params, "foo nohz_full=1");
+        assert_eq!(rt.image, "/opt/kata/kata.img");
+
+        boot_info.valid_kernel_variants.push("nvidia".to_string());
+        boot_info.select_kernel_variant("nvidia").unwrap();
+        assert_eq!(boot_info.kernel, "/opt/kata/vmlinux-nvidia");
+        assert_eq!(boot_info.kernel_params, "foo");
+        assert_eq!(boot_info.initrd, "/opt/kata/kata-nvidia.initrd");
+        assert!(boot_info.image.is_empty());
+    }
+
+    #[test]
+    fn test_guest_kernel_modules_blacklist() {
+        let mut security_info = SecurityInfo {
+            kernel_modules_blacklist: vec!["nf-tables".to_string(), "sctp".to_string()],
+            ..Default::default()
+        };
+        assert!(security_info.validate().is_ok());
+        assert_eq!(
+            security_info.guest_kernel_modules_blacklist(),
+            vec!["nf_tables".to_string(), "sctp".to_string()]
+        );
+        assert!(security_info.guest_sysctls().is_empty());
+
+        security_info.hardened_guest = true;
+        let modules = security_info.guest_kernel_modules_blacklist();
+        assert_eq!(
+            modules.len(),
+            HARDENED_GUEST_KERNEL_MODULES_BLACKLIST.len() + 1
+        );
+        assert_eq!(modules.last().unwrap(), "nf_tables");
+        assert_eq!(security_info.guest_sysctls(), HARDENED_GUEST_SYSCTLS);
+
+        security_info.kernel_modules_blacklist = vec!["a,b".to_string()];
+        assert!(security_info.validate().is_err());
+        security_info.kernel_modules_blacklist = vec!["".to_string()];
+        assert!(security_info.validate().is_err());
+    }
+
+    #[test]
+    fn test_apply_hardened_guest() {
+        let mut hv = Hypervisor::default();
+        hv.device_info.pcie_root_port = 2;
+        hv.debug_info.guest_memory_dump_path = "/var/crash".to_string();
+        hv.apply_hardened_guest();
+        assert_eq!(hv.device_info.pcie_root_port, 2);
+
+        hv.security_info.hardened_guest = true;
+        hv.security_info.disable_seccomp = true;
+        hv.apply_hardened_guest();
+        assert_eq!(hv.device_info.pcie_root_port, 0);
+        assert!(!hv.security_info.disable_seccomp);
+        assert!(hv.debug_info.guest_memory_dump_path.is_empty());
+    }
+
     #[test]
     fn test_cpu_info_adjust_config() {
         // get CPU cores of the test node
@@ -1209,11 +1861,13 @@ mod tests {
                     cpu_features: "".to_string(),
                     default_vcpus: 0,
                     default_maxvcpus: 0,
+                    ..Default::default()
                 },
                 output: CpuInfo {
                     cpu_features: "".to_string(),
                     default_vcpus,
                     default_maxvcpus: node_cpus,
+                    ..Default::default()
                 },
             },
             TestData {
@@ -1222,11 +1876,13 @@ mod tests {
                     cpu_features: "a,b,c".to_string(),
                     default_vcpus: 9999999,
                     default_maxvcpus: 9999999,
+                    ..Default::default()
                 },
                 output: CpuInfo {
                     cpu_features: "a,b,c".to_string(),
                     default_vcpus: node_cpus as i32,
                     default_maxvcpus: node_cpus,
+                    ..Default::default()
                 },
             },
             TestData {
@@ -1235,11 +1891,13 @@ mod tests {
                     cpu_features: "a, b ,c".to_string(),
                     default_vcpus: -1,
                     default_maxvcpus: 1,
+                    ..Default::default()
                 },
                 output: CpuInfo {
                     cpu_features: "a,b,c".to_string(),
                     default_vcpus: 1,
                     default_maxvcpus: 1,
+                    ..Default::default()
                 },
             },
         ];
@@ -1265,4 +1923,84 @@ mod tests {
             );
         }
     }
+
+    #[test]
+    fn test_cpu_info_validate_vcpu_rt() {
+        let mut cpu_info = CpuInfo {
+            default_vcpus: 1,
+            default_maxvcpus: 2,
+            ..Default::default()
+        };
+        assert!(cpu_info.validate().is_ok());
+
+        // real-time scheduling requires dedicated CPUs
+        cpu_info.vcpu_rt_priority = 50;
+        assert!(cpu_info.validate().is_err());
+
+        cpu_info.vcpu_rt_cpus = "4".to_string();
+        assert!(cpu_info.validate().is_err());
+
+        cpu_info.vcpu_rt_cpus = "4-x".to_string();
+        assert!(cpu_info.validate().is_err());
+
+        cpu_info.vcpu_rt_cpus = "4-5".to_string();
+        assert!(cpu_info.validate().is_ok());
+        assert_eq!(cpu_info.get_vcpu_rt_cpus().unwrap().to_vec(), vec![4, 5]);
+
+        cpu_info.vcpu_rt_priority = 100;
+        assert!(cpu_info.validate().is_err());
+    }
+
+    #[test]
+    fn test_vcpu_affinity_info_validate() {
+        let mut affinity = VcpuAffinityInfo::default();
+        assert!(affinity.validate().is_ok());
+
+        affinity.policy = "spread".to_string();
+        assert!(affinity.validate().is_err());
+
+        // the policy needs host CPUs
+        affinity.policy = VCPU_AFFINITY_PINNED.to_string();
+        assert!(affinity.validate().is_err());
+        affinity.cpus = "2-x".to_string();
+        assert!(affinity.validate().is_err());
+        affinity.cpus = "2-3,6".to_string();
+        assert!(affinity.validate().is_ok());
+        assert_eq!(affinity.get_cpus().unwrap().to_vec(), vec![2, 3, 6]);
+
+        affinity.policy = VCPU_AFFINITY_SHARED.to_string();
+        affinity.cpus = String::new();
+        affinity.numa_nodes = "1".to_string();
+        assert!(affinity.validate().is_ok());
+        assert_eq!(affinity.get_numa_nodes().unwrap().to_vec(), vec![1]);
+    }
+
+    #[test]
+    fn test_cpu_info_validate_cpu_model() {
+        let mut cpu_info = CpuInfo {
+            default_vcpus: 1,
+            default_maxvcpus: 1,
+            ..Default::default()
+        };
+        assert!(cpu_info.validate().is_ok());
+        assert!(cpu_info.is_host_cpu_model());
+
+        cpu_info.cpu_model = "host".to_string();
+        cpu_info.cpu_features = "pmu=off,+avx512f,-hle,amx".to_string();
+        assert!(cpu_info.validate().is_ok());
+        assert!(cpu_info.is_host_cpu_model());
+
+        cpu_info.cpu_model = "Cascadelake-Server-v4".to_string();
+        assert!(cpu_info.validate().is_ok());
+        assert!(!cpu_info.is_host_cpu_model());
+
+        cpu_info.cpu_model = "host,-hle".to_string();
+        assert!(cpu_info.validate().is_err());
+
+        cpu_info.cpu_model = "host".to_string();
+        for features in ["+", "pmu=", "=off", "avx 512"] {
+            cpu_info.cpu_features = features.to_string();
+            assert!(cpu_info.validate().is_err(), "{}", features);
+        }
+    }
 }
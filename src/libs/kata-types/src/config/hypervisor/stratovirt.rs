@@ -0,0 +1,181 @@
+// Copyright (c) 2019-2021 Alibaba Cloud
+// Copyright (c) 2023 Kata Containers community
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+use std::io::Result;
+use std::path::Path;
+use std::sync::Arc;
+
+use super::{default, register_hypervisor_plugin};
+
+use crate::config::default::MAX_STRATOVIRT_VCPUS;
+use crate::config::default::MIN_STRATOVIRT_MEMORY_SIZE_MB;
+
+use crate::config::hypervisor::{VIRTIO_BLK_MMIO, VIRTIO_FS};
+use crate::config::{ConfigPlugin, TomlConfig};
+use crate::{eother, resolve_path, validate_path};
+
+/// Hypervisor name for StratoVirt, used to index `TomlConfig::hypervisor`.
+pub const HYPERVISOR_NAME_STRATOVIRT: &str = "stratovirt";
+
+/// Configuration information for StratoVirt.
+#[derive(Default, Debug)]
+pub struct StratoVirtConfig {}
+
+impl StratoVirtConfig {
+    /// Create a new instance of `StratoVirtConfig`.
+    pub fn new() -> Self {
+        StratoVirtConfig {}
+    }
+
+    /// Register the StratoVirt plugin.
+    pub fn register(self) {
+        let plugin = Arc::new(self);
+        register_hypervisor_plugin(HYPERVISOR_NAME_STRATOVIRT, plugin);
+    }
+}
+
+impl ConfigPlugin for StratoVirtConfig {
+    fn get_max_cpus(&self) -> u32 {
+        MAX_STRATOVIRT_VCPUS
+    }
+
+    fn get_min_memory(&self) -> u32 {
+        MIN_STRATOVIRT_MEMORY_SIZE_MB
+    }
+
+    fn name(&self) -> &str {
+        HYPERVISOR_NAME_STRATOVIRT
+    }
+
+    /// Adjust the configuration information after loading from configuration file.
+    fn adjust_config(&self, conf: &mut TomlConfig) -> Result<()> {
+        if let Some(sv) = conf.hypervisor.get_mut(HYPERVISOR_NAME_STRATOVIRT) {
+            if sv.path.is_empty() {
+                sv.path = default::DEFAULT_STRATOVIRT_BINARY_PATH.to_string();
+            }
+            resolve_path!(sv.path, "StratoVirt binary path `{}` is invalid: {}")?;
+
+            if sv.boot_info.kernel.is_empty() {
+                sv.boot_info.kernel = default::DEFAULT_STRATOVIRT_GUEST_KERNEL_IMAGE.to_string();
+            }
+            if sv.boot_info.kernel_params.is_empty() {
+                sv.boot_info.kernel_params =
+                    default::DEFAULT_STRATOVIRT_GUEST_KERNEL_PARAMS.to_string();
+            }
+            if sv.boot_info.rootfs_type.is_empty() {
+                sv.boot_info.rootfs_type = default::DEFAULT_STRATOVIRT_ROOTFS_TYPE.to_string();
+            }
+
+            // the microvm machine only has virtio-mmio devices
+            if sv.machine_info.machine_type.is_empty() {
+                sv.machine_info.machine_type = default::DEFAULT_STRATOVIRT_MACHINE_TYPE.to_string();
+            }
+            if sv.blockdev_info.block_device_driver.is_empty() {
+                sv.blockdev_info.block_device_driver = VIRTIO_BLK_MMIO.to_string();
+            }
+
+            // the microvm machine can't hot plug vCPUs, the VM is booted with all of them
+            if sv.cpu_info.default_maxvcpus > MAX_STRATOVIRT_VCPUS {
+                sv.cpu_info.default_maxvcpus = MAX_STRATOVIRT_VCPUS;
+            }
+            if sv.cpu_info.default_vcpus as u32 > sv.cpu_info.default_maxvcpus {
+                sv.cpu_info.default_vcpus = sv.cpu_info.default_maxvcpus as i32;
+            }
+
+            if sv.machine_info.entropy_source.is_empty() {
+                sv.machine_info.entropy_source =
+                    default::DEFAULT_STRATOVIRT_ENTROPY_SOURCE.to_string();
+            }
+
+            if sv.memory_info.default_memory == 0 {
+                sv.memory_info.default_memory = default::DEFAULT_STRATOVIRT_MEMORY_SIZE_MB;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Validate the configuration information.
+    fn validate(&self, conf: &TomlConfig) -> Result<()> {
+        if let Some(sv) = conf.hypervisor.get(HYPERVISOR_NAME_STRATOVIRT) {
+            validate_path!(sv.path, "StratoVirt binary path `{}` is invalid: {}")?;
+            if !sv.ctlpath.is_empty() {
+                return Err(eother!("CtlPath for StratoVirt should be empty"));
+            }
+            if !sv.jailer_path.is_empty() {
+                return Err(eother!("Jailer for StratoVirt isn't supported"));
+            }
+
+            if sv.machine_info.machine_type != default::DEFAULT_STRATOVIRT_MACHINE_TYPE {
+                return Err(eother!(
+                    "StratoVirt only supports the {} machine type, not {}",
+                    default::DEFAULT_STRATOVIRT_MACHINE_TYPE,
+                    sv.machine_info.machine_type
+                ));
+            }
+            if !sv.blockdev_info.disable_block_device_use
+                && sv.blockdev_info.block_device_driver != VIRTIO_BLK_MMIO
+            {
+                return Err(eother!(
+                    "StratoVirt only supports {}, not {}",
+                    VIRTIO_BLK_MMIO,
+                    sv.blockdev_info.block_device_driver
+                ));
+            }
+
+            if sv.boot_info.kernel.is_empty() {
+                return Err(eother!("Guest kernel image for StratoVirt is empty"));
+            }
+            if sv.boot_info.image.is_empty() && sv.boot_info.initrd.is_empty() {
+                return Err(eother!(
+                    "Both guest boot image and initrd for StratoVirt are empty"
+                ));
+            }
+            if !sv.boot_info.firmware.is_empty() {
+                return Err(eother!("Firmware for StratoVirt should be empty"));
+            }
+
+            if (sv.cpu_info.default_vcpus > 0
+                && sv.cpu_info.default_vcpus as u32 > MAX_STRATOVIRT_VCPUS)
+                || sv.cpu_info.default_maxvcpus > MAX_STRATOVIRT_VCPUS
+            {
+                return Err(eother!(
+                    "StratoVirt cannot support {} vCPUs",
+                    sv.cpu_info.default_maxvcpus
+                ));
+            }
+
+            if sv.device_info.enable_iommu || sv.device_info.enable_iommu_platform {
+                return Err(eother!("StratoVirt does not support vIOMMU"));
+            }
+            if sv.device_info.hotplug_vfio_on_root_bus
+                || sv.device_info.default_bridges > 0
+                || sv.device_info.pcie_root_port > 0
+            {
+                return Err(eother!("StratoVirt does not support PCI hotplug options"));
+            }
+
+            if sv.memory_info.enable_virtio_mem {
+                return Err(eother!("StratoVirt does not support virtio-mem"));
+            }
+            if sv.memory_info.default_memory < MIN_STRATOVIRT_MEMORY_SIZE_MB {
+                return Err(eother!(
+                    "StratoVirt has minimal memory limitation {}",
+                    MIN_STRATOVIRT_MEMORY_SIZE_MB
+                ));
+            }
+
+            // the shared filesystem is a vhost-user-fs device, served by virtiofsd
+            if let Some(v) = sv.shared_fs.shared_fs.as_ref() {
+                if v != VIRTIO_FS {
+                    return Err(eother!("StratoVirt doesn't support {}", v));
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -12,6 +12,7 @@ use std::sync::{Arc, Mutex};
 use std::u32;
 
 use lazy_static::lazy_static;
+use regex::Regex;
 
 use crate::{eother, sl};
 
@@ -22,15 +23,21 @@ mod agent;
 mod drop_in;
 pub mod hypervisor;
 
-pub use self::agent::Agent;
+pub use self::agent::{Agent, GuestHook};
 use self::default::DEFAULT_AGENT_DBG_CONSOLE_PORT;
 pub use self::hypervisor::{
-    BootInfo, CloudHypervisorConfig, DragonballConfig, Hypervisor, QemuConfig,
-    HYPERVISOR_NAME_DRAGONBALL, HYPERVISOR_NAME_QEMU,
+    BootInfo, CloudHypervisorConfig, DragonballConfig, FirecrackerConfig, Hypervisor, QemuConfig,
+    StratoVirtConfig, VcpuAffinityInfo, HYPERVISOR_NAME_DRAGONBALL, HYPERVISOR_NAME_FIRECRACKER,
+    HYPERVISOR_NAME_QEMU, HYPERVISOR_NAME_STRATOVIRT, VCPU_AFFINITY_PINNED, VCPU_AFFINITY_SHARED,
 };
 
 mod runtime;
-pub use self::runtime::{Runtime, RuntimeVendor, RUNTIME_NAME_VIRTCONTAINER};
+pub use self::runtime::{
+    Runtime, RuntimeVendor, MIN_PROCESS_OUTPUT_BUFFER_KB, PROCESS_OUTPUT_POLICY_BLOCK,
+    PROCESS_OUTPUT_POLICY_DROP, RUNTIME_NAME_VIRTCONTAINER, SANDBOX_READINESS_CHECK_AGENT,
+    SANDBOX_READINESS_CHECK_NETWORK, SENSITIVE_HOST_PATH_ALLOW, SENSITIVE_HOST_PATH_REJECT,
+    SENSITIVE_HOST_PATH_SKIP,
+};
 
 pub use self::agent::AGENT_NAME_KATA;
 
@@ -52,8 +59,20 @@ pub const LOG_LEVEL_DEBUG: &str = "debug";
 pub const DEBUG_CONSOLE_VPORT_OPTION: &str = "agent.debug_console_vport";
 /// Option of which port the agent's log will connect to
 pub const LOG_VPORT_OPTION: &str = "agent.log_vport";
+/// Option of the ports of the in-guest metrics exporters proxied by the agent
+pub const GUEST_METRICS_PORTS_OPTION: &str = "agent.guest_metrics_ports";
 /// Option of setting the container's pipe size
 pub const CONTAINER_PIPE_SIZE_OPTION: &str = "agent.container_pipe_size";
+/// Option of enabling image signature verification in guest
+pub const SIGNATURE_VERIFICATION_OPTION: &str = "agent.enable_signature_verification";
+/// Option of the image security policy used for signature verification
+pub const IMAGE_POLICY_FILE_OPTION: &str = "agent.image_policy_file";
+
+/// The config values whose key contains one of these words are redacted when the configuration
+/// is dumped, e.g. `jaeger_password`.
+const REDACTED_CONFIG_KEYS: [&str; 4] = ["password", "secret", "token", "credential"];
+/// Replacement of the redacted config values.
+pub const REDACTED_CONFIG_VALUE: &str = "<redacted>";
 
 /// Trait to manipulate global Kata configuration information.
 pub trait ConfigPlugin: Send + Sync {
@@ -202,10 +221,41 @@ impl TomlConfig {
                     DEFAULT_AGENT_DBG_CONSOLE_PORT.to_string(),
                 );
             }
+            if !cfg.guest_metrics_ports.is_empty() {
+                let ports: Vec<String> = cfg
+                    .guest_metrics_ports
+                    .iter()
+                    .map(|p| p.to_string())
+                    .collect();
+                kv.insert(GUEST_METRICS_PORTS_OPTION.to_string(), ports.join(","));
+            }
+            if cfg.enable_signature_verification {
+                kv.insert(
+                    SIGNATURE_VERIFICATION_OPTION.to_string(),
+                    "true".to_string(),
+                );
+                if !cfg.image_policy_file.is_empty() {
+                    kv.insert(
+                        IMAGE_POLICY_FILE_OPTION.to_string(),
+                        cfg.image_policy_file.clone(),
+                    );
+                }
+            }
         }
         Ok(kv)
     }
 
+    /// Dump the effective configuration as TOML, with the secrets redacted.
+    ///
+    /// It's the configuration after the drop-ins, the annotations and the adjustments are
+    /// applied, to find out which values are really used.
+    pub fn to_redacted_toml(&self) -> Result<String> {
+        let mut value =
+            toml::Value::try_from(self).map_err(|e| eother!("serialize config: {}", e))?;
+        redact_config_value(&mut value);
+        toml::to_string_pretty(&value).map_err(|e| eother!("serialize config: {}", e))
+    }
+
     /// Probe configuration file according to the default configuration file list.
     pub fn get_default_config_file() -> Result<PathBuf> {
         for f in default::DEFAULT_RUNTIME_CONFIGURATIONS.iter() {
@@ -218,6 +268,44 @@ impl TomlConfig {
     }
 }
 
+fn redact_config_value(value: &mut toml::Value) {
+    match value {
+        toml::Value::Table(table) => {
+            for (key, value) in table.iter_mut() {
+                let key = key.to_lowercase();
+                if REDACTED_CONFIG_KEYS.iter().any(|k| key.contains(k)) {
+                    // the unset secrets are kept to show they're unset
+                    if !matches!(value, toml::Value::String(s) if s.is_empty()) {
+                        *value = toml::Value::String(REDACTED_CONFIG_VALUE.to_string());
+                    }
+                } else {
+                    redact_config_value(value);
+                }
+            }
+        }
+        toml::Value::Array(array) => array.iter_mut().for_each(redact_config_value),
+        _ => {}
+    }
+}
+
+lazy_static! {
+    // `NAME=value` with the name containing one of the redacted words, the value ends at the
+    // separators of the command lines, the env lists and the json strings
+    static ref SECRET_ENV: Regex = Regex::new(&format!(
+        r#"(?i)\b([\w.-]*(?:{})[\w.-]*)=[^\s"'\\,]*"#,
+        REDACTED_CONFIG_KEYS.join("|")
+    ))
+    .unwrap();
+}
+
+/// Redact the values of the environment variables looking like secrets in `text`, e.g.
+/// `DB_PASSWORD=pw` in a command line, in the env of a process or in the logs.
+pub fn redact_secret_env(text: &str) -> String {
+    SECRET_ENV
+        .replace_all(text, format!("${{1}}={}", REDACTED_CONFIG_VALUE).as_str())
+        .into_owned()
+}
+
 /// Validate the `path` matches one of the pattern in `patterns`.
 ///
 /// Each member in `patterns` is a path pattern as described by glob(3)
@@ -369,6 +457,7 @@ mod tests {
             enable_tracing: true,
             container_pipe_size: 20,
             debug_console_enabled: true,
+            guest_metrics_ports: vec![9400, 9100],
             ..Default::default()
         };
         let agent_name = "test_agent";
@@ -381,5 +470,49 @@ mod tests {
         assert_eq!(kv.get("agent.container_pipe_size").unwrap(), "20");
         kv.get("agent.debug_console").unwrap();
         assert_eq!(kv.get("agent.debug_console_vport").unwrap(), "1026"); // 1026 is the default port
+        assert_eq!(kv.get("agent.guest_metrics_ports").unwrap(), "9400,9100");
+    }
+
+    #[test]
+    fn test_to_redacted_toml() {
+        let mut config = TomlConfig::default();
+        config.runtime.jaeger_endpoint = "http://localhost:14268/api/traces".to_string();
+        config.runtime.jaeger_user = "kata".to_string();
+        config.runtime.jaeger_password = "pw".to_string();
+
+        let dump = config.to_redacted_toml().unwrap();
+        assert!(!dump.contains("\"pw\""));
+        let value: toml::Value = toml::from_str(&dump).unwrap();
+        let runtime = &value["runtime"];
+        assert_eq!(
+            runtime["jaeger_password"].as_str(),
+            Some(REDACTED_CONFIG_VALUE)
+        );
+        assert_eq!(runtime["jaeger_user"].as_str(), Some("kata"));
+        assert_eq!(
+            runtime["jaeger_endpoint"].as_str(),
+            Some("http://localhost:14268/api/traces")
+        );
+
+        config.runtime.jaeger_password.clear();
+        let value: toml::Value = toml::from_str(&config.to_redacted_toml().unwrap()).unwrap();
+        assert_eq!(value["runtime"]["jaeger_password"].as_str(), Some(""));
+    }
+
+    #[test]
+    fn test_redact_secret_env() {
+        assert_eq!(
+            redact_secret_env("app --db DB_PASSWORD=pw HOME=/root aws.token=t"),
+            "app --db DB_PASSWORD=<redacted> HOME=/root aws.token=<redacted>"
+        );
+        assert_eq!(
+            redact_secret_env(r#"{"env":["PATH=/bin","API_Secret=s3cr3t","GH_TOKEN="]}"#),
+            r#"{"env":["PATH=/bin","API_Secret=<redacted>","GH_TOKEN=<redacted>"]}"#
+        );
+        assert_eq!(
+            redact_secret_env(r#"msg="create container" env=\"MY_CREDENTIALS=c\""#),
+            r#"msg="create container" env=\"MY_CREDENTIALS=<redacted>\""#
+        );
+        assert_eq!(redact_secret_env("no secrets"), "no secrets");
     }
 }
@@ -14,6 +14,25 @@ use crate::{eother, validate_path};
 /// Type of runtime VirtContainer.
 pub const RUNTIME_NAME_VIRTCONTAINER: &str = "virt_container";
 
+/// Check the agent in the guest is serving before the sandbox is started.
+pub const SANDBOX_READINESS_CHECK_AGENT: &str = "agent";
+/// Check the pod network is configured in the guest before the sandbox is started.
+pub const SANDBOX_READINESS_CHECK_NETWORK: &str = "network";
+
+/// Handle the volumes under the sensitive host paths as any other volumes.
+pub const SENSITIVE_HOST_PATH_ALLOW: &str = "allow";
+/// Drop the volumes under the sensitive host paths from the container.
+pub const SENSITIVE_HOST_PATH_SKIP: &str = "skip";
+/// Refuse the containers with volumes under the sensitive host paths.
+pub const SENSITIVE_HOST_PATH_REJECT: &str = "reject";
+
+/// Stop reading the output of the processes from the guest while the host doesn't consume it.
+pub const PROCESS_OUTPUT_POLICY_BLOCK: &str = "block";
+/// Keep reading the output of the processes, and drop what the host can't consume in time.
+pub const PROCESS_OUTPUT_POLICY_DROP: &str = "drop";
+/// Minimal size in KiB of the process output buffer of the shim.
+pub const MIN_PROCESS_OUTPUT_BUFFER_KB: u32 = 128;
+
 /// Kata runtime configuration information.
 #[derive(Debug, Default, Deserialize, Serialize)]
 pub struct Runtime {
@@ -64,6 +83,14 @@ pub struct Runtime {
     #[serde(default)]
     pub disable_new_netns: bool,
 
+    /// Interval in seconds to resync the neighbors of the pod network namespace to the guest.
+    ///
+    /// With `internetworking_model=tcfilter`, the neighbors learned in the pod network
+    /// namespace after the sandbox starts, e.g. by gratuitous ARP, are unknown to the guest.
+    /// The new and changed neighbors are pushed to the guest periodically if it's not 0.
+    #[serde(default)]
+    pub neighbor_resync_interval_secs: u64,
+
     /// If specified, sandbox_bind_mounts identifies host paths to be mounted into the sandboxes
     /// shared path.
     ///
@@ -113,6 +140,176 @@ pub struct Runtime {
     #[serde(default)]
     pub disable_guest_seccomp: bool,
 
+    /// If enabled, the host devices listed in the spec of privileged containers are not passed
+    /// into the VM, privileged containers only get the devices of the guest.
+    #[serde(default)]
+    pub privileged_without_host_devices: bool,
+
+    /// If enabled, privileged containers are refused, takes precedence over
+    /// `privileged_without_host_devices`.
+    #[serde(default)]
+    pub deny_privileged: bool,
+
+    /// Action taken on the host path volumes under `sensitive_host_paths`, before they're
+    /// shared into the VM.
+    ///
+    /// Options:
+    /// - allow: the volumes are handled as any other volumes, it's the default.
+    /// - skip: the volumes are dropped from the container.
+    /// - reject: the container is refused.
+    ///
+    /// Every skipped or rejected volume is recorded in an audit log entry.
+    #[serde(default)]
+    pub sensitive_host_path_action: String,
+
+    /// Host paths the volumes of which are subject to `sensitive_host_path_action`, the
+    /// default value is ["/dev", "/sys", "/proc", "/boot"].
+    #[serde(default)]
+    pub sensitive_host_paths: Vec<String>,
+
+    /// Host paths under `sensitive_host_paths` which are still allowed to be used as volumes.
+    #[serde(default)]
+    pub sensitive_host_path_allowlist: Vec<String>,
+
+    /// Checks done before the sandbox start is reported successful, so that containers aren't
+    /// created in a half-initialized sandbox.
+    ///
+    /// Options:
+    /// - agent: the agent in the guest is serving.
+    /// - network: the interfaces of the pod network are configured in the guest.
+    #[serde(default)]
+    pub sandbox_readiness_checks: Vec<String>,
+
+    /// Timeout in milliseconds to stop the agent connection on sandbox shutdown, 0 to use the
+    /// default 3000ms. The connection is abandoned if it doesn't stop in time.
+    #[serde(default)]
+    pub shutdown_agent_timeout_ms: u64,
+
+    /// Timeout in milliseconds to shut down the VM on sandbox stop, 0 to use the default
+    /// 10000ms. The hypervisor processes are killed by SIGKILL if it doesn't shut down in time.
+    #[serde(default)]
+    pub shutdown_vm_timeout_ms: u64,
+
+    /// Timeout in milliseconds for virtiofsd to exit on sandbox shutdown, 0 to use the default
+    /// 3000ms. Virtiofsd is killed by SIGKILL if it doesn't exit in time.
+    #[serde(default)]
+    pub shutdown_virtiofsd_timeout_ms: u64,
+
+    /// Timeout in milliseconds to restore and destroy the pod network namespace on sandbox
+    /// shutdown, 0 to use the default 5000ms. The teardown is abandoned if it isn't done in time.
+    #[serde(default)]
+    pub shutdown_netns_timeout_ms: u64,
+
+    /// Timeout in milliseconds to run the guest pre-stop hooks of the agent on sandbox stop, 0
+    /// to use the default 30000ms. The hooks left are skipped if they aren't done in time.
+    #[serde(default)]
+    pub shutdown_guest_hooks_timeout_ms: u64,
+
+    /// Maximum number of concurrent connections to the ttrpc server of the shim, 0 for no limit.
+    /// The connections over the limit are closed right after being accepted.
+    #[serde(default)]
+    pub ttrpc_max_connections: u32,
+
+    /// Timeout in seconds after which an idle connection to the ttrpc server of the shim is
+    /// closed, 0 to keep the idle connections. A connection with requests in flight, e.g. a
+    /// `Wait`, isn't idle.
+    #[serde(default)]
+    pub ttrpc_idle_timeout_secs: u64,
+
+    /// Check the credentials (SO_PEERCRED) of the peers connecting to the task and the
+    /// management sockets of the shim. Only root, the user of the shim and the allowed users and
+    /// groups may issue requests then, and the management socket is restricted to its owner and
+    /// the first allowed group.
+    #[serde(default)]
+    pub enable_peer_cred_check: bool,
+
+    /// Users allowed to connect to the sockets of the shim besides root and the user of the
+    /// shim, if `enable_peer_cred_check` is set.
+    #[serde(default)]
+    pub peer_allowed_uids: Vec<u32>,
+
+    /// Groups allowed to connect to the sockets of the shim, e.g. a monitoring group, if
+    /// `enable_peer_cred_check` is set. The effective group of the peer process is checked.
+    #[serde(default)]
+    pub peer_allowed_gids: Vec<u32>,
+
+    /// Path of the file the audit records of the privileged operations are appended to, e.g.
+    /// setting iptables, enabling the debug console, copying files and passing devices through.
+    /// The records are always in the shim log with the `audit` key set.
+    #[serde(default)]
+    pub audit_log_path: String,
+
+    /// Directory the guest memory can be dumped to through the shim management API, for the
+    /// forensic analysis. The dump is disabled if it's empty.
+    #[serde(default)]
+    pub memory_dump_dir: String,
+
+    /// Time in milliseconds the stats of the containers and the metrics of the agent and the
+    /// hypervisor are cached for, so that the repeated requests, e.g. from the kubelet for every
+    /// container, are served without asking the agent again. 0 to disable the cache.
+    #[serde(default)]
+    pub stats_cache_ttl_ms: u64,
+
+    /// Timeout in milliseconds to create a container, 0 for no limit. The partially created
+    /// container is cleaned up if it isn't created in time, e.g. when the agent hangs.
+    #[serde(default)]
+    pub container_create_timeout_ms: u64,
+
+    /// Timeout in milliseconds to start a container, 0 for no limit. The container is cleaned up
+    /// if it isn't started in time.
+    #[serde(default)]
+    pub container_start_timeout_ms: u64,
+
+    /// Timeout in milliseconds to stop a container once its init process exited, 0 for no limit.
+    /// The host resources of the container are released if it isn't stopped in time.
+    #[serde(default)]
+    pub container_stop_timeout_ms: u64,
+
+    /// How the output of the container processes is handled when it's produced faster than the
+    /// host consumes it:
+    /// - block: it isn't read from the guest meanwhile, so the processes block on their full
+    ///   pipes (default)
+    /// - drop: it's kept being read, and what exceeds the buffer of the shim is dropped and
+    ///   replaced by a marker giving the size dropped, so that the chatty processes never block
+    #[serde(default)]
+    pub process_output_policy: String,
+
+    /// Size in KiB of the output buffered by the shim for each stream of a process with the drop
+    /// output policy, 1024 by default.
+    #[serde(default)]
+    pub process_output_buffer_kb: u32,
+
+    /// Interval in milliseconds to resize the guest memory to the sum of the memory limits of
+    /// the running containers plus `memory_headroom_mb`, 0 to disable it. The memory is grown
+    /// by virtio-mem and shrunk by the balloon, as enabled in the hypervisor configuration.
+    #[serde(default)]
+    pub memory_reconcile_interval_ms: u64,
+
+    /// Memory in MiB kept in the guest on top of the memory limits of the containers, for the
+    /// guest kernel, the agent and the containers without a limit. 0 to use `default_memory`.
+    #[serde(default)]
+    pub memory_headroom_mb: u32,
+
+    /// Check that the vcpus and the memory of the VM fit the resources currently available on
+    /// the host before it's started, so that the sandbox creation fails fast rather than the VM
+    /// running the node out of memory while booting.
+    #[serde(default)]
+    pub host_resource_check: bool,
+
+    /// Memory in MiB of the host kept for the host itself by the host resource check.
+    #[serde(default)]
+    pub host_reserved_memory_mb: u32,
+
+    /// Number of the CPUs of the host kept for the host itself by the host resource check.
+    #[serde(default)]
+    pub host_reserved_cpus: u32,
+
+    /// Intel RDT schemata of the resctrl group the vCPU threads are assigned to, one
+    /// `<resource>:<domain>=<value>;...` line per resource, e.g. `L3:0=ff;1=ff` or `MB:0=50`.
+    /// It overrides the `linux.intelRdt` schemata of the sandbox spec. Empty to use the spec only.
+    #[serde(default)]
+    pub rdt_schemata: String,
+
     /// Determines how VFIO devices should be be presented to the container.
     ///
     /// Options:
@@ -128,6 +325,33 @@ pub struct Runtime {
     #[serde(default)]
     pub vfio_mode: String,
 
+    /// If enabled, the sandbox (pause) container will use the pause bundle built into the guest
+    /// image instead of sharing the pause image rootfs from host.
+    ///
+    /// This saves one shared filesystem mount per pod and speeds up pod creation, but requires
+    /// a guest image which provides the pause bundle at `/pause_bundle`.
+    #[serde(default)]
+    pub use_builtin_pause: bool,
+
+    /// Host directory to store the persistent guest image cache disks, empty to disable it.
+    ///
+    /// In guest-pull mode, container images are pulled and unpacked inside the guest. If
+    /// enabled, a cache disk is attached to each sandbox and reused across restarts of the
+    /// same pod, so image layers pulled before don't need to be pulled again.
+    #[serde(default)]
+    pub guest_image_cache_dir: String,
+
+    /// Size in MiB of each guest image cache disk.
+    #[serde(default)]
+    pub guest_image_cache_disk_size_mb: u32,
+
+    /// Total size limit in MiB of all the cache disks under `guest_image_cache_dir`.
+    ///
+    /// When the limit is exceeded, the least recently used cache disks which are not in use
+    /// will be evicted. Zero means unlimited.
+    #[serde(default)]
+    pub guest_image_cache_limit_mb: u64,
+
     /// Vendor customized runtime configuration.
     #[serde(default, flatten)]
     pub vendor: RuntimeVendor,
@@ -148,6 +372,25 @@ pub struct Runtime {
     /// to the hypervisor.
     #[serde(default)]
     pub dan_conf: String,
+
+    /// Base directory in the BPF filesystem of the eBPF programs pinned by the CNI plugins, empty
+    /// disables the offload of the network policies.
+    ///
+    /// An eBPF datapath pins the tc program of an interface of the pod at
+    /// `<network_bpf_dir>/<sandbox id>/<interface name>` before the sandbox is created, and the
+    /// program is attached to the ingress of the tap of the interface on the host, so that the
+    /// policies of the datapath apply to the traffic of the guest.
+    #[serde(default)]
+    pub network_bpf_dir: String,
+
+    /// Directory of the NRI (Node Resource Interface) plugins run by the shim before and after
+    /// the creation of the sandbox and of its containers, empty disables them.
+    ///
+    /// The plugins are the executables of the directory, run in the order of their names. They
+    /// may adjust the mounts, the env vars, the annotations and the resources of a container
+    /// before it's created.
+    #[serde(default)]
+    pub nri_plugin_dir: String,
 }
 
 impl ConfigOps for Runtime {
@@ -157,6 +400,30 @@ impl ConfigOps for Runtime {
             conf.runtime.internetworking_model = default::DEFAULT_INTERNETWORKING_MODEL.to_owned();
         }
 
+        if conf.runtime.sensitive_host_path_action.is_empty() {
+            conf.runtime.sensitive_host_path_action = SENSITIVE_HOST_PATH_ALLOW.to_owned();
+        }
+        if conf.runtime.sensitive_host_paths.is_empty() {
+            conf.runtime.sensitive_host_paths = default::DEFAULT_SENSITIVE_HOST_PATHS
+                .iter()
+                .map(|p| p.to_string())
+                .collect();
+        }
+
+        if conf.runtime.process_output_policy.is_empty() {
+            conf.runtime.process_output_policy = PROCESS_OUTPUT_POLICY_BLOCK.to_owned();
+        }
+        if conf.runtime.process_output_buffer_kb == 0 {
+            conf.runtime.process_output_buffer_kb = default::DEFAULT_PROCESS_OUTPUT_BUFFER_KB;
+        }
+
+        if !conf.runtime.guest_image_cache_dir.is_empty()
+            && conf.runtime.guest_image_cache_disk_size_mb == 0
+        {
+            conf.runtime.guest_image_cache_disk_size_mb =
+                default::DEFAULT_GUEST_IMAGE_CACHE_DISK_SIZE_MB;
+        }
+
         for bind in conf.runtime.sandbox_bind_mounts.iter_mut() {
             // Split the bind mount, canonicalize the path and then append rw mode to it.
             let (real_path, mode) = split_bind_mounts(bind);
@@ -194,6 +461,93 @@ impl ConfigOps for Runtime {
             ));
         }
 
+        for check in conf.runtime.sandbox_readiness_checks.iter() {
+            if check != SANDBOX_READINESS_CHECK_AGENT && check != SANDBOX_READINESS_CHECK_NETWORK {
+                return Err(eother!(
+                    "Invalid sandbox readiness check `{}` in configuration file",
+                    check
+                ));
+            }
+        }
+
+        let action = &conf.runtime.sensitive_host_path_action;
+        if !action.is_empty()
+            && action != SENSITIVE_HOST_PATH_ALLOW
+            && action != SENSITIVE_HOST_PATH_SKIP
+            && action != SENSITIVE_HOST_PATH_REJECT
+        {
+            return Err(eother!(
+                "Invalid sensitive_host_path_action `{}` in configuration file",
+                action
+            ));
+        }
+        for path in conf
+            .runtime
+            .sensitive_host_paths
+            .iter()
+            .chain(conf.runtime.sensitive_host_path_allowlist.iter())
+        {
+            if !Path::new(path).is_absolute() {
+                return Err(eother!(
+                    "sensitive host path `{}` is not an absolute path",
+                    path
+                ));
+            }
+        }
+
+        if conf.runtime.deny_privileged && conf.runtime.privileged_without_host_devices {
+            warn!(
+                sl!(),
+                "privileged_without_host_devices is ignored as deny_privileged is enabled"
+            );
+        }
+
+        let policy = &conf.runtime.process_output_policy;
+        if !policy.is_empty()
+            && policy != PROCESS_OUTPUT_POLICY_BLOCK
+            && policy != PROCESS_OUTPUT_POLICY_DROP
+        {
+            return Err(eother!(
+                "Invalid process_output_policy `{}` in configuration file",
+                policy
+            ));
+        }
+        let buffer_kb = conf.runtime.process_output_buffer_kb;
+        if buffer_kb != 0 && buffer_kb < MIN_PROCESS_OUTPUT_BUFFER_KB {
+            return Err(eother!(
+                "process_output_buffer_kb {} is less than {}",
+                buffer_kb,
+                MIN_PROCESS_OUTPUT_BUFFER_KB
+            ));
+        }
+
+        let bpf_dir = &conf.runtime.network_bpf_dir;
+        if !bpf_dir.is_empty() && !Path::new(bpf_dir).is_absolute() {
+            return Err(eother!(
+                "network_bpf_dir `{}` is not an absolute path",
+                bpf_dir
+            ));
+        }
+
+        let nri_plugin_dir = &conf.runtime.nri_plugin_dir;
+        if !nri_plugin_dir.is_empty() && !Path::new(nri_plugin_dir).is_absolute() {
+            return Err(eother!(
+                "nri_plugin_dir `{}` is not an absolute path",
+                nri_plugin_dir
+            ));
+        }
+
+        let cache_limit_mb = conf.runtime.guest_image_cache_limit_mb;
+        if cache_limit_mb != 0
+            && (conf.runtime.guest_image_cache_disk_size_mb as u64) > cache_limit_mb
+        {
+            return Err(eother!(
+                "guest_image_cache_disk_size_mb {} exceeds guest_image_cache_limit_mb {}",
+                conf.runtime.guest_image_cache_disk_size_mb,
+                cache_limit_mb
+            ));
+        }
+
         for bind in conf.runtime.sandbox_bind_mounts.iter() {
             // Just validate the real_path.
             let (real_path, _mode) = split_bind_mounts(bind);
@@ -279,6 +633,66 @@ vfio_mode = "vfio,guest-kernel"
 [runtime]
 enable_debug = true
 vfio_mode = "guest_kernel"
+"#;
+        let config: TomlConfig = TomlConfig::load(content).unwrap();
+        config.validate().unwrap_err();
+
+        let content = r#"
+[runtime]
+sandbox_readiness_checks = ["agent", "storage"]
+"#;
+        let config: TomlConfig = TomlConfig::load(content).unwrap();
+        config.validate().unwrap_err();
+
+        let content = r#"
+[runtime]
+sensitive_host_path_action = "deny"
+"#;
+        let config: TomlConfig = TomlConfig::load(content).unwrap();
+        config.validate().unwrap_err();
+
+        let content = r#"
+[runtime]
+sensitive_host_path_action = "skip"
+sensitive_host_path_allowlist = ["dev/fuse"]
+"#;
+        let config: TomlConfig = TomlConfig::load(content).unwrap();
+        config.validate().unwrap_err();
+
+        let content = r#"
+[runtime]
+guest_image_cache_dir = "/var/lib/kata/image-cache"
+guest_image_cache_disk_size_mb = 2048
+guest_image_cache_limit_mb = 1024
+"#;
+        let config: TomlConfig = TomlConfig::load(content).unwrap();
+        config.validate().unwrap_err();
+
+        let content = r#"
+[runtime]
+process_output_policy = "discard"
+"#;
+        let config: TomlConfig = TomlConfig::load(content).unwrap();
+        config.validate().unwrap_err();
+
+        let content = r#"
+[runtime]
+process_output_policy = "drop"
+process_output_buffer_kb = 64
+"#;
+        let config: TomlConfig = TomlConfig::load(content).unwrap();
+        config.validate().unwrap_err();
+
+        let content = r#"
+[runtime]
+network_bpf_dir = "sys/fs/bpf/kata"
+"#;
+        let config: TomlConfig = TomlConfig::load(content).unwrap();
+        config.validate().unwrap_err();
+
+        let content = r#"
+[runtime]
+nri_plugin_dir = "opt/nri/plugins"
 "#;
         let config: TomlConfig = TomlConfig::load(content).unwrap();
         config.validate().unwrap_err();
@@ -302,6 +716,7 @@ jaeger_password = "pw"
 enable_pprof = true
 disable_guest_seccomp = true
 vfio_mode = "vfio"
+use_builtin_pause = true
 field_should_be_ignored = true
 "#;
         let config: TomlConfig = TomlConfig::load(content).unwrap();
@@ -316,8 +731,17 @@ field_should_be_ignored = true
         assert_eq!(config.runtime.sandbox_bind_mounts.len(), 0);
         assert!(config.runtime.sandbox_cgroup_only);
         assert!(config.runtime.enable_tracing);
+        assert!(config.runtime.use_builtin_pause);
         assert!(config.runtime.is_experiment_enabled("a"));
         assert!(config.runtime.is_experiment_enabled("b"));
         assert!(!config.runtime.is_experiment_enabled("c"));
+        assert_eq!(
+            config.runtime.process_output_policy,
+            PROCESS_OUTPUT_POLICY_BLOCK
+        );
+        assert_eq!(
+            config.runtime.process_output_buffer_kb,
+            default::DEFAULT_PROCESS_OUTPUT_BUFFER_KB
+        );
     }
 }
@@ -96,6 +96,64 @@ pub fn container_type_with_id(spec: &oci::Spec) -> (ContainerType, Option<String
     (container_type, sid)
 }
 
+/// Kubernetes identity of a pod sandbox or container, from OCI annotations.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct K8sMetadata {
+    /// Name of the pod.
+    pub pod_name: Option<String>,
+    /// Namespace of the pod.
+    pub pod_namespace: Option<String>,
+    /// UID of the pod.
+    pub pod_uid: Option<String>,
+    /// Name of the container, not set for the pod sandbox.
+    pub container_name: Option<String>,
+}
+
+impl K8sMetadata {
+    /// Key-value pairs of the metadata which is set, to label metrics and logs.
+    pub fn labels(&self) -> Vec<(&'static str, String)> {
+        [
+            ("pod_name", &self.pod_name),
+            ("pod_namespace", &self.pod_namespace),
+            ("pod_uid", &self.pod_uid),
+            ("container_name", &self.container_name),
+        ]
+        .iter()
+        .filter_map(|(k, v)| (*v).clone().map(|v| (*k, v)))
+        .collect()
+    }
+}
+
+/// Get the Kubernetes identity of a pod sandbox or container from OCI annotations
+/// set by Containerd or CRI-O.
+pub fn metadata(spec: &oci::Spec) -> K8sMetadata {
+    let get = |keys: &[&str]| {
+        keys.iter()
+            .find_map(|k| spec.annotations.get(*k))
+            .filter(|v| !v.is_empty())
+            .cloned()
+    };
+
+    K8sMetadata {
+        pod_name: get(&[
+            annotations::cri_containerd::SANDBOX_NAME_LABEL_KEY,
+            annotations::crio::SANDBOX_NAME_LABEL_KEY,
+        ]),
+        pod_namespace: get(&[
+            annotations::cri_containerd::SANDBOX_NAMESPACE_LABEL_KEY,
+            annotations::crio::SANDBOX_NAMESPACE_LABEL_KEY,
+        ]),
+        pod_uid: get(&[
+            annotations::cri_containerd::SANDBOX_UID_LABEL_KEY,
+            annotations::crio::SANDBOX_UID_LABEL_KEY,
+        ]),
+        container_name: get(&[
+            annotations::cri_containerd::CONTAINER_NAME_LABEL_KEY,
+            annotations::crio::CONTAINER_NAME_LABEL_KEY,
+        ]),
+    }
+}
+
 // count_files will return the number of files within a given path.
 // If the total number of
 // files observed is greater than limit, break and return -1
@@ -261,6 +319,50 @@ mod tests {
         assert!(!is_secret(path));
     }
 
+    #[test]
+    fn test_metadata() {
+        let mut spec = oci::Spec::default();
+        assert_eq!(metadata(&spec), K8sMetadata::default());
+        assert!(metadata(&spec).labels().is_empty());
+
+        // cri containerd container
+        spec.annotations = [
+            (annotations::cri_containerd::SANDBOX_NAME_LABEL_KEY, "nginx"),
+            (
+                annotations::cri_containerd::SANDBOX_NAMESPACE_LABEL_KEY,
+                "default",
+            ),
+            (annotations::cri_containerd::SANDBOX_UID_LABEL_KEY, "1234"),
+            (annotations::cri_containerd::CONTAINER_NAME_LABEL_KEY, "web"),
+        ]
+        .iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+        let meta = metadata(&spec);
+        assert_eq!(meta.pod_name.as_deref(), Some("nginx"));
+        assert_eq!(meta.pod_namespace.as_deref(), Some("default"));
+        assert_eq!(meta.pod_uid.as_deref(), Some("1234"));
+        assert_eq!(meta.container_name.as_deref(), Some("web"));
+        assert_eq!(meta.labels().len(), 4);
+
+        // crio sandbox
+        spec.annotations = [
+            (annotations::crio::SANDBOX_NAME_LABEL_KEY, "nginx"),
+            (annotations::crio::SANDBOX_NAMESPACE_LABEL_KEY, "default"),
+        ]
+        .iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+        let meta = metadata(&spec);
+        assert_eq!(
+            meta.labels(),
+            vec![
+                ("pod_name", "nginx".to_string()),
+                ("pod_namespace", "default".to_string())
+            ]
+        );
+    }
+
     #[test]
     fn test_container_type() {
         let sid = "sid".to_string();
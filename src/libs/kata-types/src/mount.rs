@@ -30,6 +30,17 @@ pub const KATA_MOUNT_INFO_FILE_NAME: &str = "mountInfo.json";
 /// Specify `fsgid` for a volume or mount, `fsgid=1`.
 pub const KATA_MOUNT_OPTION_FS_GID: &str = "fsgid";
 
+/// Storage driver option to limit the size of the storage in bytes with project quota,
+/// `quota_bytes=1073741824`. The storage must be mounted with the `prjquota` option.
+pub const KATA_STORAGE_DRIVER_OPTION_QUOTA: &str = "quota_bytes";
+
+/// Storage driver option to place the upper dir of a writable overlay storage in a guest tmpfs
+/// of the size in bytes, `tmpfs_size_bytes=1073741824`.
+pub const KATA_STORAGE_DRIVER_OPTION_TMPFS_SIZE: &str = "tmpfs_size_bytes";
+
+/// Option of overlay storages to create their upper and work dirs in the guest.
+pub const KATA_OVERLAY_RW_OPTION: &str = "io.katacontainers.fs-opt.overlay-rw";
+
 /// KATA_DIRECT_VOLUME_ROOT_PATH is the root path used for concatenating with the direct-volume mount info file path
 pub const KATA_DIRECT_VOLUME_ROOT_PATH: &str = "/run/kata-containers/shared/direct-volumes";
 
@@ -59,6 +70,106 @@ pub const KATA_VIRTUAL_VOLUME_LAYER_NYDUS_FS: &str = "layer_nydus_fs";
 /// Download and extra container image inside guest vm.
 pub const KATA_VIRTUAL_VOLUME_IMAGE_GUEST_PULL: &str = "image_guest_pull";
 
+/// Mount option carrying a base64 encoded `KataVirtualVolume`, set by the snapshotters,
+/// `io.katacontainers.volume=<base64>`.
+pub const KATA_VIRTUAL_VOLUME_OPTION: &str = "io.katacontainers.volume";
+
+/// Mount option carrying the uid mappings of an idmapped mount,
+/// `uidmap=<container_id>:<host_id>:<size>[,...]`.
+pub const MOUNT_OPTION_UID_MAP: &str = "uidmap";
+
+/// Mount option carrying the gid mappings of an idmapped mount,
+/// `gidmap=<container_id>:<host_id>:<size>[,...]`.
+pub const MOUNT_OPTION_GID_MAP: &str = "gidmap";
+
+/// Propagation of the mount and umount events of a mount point, the recursive ones apply to the
+/// mount points below it too.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, serde::Serialize, serde::Deserialize)]
+pub enum MountPropagation {
+    /// `shared`
+    Shared,
+    /// `rshared`
+    RShared,
+    /// `slave`
+    Slave,
+    /// `rslave`
+    RSlave,
+    /// `private`
+    Private,
+    /// `rprivate`
+    RPrivate,
+    /// `unbindable`
+    Unbindable,
+    /// `runbindable`
+    RUnbindable,
+}
+
+impl MountPropagation {
+    /// Get the propagation of the mount option, `None` for the other options.
+    pub fn from_option(option: &str) -> Option<Self> {
+        match option {
+            "shared" => Some(Self::Shared),
+            "rshared" => Some(Self::RShared),
+            "slave" => Some(Self::Slave),
+            "rslave" => Some(Self::RSlave),
+            "private" => Some(Self::Private),
+            "rprivate" => Some(Self::RPrivate),
+            "unbindable" => Some(Self::Unbindable),
+            "runbindable" => Some(Self::RUnbindable),
+            _ => None,
+        }
+    }
+
+    /// Get the mount option of the propagation.
+    pub fn as_option(&self) -> &'static str {
+        match self {
+            Self::Shared => "shared",
+            Self::RShared => "rshared",
+            Self::Slave => "slave",
+            Self::RSlave => "rslave",
+            Self::Private => "private",
+            Self::RPrivate => "rprivate",
+            Self::Unbindable => "unbindable",
+            Self::RUnbindable => "runbindable",
+        }
+    }
+}
+
+/// A range of ids of an idmapped mount.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct MountIdMapping {
+    /// The first id in the container.
+    pub container_id: u32,
+    /// The first id on the host.
+    pub host_id: u32,
+    /// The number of ids.
+    pub size: u32,
+}
+
+impl MountIdMapping {
+    /// Parse the mappings of a `uidmap` or `gidmap` option value, `0:1000:65536[,...]`.
+    pub fn parse_mappings(value: &str) -> Result<Vec<Self>> {
+        value
+            .split(',')
+            .map(|mapping| {
+                let ids = mapping
+                    .split(':')
+                    .map(|id| id.trim().parse::<u32>())
+                    .collect::<std::result::Result<Vec<u32>, _>>()
+                    .with_context(|| format!("invalid id mapping {:?}", mapping))?;
+                match ids[..] {
+                    [container_id, host_id, size] if size > 0 => Ok(Self {
+                        container_id,
+                        host_id,
+                        size,
+                    }),
+                    _ => Err(anyhow!("invalid id mapping {:?}", mapping)),
+                }
+            })
+            .collect()
+    }
+}
+
 /// Information about a mount.
 #[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
 pub struct Mount {
@@ -80,6 +191,15 @@ pub struct Mount {
     pub host_shared_fs_path: Option<PathBuf>,
     /// Whether to mount the mountpoint in readonly mode
     pub read_only: bool,
+    /// Propagation of the mount point, the last one of the options.
+    #[serde(default)]
+    pub propagation: Option<MountPropagation>,
+    /// Uid mappings of an idmapped mount, they aren't kept in the options.
+    #[serde(default)]
+    pub uid_mappings: Vec<MountIdMapping>,
+    /// Gid mappings of an idmapped mount, they aren't kept in the options.
+    #[serde(default)]
+    pub gid_mappings: Vec<MountIdMapping>,
 }
 
 impl Mount {
@@ -87,6 +207,47 @@ impl Mount {
     pub fn option_size(&self) -> usize {
         self.options.iter().map(|v| v.len() + 1).sum()
     }
+
+    /// Set the read-only mode, the propagation and the id mappings of the mount from its
+    /// options, the id mappings are removed from the options as they aren't mount flags.
+    pub fn parse_options(&mut self) -> Result<()> {
+        let mut options = Vec::with_capacity(self.options.len());
+        for option in self.options.drain(..) {
+            match option.split_once('=') {
+                Some((MOUNT_OPTION_UID_MAP, value)) => {
+                    self.uid_mappings = MountIdMapping::parse_mappings(value)?
+                }
+                Some((MOUNT_OPTION_GID_MAP, value)) => {
+                    self.gid_mappings = MountIdMapping::parse_mappings(value)?
+                }
+                _ => options.push(option),
+            }
+        }
+        self.options = options;
+
+        for option in &self.options {
+            match option.as_str() {
+                "ro" => self.read_only = true,
+                "rw" => self.read_only = false,
+                option => {
+                    if let Some(propagation) = MountPropagation::from_option(option) {
+                        self.propagation = Some(propagation);
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether the mount is a bind mount.
+    pub fn is_bind(&self) -> bool {
+        self.options.iter().any(|o| o == "bind" || o == "rbind")
+    }
+
+    /// Whether the mount is idmapped.
+    pub fn is_idmapped(&self) -> bool {
+        !self.uid_mappings.is_empty() || !self.gid_mappings.is_empty()
+    }
 }
 
 /// DirectVolumeMountInfo contains the information needed by Kata
@@ -550,6 +711,73 @@ mod tests {
         assert!(!is_kata_special_volume("kata:"));
     }
 
+    #[test]
+    fn test_mount_parse_options() {
+        let mut mount = Mount {
+            options: vec![
+                "rbind".to_string(),
+                "ro".to_string(),
+                "private".to_string(),
+                "rslave".to_string(),
+                "uidmap=0:1000:65536".to_string(),
+                "gidmap=0:2000:1,1:3000:10".to_string(),
+            ],
+            ..Default::default()
+        };
+        mount.parse_options().unwrap();
+        assert!(mount.read_only);
+        assert!(mount.is_bind());
+        assert_eq!(mount.propagation, Some(MountPropagation::RSlave));
+        assert_eq!(mount.options, vec!["rbind", "ro", "private", "rslave"]);
+        assert!(mount.is_idmapped());
+        assert_eq!(
+            mount.uid_mappings,
+            vec![MountIdMapping {
+                container_id: 0,
+                host_id: 1000,
+                size: 65536
+            }]
+        );
+        assert_eq!(mount.gid_mappings.len(), 2);
+        assert_eq!(mount.gid_mappings[1].host_id, 3000);
+
+        let mut mount = Mount {
+            options: vec!["ro".to_string(), "rw".to_string()],
+            ..Default::default()
+        };
+        mount.parse_options().unwrap();
+        assert!(!mount.read_only);
+        assert!(!mount.is_bind());
+        assert!(mount.propagation.is_none());
+        assert!(!mount.is_idmapped());
+
+        for value in ["", "0:1000", "0:1000:0", "a:1000:1"] {
+            let mut mount = Mount {
+                options: vec![format!("uidmap={}", value)],
+                ..Default::default()
+            };
+            assert!(mount.parse_options().is_err(), "{}", value);
+        }
+    }
+
+    #[test]
+    fn test_mount_propagation() {
+        for option in [
+            "shared",
+            "rshared",
+            "slave",
+            "rslave",
+            "private",
+            "rprivate",
+            "unbindable",
+            "runbindable",
+        ] {
+            let propagation = MountPropagation::from_option(option).unwrap();
+            assert_eq!(propagation.as_option(), option);
+        }
+        assert!(MountPropagation::from_option("bind").is_none());
+    }
+
     #[test]
     fn test_split_bind_mounts() {
         let test01 = "xxx0:ro";
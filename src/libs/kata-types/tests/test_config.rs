@@ -5,19 +5,25 @@
 #[cfg(test)]
 mod tests {
     use kata_types::annotations::{
-        Annotation, KATA_ANNO_CFG_AGENT_CONTAINER_PIPE_SIZE, KATA_ANNO_CFG_AGENT_TRACE,
+        Annotation, KATA_ANNO_CFG_AGENT_CONTAINER_PIPE_SIZE, KATA_ANNO_CFG_AGENT_ENABLE_DNS_CACHE,
+        KATA_ANNO_CFG_AGENT_ENABLE_SIGNATURE_VERIFICATION, KATA_ANNO_CFG_AGENT_IMAGE_POLICY_FILE,
+        KATA_ANNO_CFG_AGENT_TRACE,
         KATA_ANNO_CFG_DISABLE_GUEST_SECCOMP, KATA_ANNO_CFG_ENABLE_PPROF,
-        KATA_ANNO_CFG_EXPERIMENTAL, KATA_ANNO_CFG_HYPERVISOR_BLOCK_DEV_CACHE_NOFLUSH,
+        KATA_ANNO_CFG_EXPERIMENTAL, KATA_ANNO_CFG_HYPERVISOR_BLOCK_DEV_AIO,
+        KATA_ANNO_CFG_HYPERVISOR_BLOCK_DEV_CACHE_NOFLUSH,
         KATA_ANNO_CFG_HYPERVISOR_BLOCK_DEV_DRIVER, KATA_ANNO_CFG_HYPERVISOR_CTLPATH,
         KATA_ANNO_CFG_HYPERVISOR_DEFAULT_MEMORY, KATA_ANNO_CFG_HYPERVISOR_DEFAULT_VCPUS,
         KATA_ANNO_CFG_HYPERVISOR_ENABLE_GUEST_SWAP, KATA_ANNO_CFG_HYPERVISOR_ENABLE_HUGEPAGES,
         KATA_ANNO_CFG_HYPERVISOR_ENABLE_IO_THREADS, KATA_ANNO_CFG_HYPERVISOR_ENABLE_SWAP,
         KATA_ANNO_CFG_HYPERVISOR_FILE_BACKED_MEM_ROOT_DIR,
-        KATA_ANNO_CFG_HYPERVISOR_GUEST_HOOK_PATH, KATA_ANNO_CFG_HYPERVISOR_JAILER_PATH,
+        KATA_ANNO_CFG_HYPERVISOR_GUEST_HOOK_PATH, KATA_ANNO_CFG_HYPERVISOR_HARDENED_GUEST,
+        KATA_ANNO_CFG_HYPERVISOR_JAILER_PATH, KATA_ANNO_CFG_HYPERVISOR_KERNEL_MODULES_BLACKLIST,
         KATA_ANNO_CFG_HYPERVISOR_KERNEL_PATH, KATA_ANNO_CFG_HYPERVISOR_MEMORY_PREALLOC,
         KATA_ANNO_CFG_HYPERVISOR_MEMORY_SLOTS, KATA_ANNO_CFG_HYPERVISOR_PATH,
+        KATA_ANNO_CFG_HYPERVISOR_SIZING_PROFILE,
         KATA_ANNO_CFG_HYPERVISOR_VHOSTUSER_STORE_PATH, KATA_ANNO_CFG_HYPERVISOR_VIRTIO_FS_DAEMON,
-        KATA_ANNO_CFG_HYPERVISOR_VIRTIO_FS_EXTRA_ARGS, KATA_ANNO_CFG_HYPERVISOR_VIRTIO_MEM,
+        KATA_ANNO_CFG_HYPERVISOR_VIRTIO_FS_EXTRA_ARGS, KATA_ANNO_CFG_HYPERVISOR_VIRTIO_FS_IS_DAX,
+        KATA_ANNO_CFG_HYPERVISOR_VIRTIO_FS_THREAD_POOL_SIZE, KATA_ANNO_CFG_HYPERVISOR_VIRTIO_MEM,
         KATA_ANNO_CFG_KERNEL_MODULES, KATA_ANNO_CFG_RUNTIME_NAME,
     };
     use kata_types::config::KataConfig;
@@ -76,6 +82,18 @@ mod tests {
             KATA_ANNO_CFG_AGENT_CONTAINER_PIPE_SIZE.to_string(),
             "3".to_string(),
         );
+        anno_hash.insert(
+            KATA_ANNO_CFG_AGENT_ENABLE_SIGNATURE_VERIFICATION.to_string(),
+            "true".to_string(),
+        );
+        anno_hash.insert(
+            KATA_ANNO_CFG_AGENT_IMAGE_POLICY_FILE.to_string(),
+            "kbs:///default/security-policy/test".to_string(),
+        );
+        anno_hash.insert(
+            KATA_ANNO_CFG_AGENT_ENABLE_DNS_CACHE.to_string(),
+            "true".to_string(),
+        );
         anno_hash.insert(
             KATA_ANNO_CFG_HYPERVISOR_PATH.to_string(),
             "./hypervisor_path".to_string(),
@@ -84,6 +102,10 @@ mod tests {
             KATA_ANNO_CFG_HYPERVISOR_BLOCK_DEV_DRIVER.to_string(),
             "device".to_string(),
         );
+        anno_hash.insert(
+            KATA_ANNO_CFG_HYPERVISOR_BLOCK_DEV_AIO.to_string(),
+            "io_uring".to_string(),
+        );
         anno_hash.insert(
             KATA_ANNO_CFG_HYPERVISOR_BLOCK_DEV_CACHE_NOFLUSH.to_string(),
             "false".to_string(),
@@ -183,10 +205,17 @@ mod tests {
             assert_eq!(ag.kernel_modules[3], "r33w");
             assert!(!ag.enable_tracing);
             assert_eq!(ag.container_pipe_size, 3);
+            assert!(ag.enable_signature_verification);
+            assert_eq!(
+                ag.image_policy_file,
+                "kbs:///default/security-policy/test"
+            );
+            assert!(ag.enable_dns_cache);
         }
         if let Some(hv) = KataConfig::get_default_config().get_hypervisor() {
             assert_eq!(hv.path, "./hypervisor_path".to_string());
             assert_eq!(hv.blockdev_info.block_device_driver, "device");
+            assert_eq!(hv.blockdev_info.block_device_aio, "io_uring");
             assert!(!hv.blockdev_info.block_device_cache_noflush);
             assert!(hv.blockdev_info.block_device_cache_set);
             assert_eq!(hv.blockdev_info.vhost_user_store_path, "./store_path");
@@ -490,4 +519,196 @@ mod tests {
         let mut config = TomlConfig::load(content).unwrap();
         assert!(anno.update_config_by_annotation(&mut config).is_err());
     }
+
+    #[test]
+    fn test_change_sizing_profile() {
+        let content = include_str!("texture/configuration-anno-0.toml");
+
+        let qemu = QemuConfig::new();
+        qemu.register();
+
+        let mut anno_hash = HashMap::new();
+        anno_hash.insert(
+            KATA_ANNO_CFG_HYPERVISOR_SIZING_PROFILE.to_string(),
+            "medium".to_string(),
+        );
+        let anno = Annotation::new(anno_hash.clone());
+        let mut config = TomlConfig::load(content).unwrap();
+        assert!(anno.update_config_by_annotation(&mut config).is_ok());
+        let hv = config.hypervisor.get("qemu").unwrap();
+        assert_eq!(hv.memory_info.default_memory, 2048);
+        assert_eq!(hv.network_info.network_queues, 2);
+        assert_eq!(
+            hv.cpu_info.default_vcpus,
+            2.min(hv.cpu_info.default_maxvcpus as i32)
+        );
+
+        // the annotation of a single knob overrides the profile
+        anno_hash.insert(
+            KATA_ANNO_CFG_HYPERVISOR_DEFAULT_MEMORY.to_string(),
+            "3GiB".to_string(),
+        );
+        let anno = Annotation::new(anno_hash);
+        let mut config = TomlConfig::load(content).unwrap();
+        assert!(anno.update_config_by_annotation(&mut config).is_ok());
+        let hv = config.hypervisor.get("qemu").unwrap();
+        assert_eq!(hv.memory_info.default_memory, 3072);
+        assert_eq!(hv.network_info.network_queues, 2);
+    }
+
+    #[test]
+    fn test_fail_to_change_sizing_profile() {
+        let qemu = QemuConfig::new();
+        qemu.register();
+
+        let mut anno_hash = HashMap::new();
+        anno_hash.insert(
+            KATA_ANNO_CFG_HYPERVISOR_SIZING_PROFILE.to_string(),
+            "huge".to_string(),
+        );
+        let anno = Annotation::new(anno_hash);
+
+        // not a defined profile
+        let content = include_str!("texture/configuration-anno-0.toml");
+        let mut config = TomlConfig::load(content).unwrap();
+        assert!(anno.update_config_by_annotation(&mut config).is_err());
+
+        // the profiles of the configuration file replace the built-in ones
+        let content = format!(
+            "{}\n[hypervisor.qemu.sizing_profiles.huge]\ndefault_memory = 16\n",
+            content
+        );
+        let mut config = TomlConfig::load(&content).unwrap();
+        assert!(config
+            .hypervisor
+            .get("qemu")
+            .unwrap()
+            .sizing_profiles
+            .get("small")
+            .is_none());
+        assert!(anno.update_config_by_annotation(&mut config).is_err());
+
+        // not enabled
+        let content = include_str!("texture/configuration-anno-1.toml");
+        let mut config = TomlConfig::load(content).unwrap();
+        assert!(anno.update_config_by_annotation(&mut config).is_ok());
+    }
+
+    #[test]
+    fn test_change_hardened_guest() {
+        let content = include_str!("texture/configuration-anno-0.toml");
+
+        let qemu = QemuConfig::new();
+        qemu.register();
+
+        let mut anno_hash = HashMap::new();
+        anno_hash.insert(
+            KATA_ANNO_CFG_HYPERVISOR_KERNEL_MODULES_BLACKLIST.to_string(),
+            "nf_tables, vsock_loopback".to_string(),
+        );
+        anno_hash.insert(
+            KATA_ANNO_CFG_DISABLE_GUEST_SECCOMP.to_string(),
+            "true".to_string(),
+        );
+        let anno = Annotation::new(anno_hash.clone());
+        let mut config = TomlConfig::load(content).unwrap();
+        assert!(anno.update_config_by_annotation(&mut config).is_ok());
+        let hv = config.hypervisor.get("qemu").unwrap();
+        assert!(!hv.security_info.hardened_guest);
+        assert_eq!(
+            hv.security_info.kernel_modules_blacklist,
+            vec!["nf_tables".to_string(), "vsock_loopback".to_string()]
+        );
+        assert_eq!(hv.device_info.pcie_root_port, 2);
+        assert!(config.runtime.disable_guest_seccomp);
+
+        // the preset overrides the annotations
+        anno_hash.insert(
+            KATA_ANNO_CFG_HYPERVISOR_HARDENED_GUEST.to_string(),
+            "true".to_string(),
+        );
+        let anno = Annotation::new(anno_hash);
+        let mut config = TomlConfig::load(content).unwrap();
+        assert!(anno.update_config_by_annotation(&mut config).is_ok());
+        let hv = config.hypervisor.get("qemu").unwrap();
+        assert!(hv.security_info.hardened_guest);
+        assert_eq!(hv.device_info.pcie_root_port, 0);
+        assert!(!hv.device_info.hotplug_vfio_on_root_bus);
+        assert!(hv.debug_info.guest_memory_dump_path.is_empty());
+        assert!(!config.agent.get("agent0").unwrap().debug_console_enabled);
+        assert!(!config.runtime.disable_guest_seccomp);
+
+        // the preset of the configuration can't be disabled
+        let mut anno_hash = HashMap::new();
+        anno_hash.insert(
+            KATA_ANNO_CFG_HYPERVISOR_HARDENED_GUEST.to_string(),
+            "false".to_string(),
+        );
+        let anno = Annotation::new(anno_hash);
+        let content = content.replace("rootless = true", "rootless = true\nhardened_guest = true");
+        let mut config = TomlConfig::load(&content).unwrap();
+        assert!(anno.update_config_by_annotation(&mut config).is_ok());
+        let hv = config.hypervisor.get("qemu").unwrap();
+        assert!(hv.security_info.hardened_guest);
+    }
+
+    #[test]
+    fn test_change_virtio_fs_dax() {
+        let content = include_str!("texture/configuration-anno-0.toml");
+
+        let qemu = QemuConfig::new();
+        qemu.register();
+
+        // the window of the configuration is disabled for the sandbox
+        let mut anno_hash = HashMap::new();
+        anno_hash.insert(
+            KATA_ANNO_CFG_HYPERVISOR_VIRTIO_FS_IS_DAX.to_string(),
+            "false".to_string(),
+        );
+        anno_hash.insert(
+            KATA_ANNO_CFG_HYPERVISOR_VIRTIO_FS_THREAD_POOL_SIZE.to_string(),
+            "16".to_string(),
+        );
+        let anno = Annotation::new(anno_hash.clone());
+        let mut config = TomlConfig::load(content).unwrap();
+        assert!(
+            config
+                .hypervisor
+                .get("qemu")
+                .unwrap()
+                .shared_fs
+                .virtio_fs_is_dax
+        );
+        assert!(anno.update_config_by_annotation(&mut config).is_ok());
+        let hv = config.hypervisor.get("qemu").unwrap();
+        assert!(!hv.shared_fs.virtio_fs_is_dax);
+        assert_eq!(hv.shared_fs.virtio_fs_cache_size, 0);
+        assert_eq!(hv.shared_fs.virtio_fs_thread_pool_size, 16);
+
+        // and enabled again with the default size
+        anno_hash.insert(
+            KATA_ANNO_CFG_HYPERVISOR_VIRTIO_FS_IS_DAX.to_string(),
+            "true".to_string(),
+        );
+        let anno = Annotation::new(anno_hash.clone());
+        let mut config = TomlConfig::load(content).unwrap();
+        config
+            .hypervisor
+            .get_mut("qemu")
+            .unwrap()
+            .shared_fs
+            .virtio_fs_cache_size = 0;
+        assert!(anno.update_config_by_annotation(&mut config).is_ok());
+        let hv = config.hypervisor.get("qemu").unwrap();
+        assert!(hv.shared_fs.virtio_fs_is_dax);
+        assert!(hv.shared_fs.virtio_fs_cache_size > 0);
+
+        anno_hash.insert(
+            KATA_ANNO_CFG_HYPERVISOR_VIRTIO_FS_THREAD_POOL_SIZE.to_string(),
+            "-1".to_string(),
+        );
+        let anno = Annotation::new(anno_hash);
+        let mut config = TomlConfig::load(content).unwrap();
+        assert!(anno.update_config_by_annotation(&mut config).is_err());
+    }
 }
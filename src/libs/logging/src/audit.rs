@@ -0,0 +1,162 @@
+// Copyright (c) 2023 Kata Containers community
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+//! Audit records of the privileged operations.
+//!
+//! Every record is logged with the `audit` key set, so that it can be filtered
+//! out of the normal log, e.g. by a journald field, and is also appended as a
+//! JSON line to the audit file if one is set.
+
+use std::fmt;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use slog::{info, warn, Logger};
+
+/// The key set on every audit record in the log.
+pub const AUDIT_KEY: &str = "audit";
+
+static AUDIT_FILE: Mutex<Option<File>> = Mutex::new(None);
+
+/// Outcome of an audited operation.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Outcome {
+    Success,
+    Failure,
+}
+
+impl fmt::Display for Outcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Outcome::Success => write!(f, "success"),
+            Outcome::Failure => write!(f, "failure"),
+        }
+    }
+}
+
+/// A privileged operation, who requested it and how it ended.
+#[derive(Clone, Debug)]
+pub struct AuditRecord<'a> {
+    /// The operation, e.g. "set_iptables".
+    pub operation: &'a str,
+    /// Identity of the requester, e.g. the credentials of the peer process.
+    pub requester: &'a str,
+    /// What the operation is done on, e.g. the path of the file copied.
+    pub target: &'a str,
+    pub outcome: Outcome,
+    /// The error if the operation failed.
+    pub error: Option<String>,
+}
+
+impl<'a> AuditRecord<'a> {
+    /// Create a record with the outcome of `result`.
+    pub fn new<T, E: fmt::Debug>(
+        operation: &'a str,
+        requester: &'a str,
+        target: &'a str,
+        result: &Result<T, E>,
+    ) -> Self {
+        let (outcome, error) = match result {
+            Ok(_) => (Outcome::Success, None),
+            Err(e) => (Outcome::Failure, Some(format!("{:?}", e))),
+        };
+        Self {
+            operation,
+            requester,
+            target,
+            outcome,
+            error,
+        }
+    }
+
+    fn to_json(&self) -> serde_json::Value {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs_f64())
+            .unwrap_or_default();
+        serde_json::json!({
+            "timestamp": timestamp,
+            "pid": std::process::id(),
+            "operation": self.operation,
+            "requester": self.requester,
+            "target": self.target,
+            "outcome": self.outcome.to_string(),
+            "error": self.error,
+        })
+    }
+}
+
+/// Append the audit records to the file at `path` as well, the file is only
+/// accessible to its owner.
+pub fn set_audit_file<P: AsRef<Path>>(path: P) -> io::Result<()> {
+    let file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .mode(0o600)
+        .open(path)?;
+    *AUDIT_FILE.lock().unwrap() = Some(file);
+    Ok(())
+}
+
+/// Record the audited operation.
+pub fn audit(logger: &Logger, record: &AuditRecord) {
+    info!(logger, "audit: {}", record.operation;
+        AUDIT_KEY => true,
+        "operation" => record.operation,
+        "requester" => record.requester,
+        "target" => record.target,
+        "outcome" => record.outcome.to_string(),
+        "error" => record.error.as_deref().unwrap_or_default());
+
+    if let Some(file) = AUDIT_FILE.lock().unwrap().as_mut() {
+        if let Err(e) = writeln!(file, "{}", record.to_json()) {
+            warn!(logger, "failed to write audit file: {:?}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::os::unix::fs::PermissionsExt;
+
+    #[test]
+    fn test_audit() {
+        let tmpdir = tempfile::tempdir().unwrap();
+        let path = tmpdir.path().join("audit.log");
+        set_audit_file(&path).unwrap();
+
+        let logger = Logger::root(slog::Discard, slog::o!());
+        let ok: Result<(), String> = Ok(());
+        audit(&logger, &AuditRecord::new("copy_file", "uid=0", "/a", &ok));
+        let err: Result<(), String> = Err("denied".to_string());
+        audit(
+            &logger,
+            &AuditRecord::new("set_iptables", "uid=0", "", &err),
+        );
+        *AUDIT_FILE.lock().unwrap() = None;
+
+        let content = fs::read_to_string(&path).unwrap();
+        let records: Vec<serde_json::Value> = content
+            .lines()
+            .map(|l| serde_json::from_str(l).unwrap())
+            .collect();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0]["operation"], "copy_file");
+        assert_eq!(records[0]["outcome"], "success");
+        assert_eq!(records[0]["error"], serde_json::Value::Null);
+        assert_eq!(records[1]["outcome"], "failure");
+        assert_eq!(records[1]["error"], "\"denied\"");
+        assert_eq!(
+            fs::metadata(&path).unwrap().permissions().mode() & 0o777,
+            0o600
+        );
+    }
+}
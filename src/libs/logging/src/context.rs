@@ -0,0 +1,80 @@
+// Copyright (c) 2023 Kata Containers community
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+//! Task scoped logging context.
+//!
+//! A logger set by [`with_log_context`] is used as the parent of all the loggers
+//! created by the `logger_with_subsystem!` macros while running the future, so
+//! the key/value pairs of the context, e.g. the container id, are attached to
+//! every log line without passing the logger around.
+
+use std::future::Future;
+
+tokio::task_local! {
+    static LOG_CONTEXT: slog::Logger;
+}
+
+/// Get the logger of the current task context, fall back to the global logger
+/// if no context is set.
+pub fn context_logger() -> slog::Logger {
+    LOG_CONTEXT
+        .try_with(|logger| logger.clone())
+        .unwrap_or_else(|_| slog_scope::logger())
+}
+
+/// Run the future with the logger as the logging context.
+///
+/// The context is not inherited by the tasks spawned in the future.
+pub async fn with_log_context<F>(logger: slog::Logger, f: F) -> F::Output
+where
+    F: Future,
+{
+    LOG_CONTEXT.scope(logger, f).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use slog::{o, Drain};
+    use std::sync::{Arc, Mutex};
+
+    // A drain saving the serialized logger values of the records.
+    #[derive(Clone, Default)]
+    struct ValuesDrain {
+        values: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl Drain for ValuesDrain {
+        type Ok = ();
+        type Err = slog::Never;
+
+        fn log(
+            &self,
+            _record: &slog::Record,
+            values: &slog::OwnedKVList,
+        ) -> Result<Self::Ok, Self::Err> {
+            self.values.lock().unwrap().push(format!("{:?}", values));
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_log_context() {
+        let drain = ValuesDrain::default();
+        let root = slog::Logger::root(drain.clone().fuse(), o!("source" => "sid"));
+        let _guard = slog_scope::set_global_logger(root.clone());
+
+        slog::info!(context_logger(), "no context");
+        with_log_context(root.new(o!("container_id" => "cid")), async {
+            slog::info!(context_logger(), "with context");
+        })
+        .await;
+
+        let values = drain.values.lock().unwrap();
+        assert_eq!(values.len(), 2);
+        assert!(!values[0].contains("container_id"));
+        assert!(values[1].contains("container_id"));
+    }
+}
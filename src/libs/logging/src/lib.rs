@@ -11,12 +11,16 @@ use std::process;
 use std::result;
 use std::sync::Mutex;
 
+pub mod audit;
+#[cfg(feature = "context")]
+pub mod context;
 mod file_rotate;
 mod log_writer;
 
 pub use file_rotate::FileRotator;
 pub use log_writer::LogWriter;
 
+#[cfg(not(feature = "context"))]
 #[macro_export]
 macro_rules! logger_with_subsystem {
     ($name: ident, $subsystem: expr) => {
@@ -28,6 +32,20 @@ macro_rules! logger_with_subsystem {
     };
 }
 
+// With the logging context enabled, the loggers inherit the key/value pairs
+// of the context of the current task.
+#[cfg(feature = "context")]
+#[macro_export]
+macro_rules! logger_with_subsystem {
+    ($name: ident, $subsystem: expr) => {
+        macro_rules! $name {
+                            () => {
+                                    $crate::context::context_logger().new(slog::o!("subsystem" => $subsystem))
+                            };
+                        }
+    };
+}
+
 const LOG_LEVELS: &[(&str, slog::Level)] = &[
     ("trace", slog::Level::Trace),
     ("debug", slog::Level::Debug),
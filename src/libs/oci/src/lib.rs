@@ -810,12 +810,20 @@ pub struct LinuxSyscall {
 
 #[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
 pub struct LinuxIntelRdt {
+    #[serde(default, skip_serializing_if = "String::is_empty", rename = "closID")]
+    pub clos_id: String,
     #[serde(
         default,
         skip_serializing_if = "String::is_empty",
         rename = "l3CacheSchema"
     )]
     pub l3_cache_schema: String,
+    #[serde(
+        default,
+        skip_serializing_if = "String::is_empty",
+        rename = "memBwSchema"
+    )]
+    pub mem_bw_schema: String,
 }
 
 #[derive(Debug, Serialize, Deserialize, Copy, Clone, PartialEq)]
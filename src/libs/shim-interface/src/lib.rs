@@ -21,6 +21,7 @@ use anyhow::{anyhow, Result};
 
 pub mod shim_mgmt;
 
+use kata_sys_util::naming::sandbox_dir_name;
 use kata_types::config::KATA_PATH;
 
 pub const SHIM_MGMT_SOCK_NAME: &str = "shim-monitor.sock";
@@ -41,7 +42,7 @@ pub fn mgmt_socket_addr(sid: &str) -> Result<String> {
     }
 
     let p = Path::new(&sb_storage_path())
-        .join(sid)
+        .join(sandbox_dir_name(sid))
         .join(SHIM_MGMT_SOCK_NAME);
 
     if let Some(p) = p.to_str() {
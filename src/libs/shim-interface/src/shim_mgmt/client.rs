@@ -36,13 +36,18 @@ impl MgmtClient {
         let s_addr = unix_socket_path
             .strip_prefix("unix:")
             .context("failed to strip prefix")?;
-        let sock_path = Path::new("/").join(s_addr).as_path().to_owned();
-        let client = Client::unix();
-        Ok(Self {
-            sock_path,
-            client,
+        let sock_path = Path::new("/").join(s_addr);
+        Ok(Self::from_socket_path(&sock_path, timeout))
+    }
+
+    /// Construct a new client connecting to the shim mgmt server listening on `sock_path`,
+    /// e.g. a socket found on the host rather than derived from a sandbox id
+    pub fn from_socket_path(sock_path: &Path, timeout: Option<Duration>) -> Self {
+        Self {
+            sock_path: sock_path.to_path_buf(),
+            client: Client::unix(),
             timeout,
-        })
+        }
     }
 
     /// The http GET method for client, return a raw response. Further handling should be done by caller.
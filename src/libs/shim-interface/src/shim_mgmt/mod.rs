@@ -20,5 +20,37 @@ pub const IP_TABLE_URL: &str = "/iptables";
 pub const IP6_TABLE_URL: &str = "/ip6tables";
 /// URL for querying metrics inside shim
 pub const METRICS_URL: &str = "/metrics";
+/// URL for scraping an in-guest metrics exporter through the vsock of the sandbox
+pub const GUEST_METRICS_URL: &str = "/guest-metrics";
+/// The key for the TCP port of the in-guest metrics exporter, one of the configured ports
+pub const GUEST_METRICS_PORT_KEY: &str = "port";
+/// URL for enabling or disabling the guest debug console
+pub const DEBUG_CONSOLE_URL: &str = "/debug-console";
+/// The key for enabling the guest debug console, the value is "true" or "false"
+pub const DEBUG_CONSOLE_ENABLE_KEY: &str = "enable";
+/// URL for attaching to the guest debug console, the HTTP connection is upgraded to the console
+pub const DEBUG_CONSOLE_ATTACH_URL: &str = "/debug-console/attach";
+/// The protocol the connection attaching to the guest debug console is upgraded to
+pub const DEBUG_CONSOLE_PROTOCOL: &str = "kata-debug-console";
+/// URL for rebooting the guest and re-creating the containers in it
+pub const REBOOT_URL: &str = "/reboot";
+/// URL for copying files into (PUT) or out of (GET) the guest
+pub const COPY_FILE_URL: &str = "/copy-file";
+/// The key for the path of the guest file to copy
+pub const COPY_FILE_PATH_KEY: &str = "path";
+/// The key for the octal mode of the guest file written by a PUT
+pub const COPY_FILE_MODE_KEY: &str = "mode";
+/// URL for streaming the stats of the containers
+pub const STATS_URL: &str = "/stats";
+/// The key for the interval in seconds between the streamed stats
+pub const STATS_INTERVAL_KEY: &str = "interval";
+/// URL for dumping the guest memory for the forensic analysis
+pub const MEMORY_DUMP_URL: &str = "/memory-dump";
+/// The key for the path of the memory dump file on the host
+pub const MEMORY_DUMP_PATH_KEY: &str = "path";
+/// URL for dumping the effective configuration of the sandbox, with the secrets redacted
+pub const CONFIG_URL: &str = "/config";
+/// URL for collecting the debug bundle of the sandbox, a tarball with the secrets redacted
+pub const DEBUG_BUNDLE_URL: &str = "/debug-bundle";
 
 pub const ERR_NO_SHIM_SERVER: &str = "Failed to create shim management server";
@@ -4,11 +4,15 @@
 // SPDX-License-Identifier: Apache-2.0
 //
 
+use std::time::Duration;
+
 use anyhow::{Context, Result};
 use async_trait::async_trait;
+use tokio::net::UnixStream;
 use tracing::instrument;
 use ttrpc::context as ttrpc_ctx;
 
+use kata_sys_util::retry::{is_transient_error, RetryPolicy};
 use kata_types::config::Agent as AgentConfig;
 
 use crate::{kata::KataAgent, Agent, AgentManager, HealthService};
@@ -16,11 +20,56 @@ use crate::{kata::KataAgent, Agent, AgentManager, HealthService};
 /// millisecond to nanosecond
 const MILLISECOND_TO_NANOSECOND: i64 = 1_000_000;
 
+/// retry policy of the requests safe to send again
+const RPC_RETRY_POLICY: RetryPolicy =
+    RetryPolicy::new(3, Duration::from_millis(100), Duration::from_secs(1));
+
+/// the requests which read the state of the guest, or set it to the same value each time, they're
+/// retried on transient errors, the other ones may have been handled by the agent already
+const IDEMPOTENT_REQUESTS: [&str; 15] = [
+    "check",
+    "version",
+    "update_container",
+    "stats_container",
+    "tty_win_resize",
+    "update_interface",
+    "update_routes",
+    "list_interfaces",
+    "list_routes",
+    "read_file",
+    "get_ip_tables",
+    "set_ip_tables",
+    "get_volume_stats",
+    "online_cpu_mem",
+    "get_metrics",
+];
+
 /// new ttrpc context with timeout
 fn new_ttrpc_ctx(timeout: i64) -> ttrpc_ctx::Context {
     ttrpc_ctx::with_timeout(timeout)
 }
 
+fn retry_policy(name: &str) -> RetryPolicy {
+    if IDEMPOTENT_REQUESTS.contains(&name) {
+        RPC_RETRY_POLICY
+    } else {
+        RetryPolicy::once()
+    }
+}
+
+// is_transient_rpc_error checks whether the request failed on the connection to the agent, or
+// because the agent was unavailable
+fn is_transient_rpc_error(err: &anyhow::Error) -> bool {
+    let rpc_error = err
+        .chain()
+        .any(|cause| match cause.downcast_ref::<ttrpc::Error>() {
+            Some(ttrpc::Error::Socket(_)) => true,
+            Some(ttrpc::Error::RpcStatus(status)) => status.code == ttrpc::Code::UNAVAILABLE.into(),
+            _ => false,
+        });
+    rpc_error || is_transient_error(err)
+}
+
 #[async_trait]
 impl AgentManager for KataAgent {
     #[instrument]
@@ -49,6 +98,14 @@ impl AgentManager for KataAgent {
     async fn agent_config(&self) -> AgentConfig {
         self.agent_config().await
     }
+
+    async fn recent_logs(&self) -> Vec<String> {
+        self.recent_logs().await
+    }
+
+    async fn connect_port(&self, port: u32) -> Result<UnixStream> {
+        self.connect_port(port).await
+    }
 }
 
 // implement for health service
@@ -58,9 +115,12 @@ macro_rules! impl_health_service {
         impl HealthService for KataAgent {
             $(async fn $name(&self, req: $req) -> Result<$resp> {
                 let r = req.into();
-                let (client, timeout, _) = self.get_health_client().await.context("get health client")?;
-                let resp = client.$name(new_ttrpc_ctx(timeout * MILLISECOND_TO_NANOSECOND), &r).await?;
-                Ok(resp.into())
+                let r = &r;
+                retry_policy(stringify!($name)).run(stringify!($name), is_transient_rpc_error, move || async move {
+                    let (client, timeout, _) = self.get_health_client().await.context("get health client")?;
+                    let resp = client.$name(new_ttrpc_ctx(timeout * MILLISECOND_TO_NANOSECOND), r).await?;
+                    Ok::<$resp, anyhow::Error>(resp.into())
+                }).await
             })*
         }
     };
@@ -78,15 +138,18 @@ macro_rules! impl_agent {
             #[instrument(skip(req))]
             $(async fn $name(&self, req: $req) -> Result<$resp> {
                 let r = req.into();
-                let (client, mut timeout, _) = self.get_agent_client().await.context("get client")?;
+                let r = &r;
+                retry_policy(stringify!($name)).run(stringify!($name), is_transient_rpc_error, move || async move {
+                    let (client, mut timeout, _) = self.get_agent_client().await.context("get client")?;
 
-                // update new timeout
-                if let Some(v) = $new_timeout {
-                    timeout = v;
-                }
+                    // update new timeout
+                    if let Some(v) = $new_timeout {
+                        timeout = v;
+                    }
 
-                let resp = client.$name(new_ttrpc_ctx(timeout * MILLISECOND_TO_NANOSECOND), &r).await?;
-                Ok(resp.into())
+                    let resp = client.$name(new_ttrpc_ctx(timeout * MILLISECOND_TO_NANOSECOND), r).await?;
+                    Ok::<$resp, anyhow::Error>(resp.into())
+                }).await
             })*
         }
     };
@@ -103,6 +166,10 @@ impl_agent!(
     stats_container | crate::ContainerID | crate::StatsContainerResponse | None,
     pause_container | crate::ContainerID | crate::Empty | None,
     resume_container | crate::ContainerID | crate::Empty | None,
+    checkpoint_container
+        | crate::CheckpointContainerRequest
+        | crate::CheckpointContainerResponse
+        | Some(0),
     write_stdin | crate::WriteStreamRequest | crate::WriteStreamResponse | Some(0),
     read_stdout | crate::ReadStreamRequest | crate::ReadStreamResponse | Some(0),
     read_stderr | crate::ReadStreamRequest | crate::ReadStreamResponse | Some(0),
@@ -116,11 +183,40 @@ impl_agent!(
     create_sandbox | crate::CreateSandboxRequest | crate::Empty | None,
     destroy_sandbox | crate::Empty | crate::Empty | None,
     copy_file | crate::CopyFileRequest | crate::Empty | None,
+    read_file | crate::ReadFileRequest | crate::ReadFileResponse | None,
     get_oom_event | crate::Empty | crate::OomEventResponse | Some(0),
+    get_storage_event | crate::Empty | crate::StorageEvent | Some(0),
     get_ip_tables | crate::GetIPTablesRequest | crate::GetIPTablesResponse | None,
     set_ip_tables | crate::SetIPTablesRequest | crate::SetIPTablesResponse | None,
     get_volume_stats | crate::VolumeStatsRequest | crate::VolumeStatsResponse | None,
     resize_volume | crate::ResizeVolumeRequest | crate::Empty | None,
+    set_debug_console | crate::SetDebugConsoleRequest | crate::Empty | None,
+    exec_guest_hook | crate::ExecGuestHookRequest | crate::Empty | Some(0),
     online_cpu_mem | crate::OnlineCPUMemRequest | crate::Empty | None,
     get_metrics | crate::Empty | crate::MetricsResponse | None
 );
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_transient_rpc_error() {
+        let err = anyhow::Error::from(ttrpc::Error::Socket("connection reset".to_string()));
+        assert!(is_transient_rpc_error(&err.context("stats container")));
+        let err = ttrpc::error::get_rpc_status(ttrpc::Code::UNAVAILABLE, "agent not ready");
+        assert!(is_transient_rpc_error(&err.into()));
+
+        let err = ttrpc::error::get_rpc_status(ttrpc::Code::NOT_FOUND, "no such container");
+        assert!(!is_transient_rpc_error(&err.into()));
+        assert!(!is_transient_rpc_error(&anyhow::anyhow!("get client")));
+    }
+
+    #[test]
+    fn test_retry_policy() {
+        assert_eq!(retry_policy("stats_container"), RPC_RETRY_POLICY);
+        // the requests changing the guest aren't sent twice
+        assert_eq!(retry_policy("exec_process"), RetryPolicy::once());
+        assert_eq!(retry_policy("create_container"), RetryPolicy::once());
+    }
+}
@@ -15,7 +15,7 @@ use std::{
 use anyhow::{Context, Result};
 use kata_types::config::Agent as AgentConfig;
 use protocols::{agent_ttrpc_async as agent_ttrpc, health_ttrpc_async as health_ttrpc};
-use tokio::sync::RwLock;
+use tokio::{net::UnixStream, sync::RwLock};
 use ttrpc::asynchronous::Client;
 
 use crate::{log_forwarder::LogForwarder, sock};
@@ -120,6 +120,22 @@ impl KataAgent {
         Ok(())
     }
 
+    pub(crate) async fn connect_port(&self, port: u32) -> Result<UnixStream> {
+        let (address, config) = {
+            let inner = self.inner.read().await;
+            (
+                inner.socket_address.clone(),
+                sock::ConnectConfig::new(
+                    inner.config.dial_timeout_ms as u64,
+                    inner.config.reconnect_timeout_ms as u64,
+                ),
+            )
+        };
+        let sock = sock::new(&address, port).context("new sock")?;
+        let stream = sock.connect(&config).await.context("connect")?;
+        Ok(stream.into())
+    }
+
     pub(crate) async fn start_log_forwarder(&self) -> Result<()> {
         let mut inner = self.inner.write().await;
         let config = sock::ConnectConfig::new(
@@ -141,6 +157,11 @@ impl KataAgent {
         inner.log_forwarder.stop();
     }
 
+    pub(crate) async fn recent_logs(&self) -> Vec<String> {
+        let inner = self.inner.read().await;
+        inner.log_forwarder.recent_logs()
+    }
+
     pub(crate) async fn agent_sock(&self) -> Result<String> {
         let inner = self.inner.read().await;
         Ok(format!(
@@ -14,20 +14,22 @@ use protocols::{
 use crate::{
     types::{
         ARPNeighbor, ARPNeighbors, AddArpNeighborRequest, AgentDetails, BlkioStats,
-        BlkioStatsEntry, CgroupStats, CheckRequest, CloseStdinRequest, ContainerID,
-        CopyFileRequest, CpuStats, CpuUsage, CreateContainerRequest, CreateSandboxRequest, Device,
-        Empty, ExecProcessRequest, FSGroup, FSGroupChangePolicy, GetIPTablesRequest,
-        GetIPTablesResponse, GuestDetailsResponse, HealthCheckResponse, HugetlbStats, IPAddress,
-        IPFamily, Interface, Interfaces, KernelModule, MemHotplugByProbeRequest, MemoryData,
-        MemoryStats, MetricsResponse, NetworkStats, OnlineCPUMemRequest, PidsStats,
-        ReadStreamRequest, ReadStreamResponse, RemoveContainerRequest, ReseedRandomDevRequest,
-        ResizeVolumeRequest, Route, Routes, SetGuestDateTimeRequest, SetIPTablesRequest,
+        BlkioStatsEntry, CgroupStats, CheckRequest, CheckpointContainerRequest,
+        CheckpointContainerResponse, CloseStdinRequest, ContainerID, CopyFileRequest, CpuStats,
+        CpuUsage, CreateContainerRequest, CreateSandboxRequest, Device, Empty,
+        ExecGuestHookRequest, ExecProcessRequest, FSGroup, FSGroupChangePolicy, FilesystemUsage,
+        GetIPTablesRequest, GetIPTablesResponse, GuestDetailsResponse, HealthCheckResponse,
+        HugetlbStats, IPAddress, IPFamily, Interface, Interfaces, KernelModule,
+        MemHotplugByProbeRequest, MemoryData, MemoryStats, MetricsResponse, NetworkStats,
+        OnlineCPUMemRequest, PidsStats, ReadFileRequest, ReadFileResponse, ReadStreamRequest,
+        ReadStreamResponse, RemoveContainerRequest, ReseedRandomDevRequest, ResizeVolumeRequest,
+        Route, Routes, Rule, SetDebugConsoleRequest, SetGuestDateTimeRequest, SetIPTablesRequest,
         SetIPTablesResponse, SignalProcessRequest, StatsContainerResponse, Storage, StringUser,
         ThrottlingData, TtyWinResizeRequest, UpdateContainerRequest, UpdateInterfaceRequest,
         UpdateRoutesRequest, VersionCheckResponse, VolumeStatsRequest, VolumeStatsResponse,
         WaitProcessRequest, WriteStreamRequest,
     },
-    OomEventResponse, WaitProcessResponse, WriteStreamResponse,
+    OomEventResponse, StorageEvent, StorageEventType, WaitProcessResponse, WriteStreamResponse,
 };
 
 fn trans_vec<F: Sized + Clone, T: From<F>>(from: Vec<F>) -> Vec<T> {
@@ -215,6 +217,8 @@ impl From<Route> for types::Route {
             source: from.source,
             scope: from.scope,
             family: protobuf::EnumOrUnknown::new(from.family.into()),
+            metric: from.metric,
+            table: from.table,
             ..Default::default()
         }
     }
@@ -229,6 +233,24 @@ impl From<types::Route> for Route {
             source: src.source,
             scope: src.scope,
             family: src.family.unwrap().into(),
+            metric: src.metric,
+            table: src.table,
+        }
+    }
+}
+
+impl From<Rule> for types::Rule {
+    fn from(from: Rule) -> Self {
+        Self {
+            source: from.source,
+            dest: from.dest,
+            iif: from.iif,
+            oif: from.oif,
+            fwmark: from.fwmark,
+            table: from.table,
+            priority: from.priority,
+            family: protobuf::EnumOrUnknown::new(from.family.into()),
+            ..Default::default()
         }
     }
 }
@@ -311,6 +333,25 @@ impl From<ContainerID> for agent::ResumeContainerRequest {
     }
 }
 
+impl From<CheckpointContainerRequest> for agent::CheckpointContainerRequest {
+    fn from(from: CheckpointContainerRequest) -> Self {
+        Self {
+            container_id: from.container_id,
+            leave_running: from.leave_running,
+            ..Default::default()
+        }
+    }
+}
+
+impl From<agent::CheckpointContainerResponse> for CheckpointContainerResponse {
+    fn from(from: agent::CheckpointContainerResponse) -> Self {
+        Self {
+            image_dir: from.image_dir,
+            files: from.files,
+        }
+    }
+}
+
 impl From<SignalProcessRequest> for agent::SignalProcessRequest {
     fn from(from: SignalProcessRequest) -> Self {
         Self {
@@ -533,12 +574,23 @@ impl From<agent::NetworkStats> for NetworkStats {
     }
 }
 
+impl From<agent::FilesystemUsage> for FilesystemUsage {
+    fn from(src: agent::FilesystemUsage) -> Self {
+        Self {
+            path: src.path,
+            used_bytes: src.used_bytes,
+            inodes_used: src.inodes_used,
+        }
+    }
+}
+
 // translate ttrpc::agent response to interface::agent response
 impl From<agent::StatsContainerResponse> for StatsContainerResponse {
     fn from(src: agent::StatsContainerResponse) -> Self {
         Self {
             cgroup_stats: into_option(src.cgroup_stats),
             network_stats: trans_vec(src.network_stats),
+            writable_layer: into_option(src.writable_layer),
         }
     }
 }
@@ -603,6 +655,7 @@ impl From<UpdateRoutesRequest> for agent::UpdateRoutesRequest {
     fn from(from: UpdateRoutesRequest) -> Self {
         Self {
             routes: from_option(from.route),
+            rules: trans_vec(from.rules),
             ..Default::default()
         }
     }
@@ -657,6 +710,7 @@ impl From<CreateSandboxRequest> for agent::CreateSandboxRequest {
             sandbox_id: from.sandbox_id,
             guest_hook_path: from.guest_hook_path,
             kernel_modules: trans_vec(from.kernel_modules),
+            dns_cache: from.dns_cache,
             ..Default::default()
         }
     }
@@ -747,6 +801,26 @@ impl From<CopyFileRequest> for agent::CopyFileRequest {
     }
 }
 
+impl From<ReadFileRequest> for agent::ReadFileRequest {
+    fn from(from: ReadFileRequest) -> Self {
+        Self {
+            path: from.path,
+            offset: from.offset,
+            len: from.len,
+            ..Default::default()
+        }
+    }
+}
+
+impl From<agent::ReadFileResponse> for ReadFileResponse {
+    fn from(from: agent::ReadFileResponse) -> Self {
+        Self {
+            file_size: from.file_size,
+            data: from.data,
+        }
+    }
+}
+
 impl From<agent::WaitProcessResponse> for WaitProcessResponse {
     fn from(from: agent::WaitProcessResponse) -> Self {
         Self {
@@ -813,6 +887,31 @@ impl From<agent::OOMEvent> for OomEventResponse {
     }
 }
 
+impl From<Empty> for agent::GetStorageEventRequest {
+    fn from(_: Empty) -> Self {
+        Self {
+            ..Default::default()
+        }
+    }
+}
+
+impl From<agent::StorageEvent> for StorageEvent {
+    fn from(from: agent::StorageEvent) -> Self {
+        let event_type = match from.type_.enum_value_or_default() {
+            agent::storage_event::Type::NEAR_FULL => StorageEventType::NearFull,
+            agent::storage_event::Type::MOUNT_FAILED => StorageEventType::MountFailed,
+        };
+        Self {
+            event_type,
+            container_id: from.container_id,
+            mount_point: from.mount_point,
+            used_bytes: from.used_bytes,
+            capacity_bytes: from.capacity_bytes,
+            message: from.message,
+        }
+    }
+}
+
 impl From<VolumeStatsRequest> for agent::VolumeStatsRequest {
     fn from(from: VolumeStatsRequest) -> Self {
         Self {
@@ -842,3 +941,23 @@ impl From<ResizeVolumeRequest> for agent::ResizeVolumeRequest {
         }
     }
 }
+
+impl From<SetDebugConsoleRequest> for agent::SetDebugConsoleRequest {
+    fn from(from: SetDebugConsoleRequest) -> Self {
+        Self {
+            enable: from.enable,
+            vport: from.vport,
+            ..Default::default()
+        }
+    }
+}
+
+impl From<ExecGuestHookRequest> for agent::ExecGuestHookRequest {
+    fn from(from: ExecGuestHookRequest) -> Self {
+        Self {
+            args: from.args,
+            timeout: from.timeout,
+            ..Default::default()
+        }
+    }
+}
@@ -15,21 +15,23 @@ mod sock;
 pub mod types;
 pub use types::{
     ARPNeighbor, ARPNeighbors, AddArpNeighborRequest, BlkioStatsEntry, CheckRequest,
-    CloseStdinRequest, ContainerID, ContainerProcessID, CopyFileRequest, CreateContainerRequest,
-    CreateSandboxRequest, Empty, ExecProcessRequest, GetGuestDetailsRequest, GetIPTablesRequest,
+    CheckpointContainerRequest, CheckpointContainerResponse, CloseStdinRequest, ContainerID,
+    ContainerProcessID, CopyFileRequest, CreateContainerRequest, CreateSandboxRequest, Empty,
+    ExecGuestHookRequest, ExecProcessRequest, GetGuestDetailsRequest, GetIPTablesRequest,
     GetIPTablesResponse, GuestDetailsResponse, HealthCheckResponse, IPAddress, IPFamily, Interface,
     Interfaces, ListProcessesRequest, MemHotplugByProbeRequest, MetricsResponse,
-    OnlineCPUMemRequest, OomEventResponse, ReadStreamRequest, ReadStreamResponse,
-    RemoveContainerRequest, ReseedRandomDevRequest, ResizeVolumeRequest, Route, Routes,
-    SetGuestDateTimeRequest, SetIPTablesRequest, SetIPTablesResponse, SignalProcessRequest,
-    StatsContainerResponse, Storage, TtyWinResizeRequest, UpdateContainerRequest,
-    UpdateInterfaceRequest, UpdateRoutesRequest, VersionCheckResponse, VolumeStatsRequest,
-    VolumeStatsResponse, WaitProcessRequest, WaitProcessResponse, WriteStreamRequest,
-    WriteStreamResponse,
+    OnlineCPUMemRequest, OomEventResponse, ReadFileRequest, ReadFileResponse, ReadStreamRequest,
+    ReadStreamResponse, RemoveContainerRequest, ReseedRandomDevRequest, ResizeVolumeRequest, Route,
+    Routes, Rule, SetDebugConsoleRequest, SetGuestDateTimeRequest, SetIPTablesRequest,
+    SetIPTablesResponse, SignalProcessRequest, StatsContainerResponse, Storage, StorageEvent,
+    StorageEventType, TtyWinResizeRequest, UpdateContainerRequest, UpdateInterfaceRequest,
+    UpdateRoutesRequest, VersionCheckResponse, VolumeStatsRequest, VolumeStatsResponse,
+    WaitProcessRequest, WaitProcessResponse, WriteStreamRequest, WriteStreamResponse,
 };
 
 use anyhow::Result;
 use async_trait::async_trait;
+use tokio::net::UnixStream;
 
 use kata_types::config::Agent as AgentConfig;
 
@@ -42,6 +44,11 @@ pub trait AgentManager: Send + Sync {
 
     async fn agent_sock(&self) -> Result<String>;
     async fn agent_config(&self) -> AgentConfig;
+    /// The last lines of the agent logs, the oldest first.
+    async fn recent_logs(&self) -> Vec<String>;
+    /// Connect to a port of the guest other than the one of the agent, e.g.
+    /// the debug console, through the same socket of the VM.
+    async fn connect_port(&self, port: u32) -> Result<UnixStream>;
 }
 
 #[async_trait]
@@ -72,6 +79,10 @@ pub trait Agent: AgentManager + HealthService + Send + Sync {
     async fn start_container(&self, req: ContainerID) -> Result<Empty>;
     async fn stats_container(&self, req: ContainerID) -> Result<StatsContainerResponse>;
     async fn update_container(&self, req: UpdateContainerRequest) -> Result<Empty>;
+    async fn checkpoint_container(
+        &self,
+        req: CheckpointContainerRequest,
+    ) -> Result<CheckpointContainerResponse>;
 
     // process
     async fn exec_process(&self, req: ExecProcessRequest) -> Result<Empty>;
@@ -87,10 +98,14 @@ pub trait Agent: AgentManager + HealthService + Send + Sync {
 
     // utils
     async fn copy_file(&self, req: CopyFileRequest) -> Result<Empty>;
+    async fn read_file(&self, req: ReadFileRequest) -> Result<ReadFileResponse>;
     async fn get_metrics(&self, req: Empty) -> Result<MetricsResponse>;
     async fn get_oom_event(&self, req: Empty) -> Result<OomEventResponse>;
+    async fn get_storage_event(&self, req: Empty) -> Result<StorageEvent>;
     async fn get_ip_tables(&self, req: GetIPTablesRequest) -> Result<GetIPTablesResponse>;
     async fn set_ip_tables(&self, req: SetIPTablesRequest) -> Result<SetIPTablesResponse>;
     async fn get_volume_stats(&self, req: VolumeStatsRequest) -> Result<VolumeStatsResponse>;
     async fn resize_volume(&self, req: ResizeVolumeRequest) -> Result<Empty>;
+    async fn set_debug_console(&self, req: SetDebugConsoleRequest) -> Result<Empty>;
+    async fn exec_guest_hook(&self, req: ExecGuestHookRequest) -> Result<Empty>;
 }
@@ -4,6 +4,9 @@
 // SPDX-License-Identifier: Apache-2.0
 //
 
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
 use anyhow::Result;
 use tokio::io::{AsyncBufReadExt, BufReader};
 
@@ -17,13 +20,25 @@ const LOG_LEVEL_WARNING: &str = "WARN";
 const LOG_LEVEL_ERROR: &str = "ERRO";
 const LOG_LEVEL_CRITICAL: &str = "CRIT";
 
+// the number of the last agent log lines kept for the debug bundle
+const RECENT_LOG_LINES: usize = 1000;
+
 pub(crate) struct LogForwarder {
     task_handler: Option<tokio::task::JoinHandle<()>>,
+    // kept across the restarts of the forwarder, to have the logs before a guest reboot
+    recent_logs: Arc<Mutex<VecDeque<String>>>,
 }
 
 impl LogForwarder {
     pub(crate) fn new() -> Self {
-        Self { task_handler: None }
+        Self {
+            task_handler: None,
+            recent_logs: Arc::new(Mutex::new(VecDeque::with_capacity(RECENT_LOG_LINES))),
+        }
+    }
+
+    pub(crate) fn recent_logs(&self) -> Vec<String> {
+        self.recent_logs.lock().unwrap().iter().cloned().collect()
     }
 
     pub(crate) fn stop(&mut self) {
@@ -43,6 +58,7 @@ impl LogForwarder {
     ) -> Result<()> {
         let logger = sl!().clone();
         let address = address.to_string();
+        let recent_logs = self.recent_logs.clone();
         let task_handler = tokio::spawn(async move {
             loop {
                 info!(logger, "try to connect to get agent log");
@@ -73,6 +89,7 @@ impl LogForwarder {
                                 LOG_LEVEL_CRITICAL => crit!(sl!(), "{}", l),
                                 _ => info!(sl!(), "{}", l),
                             }
+                            keep_recent_log(&recent_logs, l);
                         }
                     }
                     Err(err) => {
@@ -86,6 +103,14 @@ impl LogForwarder {
     }
 }
 
+fn keep_recent_log(recent_logs: &Mutex<VecDeque<String>>, line: String) {
+    let mut recent_logs = recent_logs.lock().unwrap();
+    if recent_logs.len() == RECENT_LOG_LINES {
+        recent_logs.pop_front();
+    }
+    recent_logs.push_back(line);
+}
+
 pub fn parse_agent_log_level(s: &str) -> &str {
     let v: serde_json::Result<serde_json::Value> = serde_json::from_str(s);
     match v {
@@ -108,7 +133,7 @@ pub fn parse_agent_log_level(s: &str) -> &str {
 
 #[cfg(test)]
 mod tests {
-    use super::parse_agent_log_level;
+    use super::*;
 
     #[test]
     fn test_parse_agent_log_level() {
@@ -154,4 +179,19 @@ mod tests {
             assert_eq!(result, excepted);
         }
     }
+
+    #[test]
+    fn test_keep_recent_log() {
+        let forwarder = LogForwarder::new();
+        for i in 0..RECENT_LOG_LINES + 2 {
+            keep_recent_log(&forwarder.recent_logs, i.to_string());
+        }
+        let logs = forwarder.recent_logs();
+        assert_eq!(logs.len(), RECENT_LOG_LINES);
+        assert_eq!(logs[0], "2");
+        assert_eq!(
+            logs[RECENT_LOG_LINES - 1],
+            (RECENT_LOG_LINES + 1).to_string()
+        );
+    }
 }
@@ -66,6 +66,14 @@ impl IntoRawFd for Stream {
     }
 }
 
+impl From<Stream> for UnixStream {
+    fn from(stream: Stream) -> Self {
+        match stream {
+            Stream::Unix(stream) | Stream::Vsock(stream) => stream,
+        }
+    }
+}
+
 impl AsyncRead for Stream {
     fn poll_read(
         self: Pin<&mut Self>,
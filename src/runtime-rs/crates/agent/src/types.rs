@@ -102,6 +102,10 @@ pub struct Route {
     pub source: String,
     pub scope: u32,
     pub family: IPFamily,
+    #[serde(default)]
+    pub metric: u32,
+    #[serde(default)]
+    pub table: u32,
 }
 
 #[derive(Deserialize, Debug, PartialEq, Clone, Default)]
@@ -109,6 +113,18 @@ pub struct Routes {
     pub routes: Vec<Route>,
 }
 
+#[derive(Deserialize, Debug, PartialEq, Clone, Default)]
+pub struct Rule {
+    pub source: String,
+    pub dest: String,
+    pub iif: String,
+    pub oif: String,
+    pub fwmark: u32,
+    pub table: u32,
+    pub priority: u32,
+    pub family: IPFamily,
+}
+
 #[derive(PartialEq, Clone, Default)]
 pub struct CreateContainerRequest {
     pub process_id: ContainerProcessID,
@@ -244,7 +260,7 @@ pub struct ExecProcessRequest {
     pub process: Option<oci::Process>,
 }
 
-#[derive(PartialEq, Clone, Default, Debug)]
+#[derive(PartialEq, Clone, Default, Debug, Serialize)]
 pub struct CpuUsage {
     pub total_usage: u64,
     pub percpu_usage: ::std::vec::Vec<u64>,
@@ -252,27 +268,27 @@ pub struct CpuUsage {
     pub usage_in_usermode: u64,
 }
 
-#[derive(PartialEq, Clone, Default, Debug)]
+#[derive(PartialEq, Clone, Default, Debug, Serialize)]
 pub struct ThrottlingData {
     pub periods: u64,
     pub throttled_periods: u64,
     pub throttled_time: u64,
 }
 
-#[derive(PartialEq, Clone, Default, Debug)]
+#[derive(PartialEq, Clone, Default, Debug, Serialize)]
 pub struct LoadData {
     pub one: String,
     pub five: String,
     pub fifteen: String,
 }
 
-#[derive(PartialEq, Clone, Default, Debug)]
+#[derive(PartialEq, Clone, Default, Debug, Serialize)]
 pub struct CpuStats {
     pub cpu_usage: Option<CpuUsage>,
     pub throttling_data: Option<ThrottlingData>,
 }
 
-#[derive(PartialEq, Clone, Default, Debug)]
+#[derive(PartialEq, Clone, Default, Debug, Serialize)]
 pub struct MemoryData {
     pub usage: u64,
     pub max_usage: u64,
@@ -280,7 +296,7 @@ pub struct MemoryData {
     pub limit: u64,
 }
 
-#[derive(PartialEq, Clone, Default, Debug)]
+#[derive(PartialEq, Clone, Default, Debug, Serialize)]
 pub struct MemoryStats {
     pub cache: u64,
     pub usage: Option<MemoryData>,
@@ -290,13 +306,13 @@ pub struct MemoryStats {
     pub stats: ::std::collections::HashMap<String, u64>,
 }
 
-#[derive(PartialEq, Clone, Default, Debug)]
+#[derive(PartialEq, Clone, Default, Debug, Serialize)]
 pub struct PidsStats {
     pub current: u64,
     pub limit: u64,
 }
 
-#[derive(PartialEq, Clone, Default, Debug)]
+#[derive(PartialEq, Clone, Default, Debug, Serialize)]
 pub struct BlkioStatsEntry {
     pub major: u64,
     pub minor: u64,
@@ -304,7 +320,7 @@ pub struct BlkioStatsEntry {
     pub value: u64,
 }
 
-#[derive(PartialEq, Clone, Default, Debug)]
+#[derive(PartialEq, Clone, Default, Debug, Serialize)]
 pub struct BlkioStats {
     pub io_service_bytes_recursive: Vec<BlkioStatsEntry>,
     pub io_serviced_recursive: Vec<BlkioStatsEntry>,
@@ -316,14 +332,14 @@ pub struct BlkioStats {
     pub sectors_recursive: Vec<BlkioStatsEntry>,
 }
 
-#[derive(PartialEq, Clone, Default, Debug)]
+#[derive(PartialEq, Clone, Default, Debug, Serialize)]
 pub struct HugetlbStats {
     pub usage: u64,
     pub max_usage: u64,
     pub failcnt: u64,
 }
 
-#[derive(PartialEq, Clone, Default, Debug)]
+#[derive(PartialEq, Clone, Default, Debug, Serialize)]
 pub struct CgroupStats {
     pub cpu_stats: Option<CpuStats>,
     pub memory_stats: Option<MemoryStats>,
@@ -332,7 +348,7 @@ pub struct CgroupStats {
     pub hugetlb_stats: ::std::collections::HashMap<String, HugetlbStats>,
 }
 
-#[derive(PartialEq, Clone, Default, Debug)]
+#[derive(PartialEq, Clone, Default, Debug, Serialize)]
 pub struct NetworkStats {
     pub name: String,
     pub rx_bytes: u64,
@@ -345,10 +361,18 @@ pub struct NetworkStats {
     pub tx_dropped: u64,
 }
 
-#[derive(PartialEq, Clone, Default, Debug)]
+#[derive(PartialEq, Clone, Default, Debug, Serialize)]
+pub struct FilesystemUsage {
+    pub path: String,
+    pub used_bytes: u64,
+    pub inodes_used: u64,
+}
+
+#[derive(PartialEq, Clone, Default, Debug, Serialize)]
 pub struct StatsContainerResponse {
     pub cgroup_stats: Option<CgroupStats>,
     pub network_stats: Vec<NetworkStats>,
+    pub writable_layer: Option<FilesystemUsage>,
 }
 
 #[derive(PartialEq, Clone, Default, Debug)]
@@ -387,6 +411,7 @@ pub struct UpdateInterfaceRequest {
 #[derive(PartialEq, Clone, Default, Debug)]
 pub struct UpdateRoutesRequest {
     pub route: Option<Routes>,
+    pub rules: Vec<Rule>,
 }
 
 #[derive(Deserialize, PartialEq, Clone, Default, Debug)]
@@ -473,6 +498,7 @@ pub struct CreateSandboxRequest {
     pub sandbox_id: String,
     pub guest_hook_path: String,
     pub kernel_modules: Vec<KernelModule>,
+    pub dns_cache: bool,
 }
 
 #[derive(PartialEq, Clone, Default)]
@@ -532,6 +558,31 @@ pub struct CopyFileRequest {
     pub data: ::std::vec::Vec<u8>,
 }
 
+#[derive(PartialEq, Clone, Default, Debug)]
+pub struct ReadFileRequest {
+    pub path: String,
+    pub offset: i64,
+    pub len: u32,
+}
+
+#[derive(PartialEq, Clone, Default, Debug)]
+pub struct ReadFileResponse {
+    pub file_size: i64,
+    pub data: Vec<u8>,
+}
+
+#[derive(PartialEq, Clone, Default, Debug)]
+pub struct CheckpointContainerRequest {
+    pub container_id: String,
+    pub leave_running: bool,
+}
+
+#[derive(PartialEq, Clone, Default, Debug)]
+pub struct CheckpointContainerResponse {
+    pub image_dir: String,
+    pub files: Vec<String>,
+}
+
 #[derive(PartialEq, Clone, Default, Debug)]
 pub struct CheckRequest {
     pub service: String,
@@ -566,6 +617,25 @@ pub struct OomEventResponse {
     pub container_id: String,
 }
 
+#[derive(PartialEq, Clone, Copy, Default, Debug)]
+pub enum StorageEventType {
+    #[default]
+    NearFull,
+    MountFailed,
+}
+
+/// A storage problem found in the guest, the container id is empty for the
+/// storages of the sandbox.
+#[derive(PartialEq, Clone, Default, Debug)]
+pub struct StorageEvent {
+    pub event_type: StorageEventType,
+    pub container_id: String,
+    pub mount_point: String,
+    pub used_bytes: u64,
+    pub capacity_bytes: u64,
+    pub message: String,
+}
+
 // ResizeVolumeRequest is also the common struct for serialization and deserialization with json
 // between shim-client HTTP calls to the shim-mgmt-server
 #[derive(Serialize, Deserialize, PartialEq, Clone, Default, Debug)]
@@ -584,6 +654,18 @@ pub struct VolumeStatsResponse {
     pub data: String,
 }
 
+#[derive(PartialEq, Clone, Default, Debug)]
+pub struct SetDebugConsoleRequest {
+    pub enable: bool,
+    pub vport: u32,
+}
+
+#[derive(PartialEq, Clone, Default, Debug)]
+pub struct ExecGuestHookRequest {
+    pub args: Vec<String>,
+    pub timeout: u32,
+}
+
 #[cfg(test)]
 mod test {
     use std::convert::TryFrom;
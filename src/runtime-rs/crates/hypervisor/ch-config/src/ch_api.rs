@@ -2,7 +2,7 @@
 //
 // SPDX-License-Identifier: Apache-2.0
 
-use crate::{DeviceConfig, DiskConfig, FsConfig, VmConfig};
+use crate::{DeviceConfig, DiskConfig, FsConfig, VmConfig, VmResize};
 use anyhow::{anyhow, Result};
 use api_client::simple_api_full_command_and_response;
 
@@ -57,6 +57,16 @@ pub async fn cloud_hypervisor_vm_start(mut socket: UnixStream) -> Result<Option<
     .await?
 }
 
+pub async fn cloud_hypervisor_vm_reboot(mut socket: UnixStream) -> Result<Option<String>> {
+    task::spawn_blocking(move || -> Result<Option<String>> {
+        let response = simple_api_full_command_and_response(&mut socket, "PUT", "vm.reboot", None)
+            .map_err(|e| anyhow!(e))?;
+
+        Ok(response)
+    })
+    .await?
+}
+
 #[allow(dead_code)]
 pub async fn cloud_hypervisor_vm_stop(mut socket: UnixStream) -> Result<Option<String>> {
     task::spawn_blocking(move || -> Result<Option<String>> {
@@ -122,3 +132,21 @@ pub async fn cloud_hypervisor_vm_fs_add(
     })
     .await?
 }
+
+pub async fn cloud_hypervisor_vm_resize(
+    mut socket: UnixStream,
+    resize: VmResize,
+) -> Result<Option<String>> {
+    task::spawn_blocking(move || -> Result<Option<String>> {
+        let response = simple_api_full_command_and_response(
+            &mut socket,
+            "PUT",
+            "vm.resize",
+            Some(&serde_json::to_string(&resize)?),
+        )
+        .map_err(|e| anyhow!(e))?;
+
+        Ok(response)
+    })
+    .await?
+}
@@ -6,8 +6,9 @@ use crate::net_util::MAC_ADDR_LEN;
 use crate::NamedHypervisorConfig;
 use crate::VmConfig;
 use crate::{
-    ConsoleConfig, ConsoleOutputMode, CpuFeatures, CpuTopology, CpusConfig, DiskConfig, MacAddr,
-    MemoryConfig, PayloadConfig, PlatformConfig, PmemConfig, RngConfig, VsockConfig,
+    ConsoleConfig, ConsoleOutputMode, CpuFeatures, CpuTopology, CpusConfig, DiskConfig,
+    HotplugMethod, MacAddr, MemoryConfig, PayloadConfig, PlatformConfig, PmemConfig, RngConfig,
+    VsockConfig,
 };
 use anyhow::{anyhow, Context, Result};
 use kata_types::config::default::DEFAULT_CH_ENTROPY_SOURCE;
@@ -42,6 +43,8 @@ impl TryFrom<NamedHypervisorConfig> for VmConfig {
 
         let debug = cfg.debug_info.enable_debug;
         let confidential_guest = cfg.security_info.confidential_guest;
+        let enable_iommu = cfg.device_info.enable_iommu;
+        let enable_iommu_platform = cfg.device_info.enable_iommu_platform;
 
         let tdx_enabled = n.tdx_enabled;
 
@@ -123,7 +126,7 @@ impl TryFrom<NamedHypervisorConfig> for VmConfig {
 
         let platform = get_platform_cfg(tdx_enabled);
 
-        let cfg = VmConfig {
+        let mut cfg = VmConfig {
             cpus,
             memory,
             serial,
@@ -136,10 +139,15 @@ impl TryFrom<NamedHypervisorConfig> for VmConfig {
             vsock: Some(vsock),
             rng,
             platform,
+            iommu: enable_iommu,
 
             ..Default::default()
         };
 
+        if enable_iommu_platform {
+            set_iommu_platform(&mut cfg);
+        }
+
         Ok(cfg)
     }
 }
@@ -210,12 +218,20 @@ impl TryFrom<(MemoryInfo, bool)> for MemoryConfig {
             Some(aligned_hotplug_size_bytes)
         };
 
+        // ACPI can't unplug the memory hot plugged
+        let hotplug_method = if mem.enable_virtio_mem {
+            HotplugMethod::VirtioMem
+        } else {
+            HotplugMethod::Acpi
+        };
+
         let cfg = MemoryConfig {
             size: mem_bytes,
 
             // Required
             shared: true,
 
+            hotplug_method,
             hotplug_size,
 
             ..Default::default()
@@ -243,6 +259,11 @@ impl TryFrom<CpuInfo> for CpusConfig {
     type Error = CpusConfigError;
 
     fn try_from(cpu: CpuInfo) -> Result<Self, Self::Error> {
+        // CH always exposes the host CPU, only some of its flags are optional
+        if !cpu.is_host_cpu_model() {
+            return Err(CpusConfigError::UnsupportedCPUModel(cpu.cpu_model));
+        }
+
         let boot_vcpus =
             u8::try_from(cpu.default_vcpus).map_err(CpusConfigError::BootVCPUsTooBig)?;
 
@@ -277,7 +298,9 @@ impl TryFrom<CpuInfo> for CpusConfig {
 impl From<String> for CpuFeatures {
     #[cfg(target_arch = "x86_64")]
     fn from(s: String) -> Self {
-        let amx = s.split(',').any(|x| x == "amx");
+        let amx = s
+            .split(',')
+            .any(|x| x == "amx" || x == "+amx" || x == "amx=on");
 
         CpuFeatures { amx }
     }
@@ -434,6 +457,17 @@ fn get_console_cfg(debug: bool, confidential_guest: bool) -> ConsoleConfig {
     }
 }
 
+// Place the virtio devices behind the virtio-iommu, which CH then adds to the VM.
+fn set_iommu_platform(cfg: &mut VmConfig) {
+    cfg.disks.iter_mut().flatten().for_each(|d| d.iommu = true);
+    cfg.net.iter_mut().flatten().for_each(|n| n.iommu = true);
+    cfg.pmem.iter_mut().flatten().for_each(|p| p.iommu = true);
+    if let Some(vsock) = cfg.vsock.as_mut() {
+        vsock.iommu = true;
+    }
+    cfg.rng.iommu = true;
+}
+
 fn get_platform_cfg(tdx_enabled: bool) -> Option<PlatformConfig> {
     if tdx_enabled {
         let platform = PlatformConfig {
@@ -809,6 +843,27 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_set_iommu_platform() {
+        let mut cfg = VmConfig {
+            disks: Some(vec![DiskConfig::default(), DiskConfig::default()]),
+            pmem: Some(vec![PmemConfig::default()]),
+            vsock: Some(VsockConfig::default()),
+
+            ..Default::default()
+        };
+
+        set_iommu_platform(&mut cfg);
+
+        assert!(cfg.disks.unwrap().iter().all(|d| d.iommu));
+        assert!(cfg.pmem.unwrap().iter().all(|p| p.iommu));
+        assert!(cfg.vsock.unwrap().iommu);
+        assert!(cfg.rng.iommu);
+        assert!(cfg.net.is_none());
+        // the virtio-iommu is added by CH for the devices behind it
+        assert!(!cfg.iommu);
+    }
+
     #[test]
     fn test_bootinfo_to_pmemconfig() {
         #[derive(Debug)]
@@ -946,6 +1001,21 @@ mod tests {
                 result: CpuFeatures { amx: true },
             },
             #[cfg(target_arch = "x86_64")]
+            TestData {
+                s: "pmu=off,+amx",
+                result: CpuFeatures { amx: true },
+            },
+            #[cfg(target_arch = "x86_64")]
+            TestData {
+                s: "amx=on",
+                result: CpuFeatures { amx: true },
+            },
+            #[cfg(target_arch = "x86_64")]
+            TestData {
+                s: "-amx",
+                result: CpuFeatures { amx: false },
+            },
+            #[cfg(target_arch = "x86_64")]
             TestData {
                 s: "amxyz",
                 result: CpuFeatures { amx: false },
@@ -1120,6 +1190,35 @@ mod tests {
                     ..Default::default()
                 }),
             },
+            TestData {
+                cpu_info: CpuInfo {
+                    cpu_model: "host".to_string(),
+
+                    ..Default::default()
+                },
+                result: Ok(CpusConfig {
+                    boot_vcpus: 0,
+                    max_vcpus: 0,
+                    topology: Some(CpuTopology {
+                        cores_per_die: 0,
+
+                        ..topology
+                    }),
+                    max_phys_bits: DEFAULT_CH_MAX_PHYS_BITS,
+
+                    ..Default::default()
+                }),
+            },
+            TestData {
+                cpu_info: CpuInfo {
+                    cpu_model: "Cascadelake-Server-v4".to_string(),
+
+                    ..Default::default()
+                },
+                result: Err(CpusConfigError::UnsupportedCPUModel(
+                    "Cascadelake-Server-v4".to_string(),
+                )),
+            },
             TestData {
                 cpu_info,
                 result: Ok(cpus_config),
@@ -1375,6 +1474,26 @@ mod tests {
                     ..Default::default()
                 }),
             },
+            TestData {
+                mem_info: MemoryInfo {
+                    default_memory: 1024,
+                    enable_virtio_mem: true,
+
+                    ..Default::default()
+                },
+                confidential_guest: false,
+                result: Ok(MemoryConfig {
+                    size: 1024_u64 * MIB,
+                    shared: true,
+                    hotplug_method: HotplugMethod::VirtioMem,
+                    hotplug_size: checked_next_multiple_of(
+                        usable_max_mem_bytes - (1024 * MIB),
+                        PMEM_ALIGN_BYTES,
+                    ),
+
+                    ..Default::default()
+                }),
+            },
             TestData {
                 mem_info: mem_info_std,
                 confidential_guest: false,
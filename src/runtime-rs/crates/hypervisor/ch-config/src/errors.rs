@@ -65,6 +65,9 @@ pub enum CpusConfigError {
 
     #[error("Too many max vCPUs specified: {0}")]
     MaxVCPUsTooBig(<u8 as TryFrom<u32>>::Error),
+
+    #[error("CPU model {0} isn't supported, only the host one")]
+    UnsupportedCPUModel(String),
 }
 
 #[derive(Error, Debug, PartialEq)]
@@ -463,6 +463,17 @@ pub struct VsockConfig {
     pub pci_segment: u16,
 }
 
+// The resources of a running VM to resize, the unset ones are kept.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize, Default)]
+pub struct VmResize {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub desired_vcpus: Option<u8>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub desired_ram: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub desired_balloon: Option<u64>,
+}
+
 //--------------------------------------------------------------------
 // For serde serialization
 
@@ -46,6 +46,9 @@ pub struct CloudHypervisorInner {
     /// List of devices that will be added to the VM once it boots
     pub(crate) pending_devices: Vec<DeviceType>,
 
+    /// Memory in MiB hot plugged on top of the default memory
+    pub(crate) mem_hotplug_mb: u32,
+
     pub(crate) _capabilities: Capabilities,
 
     pub(crate) shutdown_tx: Option<Sender<bool>>,
@@ -82,6 +85,7 @@ impl CloudHypervisorInner {
             run_dir: String::default(),
             netns: None,
             pending_devices: vec![],
+            mem_hotplug_mb: 0,
             _capabilities: capabilities,
             shutdown_tx: Some(tx),
             shutdown_rx: Some(rx),
@@ -15,6 +15,7 @@ use anyhow::{anyhow, Context, Result};
 use ch_config::ch_api::{cloud_hypervisor_vm_blockdev_add, cloud_hypervisor_vm_fs_add};
 use ch_config::DiskConfig;
 use ch_config::{net_util::MacAddr, FsConfig, NetConfig};
+use kata_types::config::hypervisor::BLOCK_DEVICE_AIO_IO_URING;
 use safe_path::scoped_join;
 use std::convert::TryFrom;
 use std::path::PathBuf;
@@ -131,11 +132,19 @@ impl CloudHypervisorInner {
         let num_queues: usize = DEFAULT_DISK_QUEUES;
         let queue_size: u16 = DEFAULT_DISK_QUEUE_SIZE;
 
+        let blockdev_info = self.hypervisor_config().blockdev_info;
+
+        // Cloud Hypervisor only offers io_uring as an asynchronous backend,
+        // any other aio mode falls back to its synchronous implementation.
         let block_config = DiskConfig {
             path: Some(cfg.path_on_host.as_str().into()),
             readonly: cfg.is_readonly,
+            direct: cfg
+                .is_direct
+                .unwrap_or(blockdev_info.block_device_cache_direct),
             num_queues,
             queue_size,
+            disable_io_uring: blockdev_info.block_device_aio != BLOCK_DEVICE_AIO_IO_URING,
             ..Default::default()
         };
 
@@ -7,18 +7,19 @@ use super::inner::CloudHypervisorInner;
 use crate::ch::utils::get_api_socket_path;
 use crate::ch::utils::get_vsock_path;
 use crate::kernel_param::KernelParams;
-use crate::utils::{get_jailer_root, get_sandbox_path};
+use crate::utils::{get_command_line, get_jailer_root, get_sandbox_path};
 use crate::VM_ROOTFS_DRIVER_PMEM;
 use crate::{VcpuThreadIds, VmmState};
 use anyhow::{anyhow, Context, Result};
 use ch_config::ch_api::{
-    cloud_hypervisor_vm_create, cloud_hypervisor_vm_start, cloud_hypervisor_vmm_ping,
-    cloud_hypervisor_vmm_shutdown,
+    cloud_hypervisor_vm_create, cloud_hypervisor_vm_reboot, cloud_hypervisor_vm_resize,
+    cloud_hypervisor_vm_start, cloud_hypervisor_vmm_ping, cloud_hypervisor_vmm_shutdown,
 };
-use ch_config::{NamedHypervisorConfig, VmConfig};
+use ch_config::{NamedHypervisorConfig, VmConfig, VmResize};
 use core::future::poll_fn;
 use futures::executor::block_on;
 use futures::future::join_all;
+use kata_sys_util::naming;
 use kata_types::capabilities::{Capabilities, CapabilityBits};
 use kata_types::config::default::DEFAULT_CH_ROOTFS_TYPE;
 use std::convert::TryFrom;
@@ -39,6 +40,8 @@ const CH_NAME: &str = "cloud-hypervisor";
 /// Number of milliseconds to wait before retrying a CH operation.
 const CH_POLL_TIME_MS: u64 = 50;
 
+const MIB: u64 = 1024 * 1024;
+
 impl CloudHypervisorInner {
     async fn start_hypervisor(&mut self, timeout_secs: i32) -> Result<()> {
         self.cloud_hypervisor_launch(timeout_secs)
@@ -97,10 +100,20 @@ impl CloudHypervisorInner {
         // Add the rootfs device
         params.append(&mut rootfs_param);
 
+        if cfg.cpu_info.vcpu_rt_priority > 0 {
+            params.append(&mut KernelParams::new_realtime_kernel_params());
+        }
+
         // Finally, add the user-specified options at the end
         // (so they will take priority).
         params.append(&mut KernelParams::from_string(&cfg.boot_info.kernel_params));
 
+        // The kernel modules blacklist and the sysctls of the hardened guest come last, so that
+        // the user-specified options can't override them.
+        params.append(&mut KernelParams::new_hardening_kernel_params(
+            &cfg.security_info,
+        ));
+
         let kernel_params = params.to_string()?;
 
         Ok(kernel_params)
@@ -425,7 +438,7 @@ impl CloudHypervisorInner {
         self.run_dir = get_sandbox_path(&self.id);
         self.vm_path = self.run_dir.to_string();
 
-        create_dir_all(&self.run_dir)
+        naming::claim_dir(Path::new(&self.run_dir), &self.id)
             .with_context(|| anyhow!("failed to create sandbox directory {}", self.run_dir))?;
 
         if !self.jailer_root.is_empty() {
@@ -451,6 +464,27 @@ impl CloudHypervisorInner {
         Ok(())
     }
 
+    // The guest is rebooted by the VMM, all the devices, including the
+    // hotplugged ones, are kept.
+    pub(crate) async fn reboot_vm(&mut self) -> Result<()> {
+        let socket = self
+            .api_socket
+            .as_ref()
+            .ok_or("missing socket")
+            .map_err(|e| anyhow!(e))?;
+
+        let response =
+            cloud_hypervisor_vm_reboot(socket.try_clone().context("failed to clone socket")?)
+                .await
+                .context("reboot failed")?;
+
+        if let Some(detail) = response {
+            debug!(sl!(), "reboot response: {:?}", detail);
+        }
+
+        Ok(())
+    }
+
     pub(crate) fn pause_vm(&self) -> Result<()> {
         Ok(())
     }
@@ -485,10 +519,108 @@ impl CloudHypervisorInner {
         Ok(())
     }
 
+    // the vcpus are hot plugged up to the max vcpus the VM is booted with
     pub(crate) async fn resize_vcpu(&self, old_vcpu: u32, new_vcpu: u32) -> Result<(u32, u32)> {
+        if old_vcpu == new_vcpu {
+            return Ok((old_vcpu, new_vcpu));
+        }
+        if new_vcpu == 0 {
+            return Err(anyhow!("resize vcpu error: 0 vcpu resizing is invalid"));
+        }
+
+        let max_vcpu = self
+            .config
+            .as_ref()
+            .map_or(new_vcpu, |c| c.cpu_info.default_maxvcpus);
+        let new_vcpu = if new_vcpu > max_vcpu {
+            warn!(
+                sl!(),
+                "resize vcpu: {} vcpus exceed the max {}, the max is used", new_vcpu, max_vcpu
+            );
+            max_vcpu
+        } else {
+            new_vcpu
+        };
+
+        let socket = self
+            .api_socket
+            .as_ref()
+            .ok_or("missing socket")
+            .map_err(|e| anyhow!(e))?;
+        let resize = VmResize {
+            desired_vcpus: Some(u8::try_from(new_vcpu).context("too many vcpus")?),
+            ..Default::default()
+        };
+        let response = cloud_hypervisor_vm_resize(
+            socket.try_clone().context("failed to clone socket")?,
+            resize,
+        )
+        .await
+        .context("resize vcpus failed")?;
+
+        if let Some(detail) = response {
+            debug!(sl!(), "resize vcpus response: {:?}", detail);
+        }
+
         Ok((old_vcpu, new_vcpu))
     }
 
+    // the memory is hot plugged on top of the default memory up to the max
+    // memory, by virtio-mem which unplugs it too, or by ACPI which can't
+    pub(crate) async fn resize_memory(&mut self, new_mem_mb: u32) -> Result<u32> {
+        let config = self
+            .config
+            .as_ref()
+            .ok_or_else(|| anyhow!("resize memory: no hypervisor config"))?;
+        let memory_info = &config.memory_info;
+        let default_memory = memory_info.default_memory;
+        // the confidential guests are booted without memory to hot plug
+        let max_hotplug_mb = if config.security_info.confidential_guest {
+            0
+        } else {
+            memory_info.default_maxmemory.saturating_sub(default_memory)
+        };
+
+        let mut hotplug_mb = new_mem_mb
+            .saturating_sub(default_memory)
+            .min(max_hotplug_mb);
+        if hotplug_mb < self.mem_hotplug_mb && !memory_info.enable_virtio_mem {
+            warn!(
+                sl!(),
+                "resize memory: ACPI can't unplug memory, keep {} MiB instead of {} MiB",
+                default_memory + self.mem_hotplug_mb,
+                new_mem_mb
+            );
+            hotplug_mb = self.mem_hotplug_mb;
+        }
+        if hotplug_mb == self.mem_hotplug_mb {
+            return Ok(default_memory + hotplug_mb);
+        }
+
+        let socket = self
+            .api_socket
+            .as_ref()
+            .ok_or("missing socket")
+            .map_err(|e| anyhow!(e))?;
+        let resize = VmResize {
+            desired_ram: Some((default_memory + hotplug_mb) as u64 * MIB),
+            ..Default::default()
+        };
+        let response = cloud_hypervisor_vm_resize(
+            socket.try_clone().context("failed to clone socket")?,
+            resize,
+        )
+        .await
+        .context("resize memory failed")?;
+
+        if let Some(detail) = response {
+            debug!(sl!(), "resize memory response: {:?}", detail);
+        }
+
+        self.mem_hotplug_mb = hotplug_mb;
+        Ok(default_memory + hotplug_mb)
+    }
+
     pub(crate) async fn get_pids(&self) -> Result<Vec<u32>> {
         Ok(Vec::<u32>::new())
     }
@@ -531,6 +663,19 @@ impl CloudHypervisorInner {
     pub(crate) async fn get_hypervisor_metrics(&self) -> Result<String> {
         todo!()
     }
+
+    pub(crate) async fn dump_guest_memory(&self, _path: &str) -> Result<()> {
+        Err(anyhow!(
+            "guest memory dump isn't supported by cloud-hypervisor yet"
+        ))
+    }
+
+    pub(crate) async fn get_command_line(&self) -> Result<Vec<String>> {
+        let pid = self
+            .pid
+            .ok_or_else(|| anyhow!("{} isn't running", CH_NAME))?;
+        get_command_line(pid)
+    }
 }
 
 // Log all output from the CH process until a shutdown signal is received.
@@ -583,3 +728,32 @@ async fn cloud_hypervisor_log_output(mut child: Child, mut shutdown: Receiver<bo
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use kata_types::config::hypervisor::Hypervisor as HypervisorConfig;
+
+    #[actix_rt::test]
+    async fn test_resize_memory_unchanged() {
+        let mut config = HypervisorConfig::default();
+        config.memory_info.default_memory = 1024;
+        config.memory_info.default_maxmemory = 2048;
+        let mut ch = CloudHypervisorInner::new();
+        ch.set_hypervisor_config(config);
+        ch.mem_hotplug_mb = 1024;
+
+        // capped to the max memory
+        assert_eq!(ch.resize_memory(4096).await.unwrap(), 2048);
+        // ACPI can't unplug the memory
+        assert_eq!(ch.resize_memory(512).await.unwrap(), 2048);
+        assert_eq!(ch.mem_hotplug_mb, 1024);
+
+        // virtio-mem unplugs it through the API
+        let mut config = ch.hypervisor_config();
+        config.memory_info.enable_virtio_mem = true;
+        ch.set_hypervisor_config(config);
+        assert!(ch.resize_memory(512).await.is_err());
+        assert_eq!(ch.mem_hotplug_mb, 1024);
+    }
+}
@@ -5,7 +5,7 @@
 
 use super::HypervisorState;
 use crate::device::DeviceType;
-use crate::{Hypervisor, VcpuThreadIds};
+use crate::{metrics, Hypervisor, VcpuThreadIds, HYPERVISOR_NAME_CH};
 use anyhow::{Context, Result};
 use async_trait::async_trait;
 use kata_types::capabilities::Capabilities;
@@ -18,7 +18,7 @@ use tokio::sync::RwLock;
 #[macro_export]
 macro_rules! sl {
       () => {
-          slog_scope::logger().new(o!("subsystem" => "cloud-hypervisor"))
+          logging::context::context_logger().new(o!("subsystem" => "cloud-hypervisor"))
       };
   }
 
@@ -56,12 +56,26 @@ impl Hypervisor for CloudHypervisor {
 
     async fn start_vm(&self, timeout: i32) -> Result<()> {
         let mut inner = self.inner.write().await;
-        inner.start_vm(timeout).await
+        metrics::observe(
+            HYPERVISOR_NAME_CH,
+            metrics::OP_START_VM,
+            "",
+            inner.start_vm(timeout),
+        )
+        .await
     }
 
     async fn stop_vm(&self) -> Result<()> {
         let mut inner = self.inner.write().await;
-        inner.stop_vm()
+        metrics::observe(HYPERVISOR_NAME_CH, metrics::OP_STOP_VM, "", async {
+            inner.stop_vm()
+        })
+        .await
+    }
+
+    async fn reboot_vm(&self) -> Result<()> {
+        let mut inner = self.inner.write().await;
+        inner.reboot_vm().await
     }
 
     async fn pause_vm(&self) -> Result<()> {
@@ -81,12 +95,26 @@ impl Hypervisor for CloudHypervisor {
 
     async fn add_device(&self, device: DeviceType) -> Result<()> {
         let mut inner = self.inner.write().await;
-        inner.add_device(device).await
+        let kind = metrics::device_kind(&device);
+        metrics::observe(
+            HYPERVISOR_NAME_CH,
+            metrics::OP_ADD_DEVICE,
+            kind,
+            inner.add_device(device),
+        )
+        .await
     }
 
     async fn remove_device(&self, device: DeviceType) -> Result<()> {
         let mut inner = self.inner.write().await;
-        inner.remove_device(device).await
+        let kind = metrics::device_kind(&device);
+        metrics::observe(
+            HYPERVISOR_NAME_CH,
+            metrics::OP_REMOVE_DEVICE,
+            kind,
+            inner.remove_device(device),
+        )
+        .await
     }
 
     async fn get_agent_socket(&self) -> Result<String> {
@@ -116,7 +144,24 @@ impl Hypervisor for CloudHypervisor {
 
     async fn resize_vcpu(&self, old_vcpu: u32, new_vcpu: u32) -> Result<(u32, u32)> {
         let inner = self.inner.read().await;
-        inner.resize_vcpu(old_vcpu, new_vcpu).await
+        metrics::observe(
+            HYPERVISOR_NAME_CH,
+            metrics::OP_RESIZE_VCPU,
+            "",
+            inner.resize_vcpu(old_vcpu, new_vcpu),
+        )
+        .await
+    }
+
+    async fn resize_memory(&self, new_mem_mb: u32) -> Result<u32> {
+        let mut inner = self.inner.write().await;
+        metrics::observe(
+            HYPERVISOR_NAME_CH,
+            metrics::OP_RESIZE_MEMORY,
+            "",
+            inner.resize_memory(new_mem_mb),
+        )
+        .await
     }
 
     async fn get_pids(&self) -> Result<Vec<u32>> {
@@ -157,6 +202,16 @@ impl Hypervisor for CloudHypervisor {
         let inner = self.inner.read().await;
         inner.get_hypervisor_metrics().await
     }
+
+    async fn dump_guest_memory(&self, path: &str) -> Result<()> {
+        let inner = self.inner.read().await;
+        inner.dump_guest_memory(path).await
+    }
+
+    async fn get_command_line(&self) -> Result<Vec<String>> {
+        let inner = self.inner.read().await;
+        inner.get_command_line().await
+    }
 }
 
 #[async_trait]
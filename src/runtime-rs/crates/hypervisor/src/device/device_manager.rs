@@ -4,25 +4,36 @@
 // SPDX-License-Identifier: Apache-2.0
 //
 
-use std::{collections::HashMap, sync::Arc};
+use std::{collections::HashMap, sync::Arc, time::Duration};
 
 use anyhow::{anyhow, Context, Result};
-use kata_sys_util::rand::RandomBytes;
+use kata_sys_util::{
+    rand::RandomBytes,
+    retry::{is_transient_error, RetryPolicy},
+};
 use tokio::sync::{Mutex, RwLock};
 
 use crate::{
     vhost_user_blk::VhostUserBlkDevice, BlockConfig, BlockDevice, HybridVsockDevice, Hypervisor,
     NetworkDevice, VfioDevice, VhostUserConfig, KATA_BLK_DEV_TYPE, KATA_MMIO_BLK_DEV_TYPE,
-    KATA_NVDIMM_DEV_TYPE, VIRTIO_BLOCK_MMIO, VIRTIO_BLOCK_PCI, VIRTIO_PMEM,
+    KATA_NVDIMM_DEV_TYPE, KATA_NVME_DEV_TYPE, KATA_SCSI_DEV_TYPE, NVME, VIRTIO_BLOCK_MMIO,
+    VIRTIO_BLOCK_PCI, VIRTIO_PMEM, VIRTIO_SCSI,
 };
 
 use super::{
-    util::{get_host_path, get_virt_drive_name, DEVICE_TYPE_BLOCK},
+    util::{
+        get_host_path, get_nvme_drive_name, get_scsi_address, get_virt_drive_name,
+        DEVICE_TYPE_BLOCK,
+    },
     Device, DeviceConfig, DeviceType,
 };
 
 pub type ArcMutexDevice = Arc<Mutex<dyn Device>>;
 
+// retry policy of the hotplug of the devices failing with transient errors, e.g. the VMM busy
+const HOTPLUG_RETRY_POLICY: RetryPolicy =
+    RetryPolicy::new(3, Duration::from_millis(200), Duration::from_secs(2));
+
 macro_rules! declare_index {
     ($self:ident, $index:ident, $released_index:ident) => {{
         let current_index = if let Some(index) = $self.$released_index.pop() {
@@ -117,10 +128,26 @@ impl DeviceManager {
         let device = self
             .devices
             .get(device_id)
-            .context("failed to find device")?;
-        let mut device_guard = device.lock().await;
-        // attach device
-        let result = device_guard.attach(self.hypervisor.as_ref()).await;
+            .context("failed to find device")?
+            .clone();
+        // attach device, the attach of a device rolls back on failure, but a vfio device is the
+        // devices of its IOMMU group added one by one
+        let policy = match device.lock().await.get_device_info().await {
+            DeviceType::Vfio(_) => RetryPolicy::once(),
+            _ => HOTPLUG_RETRY_POLICY,
+        };
+        let hypervisor = self.hypervisor.as_ref();
+        let result = policy
+            .run(
+                &format!("attach device {}", device_id),
+                is_transient_error,
+                || {
+                    let device = device.clone();
+                    async move { device.lock().await.attach(hypervisor).await }
+                },
+            )
+            .await;
+        let device_guard = device.lock().await;
         // handle attach error
         if let Err(e) = result {
             match device_guard.get_device_info().await {
@@ -265,8 +292,15 @@ impl DeviceManager {
         let dev: ArcMutexDevice = match device_config {
             DeviceConfig::BlockCfg(config) => {
                 // try to find the device, if found and just return id.
-                if let Some(device_matched_id) = self.find_device(config.path_on_host.clone()).await
-                {
+                // the host block devices may be given by their major and minor numbers only,
+                // e.g. the bind mounted block volumes shared by several containers.
+                let host_path = if config.path_on_host.is_empty() {
+                    get_host_path(DEVICE_TYPE_BLOCK, config.major, config.minor)
+                        .context("failed to get host path")?
+                } else {
+                    config.path_on_host.clone()
+                };
+                if let Some(device_matched_id) = self.find_device(host_path).await {
                     return Ok(device_matched_id);
                 }
 
@@ -392,6 +426,28 @@ impl DeviceManager {
                 block_config.driver_option = KATA_NVDIMM_DEV_TYPE.to_string();
                 is_pmem = true;
             }
+            VIRTIO_SCSI => {
+                if !self
+                    .hypervisor
+                    .capabilities()
+                    .await?
+                    .is_scsi_block_device_supported()
+                {
+                    return Err(anyhow!("hypervisor doesn't support virtio-scsi"));
+                }
+                block_config.driver_option = KATA_SCSI_DEV_TYPE.to_string();
+            }
+            NVME => {
+                if !self
+                    .hypervisor
+                    .capabilities()
+                    .await?
+                    .is_nvme_block_device_supported()
+                {
+                    return Err(anyhow!("hypervisor doesn't support nvme"));
+                }
+                block_config.driver_option = KATA_NVME_DEV_TYPE.to_string();
+            }
             _ => {
                 return Err(anyhow!(
                     "unsupported driver type {}",
@@ -406,6 +462,24 @@ impl DeviceManager {
             block_config.virt_path = virt_path.1;
         }
 
+        // scsi devices are located by scsi address, and nvme devices are named by the
+        // controller in guest, rather than the virtio-blk disk name.
+        match block_config.driver_option.as_str() {
+            KATA_SCSI_DEV_TYPE => match get_scsi_address(block_config.index) {
+                Ok(address) => block_config.virt_path = address,
+                Err(e) => {
+                    self.shared_info
+                        .release_device_index(block_config.index, false);
+                    return Err(e);
+                }
+            },
+            KATA_NVME_DEV_TYPE => {
+                block_config.virt_path =
+                    format!("/dev/{}", get_nvme_drive_name(block_config.index));
+            }
+            _ => {}
+        }
+
         // if the path on host is empty, we need to get device host path from the device major and minor number
         // Otherwise, it might be rawfile based block device, the host path is already passed from the runtime,
         // so we don't need to do anything here.
@@ -475,7 +549,7 @@ pub async fn get_block_driver(d: &RwLock<DeviceManager>) -> String {
     d.read().await.get_block_driver().await
 }
 
-#[cfg(test)]
+#[cfg(all(test, feature = "qemu"))]
 mod tests {
     use super::DeviceManager;
     use crate::{
@@ -536,4 +610,28 @@ mod tests {
             assert_eq!(1, 0)
         }
     }
+
+    #[actix_rt::test]
+    async fn test_new_shared_block_device() {
+        let d = new_device_manager().await.unwrap();
+        let block_driver = get_block_driver(&d).await;
+        let dev_info = DeviceConfig::BlockCfg(BlockConfig {
+            path_on_host: "/dev/dddzzz".to_string(),
+            driver_option: block_driver.clone(),
+            ..Default::default()
+        });
+        let device_id = d.write().await.new_device(&dev_info).await.unwrap();
+
+        // the same host device used by another container is the same device
+        let shared_device_id = d.write().await.new_device(&dev_info).await.unwrap();
+        assert_eq!(shared_device_id, device_id);
+
+        let other_dev_info = DeviceConfig::BlockCfg(BlockConfig {
+            path_on_host: "/dev/dddyyy".to_string(),
+            driver_option: block_driver,
+            ..Default::default()
+        });
+        let other_device_id = d.write().await.new_device(&other_dev_info).await.unwrap();
+        assert_ne!(other_device_id, device_id);
+    }
 }
@@ -41,6 +41,10 @@ const INTEL_IOMMU_PREFIX: &str = "dmar";
 const AMD_IOMMU_PREFIX: &str = "ivhd";
 const ARM_IOMMU_PREFIX: &str = "smmu";
 
+// the drivers the devices of an IOMMU group may be bound to when the group is assigned, the
+// bridges are kept by the host
+const VFIO_VIABLE_DRIVERS: &[&str] = &[VFIO_PCI_DRIVER, "pci-stub", "pcieport"];
+
 lazy_static! {
     static ref GUEST_DEVICE_ID: Arc<AtomicU8> = Arc::new(AtomicU8::new(0_u8));
     static ref HOST_GUEST_MAP: Arc<RwLock<HashMap<String, String>>> =
@@ -457,12 +461,31 @@ impl Device for VfioDevice {
 
         // /sys/kernel/iommu_groups/X/devices
         // DDDD:BB:DD.F0 DDDD:BB:DD.F1
-        let iommu_devices = fs::read_dir(iommu_devs_path.clone())?
+        let iommu_devices = fs::read_dir(iommu_devs_path.clone())
+            .with_context(|| {
+                format!(
+                    "IOMMU group {} not found, is the IOMMU enabled in the host?",
+                    vfio_group
+                )
+            })?
             .filter_map(|e| {
                 let x = e.ok()?.file_name().to_string_lossy().into_owned();
                 Some(x)
             })
             .collect::<Vec<String>>();
+
+        // all the devices of the IOMMU group are assigned together, none may be used by the host
+        let conflicts = iommu_group_conflicts(&iommu_devices, get_device_driver);
+        if !conflicts.is_empty() {
+            return Err(anyhow!(
+                "IOMMU group {} of {} can't be assigned, its devices {} are used by the host, bind them to {} first",
+                vfio_group,
+                host_path,
+                conflicts.join(", "),
+                VFIO_PCI_DRIVER
+            ));
+        }
+
         if iommu_devices.len() > 1 {
             warn!(sl!(), "vfio device {} with multi-function", host_path);
         }
@@ -626,6 +649,34 @@ pub fn is_equal_driver(bdf: &str, host_driver: &str) -> bool {
     false
 }
 
+// get_device_driver returns the driver the device is bound to in the host, if any
+fn get_device_driver(bdf: &str) -> Option<String> {
+    let driver_file = Path::new(SYS_BUS_PCI_DEVICES)
+        .join(normalize_device_bdf(bdf))
+        .join("driver");
+    let driver_path = fs::read_link(driver_file).ok()?;
+    driver_path
+        .file_name()
+        .map(|v| v.to_string_lossy().into_owned())
+}
+
+// iommu_group_conflicts returns the devices of the IOMMU group bound to a host driver, with
+// their drivers, VFIO refuses to assign the group while any is.
+fn iommu_group_conflicts(
+    devices: &[String],
+    driver_of: impl Fn(&str) -> Option<String>,
+) -> Vec<String> {
+    devices
+        .iter()
+        .filter_map(|d| match driver_of(d) {
+            Some(driver) if !VFIO_VIABLE_DRIVERS.contains(&driver.as_str()) => {
+                Some(format!("{} (driver {})", d, driver))
+            }
+            _ => None,
+        })
+        .collect()
+}
+
 // bind_device_to_host binds the device to the host driver after unbinding from vfio-pci.
 pub fn bind_device_to_host(bdf: &str, host_driver: &str, _vendor_device_id: &str) -> Result<()> {
     // Unbind from vfio-pci driver to the original host driver
@@ -776,3 +827,40 @@ pub fn get_vfio_device(device: String) -> Result<String> {
 
     Ok(vfio_device)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_iommu_group_conflicts() {
+        let drivers: HashMap<&str, &str> = [
+            ("0000:01:00.0", "vfio-pci"),
+            ("0000:01:00.1", "snd_hda_intel"),
+            ("0000:00:01.0", "pcieport"),
+            ("0000:01:00.3", "nvme"),
+        ]
+        .iter()
+        .cloned()
+        .collect();
+        let driver_of = |d: &str| drivers.get(d).map(|v| v.to_string());
+
+        let devices: Vec<String> = ["0000:01:00.0", "0000:00:01.0", "0000:01:00.2"]
+            .iter()
+            .map(|d| d.to_string())
+            .collect();
+        assert!(iommu_group_conflicts(&devices, driver_of).is_empty());
+
+        let devices: Vec<String> = ["0000:01:00.0", "0000:01:00.1", "0000:01:00.3"]
+            .iter()
+            .map(|d| d.to_string())
+            .collect();
+        assert_eq!(
+            iommu_group_conflicts(&devices, driver_of),
+            vec![
+                "0000:01:00.1 (driver snd_hda_intel)".to_string(),
+                "0000:01:00.3 (driver nvme)".to_string(),
+            ]
+        );
+    }
+}
@@ -14,9 +14,13 @@ use async_trait::async_trait;
 pub const VIRTIO_BLOCK_PCI: &str = "virtio-blk-pci";
 pub const VIRTIO_BLOCK_MMIO: &str = "virtio-blk-mmio";
 pub const VIRTIO_PMEM: &str = "virtio-pmem";
+pub const VIRTIO_SCSI: &str = "virtio-scsi";
+pub const NVME: &str = "nvme";
 pub const KATA_MMIO_BLK_DEV_TYPE: &str = "mmioblk";
 pub const KATA_BLK_DEV_TYPE: &str = "blk";
 pub const KATA_NVDIMM_DEV_TYPE: &str = "nvdimm";
+pub const KATA_SCSI_DEV_TYPE: &str = "scsi";
+pub const KATA_NVME_DEV_TYPE: &str = "nvme";
 
 #[derive(Debug, Clone, Default)]
 pub struct BlockConfig {
@@ -30,6 +34,11 @@ pub struct BlockConfig {
     /// Don't close `path_on_host` file when dropping the device.
     pub no_drop: bool,
 
+    /// If set, overrides the hypervisor level block_device_cache_direct for
+    /// the drive: true bypasses the host page cache (cache mode `none`),
+    /// false goes through it (cache mode `writeback`).
+    pub is_direct: Option<bool>,
+
     /// device index
     pub index: u64,
 
@@ -4,9 +4,11 @@
 // SPDX-License-Identifier: Apache-2.0
 //
 
-use anyhow::{Context, Result};
+use anyhow::{anyhow, Context, Result};
+use kata_sys_util::host_lock::{self, HostLockGuard, HostResource};
 use rand::Rng;
 use std::os::unix::prelude::AsRawFd;
+use std::{io, time::Duration};
 use tokio::fs::{File, OpenOptions};
 
 use async_trait::async_trait;
@@ -159,3 +161,134 @@ impl VsockDevice {
         );
     }
 }
+
+// the context IDs are searched from the first usable one, so the host locks of the registry are
+// bounded by the VMs running at once
+const FIRST_USABLE_GUEST_CID: u32 = 3;
+const MAX_GUEST_CID_SEARCH: u32 = 4096;
+
+/// A guest CID reserved for a VM using vhost-vsock.
+///
+/// The CID is registered in the host lock of the CID as long as the reservation is alive, so the
+/// shims of the host never pick the same one, and it's checked to be unused by the other VMs.
+#[derive(Debug)]
+pub struct GuestCid {
+    pub cid: u32,
+    _guard: HostLockGuard,
+}
+
+/// Check that /dev/vhost-vsock can be used to create the vsock of the VM, the VM fails to boot
+/// with an obscure error otherwise.
+pub fn check_vhost_vsock() -> Result<()> {
+    std::fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(VHOST_VSOCK_DEVICE)
+        .map(|_| ())
+        .map_err(|e| match e.kind() {
+            io::ErrorKind::NotFound => anyhow!(
+                "{} doesn't exist, try to run modprobe vhost_vsock",
+                VHOST_VSOCK_DEVICE
+            ),
+            io::ErrorKind::PermissionDenied => {
+                anyhow!("no permission to open {}: {}", VHOST_VSOCK_DEVICE, e)
+            }
+            _ => anyhow!(e).context(format!("failed to open {}", VHOST_VSOCK_DEVICE)),
+        })
+}
+
+/// Reserve a guest CID free among the shims of the host and the other VMs.
+pub fn reserve_guest_cid() -> Result<GuestCid> {
+    find_guest_cid(|cid| {
+        let guard = match host_lock::lock(
+            HostResource::VsockCid,
+            Some(&cid.to_string()),
+            Duration::ZERO,
+        ) {
+            Ok(guard) => guard,
+            // reserved by another shim
+            Err(host_lock::Error::Timeout(..)) => return Ok(None),
+            Err(e) => return Err(e).context("lock vsock context ID"),
+        };
+
+        // the CID may be used by a VM not run by kata, the hypervisor sets it itself so the
+        // vhost fd of the check is closed
+        if !is_guest_cid_free(cid)? {
+            return Ok(None);
+        }
+        Ok(Some(GuestCid { cid, _guard: guard }))
+    })
+}
+
+/// Reserve again the guest CID `cid` of a running VM, once its shim restarts.
+pub fn reclaim_guest_cid(cid: u32) -> Result<GuestCid> {
+    let guard = host_lock::lock(
+        HostResource::VsockCid,
+        Some(&cid.to_string()),
+        Duration::ZERO,
+    )
+    .with_context(|| format!("lock vsock context ID {}", cid))?;
+    Ok(GuestCid { cid, _guard: guard })
+}
+
+fn is_guest_cid_free(cid: u32) -> Result<bool> {
+    let vhost_fd = std::fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(VHOST_VSOCK_DEVICE)
+        .context(format!("failed to open {}", VHOST_VSOCK_DEVICE))?;
+    match unsafe { vhost_vsock_set_guest_cid(vhost_fd.as_raw_fd(), &(cid as u64)) } {
+        Ok(_) => Ok(true),
+        Err(nix::Error::EADDRINUSE) => Ok(false),
+        Err(err) => Err(err).context("failed to set guest CID"),
+    }
+}
+
+// find_guest_cid returns the first CID reserved by reserve, which returns None for the CIDs in use
+fn find_guest_cid<T, F>(mut reserve: F) -> Result<T>
+where
+    F: FnMut(u32) -> Result<Option<T>>,
+{
+    for cid in FIRST_USABLE_GUEST_CID..FIRST_USABLE_GUEST_CID + MAX_GUEST_CID_SEARCH {
+        if let Some(reserved) = reserve(cid)? {
+            return Ok(reserved);
+        }
+    }
+
+    Err(anyhow!(
+        "no free vsock context ID in the first {} ones, all in use by other VMs",
+        MAX_GUEST_CID_SEARCH
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    #[test]
+    fn test_find_guest_cid() {
+        let mut used: HashSet<u32> = [3, 4, 6].iter().copied().collect();
+        let mut reserve = |cid: u32| -> Result<Option<u32>> {
+            if used.insert(cid) {
+                Ok(Some(cid))
+            } else {
+                Ok(None)
+            }
+        };
+        assert_eq!(find_guest_cid(&mut reserve).unwrap(), 5);
+        assert_eq!(find_guest_cid(&mut reserve).unwrap(), 7);
+
+        // all in use
+        assert!(find_guest_cid(|_| Ok(None::<u32>)).is_err());
+
+        // the errors stop the search
+        let mut tried = 0;
+        let result = find_guest_cid(|_| -> Result<Option<u32>> {
+            tried += 1;
+            Err(anyhow!("failed"))
+        });
+        assert!(result.is_err());
+        assert_eq!(tried, 1);
+    }
+}
@@ -69,9 +69,31 @@ pub(crate) fn get_virt_drive_name(mut index: i32) -> Result<String> {
     Ok(String::from(PREFIX) + std::str::from_utf8(&disk_letters)?)
 }
 
+// Max number of luns per scsi target, and max number of scsi targets per controller.
+const SCSI_MAX_LUN: u64 = 255;
+const SCSI_MAX_ID: u64 = 255;
+
+// get_scsi_address returns the "<scsi-id>:<lun>" address of the device with index on
+// the virtio-scsi controller, which is used by agent to locate the device in guest.
+pub(crate) fn get_scsi_address(index: u64) -> Result<String> {
+    let scsi_id = index / (SCSI_MAX_LUN + 1);
+    let lun = index % (SCSI_MAX_LUN + 1);
+    if scsi_id > SCSI_MAX_ID {
+        return Err(anyhow!("index {} exceeds the max scsi address", index));
+    }
+
+    Ok(format!("{}:{}", scsi_id, lun))
+}
+
+// get_nvme_drive_name returns the disk name of the nvme device with index, each device
+// is attached to its own controller with a single namespace.
+pub(crate) fn get_nvme_drive_name(index: u64) -> String {
+    format!("nvme{}n1", index)
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::device::util::get_virt_drive_name;
+    use crate::device::util::{get_nvme_drive_name, get_scsi_address, get_virt_drive_name};
 
     #[actix_rt::test]
     async fn test_get_virt_drive_name() {
@@ -88,4 +110,19 @@ mod tests {
             assert_eq!(&out, output);
         }
     }
+
+    #[test]
+    fn test_get_scsi_address() {
+        assert_eq!(get_scsi_address(0).unwrap(), "0:0");
+        assert_eq!(get_scsi_address(255).unwrap(), "0:255");
+        assert_eq!(get_scsi_address(256).unwrap(), "1:0");
+        assert_eq!(get_scsi_address(65535).unwrap(), "255:255");
+        assert!(get_scsi_address(65536).is_err());
+    }
+
+    #[test]
+    fn test_get_nvme_drive_name() {
+        assert_eq!(get_nvme_drive_name(0), "nvme0n1");
+        assert_eq!(get_nvme_drive_name(3), "nvme3n1");
+    }
 }
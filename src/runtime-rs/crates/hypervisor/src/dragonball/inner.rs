@@ -6,30 +6,35 @@
 
 use super::vmm_instance::VmmInstance;
 use crate::{
-    device::DeviceType, hypervisor_persist::HypervisorState, kernel_param::KernelParams, VmmState,
-    DEV_HUGEPAGES, HUGETLBFS, HUGE_SHMEM, HYPERVISOR_DRAGONBALL, SHMEM,
+    device::DeviceType,
+    hypervisor_persist::HypervisorState,
+    kernel_param::KernelParams,
+    utils::{get_free_hugepages_mib, get_sandbox_path},
+    VmmState, DEV_HUGEPAGES, HUGETLBFS, HUGE_SHMEM, HYPERVISOR_DRAGONBALL, SHMEM,
 };
 use anyhow::{anyhow, Context, Result};
 use async_trait::async_trait;
 use dragonball::{
-    api::v1::{BootSourceConfig, VcpuResizeInfo},
+    api::v1::{BalloonDeviceConfigInfo, BootSourceConfig, MemDeviceConfigInfo, VcpuResizeInfo},
     vm::VmConfigInfo,
 };
 
-use kata_sys_util::mount;
+use kata_sys_util::{
+    host_lock::{self, HostResource, DEFAULT_LOCK_TIMEOUT},
+    mount, naming,
+};
 use kata_types::{
     capabilities::{Capabilities, CapabilityBits},
-    config::{
-        hypervisor::{HugePageType, Hypervisor as HypervisorConfig},
-        KATA_PATH,
-    },
+    config::hypervisor::{HugePageType, Hypervisor as HypervisorConfig},
 };
 use nix::mount::MsFlags;
 use persist::sandbox_persist::Persist;
-use std::{collections::HashSet, fs::create_dir_all};
+use std::{collections::HashSet, fs::create_dir_all, path::Path};
 
 const DRAGONBALL_KERNEL: &str = "vmlinux";
 const DRAGONBALL_ROOT_FS: &str = "rootfs";
+const MEM_DEVICE_ID: &str = "mem0";
+const BALLOON_DEVICE_ID: &str = "balloon0";
 
 #[derive(Debug)]
 pub struct DragonballInner {
@@ -68,6 +73,12 @@ pub struct DragonballInner {
 
     /// dragonball capabilities
     pub(crate) capabilities: Capabilities,
+
+    /// memory in MiB plugged by virtio-mem on top of the default memory
+    pub(crate) mem_hotplug_mb: u32,
+
+    /// memory in MiB taken back from the guest by the balloon
+    pub(crate) balloon_mb: u32,
 }
 
 impl DragonballInner {
@@ -91,6 +102,8 @@ impl DragonballInner {
             run_dir: "".to_string(),
             cached_block_devices: Default::default(),
             capabilities,
+            mem_hotplug_mb: 0,
+            balloon_mb: 0,
         }
     }
 
@@ -108,9 +121,15 @@ impl DragonballInner {
             &rootfs_driver,
             &self.config.boot_info.rootfs_type,
         )?);
+        if self.config.cpu_info.vcpu_rt_priority > 0 {
+            kernel_params.append(&mut KernelParams::new_realtime_kernel_params());
+        }
         kernel_params.append(&mut KernelParams::from_string(
             &self.config.boot_info.kernel_params,
         ));
+        kernel_params.append(&mut KernelParams::new_hardening_kernel_params(
+            &self.config.security_info,
+        ));
         info!(sl!(), "prepared kernel_params={:?}", kernel_params);
 
         // set boot source
@@ -128,6 +147,30 @@ impl DragonballInner {
             self.add_device(dev).await.context("add_device")?;
         }
 
+        // the devices resizing the memory are added empty, and resized once the VM is running
+        self.add_memory_devices()
+            .context("add memory resizing devices")?;
+
+        // the hugetlbfs pages are reserved when the guest memory is mapped, so the VMs started
+        // at the same time on the node take them one after the other, and the start fails early
+        // if the pool is too small
+        let _guard = if self.uses_hugetlbfs() {
+            let guard = host_lock::lock(HostResource::Hugepages, None, DEFAULT_LOCK_TIMEOUT)
+                .context("lock hugepages")?;
+            let free_mib = get_free_hugepages_mib().context("get free hugepages")?;
+            let mem_mib = self.config.memory_info.default_memory as u64;
+            if free_mib < mem_mib {
+                return Err(anyhow!(
+                    "not enough free hugepages: {} MiB needed, {} MiB free",
+                    mem_mib,
+                    free_mib
+                ));
+            }
+            Some(guard)
+        } else {
+            None
+        };
+
         // start vmm and wait ready
         self.start_vmm_instance().context("start vmm instance")?;
         self.wait_vmm_ready(timeout).context("wait vmm")?;
@@ -135,20 +178,25 @@ impl DragonballInner {
         Ok(())
     }
 
+    fn uses_hugetlbfs(&self) -> bool {
+        self.config.memory_info.enable_hugepages
+            && self.config.memory_info.hugepage_type == HugePageType::Hugetlbfs
+    }
+
     pub(crate) fn run_vmm_server(&mut self) -> Result<()> {
         if !self.config.jailer_path.is_empty() {
             self.jailed = true;
         }
 
+        // create run dir, which fails if its name clashes with another sandbox
+        self.run_dir = get_sandbox_path(&self.id);
+        naming::claim_dir(Path::new(&self.run_dir), &self.id)
+            .with_context(|| format!("failed to create dir {}", self.run_dir.as_str()))?;
+
         // create jailer root
         create_dir_all(self.jailer_root.as_str())
             .map_err(|e| anyhow!("Failed to create dir {} err : {:?}", self.jailer_root, e))?;
 
-        // create run dir
-        self.run_dir = [KATA_PATH, self.id.as_str()].join("/");
-        create_dir_all(self.run_dir.as_str())
-            .with_context(|| format!("failed to create dir {}", self.run_dir.as_str()))?;
-
         // run vmm server
         self.vmm_instance
             .run_vmm_server(&self.id, self.netns.clone())
@@ -176,6 +224,14 @@ impl DragonballInner {
     }
 
     fn set_vm_base_config(&mut self) -> Result<()> {
+        // the vCPUs always get the CPU flags of the host supported by KVM
+        if !self.config.cpu_info.is_host_cpu_model() {
+            return Err(anyhow!(
+                "cpu model {} isn't supported by dragonball",
+                self.config.cpu_info.cpu_model
+            ));
+        }
+
         let serial_path = [&self.run_dir, "console.sock"].join("/");
         let (mem_type, mem_file_path) = if self.config.memory_info.enable_hugepages {
             match self.config.memory_info.hugepage_type {
@@ -338,6 +394,87 @@ impl DragonballInner {
         Ok((old_vcpus, new_vcpus))
     }
 
+    // memory in MiB virtio-mem could plug on top of the default memory
+    fn max_mem_hotplug_mb(&self) -> u32 {
+        let memory_info = &self.config.memory_info;
+        if memory_info.enable_virtio_mem {
+            memory_info
+                .default_maxmemory
+                .saturating_sub(memory_info.default_memory)
+        } else {
+            0
+        }
+    }
+
+    fn add_memory_devices(&self) -> Result<()> {
+        let max_hotplug_mb = self.max_mem_hotplug_mb();
+        if max_hotplug_mb > 0 {
+            self.vmm_instance
+                .insert_mem(&mem_device_config(0, max_hotplug_mb))
+                .context("insert mem device")?;
+        }
+        if self.config.memory_info.enable_balloon {
+            self.vmm_instance
+                .insert_balloon(&balloon_device_config(0))
+                .context("insert balloon device")?;
+        }
+        Ok(())
+    }
+
+    // the memory in MiB the guest has: the default memory, grown by virtio-mem and shrunk
+    // by the balloon
+    fn current_memory(&self) -> u32 {
+        self.config.memory_info.default_memory + self.mem_hotplug_mb - self.balloon_mb
+    }
+
+    // resize the memory as far as the virtio-mem and the balloon devices allow, returns the
+    // memory size after resizing
+    pub(crate) async fn resize_memory(&mut self, new_mem_mb: u32) -> Result<u32> {
+        let default_memory = self.config.memory_info.default_memory;
+        let (hotplug_mb, balloon_mb) = if new_mem_mb >= default_memory {
+            (
+                (new_mem_mb - default_memory).min(self.max_mem_hotplug_mb()),
+                0,
+            )
+        } else if self.config.memory_info.enable_balloon {
+            (0, default_memory - new_mem_mb)
+        } else {
+            (0, 0)
+        };
+
+        // deflate the balloon before unplugging the memory, and inflate it after
+        if balloon_mb < self.balloon_mb {
+            self.resize_balloon(balloon_mb)?;
+        }
+        if hotplug_mb != self.mem_hotplug_mb {
+            info!(
+                sl!(),
+                "resize virtio-mem from {} MiB to {} MiB", self.mem_hotplug_mb, hotplug_mb
+            );
+            self.vmm_instance
+                .insert_mem(&mem_device_config(hotplug_mb, self.max_mem_hotplug_mb()))
+                .context("resize mem device")?;
+            self.mem_hotplug_mb = hotplug_mb;
+        }
+        if balloon_mb > self.balloon_mb {
+            self.resize_balloon(balloon_mb)?;
+        }
+
+        Ok(self.current_memory())
+    }
+
+    fn resize_balloon(&mut self, balloon_mb: u32) -> Result<()> {
+        info!(
+            sl!(),
+            "resize balloon from {} MiB to {} MiB", self.balloon_mb, balloon_mb
+        );
+        self.vmm_instance
+            .insert_balloon(&balloon_device_config(balloon_mb))
+            .context("resize balloon device")?;
+        self.balloon_mb = balloon_mb;
+        Ok(())
+    }
+
     pub fn set_hypervisor_config(&mut self, config: HypervisorConfig) {
         self.config = config;
     }
@@ -347,6 +484,31 @@ impl DragonballInner {
     }
 }
 
+fn mem_device_config(size_mb: u32, capacity_mb: u32) -> MemDeviceConfigInfo {
+    MemDeviceConfigInfo {
+        mem_id: MEM_DEVICE_ID.to_string(),
+        size_mib: size_mb as u64,
+        capacity_mib: capacity_mb as u64,
+        multi_region: false,
+        host_numa_node_id: None,
+        guest_numa_node_id: None,
+        use_shared_irq: None,
+        use_generic_irq: None,
+    }
+}
+
+fn balloon_device_config(size_mb: u32) -> BalloonDeviceConfigInfo {
+    BalloonDeviceConfigInfo {
+        balloon_id: BALLOON_DEVICE_ID.to_string(),
+        size_mib: size_mb as u64,
+        use_shared_irq: None,
+        use_generic_irq: None,
+        // the guest gets the memory back rather than killing the containers
+        f_deflate_on_oom: true,
+        f_reporting: false,
+    }
+}
+
 #[async_trait]
 impl Persist for DragonballInner {
     type State = HypervisorState;
@@ -386,6 +548,8 @@ impl Persist for DragonballInner {
             pending_devices: vec![],
             cached_block_devices: hypervisor_state.cached_block_devices,
             capabilities: Capabilities::new(),
+            mem_hotplug_mb: 0,
+            balloon_mb: 0,
         })
     }
 }
@@ -4,6 +4,7 @@
 // SPDX-License-Identifier: Apache-2.0
 //
 
+use std::convert::TryFrom;
 use std::path::PathBuf;
 
 use anyhow::{anyhow, Context, Result};
@@ -57,6 +58,7 @@ impl DragonballInner {
                     block.device_id.as_str(),
                     block.config.is_readonly,
                     block.config.no_drop,
+                    block.config.is_direct,
                 )
                 .context("add block device"),
             DeviceType::VhostUserBlk(block) => self
@@ -65,6 +67,7 @@ impl DragonballInner {
                     block.device_id.as_str(),
                     block.is_readonly,
                     block.no_drop,
+                    None,
                 )
                 .context("add vhost user based block device"),
             DeviceType::HybridVsock(hvsock) => self.add_hvsock(&hvsock.config).context("add vsock"),
@@ -175,6 +178,7 @@ impl DragonballInner {
         id: &str,
         read_only: bool,
         no_drop: bool,
+        is_direct: Option<bool>,
     ) -> Result<()> {
         let jailed_drive = self.get_resource(path, id).context("get resource")?;
         self.cached_block_devices.insert(id.to_string());
@@ -183,7 +187,7 @@ impl DragonballInner {
             drive_id: id.to_string(),
             device_type: BlockDeviceType::get_type(path),
             path_on_host: PathBuf::from(jailed_drive),
-            is_direct: self.config.blockdev_info.block_device_cache_direct,
+            is_direct: is_direct.unwrap_or(self.config.blockdev_info.block_device_cache_direct),
             no_drop,
             is_read_only: read_only,
             ..Default::default()
@@ -254,6 +258,9 @@ impl DragonballInner {
         fs_cfg.mode = String::from("virtio");
         fs_cfg.cache_policy = self.config.shared_fs.virtio_fs_cache.clone();
         fs_cfg.fuse_killpriv_v2 = true;
+        // overridden by the extra args
+        fs_cfg.thread_pool_size = u16::try_from(self.config.shared_fs.virtio_fs_thread_pool_size)
+            .context("invalid virtio-fs thread pool size")?;
 
         info!(
             sl!(),
@@ -428,5 +435,19 @@ mod tests {
         assert_eq!(fs_cfg.cache_policy, "auto".to_string());
         assert!(fs_cfg.drop_sys_resource);
         assert!(fs_cfg.thread_pool_size == 128);
+
+        // the size of the configuration applies without the extra args
+        let mut fs_cfg = FsDeviceConfigInfo::default();
+        dragonball.config.shared_fs.virtio_fs_extra_args = vec![];
+        dragonball.config.shared_fs.virtio_fs_thread_pool_size = 16;
+        dragonball
+            .parse_inline_virtiofs_args(&mut fs_cfg, &mut options)
+            .unwrap();
+        assert!(fs_cfg.thread_pool_size == 16);
+
+        dragonball.config.shared_fs.virtio_fs_thread_pool_size = u32::MAX;
+        assert!(dragonball
+            .parse_inline_virtiofs_args(&mut fs_cfg, &mut options)
+            .is_err());
     }
 }
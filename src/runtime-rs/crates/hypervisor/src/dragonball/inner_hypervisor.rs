@@ -9,7 +9,7 @@ use std::{
     iter::FromIterator,
 };
 
-use anyhow::{Context, Ok, Result};
+use anyhow::{anyhow, Context, Ok, Result};
 use kata_types::capabilities::Capabilities;
 
 use super::inner::DragonballInner;
@@ -51,6 +51,11 @@ impl DragonballInner {
         Ok(())
     }
 
+    pub(crate) async fn reboot_vm(&mut self) -> Result<()> {
+        info!(sl!(), "Rebooting dragonball VM");
+        Err(anyhow!("reboot vm is not supported by dragonball"))
+    }
+
     pub(crate) fn pause_vm(&self) -> Result<()> {
         info!(sl!(), "do pause vm");
         self.vmm_instance.pause().context("pause vm")?;
@@ -81,6 +86,17 @@ impl DragonballInner {
         self.vmm_instance.get_hypervisor_metrics()
     }
 
+    pub(crate) async fn dump_guest_memory(&self, path: &str) -> Result<()> {
+        info!(sl!(), "dump guest memory to {}", path);
+        self.vmm_instance.dump_guest_memory(path)
+    }
+
+    pub(crate) async fn get_command_line(&self) -> Result<Vec<String>> {
+        Err(anyhow!(
+            "dragonball runs in the shim process, it has no command line"
+        ))
+    }
+
     pub(crate) async fn disconnect(&mut self) {
         self.state = VmmState::NotReady;
     }
@@ -21,7 +21,7 @@ use kata_types::config::hypervisor::Hypervisor as HypervisorConfig;
 use tokio::sync::RwLock;
 use tracing::instrument;
 
-use crate::{DeviceType, Hypervisor, VcpuThreadIds};
+use crate::{metrics, DeviceType, Hypervisor, VcpuThreadIds, HYPERVISOR_DRAGONBALL};
 
 pub struct Dragonball {
     inner: Arc<RwLock<DragonballInner>>,
@@ -63,12 +63,26 @@ impl Hypervisor for Dragonball {
     #[instrument]
     async fn start_vm(&self, timeout: i32) -> Result<()> {
         let mut inner = self.inner.write().await;
-        inner.start_vm(timeout).await
+        metrics::observe(
+            HYPERVISOR_DRAGONBALL,
+            metrics::OP_START_VM,
+            "",
+            inner.start_vm(timeout),
+        )
+        .await
     }
 
     async fn stop_vm(&self) -> Result<()> {
         let mut inner = self.inner.write().await;
-        inner.stop_vm()
+        metrics::observe(HYPERVISOR_DRAGONBALL, metrics::OP_STOP_VM, "", async {
+            inner.stop_vm()
+        })
+        .await
+    }
+
+    async fn reboot_vm(&self) -> Result<()> {
+        let mut inner = self.inner.write().await;
+        inner.reboot_vm().await
     }
 
     async fn pause_vm(&self) -> Result<()> {
@@ -89,17 +103,48 @@ impl Hypervisor for Dragonball {
     // returns Result<(old_vcpus, new_vcpus)>
     async fn resize_vcpu(&self, old_vcpus: u32, new_vcpus: u32) -> Result<(u32, u32)> {
         let inner = self.inner.read().await;
-        inner.resize_vcpu(old_vcpus, new_vcpus).await
+        metrics::observe(
+            HYPERVISOR_DRAGONBALL,
+            metrics::OP_RESIZE_VCPU,
+            "",
+            inner.resize_vcpu(old_vcpus, new_vcpus),
+        )
+        .await
+    }
+
+    async fn resize_memory(&self, new_mem_mb: u32) -> Result<u32> {
+        let mut inner = self.inner.write().await;
+        metrics::observe(
+            HYPERVISOR_DRAGONBALL,
+            metrics::OP_RESIZE_MEMORY,
+            "",
+            inner.resize_memory(new_mem_mb),
+        )
+        .await
     }
 
     async fn add_device(&self, device: DeviceType) -> Result<()> {
         let mut inner = self.inner.write().await;
-        inner.add_device(device).await
+        let kind = metrics::device_kind(&device);
+        metrics::observe(
+            HYPERVISOR_DRAGONBALL,
+            metrics::OP_ADD_DEVICE,
+            kind,
+            inner.add_device(device),
+        )
+        .await
     }
 
     async fn remove_device(&self, device: DeviceType) -> Result<()> {
         let mut inner = self.inner.write().await;
-        inner.remove_device(device).await
+        let kind = metrics::device_kind(&device);
+        metrics::observe(
+            HYPERVISOR_DRAGONBALL,
+            metrics::OP_REMOVE_DEVICE,
+            kind,
+            inner.remove_device(device),
+        )
+        .await
     }
 
     async fn get_agent_socket(&self) -> Result<String> {
@@ -165,6 +210,16 @@ impl Hypervisor for Dragonball {
         let inner = self.inner.read().await;
         inner.get_hypervisor_metrics().await
     }
+
+    async fn dump_guest_memory(&self, path: &str) -> Result<()> {
+        let inner = self.inner.read().await;
+        inner.dump_guest_memory(path).await
+    }
+
+    async fn get_command_line(&self) -> Result<Vec<String>> {
+        let inner = self.inner.read().await;
+        inner.get_command_line().await
+    }
 }
 
 #[async_trait]
@@ -7,6 +7,7 @@
 use std::{
     fs::{File, OpenOptions},
     os::unix::{io::IntoRawFd, prelude::AsRawFd},
+    path::PathBuf,
     sync::{Arc, Mutex, RwLock},
     thread,
 };
@@ -15,9 +16,10 @@ use anyhow::{anyhow, Context, Result};
 use crossbeam_channel::{unbounded, Receiver, Sender};
 use dragonball::{
     api::v1::{
-        BlockDeviceConfigInfo, BootSourceConfig, FsDeviceConfigInfo, FsMountConfigInfo,
-        InstanceInfo, InstanceState, VcpuResizeInfo, VirtioNetDeviceConfigInfo, VmmAction,
-        VmmActionError, VmmData, VmmRequest, VmmResponse, VmmService, VsockDeviceConfigInfo,
+        BalloonDeviceConfigInfo, BlockDeviceConfigInfo, BootSourceConfig, FsDeviceConfigInfo,
+        FsMountConfigInfo, InstanceInfo, InstanceState, MemDeviceConfigInfo, VcpuResizeInfo,
+        VirtioNetDeviceConfigInfo, VmmAction, VmmActionError, VmmData, VmmRequest, VmmResponse,
+        VmmService, VsockDeviceConfigInfo,
     },
     vm::VmConfigInfo,
     Vmm,
@@ -26,7 +28,7 @@ use nix::sched::{setns, CloneFlags};
 use seccompiler::BpfProgram;
 use vmm_sys_util::eventfd::EventFd;
 
-use crate::ShareFsOperation;
+use crate::{utils::block_in_place, ShareFsOperation};
 
 pub enum Request {
     Sync(VmmAction),
@@ -255,6 +257,20 @@ impl VmmInstance {
         Ok(())
     }
 
+    pub fn insert_mem(&self, mem_cfg: &MemDeviceConfigInfo) -> Result<()> {
+        self.handle_request(Request::Sync(VmmAction::InsertMemDevice(mem_cfg.clone())))
+            .with_context(|| format!("Failed to insert mem device {:?}", mem_cfg))?;
+        Ok(())
+    }
+
+    pub fn insert_balloon(&self, balloon_cfg: &BalloonDeviceConfigInfo) -> Result<()> {
+        self.handle_request(Request::Sync(VmmAction::InsertBalloonDevice(
+            balloon_cfg.clone(),
+        )))
+        .with_context(|| format!("Failed to insert balloon device {:?}", balloon_cfg))?;
+        Ok(())
+    }
+
     pub fn pause(&self) -> Result<()> {
         todo!()
     }
@@ -276,6 +292,14 @@ impl VmmInstance {
         Err(anyhow!("Failed to get hypervisor metrics"))
     }
 
+    pub fn dump_guest_memory(&self, path: &str) -> Result<()> {
+        self.handle_request(Request::Sync(VmmAction::DumpGuestMemory(PathBuf::from(
+            path,
+        ))))
+        .with_context(|| format!("Failed to dump guest memory to {}", path))?;
+        Ok(())
+    }
+
     pub fn stop(&mut self) -> Result<()> {
         self.handle_request(Request::Sync(VmmAction::ShutdownMicroVm))
             .map_err(|e| {
@@ -297,7 +321,13 @@ impl VmmInstance {
         Ok(())
     }
 
+    // the request waits for the response of the VMM, the worker thread of the
+    // runtime is handed over meanwhile
     fn send_request(&self, vmm_action: VmmAction) -> Result<VmmResponse> {
+        block_in_place(|| self.do_send_request(vmm_action))
+    }
+
+    fn do_send_request(&self, vmm_action: VmmAction) -> Result<VmmResponse> {
         if let Some(ref to_vmm) = self.to_vmm {
             to_vmm
                 .send(Box::new(vmm_action.clone()))
@@ -346,7 +376,9 @@ impl VmmInstance {
                     }
                     Err(vmm_action_error) => {
                         if let VmmActionError::UpcallServerNotReady = vmm_action_error {
-                            std::thread::sleep(std::time::Duration::from_millis(10));
+                            block_in_place(|| {
+                                std::thread::sleep(std::time::Duration::from_millis(10))
+                            });
                             continue;
                         } else {
                             return Err(vmm_action_error.into());
@@ -0,0 +1,192 @@
+// Copyright (c) 2023 Kata Containers community
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+// The client of the API of Firecracker, HTTP requests with JSON bodies sent to
+// its unix socket. See the swagger definition of the API:
+// https://github.com/firecracker-microvm/firecracker/blob/main/src/api_server/swagger/firecracker.yaml
+
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use anyhow::{anyhow, Context, Result};
+use hyper::{Body, Client, Method, Request};
+use hyperlocal::{UnixClientExt, UnixConnector, Uri};
+use serde::{Deserialize, Serialize};
+
+// every request is handled right away by the API thread of the VMM
+const FC_API_TIMEOUT: Duration = Duration::from_secs(10);
+
+pub(crate) const INSTANCE_START: &str = "InstanceStart";
+pub(crate) const VM_STATE_PAUSED: &str = "Paused";
+pub(crate) const VM_STATE_RESUMED: &str = "Resumed";
+
+#[derive(Serialize, Debug, Default, Clone, PartialEq)]
+pub(crate) struct MachineConfig {
+    pub vcpu_count: u32,
+    pub mem_size_mib: u32,
+}
+
+#[derive(Serialize, Debug, Default, Clone, PartialEq)]
+pub(crate) struct BootSource {
+    pub kernel_image_path: String,
+    pub boot_args: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub initrd_path: Option<String>,
+}
+
+#[derive(Serialize, Debug, Default, Clone, PartialEq)]
+pub(crate) struct Drive {
+    pub drive_id: String,
+    pub path_on_host: String,
+    pub is_root_device: bool,
+    pub is_read_only: bool,
+    // "Sync" or "Async", the latter is io_uring
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub io_engine: Option<String>,
+}
+
+// only the backing file of a drive can be changed once the VM is running
+#[derive(Serialize, Debug, Default, Clone, PartialEq)]
+pub(crate) struct PartialDrive {
+    pub drive_id: String,
+    pub path_on_host: String,
+}
+
+#[derive(Serialize, Debug, Default, Clone, PartialEq)]
+pub(crate) struct NetworkInterface {
+    pub iface_id: String,
+    pub host_dev_name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub guest_mac: Option<String>,
+}
+
+#[derive(Serialize, Debug, Default, Clone, PartialEq)]
+pub(crate) struct Vsock {
+    pub guest_cid: u32,
+    pub uds_path: String,
+}
+
+#[derive(Serialize, Debug, Default, Clone, PartialEq)]
+pub(crate) struct InstanceActionInfo {
+    pub action_type: String,
+}
+
+#[derive(Serialize, Debug, Default, Clone, PartialEq)]
+pub(crate) struct Vm {
+    pub state: String,
+}
+
+#[derive(Deserialize, Debug, Default)]
+struct ApiError {
+    fault_message: String,
+}
+
+pub(crate) struct FcApiClient {
+    sock_path: PathBuf,
+    client: Client<UnixConnector, Body>,
+}
+
+impl std::fmt::Debug for FcApiClient {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("FcApiClient")
+            .field("sock_path", &self.sock_path)
+            .finish()
+    }
+}
+
+impl FcApiClient {
+    pub(crate) fn new(sock_path: &Path) -> Self {
+        Self {
+            sock_path: sock_path.to_path_buf(),
+            client: Client::unix(),
+        }
+    }
+
+    /// Get the information of the instance, which succeeds once the API server is up.
+    pub(crate) async fn ping(&self) -> Result<()> {
+        self.request(Method::GET, "/", None).await
+    }
+
+    pub(crate) async fn put<T: Serialize>(&self, path: &str, body: &T) -> Result<()> {
+        let body = serde_json::to_vec(body).context("serialize request")?;
+        self.request(Method::PUT, path, Some(body)).await
+    }
+
+    pub(crate) async fn patch<T: Serialize>(&self, path: &str, body: &T) -> Result<()> {
+        let body = serde_json::to_vec(body).context("serialize request")?;
+        self.request(Method::PATCH, path, Some(body)).await
+    }
+
+    async fn request(&self, method: Method, path: &str, body: Option<Vec<u8>>) -> Result<()> {
+        let msg = format!("{} {}", method, path);
+        let url: hyper::Uri = Uri::new(&self.sock_path, path).into();
+        let req = Request::builder()
+            .method(method)
+            .uri(url)
+            .header("Accept", "application/json")
+            .header("Content-Type", "application/json")
+            .body(body.map_or_else(Body::empty, Body::from))?;
+
+        let resp = tokio::time::timeout(FC_API_TIMEOUT, self.client.request(req))
+            .await
+            .map_err(|_| anyhow!("{} timeout after {:?}", msg, FC_API_TIMEOUT))?
+            .with_context(|| format!("{} failed", msg))?;
+        let status = resp.status();
+        let body = hyper::body::to_bytes(resp.into_body())
+            .await
+            .with_context(|| format!("read the response of {}", msg))?;
+        if !status.is_success() {
+            return Err(anyhow!("{}: {} {}", msg, status, fault_message(&body)));
+        }
+
+        Ok(())
+    }
+}
+
+// the error of a request is described by its fault message
+fn fault_message(body: &[u8]) -> String {
+    serde_json::from_slice::<ApiError>(body)
+        .map(|e| e.fault_message)
+        .unwrap_or_else(|_| String::from_utf8_lossy(body).into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_request_body() {
+        let drive = Drive {
+            drive_id: "drive_1".to_string(),
+            path_on_host: "/drive_1".to_string(),
+            is_root_device: false,
+            is_read_only: true,
+            io_engine: None,
+        };
+        assert_eq!(
+            serde_json::to_string(&drive).unwrap(),
+            r#"{"drive_id":"drive_1","path_on_host":"/drive_1","is_root_device":false,"is_read_only":true}"#
+        );
+
+        let iface = NetworkInterface {
+            iface_id: "eth0".to_string(),
+            host_dev_name: "tap0_kata".to_string(),
+            guest_mac: Some("02:00:00:00:00:01".to_string()),
+        };
+        assert_eq!(
+            serde_json::to_string(&iface).unwrap(),
+            r#"{"iface_id":"eth0","host_dev_name":"tap0_kata","guest_mac":"02:00:00:00:00:01"}"#
+        );
+    }
+
+    #[test]
+    fn test_fault_message() {
+        assert_eq!(
+            fault_message(br#"{"fault_message":"The requested operation is not supported after starting the microVM."}"#),
+            "The requested operation is not supported after starting the microVM."
+        );
+        assert_eq!(fault_message(b"Bad Request"), "Bad Request");
+    }
+}
@@ -0,0 +1,186 @@
+// Copyright (c) 2019-2022 Alibaba Cloud
+// Copyright (c) 2023 Kata Containers community
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+use super::fc_api::FcApiClient;
+use crate::device::DeviceType;
+use crate::hypervisor_persist::HypervisorState;
+use crate::{VmmState, HYPERVISOR_NAME_FIRECRACKER};
+use anyhow::{anyhow, Context, Result};
+use async_trait::async_trait;
+use kata_sys_util::mount;
+use kata_types::capabilities::{Capabilities, CapabilityBits};
+use kata_types::config::hypervisor::Hypervisor as HypervisorConfig;
+use nix::mount::MsFlags;
+use persist::sandbox_persist::Persist;
+use std::collections::HashSet;
+use std::path::Path;
+use tokio::process::Child;
+use tokio::task::JoinHandle;
+
+// the files of the VMM in its root, named from it when jailed
+pub(crate) const FC_API_SOCKET: &str = "api.sock";
+pub(crate) const FC_KERNEL: &str = "vmlinux";
+pub(crate) const FC_INITRD: &str = "initrd";
+
+#[derive(Debug)]
+pub struct FirecrackerInner {
+    /// sandbox id
+    pub(crate) id: String,
+
+    /// vm path
+    pub(crate) vm_path: String,
+
+    /// jailed flag
+    pub(crate) jailed: bool,
+
+    /// root of the files of the VMM, the chroot of the jailer when jailed
+    pub(crate) jailer_root: String,
+
+    /// netns
+    pub(crate) netns: Option<String>,
+
+    /// hypervisor config
+    pub(crate) config: HypervisorConfig,
+
+    /// vmm state
+    pub(crate) state: VmmState,
+
+    /// the VMM process, the jailer execs it
+    pub(crate) process: Option<Child>,
+    pub(crate) pid: Option<u32>,
+
+    /// the tasks logging the output of the VMM
+    pub(crate) tasks: Vec<JoinHandle<()>>,
+
+    /// devices added before the VM boots
+    pub(crate) pending_devices: Vec<DeviceType>,
+
+    /// the drives of the pool backed by a block device
+    pub(crate) cached_block_devices: HashSet<String>,
+
+    pub(crate) capabilities: Capabilities,
+}
+
+impl FirecrackerInner {
+    pub fn new() -> Self {
+        // there is no shared filesystem device, the block devices of the
+        // containers are hot plugged into the drives of the pool
+        let mut capabilities = Capabilities::new();
+        capabilities
+            .set(CapabilityBits::BlockDeviceSupport | CapabilityBits::BlockDeviceHotplugSupport);
+
+        Self {
+            id: String::default(),
+            vm_path: String::default(),
+            jailed: false,
+            jailer_root: String::default(),
+            netns: None,
+            config: Default::default(),
+            state: VmmState::NotReady,
+            process: None,
+            pid: None,
+            tasks: vec![],
+            pending_devices: vec![],
+            cached_block_devices: Default::default(),
+            capabilities,
+        }
+    }
+
+    pub fn set_hypervisor_config(&mut self, config: HypervisorConfig) {
+        self.config = config;
+    }
+
+    pub fn hypervisor_config(&self) -> HypervisorConfig {
+        self.config.clone()
+    }
+
+    pub(crate) fn api_client(&self) -> FcApiClient {
+        FcApiClient::new(&Path::new(&self.jailer_root).join(FC_API_SOCKET))
+    }
+
+    /// The path of the file `name` of the root of the VMM, as seen by the VMM.
+    pub(crate) fn vmm_path(&self, name: &str) -> String {
+        if self.jailed {
+            ["", name].join("/")
+        } else {
+            [self.jailer_root.as_str(), name].join("/")
+        }
+    }
+
+    /// Get the path of `src` for the VMM, it's bind mounted into the root as
+    /// `name` when jailed.
+    pub(crate) fn get_resource(&self, src: &str, name: &str) -> Result<String> {
+        if !self.jailed {
+            return Ok(src.to_string());
+        }
+
+        info!(sl!(), "jail resource: src {} dst {}", src, name);
+        if src.is_empty() || name.is_empty() {
+            return Err(anyhow!("invalid param src {} dst {}", src, name));
+        }
+        let jailed_location = [self.jailer_root.as_str(), name].join("/");
+        mount::bind_mount_unchecked(src, jailed_location.as_str(), false, MsFlags::MS_SLAVE)
+            .context("bind_mount")?;
+
+        Ok(self.vmm_path(name))
+    }
+
+    pub(crate) fn umount_jail_resource(&self, name: &str) -> Result<()> {
+        let path = [self.jailer_root.as_str(), name].join("/");
+        nix::mount::umount2(path.as_str(), nix::mount::MntFlags::MNT_DETACH)
+            .with_context(|| format!("umount path {}", &path))?;
+        std::fs::remove_file(&path).with_context(|| format!("remove {}", &path))
+    }
+}
+
+impl Default for FirecrackerInner {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl Persist for FirecrackerInner {
+    type State = HypervisorState;
+    type ConstructorArgs = ();
+
+    /// Save a state of hypervisor
+    async fn save(&self) -> Result<Self::State> {
+        Ok(HypervisorState {
+            hypervisor_type: HYPERVISOR_NAME_FIRECRACKER.to_string(),
+            pid: self.pid.map(|pid| pid as i32),
+            api_socket: [self.jailer_root.as_str(), FC_API_SOCKET].join("/"),
+            id: self.id.clone(),
+            vm_path: self.vm_path.clone(),
+            jailed: self.jailed,
+            jailer_root: self.jailer_root.clone(),
+            netns: self.netns.clone(),
+            config: self.hypervisor_config(),
+            run_dir: self.vm_path.clone(),
+            cached_block_devices: self.cached_block_devices.clone(),
+            ..Default::default()
+        })
+    }
+
+    /// Restore hypervisor
+    async fn restore(
+        _hypervisor_args: Self::ConstructorArgs,
+        hypervisor_state: Self::State,
+    ) -> Result<Self> {
+        // the VMM is no child of the restarted shim, it's stopped by its pid
+        Ok(Self {
+            id: hypervisor_state.id,
+            vm_path: hypervisor_state.vm_path,
+            jailed: hypervisor_state.jailed,
+            jailer_root: hypervisor_state.jailer_root,
+            netns: hypervisor_state.netns,
+            config: hypervisor_state.config,
+            pid: hypervisor_state.pid.map(|pid| pid as u32),
+            cached_block_devices: hypervisor_state.cached_block_devices,
+            ..Default::default()
+        })
+    }
+}
@@ -0,0 +1,256 @@
+// Copyright (c) 2019-2022 Alibaba Cloud
+// Copyright (c) 2023 Kata Containers community
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+use super::fc_api::{Drive, NetworkInterface, PartialDrive, Vsock};
+use super::inner::FirecrackerInner;
+use super::inner_hypervisor::disk_name;
+use crate::device::DeviceType;
+use crate::{BlockConfig, HybridVsockConfig, NetworkConfig, VmmState, DEFAULT_HYBRID_VSOCK_NAME};
+use anyhow::{anyhow, Context, Result};
+use kata_types::config::hypervisor::BLOCK_DEVICE_AIO_IO_URING;
+use std::collections::HashMap;
+use std::fs::File;
+
+// Firecracker can't hot plug devices. The block devices are hot plugged into a
+// pool of drives attached at boot instead, by replacing the empty file backing
+// a drive, and the guest sees the new size of the drive. The drive of index i
+// is the i-th virtio-mmio block device of the guest, i.e. the /dev/vdX of the
+// index of the block device.
+const FC_DRIVE_POOL_SIZE: u64 = 8;
+
+const FC_IO_ENGINE_ASYNC: &str = "Async";
+
+fn drive_id(index: u64) -> String {
+    format!("drive_{}", index)
+}
+
+impl FirecrackerInner {
+    pub(crate) fn create_drive_placeholders(&self) -> Result<()> {
+        for index in 0..FC_DRIVE_POOL_SIZE {
+            let path = [self.jailer_root.as_str(), &drive_id(index)].join("/");
+            File::create(&path).with_context(|| format!("create {}", path))?;
+        }
+        Ok(())
+    }
+
+    pub(crate) async fn add_device(&mut self, device: DeviceType) -> Result<()> {
+        if self.state != VmmState::VmRunning {
+            self.pending_devices.push(device);
+            return Ok(());
+        }
+
+        match device {
+            DeviceType::Block(block) => self
+                .hotplug_block_device(&block.config)
+                .await
+                .context("add block device"),
+            _ => Err(anyhow!("firecracker can't hot plug device {:?}", device)),
+        }
+    }
+
+    pub(crate) async fn remove_device(&mut self, device: DeviceType) -> Result<()> {
+        if self.state != VmmState::VmRunning {
+            return Ok(());
+        }
+
+        match device {
+            DeviceType::Block(block) => self
+                .unplug_block_device(&block.config)
+                .await
+                .context("remove block device"),
+            // the other devices are removed along with the VM
+            _ => Ok(()),
+        }
+    }
+
+    /// Add the devices requested before the VM boots, and the pool of drives.
+    pub(crate) async fn add_pending_devices(&mut self) -> Result<()> {
+        let mut block_devices = HashMap::new();
+        for device in std::mem::take(&mut self.pending_devices) {
+            match device {
+                DeviceType::Block(block) => {
+                    check_drive_index(block.config.index)?;
+                    block_devices.insert(block.config.index, block.config);
+                }
+                DeviceType::Network(network) => self
+                    .add_net_device(&network.config)
+                    .await
+                    .context("add net device")?,
+                DeviceType::HybridVsock(hvsock) => {
+                    self.add_hvsock(&hvsock.config).await.context("add vsock")?
+                }
+                _ => return Err(anyhow!("firecracker doesn't support device {:?}", device)),
+            }
+        }
+
+        let io_engine = if self.config.blockdev_info.block_device_aio == BLOCK_DEVICE_AIO_IO_URING {
+            Some(FC_IO_ENGINE_ASYNC.to_string())
+        } else {
+            None
+        };
+        for index in 0..FC_DRIVE_POOL_SIZE {
+            let id = drive_id(index);
+            let drive = match block_devices.get(&index) {
+                // the guest booted from the initrd keeps the first drive free
+                Some(config) if config.path_on_host != self.config.boot_info.initrd => Drive {
+                    drive_id: id.clone(),
+                    path_on_host: self.attach_disk(&id, &config.path_on_host)?,
+                    is_root_device: false,
+                    is_read_only: config.is_readonly,
+                    io_engine: io_engine.clone(),
+                },
+                _ => Drive {
+                    drive_id: id.clone(),
+                    path_on_host: self.vmm_path(&id),
+                    is_root_device: false,
+                    is_read_only: false,
+                    io_engine: io_engine.clone(),
+                },
+            };
+            self.api_client()
+                .put(&format!("/drives/{}", id), &drive)
+                .await
+                .with_context(|| format!("add drive {}", id))?;
+        }
+
+        Ok(())
+    }
+
+    async fn hotplug_block_device(&mut self, config: &BlockConfig) -> Result<()> {
+        check_drive_index(config.index)?;
+        let id = drive_id(config.index);
+        if self.cached_block_devices.contains(&id) {
+            return Err(anyhow!("drive {} is in use", id));
+        }
+        if config.is_readonly {
+            warn!(
+                sl!(),
+                "the drive {} of {} can't be made read-only once the VM is running",
+                id,
+                config.path_on_host
+            );
+        }
+
+        let path_on_host = self.attach_disk(&id, &config.path_on_host)?;
+        let drive = PartialDrive {
+            drive_id: id.clone(),
+            path_on_host,
+        };
+        if let Err(e) = self
+            .api_client()
+            .patch(&format!("/drives/{}", id), &drive)
+            .await
+        {
+            self.detach_disk(&id).ok();
+            return Err(e);
+        }
+
+        Ok(())
+    }
+
+    async fn unplug_block_device(&mut self, config: &BlockConfig) -> Result<()> {
+        let id = drive_id(config.index);
+        if !self.cached_block_devices.contains(&id) {
+            return Ok(());
+        }
+
+        let drive = PartialDrive {
+            drive_id: id.clone(),
+            path_on_host: self.vmm_path(&id),
+        };
+        self.api_client()
+            .patch(&format!("/drives/{}", id), &drive)
+            .await?;
+
+        self.detach_disk(&id)
+    }
+
+    // Get the path of the block device backing the drive `id` for the VMM.
+    fn attach_disk(&mut self, id: &str, path: &str) -> Result<String> {
+        let path = self
+            .get_resource(path, &disk_name(id))
+            .context("get resource")?;
+        self.cached_block_devices.insert(id.to_string());
+        Ok(path)
+    }
+
+    fn detach_disk(&mut self, id: &str) -> Result<()> {
+        if self.jailed {
+            self.umount_jail_resource(&disk_name(id))
+                .context("umount jail resource")?;
+        }
+        self.cached_block_devices.remove(id);
+        Ok(())
+    }
+
+    async fn add_net_device(&mut self, config: &NetworkConfig) -> Result<()> {
+        let iface = NetworkInterface {
+            iface_id: config.virt_iface_name.clone(),
+            host_dev_name: config.host_dev_name.clone(),
+            guest_mac: config.guest_mac.as_ref().map(|mac| format!("{:?}", mac)),
+        };
+        info!(
+            sl!(),
+            "add {} endpoint to {}", iface.host_dev_name, iface.iface_id
+        );
+
+        self.api_client()
+            .put(&format!("/network-interfaces/{}", iface.iface_id), &iface)
+            .await
+    }
+
+    // The vsock is backed by the hybrid vsock socket in the root of the VMM,
+    // rather than by the `uds_path` of the config which is out of the jail.
+    async fn add_hvsock(&mut self, config: &HybridVsockConfig) -> Result<()> {
+        let _ =
+            std::fs::remove_file([self.jailer_root.as_str(), DEFAULT_HYBRID_VSOCK_NAME].join("/"));
+        let vsock = Vsock {
+            guest_cid: config.guest_cid,
+            uds_path: self.vmm_path(DEFAULT_HYBRID_VSOCK_NAME),
+        };
+        debug!(sl!(), "HybridVsock configure: {:?}", &vsock);
+
+        self.api_client().put("/vsock", &vsock).await
+    }
+}
+
+fn check_drive_index(index: u64) -> Result<()> {
+    if index >= FC_DRIVE_POOL_SIZE {
+        return Err(anyhow!(
+            "no drive left for the block device of index {}, the pool has {} drives",
+            index,
+            FC_DRIVE_POOL_SIZE
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[actix_rt::test]
+    async fn test_pending_devices() {
+        let mut fc = FirecrackerInner::new();
+        let block = |index| {
+            DeviceType::Block(crate::BlockDevice {
+                config: BlockConfig {
+                    index,
+                    ..Default::default()
+                },
+                ..Default::default()
+            })
+        };
+
+        // queued until the VM boots
+        fc.add_device(block(0)).await.unwrap();
+        assert_eq!(fc.pending_devices.len(), 1);
+        fc.remove_device(block(0)).await.unwrap();
+
+        assert!(check_drive_index(FC_DRIVE_POOL_SIZE - 1).is_ok());
+        assert!(check_drive_index(FC_DRIVE_POOL_SIZE).is_err());
+    }
+}
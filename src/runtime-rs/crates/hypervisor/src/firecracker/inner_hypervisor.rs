@@ -0,0 +1,563 @@
+// Copyright (c) 2019-2022 Alibaba Cloud
+// Copyright (c) 2023 Kata Containers community
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+use super::fc_api::{
+    BootSource, InstanceActionInfo, MachineConfig, Vm, INSTANCE_START, VM_STATE_PAUSED,
+    VM_STATE_RESUMED,
+};
+use super::inner::{FirecrackerInner, FC_API_SOCKET, FC_INITRD, FC_KERNEL};
+use crate::kernel_param::KernelParams;
+use crate::utils::{get_child_threads, get_command_line, get_jailer_root, get_sandbox_path};
+use crate::{
+    VcpuThreadIds, VmmState, DEFAULT_HYBRID_VSOCK_NAME, JAILER_ROOT, VM_ROOTFS_DRIVER_MMIO,
+};
+use anyhow::{anyhow, Context, Result};
+use futures::future::join_all;
+use kata_sys_util::{mount, naming};
+use kata_types::capabilities::Capabilities;
+use nix::mount::MsFlags;
+use nix::sched::{setns, CloneFlags};
+use nix::sys::signal::{kill, Signal};
+use nix::unistd::Pid;
+use std::fs::{create_dir_all, File};
+use std::os::unix::io::AsRawFd;
+use std::path::Path;
+use std::process::Stdio;
+use tokio::io::{AsyncBufReadExt, AsyncRead, BufReader};
+use tokio::process::Command;
+use tokio::time::{Duration, Instant};
+
+const FC_NAME: &str = "firecracker";
+
+// The id of the jail, which is in the directory of the sandbox already. It's
+// short for the paths of the sockets in the jail to fit.
+const FC_JAIL_ID: &str = "vm";
+
+/// Number of milliseconds to wait before retrying to reach the API socket.
+const FC_POLL_TIME_MS: u64 = 10;
+
+impl FirecrackerInner {
+    pub(crate) async fn prepare_vm(&mut self, id: &str, netns: Option<String>) -> Result<()> {
+        self.id = id.to_string();
+        self.state = VmmState::NotReady;
+
+        self.vm_path = get_sandbox_path(id);
+        self.jailed = !self.config.jailer_path.is_empty();
+        self.jailer_root = if self.jailed {
+            jail_root(&self.vm_path, &self.config.path)
+        } else {
+            get_jailer_root(id)
+        };
+        self.netns = netns;
+
+        Ok(())
+    }
+
+    pub(crate) async fn start_vm(&mut self, timeout_secs: i32) -> Result<()> {
+        self.setup_environment().context("setup environment")?;
+        self.launch().await.context("launch")?;
+
+        let result = async {
+            self.wait_vmm_ready(timeout_secs)
+                .await
+                .context("wait vmm")?;
+            self.boot_vm().await.context("boot vm")
+        }
+        .await;
+        if let Err(error) = result {
+            error!(sl!(), "start firecracker vm error {:?}", error);
+            if let Err(err) = self.stop_vm().await {
+                error!(sl!(), "failed to stop firecracker err : {:?}", err);
+            }
+            return Err(error);
+        }
+
+        Ok(())
+    }
+
+    fn setup_environment(&mut self) -> Result<()> {
+        // create run dir, which fails if its name clashes with another sandbox
+        naming::claim_dir(Path::new(&self.vm_path), &self.id)
+            .with_context(|| format!("failed to create dir {}", self.vm_path))?;
+        create_dir_all(&self.jailer_root)
+            .with_context(|| format!("failed to create dir {}", self.jailer_root))?;
+
+        for name in &[FC_API_SOCKET, DEFAULT_HYBRID_VSOCK_NAME] {
+            naming::check_socket_path(&[self.jailer_root.as_str(), name].join("/"))
+                .context("check socket path")?;
+        }
+
+        // the jail is shared, so that the drives bind mounted into it once
+        // the jailer is running propagate into the mount namespace of the jailer
+        if self.jailed {
+            mount::bind_mount_unchecked(
+                &self.jailer_root,
+                &self.jailer_root,
+                false,
+                MsFlags::MS_SHARED,
+            )
+            .context("bind mount jail")?;
+        }
+
+        self.create_drive_placeholders()
+            .context("create drive placeholders")
+    }
+
+    async fn launch(&mut self) -> Result<()> {
+        if let Some(pid) = self.pid {
+            return Err(anyhow!("{} already running with PID {}", FC_NAME, pid));
+        }
+
+        let _ = std::fs::remove_file([self.jailer_root.as_str(), FC_API_SOCKET].join("/"));
+
+        let (program, args) = self.vmm_command();
+        info!(sl!(), "launch {} {:?}", program, args);
+        let mut cmd = Command::new(&program);
+        cmd.args(&args)
+            .current_dir("/")
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+
+        // the jailer joins the netns itself
+        let netns = match self.netns.as_ref() {
+            Some(netns) if !self.jailed => {
+                Some(File::open(netns).with_context(|| format!("open netns path {}", netns))?)
+            }
+            _ => None,
+        };
+        if let Some(netns) = netns.as_ref() {
+            let netns_fd = netns.as_raw_fd();
+            // Safety: setns is async-signal-safe.
+            unsafe {
+                cmd.pre_exec(move || {
+                    setns(netns_fd, CloneFlags::CLONE_NEWNET).map_err(std::io::Error::from)
+                });
+            }
+        }
+
+        let mut child = cmd
+            .spawn()
+            .with_context(|| format!("{} spawn failed", program))?;
+        self.pid = child.id();
+
+        if let Some(stdout) = child.stdout.take() {
+            self.tasks.push(log_output(stdout, "stdout"));
+        }
+        if let Some(stderr) = child.stderr.take() {
+            self.tasks.push(log_output(stderr, "stderr"));
+        }
+        self.process = Some(child);
+
+        Ok(())
+    }
+
+    // The program and the arguments starting the VMM, by the jailer if jailed.
+    fn vmm_command(&self) -> (String, Vec<String>) {
+        let mut fc_args = vec!["--api-sock".to_string(), self.vmm_path(FC_API_SOCKET)];
+        if self.config.security_info.disable_seccomp {
+            fc_args.push("--no-seccomp".to_string());
+        }
+
+        if !self.jailed {
+            return (self.config.path.clone(), fc_args);
+        }
+
+        let mut args: Vec<String> = vec![
+            "--id",
+            FC_JAIL_ID,
+            "--exec-file",
+            self.config.path.as_str(),
+            "--uid",
+            "0",
+            "--gid",
+            "0",
+            "--chroot-base-dir",
+            self.vm_path.as_str(),
+        ]
+        .into_iter()
+        .map(String::from)
+        .collect();
+        if let Some(netns) = self.netns.as_ref() {
+            args.push("--netns".to_string());
+            args.push(netns.clone());
+        }
+        args.push("--".to_string());
+        args.append(&mut fc_args);
+
+        (self.config.jailer_path.clone(), args)
+    }
+
+    async fn wait_vmm_ready(&mut self, timeout_secs: i32) -> Result<()> {
+        let client = self.api_client();
+        let deadline = Instant::now() + Duration::from_secs(timeout_secs as u64);
+        loop {
+            if client.ping().await.is_ok() {
+                return Ok(());
+            }
+            if let Some(child) = self.process.as_mut() {
+                if let Some(status) = child.try_wait().context("wait vmm")? {
+                    return Err(anyhow!("{} exited with {}", FC_NAME, status));
+                }
+            }
+            if Instant::now() > deadline {
+                return Err(anyhow!(
+                    "API socket isn't ready after {} seconds",
+                    timeout_secs
+                ));
+            }
+            tokio::time::sleep(Duration::from_millis(FC_POLL_TIME_MS)).await;
+        }
+    }
+
+    fn get_kernel_params(&self) -> Result<String> {
+        let config = &self.config;
+        let mut params = KernelParams::new(config.debug_info.enable_debug);
+
+        // the guest is booted from the initrd, or from the first drive
+        if config.boot_info.initrd.is_empty() {
+            params.append(&mut KernelParams::new_rootfs_kernel_params(
+                VM_ROOTFS_DRIVER_MMIO,
+                &config.boot_info.rootfs_type,
+            )?);
+        }
+
+        // the devices are virtio-mmio ones, described on the command line
+        // by Firecracker
+        params.append(&mut KernelParams::from_string("pci=off iommu=off"));
+        if config.debug_info.enable_debug {
+            params.append(&mut KernelParams::from_string("console=ttyS0"));
+        } else {
+            params.append(&mut KernelParams::from_string("quiet 8250.nr_uarts=0"));
+        }
+
+        if config.cpu_info.vcpu_rt_priority > 0 {
+            params.append(&mut KernelParams::new_realtime_kernel_params());
+        }
+        params.append(&mut KernelParams::from_string(
+            &config.boot_info.kernel_params,
+        ));
+        params.append(&mut KernelParams::new_hardening_kernel_params(
+            &config.security_info,
+        ));
+
+        params.to_string()
+    }
+
+    async fn boot_vm(&mut self) -> Result<()> {
+        let client = self.api_client();
+
+        let machine_config = MachineConfig {
+            vcpu_count: self.config.cpu_info.default_vcpus as u32,
+            mem_size_mib: self.config.memory_info.default_memory,
+        };
+        info!(sl!(), "machine config: {:?}", machine_config);
+        client
+            .put("/machine-config", &machine_config)
+            .await
+            .context("set machine config")?;
+
+        let initrd = &self.config.boot_info.initrd;
+        let boot_source = BootSource {
+            kernel_image_path: self
+                .get_resource(&self.config.boot_info.kernel, FC_KERNEL)
+                .context("get kernel")?,
+            boot_args: self.get_kernel_params().context("get kernel params")?,
+            initrd_path: if initrd.is_empty() {
+                None
+            } else {
+                Some(self.get_resource(initrd, FC_INITRD).context("get initrd")?)
+            },
+        };
+        info!(sl!(), "boot source: {:?}", boot_source);
+        client
+            .put("/boot-source", &boot_source)
+            .await
+            .context("set boot source")?;
+
+        self.add_pending_devices()
+            .await
+            .context("add pending devices")?;
+
+        client
+            .put(
+                "/actions",
+                &InstanceActionInfo {
+                    action_type: INSTANCE_START.to_string(),
+                },
+            )
+            .await
+            .context("start instance")?;
+        self.state = VmmState::VmRunning;
+
+        Ok(())
+    }
+
+    pub(crate) async fn stop_vm(&mut self) -> Result<()> {
+        info!(sl!(), "Stopping firecracker VM");
+        match self.process.take() {
+            // Note that this kills _and_ waits for the process!
+            Some(mut child) => child.kill().await.context("kill vmm")?,
+            // the VMM of a restored sandbox, unless its pid was reused
+            None => {
+                if let Some(pid) = self.pid.filter(|pid| self.is_vmm(*pid)) {
+                    kill(Pid::from_raw(pid as i32), Signal::SIGKILL).context("kill vmm")?;
+                }
+            }
+        }
+        join_all(self.tasks.drain(..)).await;
+
+        self.pid = None;
+        self.state = VmmState::NotReady;
+        Ok(())
+    }
+
+    fn is_vmm(&self, pid: u32) -> bool {
+        let name = Path::new(&self.config.path).file_name();
+        get_command_line(pid)
+            .ok()
+            .and_then(|cmdline| cmdline.into_iter().next())
+            .map_or(false, |arg0| Path::new(&arg0).file_name() == name)
+    }
+
+    pub(crate) async fn reboot_vm(&mut self) -> Result<()> {
+        Err(anyhow!("reboot vm is not supported by firecracker"))
+    }
+
+    pub(crate) async fn pause_vm(&self) -> Result<()> {
+        info!(sl!(), "do pause vm");
+        self.set_vm_state(VM_STATE_PAUSED).await.context("pause vm")
+    }
+
+    pub(crate) async fn resume_vm(&self) -> Result<()> {
+        info!(sl!(), "do resume vm");
+        self.set_vm_state(VM_STATE_RESUMED)
+            .await
+            .context("resume vm")
+    }
+
+    async fn set_vm_state(&self, state: &str) -> Result<()> {
+        self.api_client()
+            .patch(
+                "/vm",
+                &Vm {
+                    state: state.to_string(),
+                },
+            )
+            .await
+    }
+
+    pub(crate) async fn save_vm(&self) -> Result<()> {
+        Err(anyhow!("save vm is not supported by firecracker yet"))
+    }
+
+    pub(crate) async fn get_agent_socket(&self) -> Result<String> {
+        const HYBRID_VSOCK_SCHEME: &str = "hvsock";
+        Ok(format!(
+            "{}://{}/{}",
+            HYBRID_VSOCK_SCHEME, self.jailer_root, DEFAULT_HYBRID_VSOCK_NAME
+        ))
+    }
+
+    pub(crate) async fn disconnect(&mut self) {
+        self.state = VmmState::NotReady;
+    }
+
+    pub(crate) async fn get_thread_ids(&self) -> Result<VcpuThreadIds> {
+        let mut vcpu_thread_ids = VcpuThreadIds::default();
+        let pid = match self.pid {
+            Some(pid) => pid,
+            None => return Ok(vcpu_thread_ids),
+        };
+
+        for tid in get_child_threads(pid) {
+            let comm = std::fs::read_to_string(format!("/proc/{}/task/{}/comm", pid, tid))
+                .unwrap_or_default();
+            if let Some(vcpu) = parse_vcpu_thread_name(&comm) {
+                vcpu_thread_ids.vcpus.insert(vcpu, tid);
+            }
+        }
+        info!(sl!(), "get thread ids {:?}", vcpu_thread_ids);
+        Ok(vcpu_thread_ids)
+    }
+
+    pub(crate) async fn cleanup(&self) -> Result<()> {
+        if self.jailed {
+            self.umount_jail_resource(FC_KERNEL).ok();
+            self.umount_jail_resource(FC_INITRD).ok();
+            for id in &self.cached_block_devices {
+                self.umount_jail_resource(&disk_name(id)).ok();
+            }
+            nix::mount::umount2(self.jailer_root.as_str(), nix::mount::MntFlags::MNT_DETACH).ok();
+        }
+
+        std::fs::remove_dir_all(&self.vm_path)
+            .map_err(|err| {
+                error!(sl!(), "failed to remove dir all for {}", &self.vm_path);
+                err
+            })
+            .ok();
+        Ok(())
+    }
+
+    // the VM is booted with all its vCPUs and memory, none can be hot plugged
+    pub(crate) async fn resize_vcpu(&self, old_vcpu: u32, new_vcpu: u32) -> Result<(u32, u32)> {
+        if old_vcpu != new_vcpu {
+            warn!(
+                sl!(),
+                "resize vcpu: firecracker can't hot plug vcpus, keep {} vcpus instead of {}",
+                old_vcpu,
+                new_vcpu
+            );
+        }
+        Ok((old_vcpu, old_vcpu))
+    }
+
+    pub(crate) async fn resize_memory(&self, new_mem_mb: u32) -> Result<u32> {
+        let default_memory = self.config.memory_info.default_memory;
+        if new_mem_mb != default_memory {
+            warn!(
+                sl!(),
+                "resize memory: firecracker can't hot plug memory, keep {} MiB instead of {} MiB",
+                default_memory,
+                new_mem_mb
+            );
+        }
+        Ok(default_memory)
+    }
+
+    pub(crate) async fn get_pids(&self) -> Result<Vec<u32>> {
+        Ok(self.pid.into_iter().collect())
+    }
+
+    pub(crate) async fn get_vmm_master_tid(&self) -> Result<u32> {
+        self.pid
+            .ok_or_else(|| anyhow!("could not get vmm master tid"))
+    }
+
+    pub(crate) async fn get_ns_path(&self) -> Result<String> {
+        let pid = self.pid.ok_or_else(|| anyhow!("could not get ns path"))?;
+        Ok(format!("/proc/{}/ns", pid))
+    }
+
+    pub(crate) async fn check(&self) -> Result<()> {
+        if self.state != VmmState::VmRunning {
+            return Ok(());
+        }
+        self.api_client().ping().await.context("ping vmm")
+    }
+
+    pub(crate) async fn get_jailer_root(&self) -> Result<String> {
+        Ok(self.jailer_root.clone())
+    }
+
+    pub(crate) async fn capabilities(&self) -> Result<Capabilities> {
+        Ok(self.capabilities.clone())
+    }
+
+    pub(crate) async fn get_hypervisor_metrics(&self) -> Result<String> {
+        Err(anyhow!(
+            "hypervisor metrics aren't supported by firecracker yet"
+        ))
+    }
+
+    pub(crate) async fn dump_guest_memory(&self, _path: &str) -> Result<()> {
+        Err(anyhow!("guest memory dump isn't supported by firecracker"))
+    }
+
+    pub(crate) async fn get_command_line(&self) -> Result<Vec<String>> {
+        let pid = self
+            .pid
+            .ok_or_else(|| anyhow!("{} isn't running", FC_NAME))?;
+        get_command_line(pid)
+    }
+}
+
+// The root of the jail of the jailer started with `--chroot-base-dir` chroot_base,
+// i.e. <chroot_base>/<exec file name>/<id>/root.
+fn jail_root(chroot_base: &str, exec_file: &str) -> String {
+    let exec_name = Path::new(exec_file)
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    [chroot_base, &exec_name, FC_JAIL_ID, JAILER_ROOT].join("/")
+}
+
+// The vCPU threads of Firecracker are named "fc_vcpu <index>".
+fn parse_vcpu_thread_name(comm: &str) -> Option<u32> {
+    comm.trim().strip_prefix("fc_vcpu ")?.parse().ok()
+}
+
+// the file a block device is bind mounted at into the jail, for the drive `id`
+pub(crate) fn disk_name(id: &str) -> String {
+    format!("{}.disk", id)
+}
+
+// Log the output of the VMM until it exits.
+fn log_output<R>(reader: R, stream: &'static str) -> tokio::task::JoinHandle<()>
+where
+    R: AsyncRead + Unpin + Send + 'static,
+{
+    tokio::spawn(async move {
+        let mut lines = BufReader::new(reader).lines();
+        while let Ok(Some(line)) = lines.next_line().await {
+            info!(sl!(), "{}", line; "stream" => stream, "vmm" => FC_NAME);
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_jail_root() {
+        assert_eq!(
+            jail_root("/run/kata/sandbox", "/usr/bin/firecracker"),
+            "/run/kata/sandbox/firecracker/vm/root"
+        );
+    }
+
+    #[test]
+    fn test_parse_vcpu_thread_name() {
+        assert_eq!(parse_vcpu_thread_name("fc_vcpu 0\n"), Some(0));
+        assert_eq!(parse_vcpu_thread_name("fc_vcpu 12"), Some(12));
+        assert_eq!(parse_vcpu_thread_name("fc_api\n"), None);
+        assert_eq!(parse_vcpu_thread_name("firecracker\n"), None);
+    }
+
+    #[actix_rt::test]
+    async fn test_vmm_command() {
+        let mut fc = FirecrackerInner::new();
+        fc.config.path = "/usr/bin/firecracker".to_string();
+        fc.prepare_vm("sandbox", Some("/var/run/netns/cni-1".to_string()))
+            .await
+            .unwrap();
+        let (program, args) = fc.vmm_command();
+        assert_eq!(program, "/usr/bin/firecracker");
+        assert_eq!(args, vec!["--api-sock", "/run/kata/sandbox/root/api.sock"]);
+
+        let mut fc = FirecrackerInner::new();
+        fc.config.path = "/usr/bin/firecracker".to_string();
+        fc.config.jailer_path = "/usr/bin/jailer".to_string();
+        fc.config.security_info.disable_seccomp = true;
+        fc.prepare_vm("sandbox", Some("/var/run/netns/cni-1".to_string()))
+            .await
+            .unwrap();
+        let (program, args) = fc.vmm_command();
+        assert_eq!(program, "/usr/bin/jailer");
+        assert_eq!(
+            args.join(" "),
+            "--id vm --exec-file /usr/bin/firecracker --uid 0 --gid 0 \
+             --chroot-base-dir /run/kata/sandbox --netns /var/run/netns/cni-1 \
+             -- --api-sock /api.sock --no-seccomp"
+        );
+        assert_eq!(
+            fc.get_agent_socket().await.unwrap(),
+            "hvsock:///run/kata/sandbox/firecracker/vm/root/kata.hvsock"
+        );
+    }
+}
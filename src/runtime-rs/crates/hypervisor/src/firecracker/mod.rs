@@ -0,0 +1,235 @@
+// Copyright (c) 2019-2022 Alibaba Cloud
+// Copyright (c) 2023 Kata Containers community
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+mod fc_api;
+mod inner;
+mod inner_device;
+mod inner_hypervisor;
+
+use super::HypervisorState;
+use crate::device::DeviceType;
+use crate::{metrics, Hypervisor, VcpuThreadIds, HYPERVISOR_NAME_FIRECRACKER};
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use inner::FirecrackerInner;
+use kata_types::capabilities::Capabilities;
+use kata_types::config::hypervisor::Hypervisor as HypervisorConfig;
+use persist::sandbox_persist::Persist;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+
+/// The Firecracker VMM, driven through its API socket, optionally confined by its jailer.
+#[derive(Debug, Default, Clone)]
+pub struct Firecracker {
+    inner: Arc<RwLock<FirecrackerInner>>,
+}
+
+impl Firecracker {
+    pub fn new() -> Self {
+        Self {
+            inner: Arc::new(RwLock::new(FirecrackerInner::new())),
+        }
+    }
+
+    pub async fn set_hypervisor_config(&mut self, config: HypervisorConfig) {
+        let mut inner = self.inner.write().await;
+        inner.set_hypervisor_config(config)
+    }
+}
+
+#[async_trait]
+impl Hypervisor for Firecracker {
+    async fn prepare_vm(&self, id: &str, netns: Option<String>) -> Result<()> {
+        let mut inner = self.inner.write().await;
+        inner.prepare_vm(id, netns).await
+    }
+
+    async fn start_vm(&self, timeout: i32) -> Result<()> {
+        let mut inner = self.inner.write().await;
+        metrics::observe(
+            HYPERVISOR_NAME_FIRECRACKER,
+            metrics::OP_START_VM,
+            "",
+            inner.start_vm(timeout),
+        )
+        .await
+    }
+
+    async fn stop_vm(&self) -> Result<()> {
+        let mut inner = self.inner.write().await;
+        metrics::observe(
+            HYPERVISOR_NAME_FIRECRACKER,
+            metrics::OP_STOP_VM,
+            "",
+            inner.stop_vm(),
+        )
+        .await
+    }
+
+    async fn reboot_vm(&self) -> Result<()> {
+        let mut inner = self.inner.write().await;
+        inner.reboot_vm().await
+    }
+
+    async fn pause_vm(&self) -> Result<()> {
+        let inner = self.inner.read().await;
+        inner.pause_vm().await
+    }
+
+    async fn resume_vm(&self) -> Result<()> {
+        let inner = self.inner.read().await;
+        inner.resume_vm().await
+    }
+
+    async fn save_vm(&self) -> Result<()> {
+        let inner = self.inner.read().await;
+        inner.save_vm().await
+    }
+
+    async fn add_device(&self, device: DeviceType) -> Result<()> {
+        let mut inner = self.inner.write().await;
+        let kind = metrics::device_kind(&device);
+        metrics::observe(
+            HYPERVISOR_NAME_FIRECRACKER,
+            metrics::OP_ADD_DEVICE,
+            kind,
+            inner.add_device(device),
+        )
+        .await
+    }
+
+    async fn remove_device(&self, device: DeviceType) -> Result<()> {
+        let mut inner = self.inner.write().await;
+        let kind = metrics::device_kind(&device);
+        metrics::observe(
+            HYPERVISOR_NAME_FIRECRACKER,
+            metrics::OP_REMOVE_DEVICE,
+            kind,
+            inner.remove_device(device),
+        )
+        .await
+    }
+
+    async fn get_agent_socket(&self) -> Result<String> {
+        let inner = self.inner.read().await;
+        inner.get_agent_socket().await
+    }
+
+    async fn disconnect(&self) {
+        let mut inner = self.inner.write().await;
+        inner.disconnect().await
+    }
+
+    async fn hypervisor_config(&self) -> HypervisorConfig {
+        let inner = self.inner.read().await;
+        inner.hypervisor_config()
+    }
+
+    async fn get_thread_ids(&self) -> Result<VcpuThreadIds> {
+        let inner = self.inner.read().await;
+        inner.get_thread_ids().await
+    }
+
+    async fn cleanup(&self) -> Result<()> {
+        let inner = self.inner.read().await;
+        inner.cleanup().await
+    }
+
+    async fn resize_vcpu(&self, old_vcpu: u32, new_vcpu: u32) -> Result<(u32, u32)> {
+        let inner = self.inner.read().await;
+        metrics::observe(
+            HYPERVISOR_NAME_FIRECRACKER,
+            metrics::OP_RESIZE_VCPU,
+            "",
+            inner.resize_vcpu(old_vcpu, new_vcpu),
+        )
+        .await
+    }
+
+    async fn resize_memory(&self, new_mem_mb: u32) -> Result<u32> {
+        let inner = self.inner.read().await;
+        metrics::observe(
+            HYPERVISOR_NAME_FIRECRACKER,
+            metrics::OP_RESIZE_MEMORY,
+            "",
+            inner.resize_memory(new_mem_mb),
+        )
+        .await
+    }
+
+    async fn get_pids(&self) -> Result<Vec<u32>> {
+        let inner = self.inner.read().await;
+        inner.get_pids().await
+    }
+
+    async fn get_vmm_master_tid(&self) -> Result<u32> {
+        let inner = self.inner.read().await;
+        inner.get_vmm_master_tid().await
+    }
+
+    async fn get_ns_path(&self) -> Result<String> {
+        let inner = self.inner.read().await;
+        inner.get_ns_path().await
+    }
+
+    async fn check(&self) -> Result<()> {
+        let inner = self.inner.read().await;
+        inner.check().await
+    }
+
+    async fn get_jailer_root(&self) -> Result<String> {
+        let inner = self.inner.read().await;
+        inner.get_jailer_root().await
+    }
+
+    async fn save_state(&self) -> Result<HypervisorState> {
+        self.save().await
+    }
+
+    async fn capabilities(&self) -> Result<Capabilities> {
+        let inner = self.inner.read().await;
+        inner.capabilities().await
+    }
+
+    async fn get_hypervisor_metrics(&self) -> Result<String> {
+        let inner = self.inner.read().await;
+        inner.get_hypervisor_metrics().await
+    }
+
+    async fn dump_guest_memory(&self, path: &str) -> Result<()> {
+        let inner = self.inner.read().await;
+        inner.dump_guest_memory(path).await
+    }
+
+    async fn get_command_line(&self) -> Result<Vec<String>> {
+        let inner = self.inner.read().await;
+        inner.get_command_line().await
+    }
+}
+
+#[async_trait]
+impl Persist for Firecracker {
+    type State = HypervisorState;
+    type ConstructorArgs = ();
+
+    async fn save(&self) -> Result<Self::State> {
+        let inner = self.inner.read().await;
+        inner
+            .save()
+            .await
+            .context("save firecracker hypervisor state")
+    }
+
+    async fn restore(
+        hypervisor_args: Self::ConstructorArgs,
+        hypervisor_state: Self::State,
+    ) -> Result<Self> {
+        let inner = FirecrackerInner::restore(hypervisor_args, hypervisor_state).await?;
+        Ok(Self {
+            inner: Arc::new(RwLock::new(inner)),
+        })
+    }
+}
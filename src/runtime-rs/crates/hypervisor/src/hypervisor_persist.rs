@@ -33,4 +33,6 @@ pub struct HypervisorState {
     /// cached block device
     pub cached_block_devices: HashSet<String>,
     pub virtiofs_daemon_pid: i32,
+    /// guest CID of the vhost-vsock of the VM
+    pub guest_cid: Option<u32>,
 }
@@ -10,7 +10,7 @@ use crate::{
     VM_ROOTFS_DRIVER_BLK, VM_ROOTFS_DRIVER_MMIO, VM_ROOTFS_DRIVER_PMEM, VM_ROOTFS_FILESYSTEM_EROFS,
     VM_ROOTFS_FILESYSTEM_EXT4, VM_ROOTFS_FILESYSTEM_XFS, VM_ROOTFS_ROOT_BLK, VM_ROOTFS_ROOT_PMEM,
 };
-use kata_types::config::LOG_VPORT_OPTION;
+use kata_types::config::{hypervisor::SecurityInfo, LOG_VPORT_OPTION};
 
 // Port where the agent will send the logs. Logs are sent through the vsock in cases
 // where the hypervisor has no console.sock, i.e dragonball
@@ -113,6 +113,34 @@ impl KernelParams {
         Ok(Self { params })
     }
 
+    // Kernel params reducing the latency jitter of a PREEMPT_RT guest whose
+    // vCPUs are running with real-time priority on dedicated host CPUs.
+    pub(crate) fn new_realtime_kernel_params() -> Self {
+        let params = vec![
+            Param::new("skew_tick", "1"),
+            Param::new("nowatchdog", ""),
+            Param::new("nosoftlockup", ""),
+        ];
+
+        Self { params }
+    }
+
+    // Kernel params of the kernel modules blacklist and of the sysctls of the guest, appended
+    // after the user-specified ones so that they take priority.
+    pub(crate) fn new_hardening_kernel_params(security_info: &SecurityInfo) -> Self {
+        let mut params = vec![];
+
+        let modules = security_info.guest_kernel_modules_blacklist();
+        if !modules.is_empty() {
+            params.push(Param::new("module_blacklist", &modules.join(",")));
+        }
+        for (key, value) in security_info.guest_sysctls() {
+            params.push(Param::new(&format!("sysctl.{}", key), value));
+        }
+
+        Self { params }
+    }
+
     pub(crate) fn append(&mut self, params: &mut KernelParams) {
         self.params.append(&mut params.params);
     }
@@ -204,6 +232,40 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_realtime_kernel_params() -> Result<()> {
+        let kernel_params = KernelParams::new_realtime_kernel_params();
+        assert_eq!(
+            kernel_params.to_string()?,
+            "skew_tick=1 nowatchdog nosoftlockup".to_string()
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_hardening_kernel_params() -> Result<()> {
+        let mut security_info = SecurityInfo::default();
+        let kernel_params = KernelParams::new_hardening_kernel_params(&security_info);
+        assert_eq!(kernel_params.to_string()?, "".to_string());
+
+        security_info.kernel_modules_blacklist = vec!["nf_tables".to_string(), "sctp".to_string()];
+        let kernel_params = KernelParams::new_hardening_kernel_params(&security_info);
+        assert_eq!(
+            kernel_params.to_string()?,
+            "module_blacklist=nf_tables,sctp".to_string()
+        );
+
+        security_info.hardened_guest = true;
+        let kernel_params =
+            KernelParams::new_hardening_kernel_params(&security_info).to_string()?;
+        assert!(kernel_params.starts_with("module_blacklist=dccp,sctp,"));
+        assert!(kernel_params.contains(",nf_tables sysctl.kernel.kptr_restrict=2 "));
+        assert!(kernel_params.contains(" sysctl.kernel.unprivileged_bpf_disabled=1 "));
+
+        Ok(())
+    }
+
     #[derive(Debug)]
     struct TestData<'a> {
         rootfs_driver: &'a str,
@@ -15,6 +15,8 @@ pub use device::driver::*;
 use device::DeviceType;
 pub mod dragonball;
 mod kernel_param;
+pub mod metrics;
+#[cfg(feature = "qemu")]
 pub mod qemu;
 pub use kernel_param::Param;
 pub mod utils;
@@ -23,13 +25,21 @@ use std::collections::HashMap;
 #[cfg(feature = "cloud-hypervisor")]
 pub mod ch;
 
+#[cfg(feature = "firecracker")]
+pub mod firecracker;
+
+#[cfg(feature = "stratovirt")]
+pub mod stratovirt;
+
 use anyhow::Result;
 use async_trait::async_trait;
 use hypervisor_persist::HypervisorState;
 use kata_types::capabilities::Capabilities;
 use kata_types::config::hypervisor::Hypervisor as HypervisorConfig;
 
-pub use kata_types::config::hypervisor::HYPERVISOR_NAME_CH;
+pub use kata_types::config::hypervisor::{
+    HYPERVISOR_NAME_CH, HYPERVISOR_NAME_FIRECRACKER, HYPERVISOR_NAME_STRATOVIRT,
+};
 
 // Config which driver to use as vm root dev
 const VM_ROOTFS_DRIVER_BLK: &str = "virtio-blk-pci";
@@ -79,10 +89,12 @@ pub trait Hypervisor: std::fmt::Debug + Send + Sync {
     async fn prepare_vm(&self, id: &str, netns: Option<String>) -> Result<()>;
     async fn start_vm(&self, timeout: i32) -> Result<()>;
     async fn stop_vm(&self) -> Result<()>;
+    async fn reboot_vm(&self) -> Result<()>;
     async fn pause_vm(&self) -> Result<()>;
     async fn save_vm(&self) -> Result<()>;
     async fn resume_vm(&self) -> Result<()>;
     async fn resize_vcpu(&self, old_vcpus: u32, new_vcpus: u32) -> Result<(u32, u32)>; // returns (old_vcpus, new_vcpus)
+    async fn resize_memory(&self, new_mem_mb: u32) -> Result<u32>; // returns the memory size in MiB after resizing
 
     // device manager
     async fn add_device(&self, device: DeviceType) -> Result<()>;
@@ -102,4 +114,8 @@ pub trait Hypervisor: std::fmt::Debug + Send + Sync {
     async fn save_state(&self) -> Result<HypervisorState>;
     async fn capabilities(&self) -> Result<Capabilities>;
     async fn get_hypervisor_metrics(&self) -> Result<String>;
+    // dump the guest memory to a new file at `path`, for the forensic analysis
+    async fn dump_guest_memory(&self, path: &str) -> Result<()>;
+    // the command line of the VMM process, for the debug bundle
+    async fn get_command_line(&self) -> Result<Vec<String>>;
 }
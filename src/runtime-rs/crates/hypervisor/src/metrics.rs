@@ -0,0 +1,103 @@
+// Copyright (c) 2023 Kata Containers community
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+use std::future::Future;
+use std::time::Instant;
+
+use anyhow::Result;
+use lazy_static::lazy_static;
+use prometheus::{HistogramOpts, HistogramVec};
+
+use crate::device::DeviceType;
+
+const NAMESPACE_KATA_HYPERVISOR: &str = "kata_hypervisor";
+
+pub(crate) const OP_START_VM: &str = "start_vm";
+pub(crate) const OP_STOP_VM: &str = "stop_vm";
+pub(crate) const OP_ADD_DEVICE: &str = "add_device";
+pub(crate) const OP_REMOVE_DEVICE: &str = "remove_device";
+pub(crate) const OP_RESIZE_VCPU: &str = "resize_vcpu";
+pub(crate) const OP_RESIZE_MEMORY: &str = "resize_memory";
+
+lazy_static! {
+    /// Count and latency of the hypervisor operations, so that the hypervisors could be
+    /// compared on the same workloads. It's registered by the shim metrics.
+    pub static ref HYPERVISOR_OPERATIONS: HistogramVec = HistogramVec::new(
+        HistogramOpts::new(
+            format!("{}_{}", NAMESPACE_KATA_HYPERVISOR, "operation_duration_seconds"),
+            "Kata hypervisor operation latencies in seconds.",
+        )
+        .buckets(vec![
+            0.001, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+        ]),
+        &["hypervisor", "operation", "device", "result"]
+    )
+    .unwrap();
+}
+
+pub(crate) fn device_kind(device: &DeviceType) -> &'static str {
+    match device {
+        DeviceType::Block(_) => "block",
+        DeviceType::VhostUserBlk(_) => "vhost_user_blk",
+        DeviceType::Vfio(_) => "vfio",
+        DeviceType::Network(_) => "network",
+        DeviceType::ShareFs(_) => "share_fs",
+        DeviceType::ShareFsMount(_) => "share_fs_mount",
+        DeviceType::HybridVsock(_) => "hybrid_vsock",
+        DeviceType::Vsock(_) => "vsock",
+    }
+}
+
+/// Run the `operation` of the `hypervisor`, and observe its latency and result. `device` is
+/// the kind of the device operated on, empty if the operation isn't on a device.
+pub(crate) async fn observe<T, F>(
+    hypervisor: &str,
+    operation: &str,
+    device: &str,
+    f: F,
+) -> Result<T>
+where
+    F: Future<Output = Result<T>>,
+{
+    let start = Instant::now();
+    let result = f.await;
+    HYPERVISOR_OPERATIONS
+        .with_label_values(&[
+            hypervisor,
+            operation,
+            device,
+            if result.is_ok() { "ok" } else { "error" },
+        ])
+        .observe(start.elapsed().as_secs_f64());
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::anyhow;
+
+    #[actix_rt::test]
+    async fn test_observe() {
+        let ok = HYPERVISOR_OPERATIONS.with_label_values(&["test", OP_START_VM, "", "ok"]);
+        let err = HYPERVISOR_OPERATIONS.with_label_values(&["test", OP_START_VM, "", "error"]);
+        let (ok_count, err_count) = (ok.get_sample_count(), err.get_sample_count());
+
+        assert_eq!(
+            observe("test", OP_START_VM, "", async { Ok(1) })
+                .await
+                .unwrap(),
+            1
+        );
+        assert!(
+            observe::<(), _>("test", OP_START_VM, "", async { Err(anyhow!("failed")) })
+                .await
+                .is_err()
+        );
+
+        assert_eq!(ok.get_sample_count(), ok_count + 1);
+        assert_eq!(err.get_sample_count(), err_count + 1);
+    }
+}
@@ -3,28 +3,51 @@
 // SPDX-License-Identifier: Apache-2.0
 //
 
-use anyhow::Result;
+use anyhow::{anyhow, Context, Result};
 
-use crate::{HypervisorConfig, VcpuThreadIds};
+use crate::{
+    check_vhost_vsock, reserve_guest_cid, BlockConfig, GuestCid, HypervisorConfig, VcpuThreadIds,
+    KATA_BLK_DEV_TYPE, KATA_NVME_DEV_TYPE, KATA_SCSI_DEV_TYPE,
+};
 use kata_types::capabilities::{Capabilities, CapabilityBits};
+use kata_types::config::default::DEFAULT_AGENT_VSOCK_PORT;
+use kata_types::config::hypervisor::{
+    BlockDeviceInfo, CpuInfo, DeviceInfo, BLOCK_DEVICE_AIO_NATIVE, BLOCK_DEVICE_AIO_THREADS,
+    CPU_MODEL_HOST,
+};
 
 const VSOCK_SCHEME: &str = "vsock";
-const VSOCK_AGENT_CID: u32 = 3;
-const VSOCK_AGENT_PORT: u32 = 1024;
+const SCSI_CONTROLLER_ID: &str = "scsi0";
 #[derive(Debug)]
 pub struct QemuInner {
     config: HypervisorConfig,
+    /// block devices to be cold plugged when starting the VM
+    block_devices: Vec<BlockConfig>,
+    /// guest CID of the vsock of the agent, reserved when preparing the VM
+    guest_cid: Option<GuestCid>,
+    /// the command line QEMU is started with
+    command_line: Vec<String>,
 }
 
 impl QemuInner {
     pub fn new() -> QemuInner {
         QemuInner {
             config: Default::default(),
+            block_devices: vec![],
+            guest_cid: None,
+            command_line: vec![],
         }
     }
 
     pub(crate) async fn prepare_vm(&mut self, _id: &str, _netns: Option<String>) -> Result<()> {
         info!(sl!(), "Preparing QEMU VM");
+
+        // fail before the VM boots if its vsock can't be created
+        check_vhost_vsock().context("check vhost-vsock")?;
+        let guest_cid = reserve_guest_cid().context("reserve vsock context ID")?;
+        info!(sl!(), "reserved vsock context ID {}", guest_cid.cid);
+        self.guest_cid = Some(guest_cid);
+
         Ok(())
     }
 
@@ -43,9 +66,21 @@ impl QemuInner {
             .arg("-vga")
             .arg("none")
             .arg("-nodefaults")
-            .arg("-nographic");
+            .arg("-nographic")
+            .args(cpu_args(&self.config.cpu_info))
+            .args(iommu_args(&self.config.device_info))
+            .args(vsock_args(self.guest_cid()?))
+            .args(block_device_args(
+                &self.config.blockdev_info,
+                &self.block_devices,
+                self.config.device_info.enable_iommu_platform,
+            )?);
 
         command.spawn()?;
+        self.command_line = std::iter::once(command.get_program())
+            .chain(command.get_args())
+            .map(|arg| arg.to_string_lossy().into_owned())
+            .collect();
 
         Ok(())
     }
@@ -55,6 +90,11 @@ impl QemuInner {
         todo!()
     }
 
+    pub(crate) async fn reboot_vm(&mut self) -> Result<()> {
+        info!(sl!(), "Rebooting QEMU VM");
+        Err(anyhow!("reboot vm is not supported by QEMU yet"))
+    }
+
     pub(crate) fn pause_vm(&self) -> Result<()> {
         info!(sl!(), "Pausing QEMU VM");
         todo!()
@@ -69,16 +109,23 @@ impl QemuInner {
         todo!()
     }
 
-    /// TODO: using a single hardcoded CID is clearly not adequate in the long
-    /// run. Use the recently added VsockConfig infrastructure to fix this.
     pub(crate) async fn get_agent_socket(&self) -> Result<String> {
         info!(sl!(), "QemuInner::get_agent_socket()");
         Ok(format!(
             "{}://{}:{}",
-            VSOCK_SCHEME, VSOCK_AGENT_CID, VSOCK_AGENT_PORT
+            VSOCK_SCHEME,
+            self.guest_cid()?,
+            DEFAULT_AGENT_VSOCK_PORT
         ))
     }
 
+    fn guest_cid(&self) -> Result<u32> {
+        self.guest_cid
+            .as_ref()
+            .map(|c| c.cid)
+            .ok_or_else(|| anyhow!("no vsock context ID reserved, the VM isn't prepared"))
+    }
+
     pub(crate) async fn disconnect(&mut self) {
         info!(sl!(), "QemuInner::disconnect()");
         todo!()
@@ -104,9 +151,33 @@ impl QemuInner {
         todo!()
     }
 
-    pub(crate) async fn resize_vcpu(&self, _old_vcpus: u32, _new_vcpus: u32) -> Result<(u32, u32)> {
+    // the vcpus are hot plugged with device_add through QMP, which isn't
+    // supported yet, the VM keeps its vcpus
+    pub(crate) async fn resize_vcpu(&self, old_vcpus: u32, new_vcpus: u32) -> Result<(u32, u32)> {
         info!(sl!(), "QemuInner::resize_vcpu()");
-        todo!()
+        if old_vcpus != new_vcpus {
+            warn!(
+                sl!(),
+                "vcpu hotplug isn't supported, keep {} vcpus instead of {}", old_vcpus, new_vcpus
+            );
+        }
+        Ok((old_vcpus, old_vcpus))
+    }
+
+    // the memory is hot plugged with object_add and device_add through QMP,
+    // which isn't supported yet, the VM keeps its default memory
+    pub(crate) async fn resize_memory(&self, new_mem_mb: u32) -> Result<u32> {
+        info!(sl!(), "QemuInner::resize_memory()");
+        let default_memory = self.config.memory_info.default_memory;
+        if new_mem_mb != default_memory {
+            warn!(
+                sl!(),
+                "memory hotplug isn't supported, keep {} MiB instead of {} MiB",
+                default_memory,
+                new_mem_mb
+            );
+        }
+        Ok(default_memory)
     }
 
     pub(crate) async fn get_pids(&self) -> Result<Vec<u32>> {
@@ -124,7 +195,12 @@ impl QemuInner {
 
     pub(crate) async fn capabilities(&self) -> Result<Capabilities> {
         let mut caps = Capabilities::default();
-        caps.set(CapabilityBits::FsSharingSupport);
+        caps.set(
+            CapabilityBits::BlockDeviceSupport
+                | CapabilityBits::BlockDeviceScsiSupport
+                | CapabilityBits::BlockDeviceNvmeSupport
+                | CapabilityBits::FsSharingSupport,
+        );
         Ok(caps)
     }
 
@@ -140,6 +216,138 @@ impl QemuInner {
     pub(crate) async fn get_hypervisor_metrics(&self) -> Result<String> {
         todo!()
     }
+
+    pub(crate) async fn dump_guest_memory(&self, _path: &str) -> Result<()> {
+        // needs dump-guest-memory through QMP, which isn't supported yet
+        Err(anyhow!("guest memory dump isn't supported by qemu yet"))
+    }
+
+    pub(crate) async fn get_command_line(&self) -> Result<Vec<String>> {
+        if self.command_line.is_empty() {
+            return Err(anyhow!("QEMU isn't started"));
+        }
+        Ok(self.command_line.clone())
+    }
+}
+
+// cpu_args generates the QEMU arguments of the guest CPU, the host one or a named model, with
+// the flags added or removed by cpu_features, the default CPU of QEMU if none is configured.
+fn cpu_args(info: &CpuInfo) -> Vec<String> {
+    if info.cpu_model.is_empty() && info.cpu_features.is_empty() {
+        return vec![];
+    }
+
+    let mut cpu = if info.is_host_cpu_model() {
+        CPU_MODEL_HOST.to_string()
+    } else {
+        info.cpu_model.clone()
+    };
+    if !info.cpu_features.is_empty() {
+        cpu.push(',');
+        cpu.push_str(&info.cpu_features);
+    }
+    vec!["-cpu".to_string(), cpu]
+}
+
+// iommu_args generates the QEMU arguments of the vIOMMU, the assigned devices are then isolated
+// from each other in the guest too. The interrupt remapping requires the split irqchip.
+fn iommu_args(info: &DeviceInfo) -> Vec<String> {
+    if !info.enable_iommu {
+        return vec![];
+    }
+
+    vec![
+        "-machine".to_string(),
+        "kernel_irqchip=split".to_string(),
+        "-device".to_string(),
+        "intel-iommu,intremap=on,device-iotlb=on,caching-mode=on".to_string(),
+    ]
+}
+
+// vsock_args generates the QEMU arguments of the vsock of the agent with the reserved guest CID.
+fn vsock_args(guest_cid: u32) -> Vec<String> {
+    vec![
+        "-device".to_string(),
+        format!(
+            "vhost-vsock-pci,id=vsock-{},guest-cid={}",
+            guest_cid, guest_cid
+        ),
+    ]
+}
+
+// block_device_args generates the QEMU arguments to cold plug the block devices
+// with the driver selected by block_device_driver and the aio mode selected by
+// block_device_aio. The virtio devices go through the IOMMU if iommu_platform is set.
+fn block_device_args(
+    info: &BlockDeviceInfo,
+    devices: &[BlockConfig],
+    iommu_platform: bool,
+) -> Result<Vec<String>> {
+    let virtio_opts = if iommu_platform {
+        ",iommu_platform=on,disable-legacy=on"
+    } else {
+        ""
+    };
+    let mut args = vec![];
+    let mut has_scsi_controller = false;
+
+    for d in devices {
+        let drive_id = format!("drive-{}", d.index);
+        let mut drive = format!("id={},file={},format=raw,if=none", drive_id, d.path_on_host);
+        let direct = d.is_direct.unwrap_or(info.block_device_cache_direct);
+        // native aio requires O_DIRECT, fall back to threads for the drives
+        // using the host page cache.
+        let aio = if info.block_device_aio == BLOCK_DEVICE_AIO_NATIVE && !direct {
+            BLOCK_DEVICE_AIO_THREADS
+        } else {
+            info.block_device_aio.as_str()
+        };
+        if !aio.is_empty() {
+            drive.push_str(&format!(",aio={}", aio));
+        }
+        if direct {
+            drive.push_str(",cache.direct=on");
+        }
+        if d.is_readonly {
+            drive.push_str(",readonly=on");
+        }
+        args.push("-drive".to_string());
+        args.push(drive);
+
+        let device = match d.driver_option.as_str() {
+            KATA_BLK_DEV_TYPE => format!("virtio-blk-pci,drive={}{}", drive_id, virtio_opts),
+            KATA_SCSI_DEV_TYPE => {
+                if !has_scsi_controller {
+                    args.push("-device".to_string());
+                    args.push(format!(
+                        "virtio-scsi-pci,id={}{}",
+                        SCSI_CONTROLLER_ID, virtio_opts
+                    ));
+                    has_scsi_controller = true;
+                }
+                // virt_path of scsi device is its "<scsi-id>:<lun>" address
+                let (scsi_id, lun) = d
+                    .virt_path
+                    .split_once(':')
+                    .ok_or_else(|| anyhow!("invalid scsi address {}", d.virt_path))?;
+                format!(
+                    "scsi-hd,drive={},bus={}.0,scsi-id={},lun={}",
+                    drive_id, SCSI_CONTROLLER_ID, scsi_id, lun
+                )
+            }
+            KATA_NVME_DEV_TYPE => format!("nvme,drive={},serial=nvme-{}", drive_id, d.index),
+            _ => {
+                return Err(anyhow!(
+                    "QEMU doesn't support block driver {}",
+                    d.driver_option
+                ))
+            }
+        };
+        args.push("-device".to_string());
+        args.push(device);
+    }
+
+    Ok(args)
 }
 
 use crate::device::DeviceType;
@@ -148,7 +356,13 @@ use crate::device::DeviceType;
 impl QemuInner {
     pub(crate) async fn add_device(&mut self, device: DeviceType) -> Result<()> {
         info!(sl!(), "QemuInner::add_device() {}", device);
-        todo!()
+        match device {
+            DeviceType::Block(block) => {
+                self.block_devices.push(block.config);
+                Ok(())
+            }
+            _ => todo!(),
+        }
     }
 
     pub(crate) async fn remove_device(&mut self, device: DeviceType) -> Result<()> {
@@ -156,3 +370,64 @@ impl QemuInner {
         todo!()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cpu_args() {
+        let mut info = CpuInfo::default();
+        assert!(cpu_args(&info).is_empty());
+
+        info.cpu_features = "pmu=off,+avx512f".to_string();
+        assert_eq!(cpu_args(&info), vec!["-cpu", "host,pmu=off,+avx512f"]);
+
+        info.cpu_model = "host".to_string();
+        assert_eq!(cpu_args(&info), vec!["-cpu", "host,pmu=off,+avx512f"]);
+
+        info.cpu_model = "Cascadelake-Server-v4".to_string();
+        info.cpu_features = "-hle,-rtm".to_string();
+        assert_eq!(
+            cpu_args(&info),
+            vec!["-cpu", "Cascadelake-Server-v4,-hle,-rtm"]
+        );
+    }
+
+    #[test]
+    fn test_vsock_args() {
+        assert_eq!(
+            vsock_args(5),
+            vec!["-device", "vhost-vsock-pci,id=vsock-5,guest-cid=5"]
+        );
+
+        let inner = QemuInner::new();
+        assert!(inner.guest_cid().is_err());
+    }
+
+    #[test]
+    fn test_iommu_args() {
+        let mut info = DeviceInfo::default();
+        assert!(iommu_args(&info).is_empty());
+
+        info.enable_iommu = true;
+        let args = iommu_args(&info);
+        assert_eq!(args[1], "kernel_irqchip=split");
+        assert!(args[3].starts_with("intel-iommu,intremap=on"));
+
+        let devices = vec![BlockConfig {
+            index: 1,
+            path_on_host: "/dev/sdb".to_string(),
+            driver_option: KATA_BLK_DEV_TYPE.to_string(),
+            is_direct: Some(true),
+            ..Default::default()
+        }];
+        let args = block_device_args(&BlockDeviceInfo::default(), &devices, true).unwrap();
+        assert_eq!(
+            args[3],
+            "virtio-blk-pci,drive=drive-1,iommu_platform=on,disable-legacy=on"
+        );
+        let args = block_device_args(&BlockDeviceInfo::default(), &devices, false).unwrap();
+        assert_eq!(args[3], "virtio-blk-pci,drive=drive-1");
+    }
+}
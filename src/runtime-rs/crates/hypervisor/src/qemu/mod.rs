@@ -7,7 +7,7 @@ mod inner;
 
 use crate::device::DeviceType;
 use crate::hypervisor_persist::HypervisorState;
-use crate::Hypervisor;
+use crate::{metrics, Hypervisor, HYPERVISOR_QEMU};
 use crate::{HypervisorConfig, VcpuThreadIds};
 use inner::QemuInner;
 use kata_types::capabilities::Capabilities;
@@ -51,12 +51,26 @@ impl Hypervisor for Qemu {
 
     async fn start_vm(&self, timeout: i32) -> Result<()> {
         let mut inner = self.inner.write().await;
-        inner.start_vm(timeout).await
+        metrics::observe(
+            HYPERVISOR_QEMU,
+            metrics::OP_START_VM,
+            "",
+            inner.start_vm(timeout),
+        )
+        .await
     }
 
     async fn stop_vm(&self) -> Result<()> {
         let mut inner = self.inner.write().await;
-        inner.stop_vm()
+        metrics::observe(HYPERVISOR_QEMU, metrics::OP_STOP_VM, "", async {
+            inner.stop_vm()
+        })
+        .await
+    }
+
+    async fn reboot_vm(&self) -> Result<()> {
+        let mut inner = self.inner.write().await;
+        inner.reboot_vm().await
     }
 
     async fn pause_vm(&self) -> Result<()> {
@@ -74,14 +88,39 @@ impl Hypervisor for Qemu {
         inner.save_vm().await
     }
 
+    async fn resize_memory(&self, new_mem_mb: u32) -> Result<u32> {
+        let inner = self.inner.read().await;
+        metrics::observe(
+            HYPERVISOR_QEMU,
+            metrics::OP_RESIZE_MEMORY,
+            "",
+            inner.resize_memory(new_mem_mb),
+        )
+        .await
+    }
+
     async fn add_device(&self, device: DeviceType) -> Result<()> {
         let mut inner = self.inner.write().await;
-        inner.add_device(device).await
+        let kind = metrics::device_kind(&device);
+        metrics::observe(
+            HYPERVISOR_QEMU,
+            metrics::OP_ADD_DEVICE,
+            kind,
+            inner.add_device(device),
+        )
+        .await
     }
 
     async fn remove_device(&self, device: DeviceType) -> Result<()> {
         let mut inner = self.inner.write().await;
-        inner.remove_device(device).await
+        let kind = metrics::device_kind(&device);
+        metrics::observe(
+            HYPERVISOR_QEMU,
+            metrics::OP_REMOVE_DEVICE,
+            kind,
+            inner.remove_device(device),
+        )
+        .await
     }
 
     async fn get_agent_socket(&self) -> Result<String> {
@@ -121,7 +160,13 @@ impl Hypervisor for Qemu {
 
     async fn resize_vcpu(&self, old_vcpus: u32, new_vcpus: u32) -> Result<(u32, u32)> {
         let inner = self.inner.read().await;
-        inner.resize_vcpu(old_vcpus, new_vcpus).await
+        metrics::observe(
+            HYPERVISOR_QEMU,
+            metrics::OP_RESIZE_VCPU,
+            "",
+            inner.resize_vcpu(old_vcpus, new_vcpus),
+        )
+        .await
     }
 
     async fn get_pids(&self) -> Result<Vec<u32>> {
@@ -152,4 +197,14 @@ impl Hypervisor for Qemu {
         let inner = self.inner.read().await;
         inner.get_hypervisor_metrics().await
     }
+
+    async fn dump_guest_memory(&self, path: &str) -> Result<()> {
+        let inner = self.inner.read().await;
+        inner.dump_guest_memory(path).await
+    }
+
+    async fn get_command_line(&self) -> Result<Vec<String>> {
+        let inner = self.inner.read().await;
+        inner.get_command_line().await
+    }
 }
@@ -0,0 +1,218 @@
+// Copyright (c) 2019-2022 Alibaba Cloud
+// Copyright (c) 2023 Kata Containers community
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+use super::qmp::Qmp;
+use crate::device::DeviceType;
+use crate::hypervisor_persist::HypervisorState;
+use crate::{reclaim_guest_cid, GuestCid, VmmState, HYPERVISOR_NAME_STRATOVIRT};
+use anyhow::{anyhow, Context, Result};
+use async_trait::async_trait;
+use kata_types::capabilities::{Capabilities, CapabilityBits};
+use kata_types::config::hypervisor::Hypervisor as HypervisorConfig;
+use persist::sandbox_persist::Persist;
+use std::collections::HashSet;
+use std::path::Path;
+use tokio::net::UnixStream;
+use tokio::process::Child;
+use tokio::task::JoinHandle;
+
+pub(crate) const SV_QMP_SOCKET: &str = "qmp.sock";
+
+#[derive(Debug)]
+pub struct StratoVirtInner {
+    /// sandbox id
+    pub(crate) id: String,
+
+    /// vm path
+    pub(crate) vm_path: String,
+
+    /// root of the sockets of the VM and of its virtiofsd
+    pub(crate) jailer_root: String,
+
+    /// netns
+    pub(crate) netns: Option<String>,
+
+    /// hypervisor config
+    pub(crate) config: HypervisorConfig,
+
+    /// vmm state
+    pub(crate) state: VmmState,
+
+    /// guest CID of the vsock of the agent, reserved when preparing the VM
+    pub(crate) guest_cid: Option<GuestCid>,
+
+    /// the VMM process
+    pub(crate) process: Option<Child>,
+    pub(crate) pid: Option<u32>,
+
+    /// the tasks logging the output of the VMM
+    pub(crate) tasks: Vec<JoinHandle<()>>,
+
+    /// devices added before the VM boots, they are on its command line
+    pub(crate) pending_devices: Vec<DeviceType>,
+
+    /// the replaceable block slots, with the id of the drive hot plugged into each
+    pub(crate) block_slots: Vec<Option<String>>,
+
+    pub(crate) capabilities: Capabilities,
+}
+
+impl StratoVirtInner {
+    pub fn new() -> Self {
+        let mut capabilities = Capabilities::new();
+        capabilities
+            .set(CapabilityBits::BlockDeviceSupport | CapabilityBits::BlockDeviceHotplugSupport);
+
+        Self {
+            id: String::default(),
+            vm_path: String::default(),
+            jailer_root: String::default(),
+            netns: None,
+            config: Default::default(),
+            state: VmmState::NotReady,
+            guest_cid: None,
+            process: None,
+            pid: None,
+            tasks: vec![],
+            pending_devices: vec![],
+            block_slots: vec![],
+            capabilities,
+        }
+    }
+
+    pub fn set_hypervisor_config(&mut self, config: HypervisorConfig) {
+        // the shared filesystem is a vhost-user-fs device
+        if config.shared_fs.shared_fs.is_some() {
+            self.capabilities.set(
+                CapabilityBits::BlockDeviceSupport
+                    | CapabilityBits::BlockDeviceHotplugSupport
+                    | CapabilityBits::FsSharingSupport,
+            );
+        }
+        self.config = config;
+    }
+
+    pub fn hypervisor_config(&self) -> HypervisorConfig {
+        self.config.clone()
+    }
+
+    pub(crate) fn qmp_socket(&self) -> String {
+        [self.vm_path.as_str(), SV_QMP_SOCKET].join("/")
+    }
+
+    /// Connect to the QMP socket of the VMM, a connection per operation so
+    /// that a restored sandbox needs no state of it.
+    pub(crate) async fn qmp(&self) -> Result<Qmp<UnixStream>> {
+        Qmp::connect(Path::new(&self.qmp_socket())).await
+    }
+
+    pub(crate) fn guest_cid(&self) -> Result<u32> {
+        self.guest_cid
+            .as_ref()
+            .map(|c| c.cid)
+            .ok_or_else(|| anyhow!("no vsock context ID reserved, the VM isn't prepared"))
+    }
+}
+
+impl Default for StratoVirtInner {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// The drives hot plugged into the replaceable slots are saved as `<slot>:<drive id>`.
+fn slot_entries(slots: &[Option<String>]) -> HashSet<String> {
+    slots
+        .iter()
+        .enumerate()
+        .filter_map(|(slot, id)| id.as_ref().map(|id| format!("{}:{}", slot, id)))
+        .collect()
+}
+
+fn slots_from_entries(entries: &HashSet<String>) -> Vec<Option<String>> {
+    let mut slots = vec![];
+    for entry in entries {
+        if let Some((slot, id)) = entry.split_once(':') {
+            if let Ok(slot) = slot.parse::<usize>() {
+                if slots.len() <= slot {
+                    slots.resize(slot + 1, None);
+                }
+                slots[slot] = Some(id.to_string());
+            }
+        }
+    }
+    slots
+}
+
+#[async_trait]
+impl Persist for StratoVirtInner {
+    type State = HypervisorState;
+    type ConstructorArgs = ();
+
+    /// Save a state of hypervisor
+    async fn save(&self) -> Result<Self::State> {
+        Ok(HypervisorState {
+            hypervisor_type: HYPERVISOR_NAME_STRATOVIRT.to_string(),
+            pid: self.pid.map(|pid| pid as i32),
+            api_socket: self.qmp_socket(),
+            id: self.id.clone(),
+            vm_path: self.vm_path.clone(),
+            jailer_root: self.jailer_root.clone(),
+            netns: self.netns.clone(),
+            config: self.hypervisor_config(),
+            run_dir: self.vm_path.clone(),
+            cached_block_devices: slot_entries(&self.block_slots),
+            guest_cid: self.guest_cid.as_ref().map(|c| c.cid),
+            ..Default::default()
+        })
+    }
+
+    /// Restore hypervisor
+    async fn restore(
+        _hypervisor_args: Self::ConstructorArgs,
+        hypervisor_state: Self::State,
+    ) -> Result<Self> {
+        // the VMM is no child of the restarted shim, it's stopped by its pid,
+        // and the QMP socket is reconnected for each operation
+        let mut inner = Self {
+            id: hypervisor_state.id,
+            vm_path: hypervisor_state.vm_path,
+            jailer_root: hypervisor_state.jailer_root,
+            netns: hypervisor_state.netns,
+            pid: hypervisor_state.pid.map(|pid| pid as u32),
+            block_slots: slots_from_entries(&hypervisor_state.cached_block_devices),
+            ..Default::default()
+        };
+        inner.set_hypervisor_config(hypervisor_state.config);
+        if let Some(cid) = hypervisor_state.guest_cid {
+            inner.guest_cid = Some(reclaim_guest_cid(cid).context("reclaim vsock context ID")?);
+        }
+        if inner.pid.is_some() {
+            inner.state = VmmState::VmRunning;
+        }
+        Ok(inner)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_slot_entries() {
+        let slots = vec![
+            Some("drive-1".to_string()),
+            None,
+            Some("drive-3".to_string()),
+        ];
+        let entries = slot_entries(&slots);
+        assert_eq!(entries.len(), 2);
+        assert!(entries.contains("0:drive-1"));
+        assert!(entries.contains("2:drive-3"));
+        assert_eq!(slots_from_entries(&entries), slots);
+        assert!(slots_from_entries(&HashSet::new()).is_empty());
+    }
+}
@@ -0,0 +1,250 @@
+// Copyright (c) 2019-2022 Alibaba Cloud
+// Copyright (c) 2023 Kata Containers community
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+use super::inner::StratoVirtInner;
+use super::qmp::{BlockdevAdd, BlockdevCache, BlockdevFile, DeviceAdd};
+use crate::device::DeviceType;
+use crate::{BlockConfig, NetworkConfig, ShareFsDeviceConfig, VmmState, VM_ROOTFS_DRIVER_MMIO};
+use anyhow::{anyhow, Context, Result};
+use serde_json::json;
+
+// The microvm machine can't add virtio-mmio devices once it's running. It has
+// replaceable virtio-mmio block slots instead, empty until a drive is hot
+// plugged into one of them with device_add.
+const SV_REPLACEABLE_BLK_SLOTS: usize = 4;
+
+fn drive_id(index: u64) -> String {
+    format!("drive-{}", index)
+}
+
+impl StratoVirtInner {
+    pub(crate) async fn add_device(&mut self, device: DeviceType) -> Result<()> {
+        if self.state != VmmState::VmRunning {
+            self.pending_devices.push(device);
+            return Ok(());
+        }
+
+        match device {
+            DeviceType::Block(block) => self
+                .hotplug_block_device(&block.config)
+                .await
+                .context("add block device"),
+            _ => Err(anyhow!("stratovirt can't hot plug device {:?}", device)),
+        }
+    }
+
+    pub(crate) async fn remove_device(&mut self, device: DeviceType) -> Result<()> {
+        if self.state != VmmState::VmRunning {
+            return Ok(());
+        }
+
+        match device {
+            DeviceType::Block(block) => self
+                .unplug_block_device(&block.config)
+                .await
+                .context("remove block device"),
+            // the other devices are removed along with the VM
+            _ => Ok(()),
+        }
+    }
+
+    /// The arguments of the devices requested before the VM boots.
+    pub(crate) fn pending_device_args(&self) -> Result<Vec<String>> {
+        let mut args = vec![];
+        for device in &self.pending_devices {
+            match device {
+                // the guest booted from the initrd has no root drive
+                DeviceType::Block(block)
+                    if block.config.path_on_host != self.config.boot_info.initrd =>
+                {
+                    args.append(&mut self.block_device_args(&block.config))
+                }
+                DeviceType::Block(_) => (),
+                DeviceType::Network(network) => args.append(&mut net_device_args(&network.config)),
+                DeviceType::ShareFs(share_fs) => {
+                    args.append(&mut share_fs_device_args(&share_fs.config))
+                }
+                // the agent is reached through the vhost-vsock of the guest
+                // CID reserved for the VM, rather than through a hybrid vsock
+                DeviceType::HybridVsock(_) => (),
+                _ => return Err(anyhow!("stratovirt doesn't support device {:?}", device)),
+            }
+        }
+        Ok(args)
+    }
+
+    fn block_device_args(&self, config: &BlockConfig) -> Vec<String> {
+        let id = drive_id(config.index);
+        vec![
+            "-drive".to_string(),
+            format!(
+                "id={},file={},readonly={},direct={}",
+                id,
+                config.path_on_host,
+                on_off(config.is_readonly),
+                on_off(self.is_direct(config))
+            ),
+            "-device".to_string(),
+            format!("virtio-blk-device,drive={},id={}", id, id),
+        ]
+    }
+
+    fn is_direct(&self, config: &BlockConfig) -> bool {
+        config
+            .is_direct
+            .unwrap_or(self.config.blockdev_info.block_device_cache_direct)
+    }
+
+    async fn hotplug_block_device(&mut self, config: &BlockConfig) -> Result<()> {
+        let id = drive_id(config.index);
+        if self
+            .block_slots
+            .iter()
+            .any(|s| s.as_deref() == Some(id.as_str()))
+        {
+            return Err(anyhow!("drive {} is in use", id));
+        }
+        let slot = free_slot(&self.block_slots)?;
+
+        let mut qmp = self.qmp().await?;
+        let blockdev = BlockdevAdd {
+            node_name: id.clone(),
+            file: BlockdevFile {
+                driver: "file".to_string(),
+                filename: config.path_on_host.clone(),
+            },
+            cache: BlockdevCache {
+                direct: self.is_direct(config),
+            },
+            read_only: config.is_readonly,
+        };
+        qmp.execute("blockdev-add", Some(&blockdev))
+            .await
+            .with_context(|| format!("add block backend {}", id))?;
+
+        let device = DeviceAdd {
+            id: id.clone(),
+            driver: VM_ROOTFS_DRIVER_MMIO.to_string(),
+            addr: format!("{:#x}", slot),
+            drive: id.clone(),
+        };
+        if let Err(e) = qmp.execute("device_add", Some(&device)).await {
+            qmp.execute("blockdev-del", Some(&json!({ "node-name": id })))
+                .await
+                .ok();
+            return Err(e.context(format!("add block device {}", id)));
+        }
+
+        if self.block_slots.len() <= slot {
+            self.block_slots.resize(slot + 1, None);
+        }
+        self.block_slots[slot] = Some(id);
+        Ok(())
+    }
+
+    async fn unplug_block_device(&mut self, config: &BlockConfig) -> Result<()> {
+        let id = drive_id(config.index);
+        // the drives on the command line stay until the VM stops
+        let slot = match self
+            .block_slots
+            .iter()
+            .position(|s| s.as_deref() == Some(id.as_str()))
+        {
+            Some(slot) => slot,
+            None => return Ok(()),
+        };
+
+        let mut qmp = self.qmp().await?;
+        qmp.execute("device_del", Some(&json!({ "id": id })))
+            .await
+            .with_context(|| format!("remove block device {}", id))?;
+        qmp.execute("blockdev-del", Some(&json!({ "node-name": id })))
+            .await
+            .with_context(|| format!("remove block backend {}", id))?;
+
+        self.block_slots[slot] = None;
+        Ok(())
+    }
+}
+
+fn free_slot(slots: &[Option<String>]) -> Result<usize> {
+    (0..SV_REPLACEABLE_BLK_SLOTS)
+        .find(|slot| slots.get(*slot).map_or(true, |s| s.is_none()))
+        .ok_or_else(|| {
+            anyhow!(
+                "no block slot left, all the {} replaceable ones are in use",
+                SV_REPLACEABLE_BLK_SLOTS
+            )
+        })
+}
+
+fn net_device_args(config: &NetworkConfig) -> Vec<String> {
+    let id = &config.virt_iface_name;
+    let mut device = format!("virtio-net-device,netdev={},id={}", id, id);
+    if let Some(mac) = config.guest_mac.as_ref() {
+        device.push_str(&format!(",mac={:?}", mac));
+    }
+    vec![
+        "-netdev".to_string(),
+        format!("tap,id={},ifname={}", id, config.host_dev_name),
+        "-device".to_string(),
+        device,
+    ]
+}
+
+// The filesystem is served by the virtiofsd listening on `sock_path`.
+fn share_fs_device_args(config: &ShareFsDeviceConfig) -> Vec<String> {
+    let chardev = format!("chardev-{}", config.mount_tag);
+    vec![
+        "-chardev".to_string(),
+        format!("socket,id={},path={}", chardev, config.sock_path),
+        "-device".to_string(),
+        format!(
+            "vhost-user-fs-device,id=fs-{},chardev={},tag={}",
+            config.mount_tag, chardev, config.mount_tag
+        ),
+    ]
+}
+
+fn on_off(value: bool) -> &'static str {
+    if value {
+        "on"
+    } else {
+        "off"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_free_slot() {
+        assert_eq!(free_slot(&[]).unwrap(), 0);
+        assert_eq!(free_slot(&[Some("drive-1".to_string()), None]).unwrap(), 1);
+        assert_eq!(free_slot(&[Some("drive-1".to_string())]).unwrap(), 1);
+        let full = vec![Some("drive-1".to_string()); SV_REPLACEABLE_BLK_SLOTS];
+        assert!(free_slot(&full).is_err());
+    }
+
+    #[test]
+    fn test_share_fs_device_args() {
+        let config = ShareFsDeviceConfig {
+            sock_path: "/run/kata/sandbox/root/virtiofsd.sock".to_string(),
+            mount_tag: "kataShared".to_string(),
+            host_path: "/run/kata-containers/shared/sandboxes/sandbox/ro".to_string(),
+            fs_type: "virtio-fs".to_string(),
+            queue_size: 0,
+            queue_num: 0,
+            options: vec![],
+        };
+        assert_eq!(
+            share_fs_device_args(&config).join(" "),
+            "-chardev socket,id=chardev-kataShared,path=/run/kata/sandbox/root/virtiofsd.sock \
+             -device vhost-user-fs-device,id=fs-kataShared,chardev=chardev-kataShared,tag=kataShared"
+        );
+    }
+}
@@ -0,0 +1,518 @@
+// Copyright (c) 2019-2022 Alibaba Cloud
+// Copyright (c) 2023 Kata Containers community
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+use super::inner::StratoVirtInner;
+use crate::kernel_param::KernelParams;
+use crate::utils::{get_child_threads, get_command_line, get_jailer_root, get_sandbox_path};
+use crate::{check_vhost_vsock, reserve_guest_cid, VcpuThreadIds, VmmState, VM_ROOTFS_DRIVER_MMIO};
+use anyhow::{anyhow, Context, Result};
+use futures::future::join_all;
+use kata_sys_util::naming;
+use kata_types::capabilities::Capabilities;
+use kata_types::config::default::DEFAULT_AGENT_VSOCK_PORT;
+use nix::sched::{setns, CloneFlags};
+use nix::sys::signal::{kill, Signal};
+use nix::unistd::Pid;
+use serde_json::Value;
+use std::fs::{create_dir_all, File};
+use std::os::unix::io::AsRawFd;
+use std::path::Path;
+use std::process::Stdio;
+use tokio::io::{AsyncBufReadExt, AsyncRead, BufReader};
+use tokio::process::Command;
+use tokio::time::{Duration, Instant};
+
+const SV_NAME: &str = "stratovirt";
+
+const VSOCK_SCHEME: &str = "vsock";
+
+/// Number of milliseconds to wait before retrying to reach the QMP socket.
+const SV_POLL_TIME_MS: u64 = 10;
+
+/// Number of seconds to wait for the VMM to exit once asked to quit.
+const SV_QUIT_TIMEOUT_SECS: u64 = 3;
+
+impl StratoVirtInner {
+    pub(crate) async fn prepare_vm(&mut self, id: &str, netns: Option<String>) -> Result<()> {
+        self.id = id.to_string();
+        self.state = VmmState::NotReady;
+
+        self.vm_path = get_sandbox_path(id);
+        self.jailer_root = get_jailer_root(id);
+        self.netns = netns;
+
+        // fail before the VM boots if its vsock can't be created
+        check_vhost_vsock().context("check vhost-vsock")?;
+        let guest_cid = reserve_guest_cid().context("reserve vsock context ID")?;
+        info!(sl!(), "reserved vsock context ID {}", guest_cid.cid);
+        self.guest_cid = Some(guest_cid);
+
+        // the virtiofsd of the sandbox is started before the VM, with its
+        // socket in the jailer root
+        naming::claim_dir(Path::new(&self.vm_path), &self.id)
+            .with_context(|| format!("failed to create dir {}", self.vm_path))?;
+        create_dir_all(&self.jailer_root)
+            .with_context(|| format!("failed to create dir {}", self.jailer_root))?;
+        naming::check_socket_path(&self.qmp_socket()).context("check socket path")?;
+
+        Ok(())
+    }
+
+    pub(crate) async fn start_vm(&mut self, timeout_secs: i32) -> Result<()> {
+        self.launch().await.context("launch")?;
+
+        if let Err(error) = self.wait_vmm_ready(timeout_secs).await {
+            error!(sl!(), "start stratovirt vm error {:?}", error);
+            if let Err(err) = self.stop_vm().await {
+                error!(sl!(), "failed to stop stratovirt err : {:?}", err);
+            }
+            return Err(error.context("wait vmm"));
+        }
+        self.state = VmmState::VmRunning;
+
+        Ok(())
+    }
+
+    async fn launch(&mut self) -> Result<()> {
+        if let Some(pid) = self.pid {
+            return Err(anyhow!("{} already running with PID {}", SV_NAME, pid));
+        }
+
+        let _ = std::fs::remove_file(self.qmp_socket());
+
+        let args = self.vmm_args(self.guest_cid()?).context("vmm args")?;
+        info!(sl!(), "launch {} {:?}", self.config.path, args);
+        let mut cmd = Command::new(&self.config.path);
+        cmd.args(&args)
+            .current_dir("/")
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+
+        let netns = match self.netns.as_ref() {
+            Some(netns) => {
+                Some(File::open(netns).with_context(|| format!("open netns path {}", netns))?)
+            }
+            None => None,
+        };
+        if let Some(netns) = netns.as_ref() {
+            let netns_fd = netns.as_raw_fd();
+            // Safety: setns is async-signal-safe.
+            unsafe {
+                cmd.pre_exec(move || {
+                    setns(netns_fd, CloneFlags::CLONE_NEWNET).map_err(std::io::Error::from)
+                });
+            }
+        }
+
+        let mut child = cmd
+            .spawn()
+            .with_context(|| format!("{} spawn failed", self.config.path))?;
+        self.pid = child.id();
+
+        if let Some(stdout) = child.stdout.take() {
+            self.tasks.push(log_output(stdout, "stdout"));
+        }
+        if let Some(stderr) = child.stderr.take() {
+            self.tasks.push(log_output(stderr, "stderr"));
+        }
+        self.process = Some(child);
+        self.pending_devices.clear();
+
+        Ok(())
+    }
+
+    // The arguments starting the microvm, with the devices added before it
+    // boots, and the vsock of the guest `cid`.
+    fn vmm_args(&self, cid: u32) -> Result<Vec<String>> {
+        let config = &self.config;
+        let mut machine = config.machine_info.machine_type.clone();
+        // the vhost-user-fs device maps the guest memory into virtiofsd
+        if config.shared_fs.shared_fs.is_some() {
+            machine.push_str(",mem-share=on");
+        }
+
+        let mut args: Vec<String> = vec![
+            "-name".to_string(),
+            format!("sandbox-{}", self.id),
+            "-machine".to_string(),
+            machine,
+            "-smp".to_string(),
+            config.cpu_info.default_vcpus.to_string(),
+            "-m".to_string(),
+            format!("{}M", config.memory_info.default_memory),
+            "-kernel".to_string(),
+            config.boot_info.kernel.clone(),
+            "-append".to_string(),
+            self.get_kernel_params().context("get kernel params")?,
+        ];
+        if !config.boot_info.initrd.is_empty() {
+            args.push("-initrd".to_string());
+            args.push(config.boot_info.initrd.clone());
+        }
+        args.extend(vec![
+            "-qmp".to_string(),
+            format!("unix:{},server,nowait", self.qmp_socket()),
+            "-device".to_string(),
+            format!("vhost-vsock-device,id=vsock-{},guest-cid={}", cid, cid),
+            "-object".to_string(),
+            format!(
+                "rng-random,id=objrng0,filename={}",
+                config.machine_info.entropy_source
+            ),
+            "-device".to_string(),
+            "virtio-rng-device,rng=objrng0".to_string(),
+        ]);
+        if config.debug_info.enable_debug {
+            args.push("-serial".to_string());
+            args.push("stdio".to_string());
+        }
+        if config.security_info.disable_seccomp {
+            args.push("-disable-seccomp".to_string());
+        }
+
+        args.append(&mut self.pending_device_args().context("pending devices")?);
+
+        Ok(args)
+    }
+
+    async fn wait_vmm_ready(&mut self, timeout_secs: i32) -> Result<()> {
+        let deadline = Instant::now() + Duration::from_secs(timeout_secs as u64);
+        loop {
+            if self.qmp().await.is_ok() {
+                return Ok(());
+            }
+            if let Some(child) = self.process.as_mut() {
+                if let Some(status) = child.try_wait().context("wait vmm")? {
+                    return Err(anyhow!("{} exited with {}", SV_NAME, status));
+                }
+            }
+            if Instant::now() > deadline {
+                return Err(anyhow!(
+                    "QMP socket isn't ready after {} seconds",
+                    timeout_secs
+                ));
+            }
+            tokio::time::sleep(Duration::from_millis(SV_POLL_TIME_MS)).await;
+        }
+    }
+
+    fn get_kernel_params(&self) -> Result<String> {
+        let config = &self.config;
+        let mut params = KernelParams::new(config.debug_info.enable_debug);
+
+        // the guest is booted from the initrd, or from the first drive
+        if config.boot_info.initrd.is_empty() {
+            params.append(&mut KernelParams::new_rootfs_kernel_params(
+                VM_ROOTFS_DRIVER_MMIO,
+                &config.boot_info.rootfs_type,
+            )?);
+        }
+
+        // the devices are virtio-mmio ones, described on the command line
+        // by StratoVirt
+        params.append(&mut KernelParams::from_string("pci=off reboot=k panic=1"));
+        if config.debug_info.enable_debug {
+            params.append(&mut KernelParams::from_string("console=ttyS0"));
+        } else {
+            params.append(&mut KernelParams::from_string("quiet 8250.nr_uarts=0"));
+        }
+
+        if config.cpu_info.vcpu_rt_priority > 0 {
+            params.append(&mut KernelParams::new_realtime_kernel_params());
+        }
+        params.append(&mut KernelParams::from_string(
+            &config.boot_info.kernel_params,
+        ));
+        params.append(&mut KernelParams::new_hardening_kernel_params(
+            &config.security_info,
+        ));
+
+        params.to_string()
+    }
+
+    pub(crate) async fn stop_vm(&mut self) -> Result<()> {
+        info!(sl!(), "Stopping stratovirt VM");
+        if self.state == VmmState::VmRunning {
+            if let Err(e) = self.quit().await {
+                warn!(sl!(), "failed to quit stratovirt, kill it: {:?}", e);
+            }
+        }
+
+        match self.process.take() {
+            Some(mut child) => {
+                let exited =
+                    tokio::time::timeout(Duration::from_secs(SV_QUIT_TIMEOUT_SECS), child.wait())
+                        .await;
+                if !matches!(exited, Ok(Ok(_))) {
+                    // Note that this kills _and_ waits for the process!
+                    child.kill().await.context("kill vmm")?;
+                }
+            }
+            // the VMM of a restored sandbox, unless its pid was reused
+            None => {
+                if let Some(pid) = self.pid.filter(|pid| self.is_vmm(*pid)) {
+                    kill(Pid::from_raw(pid as i32), Signal::SIGKILL).context("kill vmm")?;
+                }
+            }
+        }
+        join_all(self.tasks.drain(..)).await;
+
+        self.pid = None;
+        self.state = VmmState::NotReady;
+        Ok(())
+    }
+
+    async fn quit(&self) -> Result<()> {
+        self.qmp()
+            .await?
+            .execute::<Value>("quit", None)
+            .await
+            .map(|_| ())
+    }
+
+    fn is_vmm(&self, pid: u32) -> bool {
+        let name = Path::new(&self.config.path).file_name();
+        get_command_line(pid)
+            .ok()
+            .and_then(|cmdline| cmdline.into_iter().next())
+            .map_or(false, |arg0| Path::new(&arg0).file_name() == name)
+    }
+
+    pub(crate) async fn reboot_vm(&mut self) -> Result<()> {
+        Err(anyhow!("reboot vm is not supported by stratovirt"))
+    }
+
+    pub(crate) async fn pause_vm(&self) -> Result<()> {
+        info!(sl!(), "do pause vm");
+        self.qmp()
+            .await?
+            .execute::<Value>("stop", None)
+            .await
+            .context("pause vm")?;
+        Ok(())
+    }
+
+    pub(crate) async fn resume_vm(&self) -> Result<()> {
+        info!(sl!(), "do resume vm");
+        self.qmp()
+            .await?
+            .execute::<Value>("cont", None)
+            .await
+            .context("resume vm")?;
+        Ok(())
+    }
+
+    pub(crate) async fn save_vm(&self) -> Result<()> {
+        Err(anyhow!("save vm is not supported by stratovirt yet"))
+    }
+
+    pub(crate) async fn get_agent_socket(&self) -> Result<String> {
+        Ok(format!(
+            "{}://{}:{}",
+            VSOCK_SCHEME,
+            self.guest_cid()?,
+            DEFAULT_AGENT_VSOCK_PORT
+        ))
+    }
+
+    pub(crate) async fn disconnect(&mut self) {
+        self.state = VmmState::NotReady;
+    }
+
+    pub(crate) async fn get_thread_ids(&self) -> Result<VcpuThreadIds> {
+        let mut vcpu_thread_ids = VcpuThreadIds::default();
+        let pid = match self.pid {
+            Some(pid) => pid,
+            None => return Ok(vcpu_thread_ids),
+        };
+
+        for tid in get_child_threads(pid) {
+            let comm = std::fs::read_to_string(format!("/proc/{}/task/{}/comm", pid, tid))
+                .unwrap_or_default();
+            if let Some(vcpu) = parse_vcpu_thread_name(&comm) {
+                vcpu_thread_ids.vcpus.insert(vcpu, tid);
+            }
+        }
+        info!(sl!(), "get thread ids {:?}", vcpu_thread_ids);
+        Ok(vcpu_thread_ids)
+    }
+
+    pub(crate) async fn cleanup(&self) -> Result<()> {
+        std::fs::remove_dir_all(&self.vm_path)
+            .map_err(|err| {
+                error!(sl!(), "failed to remove dir all for {}", &self.vm_path);
+                err
+            })
+            .ok();
+        Ok(())
+    }
+
+    // the microvm machine is booted with all its vCPUs and memory, none can be hot plugged
+    pub(crate) async fn resize_vcpu(&self, old_vcpu: u32, new_vcpu: u32) -> Result<(u32, u32)> {
+        if old_vcpu != new_vcpu {
+            warn!(
+                sl!(),
+                "resize vcpu: stratovirt can't hot plug vcpus, keep {} vcpus instead of {}",
+                old_vcpu,
+                new_vcpu
+            );
+        }
+        Ok((old_vcpu, old_vcpu))
+    }
+
+    pub(crate) async fn resize_memory(&self, new_mem_mb: u32) -> Result<u32> {
+        let default_memory = self.config.memory_info.default_memory;
+        if new_mem_mb != default_memory {
+            warn!(
+                sl!(),
+                "resize memory: stratovirt can't hot plug memory, keep {} MiB instead of {} MiB",
+                default_memory,
+                new_mem_mb
+            );
+        }
+        Ok(default_memory)
+    }
+
+    pub(crate) async fn get_pids(&self) -> Result<Vec<u32>> {
+        Ok(self.pid.into_iter().collect())
+    }
+
+    pub(crate) async fn get_vmm_master_tid(&self) -> Result<u32> {
+        self.pid
+            .ok_or_else(|| anyhow!("could not get vmm master tid"))
+    }
+
+    pub(crate) async fn get_ns_path(&self) -> Result<String> {
+        let pid = self.pid.ok_or_else(|| anyhow!("could not get ns path"))?;
+        Ok(format!("/proc/{}/ns", pid))
+    }
+
+    pub(crate) async fn check(&self) -> Result<()> {
+        if self.state != VmmState::VmRunning {
+            return Ok(());
+        }
+        self.qmp()
+            .await?
+            .execute::<Value>("query-status", None)
+            .await
+            .context("query status")?;
+        Ok(())
+    }
+
+    pub(crate) async fn get_jailer_root(&self) -> Result<String> {
+        Ok(self.jailer_root.clone())
+    }
+
+    pub(crate) async fn capabilities(&self) -> Result<Capabilities> {
+        Ok(self.capabilities.clone())
+    }
+
+    pub(crate) async fn get_hypervisor_metrics(&self) -> Result<String> {
+        Err(anyhow!(
+            "hypervisor metrics aren't supported by stratovirt yet"
+        ))
+    }
+
+    pub(crate) async fn dump_guest_memory(&self, _path: &str) -> Result<()> {
+        Err(anyhow!("guest memory dump isn't supported by stratovirt"))
+    }
+
+    pub(crate) async fn get_command_line(&self) -> Result<Vec<String>> {
+        let pid = self
+            .pid
+            .ok_or_else(|| anyhow!("{} isn't running", SV_NAME))?;
+        get_command_line(pid)
+    }
+}
+
+// The vCPU threads of StratoVirt are named "CPU <index>/KVM".
+fn parse_vcpu_thread_name(comm: &str) -> Option<u32> {
+    comm.trim()
+        .strip_prefix("CPU ")?
+        .strip_suffix("/KVM")?
+        .parse()
+        .ok()
+}
+
+// Log the output of the VMM until it exits.
+fn log_output<R>(reader: R, stream: &'static str) -> tokio::task::JoinHandle<()>
+where
+    R: AsyncRead + Unpin + Send + 'static,
+{
+    tokio::spawn(async move {
+        let mut lines = BufReader::new(reader).lines();
+        while let Ok(Some(line)) = lines.next_line().await {
+            info!(sl!(), "{}", line; "stream" => stream, "vmm" => SV_NAME);
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_vcpu_thread_name() {
+        assert_eq!(parse_vcpu_thread_name("CPU 0/KVM\n"), Some(0));
+        assert_eq!(parse_vcpu_thread_name("CPU 12/KVM"), Some(12));
+        assert_eq!(parse_vcpu_thread_name("stratovirt\n"), None);
+        assert_eq!(parse_vcpu_thread_name("CPU 1"), None);
+    }
+
+    #[actix_rt::test]
+    async fn test_vmm_args() {
+        let mut sv = StratoVirtInner::new();
+        sv.config.path = "/usr/bin/stratovirt".to_string();
+        sv.config.machine_info.machine_type = "microvm".to_string();
+        sv.config.machine_info.entropy_source = "/dev/urandom".to_string();
+        sv.config.boot_info.kernel = "/usr/share/kata-containers/vmlinux".to_string();
+        sv.config.boot_info.rootfs_type = "ext4".to_string();
+        sv.config.cpu_info.default_vcpus = 1;
+        sv.config.memory_info.default_memory = 128;
+        sv.id = "sandbox".to_string();
+        sv.vm_path = "/run/kata/sandbox".to_string();
+
+        // queued until the VM boots
+        sv.add_device(crate::device::DeviceType::Block(crate::BlockDevice {
+            config: crate::BlockConfig {
+                index: 0,
+                path_on_host: "/usr/share/kata-containers/kata-containers.img".to_string(),
+                is_readonly: true,
+                ..Default::default()
+            },
+            ..Default::default()
+        }))
+        .await
+        .unwrap();
+        sv.add_device(crate::device::DeviceType::Network(crate::NetworkDevice {
+            config: crate::NetworkConfig {
+                virt_iface_name: "eth0".to_string(),
+                host_dev_name: "tap0_kata".to_string(),
+                ..Default::default()
+            },
+            ..Default::default()
+        }))
+        .await
+        .unwrap();
+        assert_eq!(sv.pending_devices.len(), 2);
+
+        let args = sv.vmm_args(3).unwrap();
+        let cmdline = args.join(" ");
+        assert!(cmdline.starts_with("-name sandbox-sandbox -machine microvm -smp 1 -m 128M"));
+        assert!(cmdline.contains(
+            "-qmp unix:/run/kata/sandbox/qmp.sock,server,nowait \
+             -device vhost-vsock-device,id=vsock-3,guest-cid=3"
+        ));
+        assert!(cmdline.contains(
+            "-drive id=drive-0,file=/usr/share/kata-containers/kata-containers.img,readonly=on,direct=off \
+             -device virtio-blk-device,drive=drive-0,id=drive-0"
+        ));
+        assert!(cmdline.ends_with(
+            "-netdev tap,id=eth0,ifname=tap0_kata -device virtio-net-device,netdev=eth0,id=eth0"
+        ));
+    }
+}
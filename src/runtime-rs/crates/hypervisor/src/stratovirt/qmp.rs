@@ -0,0 +1,239 @@
+// Copyright (c) 2023 Kata Containers community
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+// The client of the QMP socket of StratoVirt, JSON commands and responses, one
+// per line, interleaved with the asynchronous events of the VM. StratoVirt
+// implements the subset of the QEMU Machine Protocol used here, see
+// https://gitee.com/openeuler/stratovirt/blob/master/docs/qmp.md
+
+use std::path::Path;
+use std::time::Duration;
+
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::io::{
+    split, AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader, Lines, ReadHalf,
+    WriteHalf,
+};
+use tokio::net::UnixStream;
+
+// every command is handled right away by the main loop of the VMM
+const QMP_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Serialize, Debug)]
+struct Command<'a> {
+    execute: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    arguments: Option<Value>,
+}
+
+#[derive(Deserialize, Debug, Default)]
+struct QmpError {
+    class: String,
+    desc: String,
+}
+
+#[derive(Serialize, Debug, Default, Clone, PartialEq)]
+pub(crate) struct BlockdevFile {
+    pub driver: String,
+    pub filename: String,
+}
+
+#[derive(Serialize, Debug, Default, Clone, PartialEq)]
+pub(crate) struct BlockdevCache {
+    pub direct: bool,
+}
+
+#[derive(Serialize, Debug, Default, Clone, PartialEq)]
+pub(crate) struct BlockdevAdd {
+    #[serde(rename = "node-name")]
+    pub node_name: String,
+    pub file: BlockdevFile,
+    pub cache: BlockdevCache,
+    #[serde(rename = "read-only")]
+    pub read_only: bool,
+}
+
+#[derive(Serialize, Debug, Default, Clone, PartialEq)]
+pub(crate) struct DeviceAdd {
+    pub id: String,
+    pub driver: String,
+    pub addr: String,
+    pub drive: String,
+}
+
+pub(crate) struct Qmp<S> {
+    lines: Lines<BufReader<ReadHalf<S>>>,
+    writer: WriteHalf<S>,
+}
+
+impl<S> std::fmt::Debug for Qmp<S> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Qmp").finish()
+    }
+}
+
+impl Qmp<UnixStream> {
+    /// Connect to the QMP socket at `path`, ready for commands.
+    pub(crate) async fn connect(path: &Path) -> Result<Self> {
+        let stream = UnixStream::connect(path)
+            .await
+            .with_context(|| format!("connect to {}", path.display()))?;
+        let mut qmp = Self::new(stream);
+        tokio::time::timeout(QMP_TIMEOUT, qmp.handshake())
+            .await
+            .map_err(|_| anyhow!("QMP handshake timeout after {:?}", QMP_TIMEOUT))??;
+        Ok(qmp)
+    }
+}
+
+impl<S: AsyncRead + AsyncWrite> Qmp<S> {
+    fn new(stream: S) -> Self {
+        let (reader, writer) = split(stream);
+        Self {
+            lines: BufReader::new(reader).lines(),
+            writer,
+        }
+    }
+
+    // The server greets with its version, and accepts the commands once the
+    // capabilities are negotiated.
+    async fn handshake(&mut self) -> Result<()> {
+        let greeting = self.read_message().await.context("read greeting")?;
+        if greeting.get("QMP").is_none() {
+            return Err(anyhow!("unexpected QMP greeting {}", greeting));
+        }
+        self.execute_command("qmp_capabilities", None)
+            .await
+            .map(|_| ())
+    }
+
+    /// Execute the command `name`, and return what it returns.
+    pub(crate) async fn execute<T: Serialize>(
+        &mut self,
+        name: &str,
+        arguments: Option<&T>,
+    ) -> Result<Value> {
+        let arguments = arguments
+            .map(serde_json::to_value)
+            .transpose()
+            .context("serialize arguments")?;
+        tokio::time::timeout(QMP_TIMEOUT, self.execute_command(name, arguments))
+            .await
+            .map_err(|_| anyhow!("{} timeout after {:?}", name, QMP_TIMEOUT))?
+    }
+
+    async fn execute_command(&mut self, name: &str, arguments: Option<Value>) -> Result<Value> {
+        let mut request = serde_json::to_vec(&Command {
+            execute: name,
+            arguments,
+        })
+        .context("serialize command")?;
+        request.push(b'\n');
+        self.writer
+            .write_all(&request)
+            .await
+            .with_context(|| format!("send {}", name))?;
+
+        loop {
+            let mut msg = self
+                .read_message()
+                .await
+                .with_context(|| format!("read the response of {}", name))?;
+            if let Some(ret) = msg.get_mut("return") {
+                return Ok(ret.take());
+            }
+            if let Some(err) = msg.get_mut("error") {
+                let err: QmpError = serde_json::from_value(err.take()).unwrap_or_default();
+                return Err(anyhow!("{} failed: {}: {}", name, err.class, err.desc));
+            }
+            // the events aren't followed, e.g. STOP and RESUME
+            if let Some(event) = msg.get("event") {
+                debug!(sl!(), "QMP event {}", event);
+            }
+        }
+    }
+
+    async fn read_message(&mut self) -> Result<Value> {
+        let line = self
+            .lines
+            .next_line()
+            .await?
+            .ok_or_else(|| anyhow!("QMP connection closed"))?;
+        serde_json::from_str(&line).with_context(|| format!("parse QMP message {}", line))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn reply(lines: &mut Lines<BufReader<ReadHalf<UnixStream>>>, expected: &str) -> Value {
+        let line = lines.next_line().await.unwrap().unwrap();
+        let cmd: Value = serde_json::from_str(&line).unwrap();
+        assert_eq!(cmd["execute"], expected);
+        cmd
+    }
+
+    #[actix_rt::test]
+    async fn test_execute() {
+        let (client, server) = UnixStream::pair().unwrap();
+        let server = tokio::spawn(async move {
+            let (reader, mut writer) = split(server);
+            let mut lines = BufReader::new(reader).lines();
+            writer
+                .write_all(b"{\"QMP\":{\"version\":{\"StratoVirt\":{\"micro\":1,\"minor\":3,\"major\":2}},\"capabilities\":[]}}\n")
+                .await
+                .unwrap();
+
+            reply(&mut lines, "qmp_capabilities").await;
+            writer.write_all(b"{\"return\":{}}\n").await.unwrap();
+
+            reply(&mut lines, "stop").await;
+            writer
+                .write_all(b"{\"event\":\"STOP\",\"data\":{},\"timestamp\":{\"seconds\":1,\"microseconds\":2}}\n{\"return\":{}}\n")
+                .await
+                .unwrap();
+
+            let cmd = reply(&mut lines, "device_del").await;
+            assert_eq!(cmd["arguments"]["id"], "drive-1");
+            writer
+                .write_all(b"{\"error\":{\"class\":\"GenericError\",\"desc\":\"Failed to find device\"}}\n")
+                .await
+                .unwrap();
+        });
+
+        let mut qmp = Qmp::new(client);
+        qmp.handshake().await.unwrap();
+        qmp.execute::<Value>("stop", None).await.unwrap();
+        let err = qmp
+            .execute("device_del", Some(&serde_json::json!({"id": "drive-1"})))
+            .await
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "device_del failed: GenericError: Failed to find device"
+        );
+        server.await.unwrap();
+    }
+
+    #[test]
+    fn test_arguments() {
+        let blockdev = BlockdevAdd {
+            node_name: "drive-1".to_string(),
+            file: BlockdevFile {
+                driver: "file".to_string(),
+                filename: "/dev/dm-1".to_string(),
+            },
+            cache: BlockdevCache { direct: true },
+            read_only: false,
+        };
+        assert_eq!(
+            serde_json::to_string(&blockdev).unwrap(),
+            r#"{"node-name":"drive-1","file":{"driver":"file","filename":"/dev/dm-1"},"cache":{"direct":true},"read-only":false}"#
+        );
+    }
+}
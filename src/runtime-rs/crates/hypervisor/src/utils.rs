@@ -6,7 +6,10 @@
 
 use std::collections::HashSet;
 
+use anyhow::{anyhow, Context, Result};
+use kata_sys_util::naming::sandbox_dir_name;
 use kata_types::config::KATA_PATH;
+use tokio::runtime::{Handle, RuntimeFlavor};
 
 use crate::{DEFAULT_HYBRID_VSOCK_NAME, JAILER_ROOT};
 
@@ -31,9 +34,10 @@ pub fn get_child_threads(pid: u32) -> HashSet<u32> {
 }
 
 // Return the path for a _hypothetical_ sandbox: the path does *not* exist
-// yet, and for this reason safe-path cannot be used.
+// yet, and for this reason safe-path cannot be used. The name of the dir is
+// bounded, so that the paths of the sockets in it fit whatever the sid.
 pub fn get_sandbox_path(sid: &str) -> String {
-    [KATA_PATH, sid].join("/")
+    [KATA_PATH, &sandbox_dir_name(sid)].join("/")
 }
 
 pub fn get_hvsock_path(sid: &str) -> String {
@@ -47,3 +51,116 @@ pub fn get_jailer_root(sid: &str) -> String {
 
     [&sandbox_path, JAILER_ROOT].join("/")
 }
+
+// Return the command line of the process pid, its arguments are separated by NUL in procfs.
+pub fn get_command_line(pid: u32) -> Result<Vec<String>> {
+    let path = format!("/proc/{}/cmdline", pid);
+    let cmdline = std::fs::read(&path).with_context(|| format!("read {}", path))?;
+    Ok(cmdline
+        .split(|b| *b == 0)
+        .filter(|arg| !arg.is_empty())
+        .map(|arg| String::from_utf8_lossy(arg).into_owned())
+        .collect())
+}
+
+// Return the free memory of the default hugepage pool of the host, in MiB.
+pub fn get_free_hugepages_mib() -> Result<u64> {
+    let meminfo = std::fs::read_to_string("/proc/meminfo").context("read /proc/meminfo")?;
+    parse_free_hugepages_mib(&meminfo)
+}
+
+// Return the memory of the host available to start new applications without swapping, in MiB.
+pub fn get_available_memory_mib() -> Result<u64> {
+    let meminfo = std::fs::read_to_string("/proc/meminfo").context("read /proc/meminfo")?;
+    Ok(meminfo_field(&meminfo, "MemAvailable:")? / 1024)
+}
+
+/// Run the blocking operation `f`, e.g. a request waiting for the VMM or a mount syscall, without
+/// stalling the other tasks of the async worker thread running it: they're handed over to the
+/// other workers meanwhile. `f` is run as is out of a multi-threaded runtime.
+pub fn block_in_place<F, R>(f: F) -> R
+where
+    F: FnOnce() -> R,
+{
+    match Handle::try_current() {
+        Ok(handle) if handle.runtime_flavor() == RuntimeFlavor::MultiThread => {
+            tokio::task::block_in_place(f)
+        }
+        _ => f(),
+    }
+}
+
+fn meminfo_field(meminfo: &str, name: &str) -> Result<u64> {
+    meminfo
+        .lines()
+        .find_map(|line| line.strip_prefix(name))
+        .and_then(|value| value.trim().trim_end_matches("kB").trim().parse().ok())
+        .ok_or_else(|| anyhow!("no {} in meminfo", name))
+}
+
+fn parse_free_hugepages_mib(meminfo: &str) -> Result<u64> {
+    let free_pages = meminfo_field(meminfo, "HugePages_Free:")?;
+    let page_size_kb = meminfo_field(meminfo, "Hugepagesize:")?;
+
+    Ok(free_pages * page_size_kb / 1024)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_get_hvsock_path() {
+        let sid = "a".repeat(64);
+        assert_eq!(
+            get_hvsock_path(&sid),
+            format!("/run/kata/{}/root/kata.hvsock", sid)
+        );
+
+        // the path of the socket with its port fits whatever the sid
+        let sid = "a".repeat(200);
+        let path = format!("{}_1024", get_hvsock_path(&sid));
+        assert!(kata_sys_util::naming::check_socket_path(&path).is_ok());
+        assert_ne!(get_hvsock_path(&sid), get_hvsock_path(&"a".repeat(201)));
+    }
+
+    #[test]
+    fn test_get_command_line() {
+        let args = get_command_line(std::process::id()).unwrap();
+        let expected: Vec<String> = std::env::args().collect();
+        assert_eq!(args, expected);
+    }
+
+    #[test]
+    fn test_parse_free_hugepages_mib() {
+        let meminfo = "MemTotal:       16314572 kB\n\
+                       HugePages_Total:     512\n\
+                       HugePages_Free:      384\n\
+                       Hugepagesize:       2048 kB\n";
+        assert_eq!(parse_free_hugepages_mib(meminfo).unwrap(), 768);
+        assert!(parse_free_hugepages_mib("MemTotal:       16314572 kB\n").is_err());
+    }
+
+    #[test]
+    fn test_block_in_place() {
+        assert_eq!(block_in_place(|| 1), 1);
+
+        let rt = tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(1)
+            .build()
+            .unwrap();
+        assert_eq!(rt.block_on(async { block_in_place(|| 2) }), 2);
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        assert_eq!(rt.block_on(async { block_in_place(|| 3) }), 3);
+    }
+
+    #[test]
+    fn test_meminfo_field() {
+        let meminfo = "MemTotal:       16314572 kB\n\
+                       MemAvailable:    8157286 kB\n";
+        assert_eq!(meminfo_field(meminfo, "MemAvailable:").unwrap(), 8157286);
+        assert!(meminfo_field(meminfo, "MemFree:").is_err());
+    }
+}
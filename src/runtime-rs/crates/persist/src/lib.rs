@@ -11,12 +11,12 @@ use serde::de;
 use std::{fs::File, io::BufReader};
 
 pub const PERSIST_FILE: &str = "state.json";
-use kata_sys_util::validate::verify_id;
+use kata_sys_util::{naming::sandbox_dir_name, validate::verify_id};
 use safe_path::scoped_join;
 
 pub fn to_disk<T: serde::Serialize>(value: &T, sid: &str) -> Result<()> {
     verify_id(sid).context("failed to verify sid")?;
-    let mut path = scoped_join(KATA_PATH, sid)?;
+    let mut path = scoped_join(KATA_PATH, sandbox_dir_name(sid))?;
     if path.exists() {
         path.push(PERSIST_FILE);
         let f = File::create(path)
@@ -34,7 +34,7 @@ where
     T: de::DeserializeOwned,
 {
     verify_id(sid).context("failed to verify sid")?;
-    let mut path = scoped_join(KATA_PATH, sid)?;
+    let mut path = scoped_join(KATA_PATH, sandbox_dir_name(sid))?;
     if path.exists() {
         path.push(PERSIST_FILE);
         let file = File::open(path).context("failed to open the file")?;
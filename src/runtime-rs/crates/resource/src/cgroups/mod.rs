@@ -29,6 +29,8 @@ use tokio::sync::RwLock;
 use crate::ResourceUpdateOp;
 
 const OS_ERROR_NO_SUCH_PROCESS: i32 = 3;
+// the period of the cpu quota of the sandbox cgroup, in us
+const SANDBOX_CPU_PERIOD: u64 = 100_000;
 
 pub struct CgroupArgs {
     pub sid: String,
@@ -36,6 +38,7 @@ pub struct CgroupArgs {
 }
 
 pub struct CgroupConfig {
+    pub sid: String,
     pub path: String,
     pub overhead_path: String,
     pub sandbox_cgroup_only: bool,
@@ -52,6 +55,7 @@ impl CgroupConfig {
             .unwrap_or_default();
 
         Ok(Self {
+            sid: sid.to_string(),
             path,
             overhead_path,
             sandbox_cgroup_only: toml_config.runtime.sandbox_cgroup_only,
@@ -230,6 +234,19 @@ impl CgroupsResource {
 
     async fn merge_resources(&self) -> Resources {
         let resources = self.resources.read().await;
+        // all the threads of the pod live in the sandbox cgroup, it's limited by the cpu of
+        // the pod, the memory is left to the pod cgroup which accounts the pod overhead too
+        let (shares, quota) = if self.cgroup_config.sandbox_cgroup_only {
+            let containers = resources
+                .iter()
+                .filter(|(cid, _)| **cid != self.cgroup_config.sid)
+                .map(|(_, r)| &r.cpu);
+            let (shares, quota) = sandbox_cpu_limits(containers);
+            // -1 lifts the quota set for the containers removed since
+            (shares, Some(quota.unwrap_or(-1)))
+        } else {
+            (None, None)
+        };
 
         let mut cpu_list: HashSet<String> = HashSet::new();
         let mut mem_list: HashSet<String> = HashSet::new();
@@ -246,6 +263,9 @@ impl CgroupsResource {
         let cpu_resource = CpuResources {
             cpus: Some(Vec::from_iter(cpu_list.into_iter()).join(",")),
             mems: Some(Vec::from_iter(mem_list.into_iter()).join(",")),
+            shares,
+            quota,
+            period: quota.map(|_| SANDBOX_CPU_PERIOD),
             ..Default::default()
         };
 
@@ -260,7 +280,10 @@ impl CgroupsResource {
 
         CpuResources {
             cpus: cpu.clone().map(|cpu| cpu.cpus),
-            mems: cpu.map(|cpu| cpu.mems),
+            mems: cpu.clone().map(|cpu| cpu.mems),
+            shares: cpu.as_ref().and_then(|cpu| cpu.shares),
+            quota: cpu.as_ref().and_then(|cpu| cpu.quota),
+            period: cpu.and_then(|cpu| cpu.period),
             ..Default::default()
         }
     }
@@ -273,6 +296,27 @@ impl CgroupsResource {
     }
 }
 
+// sandbox_cpu_limits returns the cpu shares and the cpu quota, over SANDBOX_CPU_PERIOD, of the
+// sandbox from the ones of its containers. The quota is the sum of the quotas of the containers,
+// the sandbox is unconstrained as soon as one of them is.
+fn sandbox_cpu_limits<'a>(
+    containers: impl Iterator<Item = &'a CpuResources>,
+) -> (Option<u64>, Option<i64>) {
+    let mut shares = 0;
+    let mut quota = Some(0);
+    for cpu in containers {
+        shares += cpu.shares.unwrap_or_default();
+        quota = match (quota, cpu.quota, cpu.period) {
+            (Some(sum), Some(q), Some(p)) if q > 0 && p > 0 => {
+                Some(sum + (q as u64 * SANDBOX_CPU_PERIOD / p) as i64)
+            }
+            _ => None,
+        };
+    }
+
+    ((shares > 0).then(|| shares), quota.filter(|q| *q > 0))
+}
+
 #[async_trait]
 impl Persist for CgroupsResource {
     type State = CgroupState;
@@ -294,11 +338,52 @@ impl Persist for CgroupsResource {
         let config = CgroupConfig::new(&cgroup_args.sid, &cgroup_args.config)?;
         let path = cgroup_state.path.unwrap_or_default();
         let cgroup_manager = Cgroup::load(hier, path.as_str());
+        let overhead_cgroup_manager = if !cgroup_state.sandbox_cgroup_only {
+            cgroup_state
+                .overhead_path
+                .map(|path| Cgroup::load(cgroups_rs::hierarchies::auto(), path.as_str()))
+        } else {
+            None
+        };
         Ok(Self {
             cgroup_manager,
             resources: Arc::new(RwLock::new(HashMap::new())),
-            overhead_cgroup_manager: None,
+            overhead_cgroup_manager,
             cgroup_config: config,
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sandbox_cpu_limits() {
+        let cpu = |shares, quota, period| CpuResources {
+            shares,
+            quota,
+            period,
+            ..Default::default()
+        };
+
+        // the quotas are summed over the period of the sandbox
+        let containers = vec![
+            cpu(Some(1024), Some(50_000), Some(100_000)),
+            cpu(Some(512), Some(100_000), Some(50_000)),
+        ];
+        assert_eq!(
+            sandbox_cpu_limits(containers.iter()),
+            (Some(1536), Some(250_000))
+        );
+
+        // one container without cpu limit leaves the sandbox unconstrained
+        let containers = vec![
+            cpu(Some(1024), Some(50_000), Some(100_000)),
+            cpu(Some(2), Some(-1), Some(100_000)),
+        ];
+        assert_eq!(sandbox_cpu_limits(containers.iter()), (Some(1026), None));
+
+        assert_eq!(sandbox_cpu_limits(std::iter::empty()), (None, None));
+    }
+}
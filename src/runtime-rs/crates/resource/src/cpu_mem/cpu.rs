@@ -8,18 +8,28 @@ use std::{
     cmp,
     collections::{HashMap, HashSet},
     convert::TryFrom,
+    fs,
+    str::FromStr,
     sync::Arc,
 };
 
 use agent::{Agent, OnlineCPUMemRequest};
-use anyhow::{Context, Ok, Result};
+use anyhow::{anyhow, Context, Ok, Result};
 use hypervisor::Hypervisor;
-use kata_types::{config::TomlConfig, cpu::LinuxContainerCpuResources};
+use kata_types::{
+    config::TomlConfig,
+    cpu::{CpuSet, LinuxContainerCpuResources},
+};
+use nix::{errno::Errno, sched, unistd::Pid};
 use oci::LinuxCpu;
 use tokio::sync::RwLock;
 
+use super::cpu_affinity::VcpuAffinity;
 use crate::ResourceUpdateOp;
 
+// CPUs isolated from the host scheduler by the `isolcpus` kernel parameter
+const HOST_ISOLATED_CPUS_PATH: &str = "/sys/devices/system/cpu/isolated";
+
 #[derive(Default, Debug, Clone)]
 pub struct CpuResource {
     /// Current number of vCPUs
@@ -30,6 +40,16 @@ pub struct CpuResource {
 
     /// CpuResource of each container
     pub(crate) container_cpu_resources: Arc<RwLock<HashMap<String, LinuxContainerCpuResources>>>,
+
+    /// Real-time scheduling priority of the vCPU threads, 0 if disabled
+    pub(crate) vcpu_rt_priority: u32,
+
+    /// Host CPUs dedicated to the real-time vCPU threads
+    pub(crate) vcpu_rt_cpus: Vec<u32>,
+
+    /// Placement of the vCPU threads on the host CPUs, None if they're
+    /// scheduled by the host
+    pub(crate) vcpu_affinity: Option<VcpuAffinity>,
 }
 
 impl CpuResource {
@@ -43,9 +63,70 @@ impl CpuResource {
             current_vcpu: Arc::new(RwLock::new(hypervisor_config.cpu_info.default_vcpus as u32)),
             default_vcpu: hypervisor_config.cpu_info.default_vcpus as u32,
             container_cpu_resources: Arc::new(RwLock::new(HashMap::new())),
+            vcpu_rt_priority: hypervisor_config.cpu_info.vcpu_rt_priority,
+            vcpu_rt_cpus: hypervisor_config
+                .cpu_info
+                .get_vcpu_rt_cpus()
+                .context("get vcpu rt cpus")?
+                .to_vec(),
+            vcpu_affinity: VcpuAffinity::new(&hypervisor_config.vcpu_affinity)
+                .context("get vcpu affinity")?,
         })
     }
 
+    // Run the vCPU threads with real-time scheduling priority, each of them
+    // pinned to a dedicated host CPU.
+    pub(crate) async fn setup_vcpu_rt(&self, hypervisor: &dyn Hypervisor) -> Result<()> {
+        if self.vcpu_rt_priority == 0 {
+            return Ok(());
+        }
+
+        check_host_isolated_cpus(&self.vcpu_rt_cpus).context("check host isolated cpus")?;
+
+        let tids = hypervisor
+            .get_thread_ids()
+            .await
+            .context("get vcpu thread ids")?;
+        for (vcpu, tid) in tids.vcpus.iter() {
+            let cpu = self
+                .vcpu_rt_cpus
+                .get(*vcpu as usize)
+                .ok_or_else(|| anyhow!("no dedicated cpu for vcpu {}", vcpu))?;
+            info!(
+                sl!(),
+                "run vcpu {} thread {} on cpu {} with rt priority {}",
+                vcpu,
+                tid,
+                cpu,
+                self.vcpu_rt_priority
+            );
+
+            // pin the thread before raising its priority, so that it never
+            // starves the threads of a shared cpu
+            let pid = Pid::from_raw(*tid as i32);
+            let mut cpu_set = sched::CpuSet::new();
+            cpu_set.set(*cpu as usize)?;
+            sched::sched_setaffinity(pid, &cpu_set)
+                .context(format!("set affinity of vcpu {}", vcpu))?;
+
+            let param = libc::sched_param {
+                sched_priority: self.vcpu_rt_priority as libc::c_int,
+            };
+            let ret = unsafe { libc::sched_setscheduler(pid.as_raw(), libc::SCHED_FIFO, &param) };
+            Errno::result(ret).context(format!("set scheduler of vcpu {}", vcpu))?;
+        }
+
+        Ok(())
+    }
+
+    // Place the vCPU threads on the host CPUs of the vCPU affinity.
+    pub(crate) async fn setup_vcpu_affinity(&self, hypervisor: &dyn Hypervisor) -> Result<()> {
+        match self.vcpu_affinity.as_ref() {
+            Some(affinity) => affinity.apply(hypervisor).await,
+            None => Ok(()),
+        }
+    }
+
     pub(crate) async fn update_cpu_resources(
         &self,
         cid: &str,
@@ -57,32 +138,25 @@ impl CpuResource {
         self.update_container_cpu_resources(cid, linux_cpus, op)
             .await
             .context("update container cpu resources")?;
+
+        // the vcpus required are calculated with the lock held, so that the vcpus required by a
+        // concurrent update aren't resized back to the ones calculated before it
+        let mut current_vcpu = self.current_vcpu.write().await;
         let vcpu_required = self
             .calc_cpu_resources()
             .await
             .context("calculate vcpus required")?;
 
-        if vcpu_required == self.current_vcpu().await {
+        if vcpu_required == *current_vcpu {
             return Ok(());
         }
 
-        let curr_vcpus = self
-            .do_update_cpu_resources(vcpu_required, op, hypervisor, agent)
+        *current_vcpu = self
+            .do_update_cpu_resources(*current_vcpu, vcpu_required, op, hypervisor, agent)
             .await?;
-        self.update_current_vcpu(curr_vcpus).await;
         Ok(())
     }
 
-    async fn current_vcpu(&self) -> u32 {
-        let current_vcpu = self.current_vcpu.read().await;
-        *current_vcpu
-    }
-
-    async fn update_current_vcpu(&self, new_vcpus: u32) {
-        let mut current_vcpu = self.current_vcpu.write().await;
-        *current_vcpu = new_vcpus;
-    }
-
     // update container_cpu_resources field
     async fn update_container_cpu_resources(
         &self,
@@ -145,13 +219,12 @@ impl CpuResource {
     // do hotplug and hot-unplug the vcpu
     async fn do_update_cpu_resources(
         &self,
+        old_vcpus: u32,
         new_vcpus: u32,
         op: ResourceUpdateOp,
         hypervisor: &dyn Hypervisor,
         agent: &dyn Agent,
     ) -> Result<u32> {
-        let old_vcpus = self.current_vcpu().await;
-
         // when adding vcpus, ignore old_vcpus > new_vcpus
         // when deleting vcpus, ignore old_vcpus < new_vcpus
         if (op == ResourceUpdateOp::Add && old_vcpus > new_vcpus)
@@ -181,8 +254,38 @@ impl CpuResource {
                 })
                 .await
                 .context("online vcpus")?;
+
+            // the hot plugged vcpus run in new threads
+            self.setup_vcpu_rt(hypervisor)
+                .await
+                .context("setup vcpu rt")?;
+            self.setup_vcpu_affinity(hypervisor)
+                .await
+                .context("setup vcpu affinity")?;
         }
 
         Ok(new)
     }
 }
+
+// Real-time vCPUs can't share the host CPUs with other threads, make sure
+// they are isolated from the host scheduler.
+fn check_host_isolated_cpus(cpus: &[u32]) -> Result<()> {
+    let isolated = fs::read_to_string(HOST_ISOLATED_CPUS_PATH)
+        .context(format!("read {}", HOST_ISOLATED_CPUS_PATH))?;
+    let isolated = CpuSet::from_str(isolated.trim())?;
+
+    let shared: Vec<u32> = cpus
+        .iter()
+        .filter(|cpu| !isolated.contains(*cpu))
+        .copied()
+        .collect();
+    if !shared.is_empty() {
+        return Err(anyhow!(
+            "cpus {:?} are not isolated by the isolcpus kernel parameter",
+            shared
+        ));
+    }
+
+    Ok(())
+}
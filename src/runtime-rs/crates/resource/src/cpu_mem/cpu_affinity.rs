@@ -0,0 +1,130 @@
+// Copyright (c) 2023 Kata Containers community
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+// The placement of the vCPU threads on the host CPUs, either each of them
+// pinned to one host CPU, or all of them sharing a set of host CPUs. The host
+// CPUs are the ones configured and the ones of the NUMA nodes configured, so
+// that the vCPUs stay close to the memory and the devices of their nodes.
+
+use std::collections::{BTreeSet, HashMap};
+
+use anyhow::{anyhow, Context, Result};
+use hypervisor::Hypervisor;
+use kata_types::config::{VcpuAffinityInfo, VCPU_AFFINITY_PINNED};
+use nix::{sched, unistd::Pid};
+
+#[derive(Default, Debug, Clone)]
+pub(crate) struct VcpuAffinity {
+    /// Each vCPU thread is pinned to one of the host CPUs, in turn
+    pinned: bool,
+    /// Host CPUs of the vCPU threads, in ascending order
+    cpus: Vec<u32>,
+}
+
+impl VcpuAffinity {
+    /// Get the placement of the vCPU threads, `None` if the vCPU threads are
+    /// scheduled by the host.
+    pub(crate) fn new(info: &VcpuAffinityInfo) -> Result<Option<Self>> {
+        if info.policy.is_empty() {
+            return Ok(None);
+        }
+
+        let mut cpus: BTreeSet<u32> = info.get_cpus()?.iter().copied().collect();
+        let nodes = info.get_numa_nodes()?.to_vec();
+        if !nodes.is_empty() {
+            let topology = kata_sys_util::numa::get_numa_nodes().context("get host numa nodes")?;
+            cpus.extend(cpus_of_nodes(&topology, &nodes)?);
+        }
+
+        Ok(Some(Self {
+            pinned: info.policy == VCPU_AFFINITY_PINNED,
+            cpus: cpus.into_iter().collect(),
+        }))
+    }
+
+    // cpus_of_vcpu returns the host CPUs of the vCPU, the vCPUs outnumbering
+    // the CPUs share them in turn.
+    fn cpus_of_vcpu(&self, vcpu: u32) -> Vec<u32> {
+        if self.pinned && !self.cpus.is_empty() {
+            vec![self.cpus[vcpu as usize % self.cpus.len()]]
+        } else {
+            self.cpus.clone()
+        }
+    }
+
+    /// Set the affinity of the vCPU threads of the VM.
+    pub(crate) async fn apply(&self, hypervisor: &dyn Hypervisor) -> Result<()> {
+        let tids = hypervisor
+            .get_thread_ids()
+            .await
+            .context("get vcpu thread ids")?;
+        for (vcpu, tid) in tids.vcpus.iter() {
+            let cpus = self.cpus_of_vcpu(*vcpu);
+            info!(sl!(), "run vcpu {} thread {} on cpus {:?}", vcpu, tid, cpus);
+
+            let mut cpu_set = sched::CpuSet::new();
+            for cpu in cpus {
+                cpu_set.set(cpu as usize)?;
+            }
+            sched::sched_setaffinity(Pid::from_raw(*tid as i32), &cpu_set)
+                .context(format!("set affinity of vcpu {}", vcpu))?;
+        }
+
+        Ok(())
+    }
+}
+
+// cpus_of_nodes returns the host CPUs of the NUMA nodes, from the map of the
+// host CPUs to their nodes.
+fn cpus_of_nodes(topology: &HashMap<u32, u32>, nodes: &[u32]) -> Result<Vec<u32>> {
+    let mut cpus = vec![];
+    for node in nodes {
+        let mut node_cpus: Vec<u32> = topology
+            .iter()
+            .filter(|(_, n)| *n == node)
+            .map(|(cpu, _)| *cpu)
+            .collect();
+        if node_cpus.is_empty() {
+            return Err(anyhow!("no cpu on host numa node {}", node));
+        }
+        cpus.append(&mut node_cpus);
+    }
+    cpus.sort_unstable();
+
+    Ok(cpus)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use kata_types::config::VCPU_AFFINITY_SHARED;
+
+    #[test]
+    fn test_cpus_of_nodes() {
+        let topology: HashMap<u32, u32> =
+            vec![(0, 0), (1, 0), (2, 1), (3, 1)].into_iter().collect();
+
+        assert_eq!(cpus_of_nodes(&topology, &[1]).unwrap(), vec![2, 3]);
+        assert_eq!(cpus_of_nodes(&topology, &[1, 0]).unwrap(), vec![0, 1, 2, 3]);
+        assert!(cpus_of_nodes(&topology, &[2]).is_err());
+    }
+
+    #[test]
+    fn test_cpus_of_vcpu() {
+        let mut info = VcpuAffinityInfo::default();
+        assert!(VcpuAffinity::new(&info).unwrap().is_none());
+
+        info.policy = VCPU_AFFINITY_PINNED.to_string();
+        info.cpus = "4-5".to_string();
+        let affinity = VcpuAffinity::new(&info).unwrap().unwrap();
+        assert_eq!(affinity.cpus_of_vcpu(0), vec![4]);
+        assert_eq!(affinity.cpus_of_vcpu(1), vec![5]);
+        assert_eq!(affinity.cpus_of_vcpu(2), vec![4]);
+
+        info.policy = VCPU_AFFINITY_SHARED.to_string();
+        let affinity = VcpuAffinity::new(&info).unwrap().unwrap();
+        assert_eq!(affinity.cpus_of_vcpu(3), vec![4, 5]);
+    }
+}
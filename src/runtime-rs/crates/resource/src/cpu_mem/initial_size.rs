@@ -13,6 +13,9 @@ use kata_types::{
     cpu::LinuxContainerCpuResources, k8s::container_type,
 };
 
+// the cpu shares of one CPU, the default shares of the cgroups
+const CPU_SHARES_PER_CPU: u64 = 1024;
+
 // initial resource that InitialSizeManager needs, this is the spec for the
 // sandbox/container's workload
 #[derive(Clone, Copy, Debug)]
@@ -31,11 +34,12 @@ impl TryFrom<&oci::Spec> for InitialSize {
             // podsandbox, from annotation
             ContainerType::PodSandbox => {
                 let annotation = Annotation::new(spec.annotations.clone());
-                let (period, quota, memory) =
+                let (period, quota, shares, memory) =
                     get_sizing_info(annotation).context("failed to get sizing info")?;
                 let cpu = oci::LinuxCpu {
                     period: Some(period),
                     quota: Some(quota),
+                    shares: Some(shares),
                     ..Default::default()
                 };
                 // although it may not be actually a linux container, we are only using the calculation inside
@@ -102,7 +106,16 @@ impl InitialSizeManager {
             .context("failed to get hypervisor config")?;
 
         if self.resource.vcpu > 0 {
-            hv.cpu_info.default_vcpus = self.resource.vcpu as i32
+            // capped like the default_vcpus of the configuration file, the vcpus
+            // beyond can't be hot plugged later either
+            let max_vcpus = hv.cpu_info.default_maxvcpus;
+            if self.resource.vcpu > max_vcpus {
+                warn!(
+                    sl!(),
+                    "initial vcpus {} capped by default_maxvcpus {}", self.resource.vcpu, max_vcpus
+                );
+            }
+            hv.cpu_info.default_vcpus = self.resource.vcpu.min(max_vcpus) as i32
         }
         if self.resource.mem_mb > 0 {
             // since the memory overhead introduced by kata-agent and system components
@@ -111,14 +124,29 @@ impl InitialSizeManager {
             // (if we override the default_memory here, and user apllications still
             // use memory as they orignally expected, it would be easy to OOM.)
             hv.memory_info.default_memory += self.resource.mem_mb;
+            let max_memory = hv.memory_info.default_maxmemory;
+            if max_memory != 0 && hv.memory_info.default_memory > max_memory {
+                warn!(
+                    sl!(),
+                    "initial memory {} MiB capped by default_maxmemory {} MiB",
+                    hv.memory_info.default_memory,
+                    max_memory
+                );
+                hv.memory_info.default_memory = max_memory;
+            }
         }
         Ok(())
     }
 }
 
+// the vcpus are sized by the cpu quota, or by the cpu shares (the cpu requests of
+// kubernetes) when the quota is unconstrained, the shares of one CPU or less keep the
+// default vcpus.
 fn get_nr_vcpu(resource: &LinuxContainerCpuResources) -> u32 {
     if let Some(v) = resource.get_vcpus() {
         v as u32
+    } else if resource.shares() > CPU_SHARES_PER_CPU {
+        (resource.shares().saturating_add(CPU_SHARES_PER_CPU - 1) / CPU_SHARES_PER_CPU) as u32
     } else {
         0
     }
@@ -133,15 +161,16 @@ fn convert_memory_to_mb(memory_in_byte: i64) -> u32 {
 }
 
 // from the upper layer runtime's annotation (e.g. crio, k8s), get the *cpu quota,
-// cpu period and memory limit* for a sandbox/container
-fn get_sizing_info(annotation: Annotation) -> Result<(u64, i64, i64)> {
+// cpu period, cpu shares and memory limit* for a sandbox/container
+fn get_sizing_info(annotation: Annotation) -> Result<(u64, i64, u64, i64)> {
     // since we are *adding* our result to the config, a value of 0 will cause no change
     // and if the annotation is not assigned (but static resource management is), we will
     // log a *warning* to fill that with zero value
     let period = annotation.get_sandbox_cpu_period();
     let quota = annotation.get_sandbox_cpu_quota();
+    let shares = annotation.get_sandbox_cpu_shares();
     let memory = annotation.get_sandbox_mem();
-    Ok((period, quota, memory))
+    Ok((period, quota, shares, memory))
 }
 
 #[cfg(test)]
@@ -154,6 +183,7 @@ mod tests {
     struct InputData {
         period: Option<u64>,
         quota: Option<i64>,
+        shares: Option<u64>,
         memory: Option<i64>,
     }
 
@@ -171,6 +201,7 @@ mod tests {
                 input: InputData {
                     period: None,
                     quota: None,
+                    shares: None,
                     memory: None,
                 },
                 result: InitialSize { vcpu: 0, mem_mb: 0 },
@@ -181,6 +212,7 @@ mod tests {
                 input: InputData {
                     period: Some(100_000),
                     quota: Some(220_000),
+                    shares: Some(2253),
                     memory: Some(1024 * 1024 * 512),
                 },
                 result: InitialSize {
@@ -188,6 +220,27 @@ mod tests {
                     mem_mb: 512,
                 },
             },
+            TestData {
+                desc: "cpu requests without cpu limit",
+                // 1500 mCPU requested, round up to 2 vcpus
+                input: InputData {
+                    period: None,
+                    quota: None,
+                    shares: Some(1536),
+                    memory: None,
+                },
+                result: InitialSize { vcpu: 2, mem_mb: 0 },
+            },
+            TestData {
+                desc: "cpu requests of one cpu or less",
+                input: InputData {
+                    period: None,
+                    quota: None,
+                    shares: Some(1024),
+                    memory: None,
+                },
+                result: InitialSize { vcpu: 0, mem_mb: 0 },
+            },
         ]
         .to_vec()
     }
@@ -212,6 +265,10 @@ mod tests {
                         cri_containerd::SANDBOX_CPU_QUOTA_KEY.to_string(),
                         d.input.quota.map_or(String::new(), |v| format!("{}", v)),
                     ), // CPU quota
+                    (
+                        cri_containerd::SANDBOX_CPU_SHARE_KEY.to_string(),
+                        d.input.shares.map_or(String::new(), |v| format!("{}", v)),
+                    ), // CPU shares
                     (
                         cri_containerd::SANDBOX_MEM_KEY.to_string(),
                         d.input.memory.map_or(String::new(), |v| format!("{}", v)),
@@ -258,6 +315,7 @@ mod tests {
                         cpu: Some(oci::LinuxCpu {
                             period: d.input.period,
                             quota: d.input.quota,
+                            shares: d.input.shares,
                             ..Default::default()
                         }),
                         memory: Some(oci::LinuxMemory {
@@ -292,4 +350,39 @@ mod tests {
             );
         }
     }
+
+    #[test]
+    fn test_setup_config() {
+        let mut config = TomlConfig::default();
+        config.runtime.hypervisor_name = "dragonball".to_string();
+        let mut hv = kata_types::config::hypervisor::Hypervisor::default();
+        hv.cpu_info.default_vcpus = 1;
+        hv.cpu_info.default_maxvcpus = 4;
+        hv.memory_info.default_memory = 256;
+        hv.memory_info.default_maxmemory = 1024;
+        config.hypervisor.insert("dragonball".to_string(), hv);
+
+        let manager = InitialSizeManager {
+            resource: InitialSize {
+                vcpu: 3,
+                mem_mb: 512,
+            },
+        };
+        manager.setup_config(&mut config).unwrap();
+        let hv = &config.hypervisor["dragonball"];
+        assert_eq!(hv.cpu_info.default_vcpus, 3);
+        assert_eq!(hv.memory_info.default_memory, 768);
+
+        // capped by the maximum vcpus and memory of the VM
+        let manager = InitialSizeManager {
+            resource: InitialSize {
+                vcpu: 8,
+                mem_mb: 2048,
+            },
+        };
+        manager.setup_config(&mut config).unwrap();
+        let hv = &config.hypervisor["dragonball"];
+        assert_eq!(hv.cpu_info.default_vcpus, 4);
+        assert_eq!(hv.memory_info.default_memory, 1024);
+    }
 }
@@ -0,0 +1,211 @@
+// Copyright (c) 2023 Kata Containers community
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+use std::{collections::HashMap, sync::Arc};
+
+use agent::{Agent, OnlineCPUMemRequest};
+use anyhow::{anyhow, Context, Result};
+use hypervisor::Hypervisor;
+use kata_types::config::TomlConfig;
+use oci::LinuxMemory;
+use tokio::sync::RwLock;
+
+use crate::ResourceUpdateOp;
+
+const MIB: i64 = 1 << 20;
+
+#[derive(Default, Debug, Clone)]
+pub struct MemResource {
+    /// Current memory size of the VM in MiB
+    pub(crate) current_mem_mb: Arc<RwLock<u32>>,
+
+    /// Memory in MiB kept in the guest on top of the memory limits of the containers
+    pub(crate) headroom_mb: u32,
+
+    /// Memory limit in MiB of each container, the containers without a limit aren't kept
+    pub(crate) container_mem_limits: Arc<RwLock<HashMap<String, u32>>>,
+}
+
+impl MemResource {
+    pub fn new(config: Arc<TomlConfig>) -> Result<Self> {
+        let hypervisor_name = config.runtime.hypervisor_name.clone();
+        let hypervisor_config = config
+            .hypervisor
+            .get(&hypervisor_name)
+            .context(format!("failed to get hypervisor {}", hypervisor_name))?;
+        let default_mem_mb = hypervisor_config.memory_info.default_memory;
+        let headroom_mb = match config.runtime.memory_headroom_mb {
+            0 => default_mem_mb,
+            headroom_mb => headroom_mb,
+        };
+        Ok(Self {
+            current_mem_mb: Arc::new(RwLock::new(default_mem_mb)),
+            headroom_mb,
+            container_mem_limits: Arc::new(RwLock::new(HashMap::new())),
+        })
+    }
+
+    // the VM isn't resized right away, but by the next reconcile
+    pub(crate) async fn update_container_mem_limit(
+        &self,
+        cid: &str,
+        linux_memory: Option<&LinuxMemory>,
+        op: ResourceUpdateOp,
+    ) {
+        let limit_mb = linux_memory
+            .and_then(|m| m.limit)
+            .filter(|limit| *limit > 0)
+            .map(|limit| ((limit + MIB - 1) / MIB) as u32);
+
+        let mut limits = self.container_mem_limits.write().await;
+        match (op, limit_mb) {
+            (ResourceUpdateOp::Add, Some(limit_mb))
+            | (ResourceUpdateOp::Update, Some(limit_mb)) => {
+                limits.insert(cid.to_owned(), limit_mb);
+            }
+            // the limit is removed by the update too
+            (ResourceUpdateOp::Add, None)
+            | (ResourceUpdateOp::Update, None)
+            | (ResourceUpdateOp::Del, _) => {
+                limits.remove(cid);
+            }
+        }
+    }
+
+    // the memory the containers need: the sum of their limits plus the headroom
+    async fn calc_mem_required(&self) -> u32 {
+        let limits = self.container_mem_limits.read().await;
+        limits.values().sum::<u32>() + self.headroom_mb
+    }
+
+    /// Grow the memory of the VM right away to what the containers need, for the memory limit of
+    /// a container raised in place, failing if the VM can't hold it. The VM isn't shrunk here but
+    /// by the reconcile, once the lowered limits are applied in the guest.
+    pub(crate) async fn grow(&self, hypervisor: &dyn Hypervisor, agent: &dyn Agent) -> Result<()> {
+        let mut current_mem_mb = self.current_mem_mb.write().await;
+        let mem_required = self.calc_mem_required().await;
+        if mem_required <= *current_mem_mb {
+            return Ok(());
+        }
+
+        let new_mem_mb = hypervisor
+            .resize_memory(mem_required)
+            .await
+            .context("resize memory")?;
+        info!(
+            sl!(),
+            "grew memory from {} MiB to {} MiB, {} MiB required",
+            *current_mem_mb,
+            new_mem_mb,
+            mem_required
+        );
+        if new_mem_mb > *current_mem_mb {
+            online_memory(agent).await?;
+        }
+        *current_mem_mb = new_mem_mb;
+        if new_mem_mb < mem_required {
+            return Err(anyhow!(
+                "memory of the VM grown to {} MiB only, {} MiB required",
+                new_mem_mb,
+                mem_required
+            ));
+        }
+        Ok(())
+    }
+
+    /// Resize the memory of the VM to what the running containers need, so that it's grown when
+    /// the containers are added, and shrunk when they exit.
+    pub(crate) async fn reconcile(
+        &self,
+        hypervisor: &dyn Hypervisor,
+        agent: &dyn Agent,
+    ) -> Result<()> {
+        // the memory required is calculated with the lock held, so that the memory grown for a
+        // concurrent update isn't shrunk back to the one calculated before it
+        let mut current_mem_mb = self.current_mem_mb.write().await;
+        let mem_required = self.calc_mem_required().await;
+        if mem_required == *current_mem_mb {
+            return Ok(());
+        }
+
+        let new_mem_mb = hypervisor
+            .resize_memory(mem_required)
+            .await
+            .context("resize memory")?;
+        if new_mem_mb != *current_mem_mb {
+            info!(
+                sl!(),
+                "resized memory from {} MiB to {} MiB, {} MiB required",
+                *current_mem_mb,
+                new_mem_mb,
+                mem_required
+            );
+        }
+        if new_mem_mb > *current_mem_mb {
+            online_memory(agent).await?;
+        }
+        *current_mem_mb = new_mem_mb;
+        Ok(())
+    }
+}
+
+// online_memory onlines the memory hot plugged in the guest, the one plugged
+// by virtio-mem is onlined by the guest kernel already, but the one plugged
+// by ACPI may not be
+async fn online_memory(agent: &dyn Agent) -> Result<()> {
+    agent
+        .online_cpu_mem(OnlineCPUMemRequest {
+            wait: false,
+            nb_cpus: 0,
+            cpu_only: false,
+        })
+        .await
+        .context("online memory")?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn memory(limit: i64) -> LinuxMemory {
+        LinuxMemory {
+            limit: Some(limit),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_calc_mem_required() {
+        let mem = MemResource {
+            headroom_mb: 256,
+            ..Default::default()
+        };
+        assert_eq!(mem.calc_mem_required().await, 256);
+
+        mem.update_container_mem_limit("c1", Some(&memory(512 * MIB)), ResourceUpdateOp::Add)
+            .await;
+        // rounded up to MiB
+        mem.update_container_mem_limit("c2", Some(&memory(MIB + 1)), ResourceUpdateOp::Add)
+            .await;
+        // no limit
+        mem.update_container_mem_limit("c3", Some(&memory(-1)), ResourceUpdateOp::Add)
+            .await;
+        mem.update_container_mem_limit("c4", None, ResourceUpdateOp::Add)
+            .await;
+        assert_eq!(mem.calc_mem_required().await, 256 + 512 + 2);
+
+        mem.update_container_mem_limit("c1", Some(&memory(1024 * MIB)), ResourceUpdateOp::Update)
+            .await;
+        assert_eq!(mem.calc_mem_required().await, 256 + 1024 + 2);
+
+        // the memory is released when the containers exit
+        mem.update_container_mem_limit("c1", None, ResourceUpdateOp::Del)
+            .await;
+        mem.update_container_mem_limit("c2", Some(&memory(MIB + 1)), ResourceUpdateOp::Del)
+            .await;
+        assert_eq!(mem.calc_mem_required().await, 256);
+    }
+}
@@ -5,4 +5,6 @@
 //
 
 pub mod cpu;
+mod cpu_affinity;
 pub mod initial_size;
+pub mod mem;
@@ -0,0 +1,277 @@
+// Copyright (c) 2019-2022 Alibaba Cloud
+// Copyright (c) 2019-2022 Ant Group
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+//! Persistent guest image cache disk.
+//!
+//! In guest-pull mode the container images are pulled and unpacked inside the guest, so they
+//! are lost whenever the pod is restarted. The image cache attaches a host-side disk to the
+//! sandbox, keyed by the pod identity, which is reused by the next sandbox of the same pod.
+//! Disks which are not in use are evicted in LRU order to keep the cache under its size limit.
+
+use std::{
+    fs,
+    os::unix::fs::MetadataExt,
+    path::{Path, PathBuf},
+    process::Command,
+    time::SystemTime,
+};
+
+use agent::Storage;
+use anyhow::{anyhow, Context, Result};
+use hypervisor::{
+    device::{
+        device_manager::{do_handle_device, get_block_driver, DeviceManager},
+        DeviceConfig, DeviceType,
+    },
+    utils::block_in_place,
+    BlockConfig,
+};
+use kata_types::annotations::cri_containerd::{
+    SANDBOX_NAMESPACE_LABEL_KEY, SANDBOX_NAME_LABEL_KEY,
+};
+use tokio::sync::RwLock;
+
+/// Mount point of the image cache disk in guest.
+pub const GUEST_IMAGE_CACHE_MOUNT_POINT: &str = "/run/kata-containers/image-cache";
+
+const CACHE_DISK_SUFFIX: &str = ".img";
+const IN_USE_SUFFIX: &str = ".inuse";
+const CACHE_DISK_FS_TYPE: &str = "ext4";
+const MIB: u64 = 1024 * 1024;
+
+#[derive(Debug, Clone)]
+pub struct ImageCacheConfig {
+    /// Host directory to store the cache disks.
+    pub cache_dir: String,
+    /// Key of the cache disk, sandboxes with the same key share the same disk.
+    pub key: String,
+    /// Size of the cache disk in MiB.
+    pub disk_size_mb: u32,
+    /// Total size limit of the cache directory in MiB, zero means unlimited.
+    pub limit_mb: u64,
+}
+
+/// Generate the cache key for a sandbox.
+///
+/// A K8s pod is identified by its namespace and name, which are stable across pod restarts.
+/// Fall back to the sandbox id for sandboxes not managed by K8s, the cache is not reused then.
+pub fn cache_key_from_spec(sid: &str, spec: &oci::Spec) -> String {
+    let key = match (
+        spec.annotations.get(SANDBOX_NAMESPACE_LABEL_KEY),
+        spec.annotations.get(SANDBOX_NAME_LABEL_KEY),
+    ) {
+        (Some(ns), Some(name)) if !ns.is_empty() && !name.is_empty() => {
+            format!("{}_{}", ns, name)
+        }
+        _ => sid.to_string(),
+    };
+
+    key.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '.' || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+pub(crate) struct ImageCache {
+    config: ImageCacheConfig,
+    disk_path: PathBuf,
+    storage: Option<Storage>,
+}
+
+impl ImageCache {
+    /// Acquire the cache disk for the sandbox, the disk is created if it doesn't exist.
+    pub fn new(sid: &str, config: ImageCacheConfig) -> Result<Self> {
+        let cache_dir = Path::new(&config.cache_dir);
+        fs::create_dir_all(cache_dir)
+            .with_context(|| format!("create image cache dir {:?}", cache_dir))?;
+
+        let disk_path = cache_dir.join(format!("{}{}", config.key, CACHE_DISK_SUFFIX));
+        // mark the disk in use before eviction, so that it won't be evicted.
+        fs::write(in_use_path(&disk_path), sid).context("mark image cache disk in use")?;
+
+        if !disk_path.exists() {
+            let reserved = config.disk_size_mb as u64 * MIB;
+            evict(cache_dir, config.limit_mb * MIB, reserved).context("evict image cache")?;
+            // the disk is preallocated and formatted in place
+            if let Err(e) = block_in_place(|| create_disk(&disk_path, reserved)) {
+                let _ = fs::remove_file(&disk_path);
+                let _ = fs::remove_file(in_use_path(&disk_path));
+                return Err(e).context("create image cache disk");
+            }
+        }
+
+        info!(sl!(), "acquired image cache disk {:?}", &disk_path);
+        Ok(Self {
+            config,
+            disk_path,
+            storage: None,
+        })
+    }
+
+    /// Attach the cache disk to the VM, must be called before the VM is started.
+    pub async fn attach(&mut self, d: &RwLock<DeviceManager>) -> Result<()> {
+        let block_config = BlockConfig {
+            path_on_host: self.disk_path.display().to_string(),
+            driver_option: get_block_driver(d).await,
+            ..Default::default()
+        };
+        let device_info = do_handle_device(d, &DeviceConfig::BlockCfg(block_config))
+            .await
+            .context("do handle image cache device")?;
+
+        if let DeviceType::Block(device) = device_info {
+            self.storage = Some(Storage {
+                driver: device.config.driver_option,
+                source: device.config.virt_path,
+                fs_type: CACHE_DISK_FS_TYPE.to_string(),
+                mount_point: GUEST_IMAGE_CACHE_MOUNT_POINT.to_string(),
+                ..Default::default()
+            });
+            Ok(())
+        } else {
+            Err(anyhow!("unexpected device type for image cache disk"))
+        }
+    }
+
+    pub fn get_storage(&self) -> Option<Storage> {
+        self.storage.clone()
+    }
+
+    /// Release the cache disk, it becomes a candidate of eviction.
+    pub fn release(&self) -> Result<()> {
+        let in_use = in_use_path(&self.disk_path);
+        if in_use.exists() {
+            fs::remove_file(&in_use).context("remove image cache in use mark")?;
+        }
+
+        evict(
+            Path::new(&self.config.cache_dir),
+            self.config.limit_mb * MIB,
+            0,
+        )
+        .context("evict image cache")
+    }
+}
+
+fn in_use_path(disk_path: &Path) -> PathBuf {
+    let mut p = disk_path.as_os_str().to_owned();
+    p.push(IN_USE_SUFFIX);
+    PathBuf::from(p)
+}
+
+fn create_disk(disk_path: &Path, size: u64) -> Result<()> {
+    let f = fs::File::create(disk_path).context("create disk file")?;
+    f.set_len(size).context("set disk size")?;
+
+    let output = Command::new(format!("mkfs.{}", CACHE_DISK_FS_TYPE))
+        .args(["-q", "-F"])
+        .arg(disk_path)
+        .output()
+        .context("run mkfs")?;
+    if !output.status.success() {
+        return Err(anyhow!(
+            "mkfs image cache disk failed: {}",
+            String::from_utf8_lossy(&output.stderr)
+        ));
+    }
+
+    Ok(())
+}
+
+/// Evict the least recently used disks which are not in use, until the space used by the
+/// cache dir plus `reserved` fits in `limit`. Zero `limit` means unlimited.
+fn evict(cache_dir: &Path, limit: u64, reserved: u64) -> Result<()> {
+    if limit == 0 {
+        return Ok(());
+    }
+
+    let mut used = reserved;
+    let mut candidates: Vec<(SystemTime, u64, PathBuf)> = vec![];
+    for entry in fs::read_dir(cache_dir).context("read image cache dir")? {
+        let path = entry?.path();
+        if path.extension().and_then(|e| e.to_str()) != Some(&CACHE_DISK_SUFFIX[1..]) {
+            continue;
+        }
+
+        let meta = fs::metadata(&path).context("get disk metadata")?;
+        // disks are sparse files, count the allocated space only.
+        let size = meta.blocks() * 512;
+        used += size;
+        if !in_use_path(&path).exists() {
+            candidates.push((meta.modified()?, size, path));
+        }
+    }
+
+    candidates.sort_by(|a, b| a.0.cmp(&b.0));
+    for (_, size, path) in candidates {
+        if used <= limit {
+            break;
+        }
+        info!(sl!(), "evict image cache disk {:?}", &path);
+        fs::remove_file(&path).with_context(|| format!("remove disk {:?}", &path))?;
+        used -= size;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{io::Write, thread, time::Duration};
+
+    fn write_disk(dir: &Path, name: &str, size: usize) -> PathBuf {
+        let path = dir.join(format!("{}{}", name, CACHE_DISK_SUFFIX));
+        let mut f = fs::File::create(&path).unwrap();
+        f.write_all(&vec![1u8; size]).unwrap();
+        f.sync_all().unwrap();
+        path
+    }
+
+    #[test]
+    fn test_cache_key_from_spec() {
+        let mut spec = oci::Spec::default();
+        assert_eq!(cache_key_from_spec("sid", &spec), "sid");
+
+        spec.annotations
+            .insert(SANDBOX_NAMESPACE_LABEL_KEY.to_string(), "default".to_string());
+        assert_eq!(cache_key_from_spec("sid", &spec), "sid");
+
+        spec.annotations
+            .insert(SANDBOX_NAME_LABEL_KEY.to_string(), "nginx/1".to_string());
+        assert_eq!(cache_key_from_spec("sid", &spec), "default_nginx_1");
+    }
+
+    #[test]
+    fn test_evict() {
+        let dir = tempfile::tempdir().unwrap();
+        let old = write_disk(dir.path(), "old", 64 * 1024);
+        thread::sleep(Duration::from_millis(20));
+        let in_use = write_disk(dir.path(), "in-use", 64 * 1024);
+        fs::write(in_use_path(&in_use), "sid").unwrap();
+        thread::sleep(Duration::from_millis(20));
+        let new = write_disk(dir.path(), "new", 64 * 1024);
+
+        // unlimited
+        evict(dir.path(), 0, u64::MAX).unwrap();
+        assert!(old.exists() && in_use.exists() && new.exists());
+
+        // the least recently used disk not in use is evicted first
+        evict(dir.path(), 160 * 1024, 0).unwrap();
+        assert!(!old.exists());
+        assert!(in_use.exists() && new.exists());
+
+        // disks in use are never evicted
+        evict(dir.path(), 1, 0).unwrap();
+        assert!(in_use.exists());
+        assert!(!new.exists());
+    }
+}
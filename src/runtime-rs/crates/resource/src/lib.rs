@@ -4,24 +4,25 @@
 // SPDX-License-Identifier: Apache-2.0
 //
 
-#[macro_use]
-extern crate lazy_static;
-
 #[macro_use]
 extern crate slog;
 
 logging::logger_with_subsystem!(sl, "resource");
 
 pub mod cgroups;
+pub mod image_cache;
 pub mod manager;
 mod manager_inner;
 pub mod network;
+pub mod rdt;
 pub mod resource_persist;
 use hypervisor::{BlockConfig, HybridVsockConfig};
+use image_cache::ImageCacheConfig;
 use network::NetworkConfig;
 pub mod rootfs;
 pub mod share_fs;
 pub mod volume;
+pub mod vsock_port;
 pub use manager::ResourceManager;
 pub mod cpu_mem;
 
@@ -33,6 +34,7 @@ pub enum ResourceConfig {
     ShareFs(SharedFsInfo),
     VmRootfs(BlockConfig),
     HybridVsock(HybridVsockConfig),
+    ImageCache(ImageCacheConfig),
 }
 
 #[derive(Debug, Clone, Copy, PartialEq)]
@@ -65,6 +65,21 @@ impl ResourceManager {
         inner.get_device_manager()
     }
 
+    pub async fn vsock_port(&self, owner: &str) -> Option<u32> {
+        let inner = self.inner.read().await;
+        inner.vsock_port(owner)
+    }
+
+    pub async fn allocate_vsock_port(&self, owner: &str) -> Result<u32> {
+        let mut inner = self.inner.write().await;
+        inner.allocate_vsock_port(owner)
+    }
+
+    pub async fn release_vsock_port(&self, owner: &str) {
+        let mut inner = self.inner.write().await;
+        inner.release_vsock_port(owner)
+    }
+
     #[instrument]
     pub async fn prepare_before_start_vm(&self, device_configs: Vec<ResourceConfig>) -> Result<()> {
         let mut inner = self.inner.write().await;
@@ -82,6 +97,11 @@ impl ResourceManager {
         inner.setup_after_start_vm().await
     }
 
+    pub async fn check_network_ready(&self) -> Result<()> {
+        let inner = self.inner.read().await;
+        inner.check_network_ready().await
+    }
+
     pub async fn get_storage_for_sandbox(&self) -> Result<Vec<Storage>> {
         let inner = self.inner.read().await;
         inner.get_storage_for_sandbox().await
@@ -100,6 +120,11 @@ impl ResourceManager {
             .await
     }
 
+    pub async fn handler_builtin_pause_rootfs(&self) -> Result<Arc<dyn Rootfs>> {
+        let inner = self.inner.read().await;
+        inner.handler_builtin_pause_rootfs().await
+    }
+
     pub async fn handler_volumes(
         &self,
         cid: &str,
@@ -129,6 +154,26 @@ impl ResourceManager {
         inner.update_linux_resource(cid, linux_resources, op).await
     }
 
+    pub async fn reconcile_memory(&self) -> Result<()> {
+        let inner = self.inner.read().await;
+        inner.reconcile_memory().await
+    }
+
+    pub async fn cleanup_network(&self) -> Result<()> {
+        let inner = self.inner.read().await;
+        inner.cleanup_network().await
+    }
+
+    pub async fn stop_share_fs(&self) -> Result<()> {
+        let inner = self.inner.read().await;
+        inner.stop_share_fs().await
+    }
+
+    pub async fn kill_share_fs(&self) -> Result<()> {
+        let inner = self.inner.read().await;
+        inner.kill_share_fs().await
+    }
+
     pub async fn cleanup(&self) -> Result<()> {
         let inner = self.inner.read().await;
         inner.cleanup().await
@@ -4,7 +4,7 @@
 // SPDX-License-Identifier: Apache-2.0
 //
 
-use std::{sync::Arc, thread};
+use std::{sync::Arc, thread, time::Duration};
 
 use agent::{types::Device, Agent, Storage};
 use anyhow::{anyhow, Context, Ok, Result};
@@ -17,21 +17,25 @@ use hypervisor::{
     },
     BlockConfig, Hypervisor, VfioConfig,
 };
-use kata_types::config::TomlConfig;
+use kata_types::config::{Agent, TomlConfig};
 use kata_types::mount::Mount;
-use oci::{Linux, LinuxCpu, LinuxResources};
+use logging::audit::{audit, AuditRecord};
+use oci::{Linux, LinuxCpu, LinuxMemory, LinuxResources};
 use persist::sandbox_persist::Persist;
 use tokio::{runtime, sync::RwLock};
 
 use crate::{
     cgroups::{CgroupArgs, CgroupsResource},
-    cpu_mem::cpu::CpuResource,
+    cpu_mem::{cpu::CpuResource, mem::MemResource},
+    image_cache::ImageCache,
     manager::ManagerArgs,
-    network::{self, Network, NetworkConfig},
+    network::{self, NeighborSync, Network, NetworkConfig},
+    rdt::RdtResource,
     resource_persist::ResourceState,
     rootfs::{RootFsResource, Rootfs},
     share_fs::{self, sandbox_bind_mounts::SandboxBindMounts, ShareFs},
     volume::{Volume, VolumeResource},
+    vsock_port::VsockPortAllocator,
     ResourceConfig, ResourceUpdateOp,
 };
 
@@ -42,12 +46,17 @@ pub(crate) struct ResourceManagerInner {
     hypervisor: Arc<dyn Hypervisor>,
     device_manager: Arc<RwLock<DeviceManager>>,
     network: Option<Arc<dyn Network>>,
+    neighbor_sync: Option<NeighborSync>,
     share_fs: Option<Arc<dyn ShareFs>>,
+    image_cache: Option<ImageCache>,
+    vsock_ports: VsockPortAllocator,
 
     pub rootfs_resource: RootFsResource,
     pub volume_resource: VolumeResource,
     pub cgroups_resource: CgroupsResource,
     pub cpu_resource: CpuResource,
+    pub mem_resource: MemResource,
+    pub rdt_resource: RdtResource,
 }
 
 impl ResourceManagerInner {
@@ -64,6 +73,15 @@ impl ResourceManagerInner {
 
         let cgroups_resource = CgroupsResource::new(sid, &toml_config)?;
         let cpu_resource = CpuResource::new(toml_config.clone())?;
+        let mem_resource = MemResource::new(toml_config.clone())?;
+        let rdt_resource = RdtResource::new(sid, &toml_config)?;
+        let agent_config = toml_config
+            .agent
+            .get(&toml_config.runtime.agent_name)
+            .cloned()
+            .unwrap_or_else(Agent::default);
+        let vsock_ports =
+            VsockPortAllocator::new(&agent_config).context("failed to reserve vsock ports")?;
         Ok(Self {
             sid: sid.to_string(),
             toml_config,
@@ -71,11 +89,16 @@ impl ResourceManagerInner {
             hypervisor,
             device_manager: Arc::new(RwLock::new(dev_manager)),
             network: None,
+            neighbor_sync: None,
             share_fs: None,
+            image_cache: None,
+            vsock_ports,
             rootfs_resource: RootFsResource::new(),
             volume_resource: VolumeResource::new(),
             cgroups_resource,
             cpu_resource,
+            mem_resource,
+            rdt_resource,
         })
     }
 
@@ -87,6 +110,18 @@ impl ResourceManagerInner {
         self.device_manager.clone()
     }
 
+    pub fn vsock_port(&self, owner: &str) -> Option<u32> {
+        self.vsock_ports.port(owner)
+    }
+
+    pub fn allocate_vsock_port(&mut self, owner: &str) -> Result<u32> {
+        self.vsock_ports.allocate(owner)
+    }
+
+    pub fn release_vsock_port(&mut self, owner: &str) {
+        self.vsock_ports.release(owner)
+    }
+
     pub async fn prepare_before_start_vm(
         &mut self,
         device_configs: Vec<ResourceConfig>,
@@ -131,6 +166,15 @@ impl ResourceManagerInner {
                         .await
                         .context("do handle hybrid-vsock device failed.")?;
                 }
+                ResourceConfig::ImageCache(c) => {
+                    let mut image_cache =
+                        ImageCache::new(&self.sid, c).context("new image cache")?;
+                    image_cache
+                        .attach(&self.device_manager)
+                        .await
+                        .context("attach image cache")?;
+                    self.image_cache = Some(image_cache);
+                }
             };
         }
 
@@ -197,11 +241,13 @@ impl ResourceManagerInner {
 
     async fn handle_routes(&self, network: &dyn Network) -> Result<()> {
         let routes = network.routes().await.context("routes")?;
-        if !routes.is_empty() {
-            info!(sl!(), "update routes {:?}", routes);
+        let rules = network.rules().await.context("rules")?;
+        if !routes.is_empty() || !rules.is_empty() {
+            info!(sl!(), "update routes {:?} rules {:?}", routes, rules);
             self.agent
                 .update_routes(agent::UpdateRoutesRequest {
                     route: Some(agent::Routes { routes }),
+                    rules,
                 })
                 .await
                 .context("update routes")?;
@@ -227,15 +273,59 @@ impl ResourceManagerInner {
                 .context("handle neighbors")?;
             self.handle_routes(network).await.context("handle routes")?;
         }
+
+        let interval = self.toml_config.runtime.neighbor_resync_interval_secs;
+        if interval > 0 {
+            if let Some(network) = self.network.as_ref() {
+                let synced = network.neighs().await.context("neighs")?;
+                self.neighbor_sync = Some(NeighborSync::start(
+                    network.clone(),
+                    self.agent.clone(),
+                    Duration::from_secs(interval),
+                    synced,
+                ));
+            }
+        }
+
+        self.cpu_resource
+            .setup_vcpu_rt(self.hypervisor.as_ref())
+            .await
+            .context("setup vcpu rt")?;
+        self.cpu_resource
+            .setup_vcpu_affinity(self.hypervisor.as_ref())
+            .await
+            .context("setup vcpu affinity")?;
+        self.rdt_resource
+            .setup(self.hypervisor.as_ref())
+            .await
+            .context("setup rdt")?;
         Ok(())
     }
 
+    pub async fn check_network_ready(&self) -> Result<()> {
+        let network = match self.network.as_ref() {
+            Some(network) => network,
+            None => return Ok(()),
+        };
+
+        let expected = network.interfaces().await.context("get interfaces")?;
+        let guest = self
+            .agent
+            .list_interfaces(agent::Empty::new())
+            .await
+            .context("list guest interfaces")?;
+        network::check_guest_interfaces(&expected, &guest.interfaces)
+    }
+
     pub async fn get_storage_for_sandbox(&self) -> Result<Vec<Storage>> {
         let mut storages = vec![];
         if let Some(d) = self.share_fs.as_ref() {
             let mut s = d.get_storages().await.context("get storage")?;
             storages.append(&mut s);
         }
+        if let Some(storage) = self.image_cache.as_ref().and_then(|c| c.get_storage()) {
+            storages.push(storage);
+        }
         Ok(storages)
     }
 
@@ -260,6 +350,10 @@ impl ResourceManagerInner {
             .await
     }
 
+    pub async fn handler_builtin_pause_rootfs(&self) -> Result<Arc<dyn Rootfs>> {
+        self.rootfs_resource.handler_builtin_pause_rootfs().await
+    }
+
     pub async fn handler_volumes(
         &self,
         cid: &str,
@@ -273,11 +367,12 @@ impl ResourceManagerInner {
                 self.device_manager.as_ref(),
                 &self.sid,
                 self.agent.clone(),
+                &self.toml_config.runtime,
             )
             .await
     }
 
-    pub async fn handler_devices(&self, _cid: &str, linux: &Linux) -> Result<Vec<Device>> {
+    pub async fn handler_devices(&self, cid: &str, linux: &Linux) -> Result<Vec<Device>> {
         let mut devices = vec![];
         for d in linux.devices.iter() {
             match d.r#type.as_str() {
@@ -315,6 +410,7 @@ impl ResourceManagerInner {
                         continue;
                     }
 
+                    let target = format!("{} of container {}", host_path, cid);
                     let dev_info = DeviceConfig::VfioCfg(VfioConfig {
                         host_path,
                         dev_type: "c".to_string(),
@@ -322,9 +418,13 @@ impl ResourceManagerInner {
                         ..Default::default()
                     });
 
-                    let device_info = do_handle_device(&self.device_manager.clone(), &dev_info)
-                        .await
-                        .context("do handle device")?;
+                    // the devices are requested by the container spec through the task API
+                    let result = do_handle_device(&self.device_manager.clone(), &dev_info).await;
+                    audit(
+                        &sl!(),
+                        &AuditRecord::new("device_passthrough", "task-api", &target, &result),
+                    );
+                    let device_info = result.context("do handle device")?;
 
                     // vfio mode: vfio-pci and vfio-pci-gk for x86_64
                     // - vfio-pci, devices appear as VFIO character devices under /dev/vfio in container.
@@ -371,13 +471,53 @@ impl ResourceManagerInner {
         }
     }
 
+    /// Restore the pod netns, after the VM is stopped.
+    pub async fn cleanup_network(&self) -> Result<()> {
+        if let Some(neighbor_sync) = &self.neighbor_sync {
+            neighbor_sync.stop();
+        }
+
+        if let Some(network) = &self.network {
+            network.cleanup().await.context("cleanup network")?;
+        }
+        Ok(())
+    }
+
+    /// Stop the daemon serving the share fs, e.g. virtiofsd.
+    pub async fn stop_share_fs(&self) -> Result<()> {
+        if let Some(share_fs) = &self.share_fs {
+            share_fs
+                .stop_daemon()
+                .await
+                .context("stop share fs daemon")?;
+        }
+        Ok(())
+    }
+
+    /// Kill the daemon serving the share fs, if it doesn't stop in time.
+    pub async fn kill_share_fs(&self) -> Result<()> {
+        if let Some(share_fs) = &self.share_fs {
+            share_fs
+                .kill_daemon()
+                .await
+                .context("kill share fs daemon")?;
+        }
+        Ok(())
+    }
+
     pub async fn cleanup(&self) -> Result<()> {
+        if let Some(neighbor_sync) = &self.neighbor_sync {
+            neighbor_sync.stop();
+        }
+
         // clean up cgroup
         self.cgroups_resource
             .delete()
             .await
             .context("delete cgroup")?;
 
+        self.rdt_resource.delete().await.context("delete rdt")?;
+
         // cleanup sandbox bind mounts: setup = false
         self.handle_sandbox_bindmounts(false)
             .await
@@ -391,6 +531,11 @@ impl ResourceManagerInner {
                 .await
                 .context("failed to cleanup host path")?;
         }
+
+        // release the image cache disk, it could be reused by the next sandbox of the pod
+        if let Some(image_cache) = &self.image_cache {
+            image_cache.release().context("release image cache")?;
+        }
         // TODO cleanup other resources
         Ok(())
     }
@@ -419,6 +564,24 @@ impl ResourceManagerInner {
                     self.agent.as_ref(),
                 )
                 .await?;
+            // the hot plugged vcpus join the rdt group too
+            self.rdt_resource
+                .assign_vcpus(self.hypervisor.as_ref())
+                .await?;
+        }
+
+        let linux_memory =
+            || -> Option<&LinuxMemory> { linux_resources.as_ref()?.memory.as_ref() }();
+        self.mem_resource
+            .update_container_mem_limit(cid, linux_memory, op)
+            .await;
+        // the limit of a running container is raised in the guest next, it needs the memory now
+        if op == ResourceUpdateOp::Update && !self.toml_config.runtime.static_sandbox_resource_mgmt
+        {
+            self.mem_resource
+                .grow(self.hypervisor.as_ref(), self.agent.as_ref())
+                .await
+                .context("grow memory")?;
         }
 
         // we should firstly update the vcpus and mems, and then update the host cgroups
@@ -430,6 +593,12 @@ impl ResourceManagerInner {
         self.agent_linux_resources(linux_resources)
     }
 
+    pub async fn reconcile_memory(&self) -> Result<()> {
+        self.mem_resource
+            .reconcile(self.hypervisor.as_ref(), self.agent.as_ref())
+            .await
+    }
+
     fn agent_linux_resources(
         &self,
         linux_resources: Option<&LinuxResources>,
@@ -469,6 +638,8 @@ impl Persist for ResourceManagerInner {
         Ok(ResourceState {
             endpoint: endpoint_state,
             cgroup_state: Some(cgroup_state),
+            vsock_ports: Some(self.vsock_ports.clone()),
+            rdt_state: Some(self.rdt_resource.save()),
         })
     }
 
@@ -489,7 +660,15 @@ impl Persist for ResourceManagerInner {
                 DeviceManager::new(resource_args.hypervisor).await?,
             )),
             network: None,
+            neighbor_sync: None,
             share_fs: None,
+            image_cache: None,
+            // the shim of an old version doesn't persist the vsock ports, the
+            // agent ports of the default config were used then.
+            vsock_ports: match resource_state.vsock_ports {
+                Some(vsock_ports) => vsock_ports,
+                None => VsockPortAllocator::new(&Agent::default())?,
+            },
             rootfs_resource: RootFsResource::new(),
             volume_resource: VolumeResource::new(),
             cgroups_resource: CgroupsResource::restore(
@@ -499,6 +678,8 @@ impl Persist for ResourceManagerInner {
             .await?,
             toml_config: Arc::new(TomlConfig::default()),
             cpu_resource: CpuResource::default(),
+            mem_resource: MemResource::default(),
+            rdt_resource: RdtResource::restore(resource_state.rdt_state.unwrap_or_default()),
         })
     }
 }
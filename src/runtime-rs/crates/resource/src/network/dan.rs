@@ -160,6 +160,16 @@ impl Network for Dan {
         Ok(routes)
     }
 
+    async fn refresh_neighs(&self) -> Result<Vec<agent::ARPNeighbor>> {
+        // the neighbors are described by the DAN config statically
+        Ok(vec![])
+    }
+
+    async fn rules(&self) -> Result<Vec<agent::Rule>> {
+        // the policy routing rules aren't described by the DAN config
+        Ok(vec![])
+    }
+
     async fn neighs(&self) -> Result<Vec<agent::ARPNeighbor>> {
         let inner = self.inner.read().await;
         let mut neighs = vec![];
@@ -192,6 +202,11 @@ impl Network for Dan {
         }
         Ok(())
     }
+
+    async fn cleanup(&self) -> Result<()> {
+        // the taps are created and removed by the DAN provider
+        Ok(())
+    }
 }
 
 /// Directly attachable network config
@@ -282,6 +297,12 @@ pub(crate) struct Route {
     // Scope
     #[serde(default)]
     pub scope: u32,
+    // Metric, the lower the more preferred
+    #[serde(default)]
+    pub metric: u32,
+    // Routing table, 0 denotes the main table
+    #[serde(default)]
+    pub table: u32,
 }
 
 impl Route {
@@ -391,6 +412,8 @@ mod tests {
                     source: "172.18.0.1".to_owned(),
                     gateway: "172.18.31.1".to_owned(),
                     scope: 0,
+                    metric: 0,
+                    table: 0,
                 }],
                 neighbors: vec![ARPNeighbor {
                     ip_address: Some("192.168.0.3/16".to_owned()),
@@ -0,0 +1,239 @@
+// Copyright (c) 2023 Kata Containers community
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+use std::{
+    io::{self, Error},
+    sync::Arc,
+};
+
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use futures::stream::TryStreamExt;
+use scopeguard::defer;
+use tokio::sync::RwLock;
+
+use hypervisor::{
+    device::{
+        device_manager::{do_handle_device, DeviceManager},
+        driver::NetworkConfig,
+        DeviceConfig, DeviceType,
+    },
+    Hypervisor, NetworkDevice,
+};
+
+use super::{
+    endpoint_persist::{BridgeEndpointState, EndpointState},
+    Endpoint,
+};
+use crate::network::{network_model::tc_filter_model::fetch_index, utils, NetworkPair};
+
+// BridgeEndpoint is the endpoint of a bridge created by the CNI plugin in the
+// pod network namespace, with the veth of the pod attached to it. The tap is
+// attached to the bridge as another port, and the guest takes over the mac and
+// ip addresses of the bridge.
+#[derive(Debug)]
+pub struct BridgeEndpoint {
+    pub(crate) net_pair: NetworkPair,
+    pub(crate) d: Arc<RwLock<DeviceManager>>,
+}
+
+impl BridgeEndpoint {
+    pub async fn new(
+        d: &Arc<RwLock<DeviceManager>>,
+        handle: &rtnetlink::Handle,
+        name: &str,
+        idx: u32,
+        queues: usize,
+    ) -> Result<Self> {
+        // the traffic is forwarded by the bridge, no network model is needed
+        let net_pair = NetworkPair::new(handle, idx, name, "none", queues)
+            .await
+            .context("error creating new NetworkPair")?;
+
+        Ok(BridgeEndpoint {
+            net_pair,
+            d: d.clone(),
+        })
+    }
+
+    fn get_network_config(&self) -> Result<NetworkConfig> {
+        let iface = &self.net_pair.tap.tap_iface;
+        let guest_mac = utils::parse_mac(&iface.hard_addr).ok_or_else(|| {
+            Error::new(
+                io::ErrorKind::InvalidData,
+                format!("hard_addr {}", &iface.hard_addr),
+            )
+        })?;
+
+        Ok(NetworkConfig {
+            host_dev_name: iface.name.clone(),
+            virt_iface_name: self.net_pair.virt_iface.name.clone(),
+            guest_mac: Some(guest_mac),
+            ..Default::default()
+        })
+    }
+
+    async fn attach_tap_to_bridge(&self) -> Result<()> {
+        let (connection, handle, _) = rtnetlink::new_connection().context("new connection")?;
+        let thread_handler = tokio::spawn(connection);
+        defer!({
+            thread_handler.abort();
+        });
+
+        let bridge_index = fetch_index(&handle, &self.net_pair.virt_iface.name)
+            .await
+            .context("fetch bridge by index")?;
+        let tap_index = fetch_index(&handle, &self.net_pair.tap.tap_iface.name)
+            .await
+            .context("fetch tap by index")?;
+
+        // The addresses belong to the guest now, the bridge would answer the
+        // traffic of the guest itself otherwise.
+        let mut addr_msg_list = handle
+            .address()
+            .get()
+            .set_link_index_filter(bridge_index)
+            .execute();
+        while let Some(addr_msg) = addr_msg_list.try_next().await? {
+            handle
+                .address()
+                .del(addr_msg)
+                .execute()
+                .await
+                .context("del bridge address")?;
+        }
+
+        // The guest takes the mac address of the bridge, so the bridge uses
+        // the one of the tap to make the frames to the guest forwarded.
+        let bridge_mac =
+            utils::parse_mac(&self.net_pair.virt_iface.hard_addr).ok_or_else(|| {
+                Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("hard_addr {}", &self.net_pair.virt_iface.hard_addr),
+                )
+            })?;
+        handle
+            .link()
+            .set(bridge_index)
+            .address(bridge_mac.0.to_vec())
+            .execute()
+            .await
+            .context("set bridge mac address")?;
+
+        handle
+            .link()
+            .set(tap_index)
+            .master(bridge_index)
+            .execute()
+            .await
+            .context("attach tap to bridge")?;
+
+        Ok(())
+    }
+
+    // Give the addresses and the mac address back to the bridge, which are
+    // taken by the guest on attach.
+    async fn restore_bridge(&self) -> Result<()> {
+        let (connection, handle, _) = rtnetlink::new_connection().context("new connection")?;
+        let thread_handler = tokio::spawn(connection);
+        defer!({
+            thread_handler.abort();
+        });
+
+        let bridge_index = fetch_index(&handle, &self.net_pair.virt_iface.name)
+            .await
+            .context("fetch bridge by index")?;
+
+        // the original mac address of the bridge is saved to the tap
+        if let Some(bridge_mac) = utils::parse_mac(&self.net_pair.tap.tap_iface.hard_addr) {
+            handle
+                .link()
+                .set(bridge_index)
+                .address(bridge_mac.0.to_vec())
+                .execute()
+                .await
+                .context("restore bridge mac address")?;
+        }
+
+        for addr in &self.net_pair.virt_iface.addrs {
+            if let Err(e) = handle
+                .address()
+                .add(bridge_index, addr.addr, addr.perfix_len)
+                .execute()
+                .await
+            {
+                // the address may not have been removed yet
+                if !matches!(&e, rtnetlink::Error::NetlinkError(m) if m.code == -libc::EEXIST) {
+                    return Err(e).context(format!("restore bridge address {}", addr.addr));
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Endpoint for BridgeEndpoint {
+    async fn name(&self) -> String {
+        self.net_pair.virt_iface.name.clone()
+    }
+
+    async fn hardware_addr(&self) -> String {
+        self.net_pair.tap.tap_iface.hard_addr.clone()
+    }
+
+    async fn tap_name(&self) -> Option<String> {
+        Some(self.net_pair.tap.tap_iface.name.clone())
+    }
+
+    async fn attach(&self) -> Result<()> {
+        self.attach_tap_to_bridge()
+            .await
+            .context("attach tap to bridge")?;
+
+        let config = self.get_network_config().context("get network config")?;
+        do_handle_device(&self.d, &DeviceConfig::NetworkCfg(config))
+            .await
+            .context("do handle network Bridge endpoint device failed.")?;
+
+        Ok(())
+    }
+
+    async fn detach(&self, h: &dyn Hypervisor) -> Result<()> {
+        let config = self
+            .get_network_config()
+            .context("error getting network config")?;
+
+        h.remove_device(DeviceType::Network(NetworkDevice {
+            config,
+            ..Default::default()
+        }))
+        .await
+        .context("remove Bridge endpoint device by hypervisor failed.")?;
+
+        Ok(())
+    }
+
+    async fn teardown(&self) -> Result<()> {
+        // the tap has to be removed from the bridge before the bridge gets
+        // its mac address back
+        self.net_pair
+            .teardown()
+            .await
+            .context("teardown network pair")?;
+        self.restore_bridge().await.context("restore bridge")
+    }
+
+    async fn save(&self) -> Option<EndpointState> {
+        Some(EndpointState {
+            bridge_endpoint: Some(BridgeEndpointState {
+                if_name: self.net_pair.virt_iface.name.clone(),
+                network_qos: self.net_pair.network_qos,
+            }),
+            ..Default::default()
+        })
+    }
+}
@@ -39,6 +39,12 @@ pub struct IpVlanEndpointState {
     pub network_qos: bool,
 }
 
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct BridgeEndpointState {
+    pub if_name: String,
+    pub network_qos: bool,
+}
+
 #[derive(Serialize, Deserialize, Clone, Default)]
 pub struct TapEndpointState {
     pub if_name: String,
@@ -52,5 +58,6 @@ pub struct EndpointState {
     pub macvlan_endpoint: Option<MacvlanEndpointState>,
     pub vlan_endpoint: Option<VlanEndpointState>,
     pub tap_endpoint: Option<TapEndpointState>,
+    pub bridge_endpoint: Option<BridgeEndpointState>,
     // TODO : other endpoint
 }
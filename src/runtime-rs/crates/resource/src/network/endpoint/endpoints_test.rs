@@ -15,7 +15,7 @@ mod tests {
     use tokio::sync::RwLock;
 
     use crate::network::{
-        endpoint::{IPVlanEndpoint, MacVlanEndpoint, VlanEndpoint},
+        endpoint::{BridgeEndpoint, IPVlanEndpoint, MacVlanEndpoint, VlanEndpoint},
         network_model::{
             self,
             tc_filter_model::{fetch_index, TcFilterModel},
@@ -394,4 +394,50 @@ mod tests {
             }
         }
     }
+
+    // this unit test tests the integrity of BridgeEndpoint::new()
+    #[actix_rt::test]
+    async fn test_bridge_construction() {
+        let idx = 8193;
+        let bridge_name = String::from("kata_test_br");
+        let tap_iface_name = format!("tap{}_kata", idx); // create by kata
+        let dm = get_device_manager().await;
+        assert!(dm.is_ok());
+        let d = dm.unwrap();
+
+        if let Ok((conn, handle, _)) =
+            rtnetlink::new_connection().context("failed to create netlink connection")
+        {
+            let thread_handler = tokio::spawn(conn);
+            defer!({
+                thread_handler.abort();
+            });
+
+            // since BridgeEndpoint::new() needs an EXISTING bridge (which is created
+            // by the CNI plugin normally), we have to manually create a bridge.
+            if let Ok(()) = handle
+                .link()
+                .add()
+                .bridge(bridge_name.clone())
+                .execute()
+                .await
+                .context("failed to create manual bridge")
+            {
+                if let Ok(result) = BridgeEndpoint::new(&d, &handle, &bridge_name, idx, 1)
+                    .await
+                    .context("failed to create new bridge endpoint")
+                {
+                    assert_eq!(result.net_pair.tap.name, format!("br{}_kata", idx));
+                    assert_eq!(result.net_pair.tap.tap_iface.name, tap_iface_name);
+                    assert_eq!(result.net_pair.virt_iface.name, bridge_name);
+                    match result.net_pair.model.model_type() {
+                        NetworkModelType::NoneModel => {}
+                        _ => unreachable!(),
+                    }
+                }
+                assert!(delete_link(&handle, bridge_name.as_str()).await.is_ok());
+                assert!(delete_link(&handle, tap_iface_name.as_str()).await.is_ok());
+            }
+        }
+    }
 }
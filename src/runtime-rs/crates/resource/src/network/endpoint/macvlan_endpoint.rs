@@ -81,6 +81,10 @@ impl Endpoint for MacVlanEndpoint {
         self.net_pair.tap.tap_iface.hard_addr.clone()
     }
 
+    async fn tap_name(&self) -> Option<String> {
+        Some(self.net_pair.tap.tap_iface.name.clone())
+    }
+
     async fn attach(&self) -> Result<()> {
         self.net_pair
             .add_network_model()
@@ -112,6 +116,13 @@ impl Endpoint for MacVlanEndpoint {
         Ok(())
     }
 
+    async fn teardown(&self) -> Result<()> {
+        self.net_pair
+            .teardown()
+            .await
+            .context("teardown network pair")
+    }
+
     async fn save(&self) -> Option<EndpointState> {
         Some(EndpointState {
             macvlan_endpoint: Some(MacvlanEndpointState {
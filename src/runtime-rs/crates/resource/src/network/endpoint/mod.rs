@@ -14,6 +14,8 @@ mod vlan_endpoint;
 pub use vlan_endpoint::VlanEndpoint;
 mod macvlan_endpoint;
 pub use macvlan_endpoint::MacVlanEndpoint;
+mod bridge_endpoint;
+pub use bridge_endpoint::BridgeEndpoint;
 pub mod endpoint_persist;
 mod endpoints_test;
 mod tap_endpoint;
@@ -29,7 +31,13 @@ use super::EndpointState;
 pub trait Endpoint: std::fmt::Debug + Send + Sync {
     async fn name(&self) -> String;
     async fn hardware_addr(&self) -> String;
+    /// The tap on the host carrying the traffic of the guest, None if the
+    /// device is passed through to the guest.
+    async fn tap_name(&self) -> Option<String>;
     async fn attach(&self) -> Result<()>;
     async fn detach(&self, hypervisor: &dyn Hypervisor) -> Result<()>;
+    /// Remove the resources created in the pod netns for the endpoint and
+    /// restore the original config of the link, after the VM is stopped.
+    async fn teardown(&self) -> Result<()>;
     async fn save(&self) -> Option<EndpointState>;
 }
@@ -13,6 +13,7 @@ use hypervisor::device::device_manager::{do_handle_device, DeviceManager};
 use hypervisor::device::DeviceConfig;
 use hypervisor::{device::driver, Hypervisor};
 use hypervisor::{get_vfio_device, VfioConfig};
+use kata_sys_util::host_lock::{self, HostResource, DEFAULT_LOCK_TIMEOUT};
 use tokio::sync::RwLock;
 
 use super::endpoint_persist::{EndpointState, PhysicalEndpointState};
@@ -89,6 +90,37 @@ impl PhysicalEndpoint {
     }
 }
 
+// the new_id and remove_id of the vfio-pci driver are shared by all the devices of the host,
+// so the shims bind their devices one at a time
+fn lock_network_devices() -> Result<host_lock::HostLockGuard> {
+    host_lock::lock(HostResource::NetworkDevice, None, DEFAULT_LOCK_TIMEOUT)
+        .context("lock network devices")
+}
+
+impl PhysicalEndpoint {
+    fn bind_back_to_host(&self) -> Result<()> {
+        // bind back the physical network interface to host.
+        // we need to do this even if a new network namespace has not
+        // been created by virt-containers.
+
+        // we do not need to enter the network namespace to bind back the
+        // physical interface to host driver.
+        let _guard = lock_network_devices()?;
+        driver::bind_device_to_host(
+            &self.bdf,
+            &self.driver,
+            &self.vendor_device_id.vendor_device_id(),
+        )
+        .with_context(|| {
+            format!(
+                "bind physical endpoint device from vfio to {}",
+                &self.driver
+            )
+        })?;
+        Ok(())
+    }
+}
+
 #[async_trait]
 impl Endpoint for PhysicalEndpoint {
     async fn name(&self) -> String {
@@ -99,14 +131,21 @@ impl Endpoint for PhysicalEndpoint {
         self.hard_addr.clone()
     }
 
+    async fn tap_name(&self) -> Option<String> {
+        None
+    }
+
     async fn attach(&self) -> Result<()> {
         // bind physical interface from host driver and bind to vfio
-        driver::bind_device_to_vfio(
-            &self.bdf,
-            &self.driver,
-            &self.vendor_device_id.vendor_device_id(),
-        )
-        .with_context(|| format!("bind physical endpoint from {} to vfio", &self.driver))?;
+        {
+            let _guard = lock_network_devices()?;
+            driver::bind_device_to_vfio(
+                &self.bdf,
+                &self.driver,
+                &self.vendor_device_id.vendor_device_id(),
+            )
+            .with_context(|| format!("bind physical endpoint from {} to vfio", &self.driver))?;
+        }
 
         let vfio_device = get_vfio_device(self.bdf.clone()).context("get vfio device failed.")?;
         let vfio_dev_config = &mut VfioConfig {
@@ -127,24 +166,11 @@ impl Endpoint for PhysicalEndpoint {
     // detach for physical endpoint unbinds the physical network interface from vfio-pci
     // and binds it back to the saved host driver.
     async fn detach(&self, _hypervisor: &dyn Hypervisor) -> Result<()> {
-        // bind back the physical network interface to host.
-        // we need to do this even if a new network namespace has not
-        // been created by virt-containers.
+        self.bind_back_to_host()
+    }
 
-        // we do not need to enter the network namespace to bind back the
-        // physical interface to host driver.
-        driver::bind_device_to_host(
-            &self.bdf,
-            &self.driver,
-            &self.vendor_device_id.vendor_device_id(),
-        )
-        .with_context(|| {
-            format!(
-                "bind physical endpoint device from vfio to {}",
-                &self.driver
-            )
-        })?;
-        Ok(())
+    async fn teardown(&self) -> Result<()> {
+        self.bind_back_to_host()
     }
 
     async fn save(&self) -> Option<EndpointState> {
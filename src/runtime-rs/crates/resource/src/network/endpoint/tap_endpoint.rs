@@ -94,6 +94,10 @@ impl Endpoint for TapEndpoint {
         self.guest_mac.clone()
     }
 
+    async fn tap_name(&self) -> Option<String> {
+        Some(self.tap_iface.name.clone())
+    }
+
     async fn attach(&self) -> Result<()> {
         let config = self.get_network_config().context("Get network config")?;
         do_handle_device(&self.dev_mgr, &DeviceConfig::NetworkCfg(config))
@@ -113,6 +117,11 @@ impl Endpoint for TapEndpoint {
         Ok(())
     }
 
+    async fn teardown(&self) -> Result<()> {
+        // the tap is created by the DAN provider, which cleans it up
+        Ok(())
+    }
+
     async fn save(&self) -> Option<EndpointState> {
         Some(EndpointState {
             tap_endpoint: Some(TapEndpointState {
@@ -80,6 +80,10 @@ impl Endpoint for VlanEndpoint {
         self.net_pair.tap.tap_iface.hard_addr.clone()
     }
 
+    async fn tap_name(&self) -> Option<String> {
+        Some(self.net_pair.tap.tap_iface.name.clone())
+    }
+
     async fn attach(&self) -> Result<()> {
         self.net_pair
             .add_network_model()
@@ -113,6 +117,13 @@ impl Endpoint for VlanEndpoint {
         Ok(())
     }
 
+    async fn teardown(&self) -> Result<()> {
+        self.net_pair
+            .teardown()
+            .await
+            .context("teardown network pair")
+    }
+
     async fn save(&self) -> Option<EndpointState> {
         Some(EndpointState {
             vlan_endpoint: Some(VlanEndpointState {
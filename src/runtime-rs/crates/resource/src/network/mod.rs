@@ -18,14 +18,17 @@ mod network_model;
 pub use network_model::NetworkModel;
 mod network_with_netns;
 pub use network_with_netns::NetworkWithNetNsConfig;
+mod neighbor_sync;
+pub(crate) use neighbor_sync::NeighborSync;
 use network_with_netns::NetworkWithNetns;
 mod network_pair;
 use network_pair::NetworkPair;
 mod utils;
 use tokio::sync::RwLock;
+pub use utils::bpf::network_bpf_dir;
 pub use utils::netns::{generate_netns_name, NetnsGuard};
 
-use anyhow::{Context, Result};
+use anyhow::{anyhow, Context, Result};
 use async_trait::async_trait;
 use hypervisor::{device::device_manager::DeviceManager, Hypervisor};
 
@@ -40,9 +43,16 @@ pub trait Network: Send + Sync {
     async fn setup(&self) -> Result<()>;
     async fn interfaces(&self) -> Result<Vec<agent::Interface>>;
     async fn routes(&self) -> Result<Vec<agent::Route>>;
+    async fn rules(&self) -> Result<Vec<agent::Rule>>;
     async fn neighs(&self) -> Result<Vec<agent::ARPNeighbor>>;
+    /// Rescan the neighbors to be proxied to the guest, which is empty if
+    /// the network doesn't need the neighbors synced.
+    async fn refresh_neighs(&self) -> Result<Vec<agent::ARPNeighbor>>;
     async fn save(&self) -> Option<Vec<EndpointState>>;
     async fn remove(&self, h: &dyn Hypervisor) -> Result<()>;
+    /// Tear down the network after the VM is stopped, the links created in
+    /// the pod netns are removed and the original links are restored.
+    async fn cleanup(&self) -> Result<()>;
 }
 
 pub async fn new(
@@ -62,3 +72,65 @@ pub async fn new(
         )),
     }
 }
+
+/// Check the interfaces of the pod network are configured in the guest, with
+/// the same hardware and IP addresses as in the pod network namespace.
+pub(crate) fn check_guest_interfaces(
+    expected: &[agent::Interface],
+    guest: &[agent::Interface],
+) -> Result<()> {
+    for e in expected {
+        let g = guest
+            .iter()
+            .find(|g| g.hw_addr == e.hw_addr)
+            .ok_or_else(|| anyhow!("interface {} ({}) not found in guest", e.name, e.hw_addr))?;
+
+        for addr in &e.ip_addresses {
+            if !g.ip_addresses.contains(addr) {
+                return Err(anyhow!(
+                    "address {}/{} of interface {} not configured in guest",
+                    addr.address,
+                    addr.mask,
+                    e.name
+                ));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_guest_interfaces() {
+        let addr = agent::IPAddress {
+            address: "10.0.0.2".to_string(),
+            mask: "24".to_string(),
+            ..Default::default()
+        };
+        let expected = vec![agent::Interface {
+            name: "eth0".to_string(),
+            hw_addr: "02:42:ac:11:00:02".to_string(),
+            ip_addresses: vec![addr.clone()],
+            ..Default::default()
+        }];
+
+        // not configured
+        assert!(check_guest_interfaces(&expected, &[]).is_err());
+
+        // configured without address
+        let mut guest = vec![agent::Interface {
+            name: "eth0".to_string(),
+            hw_addr: "02:42:ac:11:00:02".to_string(),
+            ..Default::default()
+        }];
+        assert!(check_guest_interfaces(&expected, &guest).is_err());
+
+        guest[0].ip_addresses.push(addr);
+        check_guest_interfaces(&expected, &guest).unwrap();
+        check_guest_interfaces(&[], &guest).unwrap();
+    }
+}
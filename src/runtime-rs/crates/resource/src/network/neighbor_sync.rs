@@ -0,0 +1,147 @@
+// Copyright (c) 2023 Kata Containers community
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+use std::{collections::HashMap, sync::Arc, time::Duration};
+
+use agent::{ARPNeighbor, Agent};
+use tokio::task::JoinHandle;
+
+use super::Network;
+
+// Neighbors are identified by the device and the ip address.
+type NeighborKey = (String, String);
+
+fn neighbor_key(n: &ARPNeighbor) -> NeighborKey {
+    (
+        n.device.clone(),
+        n.to_ip_address
+            .as_ref()
+            .map(|ip| ip.address.clone())
+            .unwrap_or_default(),
+    )
+}
+
+/// Sync the neighbors of the pod network namespace to the guest periodically.
+///
+/// With the tc filter model, the guest doesn't see the gratuitous ARP replies
+/// handled by the host network namespace, so the neighbors learned there,
+/// e.g. after the failover of a virtual IP, are pushed to the guest.
+pub(crate) struct NeighborSync {
+    handle: JoinHandle<()>,
+}
+
+impl NeighborSync {
+    pub(crate) fn start(
+        network: Arc<dyn Network>,
+        agent: Arc<dyn Agent>,
+        interval: Duration,
+        synced: Vec<ARPNeighbor>,
+    ) -> Self {
+        let handle = tokio::spawn(async move {
+            let mut synced: HashMap<NeighborKey, ARPNeighbor> =
+                synced.into_iter().map(|n| (neighbor_key(&n), n)).collect();
+            loop {
+                tokio::time::sleep(interval).await;
+
+                let current = match network.refresh_neighs().await {
+                    Ok(current) => current,
+                    Err(e) => {
+                        warn!(sl!(), "failed to refresh neighbors: {:?}", e);
+                        continue;
+                    }
+                };
+                let neighbors = changed_neighbors(&synced, &current);
+                if neighbors.is_empty() {
+                    continue;
+                }
+
+                info!(sl!(), "resync neighbors {:?}", neighbors);
+                let req = agent::AddArpNeighborRequest {
+                    neighbors: Some(agent::ARPNeighbors {
+                        neighbors: neighbors.clone(),
+                    }),
+                };
+                // the neighbors are retried in the next round on failure
+                if let Err(e) = agent.add_arp_neighbors(req).await {
+                    warn!(sl!(), "failed to resync neighbors: {:?}", e);
+                    continue;
+                }
+                for n in neighbors {
+                    synced.insert(neighbor_key(&n), n);
+                }
+            }
+        });
+
+        Self { handle }
+    }
+
+    pub(crate) fn stop(&self) {
+        self.handle.abort();
+    }
+}
+
+// The neighbors added or changed since the last sync. The neighbors gone in
+// the host aren't removed, the guest would expire them as the host does.
+fn changed_neighbors(
+    synced: &HashMap<NeighborKey, ARPNeighbor>,
+    current: &[ARPNeighbor],
+) -> Vec<ARPNeighbor> {
+    current
+        .iter()
+        .filter(|n| !n.ll_addr.is_empty())
+        .filter(|n| match synced.get(&neighbor_key(n)) {
+            Some(s) => s.ll_addr != n.ll_addr,
+            None => true,
+        })
+        .cloned()
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use agent::{IPAddress, IPFamily};
+
+    fn neighbor(ip: &str, ll_addr: &str) -> ARPNeighbor {
+        ARPNeighbor {
+            to_ip_address: Some(IPAddress {
+                family: IPFamily::V4,
+                address: ip.to_string(),
+                mask: "".to_string(),
+            }),
+            device: "eth0".to_string(),
+            ll_addr: ll_addr.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_changed_neighbors() {
+        let synced: HashMap<NeighborKey, ARPNeighbor> =
+            vec![neighbor("10.0.0.1", "02:00:00:00:00:01")]
+                .into_iter()
+                .map(|n| (neighbor_key(&n), n))
+                .collect();
+
+        // not changed
+        let current = vec![neighbor("10.0.0.1", "02:00:00:00:00:01")];
+        assert!(changed_neighbors(&synced, &current).is_empty());
+
+        // the mac address is taken over by another host, and a new neighbor
+        let current = vec![
+            neighbor("10.0.0.1", "02:00:00:00:00:02"),
+            neighbor("10.0.0.2", "02:00:00:00:00:03"),
+            // incomplete
+            neighbor("10.0.0.3", ""),
+        ];
+        assert_eq!(
+            changed_neighbors(&synced, &current),
+            vec![
+                neighbor("10.0.0.1", "02:00:00:00:00:02"),
+                neighbor("10.0.0.2", "02:00:00:00:00:03"),
+            ]
+        );
+    }
+}
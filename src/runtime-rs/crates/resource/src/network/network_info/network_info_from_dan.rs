@@ -74,6 +74,8 @@ impl NetworkInfoFromDan {
                     source: route.source.clone(),
                     scope: route.scope,
                     family,
+                    metric: route.metric,
+                    table: route.table,
                 })
             })
             .collect();
@@ -159,6 +161,8 @@ mod tests {
                     source: "172.18.0.1".to_owned(),
                     gateway: "172.18.31.1".to_owned(),
                     scope: 0,
+                    metric: 0,
+                    table: 0,
                 }],
                 neighbors: vec![DanARPNeighbor {
                     ip_address: Some("192.168.0.3/16".to_owned()),
@@ -194,6 +198,8 @@ mod tests {
             source: "172.18.0.1".to_owned(),
             scope: 0,
             family: IPFamily::V4,
+            metric: 0,
+            table: 0,
         }];
         assert_eq!(routes, network_info.routes().await.unwrap());
 
@@ -6,12 +6,13 @@
 
 use std::convert::TryFrom;
 
-use agent::{ARPNeighbor, IPAddress, IPFamily, Interface, Route};
+use agent::{ARPNeighbor, IPAddress, IPFamily, Interface, Route, Rule};
 use anyhow::{Context, Result};
 use async_trait::async_trait;
 use futures::stream::TryStreamExt;
 use netlink_packet_route::{
     self, neighbour::NeighbourMessage, nlas::neighbour::Nla, route::RouteMessage,
+    rule::RuleMessage, RT_TABLE_DEFAULT, RT_TABLE_LOCAL, RT_TABLE_MAIN,
 };
 
 use super::NetworkInfo;
@@ -137,7 +138,7 @@ fn generate_neigh(name: &str, n: &NeighbourMessage) -> Result<ARPNeighbor> {
     Ok(neigh)
 }
 
-async fn handle_neighbors(
+pub(crate) async fn handle_neighbors(
     handle: &rtnetlink::Handle,
     attrs: &LinkAttrs,
 ) -> Result<Vec<ARPNeighbor>> {
@@ -157,11 +158,49 @@ async fn handle_neighbors(
     Ok(neighs)
 }
 
+// Action of the rules looking up a routing table, from <linux/fib_rules.h>
+const FR_ACT_TO_TBL: u8 = 1;
+// Priorities of the rules created by the kernel for the local, main and
+// default routing tables.
+const DEFAULT_RULE_PRIORITIES: [u32; 3] = [0, 32766, 32767];
+
+// The main routing table is denoted by 0 for the agent.
+fn route_table(table: u32) -> u32 {
+    if table == RT_TABLE_MAIN as u32 {
+        0
+    } else {
+        table
+    }
+}
+
 fn generate_route(name: &str, route: &RouteMessage) -> Result<Option<Route>> {
+    use netlink_packet_route::nlas::route::Nla;
+
     if route.header.protocol == libc::RTPROT_KERNEL {
         return Ok(None);
     }
 
+    // the table id larger than 255 is only carried by the attribute
+    let table = route
+        .nlas
+        .iter()
+        .find_map(|nla| match nla {
+            Nla::Table(t) => Some(*t),
+            _ => None,
+        })
+        .unwrap_or(route.header.table as u32);
+    if table == RT_TABLE_LOCAL as u32 {
+        return Ok(None);
+    }
+    let metric = route
+        .nlas
+        .iter()
+        .find_map(|nla| match nla {
+            Nla::Priority(p) => Some(*p),
+            _ => None,
+        })
+        .unwrap_or_default();
+
     Ok(Some(Route {
         dest: route
             .destination_prefix()
@@ -179,6 +218,8 @@ fn generate_route(name: &str, route: &RouteMessage) -> Result<Option<Route>> {
         } else {
             IPFamily::V6
         },
+        metric,
+        table: route_table(table),
     }))
 }
 
@@ -214,6 +255,74 @@ async fn handle_routes(handle: &rtnetlink::Handle, attrs: &LinkAttrs) -> Result<
     Ok(routes)
 }
 
+fn generate_rule(rule: &RuleMessage) -> Result<Option<Rule>> {
+    use netlink_packet_route::rule::Nla;
+
+    if rule.header.action != FR_ACT_TO_TBL {
+        return Ok(None);
+    }
+
+    let family = rule.header.family;
+    let mut r = Rule {
+        table: rule.header.table as u32,
+        family: if family == libc::AF_INET as u8 {
+            IPFamily::V4
+        } else {
+            IPFamily::V6
+        },
+        ..Default::default()
+    };
+    for nla in &rule.nlas {
+        match nla {
+            Nla::Source(addr) => {
+                let ip = parse_ip(addr, family).context("parse source")?;
+                r.source = format!("{}/{}", ip, rule.header.src_len);
+            }
+            Nla::Destination(addr) => {
+                let ip = parse_ip(addr, family).context("parse destination")?;
+                r.dest = format!("{}/{}", ip, rule.header.dst_len);
+            }
+            Nla::Iifname(name) => r.iif = name.clone(),
+            Nla::OifName(name) => r.oif = name.clone(),
+            Nla::FwMark(mark) => r.fwmark = *mark,
+            Nla::Table(table) => r.table = *table,
+            Nla::Priority(priority) => r.priority = *priority,
+            _ => {
+                // skip the unused Nla
+            }
+        }
+    }
+
+    // the rules created by the kernel exist in the guest as well
+    let default_table = [RT_TABLE_LOCAL, RT_TABLE_MAIN, RT_TABLE_DEFAULT]
+        .iter()
+        .any(|t| *t as u32 == r.table);
+    if default_table && DEFAULT_RULE_PRIORITIES.contains(&r.priority) {
+        return Ok(None);
+    }
+    r.table = route_table(r.table);
+
+    Ok(Some(r))
+}
+
+/// Get the policy routing rules of the current network namespace.
+pub async fn handle_rules(handle: &rtnetlink::Handle) -> Result<Vec<Rule>> {
+    let mut rules = vec![];
+    for ip_version in [rtnetlink::IpVersion::V4, rtnetlink::IpVersion::V6] {
+        let mut rule_msg_list = handle.rule().get(ip_version).execute();
+        while let Some(rule) = rule_msg_list
+            .try_next()
+            .await
+            .context("try next rule msg")?
+        {
+            if let Some(rule) = generate_rule(&rule).context("generate rule")? {
+                rules.push(rule);
+            }
+        }
+    }
+    Ok(rules)
+}
+
 #[async_trait]
 impl NetworkInfo for NetworkInfoFromLink {
     async fn interface(&self) -> Result<Interface> {
@@ -228,3 +337,72 @@ impl NetworkInfo for NetworkInfoFromLink {
         Ok(self.neighs.clone())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_generate_route() {
+        use netlink_packet_route::nlas::route::Nla;
+
+        let mut msg = RouteMessage::default();
+        msg.header.address_family = libc::AF_INET as u8;
+        msg.header.protocol = libc::RTPROT_BOOT;
+        msg.header.table = RT_TABLE_MAIN;
+        msg.header.destination_prefix_length = 8;
+        msg.nlas.push(Nla::Destination(vec![10, 0, 0, 0]));
+        msg.nlas.push(Nla::Priority(100));
+
+        let route = generate_route("eth0", &msg).unwrap().unwrap();
+        assert_eq!(route.dest, "10.0.0.0/8");
+        assert_eq!(route.metric, 100);
+        assert_eq!(route.table, 0);
+
+        // the table larger than 255
+        msg.nlas.push(Nla::Table(1000));
+        let route = generate_route("eth0", &msg).unwrap().unwrap();
+        assert_eq!(route.table, 1000);
+
+        // the routes of the local table are maintained by the kernel
+        msg.nlas.pop();
+        msg.header.table = RT_TABLE_LOCAL;
+        assert!(generate_route("eth0", &msg).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_generate_rule() {
+        use netlink_packet_route::rule::Nla;
+
+        let mut msg = RuleMessage::default();
+        msg.header.family = libc::AF_INET as u8;
+        msg.header.action = FR_ACT_TO_TBL;
+        msg.header.table = RT_TABLE_MAIN;
+        msg.nlas.push(Nla::Priority(32766));
+        assert!(generate_rule(&msg).unwrap().is_none());
+
+        msg.header.table = 100;
+        msg.header.src_len = 32;
+        msg.nlas = vec![
+            Nla::Priority(1000),
+            Nla::Source(vec![10, 0, 0, 2]),
+            Nla::Iifname("eth1".to_string()),
+        ];
+        let rule = generate_rule(&msg).unwrap().unwrap();
+        assert_eq!(
+            rule,
+            Rule {
+                source: "10.0.0.2/32".to_string(),
+                iif: "eth1".to_string(),
+                table: 100,
+                priority: 1000,
+                family: IPFamily::V4,
+                ..Default::default()
+            }
+        );
+
+        // not a rule looking up a table
+        msg.header.action = 0;
+        assert!(generate_rule(&msg).unwrap().is_none());
+    }
+}
@@ -8,6 +8,8 @@ use std::{convert::TryFrom, sync::Arc, usize};
 
 use anyhow::{anyhow, Context, Result};
 use futures::stream::TryStreamExt;
+use kata_sys_util::naming;
+use scopeguard::defer;
 
 use super::{
     network_model,
@@ -50,13 +52,19 @@ impl NetworkPair {
     ) -> Result<Self> {
         let unique_id = kata_sys_util::rand::UUID::new();
         let model = network_model::new(model).context("new network model")?;
-        let tap_iface_name = format!("tap{}{}", idx, TAP_SUFFIX);
+        let tap_iface_name = naming::ifname("tap", &idx.to_string(), TAP_SUFFIX)?;
         let virt_iface_name = format!("eth{}", idx);
         let tap_link = create_link(handle, &tap_iface_name, queues)
             .await
             .context("create link")?;
 
-        let virt_link = get_link_by_name(handle, virt_iface_name.clone().as_str())
+        // the link may be named by the CNI plugin, e.g. a bridge
+        let virt_link_name = if name.is_empty() {
+            virt_iface_name.as_str()
+        } else {
+            name
+        };
+        let virt_link = get_link_by_name(handle, virt_link_name)
             .await
             .context("get link by name")?;
 
@@ -139,6 +147,34 @@ impl NetworkPair {
         model.del(self).await.context("del")?;
         Ok(())
     }
+
+    /// Remove the network model and the tap created in the pod netns, it's
+    /// fine if they have been removed already.
+    pub(crate) async fn teardown(&self) -> Result<()> {
+        if let Err(e) = self.del_network_model().await {
+            warn!(
+                sl!(),
+                "failed to del network model of {}: {:?}", self.virt_iface.name, e
+            );
+        }
+
+        let (connection, handle, _) = rtnetlink::new_connection().context("new connection")?;
+        let thread_handler = tokio::spawn(connection);
+        defer!({
+            thread_handler.abort();
+        });
+
+        // the tc filters and the qdiscs of the tap are removed with it
+        if let Ok(tap_link) = get_link_by_name(&handle, &self.tap.tap_iface.name).await {
+            handle
+                .link()
+                .del(tap_link.attrs().index)
+                .execute()
+                .await
+                .context("del tap")?;
+        }
+        Ok(())
+    }
 }
 
 pub async fn create_link(
@@ -246,16 +282,24 @@ mod tests {
                 .await
                 .is_ok());
 
-            if let Ok(_pair) = NetworkPair::new(&handle, idx, "", model, queues).await {
+            if let Ok(pair) = NetworkPair::new(&handle, idx, "", model, queues).await {
                 // the pair is created, we can find the two ends of network pair
                 assert!(get_link_by_name(&handle, virt_iface_name.as_str())
                     .await
                     .is_ok());
                 assert!(get_link_by_name(&handle, tap_name.as_str()).await.is_ok());
 
+                // the tap is removed on teardown, and the veth is kept
+                assert!(pair.teardown().await.is_ok());
+                assert!(get_link_by_name(&handle, tap_name.as_str()).await.is_err());
+                assert!(get_link_by_name(&handle, virt_iface_name.as_str())
+                    .await
+                    .is_ok());
+                // teardown again is fine
+                assert!(pair.teardown().await.is_ok());
+
                 //delete the link created in test
                 assert!(delete_link(&handle, virt_iface_name.as_str()).await.is_ok());
-                assert!(delete_link(&handle, tap_name.as_str()).await.is_ok());
             }
         }
     }
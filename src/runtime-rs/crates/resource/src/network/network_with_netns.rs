@@ -6,6 +6,7 @@
 
 use std::{
     fs,
+    path::{Path, PathBuf},
     sync::{
         atomic::{AtomicU32, Ordering},
         Arc,
@@ -23,11 +24,16 @@ use tokio::sync::RwLock;
 
 use super::{
     endpoint::{
-        Endpoint, IPVlanEndpoint, MacVlanEndpoint, PhysicalEndpoint, VethEndpoint, VlanEndpoint,
+        BridgeEndpoint, Endpoint, IPVlanEndpoint, MacVlanEndpoint, PhysicalEndpoint, VethEndpoint,
+        VlanEndpoint,
     },
     network_entity::NetworkEntity,
-    network_info::network_info_from_link::{handle_addresses, NetworkInfoFromLink},
-    utils::{link, netns},
+    network_info::network_info_from_link::{
+        handle_addresses, handle_neighbors, handle_rules, NetworkInfoFromLink,
+    },
+    network_model::{tc_filter_model::fetch_index, TC_FILTER_NET_MODEL_STR},
+    network_pair::get_link_by_name,
+    utils::{bpf, link, netns},
     Network,
 };
 use crate::network::NetworkInfo;
@@ -38,35 +44,48 @@ pub struct NetworkWithNetNsConfig {
     pub netns_path: String,
     pub queues: usize,
     pub network_created: bool,
+    /// Directory of the eBPF programs pinned for the interfaces of the pod,
+    /// None if the offload of the network policies is disabled
+    pub bpf_dir: Option<PathBuf>,
 }
 
 struct NetworkWithNetnsInner {
     netns_path: String,
+    network_model: String,
     entity_list: Vec<NetworkEntity>,
+    rules: Vec<agent::Rule>,
     network_created: bool,
+    bpf_dir: Option<PathBuf>,
 }
 
 impl NetworkWithNetnsInner {
     async fn new(config: &NetworkWithNetNsConfig, d: Arc<RwLock<DeviceManager>>) -> Result<Self> {
-        let entity_list = if config.netns_path.is_empty() {
+        let (entity_list, rules) = if config.netns_path.is_empty() {
             warn!(sl!(), "Skip to scan network for empty netns");
-            vec![]
+            (vec![], vec![])
         } else if config.network_model.as_str() == "none" {
             warn!(
                 sl!(),
                 "Skip to scan network from netns due to the none network model"
             );
-            vec![]
+            (vec![], vec![])
         } else {
             // get endpoint
-            get_entity_from_netns(config, d)
+            let entity_list = get_entity_from_netns(config, d)
+                .await
+                .context("get entity from netns")?;
+            let rules = get_rules_from_netns(config)
                 .await
-                .context("get entity from netns")?
+                .context("get rules from netns")?;
+            (entity_list, rules)
         };
         Ok(Self {
             netns_path: config.netns_path.to_string(),
+            network_model: config.network_model.clone(),
             entity_list,
+            rules,
             network_created: config.network_created,
+            bpf_dir: config.bpf_dir.clone(),
         })
     }
 }
@@ -94,6 +113,11 @@ impl Network for NetworkWithNetns {
         for e in &inner.entity_list {
             e.endpoint.attach().await.context("attach")?;
         }
+        if let Some(bpf_dir) = inner.bpf_dir.as_ref() {
+            attach_bpf_programs(bpf_dir, &inner.entity_list)
+                .await
+                .context("attach bpf programs")?;
+        }
         Ok(())
     }
 
@@ -116,6 +140,11 @@ impl Network for NetworkWithNetns {
         Ok(routes)
     }
 
+    async fn rules(&self) -> Result<Vec<agent::Rule>> {
+        let inner = self.inner.read().await;
+        Ok(inner.rules.clone())
+    }
+
     async fn neighs(&self) -> Result<Vec<agent::ARPNeighbor>> {
         let inner = self.inner.read().await;
         let mut neighs = vec![];
@@ -126,6 +155,33 @@ impl Network for NetworkWithNetns {
         Ok(neighs)
     }
 
+    async fn refresh_neighs(&self) -> Result<Vec<agent::ARPNeighbor>> {
+        let inner = self.inner.read().await;
+        // the guest sees the neighbors of the netns only through the tc filter redirection
+        if inner.network_model != TC_FILTER_NET_MODEL_STR || inner.entity_list.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let netns_path = inner.netns_path.clone();
+        let mut names = vec![];
+        for e in &inner.entity_list {
+            names.push(e.endpoint.name().await);
+        }
+        drop(inner);
+
+        // The same as the network setup, the netns is entered by a dedicated
+        // thread, so that no other task runs in the pod netns.
+        tokio::task::spawn_blocking(move || -> Result<Vec<agent::ARPNeighbor>> {
+            let _netns_guard = netns::NetnsGuard::new(&netns_path).context("net netns guard")?;
+            let rt = tokio::runtime::Builder::new_current_thread()
+                .enable_io()
+                .build()?;
+            rt.block_on(get_neighs_from_netns(&names))
+        })
+        .await
+        .context("join the neighbors scan")?
+    }
+
     async fn save(&self) -> Option<Vec<EndpointState>> {
         let inner = self.inner.read().await;
         let mut endpoint = vec![];
@@ -151,13 +207,98 @@ impl Network for NetworkWithNetns {
                 e.endpoint.detach(h).await.context("detach")?;
             }
         }
-        let netns = get_from_path(inner.netns_path.clone())?;
-        netns.remove()?;
-        fs::remove_dir_all(inner.netns_path.clone()).context("failed to remove netns path")?;
+        remove_netns(&inner.netns_path)
+    }
+
+    async fn cleanup(&self) -> Result<()> {
+        let inner = self.inner.read().await;
+        // the pod netns may have been removed by the CNI plugin already
+        if inner.netns_path.is_empty() || !Path::new(&inner.netns_path).exists() {
+            return Ok(());
+        }
+
+        let netns_path = inner.netns_path.clone();
+        let endpoints: Vec<Arc<dyn Endpoint>> = inner
+            .entity_list
+            .iter()
+            .map(|e| e.endpoint.clone())
+            .collect();
+
+        // The same as the network setup, the netns is entered by a dedicated
+        // thread, so that no other task runs in the pod netns.
+        tokio::task::spawn_blocking(move || -> Result<()> {
+            let _netns_guard = netns::NetnsGuard::new(&netns_path).context("net netns guard")?;
+            let rt = tokio::runtime::Builder::new_current_thread()
+                .enable_io()
+                .build()?;
+            rt.block_on(async {
+                // tear down all the endpoints even if some of them fail
+                let mut result = Ok(());
+                for endpoint in &endpoints {
+                    if let Err(e) = endpoint.teardown().await {
+                        let name = endpoint.name().await;
+                        error!(sl!(), "failed to teardown endpoint {}: {:?}", name, e);
+                        if result.is_ok() {
+                            result = Err(e.context(format!("teardown endpoint {}", name)));
+                        }
+                    }
+                }
+                result
+            })
+        })
+        .await
+        .context("join the network teardown")??;
+
+        if inner.network_created {
+            remove_netns(&inner.netns_path).context("remove netns")?;
+        }
         Ok(())
     }
 }
 
+// attach_bpf_programs attaches the eBPF programs pinned for the interfaces of
+// the pod to their taps, the interfaces without a program are skipped.
+async fn attach_bpf_programs(bpf_dir: &Path, entity_list: &[NetworkEntity]) -> Result<()> {
+    let (connection, handle, _) = rtnetlink::new_connection().context("new connection")?;
+    let thread_handler = tokio::spawn(connection);
+    defer!({
+        thread_handler.abort();
+    });
+
+    for e in entity_list {
+        let tap_name = match e.endpoint.tap_name().await {
+            Some(tap_name) => tap_name,
+            None => continue,
+        };
+        let path = bpf_dir.join(e.endpoint.name().await);
+        if !path.exists() {
+            continue;
+        }
+
+        info!(
+            sl!(),
+            "attach bpf program {} to tap {}",
+            path.display(),
+            tap_name
+        );
+        let tap_index = fetch_index(&handle, &tap_name)
+            .await
+            .context("fetch tap index")?;
+        bpf::attach_bpf_program(&handle, tap_index, &path)
+            .await
+            .with_context(|| format!("attach bpf program to tap {}", tap_name))?;
+    }
+
+    Ok(())
+}
+
+fn remove_netns(netns_path: &str) -> Result<()> {
+    let netns = get_from_path(netns_path)?;
+    netns.remove()?;
+    fs::remove_dir_all(netns_path).context("failed to remove netns path")?;
+    Ok(())
+}
+
 async fn get_entity_from_netns(
     config: &NetworkWithNetNsConfig,
     d: Arc<RwLock<DeviceManager>>,
@@ -210,6 +351,39 @@ async fn get_entity_from_netns(
     Ok(entity_list)
 }
 
+async fn get_rules_from_netns(config: &NetworkWithNetNsConfig) -> Result<Vec<agent::Rule>> {
+    let _netns_guard = netns::NetnsGuard::new(&config.netns_path).context("net netns guard")?;
+    let (connection, handle, _) = rtnetlink::new_connection().context("new connection")?;
+    let thread_handler = tokio::spawn(connection);
+    defer!({
+        thread_handler.abort();
+    });
+
+    let rules = handle_rules(&handle).await.context("handle rules")?;
+    info!(sl!(), "policy routing rules {:?}", rules);
+    Ok(rules)
+}
+
+async fn get_neighs_from_netns(names: &[String]) -> Result<Vec<agent::ARPNeighbor>> {
+    let (connection, handle, _) = rtnetlink::new_connection().context("new connection")?;
+    let thread_handler = tokio::spawn(connection);
+    defer!({
+        thread_handler.abort();
+    });
+
+    let mut neighs = vec![];
+    for name in names {
+        let link = get_link_by_name(&handle, name)
+            .await
+            .context("get link by name")?;
+        let mut list = handle_neighbors(&handle, link.attrs())
+            .await
+            .context("handle neighbours")?;
+        neighs.append(&mut list);
+    }
+    Ok(neighs)
+}
+
 async fn create_endpoint(
     handle: &rtnetlink::Handle,
     link: &dyn link::Link,
@@ -264,6 +438,12 @@ async fn create_endpoint(
                     .context("ipvlan endpoint")?;
                 Arc::new(ret)
             }
+            "bridge" => {
+                let ret = BridgeEndpoint::new(&d, handle, &attrs.name, idx, config.queues)
+                    .await
+                    .context("bridge endpoint")?;
+                Arc::new(ret)
+            }
             "macvlan" => {
                 let ret = MacVlanEndpoint::new(
                     &d,
@@ -0,0 +1,187 @@
+// Copyright (c) 2023 Kata Containers community
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+// The eBPF programs pinned by the CNI plugins of an eBPF datapath are
+// attached to the taps of the sandbox, so that the policies of the datapath
+// apply to the traffic of the guest without duplicating them in tc rules. The
+// program of an interface of the pod is handed off by its pin path in the BPF
+// filesystem, as its fd can't be passed to the shim.
+
+use std::ffi::CString;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::io::{AsRawFd, FromRawFd, OwnedFd, RawFd};
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, Context, Result};
+use futures::stream::StreamExt;
+use kata_types::config::TomlConfig;
+use netlink_packet_route::{
+    constants::{NLM_F_ACK, NLM_F_CREATE, NLM_F_EXCL, NLM_F_REQUEST},
+    nlas::DefaultNla,
+    tc, NetlinkMessage, NetlinkPayload, RtnlMessage, TcMessage,
+};
+use nix::errno::Errno;
+
+// BPF_OBJ_GET command of the bpf syscall
+const BPF_OBJ_GET: libc::c_long = 7;
+
+const TC_BPF_KIND: &str = "bpf";
+// attributes of the options of the bpf filter
+const TCA_BPF_FD: u16 = 6;
+const TCA_BPF_NAME: u16 = 7;
+const TCA_BPF_FLAGS: u16 = 8;
+// the return code of the program is the action on the packet
+const TCA_BPF_FLAG_ACT_DIRECT: u32 = 1;
+
+// the parent of the filters of the ingress qdisc, ffff:
+const TC_INGRESS_PARENT: u32 = 0xffff_0000;
+// ahead of the redirect of the tcfilter model, which gets the default priority
+const TC_BPF_PRIORITY: u16 = 1;
+const ETH_P_ALL: u16 = 0x0003;
+
+// attributes of BPF_OBJ_GET
+#[repr(C)]
+struct BpfObjGetAttr {
+    pathname: u64,
+    bpf_fd: u32,
+    file_flags: u32,
+}
+
+/// Get the directory of the eBPF programs pinned for the interfaces of the
+/// sandbox, `None` if the offload of the network policies is disabled.
+pub fn network_bpf_dir(config: &TomlConfig, sandbox_id: &str) -> Option<PathBuf> {
+    if config.runtime.network_bpf_dir.is_empty() {
+        return None;
+    }
+    Some(PathBuf::from(config.runtime.network_bpf_dir.as_str()).join(sandbox_id))
+}
+
+/// Attach the eBPF program pinned at `path` to the ingress of the tap whose
+/// index is `tap_index`, in the current network namespace.
+pub(crate) async fn attach_bpf_program(
+    handle: &rtnetlink::Handle,
+    tap_index: u32,
+    path: &Path,
+) -> Result<()> {
+    let prog = get_pinned_object(path).context("get pinned program")?;
+
+    // the ingress qdisc is already added by the tcfilter model
+    match handle
+        .qdisc()
+        .add(tap_index as i32)
+        .ingress()
+        .execute()
+        .await
+    {
+        Err(rtnetlink::Error::NetlinkError(e)) if e.code == -libc::EEXIST => {}
+        result => result.context("add tap ingress")?,
+    }
+
+    let name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let mut req = NetlinkMessage::from(RtnlMessage::NewTrafficFilter(bpf_filter_message(
+        tap_index,
+        prog.as_raw_fd(),
+        &name,
+    )));
+    req.header.flags = NLM_F_REQUEST | NLM_F_ACK | NLM_F_CREATE | NLM_F_EXCL;
+
+    let mut handle = handle.clone();
+    let mut response = handle.request(req)?;
+    while let Some(message) = response.next().await {
+        if let NetlinkPayload::Error(err) = message.payload {
+            return Err(anyhow!(rtnetlink::Error::NetlinkError(err)))
+                .context(format!("add bpf filter {}", path.display()));
+        }
+    }
+
+    Ok(())
+}
+
+// get_pinned_object opens the BPF object pinned at the path.
+fn get_pinned_object(path: &Path) -> Result<OwnedFd> {
+    let pathname = CString::new(path.as_os_str().as_bytes()).context("invalid path")?;
+    let attr = BpfObjGetAttr {
+        pathname: pathname.as_ptr() as u64,
+        bpf_fd: 0,
+        file_flags: 0,
+    };
+    let fd = unsafe {
+        libc::syscall(
+            libc::SYS_bpf,
+            BPF_OBJ_GET,
+            &attr as *const BpfObjGetAttr,
+            std::mem::size_of::<BpfObjGetAttr>(),
+        )
+    };
+    let fd = Errno::result(fd).with_context(|| format!("open {}", path.display()))?;
+
+    Ok(unsafe { OwnedFd::from_raw_fd(fd as RawFd) })
+}
+
+// bpf_filter_message builds the request of a bpf filter in direct-action
+// mode on the ingress of the link, for all the protocols.
+fn bpf_filter_message(index: u32, prog_fd: RawFd, name: &str) -> TcMessage {
+    let mut message = TcMessage::with_index(index as i32);
+    message.header.parent = TC_INGRESS_PARENT;
+    message.header.info = (TC_BPF_PRIORITY as u32) << 16 | ETH_P_ALL.to_be() as u32;
+
+    let mut prog_name = name.as_bytes().to_vec();
+    prog_name.push(0);
+    message.nlas = vec![
+        tc::Nla::Kind(TC_BPF_KIND.to_string()),
+        tc::Nla::Options(vec![
+            tc::TcOpt::Other(DefaultNla::new(
+                TCA_BPF_FD,
+                (prog_fd as u32).to_ne_bytes().to_vec(),
+            )),
+            tc::TcOpt::Other(DefaultNla::new(TCA_BPF_NAME, prog_name)),
+            tc::TcOpt::Other(DefaultNla::new(
+                TCA_BPF_FLAGS,
+                TCA_BPF_FLAG_ACT_DIRECT.to_ne_bytes().to_vec(),
+            )),
+        ]),
+    ];
+    message
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_network_bpf_dir() {
+        let mut config = TomlConfig::default();
+        assert!(network_bpf_dir(&config, "sid").is_none());
+
+        config.runtime.network_bpf_dir = "/sys/fs/bpf/kata".to_string();
+        assert_eq!(
+            network_bpf_dir(&config, "sid").unwrap(),
+            PathBuf::from("/sys/fs/bpf/kata/sid")
+        );
+    }
+
+    #[test]
+    fn test_bpf_filter_message() {
+        let message = bpf_filter_message(3, 10, "eth0");
+        assert_eq!(message.header.index, 3);
+        assert_eq!(message.header.parent, TC_INGRESS_PARENT);
+        assert_eq!(message.header.info >> 16, TC_BPF_PRIORITY as u32);
+        assert_eq!(message.header.info & 0xffff, ETH_P_ALL.to_be() as u32);
+
+        assert!(matches!(&message.nlas[0], tc::Nla::Kind(kind) if kind == TC_BPF_KIND));
+        match &message.nlas[1] {
+            tc::Nla::Options(opts) => assert_eq!(opts.len(), 3),
+            nla => panic!("unexpected nla {:?}", nla),
+        }
+    }
+
+    #[test]
+    fn test_get_pinned_object() {
+        assert!(get_pinned_object(Path::new("/sys/fs/bpf/nonexistent")).is_err());
+    }
+}
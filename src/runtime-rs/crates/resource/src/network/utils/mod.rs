@@ -5,6 +5,7 @@
 //
 
 pub(crate) mod address;
+pub(crate) mod bpf;
 pub(crate) mod link;
 pub(crate) mod netns;
 
@@ -0,0 +1,250 @@
+// Copyright (c) 2023 Kata Containers community
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+use std::{
+    fs::{self, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use anyhow::{anyhow, Context, Result};
+use hypervisor::Hypervisor;
+use kata_sys_util::spec::load_oci_spec;
+use kata_types::config::TomlConfig;
+use oci::LinuxIntelRdt;
+use serde::{Deserialize, Serialize};
+
+const RESCTRL_ROOT: &str = "/sys/fs/resctrl";
+const RESCTRL_TASKS: &str = "tasks";
+const RESCTRL_SCHEMATA: &str = "schemata";
+
+/// The resctrl group the vCPU threads are assigned to.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct RdtState {
+    /// Name of the group, i.e. the class of service.
+    pub clos_id: String,
+    /// Whether the group was created for the sandbox, so removed with it.
+    pub created: bool,
+}
+
+/// Intel RDT of the sandbox: the vCPU threads, which run the code of all the containers, are
+/// assigned to a host resctrl group limiting their L3 cache and memory bandwidth.
+///
+/// The group is the `linux.intelRdt.closID` of the sandbox spec if any, an existing group set up
+/// by the admin if no schemata is given. Otherwise the group of the sandbox is created with the
+/// schemata of the spec, or the `rdt_schemata` of the runtime config which may be set per pod by
+/// annotation.
+#[derive(Debug, Default)]
+pub struct RdtResource {
+    root: PathBuf,
+    state: RdtState,
+    schemata: String,
+}
+
+impl RdtResource {
+    pub fn new(sid: &str, toml_config: &TomlConfig) -> Result<Self> {
+        let spec = load_oci_spec()?;
+        let intel_rdt = spec.linux.and_then(|linux| linux.intel_rdt);
+        Self::with_root(
+            Path::new(RESCTRL_ROOT),
+            sid,
+            intel_rdt.as_ref(),
+            &toml_config.runtime.rdt_schemata,
+        )
+    }
+
+    fn with_root(
+        root: &Path,
+        sid: &str,
+        intel_rdt: Option<&LinuxIntelRdt>,
+        rdt_schemata: &str,
+    ) -> Result<Self> {
+        let mut clos_id = String::new();
+        let mut schemata = rdt_schemata.to_string();
+        if let Some(rdt) = intel_rdt {
+            clos_id = rdt.clos_id.clone();
+            if schemata.trim().is_empty() {
+                schemata = format!("{}\n{}", rdt.l3_cache_schema, rdt.mem_bw_schema);
+            }
+        }
+
+        let schemata: Vec<&str> = schemata
+            .lines()
+            .map(str::trim)
+            .filter(|l| !l.is_empty())
+            .collect();
+        if let Some(line) = schemata.iter().find(|l| !l.contains(':')) {
+            return Err(anyhow!("invalid rdt schemata {:?}", line));
+        }
+        if clos_id.is_empty() && !schemata.is_empty() {
+            clos_id = format!("kata-{}", sid);
+        }
+        if clos_id.contains('/') || clos_id == "." || clos_id == ".." {
+            return Err(anyhow!("invalid rdt closID {:?}", clos_id));
+        }
+
+        Ok(Self {
+            root: root.to_path_buf(),
+            state: RdtState {
+                clos_id,
+                created: false,
+            },
+            schemata: schemata.join("\n"),
+        })
+    }
+
+    fn is_enabled(&self) -> bool {
+        !self.state.clos_id.is_empty()
+    }
+
+    fn group_path(&self) -> PathBuf {
+        self.root.join(&self.state.clos_id)
+    }
+
+    /// Set up the resctrl group, and assign the vCPU threads to it once the VM is started.
+    pub async fn setup(&mut self, h: &dyn Hypervisor) -> Result<()> {
+        if !self.is_enabled() {
+            return Ok(());
+        }
+
+        let group = self.group_path();
+        if self.schemata.is_empty() {
+            if !group.is_dir() {
+                return Err(anyhow!("rdt group {:?} doesn't exist", group));
+            }
+        } else {
+            match fs::create_dir(&group) {
+                Ok(()) => self.state.created = true,
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {}
+                Err(e) => return Err(e).context(format!("create rdt group {:?}", group)),
+            }
+            fs::write(group.join(RESCTRL_SCHEMATA), &self.schemata)
+                .with_context(|| format!("write schemata of rdt group {:?}", group))?;
+        }
+
+        info!(sl!(), "assign vcpus to rdt group {:?}", group);
+        self.assign_vcpus(h).await
+    }
+
+    /// Assign the vCPU threads to the resctrl group, the hot plugged ones start in the group of
+    /// the VMM.
+    pub async fn assign_vcpus(&self, h: &dyn Hypervisor) -> Result<()> {
+        if !self.is_enabled() {
+            return Ok(());
+        }
+
+        let tids = h.get_thread_ids().await.context("get vcpu thread ids")?;
+        self.add_tasks(tids.vcpus.values().copied())
+    }
+
+    fn add_tasks(&self, tids: impl IntoIterator<Item = u32>) -> Result<()> {
+        let tasks = self.group_path().join(RESCTRL_TASKS);
+        // resctrl takes a single task per write
+        for tid in tids {
+            OpenOptions::new()
+                .append(true)
+                .open(&tasks)
+                .and_then(|mut f| f.write_all(format!("{}\n", tid).as_bytes()))
+                .with_context(|| format!("add task {} to {:?}", tid, tasks))?;
+        }
+        Ok(())
+    }
+
+    /// Remove the resctrl group created for the sandbox, its tasks go back to the default group.
+    pub async fn delete(&self) -> Result<()> {
+        if !self.state.created {
+            return Ok(());
+        }
+
+        let group = self.group_path();
+        match fs::remove_dir(&group) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => {
+                Err(e).context(format!("remove rdt group {:?}", group))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    pub fn save(&self) -> RdtState {
+        self.state.clone()
+    }
+
+    pub fn restore(state: RdtState) -> Self {
+        Self {
+            root: PathBuf::from(RESCTRL_ROOT),
+            state,
+            schemata: String::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn intel_rdt(clos_id: &str, l3: &str, mb: &str) -> LinuxIntelRdt {
+        LinuxIntelRdt {
+            clos_id: clos_id.to_string(),
+            l3_cache_schema: l3.to_string(),
+            mem_bw_schema: mb.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_rdt_config() {
+        let root = Path::new(RESCTRL_ROOT);
+
+        let rdt = RdtResource::with_root(root, "s1", None, "").unwrap();
+        assert!(!rdt.is_enabled());
+
+        // the group of the sandbox with the schemata of the spec
+        let spec = intel_rdt("", "L3:0=ff;1=ff", "MB:0=50");
+        let rdt = RdtResource::with_root(root, "s1", Some(&spec), "").unwrap();
+        assert_eq!(rdt.state.clos_id, "kata-s1");
+        assert_eq!(rdt.schemata, "L3:0=ff;1=ff\nMB:0=50");
+
+        // the config overrides the schemata of the spec
+        let spec = intel_rdt("gold", "L3:0=ff", "");
+        let rdt = RdtResource::with_root(root, "s1", Some(&spec), "MB:0=20\n").unwrap();
+        assert_eq!(rdt.state.clos_id, "gold");
+        assert_eq!(rdt.schemata, "MB:0=20");
+
+        // an existing group
+        let spec = intel_rdt("gold", "", "");
+        let rdt = RdtResource::with_root(root, "s1", Some(&spec), "").unwrap();
+        assert_eq!(rdt.state.clos_id, "gold");
+        assert!(rdt.schemata.is_empty());
+
+        assert!(RdtResource::with_root(root, "s1", None, "L3=ff").is_err());
+        let spec = intel_rdt("../gold", "", "");
+        assert!(RdtResource::with_root(root, "s1", Some(&spec), "").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_rdt_group() {
+        let root = tempfile::tempdir().unwrap();
+        let mut rdt = RdtResource::with_root(root.path(), "s1", None, "L3:0=f").unwrap();
+        let group = rdt.group_path();
+
+        fs::create_dir(&group).unwrap();
+        fs::write(group.join(RESCTRL_TASKS), "").unwrap();
+        rdt.add_tasks(vec![101, 102]).unwrap();
+        assert_eq!(
+            fs::read_to_string(group.join(RESCTRL_TASKS)).unwrap(),
+            "101\n102\n"
+        );
+
+        // a group not created for the sandbox is kept
+        rdt.delete().await.unwrap();
+        assert!(group.is_dir());
+
+        fs::remove_file(group.join(RESCTRL_TASKS)).unwrap();
+        rdt.state.created = true;
+        rdt.delete().await.unwrap();
+        assert!(!group.exists());
+        // removed already
+        rdt.delete().await.unwrap();
+    }
+}
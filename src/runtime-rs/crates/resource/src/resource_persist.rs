@@ -8,8 +8,14 @@ use crate::network::EndpointState;
 use serde::{Deserialize, Serialize};
 
 use crate::cgroups::cgroup_persist::CgroupState;
+use crate::rdt::RdtState;
+use crate::vsock_port::VsockPortAllocator;
 #[derive(Serialize, Deserialize, Default)]
 pub struct ResourceState {
     pub endpoint: Vec<EndpointState>,
     pub cgroup_state: Option<CgroupState>,
+    #[serde(default)]
+    pub vsock_ports: Option<VsockPortAllocator>,
+    #[serde(default)]
+    pub rdt_state: Option<RdtState>,
 }
@@ -16,11 +16,16 @@ use hypervisor::{
     },
     BlockConfig,
 };
-use kata_types::mount::Mount;
+use kata_types::mount::{Mount, MountPropagation};
 use nix::sys::stat::{self, SFlag};
 use std::fs;
 use tokio::sync::RwLock;
 
+const XFS_FS_TYPE: &str = "xfs";
+// the snapshots of devmapper are copies of the same thin device, all with
+// the UUID of its filesystem, which xfs refuses to mount twice
+const XFS_NOUUID_OPTION: &str = "nouuid";
+
 pub(crate) struct BlockRootfs {
     guest_path: String,
     device_id: String,
@@ -49,6 +54,7 @@ impl BlockRootfs {
             major: stat::major(dev_id) as i64,
             minor: stat::minor(dev_id) as i64,
             driver_option: block_driver,
+            is_readonly: rootfs.read_only,
             ..Default::default()
         };
 
@@ -60,16 +66,18 @@ impl BlockRootfs {
         let mut storage = Storage {
             fs_type: rootfs.fs_type.clone(),
             mount_point: container_path.clone(),
-            options: rootfs.options.clone(),
+            options: guest_storage_options(rootfs),
             ..Default::default()
         };
 
-        let mut device_id: String = "".to_owned();
-        if let DeviceType::Block(device) = device_info {
-            storage.driver = device.config.driver_option;
-            storage.source = device.config.virt_path;
-            device_id = device.device_id;
-        }
+        let device_id = match device_info {
+            DeviceType::Block(device) => {
+                storage.driver = device.config.driver_option;
+                storage.source = device.config.virt_path;
+                device.device_id
+            }
+            _ => return Err(anyhow!("rootfs device {} is not a block device", dev_id)),
+        };
 
         Ok(Self {
             guest_path: container_path.clone(),
@@ -82,6 +90,22 @@ impl BlockRootfs {
     }
 }
 
+// guest_storage_options returns the options of the rootfs the device is mounted with in the
+// guest, the propagation would make the agent change the propagation of the mount point
+// instead of mounting the device, the one of the rootfs is set by the spec
+fn guest_storage_options(rootfs: &Mount) -> Vec<String> {
+    let mut options: Vec<String> = rootfs
+        .options
+        .iter()
+        .filter(|o| MountPropagation::from_option(o).is_none())
+        .cloned()
+        .collect();
+    if rootfs.fs_type == XFS_FS_TYPE && !options.iter().any(|o| o == XFS_NOUUID_OPTION) {
+        options.push(XFS_NOUUID_OPTION.to_string());
+    }
+    options
+}
+
 #[async_trait]
 impl Rootfs for BlockRootfs {
     async fn get_guest_rootfs_path(&self) -> Result<String> {
@@ -124,3 +148,37 @@ pub(crate) fn is_block_rootfs(file: &str) -> Option<u64> {
     };
     None
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_guest_storage_options() {
+        let rootfs = Mount {
+            options: vec![
+                "ro".to_string(),
+                "rshared".to_string(),
+                "noatime".to_string(),
+            ],
+            ..Default::default()
+        };
+        assert_eq!(guest_storage_options(&rootfs), vec!["ro", "noatime"]);
+
+        let rootfs = Mount {
+            fs_type: XFS_FS_TYPE.to_string(),
+            options: vec!["ro".to_string()],
+            ..Default::default()
+        };
+        assert_eq!(
+            guest_storage_options(&rootfs),
+            vec!["ro", XFS_NOUUID_OPTION]
+        );
+        let rootfs = Mount {
+            fs_type: XFS_FS_TYPE.to_string(),
+            options: vec![XFS_NOUUID_OPTION.to_string()],
+            ..Default::default()
+        };
+        assert_eq!(guest_storage_options(&rootfs), vec![XFS_NOUUID_OPTION]);
+    }
+}
@@ -0,0 +1,55 @@
+// Copyright (c) 2019-2022 Alibaba Cloud
+// Copyright (c) 2019-2022 Ant Group
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+use agent::Storage;
+use anyhow::Result;
+use async_trait::async_trait;
+use hypervisor::device::device_manager::DeviceManager;
+use tokio::sync::RwLock;
+
+use super::Rootfs;
+
+/// Path of the pause bundle rootfs provided by the guest image.
+///
+/// The guest image ships a static pause binary under this directory, so the
+/// sandbox container could be started without sharing any rootfs from host.
+pub const BUILTIN_PAUSE_ROOTFS_PATH: &str = "/pause_bundle/rootfs";
+
+pub(crate) struct BuiltinPauseRootfs {
+    guest_path: String,
+}
+
+impl BuiltinPauseRootfs {
+    pub fn new() -> Self {
+        Self {
+            guest_path: BUILTIN_PAUSE_ROOTFS_PATH.to_string(),
+        }
+    }
+}
+
+#[async_trait]
+impl Rootfs for BuiltinPauseRootfs {
+    async fn get_guest_rootfs_path(&self) -> Result<String> {
+        Ok(self.guest_path.clone())
+    }
+
+    async fn get_rootfs_mount(&self) -> Result<Vec<oci::Mount>> {
+        Ok(vec![])
+    }
+
+    async fn get_storage(&self) -> Option<Storage> {
+        None
+    }
+
+    async fn get_device_id(&self) -> Result<Option<String>> {
+        Ok(None)
+    }
+
+    async fn cleanup(&self, _device_manager: &RwLock<DeviceManager>) -> Result<()> {
+        // Nothing is shared from host, the pause bundle lives in the guest image.
+        Ok(())
+    }
+}
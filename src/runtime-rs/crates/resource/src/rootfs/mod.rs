@@ -4,6 +4,7 @@
 // SPDX-License-Identifier: Apache-2.0
 //
 
+mod builtin_pause_rootfs;
 mod nydus_rootfs;
 mod share_fs_rootfs;
 use agent::Storage;
@@ -17,11 +18,15 @@ use tokio::sync::RwLock;
 
 use crate::share_fs::ShareFs;
 
+pub use self::builtin_pause_rootfs::BUILTIN_PAUSE_ROOTFS_PATH;
 use self::{block_rootfs::is_block_rootfs, nydus_rootfs::NYDUS_ROOTFS_TYPE};
 
 const ROOTFS: &str = "rootfs";
 const HYBRID_ROOTFS_LOWER_DIR: &str = "rootfs_lower";
 const TYPE_OVERLAY_FS: &str = "overlay";
+// the upper dir of the rootfs overlay is shared read-only next to the rootfs,
+// for the agent to report the usage of the writable layer
+const ROOTFS_UPPER: &str = "rootfs-upper";
 #[async_trait]
 pub trait Rootfs: Send + Sync {
     async fn get_guest_rootfs_path(&self) -> Result<String>;
@@ -87,6 +92,10 @@ impl RootFsResource {
             mounts_vec if is_single_layer_rootfs(mounts_vec) => {
                 // Safe as single_layer_rootfs must have one layer
                 let layer = &mounts_vec[0];
+                // the ids of the files would be shared with the guest unmapped
+                if layer.is_idmapped() {
+                    return Err(anyhow!("idmapped rootfs {} is not supported", layer.source));
+                }
                 let mut inner = self.inner.write().await;
                 let rootfs = if let Some(dev_id) = is_block_rootfs(&layer.source) {
                     // handle block rootfs
@@ -133,6 +142,15 @@ impl RootFsResource {
         }
     }
 
+    /// Create the rootfs for the sandbox (pause) container which is provided
+    /// by the guest image, no host rootfs is shared into the guest.
+    pub async fn handler_builtin_pause_rootfs(&self) -> Result<Arc<dyn Rootfs>> {
+        let rootfs: Arc<dyn Rootfs> = Arc::new(builtin_pause_rootfs::BuiltinPauseRootfs::new());
+        let mut inner = self.inner.write().await;
+        inner.rootfs.push(rootfs.clone());
+        Ok(rootfs)
+    }
+
     pub async fn dump(&self) {
         let inner = self.inner.read().await;
         for r in &inner.rootfs {
@@ -9,18 +9,19 @@ use std::sync::Arc;
 use agent::Storage;
 use anyhow::{Context, Result};
 use async_trait::async_trait;
-use hypervisor::device::device_manager::DeviceManager;
+use hypervisor::{device::device_manager::DeviceManager, utils::block_in_place};
 use kata_sys_util::mount::{umount_timeout, Mounter};
 use kata_types::mount::Mount;
 use tokio::sync::RwLock;
 
-use super::{Rootfs, ROOTFS};
+use super::{Rootfs, ROOTFS, ROOTFS_UPPER, TYPE_OVERLAY_FS};
 use crate::share_fs::{ShareFs, ShareFsRootfsConfig};
 
 pub(crate) struct ShareFsRootfs {
     guest_path: String,
     share_fs: Arc<dyn ShareFs>,
     config: ShareFsRootfsConfig,
+    upper_config: Option<ShareFsRootfsConfig>,
 }
 
 impl ShareFsRootfs {
@@ -32,7 +33,7 @@ impl ShareFsRootfs {
     ) -> Result<Self> {
         let bundle_rootfs = if let Some(rootfs) = rootfs {
             let bundle_rootfs = format!("{}/{}", bundle_path, ROOTFS);
-            rootfs.mount(&bundle_rootfs).context(format!(
+            block_in_place(|| rootfs.mount(&bundle_rootfs)).context(format!(
                 "mount rootfs from {:?} to {}",
                 &rootfs, &bundle_rootfs
             ))?;
@@ -46,7 +47,8 @@ impl ShareFsRootfs {
             cid: cid.to_string(),
             source: bundle_rootfs.to_string(),
             target: ROOTFS.to_string(),
-            readonly: false,
+            // the read-only snapshots are shared read-only too
+            readonly: rootfs.map_or(false, |r| r.read_only),
             is_rafs: false,
         };
 
@@ -55,10 +57,35 @@ impl ShareFsRootfs {
             .await
             .context("share rootfs")?;
 
+        let upper_config = match rootfs.and_then(overlay_upper_dir) {
+            Some(upper_dir) => {
+                let upper_config = ShareFsRootfsConfig {
+                    cid: cid.to_string(),
+                    source: upper_dir.to_string(),
+                    target: ROOTFS_UPPER.to_string(),
+                    readonly: true,
+                    is_rafs: false,
+                };
+                // only the usage of the writable layer is missed without it
+                match share_fs_mount.share_rootfs(&upper_config).await {
+                    Ok(_) => Some(upper_config),
+                    Err(e) => {
+                        warn!(
+                            sl!(),
+                            "failed to share rootfs upper dir {}: {:?}", upper_dir, e
+                        );
+                        None
+                    }
+                }
+            }
+            None => None,
+        };
+
         Ok(ShareFsRootfs {
             guest_path: mount_result.guest_path,
             share_fs: Arc::clone(share_fs),
             config,
+            upper_config,
         })
     }
 }
@@ -84,13 +111,58 @@ impl Rootfs for ShareFsRootfs {
     async fn cleanup(&self, _device_manager: &RwLock<DeviceManager>) -> Result<()> {
         // Umount the mount point shared to guest
         let share_fs_mount = self.share_fs.get_share_fs_mount();
+        if let Some(upper_config) = &self.upper_config {
+            share_fs_mount
+                .umount_rootfs(upper_config)
+                .await
+                .context("umount shared rootfs upper dir")?;
+        }
         share_fs_mount
             .umount_rootfs(&self.config)
             .await
             .context("umount shared rootfs")?;
 
         // Umount the bundle rootfs
-        umount_timeout(&self.config.source, 0).context("umount bundle rootfs")?;
+        block_in_place(|| umount_timeout(&self.config.source, 0))
+            .context("umount bundle rootfs")?;
         Ok(())
     }
 }
+
+// overlay_upper_dir returns the upper dir of the rootfs overlay, the writable
+// layer of the container
+fn overlay_upper_dir(rootfs: &Mount) -> Option<&str> {
+    if rootfs.fs_type != TYPE_OVERLAY_FS {
+        return None;
+    }
+    rootfs
+        .options
+        .iter()
+        .find_map(|o| o.strip_prefix("upperdir="))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_overlay_upper_dir() {
+        let mut rootfs = Mount {
+            fs_type: TYPE_OVERLAY_FS.to_string(),
+            options: vec![
+                "lowerdir=/l1:/l2".to_string(),
+                "upperdir=/snapshots/3/fs".to_string(),
+                "workdir=/snapshots/3/work".to_string(),
+            ],
+            ..Default::default()
+        };
+        assert_eq!(overlay_upper_dir(&rootfs), Some("/snapshots/3/fs"));
+
+        // read-only rootfs
+        rootfs.options.retain(|o| o.starts_with("lowerdir="));
+        assert_eq!(overlay_upper_dir(&rootfs), None);
+
+        rootfs.fs_type = "ext4".to_string();
+        assert_eq!(overlay_upper_dir(&rootfs), None);
+    }
+}
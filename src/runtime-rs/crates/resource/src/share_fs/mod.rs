@@ -8,7 +8,9 @@ mod share_virtio_fs;
 pub use share_virtio_fs::rafs_mount;
 mod share_virtio_fs_inline;
 use share_virtio_fs_inline::ShareVirtioFsInline;
+#[cfg(feature = "virtiofsd")]
 mod share_virtio_fs_standalone;
+#[cfg(feature = "virtiofsd")]
 use share_virtio_fs_standalone::ShareVirtioFsStandalone;
 mod utils;
 use tokio::sync::Mutex;
@@ -49,6 +51,10 @@ pub trait ShareFs: Send + Sync {
     async fn setup_device_after_start_vm(&self, h: &dyn Hypervisor) -> Result<()>;
     async fn get_storages(&self) -> Result<Vec<Storage>>;
     fn mounted_info_set(&self) -> Arc<Mutex<HashMap<String, MountedInfo>>>;
+    /// Stop the daemon serving the share fs if any, and wait for it to exit.
+    async fn stop_daemon(&self) -> Result<()>;
+    /// Kill the daemon serving the share fs if any.
+    async fn kill_daemon(&self) -> Result<()>;
 }
 
 #[derive(Debug, Clone)]
@@ -145,9 +151,15 @@ pub fn new(id: &str, config: &SharedFsInfo) -> Result<Arc<dyn ShareFs>> {
         INLINE_VIRTIO_FS => Ok(Arc::new(
             ShareVirtioFsInline::new(id, config).context("new inline virtio fs")?,
         )),
+        #[cfg(feature = "virtiofsd")]
         VIRTIO_FS => Ok(Arc::new(
             ShareVirtioFsStandalone::new(id, config).context("new standalone virtio fs")?,
         )),
+        #[cfg(not(feature = "virtiofsd"))]
+        VIRTIO_FS => Err(anyhow!(
+            "shared fs {} is not supported by this build, enable the virtiofsd feature",
+            VIRTIO_FS
+        )),
         _ => Err(anyhow!("unsupported shred fs {:?}", &shared_fs)),
     }
 }
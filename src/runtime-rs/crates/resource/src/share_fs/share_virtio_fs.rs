@@ -17,7 +17,7 @@ use hypervisor::{
     },
     Hypervisor, ShareFsDeviceConfig,
 };
-use kata_sys_util::mount;
+use kata_sys_util::{mount, naming};
 use nix::mount::MsFlags;
 
 use super::{utils, PASSTHROUGH_FS_DIR};
@@ -26,12 +26,28 @@ pub(crate) const MOUNT_GUEST_TAG: &str = "kataShared";
 
 pub(crate) const FS_TYPE_VIRTIO_FS: &str = "virtiofs";
 pub(crate) const KATA_VIRTIO_FS_DEV_TYPE: &str = "virtio-fs";
+// the guest maps the files in the DAX window instead of caching them
+const VIRTIO_FS_DAX_OPTION: &str = "dax";
+
+// shared_dir_options returns the options of the shared directory mounted in
+// the guest.
+pub(crate) fn shared_dir_options(is_dax: bool) -> Vec<String> {
+    let mut options = vec![String::from("nodev")];
+    if is_dax {
+        options.push(String::from(VIRTIO_FS_DAX_OPTION));
+    }
+    options
+}
 
+#[cfg(feature = "virtiofsd")]
 const VIRTIO_FS_SOCKET: &str = "virtiofsd.sock";
 
-pub(crate) fn generate_sock_path(root: &str) -> String {
+#[cfg(feature = "virtiofsd")]
+pub(crate) fn generate_sock_path(root: &str) -> Result<String> {
     let socket_path = Path::new(root).join(VIRTIO_FS_SOCKET);
-    socket_path.to_str().unwrap().to_string()
+    let socket_path = socket_path.to_str().unwrap().to_string();
+    naming::check_socket_path(&socket_path).context("check virtiofsd socket path")?;
+    Ok(socket_path)
 }
 
 pub(crate) async fn prepare_virtiofs(
@@ -51,7 +67,7 @@ pub(crate) async fn prepare_virtiofs(
 
     let share_fs_device = ShareFsDevice {
         config: ShareFsDeviceConfig {
-            sock_path: generate_sock_path(root),
+            sock_path: generate_sock_path(root)?,
             mount_tag: String::from(MOUNT_GUEST_TAG),
             host_path: String::from(host_ro_dest.to_str().unwrap()),
             fs_type: fs_type.to_string(),
@@ -121,3 +137,17 @@ pub async fn rafs_mount(
         .with_context(|| format!("fail to attach rafs {:?}", rafs_meta))?;
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_shared_dir_options() {
+        assert_eq!(shared_dir_options(false), vec!["nodev"]);
+        assert_eq!(
+            shared_dir_options(true),
+            vec!["nodev", VIRTIO_FS_DAX_OPTION]
+        );
+    }
+}
@@ -15,19 +15,17 @@ use tokio::sync::Mutex;
 
 use super::{
     share_virtio_fs::{
-        prepare_virtiofs, setup_inline_virtiofs, FS_TYPE_VIRTIO_FS, KATA_VIRTIO_FS_DEV_TYPE,
-        MOUNT_GUEST_TAG,
+        prepare_virtiofs, setup_inline_virtiofs, shared_dir_options, FS_TYPE_VIRTIO_FS,
+        KATA_VIRTIO_FS_DEV_TYPE, MOUNT_GUEST_TAG,
     },
     ShareFs, *,
 };
 
-lazy_static! {
-    pub(crate) static ref SHARED_DIR_VIRTIO_FS_OPTIONS: Vec::<String> = vec![String::from("nodev")];
-}
-
 #[derive(Debug, Clone)]
 pub struct ShareVirtioFsInlineConfig {
     pub id: String,
+    // virtio_fs_is_dax maps the shared files in the DAX window
+    pub virtio_fs_is_dax: bool,
 }
 
 pub struct ShareVirtioFsInline {
@@ -37,9 +35,12 @@ pub struct ShareVirtioFsInline {
 }
 
 impl ShareVirtioFsInline {
-    pub(crate) fn new(id: &str, _config: &SharedFsInfo) -> Result<Self> {
+    pub(crate) fn new(id: &str, config: &SharedFsInfo) -> Result<Self> {
         Ok(Self {
-            config: ShareVirtioFsInlineConfig { id: id.to_string() },
+            config: ShareVirtioFsInlineConfig {
+                id: id.to_string(),
+                virtio_fs_is_dax: config.virtio_fs_is_dax,
+            },
             share_fs_mount: Arc::new(VirtiofsShareMount::new(id)),
             mounted_info_set: Arc::new(Mutex::new(HashMap::new())),
         })
@@ -75,7 +76,7 @@ impl ShareFs for ShareVirtioFsInline {
             source: String::from(MOUNT_GUEST_TAG),
             fs_type: String::from(FS_TYPE_VIRTIO_FS),
             fs_group: None,
-            options: SHARED_DIR_VIRTIO_FS_OPTIONS.clone(),
+            options: shared_dir_options(self.config.virtio_fs_is_dax),
             mount_point: String::from(KATA_GUEST_SHARE_DIR),
         };
 
@@ -86,4 +87,13 @@ impl ShareFs for ShareVirtioFsInline {
     fn mounted_info_set(&self) -> Arc<Mutex<HashMap<String, MountedInfo>>> {
         self.mounted_info_set.clone()
     }
+
+    async fn stop_daemon(&self) -> Result<()> {
+        // virtio-fs is served by the VMM itself
+        Ok(())
+    }
+
+    async fn kill_daemon(&self) -> Result<()> {
+        Ok(())
+    }
 }
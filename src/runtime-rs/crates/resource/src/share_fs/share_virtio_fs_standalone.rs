@@ -4,10 +4,11 @@
 // SPDX-License-Identifier: Apache-2.0
 //
 
-use std::{collections::HashMap, process::Stdio, sync::Arc};
+use std::{collections::HashMap, process::Stdio, sync::Arc, time::Duration};
 
 use crate::share_fs::share_virtio_fs::{
-    prepare_virtiofs, FS_TYPE_VIRTIO_FS, KATA_VIRTIO_FS_DEV_TYPE, MOUNT_GUEST_TAG,
+    prepare_virtiofs, shared_dir_options, FS_TYPE_VIRTIO_FS, KATA_VIRTIO_FS_DEV_TYPE,
+    MOUNT_GUEST_TAG,
 };
 use crate::share_fs::{KATA_GUEST_SHARE_DIR, VIRTIO_FS};
 use agent::Storage;
@@ -29,6 +30,8 @@ use super::{
     virtio_fs_share_mount::VirtiofsShareMount, MountedInfo, ShareFs, ShareFsMount,
 };
 
+const VIRTIOFSD_EXIT_POLL_INTERVAL: Duration = Duration::from_millis(20);
+
 #[derive(Debug, Clone)]
 pub struct ShareVirtioFsStandaloneConfig {
     id: String,
@@ -37,6 +40,10 @@ pub struct ShareVirtioFsStandaloneConfig {
     pub virtio_fs_daemon: String,
     // virtio_fs_cache cache mode for fs version cache
     pub virtio_fs_cache: String,
+    // virtio_fs_thread_pool_size is the size of the thread pool of virtiofsd, its default if 0
+    pub virtio_fs_thread_pool_size: u32,
+    // virtio_fs_is_dax maps the shared files in the DAX window
+    pub virtio_fs_is_dax: bool,
     // virtio_fs_extra_args passes options to virtiofsd daemon
     pub virtio_fs_extra_args: Vec<String>,
 }
@@ -61,6 +68,8 @@ impl ShareVirtioFsStandalone {
                 id: id.to_string(),
                 virtio_fs_daemon: config.virtio_fs_daemon.clone(),
                 virtio_fs_cache: config.virtio_fs_cache.clone(),
+                virtio_fs_thread_pool_size: config.virtio_fs_thread_pool_size,
+                virtio_fs_is_dax: config.virtio_fs_is_dax,
                 virtio_fs_extra_args: config.virtio_fs_extra_args.clone(),
             },
             share_fs_mount: Arc::new(VirtiofsShareMount::new(id)),
@@ -87,7 +96,14 @@ impl ShareVirtioFsStandalone {
             String::from("--seccomp"),
             String::from("none"),
         ];
+        if self.config.virtio_fs_thread_pool_size > 0 {
+            args.push(format!(
+                "--thread-pool-size={}",
+                self.config.virtio_fs_thread_pool_size
+            ));
+        }
 
+        // the extra args come last to override the options above
         if !self.config.virtio_fs_extra_args.is_empty() {
             let mut extra_args: Vec<String> = self.config.virtio_fs_extra_args.clone();
             args.append(&mut extra_args);
@@ -97,7 +113,7 @@ impl ShareVirtioFsStandalone {
     }
 
     async fn setup_virtiofsd(&self, h: &dyn Hypervisor) -> Result<()> {
-        let sock_path = generate_sock_path(&h.get_jailer_root().await?);
+        let sock_path = generate_sock_path(&h.get_jailer_root().await?)?;
         let args = self.virtiofsd_args(&sock_path).context("virtiofsd args")?;
 
         let mut cmd = Command::new(&self.config.virtio_fs_daemon);
@@ -145,6 +161,28 @@ impl ShareVirtioFsStandalone {
 
         Ok(())
     }
+
+    async fn stop_virtiofsd(&self) -> Result<()> {
+        let pid = match self.inner.read().await.pid {
+            Some(pid) => ::nix::unistd::Pid::from_raw(pid as i32),
+            None => return Ok(()),
+        };
+
+        info!(sl!(), "stop virtiofsd pid {}", pid);
+        match ::nix::sys::signal::kill(pid, nix::sys::signal::SIGTERM) {
+            Err(::nix::Error::ESRCH) => return Ok(()),
+            Err(err) => return Err(anyhow!("failed to stop virtiofsd pid {} {}", pid, err)),
+            Ok(_) => {}
+        }
+
+        // the pid is gone once virtiofsd is reaped by run_virtiofsd()
+        while ::nix::sys::signal::kill(pid, None).is_ok() {
+            tokio::time::sleep(VIRTIOFSD_EXIT_POLL_INTERVAL).await;
+        }
+        self.inner.write().await.pid = None;
+
+        Ok(())
+    }
 }
 
 async fn run_virtiofsd(mut child: Child, tx: Sender<Result<()>>) -> Result<()> {
@@ -193,7 +231,7 @@ impl ShareFs for ShareVirtioFsStandalone {
             source: String::from(MOUNT_GUEST_TAG),
             fs_type: String::from(FS_TYPE_VIRTIO_FS),
             fs_group: None,
-            options: vec![String::from("nodev")],
+            options: shared_dir_options(self.config.virtio_fs_is_dax),
             mount_point: String::from(KATA_GUEST_SHARE_DIR),
         };
 
@@ -204,4 +242,14 @@ impl ShareFs for ShareVirtioFsStandalone {
     fn mounted_info_set(&self) -> Arc<Mutex<HashMap<String, MountedInfo>>> {
         self.mounted_info_set.clone()
     }
+
+    async fn stop_daemon(&self) -> Result<()> {
+        self.stop_virtiofsd().await.context("stop virtiofsd")
+    }
+
+    async fn kill_daemon(&self) -> Result<()> {
+        self.shutdown_virtiofsd()
+            .await
+            .context("shutdown virtiofsd")
+    }
 }
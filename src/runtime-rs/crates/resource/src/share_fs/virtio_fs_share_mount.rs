@@ -7,6 +7,7 @@
 use agent::Storage;
 use anyhow::{anyhow, Context, Result};
 use async_trait::async_trait;
+use hypervisor::utils::block_in_place;
 use kata_sys_util::mount::{bind_remount, umount_all, umount_timeout};
 use kata_types::k8s::is_watchable_mount;
 use kata_types::mount;
@@ -43,15 +44,17 @@ impl VirtiofsShareMount {
 impl ShareFsMount for VirtiofsShareMount {
     async fn share_rootfs(&self, config: &ShareFsRootfsConfig) -> Result<ShareFsMountResult> {
         // TODO: select virtiofs or support nydus
-        let guest_path = utils::share_to_guest(
-            &config.source,
-            &config.target,
-            &self.id,
-            &config.cid,
-            config.readonly,
-            false,
-            config.is_rafs,
-        )
+        let guest_path = block_in_place(|| {
+            utils::share_to_guest(
+                &config.source,
+                &config.target,
+                &self.id,
+                &config.cid,
+                config.readonly,
+                false,
+                config.is_rafs,
+            )
+        })
         .context("share to guest")?;
         Ok(ShareFsMountResult {
             guest_path,
@@ -60,15 +63,17 @@ impl ShareFsMount for VirtiofsShareMount {
     }
 
     async fn share_volume(&self, config: &ShareFsVolumeConfig) -> Result<ShareFsMountResult> {
-        let mut guest_path = utils::share_to_guest(
-            &config.source,
-            &config.target,
-            &self.id,
-            &config.cid,
-            config.readonly,
-            true,
-            config.is_rafs,
-        )
+        let mut guest_path = block_in_place(|| {
+            utils::share_to_guest(
+                &config.source,
+                &config.target,
+                &self.id,
+                &config.cid,
+                config.readonly,
+                true,
+                config.is_rafs,
+            )
+        })
         .context("share to guest")?;
 
         // watchable mounts
@@ -174,11 +179,11 @@ impl ShareFsMount for VirtiofsShareMount {
     async fn upgrade_to_rw(&self, file_name: &str) -> Result<()> {
         // Remount readonly directory with readwrite permission
         let host_dest = do_get_host_path(file_name, &self.id, "", true, true);
-        bind_remount(host_dest, false)
+        block_in_place(|| bind_remount(host_dest, false))
             .context("remount readonly directory with readwrite permission")?;
         // Remount readwrite directory with readwrite permission
         let host_dest = do_get_host_path(file_name, &self.id, "", true, false);
-        bind_remount(host_dest, false)
+        block_in_place(|| bind_remount(host_dest, false))
             .context("remount readwrite directory with readwrite permission")?;
         Ok(())
     }
@@ -186,18 +191,18 @@ impl ShareFsMount for VirtiofsShareMount {
     async fn downgrade_to_ro(&self, file_name: &str) -> Result<()> {
         // Remount readwrite directory with readonly permission
         let host_dest = do_get_host_path(file_name, &self.id, "", true, false);
-        bind_remount(host_dest, true)
+        block_in_place(|| bind_remount(host_dest, true))
             .context("remount readwrite directory with readonly permission")?;
         // Remount readonly directory with readonly permission
         let host_dest = do_get_host_path(file_name, &self.id, "", true, true);
-        bind_remount(host_dest, true)
+        block_in_place(|| bind_remount(host_dest, true))
             .context("remount readonly directory with readonly permission")?;
         Ok(())
     }
 
     async fn umount_volume(&self, file_name: &str) -> Result<()> {
         let host_dest = do_get_host_path(file_name, &self.id, "", true, false);
-        umount_timeout(&host_dest, 0).context("umount volume")?;
+        block_in_place(|| umount_timeout(&host_dest, 0)).context("umount volume")?;
         // Umount event will be propagated to ro directory
 
         // Remove the directory of mointpoint
@@ -214,7 +219,7 @@ impl ShareFsMount for VirtiofsShareMount {
 
     async fn umount_rootfs(&self, config: &ShareFsRootfsConfig) -> Result<()> {
         let host_dest = do_get_host_path(&config.target, &self.id, &config.cid, false, false);
-        umount_timeout(&host_dest, 0).context("umount rootfs")?;
+        block_in_place(|| umount_timeout(&host_dest, 0)).context("umount rootfs")?;
 
         // Remove the directory of mointpoint
         if let Ok(md) = fs::metadata(&host_dest) {
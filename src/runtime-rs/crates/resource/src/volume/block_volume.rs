@@ -4,12 +4,18 @@
 // SPDX-License-Identifier: Apache-2.0
 //
 
+use std::collections::HashMap;
+
 use anyhow::{anyhow, Context, Result};
 use async_trait::async_trait;
+use kata_types::annotations::{
+    KATA_ANNO_CONTAINER_VOLUME_BLOCK_CACHE_MODE, KATA_ANNO_CONTAINER_VOLUME_BLOCK_READONLY,
+};
 use nix::sys::{stat, stat::SFlag};
 use tokio::sync::RwLock;
 
 use super::Volume;
+use crate::share_fs::DEFAULT_KATA_GUEST_SANDBOX_DIR;
 use crate::volume::utils::{
     generate_shared_path, get_direct_volume_path, volume_mount_info, DEFAULT_VOLUME_FS_TYPE,
     KATA_DIRECT_VOLUME_TYPE, KATA_MOUNT_BIND_TYPE,
@@ -22,6 +28,63 @@ use hypervisor::{
     BlockConfig,
 };
 
+const CACHE_MODE_WRITEBACK: &str = "writeback";
+const CACHE_MODE_NONE: &str = "none";
+
+// the block volumes are mounted in the guest under the sandbox directory, as they may be used by
+// several containers
+const KATA_GUEST_SANDBOX_VOLUMES_DIR: &str = "volumes";
+
+fn sandbox_volume_path(device_id: &str) -> String {
+    format!(
+        "{}{}/{}",
+        DEFAULT_KATA_GUEST_SANDBOX_DIR, KATA_GUEST_SANDBOX_VOLUMES_DIR, device_id
+    )
+}
+
+/// Per volume attach options of block volume, specified by container annotations.
+#[derive(Debug, Default, PartialEq)]
+pub(crate) struct BlockVolumeOptions {
+    /// Whether to bypass the host page cache, none means using the hypervisor default.
+    pub(crate) is_direct: Option<bool>,
+    /// Whether to attach the block device as read-only.
+    pub(crate) read_only: bool,
+}
+
+impl BlockVolumeOptions {
+    pub(crate) fn new(annotations: &HashMap<String, String>, destination: &str) -> Result<Self> {
+        let mut options = Self::default();
+
+        if let Some(value) = annotations.get(KATA_ANNO_CONTAINER_VOLUME_BLOCK_CACHE_MODE) {
+            for item in value.split(',').map(str::trim).filter(|i| !i.is_empty()) {
+                let (dest, mode) = item
+                    .rsplit_once('=')
+                    .ok_or_else(|| anyhow!("invalid block volume cache mode {:?}", item))?;
+                if dest != destination {
+                    continue;
+                }
+                options.is_direct = match mode {
+                    CACHE_MODE_WRITEBACK => Some(false),
+                    CACHE_MODE_NONE => Some(true),
+                    _ => {
+                        return Err(anyhow!(
+                            "unsupported cache mode {:?} for block volume {}",
+                            mode,
+                            dest
+                        ))
+                    }
+                };
+            }
+        }
+
+        if let Some(value) = annotations.get(KATA_ANNO_CONTAINER_VOLUME_BLOCK_READONLY) {
+            options.read_only = value.split(',').map(str::trim).any(|d| d == destination);
+        }
+
+        Ok(options)
+    }
+}
+
 #[derive(Clone)]
 pub(crate) struct BlockVolume {
     storage: Option<agent::Storage>,
@@ -35,10 +98,12 @@ impl BlockVolume {
         d: &RwLock<DeviceManager>,
         m: &oci::Mount,
         read_only: bool,
+        options: BlockVolumeOptions,
         cid: &str,
         sid: &str,
     ) -> Result<Self> {
         let mnt_src: &str = &m.source;
+        let read_only = read_only || options.read_only;
         // default block device fs type: ext4.
         let mut blk_dev_fstype = DEFAULT_VOLUME_FS_TYPE.to_string();
 
@@ -51,6 +116,8 @@ impl BlockVolume {
                 BlockConfig {
                     major: stat::major(fstat.st_rdev) as i64,
                     minor: stat::minor(fstat.st_rdev) as i64,
+                    is_readonly: read_only,
+                    is_direct: options.is_direct,
                     driver_option: block_driver,
                     ..Default::default()
                 }
@@ -80,6 +147,8 @@ impl BlockVolume {
 
                 BlockConfig {
                     path_on_host: v.device,
+                    is_readonly: read_only,
+                    is_direct: options.is_direct,
                     driver_option: block_driver,
                     ..Default::default()
                 }
@@ -92,56 +161,91 @@ impl BlockVolume {
             }
         };
 
+        Self::attach(
+            d,
+            m,
+            block_device_config,
+            blk_dev_fstype,
+            read_only,
+            cid,
+            sid,
+        )
+        .await
+    }
+
+    /// Attach the block device of `block_device_config` to the VM for the volume `m`, the file
+    /// system of the device is `blk_dev_fstype`.
+    pub(crate) async fn attach(
+        d: &RwLock<DeviceManager>,
+        m: &oci::Mount,
+        block_device_config: BlockConfig,
+        blk_dev_fstype: String,
+        read_only: bool,
+        cid: &str,
+        sid: &str,
+    ) -> Result<Self> {
+        let mnt_src: &str = &m.source;
         // create and insert block device into Kata VM
-        let device_info = do_handle_device(d, &DeviceConfig::BlockCfg(block_device_config.clone()))
+        let device_info = do_handle_device(d, &DeviceConfig::BlockCfg(block_device_config))
             .await
             .context("do handle device failed.")?;
 
-        // generate host guest shared path
-        let guest_path = generate_shared_path(m.destination.clone(), read_only, cid, sid)
-            .await
-            .context("generate host-guest shared path failed")?;
-
-        // storage
-        let mut storage = agent::Storage {
-            mount_point: guest_path.clone(),
-            ..Default::default()
-        };
-
-        storage.options = if read_only {
-            vec!["ro".to_string()]
-        } else {
-            Vec::new()
-        };
-
         // As the true Block Device wrapped in DeviceType, we need to
         // get it out from the wrapper, and the device_id will be for
         // BlockVolume.
-        // safe here, device_info is correct and only unwrap it.
-        let mut device_id = String::new();
-        if let DeviceType::Block(device) = device_info {
+        let device = match device_info {
+            DeviceType::Block(device) => device,
+            _ => return Err(anyhow!("device of volume {} isn't a block device", mnt_src)),
+        };
+
+        // storage
+        let mut storage = agent::Storage {
             // blk, mmioblk
-            storage.driver = device.config.driver_option;
+            driver: device.config.driver_option,
             // /dev/vdX
-            storage.source = device.config.virt_path;
-            device_id = device.device_id;
-        }
+            source: device.config.virt_path,
+            ..Default::default()
+        };
 
         // In some case, dest is device /dev/xxx
-        if m.destination.clone().starts_with("/dev") {
-            storage.fs_type = "bind".to_string();
+        let guest_path = if m.destination.starts_with("/dev") {
+            storage.fs_type = KATA_MOUNT_BIND_TYPE.to_string();
+            if read_only {
+                storage.options.push("ro".to_string());
+            }
             storage.options.append(&mut m.options.clone());
+
+            // generate host guest shared path
+            generate_shared_path(m.destination.clone(), read_only, cid, sid)
+                .await
+                .context("generate host-guest shared path failed")?
         } else {
-            // usually, the dest is directory.
+            // usually, the dest is directory. The file system is mounted once in the guest
+            // whichever the containers using it, and bind mounted into each of them, so that
+            // they all see the same volume. It's read-only if the device is.
             storage.fs_type = blk_dev_fstype;
-        }
+            if device.config.is_readonly {
+                storage.options.push("ro".to_string());
+            }
+            sandbox_volume_path(&device.device_id)
+        };
+        storage.mount_point = guest_path.clone();
 
+        let mut mount_options = m.options.clone();
+        if !mount_options.iter().any(|o| o == "bind" || o == "rbind") {
+            mount_options.push("rbind".to_string());
+        }
+        if read_only && !mount_options.iter().any(|o| o == "ro") {
+            mount_options.retain(|o| o != "rw");
+            mount_options.push("ro".to_string());
+        }
         let mount = oci::Mount {
             destination: m.destination.clone(),
-            r#type: storage.fs_type.clone(),
+            r#type: KATA_MOUNT_BIND_TYPE.to_string(),
             source: guest_path,
-            options: m.options.clone(),
+            options: mount_options,
         };
+        let device_id = device.device_id;
 
         Ok(Self {
             storage: Some(storage),
@@ -205,3 +309,64 @@ pub(crate) fn is_block_volume(m: &oci::Mount) -> Result<bool> {
         _ => Ok(false),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sandbox_volume_path() {
+        assert_eq!(
+            sandbox_volume_path("4a5b6c7d"),
+            "/run/kata-containers/sandbox/volumes/4a5b6c7d"
+        );
+    }
+
+    #[test]
+    fn test_block_volume_options() {
+        let mut annotations = HashMap::new();
+        assert_eq!(
+            BlockVolumeOptions::new(&annotations, "/data").unwrap(),
+            BlockVolumeOptions::default()
+        );
+
+        annotations.insert(
+            KATA_ANNO_CONTAINER_VOLUME_BLOCK_CACHE_MODE.to_string(),
+            "/data=none, /logs=writeback".to_string(),
+        );
+        annotations.insert(
+            KATA_ANNO_CONTAINER_VOLUME_BLOCK_READONLY.to_string(),
+            "/logs".to_string(),
+        );
+        assert_eq!(
+            BlockVolumeOptions::new(&annotations, "/data").unwrap(),
+            BlockVolumeOptions {
+                is_direct: Some(true),
+                read_only: false,
+            }
+        );
+        assert_eq!(
+            BlockVolumeOptions::new(&annotations, "/logs").unwrap(),
+            BlockVolumeOptions {
+                is_direct: Some(false),
+                read_only: true,
+            }
+        );
+        assert_eq!(
+            BlockVolumeOptions::new(&annotations, "/other").unwrap(),
+            BlockVolumeOptions::default()
+        );
+
+        annotations.insert(
+            KATA_ANNO_CONTAINER_VOLUME_BLOCK_CACHE_MODE.to_string(),
+            "/data=unsafe".to_string(),
+        );
+        assert!(BlockVolumeOptions::new(&annotations, "/data").is_err());
+
+        annotations.insert(
+            KATA_ANNO_CONTAINER_VOLUME_BLOCK_CACHE_MODE.to_string(),
+            "/data".to_string(),
+        );
+        assert!(BlockVolumeOptions::new(&annotations, "/data").is_err());
+    }
+}
@@ -0,0 +1,140 @@
+// Copyright (c) 2023 Kata Containers community
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, Result};
+use kata_types::config::{Runtime, SENSITIVE_HOST_PATH_REJECT, SENSITIVE_HOST_PATH_SKIP};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum HostPathAction {
+    Allow,
+    Skip,
+    Reject,
+}
+
+/// Policy of the host path volumes under the sensitive host paths, e.g. /dev
+/// and /sys, checked before the volumes are shared into the VM.
+#[derive(Debug)]
+pub(crate) struct HostPathPolicy {
+    action: HostPathAction,
+    sensitive_paths: Vec<PathBuf>,
+    allowlist: Vec<PathBuf>,
+}
+
+impl HostPathPolicy {
+    pub(crate) fn new(runtime: &Runtime) -> Self {
+        let action = match runtime.sensitive_host_path_action.as_str() {
+            SENSITIVE_HOST_PATH_SKIP => HostPathAction::Skip,
+            SENSITIVE_HOST_PATH_REJECT => HostPathAction::Reject,
+            _ => HostPathAction::Allow,
+        };
+
+        Self {
+            action,
+            sensitive_paths: runtime
+                .sensitive_host_paths
+                .iter()
+                .map(PathBuf::from)
+                .collect(),
+            allowlist: runtime
+                .sensitive_host_path_allowlist
+                .iter()
+                .map(PathBuf::from)
+                .collect(),
+        }
+    }
+
+    /// Check the mount of container `cid`, returns false if the mount should
+    /// be skipped, or an error if the container should be refused.
+    pub(crate) fn check(&self, cid: &str, m: &oci::Mount) -> Result<bool> {
+        if self.action == HostPathAction::Allow || !is_host_path(m) {
+            return Ok(true);
+        }
+
+        // the source may be a symlink into the sensitive paths
+        let source = std::fs::canonicalize(&m.source).unwrap_or_else(|_| PathBuf::from(&m.source));
+        if !self.is_sensitive(&source) {
+            return Ok(true);
+        }
+
+        match self.action {
+            HostPathAction::Skip => {
+                warn!(sl!(), "audit: skip sensitive host path volume";
+                    "container" => cid,
+                    "source" => &m.source,
+                    "destination" => &m.destination);
+                Ok(false)
+            }
+            _ => {
+                warn!(sl!(), "audit: reject sensitive host path volume";
+                    "container" => cid,
+                    "source" => &m.source,
+                    "destination" => &m.destination);
+                Err(anyhow!(
+                    "host path {} of container {} is not allowed",
+                    m.source,
+                    cid
+                ))
+            }
+        }
+    }
+
+    fn is_sensitive(&self, source: &Path) -> bool {
+        self.sensitive_paths.iter().any(|p| source.starts_with(p))
+            && !self.allowlist.iter().any(|p| source.starts_with(p))
+    }
+}
+
+fn is_host_path(m: &oci::Mount) -> bool {
+    (m.r#type == "bind" || m.options.iter().any(|o| o == "bind" || o == "rbind"))
+        && Path::new(&m.source).is_absolute()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bind_mount(source: &str) -> oci::Mount {
+        oci::Mount {
+            destination: "/data".to_string(),
+            r#type: "bind".to_string(),
+            source: source.to_string(),
+            options: vec!["rbind".to_string()],
+        }
+    }
+
+    fn policy(action: &str) -> HostPathPolicy {
+        HostPathPolicy::new(&Runtime {
+            sensitive_host_path_action: action.to_string(),
+            sensitive_host_paths: vec!["/dev".to_string(), "/sys".to_string()],
+            sensitive_host_path_allowlist: vec!["/dev/fuse".to_string()],
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn test_host_path_policy() {
+        let allow = policy("allow");
+        assert!(allow.check("c1", &bind_mount("/dev/kmsg")).unwrap());
+
+        let skip = policy(SENSITIVE_HOST_PATH_SKIP);
+        assert!(!skip.check("c1", &bind_mount("/dev/kmsg")).unwrap());
+        assert!(!skip.check("c1", &bind_mount("/sys/kernel")).unwrap());
+        // allowed explicitly
+        assert!(skip.check("c1", &bind_mount("/dev/fuse")).unwrap());
+        // not a sensitive path, /device isn't under /dev
+        assert!(skip.check("c1", &bind_mount("/device")).unwrap());
+        // not a host path
+        let mut m = bind_mount("/dev");
+        m.r#type = "tmpfs".to_string();
+        m.options = vec![];
+        assert!(skip.check("c1", &m).unwrap());
+
+        let reject = policy(SENSITIVE_HOST_PATH_REJECT);
+        assert!(reject.check("c1", &bind_mount("/dev/kmsg")).is_err());
+        assert!(reject.check("c1", &bind_mount("/var/lib/data")).unwrap());
+    }
+}
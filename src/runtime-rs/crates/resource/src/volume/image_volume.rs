@@ -0,0 +1,250 @@
+// Copyright (c) 2023 Kata Containers community
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+use std::{path::Path, sync::Arc};
+
+use agent::Agent;
+use anyhow::{anyhow, Context, Result};
+use async_trait::async_trait;
+use hypervisor::device::device_manager::DeviceManager;
+use kata_types::mount::{
+    KataVirtualVolume, KATA_VIRTUAL_VOLUME_IMAGE_GUEST_PULL, KATA_VIRTUAL_VOLUME_OPTION,
+};
+use tokio::sync::RwLock;
+
+use super::{share_fs_volume::ShareFsVolume, Volume};
+use crate::share_fs::{ShareFs, DEFAULT_KATA_GUEST_SANDBOX_DIR};
+use crate::volume::utils::KATA_MOUNT_BIND_TYPE;
+
+const PROC_SELF_MOUNTINFO: &str = "/proc/self/mountinfo";
+
+// the CRI runtime mounts the image volumes of a pod under `<state dir>/image-volumes/`, e.g.
+// `/run/containerd/io.containerd.grpc.v1.cri/image-volumes/<pod id>/<image id>`
+const CRI_IMAGE_VOLUMES_DIR: &str = "image-volumes";
+
+// the image volumes pulled in the guest are mounted under the sandbox directory, as they may be
+// used by several containers
+const KATA_GUEST_SANDBOX_IMAGE_VOLUMES_DIR: &str = "image-volumes";
+
+const GUEST_PULL_FS_TYPE: &str = "overlay";
+
+fn sandbox_image_volume_path(name: &str) -> String {
+    format!(
+        "{}{}/{}",
+        DEFAULT_KATA_GUEST_SANDBOX_DIR, KATA_GUEST_SANDBOX_IMAGE_VOLUMES_DIR, name
+    )
+}
+
+/// ImageVolume for the CRI image volumes, OCI images or artifacts mounted read-only in the
+/// containers.
+///
+/// The content unpacked in the host by the snapshotter is shared read-only with the guest.
+/// In guest-pull mode the snapshotter has nothing in the host but marks the volume mount with
+/// a `KataVirtualVolume`, so the image is pulled by the agent into a sandbox directory which is
+/// bind mounted into the containers.
+pub(crate) enum ImageVolume {
+    GuestPull {
+        storage: agent::Storage,
+        mount: oci::Mount,
+    },
+    Host(ShareFsVolume),
+}
+
+impl ImageVolume {
+    pub(crate) async fn new(
+        share_fs: &Option<Arc<dyn ShareFs>>,
+        m: &oci::Mount,
+        cid: &str,
+        agent: Arc<dyn Agent>,
+    ) -> Result<Self> {
+        let mut mount = m.clone();
+        mount.r#type = KATA_MOUNT_BIND_TYPE.to_string();
+        mount.options.retain(|o| o != "rw");
+        if !mount.options.iter().any(|o| o == "ro") {
+            mount.options.push("ro".to_string());
+        }
+
+        let mountinfo = std::fs::read_to_string(PROC_SELF_MOUNTINFO)
+            .with_context(|| format!("read {}", PROC_SELF_MOUNTINFO))?;
+        let volume = match get_virtual_volume(&mountinfo, &m.source)
+            .with_context(|| format!("get virtual volume of image volume {}", m.source))?
+        {
+            Some(volume) => volume,
+            None => {
+                let volume = ShareFsVolume::new(share_fs, &mount, cid, true, agent)
+                    .await
+                    .with_context(|| format!("share image volume {:?}", m))?;
+                return Ok(Self::Host(volume));
+            }
+        };
+
+        if volume.volume_type != KATA_VIRTUAL_VOLUME_IMAGE_GUEST_PULL {
+            return Err(anyhow!(
+                "unsupported virtual volume type {:?} for image volume {}",
+                volume.volume_type,
+                m.source
+            ));
+        }
+        volume
+            .validate()
+            .with_context(|| format!("invalid virtual volume of image volume {}", m.source))?;
+
+        let name = Path::new(&m.source)
+            .file_name()
+            .ok_or_else(|| anyhow!("invalid image volume source {}", m.source))?
+            .to_string_lossy();
+        let guest_path = sandbox_image_volume_path(&name);
+        let storage = agent::Storage {
+            driver: KATA_VIRTUAL_VOLUME_IMAGE_GUEST_PULL.to_string(),
+            driver_options: vec![format!(
+                "{}={}",
+                KATA_VIRTUAL_VOLUME_IMAGE_GUEST_PULL,
+                volume.to_json()?
+            )],
+            // the image reference
+            source: volume.source.clone(),
+            fs_type: GUEST_PULL_FS_TYPE.to_string(),
+            options: vec!["ro".to_string()],
+            mount_point: guest_path.clone(),
+            ..Default::default()
+        };
+
+        if !mount.options.iter().any(|o| o == "bind" || o == "rbind") {
+            mount.options.push("rbind".to_string());
+        }
+        mount.source = guest_path;
+
+        Ok(Self::GuestPull { storage, mount })
+    }
+}
+
+#[async_trait]
+impl Volume for ImageVolume {
+    fn get_volume_mount(&self) -> Result<Vec<oci::Mount>> {
+        match self {
+            Self::GuestPull { mount, .. } => Ok(vec![mount.clone()]),
+            Self::Host(volume) => volume.get_volume_mount(),
+        }
+    }
+
+    fn get_storage(&self) -> Result<Vec<agent::Storage>> {
+        match self {
+            Self::GuestPull { storage, .. } => Ok(vec![storage.clone()]),
+            Self::Host(volume) => volume.get_storage(),
+        }
+    }
+
+    async fn cleanup(&self, device_manager: &RwLock<DeviceManager>) -> Result<()> {
+        match self {
+            // the image is removed by the agent with the last container using it
+            Self::GuestPull { .. } => Ok(()),
+            Self::Host(volume) => volume.cleanup(device_manager).await,
+        }
+    }
+
+    fn get_device_id(&self) -> Result<Option<String>> {
+        Ok(None)
+    }
+}
+
+pub(crate) fn is_image_volume(m: &oci::Mount) -> bool {
+    m.r#type == KATA_MOUNT_BIND_TYPE
+        && Path::new(&m.source)
+            .parent()
+            .and_then(Path::parent)
+            .and_then(Path::file_name)
+            .map_or(false, |d| d == CRI_IMAGE_VOLUMES_DIR)
+}
+
+// get_virtual_volume returns the `KataVirtualVolume` set by the snapshotter in the super options
+// of the mount at mount_point, the mountinfo records are
+// `id parent major:minor root mount_point options [optional...] - fs_type source super_options`
+fn get_virtual_volume(mountinfo: &str, mount_point: &str) -> Result<Option<KataVirtualVolume>> {
+    let prefix = format!("{}=", KATA_VIRTUAL_VOLUME_OPTION);
+    // the mounts on top hide the ones below
+    let record = mountinfo.lines().rev().find_map(|line| {
+        let (mount, fs) = line.split_once(" - ")?;
+        if mount.split(' ').nth(4)? != mount_point {
+            return None;
+        }
+        fs.split(' ').nth(2)
+    });
+
+    record
+        .and_then(|options| options.split(',').find_map(|o| o.strip_prefix(&prefix)))
+        .map(KataVirtualVolume::from_base64)
+        .transpose()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bind_mount(source: &str) -> oci::Mount {
+        oci::Mount {
+            destination: "/models".to_string(),
+            r#type: KATA_MOUNT_BIND_TYPE.to_string(),
+            source: source.to_string(),
+            options: vec!["rbind".to_string(), "ro".to_string()],
+        }
+    }
+
+    #[test]
+    fn test_is_image_volume() {
+        assert!(is_image_volume(&bind_mount(
+            "/run/containerd/io.containerd.grpc.v1.cri/image-volumes/p1/sha256abcd"
+        )));
+        assert!(!is_image_volume(&bind_mount(
+            "/run/containerd/io.containerd.grpc.v1.cri/image-volumes/p1"
+        )));
+        assert!(!is_image_volume(&bind_mount(
+            "/var/lib/kubelet/pods/p1/volumes/data"
+        )));
+
+        let mut m = bind_mount("/run/containerd/io.containerd.grpc.v1.cri/image-volumes/p1/i1");
+        m.r#type = "tmpfs".to_string();
+        assert!(!is_image_volume(&m));
+    }
+
+    #[test]
+    fn test_get_virtual_volume() {
+        let mut volume = KataVirtualVolume::new(KATA_VIRTUAL_VOLUME_IMAGE_GUEST_PULL.to_string());
+        volume.source = "docker.io/library/busybox:latest".to_string();
+        let mountinfo = format!(
+            "22 1 253:1 / / rw,relatime shared:1 - ext4 /dev/vda1 rw\n\
+             30 22 0:40 / /run/image-volumes/p1/i1 ro,relatime shared:8 - fuse.nydus-overlayfs overlay ro,{}={}\n\
+             31 22 0:41 / /run/image-volumes/p1/i2 ro,relatime shared:9 - overlay overlay ro,lowerdir=/l1",
+            KATA_VIRTUAL_VOLUME_OPTION,
+            volume.to_base64().unwrap()
+        );
+
+        assert_eq!(
+            get_virtual_volume(&mountinfo, "/run/image-volumes/p1/i1").unwrap(),
+            Some(volume)
+        );
+        assert_eq!(
+            get_virtual_volume(&mountinfo, "/run/image-volumes/p1/i2").unwrap(),
+            None
+        );
+        assert_eq!(
+            get_virtual_volume(&mountinfo, "/run/image-volumes/p1").unwrap(),
+            None
+        );
+
+        let mountinfo = format!(
+            "30 22 0:40 / /i1 ro - fuse.nydus-overlayfs overlay {}=invalid",
+            KATA_VIRTUAL_VOLUME_OPTION
+        );
+        assert!(get_virtual_volume(&mountinfo, "/i1").is_err());
+    }
+
+    #[test]
+    fn test_sandbox_image_volume_path() {
+        assert_eq!(
+            sandbox_image_volume_path("i1"),
+            "/run/kata-containers/sandbox/image-volumes/i1"
+        );
+    }
+}
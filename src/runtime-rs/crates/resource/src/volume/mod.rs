@@ -6,7 +6,10 @@
 
 mod block_volume;
 mod default_volume;
+mod host_path_policy;
 pub mod hugepage;
+mod image_volume;
+mod share_fs_bypass_volume;
 mod share_fs_volume;
 mod shm_volume;
 pub mod utils;
@@ -23,10 +26,12 @@ use anyhow::{Context, Result};
 use async_trait::async_trait;
 use tokio::sync::RwLock;
 
+use self::host_path_policy::HostPathPolicy;
 use self::hugepage::{get_huge_page_limits_map, get_huge_page_option};
 use crate::{share_fs::ShareFs, volume::block_volume::is_block_volume};
 use agent::Agent;
 use hypervisor::device::device_manager::DeviceManager;
+use kata_types::config::Runtime;
 
 const BIND: &str = "bind";
 
@@ -61,8 +66,10 @@ impl VolumeResource {
         d: &RwLock<DeviceManager>,
         sid: &str,
         agent: Arc<dyn Agent>,
+        runtime: &Runtime,
     ) -> Result<Vec<Arc<dyn Volume>>> {
         let mut volumes: Vec<Arc<dyn Volume>> = vec![];
+        let host_path_policy = HostPathPolicy::new(runtime);
         let oci_mounts = &spec.mounts;
         info!(sl!(), " oci mount is : {:?}", oci_mounts.clone());
         // handle mounts
@@ -76,8 +83,11 @@ impl VolumeResource {
                 )
             } else if is_block_volume(m).context("block volume type")? {
                 // handle block volume
+                let options =
+                    block_volume::BlockVolumeOptions::new(&spec.annotations, &m.destination)
+                        .context("block volume options")?;
                 Arc::new(
-                    block_volume::BlockVolume::new(d, m, read_only, cid, sid)
+                    block_volume::BlockVolume::new(d, m, read_only, options, cid, sid)
                         .await
                         .with_context(|| format!("new share fs volume {:?}", m))?,
                 )
@@ -104,6 +114,23 @@ impl VolumeResource {
                     hugepage::Hugepage::new(m, hugepage_limits, options)
                         .with_context(|| format!("handle hugepages {:?}", m))?,
                 )
+            } else if !host_path_policy
+                .check(cid, m)
+                .context("check host path policy")?
+            {
+                continue;
+            } else if share_fs_bypass_volume::is_share_fs_bypass_volume(&spec.annotations, m) {
+                Arc::new(
+                    share_fs_bypass_volume::ShareFsBypassVolume::new(d, m, read_only, cid, sid)
+                        .await
+                        .with_context(|| format!("new share fs bypass volume {:?}", m))?,
+                )
+            } else if image_volume::is_image_volume(m) {
+                Arc::new(
+                    image_volume::ImageVolume::new(share_fs, m, cid, agent.clone())
+                        .await
+                        .with_context(|| format!("new image volume {:?}", m))?,
+                )
             } else if share_fs_volume::is_share_fs_volume(m) {
                 Arc::new(
                     share_fs_volume::ShareFsVolume::new(share_fs, m, cid, read_only, agent.clone())
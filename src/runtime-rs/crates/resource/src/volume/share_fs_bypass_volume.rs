@@ -0,0 +1,270 @@
+// Copyright (c) 2023 Kata Containers community
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use anyhow::{anyhow, Context, Result};
+use async_trait::async_trait;
+use hypervisor::{
+    device::device_manager::{get_block_driver, DeviceManager},
+    BlockConfig,
+};
+use kata_types::annotations::KATA_ANNO_CONTAINER_VOLUME_SHARE_FS_BYPASS;
+use tokio::sync::RwLock;
+
+use super::{block_volume::BlockVolume, Volume};
+use crate::volume::utils::KATA_MOUNT_BIND_TYPE;
+
+// the disk images are kept on disk rather than in the memory of /run
+const SHARE_FS_BYPASS_DIR: &str = "/var/lib/kata-containers/share-fs-bypass";
+const IMAGE_FS_TYPE: &str = "ext4";
+const IMAGE_SUFFIX: &str = ".img";
+
+const MIB: u64 = 1 << 20;
+const BLOCK_SIZE: u64 = 4096;
+// room for the metadata of the file system on top of the content
+const IMAGE_SLACK: u64 = 64 * MIB;
+
+/// ShareFsBypassVolume for the volumes of host directories marked by annotation to bypass the
+/// shared file system, for the workloads dominated by its metadata overhead.
+///
+/// The directory is converted into an ext4 disk image when the container is created, which is
+/// attached read-only as a block device and mounted in the guest. The changes made to the
+/// directory afterwards aren't seen by the container.
+pub(crate) struct ShareFsBypassVolume {
+    block: BlockVolume,
+    image_path: PathBuf,
+}
+
+impl ShareFsBypassVolume {
+    pub(crate) async fn new(
+        d: &RwLock<DeviceManager>,
+        m: &oci::Mount,
+        read_only: bool,
+        cid: &str,
+        sid: &str,
+    ) -> Result<Self> {
+        if !read_only {
+            return Err(anyhow!(
+                "share fs bypass volume {} must be read-only, the writes wouldn't reach the host",
+                m.destination
+            ));
+        }
+
+        let image_path = image_path(Path::new(SHARE_FS_BYPASS_DIR), sid, cid, &m.destination);
+        let source = PathBuf::from(&m.source);
+        let path = image_path.clone();
+        tokio::task::spawn_blocking(move || create_image(&source, &path))
+            .await?
+            .with_context(|| format!("convert {} into disk image", m.source))?;
+
+        let config = BlockConfig {
+            path_on_host: image_path.display().to_string(),
+            is_readonly: true,
+            driver_option: get_block_driver(d).await,
+            ..Default::default()
+        };
+        let block = match BlockVolume::attach(
+            d,
+            m,
+            config,
+            IMAGE_FS_TYPE.to_string(),
+            true,
+            cid,
+            sid,
+        )
+        .await
+        {
+            Ok(block) => block,
+            Err(e) => {
+                remove_image(&image_path);
+                return Err(e);
+            }
+        };
+
+        Ok(Self { block, image_path })
+    }
+}
+
+#[async_trait]
+impl Volume for ShareFsBypassVolume {
+    fn get_volume_mount(&self) -> Result<Vec<oci::Mount>> {
+        self.block.get_volume_mount()
+    }
+
+    fn get_storage(&self) -> Result<Vec<agent::Storage>> {
+        self.block.get_storage()
+    }
+
+    async fn cleanup(&self, device_manager: &RwLock<DeviceManager>) -> Result<()> {
+        self.block.cleanup(device_manager).await?;
+        remove_image(&self.image_path);
+        Ok(())
+    }
+
+    fn get_device_id(&self) -> Result<Option<String>> {
+        self.block.get_device_id()
+    }
+}
+
+pub(crate) fn is_share_fs_bypass_volume(
+    annotations: &HashMap<String, String>,
+    m: &oci::Mount,
+) -> bool {
+    m.r#type == KATA_MOUNT_BIND_TYPE
+        && annotations
+            .get(KATA_ANNO_CONTAINER_VOLUME_SHARE_FS_BYPASS)
+            .map_or(false, |v| {
+                v.split(',').map(str::trim).any(|d| d == m.destination)
+            })
+        && Path::new(&m.source).is_dir()
+}
+
+// image_path is `<dir>/<sid>/<cid>/<destination>.img`, with the destination made a file name
+fn image_path(dir: &Path, sid: &str, cid: &str, destination: &str) -> PathBuf {
+    let name = destination.trim_matches('/').replace('/', "_");
+    dir.join(sid)
+        .join(cid)
+        .join(format!("{}{}", name, IMAGE_SUFFIX))
+}
+
+// the size of the image holding `used` bytes of content, rounded up to MiB
+fn image_size(used: u64) -> u64 {
+    let size = used + used / 5 + IMAGE_SLACK;
+    (size + MIB - 1) / MIB * MIB
+}
+
+// dir_size returns the bytes taken by the content of dir in file system blocks, a block is
+// counted for the inode of each entry, the symlinks aren't followed
+fn dir_size(dir: &Path) -> Result<u64> {
+    let mut size = 0;
+    for entry in fs::read_dir(dir).with_context(|| format!("read dir {}", dir.display()))? {
+        let entry = entry?;
+        let metadata = entry.metadata()?;
+        size += BLOCK_SIZE;
+        if metadata.is_dir() {
+            size += dir_size(&entry.path())?;
+        } else {
+            size += (metadata.len() + BLOCK_SIZE - 1) / BLOCK_SIZE * BLOCK_SIZE;
+        }
+    }
+    Ok(size)
+}
+
+// create_image converts source into an ext4 disk image at path, populated by mkfs (e2fsprogs
+// 1.43 at least)
+fn create_image(source: &Path, path: &Path) -> Result<()> {
+    let size = image_size(dir_size(source)?);
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).with_context(|| format!("create dir {}", dir.display()))?;
+    }
+    let f = fs::File::create(path).context("create disk image")?;
+    f.set_len(size).context("set disk image size")?;
+
+    let output = Command::new(format!("mkfs.{}", IMAGE_FS_TYPE))
+        .args(["-q", "-F", "-d"])
+        .arg(source)
+        .arg(path)
+        .output();
+    let result = match output {
+        Ok(output) if output.status.success() => Ok(()),
+        Ok(output) => Err(anyhow!(
+            "mkfs disk image failed: {}",
+            String::from_utf8_lossy(&output.stderr)
+        )),
+        Err(e) => Err(anyhow!(e).context("run mkfs")),
+    };
+    if result.is_err() {
+        remove_image(path);
+    }
+    result
+}
+
+// remove_image removes the disk image, and the directories of the container and of the sandbox
+// once empty
+fn remove_image(path: &Path) {
+    if let Err(e) = fs::remove_file(path) {
+        warn!(
+            sl!(),
+            "failed to remove disk image {}: {}",
+            path.display(),
+            e
+        );
+        return;
+    }
+    for dir in path.ancestors().skip(1).take(2) {
+        if fs::remove_dir(dir).is_err() {
+            break;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_share_fs_bypass_volume() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut annotations = HashMap::new();
+        let m = oci::Mount {
+            destination: "/models".to_string(),
+            r#type: KATA_MOUNT_BIND_TYPE.to_string(),
+            source: dir.path().display().to_string(),
+            options: vec!["rbind".to_string(), "ro".to_string()],
+        };
+        assert!(!is_share_fs_bypass_volume(&annotations, &m));
+
+        annotations.insert(
+            KATA_ANNO_CONTAINER_VOLUME_SHARE_FS_BYPASS.to_string(),
+            "/data, /models".to_string(),
+        );
+        assert!(is_share_fs_bypass_volume(&annotations, &m));
+
+        // a file
+        let file = dir.path().join("file");
+        fs::write(&file, "data").unwrap();
+        let mut f = m.clone();
+        f.source = file.display().to_string();
+        assert!(!is_share_fs_bypass_volume(&annotations, &f));
+
+        let mut other = m.clone();
+        other.destination = "/other".to_string();
+        assert!(!is_share_fs_bypass_volume(&annotations, &other));
+    }
+
+    #[test]
+    fn test_image_path() {
+        assert_eq!(
+            image_path(Path::new(SHARE_FS_BYPASS_DIR), "s1", "c1", "/data/models/"),
+            PathBuf::from("/var/lib/kata-containers/share-fs-bypass/s1/c1/data_models.img")
+        );
+    }
+
+    #[test]
+    fn test_image_size() {
+        assert_eq!(image_size(0), IMAGE_SLACK);
+        assert_eq!(image_size(100 * MIB), 120 * MIB + IMAGE_SLACK);
+        // rounded up to MiB
+        assert_eq!(image_size(1), IMAGE_SLACK + MIB);
+    }
+
+    #[test]
+    fn test_dir_size() {
+        let dir = tempfile::tempdir().unwrap();
+        assert_eq!(dir_size(dir.path()).unwrap(), 0);
+
+        fs::write(dir.path().join("a"), vec![0u8; 5000]).unwrap();
+        fs::create_dir(dir.path().join("sub")).unwrap();
+        fs::write(dir.path().join("sub").join("b"), "b").unwrap();
+        // the inodes of a, sub and b, 2 blocks for a and 1 for b
+        assert_eq!(dir_size(dir.path()).unwrap(), 6 * BLOCK_SIZE);
+    }
+}
@@ -12,7 +12,10 @@ use crate::{
     share_fs::{do_get_guest_path, do_get_host_path},
     volume::share_fs_volume::generate_mount_path,
 };
-use kata_sys_util::eother;
+use kata_sys_util::{
+    eother,
+    host_lock::{self, HostResource, DEFAULT_LOCK_TIMEOUT},
+};
 use kata_types::mount::{
     get_volume_mount_info, join_path, DirectVolumeMountInfo, KATA_DIRECT_VOLUME_ROOT_PATH,
 };
@@ -24,8 +27,14 @@ pub const KATA_VFIO_VOLUME_TYPE: &str = "vfiovol";
 pub const KATA_SPDK_VOLUME_TYPE: &str = "spdkvol";
 pub const KATA_SPOOL_VOLUME_TYPE: &str = "spoolvol";
 
-// volume mount info load infomation from mountinfo.json
+// volume mount info load infomation from mountinfo.json, locked against kata-ctl updating it
 pub fn volume_mount_info(volume_path: &str) -> Result<DirectVolumeMountInfo> {
+    let _guard = host_lock::lock(
+        HostResource::DirectVolume,
+        Some(volume_path),
+        DEFAULT_LOCK_TIMEOUT,
+    )
+    .context("lock direct volume")?;
     get_volume_mount_info(volume_path)
 }
 
@@ -0,0 +1,164 @@
+// Copyright (c) 2023 Kata Containers community
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+use std::collections::BTreeMap;
+
+use anyhow::{anyhow, Result};
+use kata_types::config::{default::DEFAULT_AGENT_DBG_CONSOLE_PORT, Agent};
+use serde::{Deserialize, Serialize};
+
+/// Owner of the agent ttrpc server port.
+pub const VSOCK_PORT_AGENT: &str = "agent";
+/// Owner of the agent log forwarding port.
+pub const VSOCK_PORT_AGENT_LOG: &str = "agent-log";
+/// Owner of the debug console port.
+pub const VSOCK_PORT_DEBUG_CONSOLE: &str = "debug-console";
+
+/// Owner of the port proxied to the in-guest metrics exporter of TCP `port`.
+pub fn guest_metrics_port_owner(port: u32) -> String {
+    format!("guest-metrics-{}", port)
+}
+
+// The well-known ports are below the base, the ports of the other features,
+// e.g. stdio streaming and OTLP forwarding, are allocated from the base.
+const VSOCK_DYNAMIC_PORT_BASE: u32 = 1100;
+
+/// Allocator of the vsock ports used between the runtime and the guest.
+///
+/// Every port is owned by a feature, a feature always gets the same port of
+/// a sandbox, and the allocation is persisted so that a restarted shim keeps
+/// talking to the guest on the same ports.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct VsockPortAllocator {
+    ports: BTreeMap<String, u32>,
+}
+
+impl VsockPortAllocator {
+    /// Create an allocator with the well-known ports of the agent reserved.
+    pub fn new(agent: &Agent) -> Result<Self> {
+        let mut allocator = Self::default();
+        allocator.reserve(VSOCK_PORT_AGENT, agent.server_port)?;
+        allocator.reserve(VSOCK_PORT_AGENT_LOG, agent.log_port)?;
+        allocator.reserve(VSOCK_PORT_DEBUG_CONSOLE, DEFAULT_AGENT_DBG_CONSOLE_PORT)?;
+        // the agent proxies the exporters from the vsock port of their number
+        for port in agent.guest_metrics_ports.iter() {
+            allocator.reserve(&guest_metrics_port_owner(*port), *port)?;
+        }
+        Ok(allocator)
+    }
+
+    /// Reserve a fixed `port` for `owner`.
+    pub fn reserve(&mut self, owner: &str, port: u32) -> Result<()> {
+        if let Some(o) = self.owner_of(port) {
+            if o != owner {
+                return Err(anyhow!(
+                    "vsock port {} of {} is already used by {}",
+                    port,
+                    owner,
+                    o
+                ));
+            }
+        }
+        if let Some(p) = self.ports.get(owner) {
+            if *p != port {
+                return Err(anyhow!("{} already has vsock port {}", owner, p));
+            }
+        }
+
+        self.ports.insert(owner.to_string(), port);
+        Ok(())
+    }
+
+    /// Allocate a port for `owner`, the port allocated before is returned if any.
+    pub fn allocate(&mut self, owner: &str) -> Result<u32> {
+        if let Some(port) = self.ports.get(owner) {
+            return Ok(*port);
+        }
+
+        let port = (VSOCK_DYNAMIC_PORT_BASE..=u32::MAX)
+            .find(|p| self.owner_of(*p).is_none())
+            .ok_or_else(|| anyhow!("no vsock port left for {}", owner))?;
+        self.ports.insert(owner.to_string(), port);
+        Ok(port)
+    }
+
+    /// Release the port of `owner`.
+    pub fn release(&mut self, owner: &str) {
+        self.ports.remove(owner);
+    }
+
+    /// Get the port of `owner`.
+    pub fn port(&self, owner: &str) -> Option<u32> {
+        self.ports.get(owner).copied()
+    }
+
+    fn owner_of(&self, port: u32) -> Option<&str> {
+        self.ports
+            .iter()
+            .find(|(_, p)| **p == port)
+            .map(|(o, _)| o.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_vsock_port_allocator() {
+        let mut allocator = VsockPortAllocator::new(&Agent::default()).unwrap();
+        assert_eq!(allocator.port(VSOCK_PORT_AGENT), Some(1024));
+        assert_eq!(allocator.port(VSOCK_PORT_AGENT_LOG), Some(1025));
+        assert_eq!(allocator.port(VSOCK_PORT_DEBUG_CONSOLE), Some(1026));
+
+        // collides with the agent server port
+        assert!(allocator.reserve("stdio", 1024).is_err());
+        // the same reservation again is fine
+        assert!(allocator.reserve(VSOCK_PORT_AGENT, 1024).is_ok());
+        assert!(allocator.reserve(VSOCK_PORT_AGENT, 1030).is_err());
+
+        let stdio = allocator.allocate("stdio").unwrap();
+        let otlp = allocator.allocate("otlp").unwrap();
+        assert_eq!(stdio, VSOCK_DYNAMIC_PORT_BASE);
+        assert_eq!(otlp, VSOCK_DYNAMIC_PORT_BASE + 1);
+        assert_eq!(allocator.allocate("stdio").unwrap(), stdio);
+
+        allocator.release("stdio");
+        assert_eq!(allocator.port("stdio"), None);
+        assert_eq!(allocator.allocate("trace").unwrap(), stdio);
+
+        // the allocation survives the persistence
+        let state = serde_json::to_string(&allocator).unwrap();
+        let restored: VsockPortAllocator = serde_json::from_str(&state).unwrap();
+        assert_eq!(restored, allocator);
+    }
+
+    #[test]
+    fn test_vsock_port_allocator_collision() {
+        let agent = Agent {
+            log_port: 1024,
+            ..Default::default()
+        };
+        assert!(VsockPortAllocator::new(&agent).is_err());
+
+        let agent = Agent {
+            guest_metrics_ports: vec![9400, 1024],
+            ..Default::default()
+        };
+        assert!(VsockPortAllocator::new(&agent).is_err());
+    }
+
+    #[test]
+    fn test_vsock_port_allocator_guest_metrics() {
+        let agent = Agent {
+            guest_metrics_ports: vec![9400],
+            ..Default::default()
+        };
+        let mut allocator = VsockPortAllocator::new(&agent).unwrap();
+        assert_eq!(allocator.port(&guest_metrics_port_owner(9400)), Some(9400));
+        assert_eq!(allocator.port(&guest_metrics_port_owner(9100)), None);
+        assert!(allocator.reserve("stdio", 9400).is_err());
+    }
+}
@@ -0,0 +1,205 @@
+// Copyright (c) 2023 Kata Containers community
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+// Latency of the hot paths of the shim: the translation of the task requests, the copy of the
+// container IO through the agent, and the container lifecycle. The agent is the mock one, so
+// no VM is needed and the numbers are the cost of the shim itself.
+
+use std::convert::TryFrom;
+use std::sync::Arc;
+use std::time::SystemTime;
+
+use agent::{
+    kata::KataAgent, Agent, AgentManager, ContainerID, ContainerProcessID, CreateContainerRequest,
+    RemoveContainerRequest, SignalProcessRequest, WaitProcessRequest,
+};
+use common::types::{ContainerProcess, ProcessExitStatus, Request, Response};
+use containerd_shim_protos::api;
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use kata_types::config::TomlConfig;
+use mock_agent::MockAgent;
+use tempfile::TempDir;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::runtime::Runtime;
+use virt_container::ContainerIo;
+
+const SERVER_PORT: u32 = 1024;
+const IO_SIZES: [usize; 3] = [4 << 10, 64 << 10, 1 << 20];
+
+struct MockEnv {
+    _dir: TempDir,
+    mock: MockAgent,
+    agent: Arc<KataAgent>,
+}
+
+impl MockEnv {
+    async fn start() -> Self {
+        let dir = tempfile::tempdir().unwrap();
+        let mock = MockAgent::start(dir.path(), SERVER_PORT).await.unwrap();
+
+        let config = TomlConfig::load(&format!(
+            r#"
+[agent.kata]
+server_port = {}
+dial_timeout_ms = 10
+reconnect_timeout_ms = 3000
+request_timeout_ms = 5000
+
+[runtime]
+agent_name = "kata"
+            "#,
+            SERVER_PORT
+        ))
+        .unwrap();
+        let agent = Arc::new(KataAgent::new(config.agent.get("kata").unwrap().clone()));
+        agent.start(&mock.address()).await.unwrap();
+
+        Self {
+            _dir: dir,
+            mock,
+            agent,
+        }
+    }
+
+    async fn create_container(&self, cid: &str) {
+        self.agent
+            .create_container(CreateContainerRequest {
+                process_id: ContainerProcessID::new(cid, ""),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+    }
+
+    async fn container_lifecycle(&self, cid: &str) {
+        self.create_container(cid).await;
+        self.agent
+            .start_container(ContainerID::new(cid))
+            .await
+            .unwrap();
+        self.agent
+            .signal_process(SignalProcessRequest {
+                process_id: ContainerProcessID::new(cid, ""),
+                signal: 9,
+            })
+            .await
+            .unwrap();
+        self.agent
+            .wait_process(WaitProcessRequest {
+                process_id: ContainerProcessID::new(cid, ""),
+            })
+            .await
+            .unwrap();
+        self.agent
+            .remove_container(RemoveContainerRequest::new(cid, 0))
+            .await
+            .unwrap();
+    }
+
+    async fn stop(self) {
+        self.agent.stop().await;
+        self.mock.stop().await;
+    }
+}
+
+fn bench_request_translation(c: &mut Criterion) {
+    let mut group = c.benchmark_group("request_translation");
+
+    let create = api::CreateTaskRequest {
+        id: "container".to_string(),
+        bundle: "/run/containerd/io.containerd.runtime.v2.task/k8s.io/container".to_string(),
+        rootfs: vec![api::Mount {
+            type_: "overlay".to_string(),
+            source: "overlay".to_string(),
+            options: vec![
+                "lowerdir=/var/lib/containerd/lower".to_string(),
+                "upperdir=/var/lib/containerd/upper".to_string(),
+                "workdir=/var/lib/containerd/work".to_string(),
+            ],
+            ..Default::default()
+        }],
+        stdout: "/run/containerd/fifo/container-stdout".to_string(),
+        stderr: "/run/containerd/fifo/container-stderr".to_string(),
+        ..Default::default()
+    };
+    group.bench_function("create_task", |b| {
+        b.iter(|| Request::try_from(black_box(create.clone())).unwrap())
+    });
+
+    let kill = api::KillRequest {
+        id: "container".to_string(),
+        exec_id: "exec".to_string(),
+        signal: 9,
+        ..Default::default()
+    };
+    group.bench_function("kill", |b| {
+        b.iter(|| Request::try_from(black_box(kill.clone())).unwrap())
+    });
+
+    group.bench_function("wait_response", |b| {
+        b.iter(|| {
+            api::WaitResponse::try_from(black_box(Response::WaitProcess(ProcessExitStatus {
+                exit_code: 137,
+                exit_time: Some(SystemTime::now()),
+            })))
+            .unwrap()
+        })
+    });
+
+    group.finish();
+}
+
+fn bench_io_copy(c: &mut Criterion) {
+    let rt = Runtime::new().unwrap();
+    let env = rt.block_on(MockEnv::start());
+    rt.block_on(env.create_container("io"));
+
+    // the mock process echoes its stdin to its stdout
+    let mut io = ContainerIo::new(
+        env.agent.clone() as Arc<dyn Agent>,
+        ContainerProcess::new("io", "").unwrap(),
+    );
+    let mut group = c.benchmark_group("io_copy");
+    for &size in IO_SIZES.iter() {
+        let data = vec![0x5a; size];
+        let mut buf = vec![0; size];
+        group.throughput(Throughput::Bytes(size as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(size), &size, |b, _| {
+            b.iter(|| {
+                rt.block_on(async {
+                    io.stdin.write_all(&data).await.unwrap();
+                    io.stdout.read_exact(&mut buf).await.unwrap();
+                })
+            })
+        });
+    }
+    group.finish();
+
+    drop(io);
+    rt.block_on(env.stop());
+}
+
+fn bench_container_lifecycle(c: &mut Criterion) {
+    let rt = Runtime::new().unwrap();
+    let env = rt.block_on(MockEnv::start());
+
+    let mut id = 0u64;
+    c.bench_function("container_lifecycle/create_start_delete", |b| {
+        b.iter(|| {
+            id += 1;
+            rt.block_on(env.container_lifecycle(&format!("container-{}", id)))
+        })
+    });
+
+    rt.block_on(env.stop());
+}
+
+criterion_group!(
+    benches,
+    bench_request_translation,
+    bench_io_copy,
+    bench_container_lifecycle
+);
+criterion_main!(benches);
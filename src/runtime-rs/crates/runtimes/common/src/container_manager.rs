@@ -4,12 +4,14 @@
 // SPDX-License-Identifier: Apache-2.0
 //
 
+use std::collections::HashMap;
+
 use anyhow::Result;
 use async_trait::async_trait;
 
 use crate::types::{
-    ContainerConfig, ContainerID, ContainerProcess, ExecProcessRequest, KillRequest,
-    ProcessExitStatus, ProcessStateInfo, ResizePTYRequest, ShutdownRequest, StatsInfo,
+    CheckpointRequest, ContainerConfig, ContainerID, ContainerProcess, ExecProcessRequest,
+    KillRequest, ProcessExitStatus, ProcessStateInfo, ResizePTYRequest, ShutdownRequest, StatsInfo,
     UpdateRequest, PID,
 };
 
@@ -20,8 +22,13 @@ pub trait ContainerManager: Send + Sync {
     async fn pause_container(&self, container_id: &ContainerID) -> Result<()>;
     async fn resume_container(&self, container_id: &ContainerID) -> Result<()>;
     async fn stats_container(&self, container_id: &ContainerID) -> Result<StatsInfo>;
+    // the stats of all the containers, keyed by container id
+    async fn stats_containers(&self) -> Result<HashMap<String, agent::StatsContainerResponse>>;
     async fn update_container(&self, req: UpdateRequest) -> Result<()>;
+    async fn checkpoint_container(&self, req: &CheckpointRequest) -> Result<()>;
     async fn connect_container(&self, container_id: &ContainerID) -> Result<PID>;
+    // re-create the containers after the guest is rebooted
+    async fn recreate_containers(&self) -> Result<()>;
 
     // process lifecycle
     async fn close_process_io(&self, process_id: &ContainerProcess) -> Result<()>;
@@ -4,7 +4,7 @@
 // SPDX-License-Identifier: Apache-2.0
 //
 
-use crate::types::{ContainerProcess, Response};
+use crate::types::{ContainerProcess, ProcessStatus, Response};
 
 #[derive(thiserror::Error, Debug)]
 pub enum Error {
@@ -14,4 +14,16 @@ pub enum Error {
     ProcessNotFound(ContainerProcess),
     #[error("unexpected response {0} to shim {1}")]
     UnexpectedResponse(Response, String),
+    #[error("image verification failed for container {0}: {1}")]
+    ImageVerificationFailed(String, String),
+    #[error("invalid state of process {0}: expected {1:?}, actual {2:?}")]
+    InvalidState(ContainerProcess, Vec<ProcessStatus>, ProcessStatus),
+    #[error("process {0} is killed as it exceeded the deadline of {1:?}")]
+    DeadlineExceeded(ContainerProcess, std::time::Duration),
+    #[error("{0} of container {1} timed out after {2:?}")]
+    Timeout(String, String, std::time::Duration),
+    #[error("not enough {0} on the host: {1} needed, {2} available")]
+    ResourceExhausted(String, String, String),
+    #[error("invalid {0}: {1}")]
+    InvalidArgument(String, String),
 }
@@ -9,7 +9,10 @@ pub use container_manager::ContainerManager;
 pub mod error;
 pub mod message;
 mod runtime_handler;
-pub use runtime_handler::{RuntimeHandler, RuntimeInstance};
+pub use runtime_handler::{
+    get_runtime_handler, register_runtime_handler, RuntimeHandler, RuntimeHandlerCapabilities,
+    RuntimeHandlerPlugin, RuntimeInstance,
+};
 mod sandbox;
-pub use sandbox::{Sandbox, SandboxNetworkEnv};
+pub use sandbox::{Sandbox, SandboxDebugInfo, SandboxNetworkEnv};
 pub mod types;
@@ -6,7 +6,13 @@
 use std::sync::Arc;
 
 use anyhow::{Context, Result};
-use containerd_shim_protos::{events::task::TaskOOM, protobuf::Message as ProtobufMessage};
+use containerd_shim_protos::{
+    events::task::{TaskExit, TaskOOM},
+    protobuf::{
+        well_known_types::timestamp::Timestamp, CodedOutputStream, Message as ProtobufMessage,
+        MessageField,
+    },
+};
 use tokio::sync::mpsc::{channel, Receiver, Sender};
 
 /// message receiver buffer size
@@ -47,6 +53,8 @@ impl Message {
 }
 
 const TASK_OOM_EVENT_TOPIC: &str = "/tasks/oom";
+const TASK_EXIT_EVENT_TOPIC: &str = "/tasks/exit";
+const KATA_STORAGE_EVENT_TOPIC: &str = "/kata/storage";
 
 pub trait Event: std::fmt::Debug + Send {
     fn r#type(&self) -> String;
@@ -67,3 +75,155 @@ impl Event for TaskOOM {
         self.write_to_bytes().context("get oom value")
     }
 }
+
+impl Event for TaskExit {
+    fn r#type(&self) -> String {
+        TASK_EXIT_EVENT_TOPIC.to_string()
+    }
+
+    fn type_url(&self) -> String {
+        "containerd.events.TaskExit".to_string()
+    }
+
+    fn value(&self) -> Result<Vec<u8>> {
+        self.write_to_bytes().context("get exit value")
+    }
+}
+
+/// A storage problem found in the guest, published for the users to find out
+/// the nearly full filesystems and the failed mounts before the write failures
+/// of their workloads. It's encoded as the protobuf message
+///
+/// ```protobuf
+/// message StorageEvent {
+///     string container_id = 1;
+///     // near_full or mount_failed
+///     string type = 2;
+///     string mount_point = 3;
+///     uint64 used_bytes = 4;
+///     uint64 capacity_bytes = 5;
+///     string message = 6;
+/// }
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct StorageEvent {
+    pub container_id: String,
+    pub r#type: String,
+    pub mount_point: String,
+    pub used_bytes: u64,
+    pub capacity_bytes: u64,
+    pub message: String,
+}
+
+impl Event for StorageEvent {
+    fn r#type(&self) -> String {
+        KATA_STORAGE_EVENT_TOPIC.to_string()
+    }
+
+    fn type_url(&self) -> String {
+        "kata.events.StorageEvent".to_string()
+    }
+
+    fn value(&self) -> Result<Vec<u8>> {
+        let mut data = vec![];
+        let mut os = CodedOutputStream::vec(&mut data);
+        // the fields of default values are omitted like protobuf does
+        for (field, value) in [
+            (1, &self.container_id),
+            (2, &self.r#type),
+            (3, &self.mount_point),
+        ] {
+            if !value.is_empty() {
+                os.write_string(field, value)?;
+            }
+        }
+        for (field, value) in [(4, self.used_bytes), (5, self.capacity_bytes)] {
+            if value != 0 {
+                os.write_uint64(field, value)?;
+            }
+        }
+        if !self.message.is_empty() {
+            os.write_string(6, &self.message)?;
+        }
+        os.flush().context("get storage event value")?;
+        drop(os);
+        Ok(data)
+    }
+}
+
+/// The exit event of the sandbox container, it's published once the sandbox
+/// fails, so that containerd tears the sandbox down.
+pub fn sandbox_exit_event(sid: &str, exit_status: u32) -> TaskExit {
+    TaskExit {
+        container_id: sid.to_string(),
+        id: sid.to_string(),
+        pid: std::process::id(),
+        exit_status,
+        exited_at: MessageField::some(Timestamp::now()),
+        ..Default::default()
+    }
+}
+
+/// The exit event of a process of a container, `exec_id` is empty for the
+/// init process of the container. It's published once the process exited in
+/// the guest, so that containerd and the kubelet find out the exit without
+/// waiting on the process.
+pub fn task_exit_event(container_id: &str, exec_id: &str, pid: u32, exit_status: u32) -> TaskExit {
+    let id = if exec_id.is_empty() {
+        container_id
+    } else {
+        exec_id
+    };
+    TaskExit {
+        container_id: container_id.to_string(),
+        id: id.to_string(),
+        pid,
+        exit_status,
+        exited_at: MessageField::some(Timestamp::now()),
+        ..Default::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_task_exit_event() {
+        let event = task_exit_event("c1", "", 10, 137);
+        assert_eq!(event.r#type(), "/tasks/exit");
+        assert_eq!(event.container_id, "c1");
+        assert_eq!(event.id, "c1");
+        assert_eq!(event.pid, 10);
+        assert_eq!(event.exit_status, 137);
+        assert!(event.exited_at.is_some());
+
+        let event = task_exit_event("c1", "e1", 10, 0);
+        assert_eq!(event.container_id, "c1");
+        assert_eq!(event.id, "e1");
+    }
+
+    #[test]
+    fn test_storage_event_value() {
+        let event = StorageEvent {
+            container_id: "c1".to_string(),
+            r#type: "near_full".to_string(),
+            used_bytes: 300,
+            ..Default::default()
+        };
+        assert_eq!(event.r#type(), "/kata/storage");
+        assert_eq!(
+            event.value().unwrap(),
+            [
+                &[0x0a, 2][..],
+                b"c1",
+                &[0x12, 9],
+                b"near_full",
+                // varint 300
+                &[0x20, 0xac, 0x02],
+            ]
+            .concat()
+        );
+        assert!(StorageEvent::default().value().unwrap().is_empty());
+    }
+}
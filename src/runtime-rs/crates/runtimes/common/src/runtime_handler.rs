@@ -4,11 +4,15 @@
 // SPDX-License-Identifier: Apache-2.0
 //
 
-use std::sync::Arc;
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
 
 use anyhow::Result;
 use async_trait::async_trait;
 use kata_types::config::TomlConfig;
+use lazy_static::lazy_static;
 use tokio::sync::mpsc::Sender;
 
 use crate::{message::Message, ContainerManager, Sandbox};
@@ -19,6 +23,15 @@ pub struct RuntimeInstance {
     pub container_manager: Arc<dyn ContainerManager>,
 }
 
+/// What a runtime handler supports, for the manager to drive it.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct RuntimeHandlerCapabilities {
+    /// The handler runs the sandboxes of the runtime configs which don't name one.
+    pub default: bool,
+    /// The sandbox left by a dead shim can be cleaned up from its persisted state.
+    pub cleanup: bool,
+}
+
 #[async_trait]
 pub trait RuntimeHandler: Send + Sync {
     fn init() -> Result<()>
@@ -29,6 +42,10 @@ pub trait RuntimeHandler: Send + Sync {
     where
         Self: Sized;
 
+    fn capabilities() -> RuntimeHandlerCapabilities
+    where
+        Self: Sized;
+
     fn new_handler() -> Arc<dyn RuntimeHandler>
     where
         Self: Sized;
@@ -40,5 +57,112 @@ pub trait RuntimeHandler: Send + Sync {
         config: Arc<TomlConfig>,
     ) -> Result<RuntimeInstance>;
 
-    fn cleanup(&self, id: &str) -> Result<()>;
+    /// Clean up the sandbox `id` left by a dead shim, only called if the handler has the cleanup
+    /// capability.
+    async fn cleanup(
+        &self,
+        id: &str,
+        msg_sender: Sender<Message>,
+        config: Arc<TomlConfig>,
+    ) -> Result<()>;
+}
+
+/// A runtime handler registered with the manager.
+#[derive(Clone)]
+pub struct RuntimeHandlerPlugin {
+    pub name: String,
+    pub capabilities: RuntimeHandlerCapabilities,
+    new_handler: fn() -> Arc<dyn RuntimeHandler>,
+}
+
+impl RuntimeHandlerPlugin {
+    pub fn new_handler(&self) -> Arc<dyn RuntimeHandler> {
+        (self.new_handler)()
+    }
+}
+
+lazy_static! {
+    static ref RUNTIME_HANDLER_PLUGINS: Mutex<HashMap<String, RuntimeHandlerPlugin>> =
+        Mutex::new(HashMap::new());
+}
+
+/// Initialize the runtime handler `H` and register it with its name.
+pub fn register_runtime_handler<H: RuntimeHandler>() -> Result<()> {
+    H::init()?;
+    let plugin = RuntimeHandlerPlugin {
+        name: H::name(),
+        capabilities: H::capabilities(),
+        new_handler: H::new_handler,
+    };
+    let mut plugins = RUNTIME_HANDLER_PLUGINS.lock().unwrap();
+    plugins.insert(plugin.name.clone(), plugin);
+    Ok(())
+}
+
+/// Get the runtime handler registered with `name`, the default one if `name` is empty.
+pub fn get_runtime_handler(name: &str) -> Option<RuntimeHandlerPlugin> {
+    let plugins = RUNTIME_HANDLER_PLUGINS.lock().unwrap();
+    if name.is_empty() {
+        return plugins.values().find(|p| p.capabilities.default).cloned();
+    }
+    plugins.get(name).cloned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct TestContainer {}
+
+    #[async_trait]
+    impl RuntimeHandler for TestContainer {
+        fn init() -> Result<()> {
+            Ok(())
+        }
+
+        fn name() -> String {
+            "test_container".to_string()
+        }
+
+        fn capabilities() -> RuntimeHandlerCapabilities {
+            RuntimeHandlerCapabilities {
+                default: true,
+                cleanup: false,
+            }
+        }
+
+        fn new_handler() -> Arc<dyn RuntimeHandler> {
+            Arc::new(TestContainer {})
+        }
+
+        async fn new_instance(
+            &self,
+            _sid: &str,
+            _msg_sender: Sender<Message>,
+            _config: Arc<TomlConfig>,
+        ) -> Result<RuntimeInstance> {
+            unimplemented!()
+        }
+
+        async fn cleanup(
+            &self,
+            _id: &str,
+            _msg_sender: Sender<Message>,
+            _config: Arc<TomlConfig>,
+        ) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_register_runtime_handler() {
+        assert!(get_runtime_handler("test_container").is_none());
+
+        register_runtime_handler::<TestContainer>().unwrap();
+        let plugin = get_runtime_handler("test_container").unwrap();
+        assert_eq!(plugin.name, "test_container");
+        assert!(!plugin.capabilities.cleanup);
+        assert_eq!(get_runtime_handler("").unwrap().name, "test_container");
+        assert!(get_runtime_handler("unknown_container").is_none());
+    }
 }
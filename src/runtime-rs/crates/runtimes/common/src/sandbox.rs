@@ -4,8 +4,11 @@
 // SPDX-License-Identifier: Apache-2.0
 //
 
+use std::time::SystemTime;
+
 use anyhow::Result;
 use async_trait::async_trait;
+use tokio::net::UnixStream;
 
 #[derive(Clone)]
 pub struct SandboxNetworkEnv {
@@ -22,6 +25,22 @@ impl std::fmt::Debug for SandboxNetworkEnv {
     }
 }
 
+/// A snapshot of the sandbox for the debug bundle, the parts which can't be
+/// collected hold the error instead.
+#[derive(Clone, Debug, Default)]
+pub struct SandboxDebugInfo {
+    /// The persisted state of the sandbox, in json.
+    pub state: String,
+    /// The command line of the VMM process.
+    pub hypervisor_command_line: String,
+    /// The interfaces and the routes of the guest.
+    pub network: String,
+    /// The health check and the version of the agent.
+    pub agent_health: String,
+    /// The last lines of the agent logs.
+    pub agent_logs: String,
+}
+
 #[async_trait]
 pub trait Sandbox: Send + Sync {
     async fn start(
@@ -32,8 +51,17 @@ pub trait Sandbox: Send + Sync {
         network_env: SandboxNetworkEnv,
     ) -> Result<()>;
     async fn stop(&self) -> Result<()>;
+    /// Reboot the guest, keeping the devices of the VM, and re-create the
+    /// sandbox in it.
+    async fn reboot(&self) -> Result<()>;
     async fn cleanup(&self) -> Result<()>;
     async fn shutdown(&self) -> Result<()>;
+    /// The time the sandbox VM is created at, `None` until it is started.
+    /// It is persisted with the sandbox, so it stays accurate after the
+    /// shim is restarted or the guest is rebooted.
+    async fn created_at(&self) -> Option<SystemTime>;
+    /// The time the guest is last started or rebooted at.
+    async fn started_at(&self) -> Option<SystemTime>;
 
     // utils
     async fn set_iptables(&self, is_ipv6: bool, data: Vec<u8>) -> Result<Vec<u8>>;
@@ -41,6 +69,19 @@ pub trait Sandbox: Send + Sync {
     async fn direct_volume_stats(&self, volume_path: &str) -> Result<String>;
     async fn direct_volume_resize(&self, resize_req: agent::ResizeVolumeRequest) -> Result<()>;
     async fn agent_sock(&self) -> Result<String>;
+    async fn set_debug_console(&self, enable: bool) -> Result<()>;
+    /// Connect to the debug console of the guest, which must have been
+    /// enabled by the configuration or by `set_debug_console`.
+    async fn connect_debug_console(&self) -> Result<UnixStream>;
+    /// Connect to the in-guest metrics exporter of TCP `port`, which must be one of the
+    /// `guest_metrics_ports` of the agent configuration.
+    async fn connect_guest_metrics(&self, port: u32) -> Result<UnixStream>;
+    async fn copy_file(&self, req: agent::CopyFileRequest) -> Result<()>;
+    async fn read_file(&self, req: agent::ReadFileRequest) -> Result<agent::ReadFileResponse>;
+    async fn dump_guest_memory(&self, path: &str) -> Result<()>;
+    /// Take a snapshot of the sandbox for the debug bundle, the sandbox
+    /// isn't started, stopped or rebooted meanwhile.
+    async fn debug_info(&self) -> Result<SandboxDebugInfo>;
 
     // metrics function
     async fn agent_metrics(&self) -> Result<String>;
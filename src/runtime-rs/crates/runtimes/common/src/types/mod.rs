@@ -31,6 +31,7 @@ pub enum Request {
     ShutdownContainer(ShutdownRequest),
     PauseContainer(ContainerID),
     ResumeContainer(ContainerID),
+    CheckpointContainer(CheckpointRequest),
     ResizeProcessPTY(ResizePTYRequest),
     StatsContainer(ContainerID),
     UpdateContainer(UpdateRequest),
@@ -38,6 +39,31 @@ pub enum Request {
     ConnectContainer(ContainerID),
 }
 
+impl Request {
+    /// Get the id of the container the request is targeting, if any.
+    pub fn container_id(&self) -> Option<&str> {
+        match self {
+            Request::CreateContainer(req) => Some(&req.container_id),
+            Request::CloseProcessIO(req)
+            | Request::DeleteProcess(req)
+            | Request::WaitProcess(req)
+            | Request::StartProcess(req)
+            | Request::StateProcess(req) => Some(req.container_id()),
+            Request::ExecProcess(req) => Some(req.process.container_id()),
+            Request::KillProcess(req) => Some(req.process.container_id()),
+            Request::ResizeProcessPTY(req) => Some(req.process.container_id()),
+            Request::ShutdownContainer(req) => Some(&req.container_id),
+            Request::PauseContainer(req)
+            | Request::ResumeContainer(req)
+            | Request::StatsContainer(req)
+            | Request::ConnectContainer(req) => Some(&req.container_id),
+            Request::UpdateContainer(req) => Some(&req.container_id),
+            Request::CheckpointContainer(req) => Some(&req.container_id),
+            Request::Pid => None,
+        }
+    }
+}
+
 /// Response: response to shim
 /// Request and Response messages need to be paired
 #[derive(Debug, Clone, Display)]
@@ -53,6 +79,7 @@ pub enum Response {
     ShutdownContainer,
     PauseContainer,
     ResumeContainer,
+    CheckpointContainer,
     ResizeProcessPTY,
     StatsContainer(StatsInfo),
     UpdateContainer,
@@ -229,6 +256,15 @@ pub struct StatsInfo {
     pub value: Option<StatsInfoValue>,
 }
 
+#[derive(Debug, Clone)]
+pub struct CheckpointRequest {
+    pub container_id: String,
+    /// The dir on the host the image of the checkpoint is written to.
+    pub path: String,
+    /// Whether the container exits after the checkpoint.
+    pub exit: bool,
+}
+
 #[derive(Debug, Clone)]
 pub struct UpdateRequest {
     pub container_id: String,
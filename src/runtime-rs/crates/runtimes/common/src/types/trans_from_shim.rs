@@ -5,36 +5,31 @@
 //
 
 use super::{
-    ContainerConfig, ContainerID, ContainerProcess, ExecProcessRequest, KillRequest, Request,
-    ResizePTYRequest, ShutdownRequest, UpdateRequest,
+    CheckpointRequest, ContainerConfig, ContainerID, ContainerProcess, ExecProcessRequest,
+    KillRequest, Request, ResizePTYRequest, ShutdownRequest, UpdateRequest,
 };
+use crate::error::Error;
 use anyhow::{Context, Result};
 use containerd_shim_protos::api;
 use kata_types::mount::Mount;
+use protobuf::{rt::read_unknown_or_skip_group, CodedInputStream, UnknownFields};
 use std::{
     convert::{From, TryFrom},
     path::PathBuf,
 };
 
-fn trans_from_shim_mount(from: &api::Mount) -> Mount {
-    let options = from.options.to_vec();
-    let mut read_only = false;
-    for o in &options {
-        if o == "ro" {
-            read_only = true;
-            break;
-        }
-    }
-
-    Mount {
+fn trans_from_shim_mount(from: &api::Mount) -> Result<Mount> {
+    let mut mount = Mount {
         source: from.source.clone(),
         destination: PathBuf::from(&from.target),
         fs_type: from.type_.clone(),
-        options,
-        device_id: None,
-        host_shared_fs_path: None,
-        read_only,
-    }
+        options: from.options.to_vec(),
+        ..Default::default()
+    };
+    mount
+        .parse_options()
+        .with_context(|| format!("parse options of mount {}", from.source))?;
+    Ok(mount)
 }
 
 impl TryFrom<api::CreateTaskRequest> for Request {
@@ -48,7 +43,11 @@ impl TryFrom<api::CreateTaskRequest> for Request {
         Ok(Request::CreateContainer(ContainerConfig {
             container_id: from.id.clone(),
             bundle: from.bundle.clone(),
-            rootfs_mounts: from.rootfs.iter().map(trans_from_shim_mount).collect(),
+            rootfs_mounts: from
+                .rootfs
+                .iter()
+                .map(trans_from_shim_mount)
+                .collect::<Result<_>>()?,
             terminal: from.terminal,
             options,
             stdin: (!from.stdin.is_empty()).then(|| from.stdin.clone()),
@@ -76,10 +75,38 @@ impl TryFrom<api::DeleteRequest> for Request {
     }
 }
 
+// validate_exec_process checks the process of an exec is well-formed, so that
+// the bad requests fail before reaching the guest. Whether the cwd exists is
+// only known in the guest.
+fn validate_exec_process(spec_value: &[u8]) -> std::result::Result<(), String> {
+    let process: oci::Process = serde_json::from_slice(spec_value).map_err(|e| e.to_string())?;
+    if process.args.is_empty() || process.args[0].is_empty() {
+        return Err("args must not be empty".to_string());
+    }
+    for env in process.env.iter() {
+        match env.split_once('=') {
+            Some((name, _)) if !name.is_empty() && !env.contains('\0') => {}
+            _ => return Err(format!("env {:?} must be NAME=VALUE", env)),
+        }
+    }
+    if !process.cwd.is_empty() && !process.cwd.starts_with('/') {
+        return Err(format!("cwd {} must be an absolute path", process.cwd));
+    }
+    // the user is resolved to its ids by the caller, the name is informative
+    let username = &process.user.username;
+    if username.contains(|c: char| c == ':' || c.is_whitespace() || c.is_control()) {
+        return Err(format!("user name {:?} is malformed", username));
+    }
+    Ok(())
+}
+
 impl TryFrom<api::ExecProcessRequest> for Request {
     type Error = anyhow::Error;
     fn try_from(from: api::ExecProcessRequest) -> Result<Self> {
         let spec = from.spec();
+        validate_exec_process(&spec.value).map_err(|e| {
+            Error::InvalidArgument(format!("process spec of exec {}", from.exec_id), e)
+        })?;
         Ok(Request::ExecProcess(ExecProcessRequest {
             process: ContainerProcess::new(&from.id, &from.exec_id).context("new process id")?,
             terminal: from.terminal,
@@ -165,6 +192,50 @@ impl TryFrom<api::ResumeRequest> for Request {
     }
 }
 
+// the fields of containerd.runc.v1.CheckpointOptions used by the checkpoint,
+// `bool exit = 1` and `string image_path = 8`
+const CHECKPOINT_OPTIONS_EXIT_TAG: u32 = 8;
+const CHECKPOINT_OPTIONS_IMAGE_PATH_TAG: u32 = 66;
+
+// trans_checkpoint_options returns the exit and the image path of the
+// checkpoint options, which are decoded by hand as their type isn't in the
+// shim protos
+fn trans_checkpoint_options(value: &[u8]) -> Result<(bool, String)> {
+    let mut is = CodedInputStream::from_bytes(value);
+    let mut unknown = UnknownFields::new();
+    let (mut exit, mut image_path) = (false, String::new());
+    while let Some(tag) = is.read_raw_tag_or_eof()? {
+        match tag {
+            CHECKPOINT_OPTIONS_EXIT_TAG => exit = is.read_bool()?,
+            CHECKPOINT_OPTIONS_IMAGE_PATH_TAG => image_path = is.read_string()?,
+            tag => read_unknown_or_skip_group(tag, &mut is, &mut unknown)?,
+        }
+    }
+    Ok((exit, image_path))
+}
+
+impl TryFrom<api::CheckpointTaskRequest> for Request {
+    type Error = anyhow::Error;
+    fn try_from(from: api::CheckpointTaskRequest) -> Result<Self> {
+        let (exit, image_path) = if from.has_options() {
+            trans_checkpoint_options(&from.options().value).context("checkpoint options")?
+        } else {
+            (false, String::new())
+        };
+        // the image path of the options is the one of the task when it's set
+        let path = if image_path.is_empty() {
+            from.path.clone()
+        } else {
+            image_path
+        };
+        Ok(Request::CheckpointContainer(CheckpointRequest {
+            container_id: ContainerID::new(&from.id)?.container_id,
+            path,
+            exit,
+        }))
+    }
+}
+
 impl TryFrom<api::StatsRequest> for Request {
     type Error = anyhow::Error;
     fn try_from(from: api::StatsRequest) -> Result<Self> {
@@ -195,3 +266,120 @@ impl TryFrom<api::ConnectRequest> for Request {
         Ok(Request::ConnectContainer(ContainerID::new(&from.id)?))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use kata_types::mount::MountPropagation;
+
+    #[test]
+    fn test_trans_from_shim_mount() {
+        let from = api::Mount {
+            type_: "bind".to_string(),
+            source: "/var/lib/snapshots/1/fs".to_string(),
+            options: vec![
+                "rbind".to_string(),
+                "ro".to_string(),
+                "rshared".to_string(),
+                "uidmap=0:100000:65536".to_string(),
+                "gidmap=0:100000:65536".to_string(),
+            ],
+            ..Default::default()
+        };
+        let mount = trans_from_shim_mount(&from).unwrap();
+        assert!(mount.read_only);
+        assert_eq!(mount.propagation, Some(MountPropagation::RShared));
+        assert!(mount.is_idmapped());
+        assert_eq!(mount.uid_mappings[0].host_id, 100000);
+        // the id mappings aren't mount flags
+        assert_eq!(mount.options, vec!["rbind", "ro", "rshared"]);
+
+        let from = api::Mount {
+            options: vec!["uidmap=0".to_string()],
+            ..from
+        };
+        assert!(trans_from_shim_mount(&from).is_err());
+    }
+
+    #[test]
+    fn test_trans_checkpoint_task_request() {
+        let mut req = api::CheckpointTaskRequest {
+            id: "c1".to_string(),
+            path: "/var/lib/checkpoint".to_string(),
+            ..Default::default()
+        };
+        match Request::try_from(req.clone()).unwrap() {
+            Request::CheckpointContainer(r) => {
+                assert_eq!(r.container_id, "c1");
+                assert_eq!(r.path, "/var/lib/checkpoint");
+                assert!(!r.exit);
+            }
+            r => panic!("unexpected request {}", r),
+        }
+
+        // exit = true, work_path = "/w", image_path = "/i"
+        let options = b"\x08\x01\x4a\x02/w\x42\x02/i".to_vec();
+        req.options = protobuf::MessageField::some(protobuf::well_known_types::any::Any {
+            type_url: "containerd.runc.v1.CheckpointOptions".to_string(),
+            value: options,
+            ..Default::default()
+        });
+        match Request::try_from(req.clone()).unwrap() {
+            Request::CheckpointContainer(r) => {
+                assert_eq!(r.path, "/i");
+                assert!(r.exit);
+            }
+            r => panic!("unexpected request {}", r),
+        }
+
+        req.id = "../c1".to_string();
+        assert!(Request::try_from(req).is_err());
+    }
+
+    #[test]
+    fn test_validate_exec_process() {
+        let valid =
+            r#"{"user":{"uid":0,"gid":0},"args":["sh"],"env":["PATH=/bin","EMPTY="],"cwd":"/"}"#;
+        assert!(validate_exec_process(valid.as_bytes()).is_ok());
+
+        for invalid in [
+            r#"{"user":{"uid":0,"gid":0},"args":[]}"#,
+            r#"{"user":{"uid":0,"gid":0},"args":[""]}"#,
+            r#"{"user":{"uid":0,"gid":0},"args":["sh"],"env":["PATH"]}"#,
+            r#"{"user":{"uid":0,"gid":0},"args":["sh"],"env":["=/bin"]}"#,
+            r#"{"user":{"uid":0,"gid":0},"args":["sh"],"cwd":"tmp"}"#,
+            r#"{"user":{"uid":-1,"gid":0},"args":["sh"]}"#,
+            r#"{"user":{"uid":0,"gid":0,"username":"user:group"},"args":["sh"]}"#,
+            "{",
+        ] {
+            assert!(
+                validate_exec_process(invalid.as_bytes()).is_err(),
+                "{}",
+                invalid
+            );
+        }
+    }
+
+    #[test]
+    fn test_trans_exec_process_request() {
+        let req = api::ExecProcessRequest {
+            id: "c1".to_string(),
+            exec_id: "e1".to_string(),
+            spec: protobuf::MessageField::some(protobuf::well_known_types::any::Any {
+                type_url: "types.containerd.io/opencontainers/runtime-spec/1/Process".to_string(),
+                value: br#"{"user":{"uid":0,"gid":0},"args":[]}"#.to_vec(),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let err = Request::try_from(req).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<Error>(),
+            Some(Error::InvalidArgument(..))
+        ));
+        assert_eq!(
+            err.to_string(),
+            "invalid process spec of exec e1: args must not be empty"
+        );
+    }
+}
@@ -6,44 +6,66 @@
 
 use std::{
     any::type_name,
-    convert::{Into, TryFrom, TryInto},
+    convert::{TryFrom, TryInto},
     time,
 };
 
-use anyhow::{anyhow, Result};
+use anyhow::{anyhow, Context, Result};
 use containerd_shim_protos::api;
 
 use super::{ProcessExitStatus, ProcessStateInfo, ProcessStatus, Response};
 use crate::error::Error;
 
-fn system_time_into(time: time::SystemTime) -> ::protobuf::well_known_types::timestamp::Timestamp {
+// Range of the valid protobuf Timestamp, from 0001-01-01T00:00:00Z to
+// 9999-12-31T23:59:59.999999999Z.
+const TIMESTAMP_MIN_SECONDS: i64 = -62_135_596_800;
+const TIMESTAMP_MAX_SECONDS: i64 = 253_402_300_799;
+
+fn system_time_into(
+    time: time::SystemTime,
+) -> Result<::protobuf::well_known_types::timestamp::Timestamp> {
+    // protobuf Timestamp always has non-negative nanos, time before epoch is
+    // represented by negative seconds counting forward nanos.
+    let (seconds, nanos) = match time.duration_since(time::UNIX_EPOCH) {
+        Ok(d) => (i64::try_from(d.as_secs()).ok(), d.subsec_nanos()),
+        Err(e) => {
+            let d = e.duration();
+            let seconds = i64::try_from(d.as_secs()).ok().map(|s| -s);
+            match d.subsec_nanos() {
+                0 => (seconds, 0),
+                n => (seconds.and_then(|s| s.checked_sub(1)), 1_000_000_000 - n),
+            }
+        }
+    };
+
+    let seconds = seconds
+        .filter(|s| (TIMESTAMP_MIN_SECONDS..=TIMESTAMP_MAX_SECONDS).contains(s))
+        .ok_or_else(|| anyhow!("system time {:?} is out of timestamp range", time))?;
+
     let mut proto_time = ::protobuf::well_known_types::timestamp::Timestamp::new();
-    proto_time.seconds = time
-        .duration_since(time::UNIX_EPOCH)
-        .unwrap_or_default()
-        .as_secs()
-        .try_into()
-        .unwrap_or_default();
+    proto_time.seconds = seconds;
+    proto_time.nanos = nanos as i32;
 
-    proto_time
+    Ok(proto_time)
 }
 
 fn option_system_time_into(
     time: Option<time::SystemTime>,
-) -> protobuf::MessageField<protobuf::well_known_types::timestamp::Timestamp> {
+) -> Result<protobuf::MessageField<protobuf::well_known_types::timestamp::Timestamp>> {
     match time {
-        Some(v) => ::protobuf::MessageField::some(system_time_into(v)),
-        None => ::protobuf::MessageField::none(),
+        Some(v) => Ok(::protobuf::MessageField::some(system_time_into(v)?)),
+        None => Ok(::protobuf::MessageField::none()),
     }
 }
 
-impl From<ProcessExitStatus> for api::WaitResponse {
-    fn from(from: ProcessExitStatus) -> Self {
-        Self {
+impl TryFrom<ProcessExitStatus> for api::WaitResponse {
+    type Error = anyhow::Error;
+    fn try_from(from: ProcessExitStatus) -> Result<Self> {
+        Ok(Self {
             exit_status: from.exit_code as u32,
-            exited_at: option_system_time_into(from.exit_time),
+            exited_at: option_system_time_into(from.exit_time).context("exit time")?,
             ..Default::default()
-        }
+        })
     }
 }
 
@@ -59,9 +81,10 @@ impl From<ProcessStatus> for api::Status {
         }
     }
 }
-impl From<ProcessStateInfo> for api::StateResponse {
-    fn from(from: ProcessStateInfo) -> Self {
-        Self {
+impl TryFrom<ProcessStateInfo> for api::StateResponse {
+    type Error = anyhow::Error;
+    fn try_from(from: ProcessStateInfo) -> Result<Self> {
+        Ok(Self {
             id: from.container_id.clone(),
             bundle: from.bundle.clone(),
             pid: from.pid.pid,
@@ -71,21 +94,22 @@ impl From<ProcessStateInfo> for api::StateResponse {
             stderr: from.stderr.unwrap_or_default(),
             terminal: from.terminal,
             exit_status: from.exit_status as u32,
-            exited_at: option_system_time_into(from.exited_at),
+            exited_at: option_system_time_into(from.exited_at).context("exited at")?,
             exec_id: from.exec_id,
             ..Default::default()
-        }
+        })
     }
 }
 
-impl From<ProcessStateInfo> for api::DeleteResponse {
-    fn from(from: ProcessStateInfo) -> Self {
-        Self {
+impl TryFrom<ProcessStateInfo> for api::DeleteResponse {
+    type Error = anyhow::Error;
+    fn try_from(from: ProcessStateInfo) -> Result<Self> {
+        Ok(Self {
             pid: from.pid.pid,
             exit_status: from.exit_status as u32,
-            exited_at: option_system_time_into(from.exited_at),
+            exited_at: option_system_time_into(from.exited_at).context("exited at")?,
             ..Default::default()
-        }
+        })
     }
 }
 
@@ -109,7 +133,7 @@ impl TryFrom<Response> for api::DeleteResponse {
     type Error = anyhow::Error;
     fn try_from(from: Response) -> Result<Self> {
         match from {
-            Response::DeleteProcess(resp) => Ok(resp.into()),
+            Response::DeleteProcess(resp) => resp.try_into(),
             _ => Err(anyhow!(Error::UnexpectedResponse(
                 from,
                 type_name::<Self>().to_string()
@@ -122,7 +146,7 @@ impl TryFrom<Response> for api::WaitResponse {
     type Error = anyhow::Error;
     fn try_from(from: Response) -> Result<Self> {
         match from {
-            Response::WaitProcess(resp) => Ok(resp.into()),
+            Response::WaitProcess(resp) => resp.try_into(),
             _ => Err(anyhow!(Error::UnexpectedResponse(
                 from,
                 type_name::<Self>().to_string()
@@ -151,7 +175,7 @@ impl TryFrom<Response> for api::StateResponse {
     type Error = anyhow::Error;
     fn try_from(from: Response) -> Result<Self> {
         match from {
-            Response::StateProcess(resp) => Ok(resp.into()),
+            Response::StateProcess(resp) => resp.try_into(),
             _ => Err(anyhow!(Error::UnexpectedResponse(
                 from,
                 type_name::<Self>().to_string()
@@ -230,6 +254,7 @@ impl TryFrom<Response> for api::Empty {
             Response::ShutdownContainer => Ok(api::Empty::new()),
             Response::PauseContainer => Ok(api::Empty::new()),
             Response::ResumeContainer => Ok(api::Empty::new()),
+            Response::CheckpointContainer => Ok(api::Empty::new()),
             Response::ResizeProcessPTY => Ok(api::Empty::new()),
             Response::UpdateContainer => Ok(api::Empty::new()),
             _ => Err(anyhow!(Error::UnexpectedResponse(
@@ -239,3 +264,67 @@ impl TryFrom<Response> for api::Empty {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+    use super::*;
+
+    #[test]
+    fn test_system_time_into() {
+        let t = system_time_into(UNIX_EPOCH).unwrap();
+        assert_eq!((t.seconds, t.nanos), (0, 0));
+
+        let t = system_time_into(UNIX_EPOCH + Duration::new(1_700_000_000, 123_456_789)).unwrap();
+        assert_eq!((t.seconds, t.nanos), (1_700_000_000, 123_456_789));
+
+        // before epoch, nanos are counted forward from the negative seconds
+        let t = system_time_into(UNIX_EPOCH - Duration::new(1, 0)).unwrap();
+        assert_eq!((t.seconds, t.nanos), (-1, 0));
+        let t = system_time_into(UNIX_EPOCH - Duration::new(1, 250_000_000)).unwrap();
+        assert_eq!((t.seconds, t.nanos), (-2, 750_000_000));
+
+        // boundaries of the protobuf timestamp
+        let max = UNIX_EPOCH + Duration::new(TIMESTAMP_MAX_SECONDS as u64, 999_999_999);
+        let t = system_time_into(max).unwrap();
+        assert_eq!((t.seconds, t.nanos), (TIMESTAMP_MAX_SECONDS, 999_999_999));
+        assert!(system_time_into(max + Duration::from_nanos(1)).is_err());
+
+        let min = UNIX_EPOCH - Duration::from_secs(TIMESTAMP_MIN_SECONDS.unsigned_abs());
+        let t = system_time_into(min).unwrap();
+        assert_eq!((t.seconds, t.nanos), (TIMESTAMP_MIN_SECONDS, 0));
+        assert!(system_time_into(min - Duration::from_nanos(1)).is_err());
+    }
+
+    #[test]
+    fn test_option_system_time_into() {
+        assert!(option_system_time_into(None).unwrap().is_none());
+
+        let now = SystemTime::now();
+        let t = option_system_time_into(Some(now)).unwrap();
+        let d = now.duration_since(UNIX_EPOCH).unwrap();
+        assert_eq!(t.seconds, d.as_secs() as i64);
+        assert_eq!(t.nanos, d.subsec_nanos() as i32);
+    }
+
+    #[test]
+    fn test_wait_response_exited_at() {
+        let exit_time = UNIX_EPOCH + Duration::new(1_700_000_000, 5);
+        let resp = api::WaitResponse::try_from(ProcessExitStatus {
+            exit_code: 1,
+            exit_time: Some(exit_time),
+        })
+        .unwrap();
+        assert_eq!(resp.exit_status, 1);
+        assert_eq!(resp.exited_at.seconds, 1_700_000_000);
+        assert_eq!(resp.exited_at.nanos, 5);
+
+        let resp = api::WaitResponse::try_from(ProcessExitStatus {
+            exit_code: 0,
+            exit_time: None,
+        })
+        .unwrap();
+        assert!(resp.exited_at.is_none());
+    }
+}
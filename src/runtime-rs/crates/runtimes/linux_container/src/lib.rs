@@ -7,7 +7,7 @@ use std::sync::Arc;
 
 use anyhow::Result;
 use async_trait::async_trait;
-use common::{message::Message, RuntimeHandler, RuntimeInstance};
+use common::{message::Message, RuntimeHandler, RuntimeHandlerCapabilities, RuntimeInstance};
 use kata_types::config::TomlConfig;
 use tokio::sync::mpsc::Sender;
 
@@ -23,6 +23,10 @@ impl RuntimeHandler for LinuxContainer {
         "linux_container".to_string()
     }
 
+    fn capabilities() -> RuntimeHandlerCapabilities {
+        RuntimeHandlerCapabilities::default()
+    }
+
     fn new_handler() -> Arc<dyn RuntimeHandler> {
         Arc::new(LinuxContainer {})
     }
@@ -36,7 +40,12 @@ impl RuntimeHandler for LinuxContainer {
         todo!()
     }
 
-    fn cleanup(&self, _id: &str) -> Result<()> {
+    async fn cleanup(
+        &self,
+        _id: &str,
+        _msg_sender: Sender<Message>,
+        _config: Arc<TomlConfig>,
+    ) -> Result<()> {
         todo!()
     }
 }
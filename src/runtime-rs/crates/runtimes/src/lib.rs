@@ -14,7 +14,9 @@ logging::logger_with_subsystem!(sl, "runtimes");
 
 pub mod manager;
 pub use manager::RuntimeHandlerManager;
+pub mod peer_cred;
 pub use shim_interface;
 mod shim_metrics;
+pub use shim_metrics::{instrument_runtime, PendingRequest};
 mod shim_mgmt;
 pub mod tracer;
@@ -8,19 +8,21 @@ use std::{path::PathBuf, str::from_utf8, sync::Arc};
 
 use anyhow::{anyhow, Context, Result};
 use common::{
-    message::Message,
+    get_runtime_handler,
+    message::{sandbox_exit_event, Action, Message},
+    register_runtime_handler,
     types::{Request, Response},
-    RuntimeHandler, RuntimeInstance, Sandbox, SandboxNetworkEnv,
+    RuntimeInstance, SandboxNetworkEnv,
 };
 use hypervisor::Param;
-use kata_sys_util::spec::load_oci_spec;
+use kata_sys_util::spec::{get_container_type, load_oci_spec};
 use kata_types::{
     annotations::Annotation, config::default::DEFAULT_GUEST_DNS_FILE, config::TomlConfig,
+    container::ContainerType, k8s,
 };
 #[cfg(feature = "linux")]
 use linux_container::LinuxContainer;
 use netns_rs::NetNs;
-use persist::sandbox_persist::Persist;
 use resource::{
     cpu_mem::initial_size::InitialSizeManager,
     network::{dan_config_path, generate_netns_name},
@@ -30,24 +32,58 @@ use tokio::fs;
 use tokio::sync::{mpsc::Sender, Mutex, RwLock};
 use tracing::instrument;
 #[cfg(feature = "virt")]
-use virt_container::{
-    sandbox::{SandboxRestoreArgs, VirtSandbox},
-    sandbox_persist::SandboxState,
-    VirtContainer,
-};
+use virt_container::VirtContainer;
 #[cfg(feature = "wasm")]
 use wasm_container::WasmContainer;
 
 use crate::{
+    peer_cred::PeerCredAuth,
+    shim_metrics::{run_starvation_watchdog, set_pod_info},
     shim_mgmt::server::MgmtServer,
     tracer::{KataTracer, ROOTSPAN},
 };
 
+// exit status of the sandbox container reported on a sandbox failure
+const SANDBOX_FAILURE_EXIT_STATUS: u32 = 255;
+
+// the persisted state of a sandbox, only its runtime handler is read by the manager
+const SANDBOX_TYPE_KEY: &str = "sandbox_type";
+
+// Register the runtime handlers built in the shim, each of them is enabled by
+// its feature.
+fn register_runtime_handlers() -> Result<()> {
+    #[cfg(feature = "linux")]
+    register_runtime_handler::<LinuxContainer>().context("register linux container")?;
+    #[cfg(feature = "wasm")]
+    register_runtime_handler::<WasmContainer>().context("register wasm container")?;
+    #[cfg(feature = "virt")]
+    register_runtime_handler::<VirtContainer>().context("register virt container")?;
+    Ok(())
+}
+
+// The pod sandbox asks for the network of the host, its spec doesn't have
+// a network namespace as set by CRI for pods with `hostNetwork`.
+fn is_host_network_pod(spec: &oci::Spec) -> bool {
+    if !matches!(get_container_type(spec), Ok(ContainerType::PodSandbox)) {
+        return false;
+    }
+
+    !spec.linux.as_ref().map_or(false, |linux| {
+        linux
+            .namespaces
+            .iter()
+            .any(|ns| ns.r#type.as_str() == oci::NETWORKNAMESPACE)
+    })
+}
+
 struct RuntimeHandlerManagerInner {
     id: String,
     msg_sender: Sender<Message>,
     kata_tracer: Arc<Mutex<KataTracer>>,
     runtime_instance: Option<Arc<RuntimeInstance>>,
+    // the reason of the sandbox failure, no more workloads are accepted
+    // once failed
+    failure: Option<String>,
 }
 
 impl std::fmt::Debug for RuntimeHandlerManagerInner {
@@ -67,6 +103,7 @@ impl RuntimeHandlerManagerInner {
             msg_sender,
             kata_tracer: Arc::new(Mutex::new(tracer)),
             runtime_instance: None,
+            failure: None,
         })
     }
 
@@ -80,17 +117,9 @@ impl RuntimeHandlerManagerInner {
         config: Arc<TomlConfig>,
     ) -> Result<()> {
         info!(sl!(), "new runtime handler {}", &config.runtime.name);
-        let runtime_handler = match config.runtime.name.as_str() {
-            #[cfg(feature = "linux")]
-            name if name == LinuxContainer::name() => LinuxContainer::new_handler(),
-            #[cfg(feature = "wasm")]
-            name if name == WasmContainer::name() => WasmContainer::new_handler(),
-            #[cfg(feature = "virt")]
-            name if name == VirtContainer::name() || name.is_empty() => {
-                VirtContainer::new_handler()
-            }
-            _ => return Err(anyhow!("Unsupported runtime: {}", &config.runtime.name)),
-        };
+        let runtime_handler = get_runtime_handler(&config.runtime.name)
+            .ok_or_else(|| anyhow!("Unsupported runtime: {}", &config.runtime.name))?
+            .new_handler();
         let runtime_instance = runtime_handler
             .new_instance(&self.id, self.msg_sender.clone(), config.clone())
             .await
@@ -133,12 +162,7 @@ impl RuntimeHandlerManagerInner {
 
         let mut dns: Vec<String> = vec![];
 
-        #[cfg(feature = "linux")]
-        LinuxContainer::init().context("init linux container")?;
-        #[cfg(feature = "wasm")]
-        WasmContainer::init().context("init wasm container")?;
-        #[cfg(feature = "virt")]
-        VirtContainer::init().context("init virt container")?;
+        register_runtime_handlers().context("register runtime handlers")?;
 
         for m in &spec.mounts {
             if m.destination == DEFAULT_GUEST_DNS_FILE {
@@ -148,6 +172,10 @@ impl RuntimeHandlerManagerInner {
         }
 
         let config = load_config(spec, options).context("load config")?;
+        if !config.runtime.audit_log_path.is_empty() {
+            logging::audit::set_audit_file(&config.runtime.audit_log_path)
+                .context("set audit log file")?;
+        }
 
         let dan_path = dan_config_path(&config, &self.id);
         let mut network_created = false;
@@ -157,6 +185,13 @@ impl RuntimeHandlerManagerInner {
         } else if dan_path.exists() {
             info!(sl!(), "Do not create a netns due to DAN");
             None
+        } else if is_host_network_pod(spec) {
+            // the VM can't join the network of the host, refuse the pod
+            // rather than starting it without any network
+            return Err(anyhow!(
+                "host network is not supported for pod sandbox {}",
+                self.id
+            ));
         } else {
             let mut netns_path = None;
             if let Some(linux) = &spec.linux {
@@ -188,21 +223,33 @@ impl RuntimeHandlerManagerInner {
             network_created,
         };
 
-        self.init_runtime_handler(spec, state, network_env, dns, Arc::new(config))
+        let peer_cred_auth = PeerCredAuth::new(&config.runtime);
+        let config = Arc::new(config);
+        self.init_runtime_handler(spec, state, network_env, dns, config.clone())
             .await
             .context("init runtime handler")?;
 
+        // label the shim metrics and logs with the kubernetes pod
+        let pod = k8s::metadata(spec);
+        if pod.pod_name.is_some() {
+            info!(sl!(), "sandbox created for pod {:?}", pod.labels());
+        }
+        set_pod_info(&pod);
+
         // the sandbox creation can reach here only once and the sandbox is created
         // so we can safely create the shim management socket right now
         // the unwrap here is safe because the runtime handler is correctly created
         let shim_mgmt_svr = MgmtServer::new(
             &self.id,
-            self.runtime_instance.as_ref().unwrap().sandbox.clone(),
+            self.runtime_instance.as_ref().unwrap(),
+            config,
+            peer_cred_auth,
         )
         .context(ERR_NO_SHIM_SERVER)?;
 
         tokio::task::spawn(Arc::new(shim_mgmt_svr).run());
         info!(sl!(), "shim management http server starts");
+        tokio::task::spawn(run_starvation_watchdog());
 
         Ok(())
     }
@@ -239,8 +286,24 @@ impl RuntimeHandlerManager {
     pub async fn cleanup(&self) -> Result<()> {
         let inner = self.inner.read().await;
         let sender = inner.msg_sender.clone();
-        let sandbox_state = persist::from_disk::<SandboxState>(&inner.id)
+        let sandbox_state = persist::from_disk::<serde_json::Value>(&inner.id)
             .context("failed to load the sandbox state")?;
+        let sandbox_type = sandbox_state
+            .get(SANDBOX_TYPE_KEY)
+            .and_then(|t| t.as_str())
+            .unwrap_or_default();
+        if sandbox_type.is_empty() {
+            return Ok(());
+        }
+
+        register_runtime_handlers().context("register runtime handlers")?;
+        let plugin = match get_runtime_handler(sandbox_type) {
+            // TODO: support the cleanup of the other runtime handlers, e.g. linux container
+            // (https://github.com/kata-containers/kata-containers/issues/4905) and wasm
+            // container (https://github.com/kata-containers/kata-containers/issues/4906)
+            Some(plugin) if plugin.capabilities.cleanup => plugin,
+            _ => return Ok(()),
+        };
 
         let config = if let Ok(spec) = load_oci_spec() {
             load_config(&spec, &None).context("load config")?
@@ -248,42 +311,10 @@ impl RuntimeHandlerManager {
             TomlConfig::default()
         };
 
-        let sandbox_args = SandboxRestoreArgs {
-            sid: inner.id.clone(),
-            toml_config: config,
-            sender,
-        };
-        match sandbox_state.sandbox_type.clone() {
-            #[cfg(feature = "linux")]
-            name if name == LinuxContainer::name() => {
-                // TODO :support linux container (https://github.com/kata-containers/kata-containers/issues/4905)
-                return Ok(());
-            }
-            #[cfg(feature = "wasm")]
-            name if name == WasmContainer::name() => {
-                // TODO :support wasm container (https://github.com/kata-containers/kata-containers/issues/4906)
-                return Ok(());
-            }
-            #[cfg(feature = "virt")]
-            name if name == VirtContainer::name() => {
-                if sandbox_args.toml_config.runtime.keep_abnormal {
-                    info!(sl!(), "skip cleanup for keep_abnormal");
-                    return Ok(());
-                }
-                let sandbox = VirtSandbox::restore(sandbox_args, sandbox_state)
-                    .await
-                    .context("failed to restore the sandbox")?;
-                sandbox
-                    .cleanup()
-                    .await
-                    .context("failed to cleanup the resource")?;
-            }
-            _ => {
-                return Ok(());
-            }
-        }
-
-        Ok(())
+        plugin
+            .new_handler()
+            .cleanup(&inner.id, sender, Arc::new(config))
+            .await
     }
 
     async fn get_runtime_instance(&self) -> Result<Arc<RuntimeInstance>> {
@@ -309,8 +340,32 @@ impl RuntimeHandlerManager {
         inner.try_init(spec, state, options).await
     }
 
+    /// Mark the sandbox failed, e.g. on a panic in handling a request, and
+    /// publish the exit of the sandbox container, so that containerd tears
+    /// the sandbox down instead of leaving the VM behind.
+    pub async fn fail_sandbox(&self, reason: &str) {
+        let mut inner = self.inner.write().await;
+        if inner.failure.is_some() {
+            return;
+        }
+        error!(sl!(), "sandbox failed: {}", reason);
+        inner.failure = Some(reason.to_string());
+
+        let event = sandbox_exit_event(&inner.id, SANDBOX_FAILURE_EXIT_STATUS);
+        let msg = Message::new(Action::Event(Arc::new(event)));
+        if let Err(e) = inner.msg_sender.send(msg).await {
+            warn!(sl!(), "failed to send sandbox exit event: {:?}", e);
+        }
+    }
+
     #[instrument(parent = &*(ROOTSPAN))]
     pub async fn handler_message(&self, req: Request) -> Result<Response> {
+        if let Request::CreateContainer(_) | Request::ExecProcess(_) = req {
+            if let Some(reason) = self.inner.read().await.failure.as_ref() {
+                return Err(anyhow!("sandbox failed: {}", reason));
+            }
+        }
+
         if let Request::CreateContainer(container_config) = req {
             // get oci spec
             let bundler_path = format!(
@@ -420,6 +475,12 @@ impl RuntimeHandlerManager {
                     .context("resume container")?;
                 Ok(Response::ResumeContainer)
             }
+            Request::CheckpointContainer(req) => {
+                cm.checkpoint_container(&req)
+                    .await
+                    .context("checkpoint container")?;
+                Ok(Response::CheckpointContainer)
+            }
             Request::ResizeProcessPTY(req) => {
                 cm.resize_process_pty(&req).await.context("resize pty")?;
                 Ok(Response::ResizeProcessPTY)
@@ -512,3 +573,34 @@ fn update_agent_kernel_params(config: &mut TomlConfig) -> Result<()> {
     }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_host_network_pod() {
+        let mut spec = oci::Spec {
+            linux: Some(oci::Linux::default()),
+            ..Default::default()
+        };
+        // not a pod sandbox
+        assert!(!is_host_network_pod(&spec));
+
+        spec.annotations.insert(
+            "io.kubernetes.cri.container-type".to_string(),
+            "sandbox".to_string(),
+        );
+        assert!(is_host_network_pod(&spec));
+
+        spec.linux
+            .as_mut()
+            .unwrap()
+            .namespaces
+            .push(oci::LinuxNamespace {
+                r#type: oci::NETWORKNAMESPACE.to_string(),
+                path: "".to_string(),
+            });
+        assert!(!is_host_network_pod(&spec));
+    }
+}
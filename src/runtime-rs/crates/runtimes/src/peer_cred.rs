@@ -0,0 +1,131 @@
+// Copyright (c) 2023 Kata Containers community
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+use std::{fs, os::unix::fs::PermissionsExt, path::Path};
+
+use anyhow::{anyhow, Context, Result};
+use kata_types::config::Runtime;
+use nix::unistd::{chown, geteuid, Gid};
+use tokio::net::UnixStream;
+
+/// Authorization of the peers connecting to the sockets of the shim, by the credentials of the
+/// peer processes (SO_PEERCRED).
+///
+/// Root and the user of the shim itself are always allowed, e.g. containerd, and so are the
+/// configured users and the processes whose effective group is one of the configured groups,
+/// e.g. a monitoring group.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PeerCredAuth {
+    uids: Vec<u32>,
+    gids: Vec<u32>,
+}
+
+impl PeerCredAuth {
+    /// The authorization configured by `runtime`, none if the check isn't enabled.
+    pub fn new(runtime: &Runtime) -> Option<Self> {
+        if !runtime.enable_peer_cred_check {
+            return None;
+        }
+
+        let mut uids = vec![0, geteuid().as_raw()];
+        uids.extend(&runtime.peer_allowed_uids);
+        uids.sort_unstable();
+        uids.dedup();
+        Some(Self {
+            uids,
+            gids: runtime.peer_allowed_gids.clone(),
+        })
+    }
+
+    fn is_allowed(&self, uid: u32, gid: u32) -> bool {
+        self.uids.contains(&uid) || self.gids.contains(&gid)
+    }
+
+    /// Check the credentials of the peer of `stream`.
+    pub fn authorize(&self, stream: &UnixStream) -> Result<()> {
+        let cred = stream.peer_cred().context("get peer credentials")?;
+        if self.is_allowed(cred.uid(), cred.gid()) {
+            return Ok(());
+        }
+
+        Err(anyhow!(
+            "peer pid {:?} uid {} gid {} isn't allowed",
+            cred.pid(),
+            cred.uid(),
+            cred.gid()
+        ))
+    }
+
+    /// Restrict the permissions of the socket file at `path` to the owner, and to the first
+    /// allowed group if any, so that the other users can't even connect.
+    pub fn restrict_socket(&self, path: &Path) -> Result<()> {
+        let mode = match self.gids.first() {
+            Some(gid) => {
+                chown(path, None, Some(Gid::from_raw(*gid)))
+                    .with_context(|| format!("chown {:?} to group {}", path, gid))?;
+                0o660
+            }
+            None => 0o600,
+        };
+        fs::set_permissions(path, fs::Permissions::from_mode(mode))
+            .with_context(|| format!("set permissions of {:?}", path))
+    }
+}
+
+/// Identity of the peer of `stream` by its credentials, for the audit records.
+pub fn peer_identity(stream: &UnixStream) -> String {
+    match stream.peer_cred() {
+        Ok(cred) => format!(
+            "pid={} uid={} gid={}",
+            cred.pid().unwrap_or_default(),
+            cred.uid(),
+            cred.gid()
+        ),
+        Err(_) => String::from("unknown"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_peer_cred_auth() {
+        assert!(PeerCredAuth::new(&Runtime::default()).is_none());
+
+        let auth = PeerCredAuth::new(&Runtime {
+            enable_peer_cred_check: true,
+            peer_allowed_uids: vec![1000],
+            peer_allowed_gids: vec![2000],
+            ..Default::default()
+        })
+        .unwrap();
+        assert!(auth.is_allowed(0, 0));
+        assert!(auth.is_allowed(geteuid().as_raw(), 0));
+        assert!(auth.is_allowed(1000, 1000));
+        assert!(auth.is_allowed(3000, 2000));
+        assert_eq!(auth.is_allowed(3000, 3000), geteuid().as_raw() == 3000);
+    }
+
+    #[tokio::test]
+    async fn test_authorize_self() {
+        let auth = PeerCredAuth::new(&Runtime {
+            enable_peer_cred_check: true,
+            ..Default::default()
+        })
+        .unwrap();
+        let (a, _b) = UnixStream::pair().unwrap();
+        assert!(auth.authorize(&a).is_ok());
+        assert_eq!(
+            peer_identity(&a),
+            format!(
+                "pid={} uid={} gid={}",
+                std::process::id(),
+                geteuid(),
+                nix::unistd::getegid()
+            )
+        );
+    }
+}
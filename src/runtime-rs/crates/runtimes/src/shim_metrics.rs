@@ -6,12 +6,21 @@
 extern crate procfs;
 
 use anyhow::{anyhow, Result};
-use prometheus::{Encoder, Gauge, GaugeVec, Opts, Registry, TextEncoder};
+use kata_types::k8s::K8sMetadata;
+use prometheus::{Encoder, Gauge, GaugeVec, Histogram, HistogramOpts, Opts, Registry, TextEncoder};
 use slog::warn;
+use std::cell::Cell;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
 use std::sync::Mutex;
+use std::time::{Duration, Instant};
 
 const NAMESPACE_KATA_SHIM: &str = "kata_shim";
 
+// the period of the probe of the starvation watchdog
+const WATCHDOG_INTERVAL: Duration = Duration::from_millis(100);
+// the scheduling delay from which the async runtime is reported starved
+const STARVATION_THRESHOLD: Duration = Duration::from_secs(1);
+
 // Convenience macro to obtain the scope logger
 macro_rules! sl {
     () => {
@@ -38,6 +47,152 @@ lazy_static! {
     static ref SHIM_IO_STAT: GaugeVec = GaugeVec::new(Opts::new(format!("{}_{}",NAMESPACE_KATA_SHIM,"io_stat"), "Kata containerd shim v2 process IO statistics."), &["item"]).unwrap();
 
     static ref SHIM_OPEN_FDS: Gauge = Gauge::new(format!("{}_{}", NAMESPACE_KATA_SHIM, "fds"), "Kata containerd shim v2 open FDs.").unwrap();
+
+    static ref SHIM_POD_INFO: GaugeVec = GaugeVec::new(Opts::new(format!("{}_{}", NAMESPACE_KATA_SHIM, "pod_info"), "Kubernetes identity of the Kata containerd shim v2 pod."), &["pod_name", "pod_namespace", "pod_uid"]).unwrap();
+
+    static ref SHIM_SCHEDULING_DELAY: Histogram = Histogram::with_opts(HistogramOpts::new(format!("{}_{}", NAMESPACE_KATA_SHIM, "runtime_scheduling_delay_seconds"), "Kata containerd shim v2 async runtime scheduling delay.").buckets(vec![0.001, 0.01, 0.1, 0.5, 1.0, 5.0])).unwrap();
+
+    static ref SHIM_WORKERS_BUSY_RATIO: Gauge = Gauge::new(format!("{}_{}", NAMESPACE_KATA_SHIM, "runtime_workers_busy_ratio"), "Kata containerd shim v2 async runtime ratio of the time its workers are busy.").unwrap();
+
+    static ref SHIM_TTRPC_PENDING_REQUESTS: GaugeVec = GaugeVec::new(Opts::new(format!("{}_{}", NAMESPACE_KATA_SHIM, "ttrpc_pending_requests"), "Kata containerd shim v2 ttrpc requests being handled."), &["method"]).unwrap();
+
+    static ref WORKERS_BUSY: WorkersBusy = WorkersBusy::default();
+}
+
+// the depth of the queues of the scheduler is only exposed by the unstable
+// metrics of tokio, with the shim built with `--cfg tokio_unstable`
+#[cfg(tokio_unstable)]
+lazy_static! {
+    static ref SHIM_QUEUED_TASKS: GaugeVec = GaugeVec::new(
+        Opts::new(
+            format!("{}_{}", NAMESPACE_KATA_SHIM, "runtime_queued_tasks"),
+            "Kata containerd shim v2 async runtime tasks waiting to be scheduled."
+        ),
+        &["queue"]
+    )
+    .unwrap();
+}
+
+thread_local! {
+    // the instant the worker of the thread was last unparked
+    static UNPARKED_AT: Cell<Option<Instant>> = Cell::new(None);
+}
+
+// WorkersBusy accumulates the busy time of the workers of the async runtime,
+// from their unpark to their park.
+#[derive(Default)]
+struct WorkersBusy {
+    workers: AtomicUsize,
+    busy_nanos: AtomicU64,
+    // the instant and the busy time of the last sample of the ratio
+    last_sample: Mutex<Option<(Instant, u64)>>,
+}
+
+impl WorkersBusy {
+    fn add_busy(&self, busy: Duration) {
+        self.busy_nanos
+            .fetch_add(busy.as_nanos() as u64, Ordering::Relaxed);
+    }
+
+    // sample_ratio returns the ratio of the time the workers were busy since
+    // the last sample, `None` for the first one.
+    fn sample_ratio(&self, now: Instant) -> Option<f64> {
+        let busy = self.busy_nanos.load(Ordering::Relaxed);
+        let workers = self.workers.load(Ordering::Relaxed);
+        let mut last_sample = self.last_sample.lock().unwrap_or_else(|e| e.into_inner());
+
+        let ratio = match *last_sample {
+            Some((at, last_busy)) if workers > 0 => {
+                let elapsed = now.saturating_duration_since(at).as_nanos() as f64 * workers as f64;
+                if elapsed > 0.0 {
+                    Some(((busy - last_busy) as f64 / elapsed).min(1.0))
+                } else {
+                    None
+                }
+            }
+            _ => None,
+        };
+        *last_sample = Some((now, busy));
+        ratio
+    }
+}
+
+fn on_worker_unpark() {
+    UNPARKED_AT.with(|at| at.set(Some(Instant::now())));
+}
+
+fn on_worker_park() {
+    if let Some(at) = UNPARKED_AT.with(|at| at.take()) {
+        WORKERS_BUSY.add_busy(at.elapsed());
+    }
+}
+
+/// Instrument the async runtime of the shim with `worker_threads` workers, so
+/// that the ratio of the time its workers are busy is in the shim metrics. A
+/// ratio close to 1 means the requests queue up in the shim.
+pub fn instrument_runtime(
+    builder: &mut tokio::runtime::Builder,
+    worker_threads: usize,
+) -> &mut tokio::runtime::Builder {
+    WORKERS_BUSY
+        .workers
+        .store(worker_threads, Ordering::Relaxed);
+    builder
+        .on_thread_start(on_worker_unpark)
+        .on_thread_unpark(on_worker_unpark)
+        .on_thread_park(on_worker_park)
+}
+
+/// A ttrpc request being handled by the shim, counted in the shim metrics until
+/// it's dropped.
+pub struct PendingRequest(Gauge);
+
+impl PendingRequest {
+    pub fn new(method: &str) -> Self {
+        let gauge = SHIM_TTRPC_PENDING_REQUESTS.with_label_values(&[method]);
+        gauge.inc();
+        Self(gauge)
+    }
+}
+
+impl Drop for PendingRequest {
+    fn drop(&mut self) {
+        self.0.dec();
+    }
+}
+
+/// Watch the async runtime of the shim for starvation: a probe task is woken up
+/// periodically, and the delay of its wake up is recorded, so that the blocking
+/// operations stalling the handling of the RPCs show up in the shim metrics.
+pub async fn run_starvation_watchdog() {
+    loop {
+        let start = Instant::now();
+        tokio::time::sleep(WATCHDOG_INTERVAL).await;
+        let delay = start.elapsed().saturating_sub(WATCHDOG_INTERVAL);
+        SHIM_SCHEDULING_DELAY.observe(delay.as_secs_f64());
+        if delay >= STARVATION_THRESHOLD {
+            warn!(
+                sl!(),
+                "async runtime starved, task scheduled {:?} late", delay
+            );
+        }
+    }
+}
+
+/// Set the kubernetes identity of the pod served by the shim, so that the shim
+/// metrics could be joined with the pod labels.
+pub fn set_pod_info(meta: &K8sMetadata) {
+    if meta.pod_name.is_none() {
+        return;
+    }
+
+    SHIM_POD_INFO
+        .with_label_values(&[
+            meta.pod_name.as_deref().unwrap_or_default(),
+            meta.pod_namespace.as_deref().unwrap_or_default(),
+            meta.pod_uid.as_deref().unwrap_or_default(),
+        ])
+        .set(1.0);
 }
 
 pub fn get_shim_metrics() -> Result<String> {
@@ -69,6 +224,15 @@ fn register_shim_metrics() -> Result<()> {
     REGISTRY.register(Box::new(SHIM_NETDEV.clone()))?;
     REGISTRY.register(Box::new(SHIM_IO_STAT.clone()))?;
     REGISTRY.register(Box::new(SHIM_OPEN_FDS.clone()))?;
+    REGISTRY.register(Box::new(SHIM_POD_INFO.clone()))?;
+    REGISTRY.register(Box::new(SHIM_SCHEDULING_DELAY.clone()))?;
+    REGISTRY.register(Box::new(SHIM_WORKERS_BUSY_RATIO.clone()))?;
+    REGISTRY.register(Box::new(SHIM_TTRPC_PENDING_REQUESTS.clone()))?;
+    #[cfg(tokio_unstable)]
+    REGISTRY.register(Box::new(SHIM_QUEUED_TASKS.clone()))?;
+    REGISTRY.register(Box::new(hypervisor::metrics::HYPERVISOR_OPERATIONS.clone()))?;
+    #[cfg(feature = "virt")]
+    REGISTRY.register(Box::new(virt_container::metrics::STORAGE_EVENTS.clone()))?;
 
     // TODO:
     // REGISTRY.register(Box::new(RPC_DURATIONS_HISTOGRAM.clone()))?;
@@ -134,12 +298,37 @@ fn update_shim_metrics() -> Result<()> {
         }
     }
 
+    if let Some(ratio) = WORKERS_BUSY.sample_ratio(Instant::now()) {
+        SHIM_WORKERS_BUSY_RATIO.set(ratio);
+    }
+
+    #[cfg(tokio_unstable)]
+    update_queued_tasks();
+
     // TODO:
     // RPC_DURATIONS_HISTOGRAM & SHIM_POD_OVERHEAD_CPU & SHIM_POD_OVERHEAD_MEMORY
 
     Ok(())
 }
 
+#[cfg(tokio_unstable)]
+fn update_queued_tasks() {
+    let handle = match tokio::runtime::Handle::try_current() {
+        Ok(handle) => handle,
+        Err(_) => return,
+    };
+    let metrics = handle.metrics();
+    let local: usize = (0..metrics.num_workers())
+        .map(|worker| metrics.worker_local_queue_depth(worker))
+        .sum();
+    SHIM_QUEUED_TASKS
+        .with_label_values(&["global"])
+        .set(metrics.injection_queue_depth() as f64);
+    SHIM_QUEUED_TASKS
+        .with_label_values(&["local"])
+        .set(local as f64);
+}
+
 fn set_gauge_vec_proc_status(gv: &prometheus::GaugeVec, status: &procfs::process::Status) {
     gv.with_label_values(&["vmpeak"])
         .set(status.vmpeak.unwrap_or(0) as f64);
@@ -233,3 +422,38 @@ fn set_gauge_vec_proc_io(gv: &prometheus::GaugeVec, io_stat: &procfs::process::I
     gv.with_label_values(&["cancelled_write_bytes"])
         .set(io_stat.cancelled_write_bytes as f64);
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_workers_busy_ratio() {
+        let busy = WorkersBusy::default();
+        busy.workers.store(2, Ordering::Relaxed);
+
+        let now = Instant::now();
+        assert!(busy.sample_ratio(now).is_none());
+
+        // 1s busy out of the 2s of the 2 workers
+        busy.add_busy(Duration::from_millis(500));
+        busy.add_busy(Duration::from_millis(500));
+        let ratio = busy.sample_ratio(now + Duration::from_secs(1)).unwrap();
+        assert!((ratio - 0.5).abs() < f64::EPSILON);
+
+        // idle since the last sample
+        let ratio = busy.sample_ratio(now + Duration::from_secs(2)).unwrap();
+        assert_eq!(ratio, 0.0);
+    }
+
+    #[test]
+    fn test_pending_request() {
+        let gauge = SHIM_TTRPC_PENDING_REQUESTS.with_label_values(&["test"]);
+        {
+            let _create = PendingRequest::new("test");
+            let _state = PendingRequest::new("test");
+            assert_eq!(gauge.get(), 2.0);
+        }
+        assert_eq!(gauge.get(), 0.0);
+    }
+}
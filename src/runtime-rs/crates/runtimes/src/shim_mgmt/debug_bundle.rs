@@ -0,0 +1,200 @@
+// Copyright (c) 2023 Kata Containers community
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+// The debug bundle attached to the bug reports, a tarball of the snapshot of
+// the sandbox with the secrets redacted
+
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::{anyhow, Context, Result};
+use common::Sandbox;
+use kata_types::config::{redact_secret_env, TomlConfig};
+
+// the directory of the files in the tarball
+const BUNDLE_DIR: &str = "kata-debug-bundle";
+// the audit log may be shared by the sandboxes of the host, only its end is
+// collected
+const AUDIT_LOG_TAIL_SIZE: u64 = 256 * 1024;
+
+const TAR_BLOCK_SIZE: usize = 512;
+const TAR_NAME_SIZE: usize = 100;
+const TAR_FILE_MODE: u32 = 0o644;
+
+/// Collect the debug bundle of the sandbox as a tar archive.
+pub(crate) async fn collect_debug_bundle(
+    sandbox: &dyn Sandbox,
+    config: &TomlConfig,
+) -> Result<Vec<u8>> {
+    let info = sandbox.debug_info().await.context("get debug info")?;
+    let config_dump = config
+        .to_redacted_toml()
+        .unwrap_or_else(|e| format!("failed to dump config: {:?}\n", e));
+    let mut files = vec![
+        ("config.toml", config_dump),
+        ("state.json", info.state),
+        ("hypervisor-cmdline.txt", info.hypervisor_command_line),
+        ("network.txt", info.network),
+        ("agent-health.txt", info.agent_health),
+        ("agent.log", info.agent_logs),
+    ];
+    if !config.runtime.audit_log_path.is_empty() {
+        let audit_log = read_tail(&config.runtime.audit_log_path, AUDIT_LOG_TAIL_SIZE)
+            .unwrap_or_else(|e| format!("failed to read audit log: {:?}\n", e));
+        files.push(("audit.log", audit_log));
+    }
+
+    let mtime = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let mut tar = TarWriter::new(mtime);
+    for (name, content) in files {
+        tar.append(
+            &format!("{}/{}", BUNDLE_DIR, name),
+            redact_secret_env(&content).as_bytes(),
+        )?;
+    }
+    Ok(tar.finish())
+}
+
+// read_tail returns the last size bytes of the file at path, from the start
+// of a line
+fn read_tail(path: &str, size: u64) -> Result<String> {
+    let mut f = File::open(path).with_context(|| format!("open {}", path))?;
+    let len = f.metadata()?.len();
+    let offset = len.saturating_sub(size);
+    f.seek(SeekFrom::Start(offset))?;
+    let mut data = vec![];
+    f.read_to_end(&mut data)?;
+
+    let mut tail = String::from_utf8_lossy(&data).into_owned();
+    if offset > 0 {
+        let start = tail.find('\n').map_or(tail.len(), |i| i + 1);
+        tail.drain(..start);
+    }
+    Ok(tail)
+}
+
+// TarWriter writes the regular files of a ustar archive in memory, which is
+// all the debug bundle needs
+struct TarWriter {
+    data: Vec<u8>,
+    mtime: u64,
+}
+
+impl TarWriter {
+    fn new(mtime: u64) -> Self {
+        Self {
+            data: vec![],
+            mtime,
+        }
+    }
+
+    fn append(&mut self, name: &str, content: &[u8]) -> Result<()> {
+        if name.len() >= TAR_NAME_SIZE {
+            return Err(anyhow!("tar file name {} is too long", name));
+        }
+
+        let mut header = [0u8; TAR_BLOCK_SIZE];
+        header[..name.len()].copy_from_slice(name.as_bytes());
+        write_octal(&mut header[100..108], TAR_FILE_MODE as u64);
+        write_octal(&mut header[108..116], 0);
+        write_octal(&mut header[116..124], 0);
+        write_octal(&mut header[124..136], content.len() as u64);
+        write_octal(&mut header[136..148], self.mtime);
+        header[156] = b'0';
+        header[257..263].copy_from_slice(b"ustar\0");
+        header[263..265].copy_from_slice(b"00");
+        // the checksum is computed with its own field filled with spaces
+        header[148..156].fill(b' ');
+        let checksum: u64 = header.iter().map(|b| *b as u64).sum();
+        write_octal(&mut header[148..155], checksum);
+
+        self.data.extend_from_slice(&header);
+        self.data.extend_from_slice(content);
+        self.pad();
+        Ok(())
+    }
+
+    // the archive ends with two zero blocks
+    fn finish(mut self) -> Vec<u8> {
+        self.data.resize(self.data.len() + 2 * TAR_BLOCK_SIZE, 0);
+        self.data
+    }
+
+    fn pad(&mut self) {
+        let rem = self.data.len() % TAR_BLOCK_SIZE;
+        if rem != 0 {
+            self.data.resize(self.data.len() + TAR_BLOCK_SIZE - rem, 0);
+        }
+    }
+}
+
+// write_octal fills the field with the zero-padded octal value, terminated by NUL
+fn write_octal(field: &mut [u8], value: u64) {
+    let s = format!("{:0width$o}\0", value, width = field.len() - 1);
+    field.copy_from_slice(&s.as_bytes()[s.len() - field.len()..]);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::io::Write;
+
+    #[test]
+    fn test_write_octal() {
+        let mut field = [0u8; 8];
+        write_octal(&mut field, 0o644);
+        assert_eq!(&field, b"0000644\0");
+    }
+
+    #[test]
+    fn test_tar_writer() {
+        let mut tar = TarWriter::new(0);
+        tar.append("bundle/a.txt", b"hello").unwrap();
+        tar.append("bundle/empty", b"").unwrap();
+        assert!(tar.append(&"x".repeat(TAR_NAME_SIZE), b"").is_err());
+        let data = tar.finish();
+
+        // a header and a content block, a header, two end blocks
+        assert_eq!(data.len(), 5 * TAR_BLOCK_SIZE);
+        let header = &data[..TAR_BLOCK_SIZE];
+        assert_eq!(&header[..12], b"bundle/a.txt");
+        assert_eq!(&header[124..136], b"00000000005\0");
+        assert_eq!(&header[257..262], b"ustar");
+        let checksum = u64::from_str_radix(std::str::from_utf8(&header[148..154]).unwrap(), 8);
+        let expected: u64 = header[..148]
+            .iter()
+            .chain(&[b' '; 8])
+            .chain(&header[156..])
+            .map(|b| *b as u64)
+            .sum();
+        assert_eq!(checksum.unwrap(), expected);
+        assert_eq!(&data[TAR_BLOCK_SIZE..TAR_BLOCK_SIZE + 5], b"hello");
+        assert_eq!(
+            &data[2 * TAR_BLOCK_SIZE..2 * TAR_BLOCK_SIZE + 12],
+            b"bundle/empty"
+        );
+        assert!(data[3 * TAR_BLOCK_SIZE..].iter().all(|b| *b == 0));
+    }
+
+    #[test]
+    fn test_read_tail() {
+        let mut f = tempfile::NamedTempFile::new().unwrap();
+        f.write_all(b"first line\nsecond line\nthird\n").unwrap();
+        let path = f.path().to_str().unwrap();
+
+        assert_eq!(
+            read_tail(path, 1024).unwrap(),
+            "first line\nsecond line\nthird\n"
+        );
+        // the partial line is dropped
+        assert_eq!(read_tail(path, 10).unwrap(), "third\n");
+        assert!(read_tail("/nonexistent", 10).is_err());
+    }
+}
@@ -0,0 +1,114 @@
+// Copyright (c) 2023 Kata Containers community
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+// The interactive debug console of the guest reached through the shim
+// management socket, the HTTP connection is upgraded and spliced to the
+// console port of the guest, so that the clients don't need to reach the
+// vsock of the VM
+
+use std::sync::Arc;
+
+use anyhow::{anyhow, Context, Result};
+use common::Sandbox;
+use hyper::{header, Body, HeaderMap, Request, Response, StatusCode};
+use logging::audit::{audit, AuditRecord};
+use shim_interface::shim_mgmt::DEBUG_CONSOLE_PROTOCOL;
+
+/// Attach to the debug console of the guest, the request must ask to upgrade
+/// the connection to the console protocol. The console is connected before
+/// the connection is upgraded, so that the failures are reported to the
+/// client.
+pub(crate) async fn attach_debug_console(
+    sandbox: Arc<dyn Sandbox>,
+    requester: &str,
+    mut req: Request<Body>,
+) -> Result<Response<Body>> {
+    if !is_console_upgrade(req.headers()) {
+        return Err(anyhow!(
+            "handler: attaching the debug console requires an upgrade to {}",
+            DEBUG_CONSOLE_PROTOCOL
+        ));
+    }
+
+    let result = sandbox.connect_debug_console().await;
+    audit(
+        &sl!(),
+        &AuditRecord::new("attach_debug_console", requester, "", &result),
+    );
+    let mut console = result.context("handler: connect debug console")?;
+
+    let upgrade = hyper::upgrade::on(&mut req);
+    tokio::spawn(async move {
+        let mut client = match upgrade.await {
+            Ok(client) => client,
+            Err(err) => {
+                warn!(
+                    sl!(),
+                    "failed to upgrade debug console connection: {:?}", err
+                );
+                return;
+            }
+        };
+        // the session ends once either side closes the connection
+        match tokio::io::copy_bidirectional(&mut client, &mut console).await {
+            Ok((sent, received)) => info!(
+                sl!(),
+                "debug console session closed, {} bytes sent, {} bytes received", sent, received
+            ),
+            Err(err) => warn!(sl!(), "debug console session failed: {:?}", err),
+        }
+    });
+
+    Ok(Response::builder()
+        .status(StatusCode::SWITCHING_PROTOCOLS)
+        .header(header::CONNECTION, "upgrade")
+        .header(header::UPGRADE, DEBUG_CONSOLE_PROTOCOL)
+        .body(Body::empty())?)
+}
+
+// is_console_upgrade checks the request asks to upgrade the connection to
+// the console protocol, the tokens of both headers are case insensitive
+fn is_console_upgrade(headers: &HeaderMap) -> bool {
+    let value = |name| {
+        headers
+            .get(name)
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or_default()
+    };
+    value(header::CONNECTION)
+        .split(',')
+        .any(|token| token.trim().eq_ignore_ascii_case("upgrade"))
+        && value(header::UPGRADE).eq_ignore_ascii_case(DEBUG_CONSOLE_PROTOCOL)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_console_upgrade() {
+        let headers = |connection: &str, upgrade: &str| {
+            let mut headers = HeaderMap::new();
+            headers.insert(header::CONNECTION, connection.parse().unwrap());
+            headers.insert(header::UPGRADE, upgrade.parse().unwrap());
+            headers
+        };
+
+        assert!(is_console_upgrade(&headers(
+            "Upgrade",
+            DEBUG_CONSOLE_PROTOCOL
+        )));
+        assert!(is_console_upgrade(&headers(
+            "keep-alive, upgrade",
+            "Kata-Debug-Console"
+        )));
+        assert!(!is_console_upgrade(&headers(
+            "keep-alive",
+            DEBUG_CONSOLE_PROTOCOL
+        )));
+        assert!(!is_console_upgrade(&headers("upgrade", "websocket")));
+        assert!(!is_console_upgrade(&HeaderMap::new()));
+    }
+}
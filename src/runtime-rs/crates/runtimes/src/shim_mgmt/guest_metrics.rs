@@ -0,0 +1,98 @@
+// Copyright (c) 2023 Kata Containers community
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+// The in-guest metrics exporters reached through the shim management socket,
+// e.g. the DCGM exporter of the GPUs of a VM-isolated pod. The agent proxies
+// the TCP port of the exporter from the vsock port of the same number, and the
+// shim sends the scrape over it, so that a host monitor scrapes the exporters
+// of all the sandboxes without a route to their guest network.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{anyhow, Context, Result};
+use common::Sandbox;
+use hyper::{header, Body, Request, Response};
+use tokio::net::UnixStream;
+
+// the path the exporters serve their metrics on, by the Prometheus convention
+const EXPORTER_METRICS_PATH: &str = "/metrics";
+// the timeout of the response head, the body is streamed afterwards
+const EXPORTER_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Scrape the in-guest exporter of TCP `port`, its status, content type and
+/// body are passed through.
+pub(crate) async fn scrape_guest_metrics(
+    sandbox: Arc<dyn Sandbox>,
+    port: u32,
+) -> Result<Response<Body>> {
+    let stream = sandbox.connect_guest_metrics(port).await?;
+    tokio::time::timeout(EXPORTER_TIMEOUT, scrape(stream))
+        .await
+        .map_err(|_| anyhow!("no response from the exporter in {:?}", EXPORTER_TIMEOUT))?
+}
+
+async fn scrape(stream: UnixStream) -> Result<Response<Body>> {
+    let (mut sender, conn) = hyper::client::conn::handshake(stream)
+        .await
+        .context("handshake")?;
+    // the connection is driven until the response body is consumed
+    tokio::spawn(async move {
+        if let Err(err) = conn.await {
+            warn!(sl!(), "guest metrics connection failed: {:?}", err);
+        }
+    });
+
+    let req = Request::builder()
+        .uri(EXPORTER_METRICS_PATH)
+        .header(header::HOST, "localhost")
+        .body(Body::empty())?;
+    let resp = sender.send_request(req).await.context("send request")?;
+
+    // the other headers are about the connection to the exporter
+    let mut builder = Response::builder().status(resp.status());
+    if let Some(content_type) = resp.headers().get(header::CONTENT_TYPE) {
+        builder = builder.header(header::CONTENT_TYPE, content_type);
+    }
+    builder.body(resp.into_body()).map_err(|e| anyhow!(e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hyper::service::service_fn;
+    use hyper::StatusCode;
+
+    #[tokio::test]
+    async fn test_scrape() {
+        let (client, server) = UnixStream::pair().unwrap();
+        tokio::spawn(async move {
+            let service = service_fn(|req: Request<Body>| async move {
+                if req.uri().path() == EXPORTER_METRICS_PATH {
+                    Response::builder()
+                        .header(header::CONTENT_TYPE, "text/plain; version=0.0.4")
+                        .body(Body::from("DCGM_FI_DEV_GPU_UTIL{gpu=\"0\"} 42\n"))
+                } else {
+                    Response::builder()
+                        .status(StatusCode::NOT_FOUND)
+                        .body(Body::empty())
+                }
+            });
+            hyper::server::conn::Http::new()
+                .serve_connection(server, service)
+                .await
+                .unwrap();
+        });
+
+        let resp = scrape(client).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(
+            resp.headers().get(header::CONTENT_TYPE).unwrap(),
+            "text/plain; version=0.0.4"
+        );
+        let body = hyper::body::to_bytes(resp.into_body()).await.unwrap();
+        assert_eq!(&body[..], b"DCGM_FI_DEV_GPU_UTIL{gpu=\"0\"} 42\n");
+    }
+}
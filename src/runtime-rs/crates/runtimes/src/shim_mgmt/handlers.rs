@@ -7,23 +7,48 @@
 // This defines the handlers corresponding to the url when a request is sent to destined url,
 // the handler function should be invoked, and the corresponding data will be in the response
 
+use super::debug_bundle::collect_debug_bundle;
+use super::debug_console::attach_debug_console;
+use super::guest_metrics::scrape_guest_metrics;
 use crate::shim_metrics::get_shim_metrics;
-use agent::ResizeVolumeRequest;
+use agent::{CopyFileRequest, ReadFileRequest, ReadFileResponse, ResizeVolumeRequest};
 use anyhow::{anyhow, Context, Result};
-use common::Sandbox;
-use hyper::{Body, Method, Request, Response, StatusCode};
+use common::{ContainerManager, Sandbox};
+use hyper::{body::HttpBody, header, Body, Method, Request, Response, StatusCode};
+use kata_types::config::TomlConfig;
+use logging::audit::{audit, AuditRecord};
+use std::collections::HashMap;
+use std::path::{Component, Path, PathBuf};
 use std::sync::Arc;
-use url::Url;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use url::form_urlencoded;
 
 use shim_interface::shim_mgmt::{
-    AGENT_URL, DIRECT_VOLUME_PATH_KEY, DIRECT_VOLUME_RESIZE_URL, DIRECT_VOLUME_STATS_URL,
-    IP6_TABLE_URL, IP_TABLE_URL, METRICS_URL,
+    AGENT_URL, CONFIG_URL, COPY_FILE_MODE_KEY, COPY_FILE_PATH_KEY, COPY_FILE_URL, DEBUG_BUNDLE_URL,
+    DEBUG_CONSOLE_ATTACH_URL, DEBUG_CONSOLE_ENABLE_KEY, DEBUG_CONSOLE_URL, DIRECT_VOLUME_PATH_KEY,
+    DIRECT_VOLUME_RESIZE_URL, DIRECT_VOLUME_STATS_URL, GUEST_METRICS_PORT_KEY, GUEST_METRICS_URL,
+    IP6_TABLE_URL, IP_TABLE_URL, MEMORY_DUMP_PATH_KEY, MEMORY_DUMP_URL, METRICS_URL, REBOOT_URL,
+    STATS_INTERVAL_KEY, STATS_URL,
 };
 
+// the guest files out of this directory can't be copied, the agent
+// enforces it as well
+const COPY_FILE_GUEST_BASE: &str = "/run/kata-containers";
+// the size of the chunks the files are transferred in, a chunk is sent
+// in a single agent request
+const COPY_FILE_CHUNK_SIZE: usize = 1024 * 1024;
+const DEFAULT_COPY_FILE_MODE: u32 = 0o644;
+const DEFAULT_COPY_FILE_DIR_MODE: u32 = 0o750;
+const DEFAULT_STATS_INTERVAL_SECS: u64 = 10;
+
 // main router for response, this works as a multiplexer on
-// http arrival which invokes the corresponding handler function
+// http arrival which invokes the corresponding handler function,
+// `requester` is the identity of the peer for the audit records
 pub(crate) async fn handler_mux(
     sandbox: Arc<dyn Sandbox>,
+    container_manager: Arc<dyn ContainerManager>,
+    config: Arc<TomlConfig>,
+    requester: String,
     req: Request<Body>,
 ) -> Result<Response<Body>> {
     info!(
@@ -35,20 +60,57 @@ pub(crate) async fn handler_mux(
     match (req.method(), req.uri().path()) {
         (&Method::GET, AGENT_URL) => agent_url_handler(sandbox, req).await,
         (&Method::PUT, IP_TABLE_URL) | (&Method::GET, IP_TABLE_URL) => {
-            ip_table_handler(sandbox, req).await
+            ip_table_handler(sandbox, &requester, req).await
         }
         (&Method::PUT, IP6_TABLE_URL) | (&Method::GET, IP6_TABLE_URL) => {
-            ipv6_table_handler(sandbox, req).await
+            ipv6_table_handler(sandbox, &requester, req).await
+        }
+        // kata-ctl queries the stats with GET, the POST is kept for the
+        // clients which used to query with it
+        (&Method::GET, DIRECT_VOLUME_STATS_URL) | (&Method::POST, DIRECT_VOLUME_STATS_URL) => {
+            direct_volume_stats_handler(sandbox, req).await
         }
-        (&Method::POST, DIRECT_VOLUME_STATS_URL) => direct_volume_stats_handler(sandbox, req).await,
         (&Method::POST, DIRECT_VOLUME_RESIZE_URL) => {
             direct_volume_resize_handler(sandbox, req).await
         }
         (&Method::GET, METRICS_URL) => metrics_url_handler(sandbox, req).await,
+        (&Method::GET, GUEST_METRICS_URL) => guest_metrics_handler(sandbox, req).await,
+        (&Method::PUT, DEBUG_CONSOLE_URL) => debug_console_handler(sandbox, &requester, req).await,
+        (&Method::GET, DEBUG_CONSOLE_ATTACH_URL) => {
+            attach_debug_console(sandbox, &requester, req).await
+        }
+        (&Method::PUT, REBOOT_URL) => reboot_handler(sandbox, container_manager, req).await,
+        (&Method::PUT, COPY_FILE_URL) | (&Method::GET, COPY_FILE_URL) => {
+            copy_file_handler(sandbox, &requester, req).await
+        }
+        (&Method::GET, STATS_URL) => stats_handler(container_manager, req).await,
+        (&Method::PUT, MEMORY_DUMP_URL) => memory_dump_handler(sandbox, &requester, req).await,
+        (&Method::GET, CONFIG_URL) => config_handler(config, req).await,
+        (&Method::GET, DEBUG_BUNDLE_URL) => {
+            debug_bundle_handler(sandbox, config, &requester, req).await
+        }
         _ => Ok(not_found(req).await),
     }
 }
 
+// the query parameters of the request, the uri of a request received by the
+// server is in the origin form, i.e. "/path?key=value", which has no base to
+// be parsed as an url
+fn query_params(req: &Request<Body>) -> HashMap<String, String> {
+    form_urlencoded::parse(req.uri().query().unwrap_or_default().as_bytes())
+        .into_owned()
+        .collect()
+}
+
+// respond with the status and the error message, so that the client gets
+// the cause of the failure rather than a closed connection
+fn error_response(status: StatusCode, err: anyhow::Error) -> Result<Response<Body>> {
+    Response::builder()
+        .status(status)
+        .body(Body::from(format!("{:?}", err)))
+        .map_err(|e| anyhow!(e))
+}
+
 // url not found
 async fn not_found(_req: Request<Body>) -> Response<Body> {
     Response::builder()
@@ -70,22 +132,28 @@ async fn agent_url_handler(
 }
 
 /// the ipv4 handler of iptable operation
-async fn ip_table_handler(sandbox: Arc<dyn Sandbox>, req: Request<Body>) -> Result<Response<Body>> {
-    generic_ip_table_handler(sandbox, req, false).await
+async fn ip_table_handler(
+    sandbox: Arc<dyn Sandbox>,
+    requester: &str,
+    req: Request<Body>,
+) -> Result<Response<Body>> {
+    generic_ip_table_handler(sandbox, requester, req, false).await
 }
 
 /// the ipv6 handler of iptable operation
 async fn ipv6_table_handler(
     sandbox: Arc<dyn Sandbox>,
+    requester: &str,
     req: Request<Body>,
 ) -> Result<Response<Body>> {
-    generic_ip_table_handler(sandbox, req, true).await
+    generic_ip_table_handler(sandbox, requester, req, true).await
 }
 
 /// the generic iptable handler, for both ipv4 and ipv6
 /// this requires iptables-series binaries to be inside guest rootfs
 async fn generic_ip_table_handler(
     sandbox: Arc<dyn Sandbox>,
+    requester: &str,
     req: Request<Body>,
     is_ipv6: bool,
 ) -> Result<Response<Body>> {
@@ -96,20 +164,37 @@ async fn generic_ip_table_handler(
                 let body = Body::from(data);
                 Response::builder().body(body).map_err(|e| anyhow!(e))
             }
-            _ => Err(anyhow!("Failed to get iptable")),
+            Err(err) => error_response(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                err.context("handler: failed to get iptables"),
+            ),
         },
 
         Method::PUT => {
             let data = hyper::body::to_bytes(req.into_body()).await?;
-            match sandbox.set_iptables(is_ipv6, data.to_vec()).await {
+            let result = sandbox.set_iptables(is_ipv6, data.to_vec()).await;
+            let operation = if is_ipv6 {
+                "set_ip6tables"
+            } else {
+                "set_iptables"
+            };
+            audit(&sl!(), &AuditRecord::new(operation, requester, "", &result));
+            match result {
                 Ok(resp_data) => Response::builder()
                     .body(Body::from(resp_data))
                     .map_err(|e| anyhow!(e)),
-                _ => Err(anyhow!("Failed to set iptable")),
+                // e.g. the rules are rejected by iptables-restore in the guest
+                Err(err) => error_response(
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    err.context("handler: failed to set iptables"),
+                ),
             }
         }
 
-        _ => Err(anyhow!("IP Tables only takes PUT and GET")),
+        _ => error_response(
+            StatusCode::METHOD_NOT_ALLOWED,
+            anyhow!("IP Tables only takes PUT and GET"),
+        ),
     }
 }
 
@@ -117,18 +202,25 @@ async fn direct_volume_stats_handler(
     sandbox: Arc<dyn Sandbox>,
     req: Request<Body>,
 ) -> Result<Response<Body>> {
-    let params = Url::parse(&req.uri().to_string())
-        .map_err(|e| anyhow!(e))?
-        .query_pairs()
-        .into_owned()
-        .collect::<std::collections::HashMap<String, String>>();
-    let volume_path = params
-        .get(DIRECT_VOLUME_PATH_KEY)
-        .context("shim-mgmt: volume path key not found in request params")?;
-    let result = sandbox.direct_volume_stats(volume_path).await;
-    match result {
+    let params = query_params(&req);
+    let volume_path = match params.get(DIRECT_VOLUME_PATH_KEY) {
+        Some(volume_path) => volume_path,
+        None => {
+            return error_response(
+                StatusCode::BAD_REQUEST,
+                anyhow!("shim-mgmt: volume path key not found in request params"),
+            )
+        }
+    };
+    match sandbox.direct_volume_stats(volume_path).await {
         Ok(stats) => Ok(Response::new(Body::from(stats))),
-        _ => Err(anyhow!("handler: Failed to get volume stats")),
+        Err(err) => error_response(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            err.context(format!(
+                "handler: failed to get stats of volume {}",
+                volume_path
+            )),
+        ),
     }
 }
 
@@ -139,16 +231,395 @@ async fn direct_volume_resize_handler(
     let body = hyper::body::to_bytes(req.into_body()).await?;
 
     // unserialize json body into resizeRequest struct
-    let resize_req: ResizeVolumeRequest =
-        serde_json::from_slice(&body).context("shim-mgmt: deserialize resizeRequest failed")?;
-    let result = sandbox.direct_volume_resize(resize_req).await;
+    let resize_req: ResizeVolumeRequest = match serde_json::from_slice(&body) {
+        Ok(resize_req) => resize_req,
+        Err(err) => {
+            return error_response(
+                StatusCode::BAD_REQUEST,
+                anyhow!(err).context("shim-mgmt: deserialize resizeRequest failed"),
+            )
+        }
+    };
+    let volume_path = resize_req.volume_guest_path.clone();
+
+    match sandbox.direct_volume_resize(resize_req).await {
+        Ok(_) => Ok(Response::new(Body::from(""))),
+        Err(err) => error_response(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            err.context(format!("handler: failed to resize volume {}", volume_path)),
+        ),
+    }
+}
+
+/// enable or disable the debug console of the running sandbox, the
+/// console is enabled with "?enable=true" and disabled with "?enable=false"
+async fn debug_console_handler(
+    sandbox: Arc<dyn Sandbox>,
+    requester: &str,
+    req: Request<Body>,
+) -> Result<Response<Body>> {
+    let params = query_params(&req);
+    let enable = params
+        .get(DEBUG_CONSOLE_ENABLE_KEY)
+        .context("shim-mgmt: enable key not found in request params")?
+        .parse::<bool>()
+        .context("shim-mgmt: invalid enable value")?;
+    info!(sl!(), "handler: debug console enable?: {}", enable);
 
+    let result = sandbox.set_debug_console(enable).await;
+    audit(
+        &sl!(),
+        &AuditRecord::new(
+            "set_debug_console",
+            requester,
+            &format!("enable={}", enable),
+            &result,
+        ),
+    );
     match result {
         Ok(_) => Ok(Response::new(Body::from(""))),
-        _ => Err(anyhow!("handler: Failed to resize volume")),
+        _ => Err(anyhow!("handler: Failed to set debug console")),
     }
 }
 
+/// reboot the guest to recover from a corrupted guest state, the containers
+/// are re-created in the rebooted guest
+async fn reboot_handler(
+    sandbox: Arc<dyn Sandbox>,
+    container_manager: Arc<dyn ContainerManager>,
+    _req: Request<Body>,
+) -> Result<Response<Body>> {
+    info!(sl!(), "handler: reboot sandbox");
+    sandbox.reboot().await.context("reboot sandbox")?;
+    container_manager
+        .recreate_containers()
+        .await
+        .context("recreate containers")?;
+    Ok(Response::new(Body::from("")))
+}
+
+/// dump the guest memory to a new host file given with "?path=<path>", for
+/// the forensic analysis, it's only allowed in the configured dump directory
+async fn memory_dump_handler(
+    sandbox: Arc<dyn Sandbox>,
+    requester: &str,
+    req: Request<Body>,
+) -> Result<Response<Body>> {
+    let params = query_params(&req);
+    let path = params
+        .get(MEMORY_DUMP_PATH_KEY)
+        .context("shim-mgmt: path key not found in request params")?;
+    info!(sl!(), "handler: dump guest memory to {}", path);
+
+    let result = sandbox.dump_guest_memory(path).await;
+    audit(
+        &sl!(),
+        &AuditRecord::new("dump_guest_memory", requester, path, &result),
+    );
+    result?;
+    Ok(Response::new(Body::from("")))
+}
+
+/// returns the effective configuration of the sandbox, after the drop-ins
+/// and the annotations are applied, with the secrets redacted
+async fn config_handler(config: Arc<TomlConfig>, _req: Request<Body>) -> Result<Response<Body>> {
+    let dump = config
+        .to_redacted_toml()
+        .context("shim-mgmt: dump config")?;
+    Response::builder()
+        .header(header::CONTENT_TYPE, "application/toml")
+        .body(Body::from(dump))
+        .map_err(|e| anyhow!(e))
+}
+
+/// returns the debug bundle of the sandbox to attach to the bug reports, a
+/// tarball of its persisted state, effective configuration, recent logs,
+/// hypervisor command line, guest network and agent health, with the
+/// secrets redacted
+async fn debug_bundle_handler(
+    sandbox: Arc<dyn Sandbox>,
+    config: Arc<TomlConfig>,
+    requester: &str,
+    _req: Request<Body>,
+) -> Result<Response<Body>> {
+    info!(sl!(), "handler: collect debug bundle");
+    let result = collect_debug_bundle(sandbox.as_ref(), &config).await;
+    audit(
+        &sl!(),
+        &AuditRecord::new("collect_debug_bundle", requester, "", &result),
+    );
+    Response::builder()
+        .header(header::CONTENT_TYPE, "application/x-tar")
+        .body(Body::from(result?))
+        .map_err(|e| anyhow!(e))
+}
+
+/// copy a file into or out of the guest without a shared filesystem,
+/// the guest file is given with "?path=<path>", PUT writes the request
+/// body to it, with the octal mode of "&mode=<mode>", and GET returns its
+/// content
+async fn copy_file_handler(
+    sandbox: Arc<dyn Sandbox>,
+    requester: &str,
+    req: Request<Body>,
+) -> Result<Response<Body>> {
+    let params = query_params(&req);
+    let path = params
+        .get(COPY_FILE_PATH_KEY)
+        .context("shim-mgmt: path key not found in request params")?
+        .to_string();
+    info!(sl!(), "handler: copy file {} {}", req.method(), path);
+
+    let operation = match *req.method() {
+        Method::GET => "copy_file_from_guest",
+        Method::PUT => "copy_file_to_guest",
+        _ => return Err(anyhow!("Copy file only takes PUT and GET")),
+    };
+    // the rejected copies are recorded as well, the content of the file
+    // read is streamed after the record
+    let result = copy_file(sandbox, &params, path.clone(), req).await;
+    audit(
+        &sl!(),
+        &AuditRecord::new(operation, requester, &path, &result),
+    );
+    result
+}
+
+async fn copy_file(
+    sandbox: Arc<dyn Sandbox>,
+    params: &HashMap<String, String>,
+    path: String,
+    req: Request<Body>,
+) -> Result<Response<Body>> {
+    check_copy_file_path(&path)?;
+
+    match *req.method() {
+        Method::GET => read_guest_file(sandbox, path).await,
+        _ => {
+            let file_mode = match params.get(COPY_FILE_MODE_KEY) {
+                Some(mode) => u32::from_str_radix(mode, 8)
+                    .context(format!("shim-mgmt: invalid mode {}", mode))?,
+                None => DEFAULT_COPY_FILE_MODE,
+            };
+            write_guest_file(sandbox, path, file_mode, req).await
+        }
+    }
+}
+
+// the guest path must be absolute, canonical and below the allowed base
+fn check_copy_file_path(path: &str) -> Result<()> {
+    let p = Path::new(path);
+    // components() silently drops the "." and redundant separators, so the
+    // path is canonical only if it's unchanged when rebuilt from them
+    let canonical: PathBuf = p.components().collect();
+    if canonical.as_os_str() != p.as_os_str()
+        || !p
+            .components()
+            .all(|c| matches!(c, Component::RootDir | Component::Normal(_)))
+    {
+        return Err(anyhow!(
+            "shim-mgmt: path {} is not absolute and canonical",
+            path
+        ));
+    }
+    if !p.is_absolute()
+        || !p.starts_with(COPY_FILE_GUEST_BASE)
+        || p == Path::new(COPY_FILE_GUEST_BASE)
+    {
+        return Err(anyhow!(
+            "shim-mgmt: path {} is not below {}",
+            path,
+            COPY_FILE_GUEST_BASE
+        ));
+    }
+
+    Ok(())
+}
+
+// stream the request body into the guest file in chunks, the body size
+// must be given by the Content-Length header
+async fn write_guest_file(
+    sandbox: Arc<dyn Sandbox>,
+    path: String,
+    file_mode: u32,
+    req: Request<Body>,
+) -> Result<Response<Body>> {
+    let file_size = req
+        .headers()
+        .get(header::CONTENT_LENGTH)
+        .context("shim-mgmt: content length is required")?
+        .to_str()?
+        .parse::<i64>()
+        .context("shim-mgmt: invalid content length")?;
+
+    let mut copy_req = CopyFileRequest {
+        path,
+        file_size,
+        file_mode,
+        dir_mode: DEFAULT_COPY_FILE_DIR_MODE,
+        ..Default::default()
+    };
+    let mut body = req.into_body();
+    let mut buf = Vec::with_capacity(COPY_FILE_CHUNK_SIZE);
+    while let Some(data) = body.data().await {
+        buf.extend_from_slice(&data?);
+        while buf.len() >= COPY_FILE_CHUNK_SIZE {
+            let chunk = buf.drain(..COPY_FILE_CHUNK_SIZE).collect();
+            write_guest_file_chunk(&sandbox, &mut copy_req, chunk).await?;
+        }
+    }
+    // an empty file still requires a request to be created
+    if !buf.is_empty() || file_size == 0 {
+        write_guest_file_chunk(&sandbox, &mut copy_req, buf).await?;
+    }
+
+    if copy_req.offset != file_size {
+        return Err(anyhow!(
+            "shim-mgmt: got {} bytes, expected {}",
+            copy_req.offset,
+            file_size
+        ));
+    }
+
+    Ok(Response::new(Body::from("")))
+}
+
+async fn write_guest_file_chunk(
+    sandbox: &Arc<dyn Sandbox>,
+    copy_req: &mut CopyFileRequest,
+    data: Vec<u8>,
+) -> Result<()> {
+    let len = data.len() as i64;
+    if copy_req.offset + len > copy_req.file_size {
+        return Err(anyhow!(
+            "shim-mgmt: got more bytes than expected {}",
+            copy_req.file_size
+        ));
+    }
+
+    let chunk = CopyFileRequest {
+        data,
+        ..copy_req.clone()
+    };
+    sandbox
+        .copy_file(chunk)
+        .await
+        .context(format!("copy file chunk at {}", copy_req.offset))?;
+    copy_req.offset += len;
+
+    Ok(())
+}
+
+// stream the content of the guest file into the response body in chunks
+async fn read_guest_file(sandbox: Arc<dyn Sandbox>, path: String) -> Result<Response<Body>> {
+    // the first chunk is read before responding, so that a missing file
+    // fails the request instead of the transfer
+    let mut resp = read_guest_file_chunk(&sandbox, &path, 0).await?;
+    let file_size = resp.file_size;
+
+    let (mut sender, body) = Body::channel();
+    tokio::spawn(async move {
+        let mut offset = 0;
+        // the file may shrink while being read, stop at its end
+        while !resp.data.is_empty() {
+            offset += resp.data.len() as i64;
+            if sender.send_data(resp.data.into()).await.is_err() {
+                // the client has gone
+                return;
+            }
+            if offset >= file_size {
+                return;
+            }
+
+            resp = match read_guest_file_chunk(&sandbox, &path, offset).await {
+                Ok(resp) => resp,
+                Err(e) => {
+                    warn!(sl!(), "failed to read guest file {}: {:?}", path, e);
+                    sender.abort();
+                    return;
+                }
+            };
+        }
+    });
+
+    Response::builder()
+        .header(header::CONTENT_LENGTH, file_size)
+        .body(body)
+        .map_err(|e| anyhow!(e))
+}
+
+async fn read_guest_file_chunk(
+    sandbox: &Arc<dyn Sandbox>,
+    path: &str,
+    offset: i64,
+) -> Result<ReadFileResponse> {
+    let req = ReadFileRequest {
+        path: path.to_string(),
+        offset,
+        len: COPY_FILE_CHUNK_SIZE as u32,
+    };
+    sandbox
+        .read_file(req)
+        .await
+        .context(format!("read file chunk at {}", offset))
+}
+
+/// stream the stats of all the containers every "?interval=<seconds>",
+/// one json object per line, until the client disconnects
+async fn stats_handler(
+    container_manager: Arc<dyn ContainerManager>,
+    req: Request<Body>,
+) -> Result<Response<Body>> {
+    let params = query_params(&req);
+    let interval = match params.get(STATS_INTERVAL_KEY) {
+        Some(interval) => interval
+            .parse::<u64>()
+            .context(format!("shim-mgmt: invalid interval {}", interval))?,
+        None => DEFAULT_STATS_INTERVAL_SECS,
+    };
+    if interval == 0 {
+        return Err(anyhow!("shim-mgmt: interval must be greater than 0"));
+    }
+    info!(sl!(), "handler: stream stats every {} seconds", interval);
+
+    let (mut sender, body) = Body::channel();
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(Duration::from_secs(interval));
+        loop {
+            ticker.tick().await;
+            let stats = match container_manager.stats_containers().await {
+                Ok(stats) => stats,
+                Err(e) => {
+                    warn!(sl!(), "failed to get stats of containers: {:?}", e);
+                    continue;
+                }
+            };
+            let timestamp = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs();
+            let sample = serde_json::json!({
+                "timestamp": timestamp,
+                "containers": stats,
+            });
+            // the client has gone if the data can't be sent
+            if sender
+                .send_data(format!("{}\n", sample).into())
+                .await
+                .is_err()
+            {
+                info!(sl!(), "handler: stop streaming stats");
+                return;
+            }
+        }
+    });
+
+    Response::builder()
+        .header(header::CONTENT_TYPE, "application/x-ndjson")
+        .body(body)
+        .map_err(|e| anyhow!(e))
+}
+
 // returns the url for metrics
 async fn metrics_url_handler(
     sandbox: Arc<dyn Sandbox>,
@@ -164,3 +635,70 @@ async fn metrics_url_handler(
         agent_metrics, hypervisor_metrics, shim_metrics
     ))))
 }
+
+/// returns the metrics of the in-guest exporter of "?port=<port>", e.g. the
+/// DCGM exporter of the GPUs, the exporter response is passed through
+async fn guest_metrics_handler(
+    sandbox: Arc<dyn Sandbox>,
+    req: Request<Body>,
+) -> Result<Response<Body>> {
+    let params = query_params(&req);
+    let port = match params.get(GUEST_METRICS_PORT_KEY).map(|p| p.parse::<u32>()) {
+        Some(Ok(port)) => port,
+        _ => {
+            return error_response(
+                StatusCode::BAD_REQUEST,
+                anyhow!("shim-mgmt: valid port key not found in request params"),
+            )
+        }
+    };
+
+    match scrape_guest_metrics(sandbox, port).await {
+        Ok(resp) => Ok(resp),
+        // e.g. the exporter isn't running in the guest
+        Err(err) => error_response(
+            StatusCode::BAD_GATEWAY,
+            err.context(format!("handler: failed to scrape guest port {}", port)),
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_copy_file_path() {
+        assert!(check_copy_file_path("/run/kata-containers/foo").is_ok());
+        assert!(check_copy_file_path("/run/kata-containers/shared/foo/bar").is_ok());
+
+        assert!(check_copy_file_path("/run/kata-containers").is_err());
+        assert!(check_copy_file_path("/run/kata-containers/").is_err());
+        assert!(check_copy_file_path("/etc/passwd").is_err());
+        assert!(check_copy_file_path("run/kata-containers/foo").is_err());
+        assert!(check_copy_file_path("/run/kata-containers/../../etc/passwd").is_err());
+        assert!(check_copy_file_path("/run/kata-containers/./foo").is_err());
+        assert!(check_copy_file_path("/run/kata-containers//foo").is_err());
+        assert!(check_copy_file_path("/run/kata-containers-foo/bar").is_err());
+    }
+
+    #[test]
+    fn test_query_params() {
+        let req = Request::builder()
+            .uri("/direct-volume/stats?path=%2Fdev%2Fvdb&foo=bar")
+            .body(Body::empty())
+            .unwrap();
+        let params = query_params(&req);
+        assert_eq!(
+            params.get(DIRECT_VOLUME_PATH_KEY).map(String::as_str),
+            Some("/dev/vdb")
+        );
+        assert_eq!(params.get("foo").map(String::as_str), Some("bar"));
+
+        let req = Request::builder()
+            .uri(DIRECT_VOLUME_STATS_URL)
+            .body(Body::empty())
+            .unwrap();
+        assert!(query_params(&req).is_empty());
+    }
+}
@@ -10,5 +10,8 @@
 //! To call services in a RESTful convention, use the client
 //! from libs/shim-interface library
 
+mod debug_bundle;
+mod debug_console;
+mod guest_metrics;
 mod handlers;
 pub mod server;
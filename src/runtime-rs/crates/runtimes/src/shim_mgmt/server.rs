@@ -14,12 +14,14 @@
 use std::{fs, path::Path, sync::Arc};
 
 use anyhow::{Context, Result};
-use common::Sandbox;
+use common::{ContainerManager, RuntimeInstance, Sandbox};
 use hyper::{server::conn::Http, service::service_fn};
+use kata_types::config::TomlConfig;
 use shim_interface::{mgmt_socket_addr, shim_mgmt::ERR_NO_SHIM_SERVER};
 use tokio::net::UnixListener;
 
 use super::handlers::handler_mux;
+use crate::peer_cred::{peer_identity, PeerCredAuth};
 
 /// The shim management server instance
 pub struct MgmtServer {
@@ -28,14 +30,31 @@ pub struct MgmtServer {
 
     /// The sandbox instance
     pub sandbox: Arc<dyn Sandbox>,
+
+    /// The container manager of the sandbox
+    pub container_manager: Arc<dyn ContainerManager>,
+
+    /// The effective configuration of the sandbox
+    pub config: Arc<TomlConfig>,
+
+    /// Authorization of the peers, if the check is enabled
+    pub peer_cred_auth: Option<PeerCredAuth>,
 }
 
 impl MgmtServer {
     /// construct a new management server
-    pub fn new(sid: &str, sandbox: Arc<dyn Sandbox>) -> Result<Self> {
+    pub fn new(
+        sid: &str,
+        instance: &RuntimeInstance,
+        config: Arc<TomlConfig>,
+        peer_cred_auth: Option<PeerCredAuth>,
+    ) -> Result<Self> {
         Ok(Self {
             s_addr: mgmt_socket_addr(sid).context(ERR_NO_SHIM_SERVER)?,
-            sandbox,
+            sandbox: instance.sandbox.clone(),
+            container_manager: instance.container_manager.clone(),
+            config,
+            peer_cred_auth,
         })
     }
 
@@ -44,18 +63,36 @@ impl MgmtServer {
     // TODO(when metrics is supported): register sandbox metrics
     // running management http server in an infinite loop, able to serve concurrent requests
     pub async fn run(self: Arc<Self>) {
-        let listener = listener_from_path(self.s_addr.clone()).await.unwrap();
+        let listener = listener_from_path(self.s_addr.clone(), self.peer_cred_auth.as_ref())
+            .await
+            .unwrap();
         // start an infinite loop, which serves the incomming uds stream
         loop {
             let (stream, _) = listener.accept().await.unwrap();
+            if let Some(auth) = self.peer_cred_auth.as_ref() {
+                if let Err(err) = auth.authorize(&stream) {
+                    warn!(sl!(), "reject management connection: {:?}", err);
+                    continue;
+                }
+            }
+            let requester = peer_identity(&stream);
             let me = self.clone();
             // spawn a light weight thread to multiplex to the handler
             tokio::task::spawn(async move {
                 if let Err(err) = Http::new()
                     .serve_connection(
                         stream,
-                        service_fn(|request| handler_mux(me.sandbox.clone(), request)),
+                        service_fn(|request| {
+                            handler_mux(
+                                me.sandbox.clone(),
+                                me.container_manager.clone(),
+                                me.config.clone(),
+                                requester.clone(),
+                                request,
+                            )
+                        }),
                     )
+                    .with_upgrades()
                     .await
                 {
                     warn!(sl!(), "Failed to serve connection: {:?}", err);
@@ -67,7 +104,11 @@ impl MgmtServer {
 
 // from path, return a unix listener corresponding to that path,
 // if the path(socket file) is not created, we create that here
-async fn listener_from_path(path: String) -> Result<UnixListener> {
+// and restrict its permissions if the peers are authorized
+async fn listener_from_path(
+    path: String,
+    peer_cred_auth: Option<&PeerCredAuth>,
+) -> Result<UnixListener> {
     // create the socket if not present
     let trim_path = path.strip_prefix("unix:").context("trim path")?;
     let file_path = Path::new("/").join(trim_path);
@@ -77,5 +118,10 @@ async fn listener_from_path(path: String) -> Result<UnixListener> {
     }
     // bind the socket and return the listener
     info!(sl!(), "mgmt-svr: binding to path {}", path);
-    UnixListener::bind(file_path).context("bind address")
+    let listener = UnixListener::bind(file_path).context("bind address")?;
+    if let Some(auth) = peer_cred_auth {
+        auth.restrict_socket(file_path)
+            .context("restrict socket permissions")?;
+    }
+    Ok(listener)
 }
@@ -0,0 +1,159 @@
+// Copyright (c) 2023 Kata Containers community
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+// The container lifecycle against the mock agent, connected through the agent socket of the
+// fake hypervisor, so that it's tested without a VM.
+
+use agent::{
+    kata::KataAgent, Agent, AgentManager, CheckRequest, ContainerID, ContainerProcessID,
+    CreateContainerRequest, CreateSandboxRequest, Empty, ExecProcessRequest, HealthService,
+    RemoveContainerRequest, SignalProcessRequest, WaitProcessRequest,
+};
+use fake_hypervisor::{FakeHypervisor, VmState};
+use hypervisor::Hypervisor;
+use kata_types::config::TomlConfig;
+use mock_agent::{ContainerStatus, MockAgent, MOCK_AGENT_VERSION};
+
+const SERVER_PORT: u32 = 1024;
+
+fn new_agent() -> KataAgent {
+    let config = TomlConfig::load(&format!(
+        r#"
+[agent.kata]
+server_port = {}
+dial_timeout_ms = 10
+reconnect_timeout_ms = 3000
+request_timeout_ms = 5000
+
+[runtime]
+agent_name = "kata"
+        "#,
+        SERVER_PORT
+    ))
+    .unwrap();
+    KataAgent::new(config.agent.get("kata").unwrap().clone())
+}
+
+async fn wait_process(agent: &KataAgent, cid: &str, exec_id: &str) -> i32 {
+    agent
+        .wait_process(WaitProcessRequest {
+            process_id: ContainerProcessID::new(cid, exec_id),
+        })
+        .await
+        .unwrap()
+        .status
+}
+
+#[tokio::test]
+async fn test_container_lifecycle() {
+    let dir = tempfile::tempdir().unwrap();
+    let mock = MockAgent::start(dir.path(), SERVER_PORT).await.unwrap();
+
+    let hypervisor = FakeHypervisor::new(&mock.address());
+    hypervisor.prepare_vm("sandbox", None).await.unwrap();
+    hypervisor.start_vm(0).await.unwrap();
+
+    let agent = new_agent();
+    agent
+        .start(&hypervisor.get_agent_socket().await.unwrap())
+        .await
+        .unwrap();
+    agent.check(CheckRequest::new("")).await.unwrap();
+    assert_eq!(
+        agent
+            .version(CheckRequest::new(""))
+            .await
+            .unwrap()
+            .agent_version,
+        MOCK_AGENT_VERSION
+    );
+
+    agent
+        .create_sandbox(CreateSandboxRequest {
+            sandbox_id: "sandbox".to_string(),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+    assert!(mock.sandbox_created());
+
+    // create and start the container
+    let cid = "container";
+    agent
+        .create_container(CreateContainerRequest {
+            process_id: ContainerProcessID::new(cid, ""),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+    assert_eq!(mock.container_status(cid), Some(ContainerStatus::Created));
+    agent.start_container(ContainerID::new(cid)).await.unwrap();
+    assert_eq!(mock.container_status(cid), Some(ContainerStatus::Running));
+
+    // exec a process and kill it
+    agent
+        .exec_process(ExecProcessRequest {
+            process_id: ContainerProcessID::new(cid, "exec"),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+    agent
+        .signal_process(SignalProcessRequest {
+            process_id: ContainerProcessID::new(cid, "exec"),
+            signal: 15,
+        })
+        .await
+        .unwrap();
+    assert_eq!(wait_process(&agent, cid, "exec").await, 128 + 15);
+
+    // the init process ends by itself
+    mock.exit_process(cid, "", 0).unwrap();
+    assert_eq!(wait_process(&agent, cid, "").await, 0);
+    assert_eq!(mock.container_status(cid), Some(ContainerStatus::Stopped));
+
+    agent
+        .remove_container(RemoveContainerRequest::new(cid, 0))
+        .await
+        .unwrap();
+    assert!(mock.container_ids().is_empty());
+
+    agent.destroy_sandbox(Empty::new()).await.unwrap();
+    assert!(!mock.sandbox_created());
+
+    agent.stop().await;
+    hypervisor.stop_vm().await.unwrap();
+    assert_eq!(hypervisor.state(), VmState::Stopped);
+    mock.stop().await;
+}
+
+#[tokio::test]
+async fn test_container_errors() {
+    let dir = tempfile::tempdir().unwrap();
+    let mock = MockAgent::start(dir.path(), SERVER_PORT).await.unwrap();
+    let hypervisor = FakeHypervisor::new(&mock.address());
+
+    let agent = new_agent();
+    agent
+        .start(&hypervisor.get_agent_socket().await.unwrap())
+        .await
+        .unwrap();
+
+    // the errors of the agent are returned to the runtime
+    assert!(agent
+        .start_container(ContainerID::new("none"))
+        .await
+        .is_err());
+    assert!(agent
+        .exec_process(ExecProcessRequest {
+            process_id: ContainerProcessID::new("none", "exec"),
+            ..Default::default()
+        })
+        .await
+        .is_err());
+
+    agent.stop().await;
+    mock.stop().await;
+}
@@ -0,0 +1,80 @@
+// Copyright (c) 2023 Kata Containers community
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+// The image of the checkpoint of a container is dumped by CRIU in the guest,
+// and copied to the host by chunks with ReadFile.
+
+use std::path::{Path, PathBuf};
+
+use agent::{Agent, CheckpointContainerResponse, ReadFileRequest};
+use anyhow::{anyhow, Context, Result};
+use tokio::{fs, io::AsyncWriteExt};
+
+// the size of the chunks the files are read in, the agent reads 1 MiB at most
+const READ_FILE_CHUNK_SIZE: u32 = 1024 * 1024;
+
+/// Copy the files of the image dumped in the guest to the dir `path` of the
+/// host.
+pub(crate) async fn copy_checkpoint_image(
+    agent: &dyn Agent,
+    image: &CheckpointContainerResponse,
+    path: &str,
+) -> Result<()> {
+    fs::create_dir_all(path)
+        .await
+        .with_context(|| format!("create dir {}", path))?;
+
+    for name in &image.files {
+        let dest = image_file_path(path, name)?;
+        let src = format!("{}/{}", image.image_dir, name);
+        let mut file = fs::File::create(&dest)
+            .await
+            .with_context(|| format!("create {}", dest.display()))?;
+        let mut offset = 0;
+        loop {
+            let resp = agent
+                .read_file(ReadFileRequest {
+                    path: src.clone(),
+                    offset,
+                    len: READ_FILE_CHUNK_SIZE,
+                })
+                .await
+                .with_context(|| format!("read {}", src))?;
+            file.write_all(&resp.data).await?;
+            offset += resp.data.len() as i64;
+            if resp.data.is_empty() || offset >= resp.file_size {
+                break;
+            }
+        }
+        file.sync_all().await?;
+    }
+
+    Ok(())
+}
+
+// image_file_path returns the path on the host of the image file name, which
+// is reported by the guest and so can't escape the dir
+fn image_file_path(dir: &str, name: &str) -> Result<PathBuf> {
+    if name.is_empty() || name.contains('/') || name == "." || name == ".." {
+        return Err(anyhow!("invalid checkpoint image file {:?}", name));
+    }
+    Ok(Path::new(dir).join(name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_image_file_path() {
+        assert_eq!(
+            image_file_path("/var/lib/ckpt", "pages-1.img").unwrap(),
+            PathBuf::from("/var/lib/ckpt/pages-1.img")
+        );
+        for name in ["", ".", "..", "../etc/passwd", "a/b"] {
+            assert!(image_file_path("/var/lib/ckpt", name).is_err(), "{}", name);
+        }
+    }
+}
@@ -5,28 +5,82 @@
 //
 
 use std::collections::HashMap;
+use std::future::Future;
 use std::sync::Arc;
+use std::time::Duration;
 
 use agent::Agent;
 use anyhow::{anyhow, Context, Result};
 use common::{
     error::Error,
+    message::Message,
     types::{
-        ContainerConfig, ContainerID, ContainerProcess, ProcessStateInfo, ProcessStatus,
-        ProcessType,
+        CheckpointRequest, ContainerConfig, ContainerID, ContainerProcess, ProcessStateInfo,
+        ProcessStatus, ProcessType,
     },
 };
 use kata_sys_util::k8s::update_ephemeral_storage_type;
+use kata_types::annotations::{
+    KATA_ANNO_CONTAINER_EXEC_TIMEOUT_SECS, KATA_ANNO_CONTAINER_RES_DISK_QUOTA_IN_BYTES,
+    KATA_ANNO_CONTAINER_ROOTFS_TMPFS_SIZE_IN_BYTES,
+};
+use kata_types::config::Runtime;
+use kata_types::k8s;
+use kata_types::mount::{
+    KATA_OVERLAY_RW_OPTION, KATA_STORAGE_DRIVER_OPTION_QUOTA, KATA_STORAGE_DRIVER_OPTION_TMPFS_SIZE,
+};
 
 use oci::{LinuxResources, Process as OCIProcess};
 use resource::{ResourceManager, ResourceUpdateOp};
-use tokio::sync::RwLock;
+use tokio::sync::{mpsc::Sender, RwLock};
 
 use super::{
+    checkpoint::copy_checkpoint_image,
+    io::OutputPolicy,
     process::{Process, ProcessWatcher},
+    sanitize::sanitize_spec,
     ContainerInner,
 };
 use crate::container_manager::logger_with_process;
+use crate::stats_cache::{Generation, StatsCache};
+
+// Messages reported by the guest image stack when an image fails the signature
+// verification or is rejected by the image security policy.
+const IMAGE_VERIFICATION_FAILURES: &[&str] = &[
+    "Image policy rejected",
+    "Signature verification failed",
+    "Security validate failed",
+];
+
+// the guest dir of the mount points of the rootfs with the writable layer in tmpfs
+const GUEST_TMPFS_ROOTFS_DIR: &str = "/run/kata-containers/tmpfs-rootfs";
+const OVERLAY_STORAGE_DRIVER: &str = "overlayfs";
+
+/// Maximum durations of the lifecycle operations of a container, none for no limit.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub(crate) struct LifecycleTimeouts {
+    pub(crate) create: Option<Duration>,
+    pub(crate) start: Option<Duration>,
+    pub(crate) stop: Option<Duration>,
+}
+
+fn timeout_or_none(ms: u64) -> Option<Duration> {
+    if ms == 0 {
+        None
+    } else {
+        Some(Duration::from_millis(ms))
+    }
+}
+
+impl LifecycleTimeouts {
+    pub(crate) fn new(runtime: &Runtime) -> Self {
+        Self {
+            create: timeout_or_none(runtime.container_create_timeout_ms),
+            start: timeout_or_none(runtime.container_start_timeout_ms),
+            stop: timeout_or_none(runtime.container_stop_timeout_ms),
+        }
+    }
+}
 
 pub struct Exec {
     pub(crate) process: Process,
@@ -42,6 +96,12 @@ pub struct Container {
     agent: Arc<dyn Agent>,
     resource_manager: Arc<ResourceManager>,
     logger: slog::Logger,
+    stats: StatsCache<agent::StatsContainerResponse>,
+    // the stats are cached for the resources they were fetched with
+    resources_generation: Generation,
+    timeouts: LifecycleTimeouts,
+    output_policy: OutputPolicy,
+    msg_sender: Sender<Message>,
 }
 
 impl Container {
@@ -51,11 +111,18 @@ impl Container {
         spec: oci::Spec,
         agent: Arc<dyn Agent>,
         resource_manager: Arc<ResourceManager>,
+        runtime: &Runtime,
+        msg_sender: Sender<Message>,
     ) -> Result<Self> {
         let container_id = ContainerID::new(&config.container_id).context("new container id")?;
-        let logger = sl!().new(o!("container_id" => config.container_id.clone()));
+        let mut logger = sl!().new(o!("container_id" => config.container_id.clone()));
+        // carry the kubernetes identity of the container in its logs
+        for (key, value) in k8s::metadata(&spec).labels() {
+            logger = logger.new(o!(key => value));
+        }
         let process = ContainerProcess::new(&config.container_id, "")?;
-        let init_process = Process::new(
+        let output_policy = OutputPolicy::new(runtime);
+        let mut init_process = Process::new(
             &process,
             pid,
             &config.bundle,
@@ -64,6 +131,8 @@ impl Container {
             config.stderr.clone(),
             config.terminal,
         );
+        init_process.output_policy = output_policy;
+        init_process.msg_sender = Some(msg_sender.clone());
         let linux_resources = spec
             .linux
             .as_ref()
@@ -83,47 +152,98 @@ impl Container {
             agent,
             resource_manager,
             logger,
+            stats: StatsCache::new(runtime.stats_cache_ttl_ms),
+            resources_generation: Generation::default(),
+            timeouts: LifecycleTimeouts::new(runtime),
+            output_policy,
+            msg_sender,
         })
     }
 
-    pub async fn create(&self, mut spec: oci::Spec) -> Result<()> {
+    pub async fn create(&self, spec: oci::Spec) -> Result<()> {
+        self.with_timeout("create", self.timeouts.create, self.do_create(spec))
+            .await
+    }
+
+    async fn do_create(&self, mut spec: oci::Spec) -> Result<()> {
         // process oci spec
         let mut inner = self.inner.write().await;
         let toml_config = self.resource_manager.config().await;
         let config = &self.config;
         let sandbox_pidns = is_pid_namespace_enabled(&spec);
+        let use_builtin_pause =
+            toml_config.runtime.use_builtin_pause && k8s::container_type(&spec).is_pod_sandbox();
+        handle_privileged(&mut spec, &config.container_id, &toml_config.runtime)
+            .context("handle privileged")?;
         amend_spec(&mut spec, toml_config.runtime.disable_guest_seccomp).context("amend spec")?;
+        let disk_quota = get_disk_quota(&spec).context("get disk quota")?;
+        let rootfs_tmpfs_size = get_rootfs_tmpfs_size(&spec).context("get rootfs tmpfs size")?;
 
-        // get mutable root from oci spec
-        let root = match spec.root.as_mut() {
+        // get root from oci spec
+        let root = match spec.root.as_ref() {
             Some(root) => root,
             None => return Err(anyhow!("spec miss root field")),
         };
 
-        // handler rootfs
-        let rootfs = self
-            .resource_manager
-            .handler_rootfs(
-                &config.container_id,
-                root,
-                &config.bundle,
-                &config.rootfs_mounts,
-            )
-            .await
-            .context("handler rootfs")?;
+        // handler rootfs, the sandbox container could use the pause bundle
+        // provided by guest image to avoid sharing rootfs from host.
+        let rootfs = if use_builtin_pause {
+            info!(
+                self.logger,
+                "use builtin pause rootfs for sandbox container"
+            );
+            self.resource_manager
+                .handler_builtin_pause_rootfs()
+                .await
+                .context("handler builtin pause rootfs")?
+        } else {
+            self.resource_manager
+                .handler_rootfs(
+                    &config.container_id,
+                    root,
+                    &config.bundle,
+                    &config.rootfs_mounts,
+                )
+                .await
+                .context("handler rootfs")?
+        };
 
-        // update rootfs
-        root.path = rootfs
+        let guest_rootfs = rootfs
             .get_guest_rootfs_path()
             .await
             .context("get guest rootfs path")?;
 
         let mut storages = vec![];
-        if let Some(storage) = rootfs.get_storage().await {
+        if let Some(mut storage) = rootfs.get_storage().await {
+            // the writable layer of block rootfs lives in the guest, limit
+            // its size with project quota in guest.
+            if let Some(quota) = disk_quota {
+                let is_block_rootfs = rootfs
+                    .get_device_id()
+                    .await
+                    .context("get rootfs device id")?
+                    .is_some();
+                if is_block_rootfs {
+                    info!(self.logger, "set writable layer quota {} bytes", quota);
+                    set_storage_quota(&mut storage, quota);
+                }
+            }
             storages.push(storage);
         }
         inner.rootfs.push(rootfs);
 
+        // the writable layer in tmpfs is an overlay on top of the rootfs
+        let guest_rootfs = match rootfs_tmpfs_size {
+            Some(size) => {
+                info!(self.logger, "set writable layer in tmpfs of {} bytes", size);
+                let storage = rootfs_tmpfs_storage(&config.container_id, &guest_rootfs, size);
+                let path = storage.mount_point.clone();
+                storages.push(storage);
+                path
+            }
+            None => guest_rootfs,
+        };
+
         // handler volumes
         let volumes = self
             .resource_manager
@@ -167,6 +287,7 @@ impl Container {
         if let Some(linux) = &mut spec.linux {
             linux.resources = resources;
         }
+        sanitize_spec(&mut spec, &guest_rootfs);
 
         // create container
         let r = agent::CreateContainerRequest {
@@ -179,21 +300,89 @@ impl Container {
         };
 
         self.agent
-            .create_container(r)
+            .create_container(r.clone())
             .await
+            .map_err(|err| image_verification_error(&config.container_id, err))
             .context("agent create container")?;
+        inner.create_request = Some(r);
         self.resource_manager.dump().await;
         Ok(())
     }
 
+    /// Re-create the container in a rebooted guest with the request it was
+    /// created with, the resources on host are kept by the reboot. The init
+    /// process is restarted if it was running, the exec processes are lost
+    /// with the guest.
+    pub async fn recreate(
+        &self,
+        containers: Arc<RwLock<HashMap<String, Container>>>,
+    ) -> Result<()> {
+        let mut inner = self.inner.write().await;
+        let r = inner
+            .create_request
+            .clone()
+            .context("container is not created in guest")?;
+
+        info!(self.logger, "recreate container");
+        self.agent
+            .create_container(r)
+            .await
+            .context("agent create container")?;
+
+        if inner.init_process.get_status().await == ProcessStatus::Running {
+            inner.init_process.reset_status().await;
+            inner
+                .start_container(&self.container_id)
+                .await
+                .context("start container")?;
+
+            let process = inner.init_process.process.clone();
+            let container_io = inner
+                .new_container_io(&process)
+                .await
+                .context("io stream")?;
+            inner
+                .init_process
+                .start_io_and_wait(containers, self.agent.clone(), container_io)
+                .await
+                .context("start io and wait")?;
+        }
+
+        Ok(())
+    }
+
     pub async fn start(
         &self,
         containers: Arc<RwLock<HashMap<String, Container>>>,
         process: &ContainerProcess,
+    ) -> Result<()> {
+        match process.process_type {
+            ProcessType::Container => {
+                self.with_timeout(
+                    "start",
+                    self.timeouts.start,
+                    self.do_start(containers, process),
+                )
+                .await
+            }
+            ProcessType::Exec => self.do_start(containers, process).await,
+        }
+    }
+
+    async fn do_start(
+        &self,
+        containers: Arc<RwLock<HashMap<String, Container>>>,
+        process: &ContainerProcess,
     ) -> Result<()> {
         let mut inner = self.inner.write().await;
         match process.process_type {
             ProcessType::Container => {
+                // an invalid request, e.g. starting twice, mustn't stop the container
+                inner
+                    .init_process
+                    .check_transition(ProcessStatus::Running)
+                    .await
+                    .context("check state")?;
                 if let Err(err) = inner.start_container(&process.container_id).await {
                     let device_manager = self.resource_manager.get_device_manager().await;
                     let _ = inner.stop_process(process, true, &device_manager).await;
@@ -207,6 +396,14 @@ impl Container {
                     .await?;
             }
             ProcessType::Exec => {
+                inner
+                    .exec_processes
+                    .get(&process.exec_id)
+                    .ok_or_else(|| Error::ProcessNotFound(process.clone()))?
+                    .process
+                    .check_transition(ProcessStatus::Running)
+                    .await
+                    .context("check state")?;
                 if let Err(e) = inner.start_exec_process(process).await {
                     let device_manager = self.resource_manager.get_device_manager().await;
                     let _ = inner.stop_process(process, true, &device_manager).await;
@@ -239,6 +436,7 @@ impl Container {
                         .start_io_and_wait(containers, self.agent.clone(), container_io)
                         .await
                         .context("start io and wait")?;
+                    exec.process.watch_deadline(self.agent.clone());
                 }
             }
         }
@@ -284,6 +482,15 @@ impl Container {
             .context("fetch exit watcher")
     }
 
+    /// Check whether the exec process was killed at its deadline.
+    pub async fn check_deadline(&self, container_process: &ContainerProcess) -> Result<()> {
+        let inner = self.inner.read().await;
+        match inner.exec_processes.get(&container_process.exec_id) {
+            Some(exec) => exec.process.check_deadline().await,
+            None => Ok(()),
+        }
+    }
+
     pub async fn kill_process(
         &self,
         container_process: &ContainerProcess,
@@ -303,7 +510,7 @@ impl Container {
         terminal: bool,
         oci_process: OCIProcess,
     ) -> Result<()> {
-        let process = Process::new(
+        let mut process = Process::new(
             container_process,
             self.pid,
             &self.config.bundle,
@@ -312,6 +519,9 @@ impl Container {
             stderr,
             terminal,
         );
+        process.timeout = get_exec_timeout(&self.spec).context("get exec timeout")?;
+        process.output_policy = self.output_policy;
+        process.msg_sender = Some(self.msg_sender.clone());
         let exec = Exec {
             process,
             oci_process,
@@ -327,6 +537,20 @@ impl Container {
     }
 
     pub async fn stop_process(&self, container_process: &ContainerProcess) -> Result<()> {
+        match container_process.process_type {
+            ProcessType::Container => {
+                self.with_timeout(
+                    "stop",
+                    self.timeouts.stop,
+                    self.do_stop_process(container_process),
+                )
+                .await
+            }
+            ProcessType::Exec => self.do_stop_process(container_process).await,
+        }
+    }
+
+    async fn do_stop_process(&self, container_process: &ContainerProcess) -> Result<()> {
         let mut inner = self.inner.write().await;
         let device_manager = self.resource_manager.get_device_manager().await;
         inner
@@ -348,16 +572,68 @@ impl Container {
         Ok(())
     }
 
+    // run the lifecycle operation `op` of the container, which is aborted if the operation
+    // doesn't complete within `limit`, e.g. when the agent hangs
+    async fn with_timeout<F>(&self, op: &str, limit: Option<Duration>, f: F) -> Result<()>
+    where
+        F: Future<Output = Result<()>>,
+    {
+        let limit = match limit {
+            Some(limit) => limit,
+            None => return f.await,
+        };
+        match tokio::time::timeout(limit, f).await {
+            Ok(result) => result,
+            Err(_) => {
+                warn!(self.logger, "{} timed out after {:?}, abort", op, limit);
+                self.abort().await;
+                Err(Error::Timeout(op.to_string(), self.config.container_id.clone(), limit).into())
+            }
+        }
+    }
+
+    // release what the timed out operation left of the container, the lock of the inner
+    // state is released as the operation is dropped
+    async fn abort(&self) {
+        let mut inner = self.inner.write().await;
+        let device_manager = self.resource_manager.get_device_manager().await;
+        inner
+            .abort_container(&self.config.container_id, &device_manager)
+            .await;
+
+        // update vcpus, mems and host cgroups
+        if let Err(e) = self
+            .resource_manager
+            .update_linux_resource(
+                &self.config.container_id,
+                inner.linux_resources.as_ref(),
+                ResourceUpdateOp::Del,
+            )
+            .await
+        {
+            warn!(
+                self.logger,
+                "abort: failed to update linux resource: {:?}", e
+            );
+        }
+    }
+
     pub async fn pause(&self) -> Result<()> {
         let inner = self.inner.read().await;
         if inner.init_process.get_status().await == ProcessStatus::Paused {
             warn!(self.logger, "container is paused no need to pause");
             return Ok(());
         }
+        inner
+            .init_process
+            .check_transition(ProcessStatus::Paused)
+            .await
+            .context("check state")?;
         self.agent
             .pause_container(self.container_id.clone().into())
             .await
             .context("agent pause container")?;
+        inner.init_process.transition(ProcessStatus::Paused).await?;
         Ok(())
     }
 
@@ -367,10 +643,44 @@ impl Container {
             warn!(self.logger, "container is running no need to resume");
             return Ok(());
         }
+        inner
+            .init_process
+            .check_transition(ProcessStatus::Running)
+            .await
+            .context("check state")?;
         self.agent
             .resume_container(self.container_id.clone().into())
             .await
             .context("agent pause container")?;
+        inner
+            .init_process
+            .transition(ProcessStatus::Running)
+            .await?;
+        Ok(())
+    }
+
+    pub async fn checkpoint(&self, req: &CheckpointRequest) -> Result<()> {
+        let inner = self.inner.read().await;
+        if inner.init_process.get_status().await != ProcessStatus::Running {
+            return Err(anyhow!("container {} is not running", req.container_id));
+        }
+        let image = self
+            .agent
+            .checkpoint_container(agent::CheckpointContainerRequest {
+                container_id: req.container_id.clone(),
+                leave_running: !req.exit,
+            })
+            .await
+            .context("agent checkpoint container")?;
+        info!(
+            self.logger,
+            "checkpoint image of {} files dumped to {}",
+            image.files.len(),
+            image.image_dir
+        );
+        copy_checkpoint_image(self.agent.as_ref(), &image, &req.path)
+            .await
+            .context("copy checkpoint image")?;
         Ok(())
     }
 
@@ -412,16 +722,35 @@ impl Container {
 
     pub async fn stats(&self) -> Result<Option<agent::StatsContainerResponse>> {
         let stats_resp = self
-            .agent
-            .stats_container(self.container_id.clone().into())
-            .await
-            .context("agent stats container")?;
+            .stats
+            .get_or_fetch_at(&self.resources_generation, || async {
+                self.agent
+                    .stats_container(self.container_id.clone().into())
+                    .await
+                    .context("agent stats container")
+            })
+            .await?;
         Ok(Some(stats_resp))
     }
 
+    /// Update the resources of the container, e.g. for the in-place resize of the pod. The memory
+    /// of the VM is grown before the limit of the container is raised in the guest, and shrunk
+    /// after it's lowered. The update fails without the resources being applied otherwise.
     pub async fn update(&self, resources: &LinuxResources) -> Result<()> {
         let mut inner = self.inner.write().await;
-        inner.linux_resources = Some(resources.clone());
+        let _update = self.resources_generation.begin_update();
+        let old_resources = inner.linux_resources.clone();
+        let old_limit = memory_limit(old_resources.as_ref());
+        let new_limit = memory_limit(Some(resources));
+        let shrink = match (old_limit, new_limit) {
+            (Some(old), Some(new)) => new < old,
+            (None, Some(_)) => true,
+            _ => false,
+        };
+        if let Some(new_limit) = new_limit.filter(|_| shrink) {
+            self.check_memory_usage(new_limit).await?;
+        }
+
         // update vcpus, mems and host cgroups
         let agent_resources = self
             .resource_manager
@@ -430,20 +759,89 @@ impl Container {
                 Some(resources),
                 ResourceUpdateOp::Update,
             )
-            .await?;
+            .await;
+        let agent_resources = match agent_resources {
+            Ok(agent_resources) => agent_resources,
+            Err(e) => {
+                self.rollback_update(old_resources.as_ref()).await;
+                return Err(e);
+            }
+        };
 
         let req = agent::UpdateContainerRequest {
             container_id: self.container_id.container_id.clone(),
             resources: agent_resources,
             mounts: Vec::new(),
         };
-        self.agent
+        if let Err(e) = self
+            .agent
             .update_container(req)
             .await
-            .context("agent update container")?;
+            .context("agent update container")
+        {
+            self.rollback_update(old_resources.as_ref()).await;
+            return Err(e);
+        }
+        inner.linux_resources = Some(resources.clone());
+
+        // the memory released by the container goes back to the host now, the reconciler would
+        // retry it otherwise
+        if shrink
+            && !self
+                .resource_manager
+                .config()
+                .await
+                .runtime
+                .static_sandbox_resource_mgmt
+        {
+            if let Err(e) = self.resource_manager.reconcile_memory().await {
+                warn!(self.logger, "failed to shrink memory: {:?}", e);
+            }
+        }
+        Ok(())
+    }
+
+    // the memory limit can't be lowered below the memory the container uses, the kernel would
+    // reclaim or OOM kill it to fit, kubelet retries the resize later instead
+    async fn check_memory_usage(&self, limit: u64) -> Result<()> {
+        let stats = self
+            .agent
+            .stats_container(self.container_id.clone().into())
+            .await
+            .context("agent stats container")?;
+        let working_set = stats
+            .cgroup_stats
+            .as_ref()
+            .and_then(|c| c.memory_stats.as_ref())
+            .map(memory_working_set)
+            .unwrap_or_default();
+        if working_set > limit {
+            return Err(anyhow!(
+                "memory limit {} below the memory used by container {}: {}",
+                limit,
+                self.config.container_id,
+                working_set
+            ));
+        }
         Ok(())
     }
 
+    // restore the records of the resources of the container, the VM is resized back by the
+    // memory reconciler
+    async fn rollback_update(&self, old_resources: Option<&LinuxResources>) {
+        if let Err(e) = self
+            .resource_manager
+            .update_linux_resource(
+                &self.config.container_id,
+                old_resources,
+                ResourceUpdateOp::Update,
+            )
+            .await
+        {
+            warn!(self.logger, "failed to roll back resources update: {:?}", e);
+        }
+    }
+
     pub async fn config(&self) -> ContainerConfig {
         self.config.clone()
     }
@@ -476,32 +874,184 @@ fn amend_spec(spec: &mut oci::Spec, disable_guest_seccomp: bool) -> Result<()> {
         if disable_guest_seccomp {
             linux.seccomp = None;
         }
+    }
 
-        if let Some(resource) = linux.resources.as_mut() {
-            resource.devices = Vec::new();
-            resource.pids = None;
-            resource.block_io = None;
-            resource.network = None;
-            resource.rdma = HashMap::new();
+    Ok(())
+}
+
+// is_privileged checks if the container is privileged, the container runtimes
+// allow all the devices in the device cgroup of privileged containers.
+fn is_privileged(spec: &oci::Spec) -> bool {
+    let resources = match spec.linux.as_ref().and_then(|l| l.resources.as_ref()) {
+        Some(resources) => resources,
+        None => return false,
+    };
+
+    resources.devices.iter().any(|d| {
+        d.allow
+            && (d.r#type.is_empty() || d.r#type == "a")
+            && d.major.is_none()
+            && d.minor.is_none()
+            && d.access == "rwm"
+    })
+}
+
+// handle_privileged applies the runtime policy of privileged containers, it must
+// be called before the device cgroup is dropped from the spec by sanitize_spec.
+fn handle_privileged(spec: &mut oci::Spec, container_id: &str, runtime: &Runtime) -> Result<()> {
+    if !is_privileged(spec) {
+        return Ok(());
+    }
+
+    if runtime.deny_privileged {
+        return Err(anyhow!(
+            "privileged container {} is not allowed",
+            container_id
+        ));
+    }
+
+    // the devices of privileged containers are all the devices of the host,
+    // don't pass them into the VM
+    if runtime.privileged_without_host_devices {
+        if let Some(linux) = spec.linux.as_mut() {
+            linux.devices.clear();
         }
+    }
 
-        // Host pidns path does not make sense in kata. Let's just align it with
-        // sandbox namespace whenever it is set.
-        let mut ns: Vec<oci::LinuxNamespace> = Vec::new();
-        for n in linux.namespaces.iter() {
-            match n.r#type.as_str() {
-                oci::PIDNAMESPACE | oci::NETWORKNAMESPACE => continue,
-                _ => ns.push(oci::LinuxNamespace {
-                    r#type: n.r#type.clone(),
-                    path: "".to_string(),
-                }),
-            }
+    Ok(())
+}
+
+// image_verification_error turns the image verification failure reported by agent into a
+// typed error, so that it could be surfaced to the caller with a meaningful status.
+fn image_verification_error(container_id: &str, err: anyhow::Error) -> anyhow::Error {
+    let msg = format!("{:?}", err);
+    if IMAGE_VERIFICATION_FAILURES
+        .iter()
+        .any(|pattern| msg.contains(pattern))
+    {
+        return anyhow!(Error::ImageVerificationFailed(
+            container_id.to_string(),
+            err.root_cause().to_string()
+        ));
+    }
+
+    err
+}
+
+// memory_limit gets the memory limit in bytes of the resources, None if unlimited.
+fn memory_limit(resources: Option<&LinuxResources>) -> Option<u64> {
+    resources
+        .and_then(|r| r.memory.as_ref())
+        .and_then(|m| m.limit)
+        .filter(|limit| *limit > 0)
+        .map(|limit| limit as u64)
+}
+
+// memory_working_set gets the memory used by the container the way kubelet accounts it against
+// the limit: the usage without the inactive page cache.
+fn memory_working_set(stats: &agent::types::MemoryStats) -> u64 {
+    let usage = stats.usage.as_ref().map_or(0, |u| u.usage);
+    // cgroup v2 and v1 keys
+    let inactive_file = stats
+        .stats
+        .get("inactive_file")
+        .or_else(|| stats.stats.get("total_inactive_file"))
+        .copied()
+        .unwrap_or_default();
+    usage.saturating_sub(inactive_file)
+}
+
+// get_disk_quota gets the size limit in bytes of the container writable layer
+// from the container annotations.
+fn get_disk_quota(spec: &oci::Spec) -> Result<Option<u64>> {
+    match spec
+        .annotations
+        .get(KATA_ANNO_CONTAINER_RES_DISK_QUOTA_IN_BYTES)
+    {
+        Some(value) => {
+            let quota = value
+                .parse::<u64>()
+                .with_context(|| format!("invalid disk quota {:?}", value))?;
+            Ok((quota > 0).then_some(quota))
         }
+        None => Ok(None),
+    }
+}
 
-        linux.namespaces = ns;
+// get_rootfs_tmpfs_size gets the size in bytes of the tmpfs holding the
+// container writable layer from the container annotations, it's charged to the
+// memory of the container, so it must fit in its memory limit.
+fn get_rootfs_tmpfs_size(spec: &oci::Spec) -> Result<Option<u64>> {
+    let value = match spec
+        .annotations
+        .get(KATA_ANNO_CONTAINER_ROOTFS_TMPFS_SIZE_IN_BYTES)
+    {
+        Some(value) => value,
+        None => return Ok(None),
+    };
+    let size = value
+        .parse::<u64>()
+        .with_context(|| format!("invalid rootfs tmpfs size {:?}", value))?;
+    if size == 0 {
+        return Ok(None);
     }
 
-    Ok(())
+    let resources = spec.linux.as_ref().and_then(|l| l.resources.as_ref());
+    if let Some(limit) = memory_limit(resources) {
+        if size > limit {
+            return Err(anyhow!(
+                "rootfs tmpfs size {} exceeds the memory limit {} of the container",
+                size,
+                limit
+            ));
+        }
+    }
+    Ok(Some(size))
+}
+
+// rootfs_tmpfs_storage requests agent to mount an overlay of the rootfs with
+// its upper dir in a tmpfs of size bytes.
+fn rootfs_tmpfs_storage(cid: &str, guest_rootfs: &str, size: u64) -> agent::Storage {
+    agent::Storage {
+        driver: OVERLAY_STORAGE_DRIVER.to_string(),
+        driver_options: vec![format!(
+            "{}={}",
+            KATA_STORAGE_DRIVER_OPTION_TMPFS_SIZE, size
+        )],
+        source: "overlay".to_string(),
+        fs_type: "overlay".to_string(),
+        options: vec![
+            format!("lowerdir={}", guest_rootfs),
+            KATA_OVERLAY_RW_OPTION.to_string(),
+        ],
+        mount_point: format!("{}/{}", GUEST_TMPFS_ROOTFS_DIR, cid),
+        ..Default::default()
+    }
+}
+
+// get_exec_timeout gets the run time limit of the exec processes from the
+// container annotations.
+fn get_exec_timeout(spec: &oci::Spec) -> Result<Option<Duration>> {
+    match spec.annotations.get(KATA_ANNO_CONTAINER_EXEC_TIMEOUT_SECS) {
+        Some(value) => {
+            let secs = value
+                .parse::<u64>()
+                .with_context(|| format!("invalid exec timeout {:?}", value))?;
+            Ok((secs > 0).then(|| Duration::from_secs(secs)))
+        }
+        None => Ok(None),
+    }
+}
+
+// set_storage_quota requests agent to limit the size of storage with project quota,
+// which requires the filesystem to be mounted with prjquota.
+fn set_storage_quota(storage: &mut agent::Storage, quota: u64) {
+    if !storage.options.iter().any(|o| o == "prjquota") {
+        storage.options.push("prjquota".to_string());
+    }
+    storage
+        .driver_options
+        .push(format!("{}={}", KATA_STORAGE_DRIVER_OPTION_QUOTA, quota));
 }
 
 // is_pid_namespace_enabled checks if Pid namespace for a container needs to be shared with its sandbox
@@ -521,7 +1071,25 @@ fn is_pid_namespace_enabled(spec: &oci::Spec) -> bool {
 #[cfg(test)]
 mod tests {
     use super::amend_spec;
+    use super::get_disk_quota;
+    use super::get_exec_timeout;
+    use super::get_rootfs_tmpfs_size;
+    use super::handle_privileged;
+    use super::image_verification_error;
     use super::is_pid_namespace_enabled;
+    use super::memory_limit;
+    use super::memory_working_set;
+    use super::rootfs_tmpfs_storage;
+    use super::set_storage_quota;
+    use super::LifecycleTimeouts;
+    use anyhow::anyhow;
+    use common::error::Error;
+    use kata_types::annotations::{
+        KATA_ANNO_CONTAINER_EXEC_TIMEOUT_SECS, KATA_ANNO_CONTAINER_RES_DISK_QUOTA_IN_BYTES,
+        KATA_ANNO_CONTAINER_ROOTFS_TMPFS_SIZE_IN_BYTES,
+    };
+    use kata_types::config::Runtime;
+    use std::time::Duration;
     #[test]
     fn test_amend_spec_disable_guest_seccomp() {
         let mut spec = oci::Spec {
@@ -543,6 +1111,179 @@ mod tests {
         assert!(spec.linux.as_ref().unwrap().seccomp.is_none());
     }
 
+    #[test]
+    fn test_handle_privileged() {
+        let privileged_spec = || oci::Spec {
+            linux: Some(oci::Linux {
+                devices: vec![oci::LinuxDevice {
+                    path: "/dev/sda".to_string(),
+                    r#type: "b".to_string(),
+                    major: 8,
+                    ..Default::default()
+                }],
+                resources: Some(oci::LinuxResources {
+                    devices: vec![oci::LinuxDeviceCgroup {
+                        allow: true,
+                        access: "rwm".to_string(),
+                        ..Default::default()
+                    }],
+                    ..Default::default()
+                }),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+
+        // host devices are kept by default
+        let mut spec = privileged_spec();
+        handle_privileged(&mut spec, "c1", &Runtime::default()).unwrap();
+        assert_eq!(spec.linux.as_ref().unwrap().devices.len(), 1);
+
+        let runtime = Runtime {
+            privileged_without_host_devices: true,
+            ..Default::default()
+        };
+        handle_privileged(&mut spec, "c1", &runtime).unwrap();
+        assert!(spec.linux.as_ref().unwrap().devices.is_empty());
+
+        let runtime = Runtime {
+            deny_privileged: true,
+            ..Default::default()
+        };
+        let mut spec = privileged_spec();
+        handle_privileged(&mut spec, "c1", &runtime).unwrap_err();
+
+        // a device cgroup not allowing all devices isn't privileged
+        spec.linux
+            .as_mut()
+            .unwrap()
+            .resources
+            .as_mut()
+            .unwrap()
+            .devices[0]
+            .r#type = "c".to_string();
+        handle_privileged(&mut spec, "c1", &runtime).unwrap();
+        assert_eq!(spec.linux.as_ref().unwrap().devices.len(), 1);
+    }
+
+    #[test]
+    fn test_image_verification_error() {
+        let err = image_verification_error("cid", anyhow!("failed to mount rootfs"));
+        assert!(err.downcast_ref::<Error>().is_none());
+
+        let err = image_verification_error(
+            "cid",
+            anyhow!("Image policy rejected: signature of quay.io/busybox is invalid")
+                .context("pull image"),
+        );
+        match err.downcast_ref::<Error>() {
+            Some(Error::ImageVerificationFailed(cid, _)) => assert_eq!(cid, "cid"),
+            _ => panic!("expected image verification error"),
+        }
+    }
+
+    #[test]
+    fn test_disk_quota() {
+        let mut spec = oci::Spec::default();
+        assert_eq!(get_disk_quota(&spec).unwrap(), None);
+
+        spec.annotations.insert(
+            KATA_ANNO_CONTAINER_RES_DISK_QUOTA_IN_BYTES.to_string(),
+            "0".to_string(),
+        );
+        assert_eq!(get_disk_quota(&spec).unwrap(), None);
+
+        spec.annotations.insert(
+            KATA_ANNO_CONTAINER_RES_DISK_QUOTA_IN_BYTES.to_string(),
+            "1g".to_string(),
+        );
+        assert!(get_disk_quota(&spec).is_err());
+
+        spec.annotations.insert(
+            KATA_ANNO_CONTAINER_RES_DISK_QUOTA_IN_BYTES.to_string(),
+            "1073741824".to_string(),
+        );
+        assert_eq!(get_disk_quota(&spec).unwrap(), Some(1073741824));
+
+        let mut storage = agent::Storage {
+            options: vec!["rw".to_string()],
+            ..Default::default()
+        };
+        set_storage_quota(&mut storage, 1073741824);
+        assert_eq!(storage.options, vec!["rw", "prjquota"]);
+        assert_eq!(storage.driver_options, vec!["quota_bytes=1073741824"]);
+    }
+
+    #[test]
+    fn test_rootfs_tmpfs_size() {
+        let mut spec = oci::Spec::default();
+        assert_eq!(get_rootfs_tmpfs_size(&spec).unwrap(), None);
+
+        spec.annotations.insert(
+            KATA_ANNO_CONTAINER_ROOTFS_TMPFS_SIZE_IN_BYTES.to_string(),
+            "1g".to_string(),
+        );
+        assert!(get_rootfs_tmpfs_size(&spec).is_err());
+
+        spec.annotations.insert(
+            KATA_ANNO_CONTAINER_ROOTFS_TMPFS_SIZE_IN_BYTES.to_string(),
+            "1073741824".to_string(),
+        );
+        assert_eq!(get_rootfs_tmpfs_size(&spec).unwrap(), Some(1073741824));
+
+        // the tmpfs is charged to the memory of the container
+        spec.linux = Some(oci::Linux {
+            resources: Some(oci::LinuxResources {
+                memory: Some(oci::LinuxMemory {
+                    limit: Some(536870912),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            }),
+            ..Default::default()
+        });
+        assert!(get_rootfs_tmpfs_size(&spec).is_err());
+
+        let storage = rootfs_tmpfs_storage("c1", "/run/kata-containers/shared/c1/rootfs", 1024);
+        assert_eq!(storage.driver, "overlayfs");
+        assert_eq!(storage.driver_options, vec!["tmpfs_size_bytes=1024"]);
+        assert_eq!(
+            storage.options,
+            vec![
+                "lowerdir=/run/kata-containers/shared/c1/rootfs",
+                "io.katacontainers.fs-opt.overlay-rw"
+            ]
+        );
+        assert_eq!(storage.mount_point, "/run/kata-containers/tmpfs-rootfs/c1");
+    }
+
+    #[test]
+    fn test_exec_timeout() {
+        let mut spec = oci::Spec::default();
+        assert_eq!(get_exec_timeout(&spec).unwrap(), None);
+
+        spec.annotations.insert(
+            KATA_ANNO_CONTAINER_EXEC_TIMEOUT_SECS.to_string(),
+            "0".to_string(),
+        );
+        assert_eq!(get_exec_timeout(&spec).unwrap(), None);
+
+        spec.annotations.insert(
+            KATA_ANNO_CONTAINER_EXEC_TIMEOUT_SECS.to_string(),
+            "10s".to_string(),
+        );
+        assert!(get_exec_timeout(&spec).is_err());
+
+        spec.annotations.insert(
+            KATA_ANNO_CONTAINER_EXEC_TIMEOUT_SECS.to_string(),
+            "30".to_string(),
+        );
+        assert_eq!(
+            get_exec_timeout(&spec).unwrap(),
+            Some(Duration::from_secs(30))
+        );
+    }
+
     #[test]
     fn test_is_pid_namespace_enabled() {
         struct TestData<'a> {
@@ -607,4 +1348,63 @@ mod tests {
             );
         }
     }
+
+    #[test]
+    fn test_lifecycle_timeouts() {
+        // no limit by default
+        let runtime = Runtime::default();
+        assert_eq!(
+            LifecycleTimeouts::new(&runtime),
+            LifecycleTimeouts::default()
+        );
+
+        let runtime = Runtime {
+            container_create_timeout_ms: 120000,
+            container_stop_timeout_ms: 500,
+            ..Default::default()
+        };
+        assert_eq!(
+            LifecycleTimeouts::new(&runtime),
+            LifecycleTimeouts {
+                create: Some(Duration::from_secs(120)),
+                start: None,
+                stop: Some(Duration::from_millis(500)),
+            }
+        );
+    }
+
+    #[test]
+    fn test_memory_limit() {
+        let resources = |limit: Option<i64>| oci::LinuxResources {
+            memory: Some(oci::LinuxMemory {
+                limit,
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        assert_eq!(memory_limit(None), None);
+        assert_eq!(memory_limit(Some(&oci::LinuxResources::default())), None);
+        assert_eq!(memory_limit(Some(&resources(None))), None);
+        assert_eq!(memory_limit(Some(&resources(Some(-1)))), None);
+        assert_eq!(memory_limit(Some(&resources(Some(1 << 30)))), Some(1 << 30));
+    }
+
+    #[test]
+    fn test_memory_working_set() {
+        let mut stats = agent::types::MemoryStats {
+            usage: Some(agent::types::MemoryData {
+                usage: 300,
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        assert_eq!(memory_working_set(&stats), 300);
+
+        stats.stats.insert("inactive_file".to_string(), 100);
+        assert_eq!(memory_working_set(&stats), 200);
+
+        stats.stats.clear();
+        stats.stats.insert("total_inactive_file".to_string(), 400);
+        assert_eq!(memory_working_set(&stats), 0);
+    }
 }
@@ -23,6 +23,7 @@ use crate::container_manager::logger_with_process;
 use super::{
     io::ContainerIo,
     process::{Process, ProcessWatcher},
+    state::check_status,
     Exec,
 };
 
@@ -34,6 +35,8 @@ pub struct ContainerInner {
     pub(crate) rootfs: Vec<Arc<dyn Rootfs>>,
     pub(crate) volumes: Vec<Arc<dyn Volume>>,
     pub(crate) linux_resources: Option<LinuxResources>,
+    // the request the container is created in guest with
+    pub(crate) create_request: Option<agent::CreateContainerRequest>,
 }
 
 impl ContainerInner {
@@ -51,6 +54,7 @@ impl ContainerInner {
             rootfs: vec![],
             volumes: vec![],
             linux_resources,
+            create_request: None,
         }
     }
 
@@ -60,20 +64,8 @@ impl ContainerInner {
 
     pub(crate) async fn check_state(&self, states: Vec<ProcessStatus>) -> Result<()> {
         let state = self.init_process.get_status().await;
-        if states.contains(&state) {
-            return Ok(());
-        }
-
-        Err(anyhow!(
-            "failed to check state {:?} for {:?}",
-            state,
-            states
-        ))
-    }
-
-    pub(crate) async fn set_state(&mut self, state: ProcessStatus) {
-        let mut status = self.init_process.status.write().await;
-        *status = state;
+        check_status(&self.init_process.process, state, &states)?;
+        Ok(())
     }
 
     pub(crate) async fn start_exec_process(&mut self, process: &ContainerProcess) -> Result<()> {
@@ -81,6 +73,10 @@ impl ContainerInner {
             .exec_processes
             .get_mut(&process.exec_id)
             .ok_or_else(|| Error::ProcessNotFound(process.clone()))?;
+        exec.process
+            .check_transition(ProcessStatus::Running)
+            .await
+            .context("check state")?;
 
         self.agent
             .exec_process(agent::ExecProcessRequest {
@@ -90,7 +86,7 @@ impl ContainerInner {
             })
             .await
             .context("exec process")?;
-        exec.process.set_status(ProcessStatus::Running).await;
+        exec.process.transition(ProcessStatus::Running).await?;
         Ok(())
     }
 
@@ -128,7 +124,8 @@ impl ContainerInner {
     }
 
     pub(crate) async fn start_container(&mut self, cid: &ContainerID) -> Result<()> {
-        self.check_state(vec![ProcessStatus::Created, ProcessStatus::Stopped])
+        self.init_process
+            .check_transition(ProcessStatus::Running)
             .await
             .context("check state")?;
 
@@ -139,7 +136,7 @@ impl ContainerInner {
             .await
             .context("start container")?;
 
-        self.set_state(ProcessStatus::Running).await;
+        self.init_process.transition(ProcessStatus::Running).await?;
 
         Ok(())
     }
@@ -211,6 +208,32 @@ impl ContainerInner {
         Ok(())
     }
 
+    /// Clean up the container whose create, start or stop timed out. The agent may not respond
+    /// anymore, so the container is removed from the guest on a best effort basis and the
+    /// resources on host are released anyway.
+    pub(crate) async fn abort_container(
+        &mut self,
+        cid: &str,
+        device_manager: &RwLock<DeviceManager>,
+    ) {
+        let remove_request = agent::RemoveContainerRequest {
+            container_id: cid.to_string(),
+            ..Default::default()
+        };
+        if let Err(e) = self.agent.remove_container(remove_request).await {
+            warn!(self.logger, "abort: agent remove container failed: {}", e);
+        }
+
+        self.init_process.stop().await;
+
+        if let Err(e) = self.clean_volumes(device_manager).await {
+            warn!(self.logger, "abort: failed to clean volumes: {:?}", e);
+        }
+        if let Err(e) = self.clean_rootfs(device_manager).await {
+            warn!(self.logger, "abort: failed to clean rootfs: {:?}", e);
+        }
+    }
+
     pub(crate) async fn stop_process(
         &mut self,
         process: &ContainerProcess,
@@ -0,0 +1,199 @@
+// Copyright (c) 2023 Kata Containers community
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, Write},
+    mem,
+    os::unix::fs::MetadataExt,
+    path::{Path, PathBuf},
+    pin::Pin,
+    task::{Context as TaskContext, Poll},
+};
+
+use anyhow::{Context, Result};
+use chrono::{SecondsFormat, Utc};
+use tokio::io::AsyncWrite;
+
+// the lines longer than this are split into partial records, like containerd does
+const CRI_LOG_MAX_LINE_SIZE: usize = 16 * 1024;
+const CRI_LOG_TAG_FULL: &str = "F";
+const CRI_LOG_TAG_PARTIAL: &str = "P";
+
+/// Container log file written by the shim in the CRI log format, i.e. every line is prefixed
+/// by its timestamp, its stream and whether it's a full or a partial line:
+///   2023-06-01T08:00:00.000000000Z stdout F hello
+/// Both the stdout and the stderr may be written to the same file, every record is appended by
+/// a single write. The file is reopened when it's rotated, i.e. renamed or removed by kubelet.
+pub(crate) struct CriLogFile {
+    path: PathBuf,
+    stream: &'static str,
+    file: File,
+    ino: u64,
+    // the end of the output not terminated by a new line yet
+    pending: Vec<u8>,
+}
+
+fn open_log_file(path: &Path) -> io::Result<(File, u64)> {
+    let file = OpenOptions::new().create(true).append(true).open(path)?;
+    let ino = file.metadata()?.ino();
+    Ok((file, ino))
+}
+
+impl CriLogFile {
+    pub(crate) fn open(path: &str, stream: &'static str) -> Result<Self> {
+        let path = PathBuf::from(path);
+        let (file, ino) =
+            open_log_file(&path).with_context(|| format!("open log file {:?}", path))?;
+        Ok(Self {
+            path,
+            stream,
+            file,
+            ino,
+            pending: vec![],
+        })
+    }
+
+    fn reopen_if_rotated(&mut self) -> io::Result<()> {
+        match fs::metadata(&self.path) {
+            Ok(m) if m.ino() == self.ino => Ok(()),
+            Ok(_) => self.reopen(),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => self.reopen(),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn reopen(&mut self) -> io::Result<()> {
+        let (file, ino) = open_log_file(&self.path)?;
+        self.file = file;
+        self.ino = ino;
+        Ok(())
+    }
+
+    fn write_record(&mut self, line: &[u8], tag: &str) -> io::Result<()> {
+        let timestamp = Utc::now().to_rfc3339_opts(SecondsFormat::Nanos, true);
+        let mut record = Vec::with_capacity(timestamp.len() + line.len() + 16);
+        write!(record, "{} {} {} ", timestamp, self.stream, tag)?;
+        record.extend_from_slice(line);
+        record.push(b'\n');
+        self.file.write_all(&record)
+    }
+
+    fn write_output(&mut self, buf: &[u8]) -> io::Result<()> {
+        self.reopen_if_rotated()?;
+
+        let mut data = buf;
+        while let Some(pos) = data.iter().position(|b| *b == b'\n') {
+            self.pending.extend_from_slice(&data[..pos]);
+            let line = mem::take(&mut self.pending);
+            self.write_record(&line, CRI_LOG_TAG_FULL)?;
+            data = &data[pos + 1..];
+        }
+        self.pending.extend_from_slice(data);
+
+        while self.pending.len() >= CRI_LOG_MAX_LINE_SIZE {
+            let rest = self.pending.split_off(CRI_LOG_MAX_LINE_SIZE);
+            let line = mem::replace(&mut self.pending, rest);
+            self.write_record(&line, CRI_LOG_TAG_PARTIAL)?;
+        }
+        Ok(())
+    }
+
+    // the last line of the output may not end with a new line
+    fn write_pending(&mut self) -> io::Result<()> {
+        if self.pending.is_empty() {
+            return Ok(());
+        }
+        let line = mem::take(&mut self.pending);
+        self.write_record(&line, CRI_LOG_TAG_FULL)
+    }
+}
+
+// the log file is a regular file, so it's written right away instead of going through the
+// blocking pool of tokio
+impl AsyncWrite for CriLogFile {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        _cx: &mut TaskContext<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Poll::Ready(self.write_output(buf).map(|_| buf.len()))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut TaskContext<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, _cx: &mut TaskContext<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(self.write_pending())
+    }
+}
+
+impl Drop for CriLogFile {
+    fn drop(&mut self) {
+        if let Err(e) = self.write_pending() {
+            warn!(sl!(), "failed to write log file {:?}: {}", self.path, e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::AsyncWriteExt;
+
+    fn records(path: &Path) -> Vec<(String, String, String)> {
+        fs::read_to_string(path)
+            .unwrap()
+            .lines()
+            .map(|l| {
+                let fields: Vec<&str> = l.splitn(4, ' ').collect();
+                assert!(chrono::DateTime::parse_from_rfc3339(fields[0]).is_ok());
+                (
+                    fields[1].to_string(),
+                    fields[2].to_string(),
+                    fields[3].to_string(),
+                )
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_cri_log_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("0.log");
+        let path_str = path.to_str().unwrap();
+
+        let mut stdout = CriLogFile::open(path_str, "stdout").unwrap();
+        let mut stderr = CriLogFile::open(path_str, "stderr").unwrap();
+        stdout.write_all(b"hello ").await.unwrap();
+        stderr.write_all(b"error\n").await.unwrap();
+        stdout.write_all(b"world\nbye").await.unwrap();
+        drop(stdout);
+
+        let record = |s: &str, t: &str, l: &str| (s.to_string(), t.to_string(), l.to_string());
+        assert_eq!(
+            records(&path),
+            vec![
+                record("stderr", "F", "error"),
+                record("stdout", "F", "hello world"),
+                record("stdout", "F", "bye"),
+            ]
+        );
+
+        // rotated by kubelet
+        fs::rename(&path, dir.path().join("0.log.1")).unwrap();
+        let long = vec![b'x'; CRI_LOG_MAX_LINE_SIZE + 1];
+        stderr.write_all(&long).await.unwrap();
+        stderr.shutdown().await.unwrap();
+        assert_eq!(
+            records(&path),
+            vec![
+                record("stderr", "P", &"x".repeat(CRI_LOG_MAX_LINE_SIZE)),
+                record("stderr", "F", "x"),
+            ]
+        );
+    }
+}
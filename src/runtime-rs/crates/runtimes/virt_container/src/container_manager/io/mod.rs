@@ -6,5 +6,8 @@
 
 mod container_io;
 pub use container_io::ContainerIo;
+mod log_file;
+mod output;
+pub(crate) use output::{copy_output, OutputPolicy};
 mod shim_io;
 pub use shim_io::ShimIo;
@@ -0,0 +1,181 @@
+// Copyright (c) 2023 Kata Containers community
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+use std::{io, sync::Arc};
+
+use kata_types::config::{Runtime, PROCESS_OUTPUT_POLICY_DROP};
+use tokio::{
+    io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader},
+    sync::{mpsc, OwnedSemaphorePermit, Semaphore},
+};
+
+// the size of the output read from the guest by a single agent request, large enough for the
+// bursts of output to take few requests
+pub(crate) const OUTPUT_CHUNK_SIZE: usize = 64 * 1024;
+
+/// How the output of a process is copied to the host when it's produced faster than the host
+/// consumes it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum OutputPolicy {
+    /// The output is read from the guest only as the host consumes it, the process blocks on
+    /// its full pipe meanwhile.
+    Block,
+    /// The output is read from the guest as soon as it's written, at most `buffer_size` bytes
+    /// are kept for the host, the rest is dropped and replaced by a marker.
+    Drop { buffer_size: usize },
+}
+
+impl OutputPolicy {
+    pub(crate) fn new(runtime: &Runtime) -> Self {
+        if runtime.process_output_policy == PROCESS_OUTPUT_POLICY_DROP {
+            Self::Drop {
+                buffer_size: runtime.process_output_buffer_kb as usize * 1024,
+            }
+        } else {
+            Self::Block
+        }
+    }
+}
+
+impl Default for OutputPolicy {
+    fn default() -> Self {
+        Self::Block
+    }
+}
+
+/// Copy the output of a process from the guest to the host with the policy, returns the size
+/// written to the host.
+pub(crate) async fn copy_output<R, W>(
+    reader: R,
+    writer: &mut W,
+    policy: OutputPolicy,
+) -> io::Result<u64>
+where
+    R: AsyncRead + Unpin,
+    W: AsyncWrite + Unpin,
+{
+    match policy {
+        OutputPolicy::Block => {
+            let mut reader = BufReader::with_capacity(OUTPUT_CHUNK_SIZE, reader);
+            tokio::io::copy_buf(&mut reader, writer).await
+        }
+        OutputPolicy::Drop { buffer_size } => copy_dropping(reader, writer, buffer_size).await,
+    }
+}
+
+fn dropped_marker(dropped: usize) -> Vec<u8> {
+    if dropped == 0 {
+        return vec![];
+    }
+    format!("\n[{} bytes of output dropped]\n", dropped).into_bytes()
+}
+
+// the chunks read are queued for the writer as long as they fit in the buffer, which is
+// accounted by the permits of the semaphore released once they are written
+async fn copy_dropping<R, W>(mut reader: R, writer: &mut W, buffer_size: usize) -> io::Result<u64>
+where
+    R: AsyncRead + Unpin,
+    W: AsyncWrite + Unpin,
+{
+    let permits = Arc::new(Semaphore::new(buffer_size));
+    let (tx, mut rx) = mpsc::unbounded_channel::<(Vec<u8>, OwnedSemaphorePermit)>();
+
+    let read = async move {
+        let mut buf = vec![0u8; OUTPUT_CHUNK_SIZE];
+        let mut dropped = 0;
+        let result = loop {
+            let n = match reader.read(&mut buf).await {
+                Ok(0) => break Ok(()),
+                Ok(n) => n,
+                Err(e) => break Err(e),
+            };
+
+            // the marker of the output dropped before goes with the next output kept
+            let mut data = dropped_marker(dropped);
+            data.extend_from_slice(&buf[..n]);
+            match permits.clone().try_acquire_many_owned(data.len() as u32) {
+                Ok(permit) => {
+                    dropped = 0;
+                    if tx.send((data, permit)).is_err() {
+                        // the writer failed
+                        break Ok(());
+                    }
+                }
+                Err(_) => dropped += n,
+            }
+        };
+
+        // the output dropped at the end is reported once the host caught up
+        if dropped > 0 {
+            let data = dropped_marker(dropped);
+            if let Ok(permit) = permits.acquire_many_owned(data.len() as u32).await {
+                let _ = tx.send((data, permit));
+            }
+        }
+        result
+    };
+
+    let write = async move {
+        let mut written = 0;
+        while let Some((data, _permit)) = rx.recv().await {
+            writer.write_all(&data).await?;
+            written += data.len() as u64;
+        }
+        Ok::<_, io::Error>(written)
+    };
+
+    let (read, write) = tokio::join!(read, write);
+    let written = write?;
+    read.map(|_| written)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_output_policy() {
+        let mut runtime = Runtime::default();
+        assert_eq!(OutputPolicy::new(&runtime), OutputPolicy::Block);
+
+        runtime.process_output_policy = PROCESS_OUTPUT_POLICY_DROP.to_string();
+        runtime.process_output_buffer_kb = 256;
+        assert_eq!(
+            OutputPolicy::new(&runtime),
+            OutputPolicy::Drop {
+                buffer_size: 256 * 1024
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_copy_output() {
+        let data = vec![b'a'; 8 * OUTPUT_CHUNK_SIZE];
+
+        let mut out = vec![];
+        let n = copy_output(&data[..], &mut out, OutputPolicy::Block)
+            .await
+            .unwrap();
+        assert_eq!(n as usize, data.len());
+        assert_eq!(out, data);
+
+        // the host reads nothing until the whole output is read from the guest, only the first
+        // two chunks fit in the buffer
+        let (mut host, mut shim) = tokio::io::duplex(16);
+        let policy = OutputPolicy::Drop {
+            buffer_size: 2 * OUTPUT_CHUNK_SIZE,
+        };
+        let copy = tokio::spawn(async move { copy_output(&data[..], &mut shim, policy).await });
+
+        let mut out = vec![];
+        host.read_to_end(&mut out).await.unwrap();
+        let n = copy.await.unwrap().unwrap();
+        assert_eq!(n as usize, out.len());
+
+        let mut expected = vec![b'a'; 2 * OUTPUT_CHUNK_SIZE];
+        expected.extend(dropped_marker(6 * OUTPUT_CHUNK_SIZE));
+        assert_eq!(out, expected);
+    }
+}
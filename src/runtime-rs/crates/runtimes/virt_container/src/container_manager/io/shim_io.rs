@@ -28,6 +28,8 @@ use tokio::{
 };
 use url::Url;
 
+use super::log_file::CriLogFile;
+
 fn open_fifo(path: &str) -> Result<AsyncUnixStream> {
     let fd = fcntl::open(path, OFlag::O_RDWR, Mode::from_bits(0).unwrap())?;
 
@@ -113,18 +115,31 @@ impl ShimIo {
         };
 
         let stdout_url = get_url(stdout);
-        let get_fd = |url: &Option<Url>| -> Option<Box<dyn AsyncWrite + Send + Unpin>> {
+        let get_fd = |url: &Option<Url>,
+                      stream: &'static str|
+         -> Option<Box<dyn AsyncWrite + Send + Unpin>> {
             info!(sl!(), "get fd for {:?}", &url);
             if let Some(url) = url {
-                if url.scheme() == "fifo" {
-                    let path = url.path();
-                    match open_fifo(path) {
+                match url.scheme() {
+                    "fifo" => match open_fifo(url.path()) {
                         Ok(s) => {
                             return Some(Box::new(ShimIoWrite::Stream(s)));
                         }
                         Err(err) => {
                             error!(sl!(), "failed to open file {} error {:?}", url.path(), err);
                         }
+                    },
+                    // the container log file, written in the CRI log format without a FIFO
+                    "file" => match CriLogFile::open(url.path(), stream) {
+                        Ok(f) => {
+                            return Some(Box::new(ShimIoWrite::LogFile(f)));
+                        }
+                        Err(err) => {
+                            error!(sl!(), "failed to open file {} error {:?}", url.path(), err);
+                        }
+                    },
+                    scheme => {
+                        warn!(sl!(), "unsupported io scheme {}", scheme);
                     }
                 }
             }
@@ -134,16 +149,15 @@ impl ShimIo {
         let stderr_url = get_url(stderr);
         Ok(Self {
             stdin: stdin_fd,
-            stdout: get_fd(&stdout_url),
-            stderr: get_fd(&stderr_url),
+            stdout: get_fd(&stdout_url, "stdout"),
+            stderr: get_fd(&stderr_url, "stderr"),
         })
     }
 }
 
-#[derive(Debug)]
 enum ShimIoWrite {
     Stream(AsyncUnixStream),
-    // TODO: support other type
+    LogFile(CriLogFile),
 }
 
 impl AsyncWrite for ShimIoWrite {
@@ -154,18 +168,21 @@ impl AsyncWrite for ShimIoWrite {
     ) -> Poll<io::Result<usize>> {
         match *self {
             ShimIoWrite::Stream(ref mut s) => Pin::new(s).poll_write(cx, buf),
+            ShimIoWrite::LogFile(ref mut f) => Pin::new(f).poll_write(cx, buf),
         }
     }
 
     fn poll_flush(mut self: Pin<&mut Self>, cx: &mut TaskContext<'_>) -> Poll<io::Result<()>> {
         match *self {
             ShimIoWrite::Stream(ref mut s) => Pin::new(s).poll_flush(cx),
+            ShimIoWrite::LogFile(ref mut f) => Pin::new(f).poll_flush(cx),
         }
     }
 
     fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut TaskContext<'_>) -> Poll<io::Result<()>> {
         match *self {
             ShimIoWrite::Stream(ref mut s) => Pin::new(s).poll_shutdown(cx),
+            ShimIoWrite::LogFile(ref mut f) => Pin::new(f).poll_shutdown(cx),
         }
     }
 }
@@ -12,10 +12,11 @@ use std::{collections::HashMap, sync::Arc};
 use agent::Agent;
 use common::{
     error::Error,
+    message::Message,
     types::{
-        ContainerConfig, ContainerID, ContainerProcess, ExecProcessRequest, KillRequest,
-        ProcessExitStatus, ProcessStateInfo, ProcessType, ResizePTYRequest, ShutdownRequest,
-        StatsInfo, UpdateRequest, PID,
+        CheckpointRequest, ContainerConfig, ContainerID, ContainerProcess, ExecProcessRequest,
+        KillRequest, ProcessExitStatus, ProcessStateInfo, ProcessType, ResizePTYRequest,
+        ShutdownRequest, StatsInfo, UpdateRequest, PID,
     },
     ContainerManager,
 };
@@ -23,7 +24,7 @@ use hypervisor::Hypervisor;
 use oci::Process as OCIProcess;
 use resource::network::NetnsGuard;
 use resource::ResourceManager;
-use tokio::sync::RwLock;
+use tokio::sync::{mpsc::Sender, RwLock};
 use tracing::instrument;
 
 use kata_sys_util::hooks::HookStates;
@@ -37,6 +38,7 @@ pub struct VirtContainerManager {
     resource_manager: Arc<ResourceManager>,
     agent: Arc<dyn Agent>,
     hypervisor: Arc<dyn Hypervisor>,
+    msg_sender: Sender<Message>,
 }
 
 impl std::fmt::Debug for VirtContainerManager {
@@ -55,6 +57,7 @@ impl VirtContainerManager {
         agent: Arc<dyn Agent>,
         hypervisor: Arc<dyn Hypervisor>,
         resource_manager: Arc<ResourceManager>,
+        msg_sender: Sender<Message>,
     ) -> Self {
         Self {
             sid: sid.to_string(),
@@ -63,6 +66,7 @@ impl VirtContainerManager {
             resource_manager,
             agent,
             hypervisor,
+            msg_sender,
         }
     }
 }
@@ -71,12 +75,15 @@ impl VirtContainerManager {
 impl ContainerManager for VirtContainerManager {
     #[instrument]
     async fn create_container(&self, config: ContainerConfig, spec: oci::Spec) -> Result<PID> {
+        let toml_config = self.resource_manager.config().await;
         let container = Container::new(
             self.pid,
             config.clone(),
             spec.clone(),
             self.agent.clone(),
             self.resource_manager.clone(),
+            &toml_config.runtime,
+            self.msg_sender.clone(),
         )
         .context("new container")?;
 
@@ -241,6 +248,11 @@ impl ContainerManager for VirtContainerManager {
 
         info!(logger, "wait process exit status {:?}", status);
 
+        let containers = self.containers.read().await;
+        if let Some(c) = containers.get(container_id) {
+            c.check_deadline(process).await?;
+        }
+
         Ok(status.clone())
     }
 
@@ -308,6 +320,16 @@ impl ContainerManager for VirtContainerManager {
         Ok(())
     }
 
+    #[instrument]
+    async fn checkpoint_container(&self, req: &CheckpointRequest) -> Result<()> {
+        let containers = self.containers.read().await;
+        let c = containers
+            .get(&req.container_id)
+            .ok_or_else(|| Error::ContainerNotFound(req.container_id.clone()))?;
+        c.checkpoint(req).await.context("checkpoint")?;
+        Ok(())
+    }
+
     #[instrument]
     async fn resize_process_pty(&self, req: &ResizePTYRequest) -> Result<()> {
         let containers = self.containers.read().await;
@@ -332,6 +354,23 @@ impl ContainerManager for VirtContainerManager {
         Ok(StatsInfo::from(stats))
     }
 
+    #[instrument]
+    async fn stats_containers(&self) -> Result<HashMap<String, agent::StatsContainerResponse>> {
+        let containers = self.containers.read().await;
+        let mut stats = HashMap::new();
+        for (id, c) in containers.iter() {
+            // a container being deleted shouldn't fail the others
+            match c.stats().await {
+                Ok(Some(s)) => {
+                    stats.insert(id.clone(), s);
+                }
+                Ok(None) => {}
+                Err(e) => warn!(sl!(), "failed to get stats of container {}: {:?}", id, e),
+            }
+        }
+        Ok(stats)
+    }
+
     #[instrument]
     async fn update_container(&self, req: UpdateRequest) -> Result<()> {
         let resource = serde_json::from_slice::<oci::LinuxResources>(&req.value)
@@ -344,6 +383,22 @@ impl ContainerManager for VirtContainerManager {
         c.update(&resource).await.context("update_container")
     }
 
+    #[instrument]
+    async fn recreate_containers(&self) -> Result<()> {
+        let containers = self.containers.read().await;
+        // the sandbox container goes first, as the other containers may
+        // join its namespaces.
+        let mut ids: Vec<&String> = containers.keys().collect();
+        ids.sort_by_key(|id| *id != &self.sid);
+        for id in ids {
+            containers[id]
+                .recreate(self.containers.clone())
+                .await
+                .with_context(|| format!("recreate container {}", id))?;
+        }
+        Ok(())
+    }
+
     #[instrument]
     async fn pid(&self) -> Result<PID> {
         Ok(PID { pid: self.pid })
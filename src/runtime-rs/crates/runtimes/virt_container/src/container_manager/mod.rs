@@ -4,14 +4,18 @@
 // SPDX-License-Identifier: Apache-2.0
 //
 
+mod checkpoint;
 mod container;
 use container::{Container, Exec};
 mod container_inner;
 mod io;
+pub use io::ContainerIo;
 use container_inner::ContainerInner;
 mod manager;
 pub use manager::VirtContainerManager;
 mod process;
+mod sanitize;
+mod state;
 
 use common::types::ContainerProcess;
 
@@ -6,17 +6,22 @@
 
 use std::collections::HashMap;
 use std::sync::Arc;
+use std::time::Duration;
 
 use agent::Agent;
 use anyhow::{Context, Result};
 use awaitgroup::{WaitGroup, Worker as WaitGroupWorker};
+use common::error::Error;
+use common::message::{task_exit_event, Action, Message};
 use common::types::{ContainerProcess, ProcessExitStatus, ProcessStateInfo, ProcessStatus, PID};
+use nix::sys::signal::Signal;
 use tokio::io::{AsyncRead, AsyncWrite};
-use tokio::sync::{watch, RwLock};
+use tokio::sync::{mpsc::Sender, watch, RwLock};
 
 use super::container::Container;
-use super::io::{ContainerIo, ShimIo};
+use super::io::{copy_output, ContainerIo, OutputPolicy, ShimIo};
 use super::logger_with_process;
+use super::state::check_transition;
 
 pub type ProcessWatcher = (
     Option<watch::Receiver<bool>>,
@@ -46,6 +51,15 @@ pub struct Process {
     // close io call should wait until the stdin io copy finished to
     // prevent stdin data lost.
     pub wg_stdin: WaitGroup,
+
+    // the process is killed if it's still running after the timeout
+    pub timeout: Option<Duration>,
+    pub deadline_exceeded: Arc<RwLock<bool>>,
+
+    // how the output is copied when the host doesn't consume it in time
+    pub(crate) output_policy: OutputPolicy,
+    // the exit of the process is published with it
+    pub(crate) msg_sender: Option<Sender<Message>>,
 }
 
 impl Process {
@@ -76,6 +90,10 @@ impl Process {
             exit_watcher_rx: Some(receiver),
             exit_watcher_tx: Some(sender),
             wg_stdin: WaitGroup::new(),
+            timeout: None,
+            deadline_exceeded: Arc::new(RwLock::new(false)),
+            output_policy: OutputPolicy::default(),
+            msg_sender: None,
         }
     }
 
@@ -95,8 +113,14 @@ impl Process {
         // start io copy for stdin
         let wgw_stdin = self.wg_stdin.worker();
         if let Some(stdin) = shim_io.stdin {
-            self.run_io_copy("stdin", wgw_stdin, stdin, container_io.stdin)
-                .await?;
+            self.run_io_copy(
+                "stdin",
+                wgw_stdin,
+                stdin,
+                container_io.stdin,
+                OutputPolicy::Block,
+            )
+            .await?;
         }
 
         // prepare for wait group for stdout, stderr
@@ -105,15 +129,27 @@ impl Process {
 
         // start io copy for stdout
         if let Some(stdout) = shim_io.stdout {
-            self.run_io_copy("stdout", wgw.clone(), container_io.stdout, stdout)
-                .await?;
+            self.run_io_copy(
+                "stdout",
+                wgw.clone(),
+                container_io.stdout,
+                stdout,
+                self.output_policy,
+            )
+            .await?;
         }
 
         // start io copy for stderr
         if !self.terminal {
             if let Some(stderr) = shim_io.stderr {
-                self.run_io_copy("stderr", wgw, container_io.stderr, stderr)
-                    .await?;
+                self.run_io_copy(
+                    "stderr",
+                    wgw,
+                    container_io.stderr,
+                    stderr,
+                    self.output_policy,
+                )
+                .await?;
             }
         }
 
@@ -127,14 +163,15 @@ impl Process {
         &'a self,
         io_name: &'a str,
         wgw: WaitGroupWorker,
-        mut reader: Box<dyn AsyncRead + Send + Unpin>,
+        reader: Box<dyn AsyncRead + Send + Unpin>,
         mut writer: Box<dyn AsyncWrite + Send + Unpin>,
+        policy: OutputPolicy,
     ) -> Result<()> {
-        info!(self.logger, "run io copy for {}", io_name);
+        info!(self.logger, "run io copy for {} {:?}", io_name, policy);
         let io_name = io_name.to_string();
         let logger = self.logger.new(o!("io_name" => io_name));
         tokio::spawn(async move {
-            match tokio::io::copy(&mut reader, &mut writer).await {
+            match copy_output(reader, &mut writer, policy).await {
                 Err(e) => {
                     warn!(logger, "run_io_copy: failed to copy stream: {}", e);
                 }
@@ -164,6 +201,8 @@ impl Process {
         let exit_status = self.exit_status.clone();
         let exit_notifier = self.exit_watcher_tx.take();
         let status = self.status.clone();
+        let msg_sender = self.msg_sender.clone();
+        let pid = self.pid;
 
         tokio::spawn(async move {
             // wait on all of the container's io stream terminated
@@ -215,11 +254,66 @@ impl Process {
             drop(status);
 
             drop(exit_notifier);
+
+            if let Some(sender) = msg_sender {
+                let event = task_exit_event(
+                    &process.container_id.container_id,
+                    &process.exec_id,
+                    pid,
+                    resp.status as u32,
+                );
+                let msg = Message::new(Action::Event(Arc::new(event)));
+                if let Err(err) = sender.send(msg).await {
+                    warn!(logger, "failed to send exit event: {:?}", err);
+                }
+            }
             info!(logger, "end io wait thread");
         });
         Ok(())
     }
 
+    /// Kill the process if it's still running at its deadline.
+    pub fn watch_deadline(&self, agent: Arc<dyn Agent>) {
+        let (timeout, mut exit_watcher) = match (self.timeout, self.exit_watcher_rx.clone()) {
+            (Some(timeout), Some(exit_watcher)) => (timeout, exit_watcher),
+            _ => return,
+        };
+        let logger = self.logger.clone();
+        let process = self.process.clone();
+        let deadline_exceeded = self.deadline_exceeded.clone();
+
+        tokio::spawn(async move {
+            let exited = async { while exit_watcher.changed().await.is_ok() {} };
+            if tokio::time::timeout(timeout, exited).await.is_ok() {
+                return;
+            }
+
+            warn!(
+                logger,
+                "process exceeded the deadline of {:?}, kill it", timeout
+            );
+            *deadline_exceeded.write().await = true;
+            let req = agent::SignalProcessRequest {
+                process_id: process.into(),
+                signal: Signal::SIGKILL as u32,
+            };
+            if let Err(err) = agent.signal_process(req).await {
+                error!(logger, "failed to kill process at the deadline: {:?}", err);
+            }
+        });
+    }
+
+    /// Check whether the process was killed at its deadline.
+    pub async fn check_deadline(&self) -> Result<()> {
+        if let Some(timeout) = self.timeout {
+            if *self.deadline_exceeded.read().await {
+                return Err(Error::DeadlineExceeded(self.process.clone(), timeout).into());
+            }
+        }
+
+        Ok(())
+    }
+
     pub fn fetch_exit_watcher(&self) -> Result<ProcessWatcher> {
         Ok((self.exit_watcher_rx.clone(), self.exit_status.clone()))
     }
@@ -263,8 +357,27 @@ impl Process {
         *status
     }
 
-    pub async fn set_status(&self, new_status: ProcessStatus) {
+    /// Check the process is allowed to transition to the new status.
+    pub async fn check_transition(&self, new_status: ProcessStatus) -> Result<()> {
+        let status = self.status.read().await;
+        check_transition(&self.process, *status, new_status)?;
+        Ok(())
+    }
+
+    /// Transition the process to the new status, if allowed by its lifecycle.
+    pub async fn transition(&self, new_status: ProcessStatus) -> Result<()> {
         let mut status = self.status.write().await;
+        check_transition(&self.process, *status, new_status)?;
         *status = new_status;
+        Ok(())
+    }
+
+    /// Reset the status of a running process to created, as it's re-created
+    /// from scratch, e.g. after the guest is rebooted.
+    pub async fn reset_status(&self) {
+        let mut status = self.status.write().await;
+        if *status == ProcessStatus::Running {
+            *status = ProcessStatus::Created;
+        }
     }
 }
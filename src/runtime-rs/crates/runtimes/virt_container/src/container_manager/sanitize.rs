@@ -0,0 +1,198 @@
+// Copyright (c) 2023 Kata Containers community
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+use std::collections::HashMap;
+
+// Sanitize the OCI spec of a container before it's sent to the agent, the same way as the Go
+// runtime does (constrainGRPCSpec), so that nothing specific to the host is applied in the
+// guest:
+// - the rootfs is the one shared to or created in the guest
+// - the systemd cgroup path is converted to a cgroupfs one, the slice is the host one
+// - the pid, network and cgroup namespaces are dropped, the others lose their host paths
+// - only the cpu and memory resources are kept, without the host cpuset and memory nodes
+// - the intel RDT is applied to the vCPUs in the host, see resource::rdt
+pub(crate) fn sanitize_spec(spec: &mut oci::Spec, guest_rootfs: &str) {
+    if let Some(root) = spec.root.as_mut() {
+        root.path = guest_rootfs.to_string();
+    }
+
+    let linux = match spec.linux.as_mut() {
+        Some(linux) => linux,
+        None => return,
+    };
+
+    if let Some(path) = cgroupfs_path(&linux.cgroups_path) {
+        linux.cgroups_path = path;
+    }
+
+    // the pid namespace is added back by the agent, either the sandbox one or a new one, and
+    // the network is set up by the runtime
+    linux.namespaces = linux
+        .namespaces
+        .iter()
+        .filter(|n| {
+            !matches!(
+                n.r#type.as_str(),
+                oci::PIDNAMESPACE | oci::NETWORKNAMESPACE | oci::CGROUPNAMESPACE
+            )
+        })
+        .map(|n| oci::LinuxNamespace {
+            r#type: n.r#type.clone(),
+            path: String::new(),
+        })
+        .collect();
+
+    linux.intel_rdt = None;
+
+    if let Some(resources) = linux.resources.as_mut() {
+        resources.devices = Vec::new();
+        resources.pids = None;
+        resources.block_io = None;
+        resources.hugepage_limits = Vec::new();
+        resources.network = None;
+        resources.rdma = HashMap::new();
+        if let Some(cpu) = resources.cpu.as_mut() {
+            cpu.cpus = String::new();
+            cpu.mems = String::new();
+        }
+    }
+}
+
+// the systemd cgroup path is `slice:prefix:name`, e.g.
+// `kubepods-besteffort.slice:cri-containerd:<id>`, converted to `/prefix/name`
+fn cgroupfs_path(cgroups_path: &str) -> Option<String> {
+    let parts: Vec<&str> = cgroups_path.split(':').collect();
+    match parts.as_slice() {
+        [slice, prefix, name] if slice.ends_with(".slice") => Some(format!("/{}/{}", prefix, name)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn namespace(r#type: &str, path: &str) -> oci::LinuxNamespace {
+        oci::LinuxNamespace {
+            r#type: r#type.to_string(),
+            path: path.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_cgroupfs_path() {
+        assert_eq!(
+            cgroupfs_path("kubepods-besteffort.slice:cri-containerd:c1"),
+            Some("/cri-containerd/c1".to_string())
+        );
+        assert_eq!(cgroupfs_path("/kubepods/besteffort/pod1/c1"), None);
+        assert_eq!(cgroupfs_path("system:docker:c1"), None);
+        assert_eq!(cgroupfs_path(""), None);
+    }
+
+    // the expected spec is the one sent by the Go runtime for the same input
+    #[test]
+    fn test_sanitize_spec() {
+        let mut spec = oci::Spec {
+            root: Some(oci::Root {
+                path: "/run/containerd/io.containerd.runtime.v2.task/k8s.io/c1/rootfs".to_string(),
+                readonly: true,
+            }),
+            linux: Some(oci::Linux {
+                cgroups_path: "kubepods-burstable-pod1.slice:cri-containerd:c1".to_string(),
+                namespaces: vec![
+                    namespace(oci::PIDNAMESPACE, "/proc/100/ns/pid"),
+                    namespace(oci::NETWORKNAMESPACE, "/var/run/netns/cni-1"),
+                    namespace(oci::IPCNAMESPACE, "/proc/100/ns/ipc"),
+                    namespace(oci::UTSNAMESPACE, "/proc/100/ns/uts"),
+                    namespace(oci::MOUNTNAMESPACE, ""),
+                    namespace(oci::CGROUPNAMESPACE, ""),
+                ],
+                resources: Some(oci::LinuxResources {
+                    devices: vec![oci::LinuxDeviceCgroup {
+                        allow: false,
+                        access: "rwm".to_string(),
+                        ..Default::default()
+                    }],
+                    memory: Some(oci::LinuxMemory {
+                        limit: Some(1 << 30),
+                        ..Default::default()
+                    }),
+                    cpu: Some(oci::LinuxCpu {
+                        shares: Some(1024),
+                        quota: Some(200000),
+                        period: Some(100000),
+                        cpus: "0-3".to_string(),
+                        mems: "0".to_string(),
+                        ..Default::default()
+                    }),
+                    pids: Some(oci::LinuxPids { limit: 1024 }),
+                    hugepage_limits: vec![oci::LinuxHugepageLimit {
+                        page_size: "2MB".to_string(),
+                        limit: 1 << 30,
+                    }],
+                    ..Default::default()
+                }),
+                intel_rdt: Some(oci::LinuxIntelRdt {
+                    clos_id: "gold".to_string(),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+
+        sanitize_spec(
+            &mut spec,
+            "/run/kata-containers/shared/containers/c1/rootfs",
+        );
+
+        let root = spec.root.as_ref().unwrap();
+        assert_eq!(
+            root.path,
+            "/run/kata-containers/shared/containers/c1/rootfs"
+        );
+        assert!(root.readonly);
+
+        let linux = spec.linux.as_ref().unwrap();
+        assert_eq!(linux.cgroups_path, "/cri-containerd/c1");
+        assert_eq!(
+            linux.namespaces,
+            vec![
+                namespace(oci::IPCNAMESPACE, ""),
+                namespace(oci::UTSNAMESPACE, ""),
+                namespace(oci::MOUNTNAMESPACE, ""),
+            ]
+        );
+        assert!(linux.intel_rdt.is_none());
+
+        let resources = linux.resources.as_ref().unwrap();
+        assert!(resources.devices.is_empty());
+        assert!(resources.pids.is_none());
+        assert!(resources.hugepage_limits.is_empty());
+        assert_eq!(resources.memory.as_ref().unwrap().limit, Some(1 << 30));
+        let cpu = resources.cpu.as_ref().unwrap();
+        assert_eq!(
+            (cpu.shares, cpu.quota, cpu.period),
+            (Some(1024), Some(200000), Some(100000))
+        );
+        assert!(cpu.cpus.is_empty());
+        assert!(cpu.mems.is_empty());
+
+        // the cgroupfs path is kept
+        let mut spec = oci::Spec {
+            linux: Some(oci::Linux {
+                cgroups_path: "/kubepods/burstable/pod1/c1".to_string(),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        sanitize_spec(&mut spec, "/rootfs");
+        assert_eq!(
+            spec.linux.as_ref().unwrap().cgroups_path,
+            "/kubepods/burstable/pod1/c1"
+        );
+    }
+}
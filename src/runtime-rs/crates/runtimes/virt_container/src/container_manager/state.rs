@@ -0,0 +1,139 @@
+// Copyright (c) 2023 Kata Containers community
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+// The lifecycle of the container and exec processes, their status only
+// moves along the transitions below on the requests of containerd:
+//
+//   Created --start--> Running --pause--> Paused --resume--> Running
+//
+// and ends up in Stopped once the process exits, whatever its status is.
+
+use common::{
+    error::Error,
+    types::{ContainerProcess, ProcessStatus},
+};
+
+// the status a process is allowed to transition to `to` from
+fn sources_of(to: ProcessStatus) -> &'static [ProcessStatus] {
+    match to {
+        ProcessStatus::Unknown => &[],
+        ProcessStatus::Created => &[ProcessStatus::Unknown],
+        ProcessStatus::Running => &[ProcessStatus::Created, ProcessStatus::Paused],
+        ProcessStatus::Pausing => &[ProcessStatus::Running],
+        ProcessStatus::Paused => &[ProcessStatus::Running, ProcessStatus::Pausing],
+        ProcessStatus::Stopped => &[
+            ProcessStatus::Created,
+            ProcessStatus::Running,
+            ProcessStatus::Pausing,
+            ProcessStatus::Paused,
+        ],
+    }
+}
+
+/// Check the process in the `from` status is allowed to transition to `to`.
+pub(crate) fn check_transition(
+    process: &ContainerProcess,
+    from: ProcessStatus,
+    to: ProcessStatus,
+) -> Result<(), Error> {
+    check_status(process, from, sources_of(to))
+}
+
+/// Check the status of the process is one of the `expected`.
+pub(crate) fn check_status(
+    process: &ContainerProcess,
+    status: ProcessStatus,
+    expected: &[ProcessStatus],
+) -> Result<(), Error> {
+    if expected.contains(&status) {
+        return Ok(());
+    }
+
+    Err(Error::InvalidState(
+        process.clone(),
+        expected.to_vec(),
+        status,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use common::types::ProcessType;
+
+    const ALL_STATUS: [ProcessStatus; 6] = [
+        ProcessStatus::Unknown,
+        ProcessStatus::Created,
+        ProcessStatus::Running,
+        ProcessStatus::Stopped,
+        ProcessStatus::Paused,
+        ProcessStatus::Pausing,
+    ];
+
+    #[test]
+    fn test_check_transition() {
+        use ProcessStatus::*;
+
+        let allowed = [
+            (Unknown, Created),
+            (Created, Running),
+            (Created, Stopped),
+            (Running, Pausing),
+            (Running, Paused),
+            (Running, Stopped),
+            (Pausing, Paused),
+            (Pausing, Stopped),
+            (Paused, Running),
+            (Paused, Stopped),
+        ];
+
+        let process = ContainerProcess::new("cid", "").unwrap();
+        for from in ALL_STATUS {
+            for to in ALL_STATUS {
+                let result = check_transition(&process, from, to);
+                assert_eq!(
+                    result.is_ok(),
+                    allowed.contains(&(from, to)),
+                    "transition from {:?} to {:?}",
+                    from,
+                    to
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_check_transition_error() {
+        let process = ContainerProcess::new("cid", "eid").unwrap();
+        assert_eq!(process.process_type, ProcessType::Exec);
+
+        // start twice
+        let err =
+            check_transition(&process, ProcessStatus::Running, ProcessStatus::Running).unwrap_err();
+        match err {
+            Error::InvalidState(p, expected, actual) => {
+                assert_eq!(p.exec_id, process.exec_id);
+                assert_eq!(
+                    expected,
+                    vec![ProcessStatus::Created, ProcessStatus::Paused]
+                );
+                assert_eq!(actual, ProcessStatus::Running);
+            }
+            _ => panic!("unexpected error {:?}", err),
+        }
+    }
+
+    #[test]
+    fn test_check_status() {
+        let process = ContainerProcess::new("cid", "").unwrap();
+        let expected = [ProcessStatus::Created, ProcessStatus::Running];
+        for status in ALL_STATUS {
+            assert_eq!(
+                check_status(&process, status, &expected).is_ok(),
+                expected.contains(&status)
+            );
+        }
+    }
+}
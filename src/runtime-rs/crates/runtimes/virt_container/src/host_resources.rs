@@ -0,0 +1,136 @@
+// Copyright (c) 2023 Kata Containers community
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+use anyhow::{Context, Result};
+use common::error::Error;
+use hypervisor::utils::{get_available_memory_mib, get_free_hugepages_mib};
+use kata_types::config::{hypervisor::Hypervisor as HypervisorConfig, Runtime};
+use nix::sched::{sched_getaffinity, CpuSet};
+use nix::unistd::Pid;
+
+/// Resources of the host currently available to the VM.
+#[derive(Debug, Default)]
+pub(crate) struct HostResources {
+    /// CPUs the shim, and so the vCPU threads, may run on.
+    pub(crate) cpus: u32,
+    /// Memory available without swapping, in MiB.
+    pub(crate) memory_mib: u64,
+    /// Free memory of the default hugepage pool, in MiB.
+    pub(crate) hugepages_mib: u64,
+}
+
+impl HostResources {
+    pub(crate) fn read(hugepages: bool) -> Result<Self> {
+        let affinity = sched_getaffinity(Pid::from_raw(0)).context("get cpu affinity")?;
+        let cpus = (0..CpuSet::count())
+            .filter(|cpu| affinity.is_set(*cpu).unwrap_or_default())
+            .count() as u32;
+        // the hugepage pool is only read when the guest memory is taken from it
+        let hugepages_mib = if hugepages {
+            get_free_hugepages_mib().context("get free hugepages")?
+        } else {
+            0
+        };
+
+        Ok(Self {
+            cpus,
+            memory_mib: get_available_memory_mib().context("get available memory")?,
+            hugepages_mib,
+        })
+    }
+}
+
+/// Check that the vcpus and the memory of the VM fit the resources available on the host, minus
+/// the ones reserved for the host. The memory of the VM is taken from the hugepage pool if the
+/// hugepages are enabled.
+pub(crate) fn check_host_resources(
+    config: &HypervisorConfig,
+    runtime: &Runtime,
+    available: &HostResources,
+) -> Result<()> {
+    let vcpus = config.cpu_info.default_vcpus.max(0) as u32;
+    let cpus = available.cpus.saturating_sub(runtime.host_reserved_cpus);
+    if vcpus > cpus {
+        return Err(Error::ResourceExhausted(
+            "CPUs".to_string(),
+            vcpus.to_string(),
+            cpus.to_string(),
+        )
+        .into());
+    }
+
+    let memory_mib = config.memory_info.default_memory as u64;
+    let (resource, free_mib) = if config.memory_info.enable_hugepages {
+        ("hugepages", available.hugepages_mib)
+    } else {
+        (
+            "memory",
+            available
+                .memory_mib
+                .saturating_sub(runtime.host_reserved_memory_mb as u64),
+        )
+    };
+    if memory_mib > free_mib {
+        return Err(Error::ResourceExhausted(
+            resource.to_string(),
+            format!("{} MiB", memory_mib),
+            format!("{} MiB", free_mib),
+        )
+        .into());
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_host_resources() {
+        let mut config = HypervisorConfig::default();
+        config.cpu_info.default_vcpus = 2;
+        config.memory_info.default_memory = 2048;
+        let mut runtime = Runtime::default();
+        let available = HostResources {
+            cpus: 4,
+            memory_mib: 4096,
+            hugepages_mib: 1024,
+        };
+        assert!(check_host_resources(&config, &runtime, &available).is_ok());
+
+        // the reserved resources are kept for the host
+        runtime.host_reserved_memory_mb = 3072;
+        let err = check_host_resources(&config, &runtime, &available).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "not enough memory on the host: 2048 MiB needed, 1024 MiB available"
+        );
+        runtime.host_reserved_memory_mb = 0;
+        runtime.host_reserved_cpus = 3;
+        assert!(matches!(
+            err_of(check_host_resources(&config, &runtime, &available)),
+            Some(Error::ResourceExhausted(..))
+        ));
+        runtime.host_reserved_cpus = 0;
+
+        // the guest memory is taken from the hugepage pool
+        config.memory_info.enable_hugepages = true;
+        assert!(check_host_resources(&config, &runtime, &available).is_err());
+        config.memory_info.default_memory = 1024;
+        assert!(check_host_resources(&config, &runtime, &available).is_ok());
+    }
+
+    fn err_of(result: Result<()>) -> Option<Error> {
+        result.err()?.downcast::<Error>().ok()
+    }
+
+    #[test]
+    fn test_read_host_resources() {
+        let resources = HostResources::read(false).unwrap();
+        assert!(resources.cpus > 0);
+        assert_eq!(resources.hugepages_mib, 0);
+    }
+}
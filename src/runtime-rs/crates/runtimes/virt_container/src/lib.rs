@@ -10,29 +10,51 @@ extern crate slog;
 logging::logger_with_subsystem!(sl, "virt-container");
 
 mod container_manager;
+pub use container_manager::ContainerIo;
 pub mod health_check;
+mod host_resources;
+mod memory_reconciler;
+pub mod metrics;
 pub mod sandbox;
 pub mod sandbox_persist;
+mod shutdown_budget;
+mod stats_cache;
 
 use std::sync::Arc;
 
 use agent::{kata::KataAgent, AGENT_KATA};
 use anyhow::{anyhow, Context, Result};
 use async_trait::async_trait;
-use common::{message::Message, RuntimeHandler, RuntimeInstance};
+use common::{
+    message::Message, RuntimeHandler, RuntimeHandlerCapabilities, RuntimeInstance, Sandbox,
+};
 use hypervisor::{dragonball::Dragonball, Hypervisor, HYPERVISOR_DRAGONBALL};
+use kata_types::config::{hypervisor::register_hypervisor_plugin, DragonballConfig, TomlConfig};
+
+#[cfg(feature = "qemu")]
 use hypervisor::{qemu::Qemu, HYPERVISOR_QEMU};
-use kata_types::config::{
-    hypervisor::register_hypervisor_plugin, DragonballConfig, QemuConfig, TomlConfig,
-};
+#[cfg(feature = "qemu")]
+use kata_types::config::QemuConfig;
 
 #[cfg(feature = "cloud-hypervisor")]
 use hypervisor::ch::CloudHypervisor;
 #[cfg(feature = "cloud-hypervisor")]
 use kata_types::config::{hypervisor::HYPERVISOR_NAME_CH, CloudHypervisorConfig};
 
+#[cfg(feature = "firecracker")]
+use hypervisor::firecracker::Firecracker;
+#[cfg(feature = "firecracker")]
+use kata_types::config::{FirecrackerConfig, HYPERVISOR_NAME_FIRECRACKER};
+
+#[cfg(feature = "stratovirt")]
+use hypervisor::stratovirt::StratoVirt;
+#[cfg(feature = "stratovirt")]
+use kata_types::config::{StratoVirtConfig, HYPERVISOR_NAME_STRATOVIRT};
+
+use persist::sandbox_persist::Persist;
 use resource::ResourceManager;
-use sandbox::VIRTCONTAINER;
+use sandbox::{SandboxRestoreArgs, VirtSandbox, VIRTCONTAINER};
+use sandbox_persist::SandboxState;
 use tokio::sync::mpsc::Sender;
 use tracing::instrument;
 
@@ -48,8 +70,11 @@ impl RuntimeHandler for VirtContainer {
         let dragonball_config = Arc::new(DragonballConfig::new());
         register_hypervisor_plugin("dragonball", dragonball_config);
 
-        let qemu_config = Arc::new(QemuConfig::new());
-        register_hypervisor_plugin("qemu", qemu_config);
+        #[cfg(feature = "qemu")]
+        {
+            let qemu_config = Arc::new(QemuConfig::new());
+            register_hypervisor_plugin("qemu", qemu_config);
+        }
 
         #[cfg(feature = "cloud-hypervisor")]
         {
@@ -57,6 +82,18 @@ impl RuntimeHandler for VirtContainer {
             register_hypervisor_plugin(HYPERVISOR_NAME_CH, ch_config);
         }
 
+        #[cfg(feature = "firecracker")]
+        {
+            let fc_config = Arc::new(FirecrackerConfig::new());
+            register_hypervisor_plugin(HYPERVISOR_NAME_FIRECRACKER, fc_config);
+        }
+
+        #[cfg(feature = "stratovirt")]
+        {
+            let sv_config = Arc::new(StratoVirtConfig::new());
+            register_hypervisor_plugin(HYPERVISOR_NAME_STRATOVIRT, sv_config);
+        }
+
         Ok(())
     }
 
@@ -64,6 +101,13 @@ impl RuntimeHandler for VirtContainer {
         VIRTCONTAINER.to_string()
     }
 
+    fn capabilities() -> RuntimeHandlerCapabilities {
+        RuntimeHandlerCapabilities {
+            default: true,
+            cleanup: true,
+        }
+    }
+
     fn new_handler() -> Arc<dyn RuntimeHandler> {
         Arc::new(VirtContainer {})
     }
@@ -83,9 +127,9 @@ impl RuntimeHandler for VirtContainer {
             Arc::new(ResourceManager::new(sid, agent.clone(), hypervisor.clone(), config).await?);
         let pid = std::process::id();
 
-        let sandbox = sandbox::VirtSandbox::new(
+        let sandbox = VirtSandbox::new(
             sid,
-            msg_sender,
+            msg_sender.clone(),
             agent.clone(),
             hypervisor.clone(),
             resource_manager.clone(),
@@ -98,6 +142,7 @@ impl RuntimeHandler for VirtContainer {
             agent,
             hypervisor,
             resource_manager,
+            msg_sender,
         );
         Ok(RuntimeInstance {
             sandbox: Arc::new(sandbox),
@@ -105,9 +150,31 @@ impl RuntimeHandler for VirtContainer {
         })
     }
 
-    fn cleanup(&self, _id: &str) -> Result<()> {
-        // TODO
-        Ok(())
+    async fn cleanup(
+        &self,
+        id: &str,
+        msg_sender: Sender<Message>,
+        config: Arc<TomlConfig>,
+    ) -> Result<()> {
+        if config.runtime.keep_abnormal {
+            info!(sl!(), "skip cleanup for keep_abnormal");
+            return Ok(());
+        }
+
+        let sandbox_state =
+            persist::from_disk::<SandboxState>(id).context("failed to load the sandbox state")?;
+        let sandbox_args = SandboxRestoreArgs {
+            sid: id.to_string(),
+            toml_config: config.as_ref().clone(),
+            sender: msg_sender,
+        };
+        let sandbox = VirtSandbox::restore(sandbox_args, sandbox_state)
+            .await
+            .context("failed to restore the sandbox")?;
+        sandbox
+            .cleanup()
+            .await
+            .context("failed to cleanup the resource")
     }
 }
 
@@ -129,6 +196,7 @@ async fn new_hypervisor(toml_config: &TomlConfig) -> Result<Arc<dyn Hypervisor>>
                 .await;
             Ok(Arc::new(hypervisor))
         }
+        #[cfg(feature = "qemu")]
         HYPERVISOR_QEMU => {
             let mut hypervisor = Qemu::new();
             hypervisor
@@ -147,6 +215,24 @@ async fn new_hypervisor(toml_config: &TomlConfig) -> Result<Arc<dyn Hypervisor>>
 
             Ok(Arc::new(hypervisor))
         }
+
+        #[cfg(feature = "firecracker")]
+        HYPERVISOR_NAME_FIRECRACKER => {
+            let mut hypervisor = Firecracker::new();
+            hypervisor
+                .set_hypervisor_config(hypervisor_config.clone())
+                .await;
+            Ok(Arc::new(hypervisor))
+        }
+
+        #[cfg(feature = "stratovirt")]
+        HYPERVISOR_NAME_STRATOVIRT => {
+            let mut hypervisor = StratoVirt::new();
+            hypervisor
+                .set_hypervisor_config(hypervisor_config.clone())
+                .await;
+            Ok(Arc::new(hypervisor))
+        }
         _ => Err(anyhow!("Unsupported hypervisor {}", &hypervisor_name)),
     }
 }
@@ -191,6 +277,7 @@ agent_name="kata"
         assert!(res.is_ok());
     }
 
+    #[cfg(feature = "qemu")]
     #[tokio::test]
     async fn test_new_hypervisor() {
         VirtContainer::init().unwrap();
@@ -0,0 +1,61 @@
+// Copyright (c) 2023 Kata Containers community
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use resource::ResourceManager;
+use tokio::sync::{mpsc, Mutex};
+
+/// Resizes the guest memory periodically to the memory limits of the running containers, so
+/// that the memory of the exited containers is given back to the host.
+pub(crate) struct MemoryReconciler {
+    interval: Duration,
+    stop_tx: mpsc::Sender<()>,
+    stop_rx: Arc<Mutex<mpsc::Receiver<()>>>,
+}
+
+impl MemoryReconciler {
+    /// A reconciler running every `interval_ms`, 0 to disable it.
+    pub(crate) fn new(interval_ms: u64) -> Self {
+        let (tx, rx) = mpsc::channel(1);
+        Self {
+            interval: Duration::from_millis(interval_ms),
+            stop_tx: tx,
+            stop_rx: Arc::new(Mutex::new(rx)),
+        }
+    }
+
+    pub(crate) fn start(&self, resource_manager: Arc<ResourceManager>) {
+        if self.interval.is_zero() {
+            return;
+        }
+
+        info!(sl!(), "start memory reconciler every {:?}", self.interval);
+        let interval = self.interval;
+        let stop_rx = self.stop_rx.clone();
+        tokio::spawn(async move {
+            let mut stop_rx = stop_rx.lock().await;
+            // stopped, or the reconciler is dropped
+            while tokio::time::timeout(interval, stop_rx.recv())
+                .await
+                .is_err()
+            {
+                if let Err(e) = resource_manager.reconcile_memory().await {
+                    warn!(sl!(), "failed to reconcile memory: {:?}", e);
+                }
+            }
+            info!(sl!(), "memory reconciler stopped");
+        });
+    }
+
+    pub(crate) async fn stop(&self) {
+        if self.interval.is_zero() {
+            return;
+        }
+        // the reconciler may have exited already
+        let _ = self.stop_tx.try_send(());
+    }
+}
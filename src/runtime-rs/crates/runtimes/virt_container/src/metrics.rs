@@ -0,0 +1,22 @@
+// Copyright (c) 2023 Kata Containers community
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+use lazy_static::lazy_static;
+use prometheus::{IntCounterVec, Opts};
+
+const NAMESPACE_KATA_SHIM: &str = "kata_shim";
+
+lazy_static! {
+    /// Count of the storage events reported by the guest, by type. It's registered by the shim
+    /// metrics.
+    pub static ref STORAGE_EVENTS: IntCounterVec = IntCounterVec::new(
+        Opts::new(
+            format!("{}_{}", NAMESPACE_KATA_SHIM, "storage_events_total"),
+            "Kata guest storage events, the nearly full filesystems and the failed mounts.",
+        ),
+        &["type"]
+    )
+    .unwrap();
+}
@@ -4,30 +4,54 @@
 // SPDX-License-Identifier: Apache-2.0
 //
 
+use std::path::{Component, Path};
 use std::sync::Arc;
+use std::time::SystemTime;
 
 use agent::kata::KataAgent;
 use agent::types::KernelModule;
-use agent::{self, Agent, GetIPTablesRequest, SetIPTablesRequest, VolumeStatsRequest};
+use agent::{
+    self, Agent, GetIPTablesRequest, SetDebugConsoleRequest, SetIPTablesRequest, VolumeStatsRequest,
+};
 use anyhow::{anyhow, Context, Result};
 use async_trait::async_trait;
-use common::message::{Action, Message};
-use common::{Sandbox, SandboxNetworkEnv};
+use common::message::{Action, Message, StorageEvent};
+use common::{Sandbox, SandboxDebugInfo, SandboxNetworkEnv};
 use containerd_shim_protos::events::task::TaskOOM;
 use hypervisor::{dragonball::Dragonball, BlockConfig, Hypervisor, HYPERVISOR_DRAGONBALL};
+#[cfg(feature = "firecracker")]
+use hypervisor::{firecracker::Firecracker, HYPERVISOR_NAME_FIRECRACKER};
+#[cfg(feature = "stratovirt")]
+use hypervisor::{stratovirt::StratoVirt, HYPERVISOR_NAME_STRATOVIRT};
 use hypervisor::{utils::get_hvsock_path, HybridVsockConfig, DEFAULT_GUEST_VSOCK_CID};
 use kata_sys_util::hooks::HookStates;
-use kata_types::config::TomlConfig;
+use kata_types::config::{
+    default::DEFAULT_AGENT_DBG_CONSOLE_PORT, GuestHook, TomlConfig, SANDBOX_READINESS_CHECK_AGENT,
+    SANDBOX_READINESS_CHECK_NETWORK,
+};
 use persist::{self, sandbox_persist::Persist};
+use resource::image_cache::{cache_key_from_spec, ImageCacheConfig};
 use resource::manager::ManagerArgs;
-use resource::network::{dan_config_path, DanNetworkConfig, NetworkConfig, NetworkWithNetNsConfig};
+use resource::network::{
+    dan_config_path, network_bpf_dir, DanNetworkConfig, NetworkConfig, NetworkWithNetNsConfig,
+};
+use resource::vsock_port::{guest_metrics_port_owner, VSOCK_PORT_DEBUG_CONSOLE};
 use resource::{ResourceConfig, ResourceManager};
+use tokio::net::UnixStream;
 use tokio::sync::{mpsc::Sender, Mutex, RwLock};
 use tracing::instrument;
 
 use crate::health_check::HealthCheck;
+use crate::host_resources::{check_host_resources, HostResources};
+use crate::memory_reconciler::MemoryReconciler;
+use crate::metrics::STORAGE_EVENTS;
+use crate::sandbox_persist::BootRecord;
+use crate::shutdown_budget::{kill_processes, ShutdownBudget, ShutdownPhase};
+use crate::stats_cache::StatsCache;
 
 pub(crate) const VIRTCONTAINER: &str = "virt_container";
+// the serving status of the agent health check
+const AGENT_HEALTH_SERVING: u32 = 1;
 pub struct SandboxRestoreArgs {
     pub sid: String,
     pub toml_config: TomlConfig,
@@ -43,12 +67,16 @@ pub enum SandboxState {
 
 struct SandboxInner {
     state: SandboxState,
+    // the request the sandbox is created in guest with, kept for
+    // re-creating the sandbox after the guest is rebooted.
+    create_sandbox_req: Option<agent::CreateSandboxRequest>,
 }
 
 impl SandboxInner {
     pub fn new() -> Self {
         Self {
             state: SandboxState::Init,
+            create_sandbox_req: None,
         }
     }
 }
@@ -58,10 +86,15 @@ pub struct VirtSandbox {
     sid: String,
     msg_sender: Arc<Mutex<Sender<Message>>>,
     inner: Arc<RwLock<SandboxInner>>,
+    // kept out of `inner` as it's saved while `inner` is locked
+    boot_record: Arc<RwLock<BootRecord>>,
     resource_manager: Arc<ResourceManager>,
     agent: Arc<dyn Agent>,
     hypervisor: Arc<dyn Hypervisor>,
     monitor: Arc<HealthCheck>,
+    memory_reconciler: Arc<MemoryReconciler>,
+    agent_metrics: StatsCache<String>,
+    hypervisor_metrics: StatsCache<String>,
 }
 
 impl std::fmt::Debug for VirtSandbox {
@@ -83,14 +116,20 @@ impl VirtSandbox {
     ) -> Result<Self> {
         let config = resource_manager.config().await;
         let keep_abnormal = config.runtime.keep_abnormal;
+        let stats_cache_ttl_ms = config.runtime.stats_cache_ttl_ms;
+        let memory_reconcile_interval_ms = config.runtime.memory_reconcile_interval_ms;
         Ok(Self {
             sid: sid.to_string(),
             msg_sender: Arc::new(Mutex::new(msg_sender)),
             inner: Arc::new(RwLock::new(SandboxInner::new())),
+            boot_record: Arc::new(RwLock::new(BootRecord::default())),
             agent,
             hypervisor,
             resource_manager,
             monitor: Arc::new(HealthCheck::new(true, keep_abnormal)),
+            memory_reconciler: Arc::new(MemoryReconciler::new(memory_reconcile_interval_ms)),
+            agent_metrics: StatsCache::new(stats_cache_ttl_ms),
+            hypervisor_metrics: StatsCache::new(stats_cache_ttl_ms),
         })
     }
 
@@ -98,6 +137,7 @@ impl VirtSandbox {
     async fn prepare_for_start_sandbox(
         &self,
         id: &str,
+        spec: &oci::Spec,
         network_env: SandboxNetworkEnv,
     ) -> Result<Vec<ResourceConfig>> {
         let mut resource_configs = vec![];
@@ -130,6 +170,17 @@ impl VirtSandbox {
         );
         resource_configs.push(vm_rootfs);
 
+        // prepare guest image cache disk config
+        let config = self.resource_manager.config().await;
+        if !config.runtime.guest_image_cache_dir.is_empty() {
+            resource_configs.push(ResourceConfig::ImageCache(ImageCacheConfig {
+                cache_dir: config.runtime.guest_image_cache_dir.clone(),
+                key: cache_key_from_spec(id, spec),
+                disk_size_mb: config.runtime.guest_image_cache_disk_size_mb,
+                limit_mb: config.runtime.guest_image_cache_limit_mb,
+            }));
+        }
+
         Ok(resource_configs)
     }
 
@@ -159,6 +210,7 @@ impl VirtSandbox {
                         .network_info
                         .network_queues as usize,
                     network_created: network_env.network_created,
+                    bpf_dir: network_bpf_dir(&config, &self.sid),
                 },
             )))
         } else {
@@ -223,6 +275,72 @@ impl VirtSandbox {
         })
     }
 
+    fn start_oom_watcher(&self) {
+        let agent = self.agent.clone();
+        let sender = self.msg_sender.clone();
+        info!(sl!(), "oom watcher start");
+        tokio::spawn(async move {
+            loop {
+                match agent
+                    .get_oom_event(agent::Empty::new())
+                    .await
+                    .context("get oom event")
+                {
+                    Ok(resp) => {
+                        let cid = &resp.container_id;
+                        warn!(sl!(), "send oom event for container {}", &cid);
+                        let event = TaskOOM {
+                            container_id: cid.to_string(),
+                            ..Default::default()
+                        };
+                        let msg = Message::new(Action::Event(Arc::new(event)));
+                        let lock_sender = sender.lock().await;
+                        if let Err(err) = lock_sender.send(msg).await.context("send event") {
+                            error!(
+                                sl!(),
+                                "failed to send oom event for {} error {:?}", cid, err
+                            );
+                        }
+                    }
+                    Err(err) => {
+                        warn!(sl!(), "failed to get oom event error {:?}", err);
+                        break;
+                    }
+                }
+            }
+        });
+    }
+
+    fn start_storage_event_watcher(&self) {
+        let agent = self.agent.clone();
+        let sender = self.msg_sender.clone();
+        info!(sl!(), "storage event watcher start");
+        tokio::spawn(async move {
+            loop {
+                match agent
+                    .get_storage_event(agent::Empty::new())
+                    .await
+                    .context("get storage event")
+                {
+                    Ok(resp) => {
+                        warn!(sl!(), "send storage event {:?}", &resp);
+                        let event = storage_event(resp);
+                        STORAGE_EVENTS.with_label_values(&[&event.r#type]).inc();
+                        let msg = Message::new(Action::Event(Arc::new(event)));
+                        let lock_sender = sender.lock().await;
+                        if let Err(err) = lock_sender.send(msg).await.context("send event") {
+                            error!(sl!(), "failed to send storage event error {:?}", err);
+                        }
+                    }
+                    Err(err) => {
+                        warn!(sl!(), "failed to get storage event error {:?}", err);
+                        break;
+                    }
+                }
+            }
+        });
+    }
+
     fn has_prestart_hooks(
         &self,
         prestart_hooks: Vec<oci::Hook>,
@@ -230,6 +348,187 @@ impl VirtSandbox {
     ) -> bool {
         !prestart_hooks.is_empty() || !create_runtime_hooks.is_empty()
     }
+
+    // check the sandbox is ready to run containers with the configured
+    // sandbox_readiness_checks
+    async fn stop_with_budget(&self, budget: &ShutdownBudget) -> Result<()> {
+        info!(sl!(), "begin stop sandbox");
+        // the hooks are run once, while the guest is still up
+        let running = {
+            let mut inner = self.inner.write().await;
+            std::mem::replace(&mut inner.state, SandboxState::Stopped) == SandboxState::Running
+        };
+        if running {
+            let hooks = self.agent.agent_config().await.guest_pre_stop_hooks;
+            if !hooks.is_empty() {
+                budget
+                    .run(
+                        ShutdownPhase::GuestHooks,
+                        self.run_guest_hooks(&hooks),
+                        // the hooks left are skipped, the VM is stopped anyway
+                        || async { Ok(()) },
+                    )
+                    .await
+                    .context("run guest hooks")?;
+            }
+        }
+        // the VM mustn't be resized while it's stopped
+        self.memory_reconciler.stop().await;
+        // get the pids before the stop, which may hang with the hypervisor locked
+        let pids = self.hypervisor.get_pids().await.unwrap_or_default();
+        budget
+            .run(
+                ShutdownPhase::VmShutdown,
+                async { self.hypervisor.stop_vm().await.context("stop vm") },
+                || async { kill_processes(&pids) },
+            )
+            .await
+    }
+
+    // run_guest_hooks runs the hooks in the guest in order, their failures
+    // are logged without stopping the ones left
+    async fn run_guest_hooks(&self, hooks: &[GuestHook]) -> Result<()> {
+        for hook in hooks {
+            info!(sl!(), "run guest hook {:?}", hook.args);
+            let req = agent::ExecGuestHookRequest {
+                args: hook.args.clone(),
+                timeout: hook.timeout,
+            };
+            if let Err(err) = self.agent.exec_guest_hook(req).await {
+                warn!(sl!(), "guest hook {:?} failed: {:?}", hook.args, err);
+            }
+        }
+        Ok(())
+    }
+
+    async fn cleanup_with_budget(&self, budget: &ShutdownBudget) -> Result<()> {
+        info!(sl!(), "delete hypervisor");
+        self.hypervisor
+            .cleanup()
+            .await
+            .context("delete hypervisor")?;
+
+        info!(sl!(), "stop share fs");
+        budget
+            .run(
+                ShutdownPhase::VirtiofsdExit,
+                self.resource_manager.stop_share_fs(),
+                || self.resource_manager.kill_share_fs(),
+            )
+            .await
+            .context("stop share fs")?;
+
+        info!(sl!(), "network clean up");
+        budget
+            .run(
+                ShutdownPhase::NetnsDestroy,
+                self.resource_manager.cleanup_network(),
+                // the netns is left to the CNI plugin
+                || async { Ok(()) },
+            )
+            .await
+            .context("network clean up")?;
+
+        info!(sl!(), "resource clean up");
+        self.resource_manager
+            .cleanup()
+            .await
+            .context("resource clean up")?;
+
+        // TODO: cleanup other sandbox resource
+        Ok(())
+    }
+
+    async fn shutdown_with_budget(&self, budget: &ShutdownBudget) -> Result<()> {
+        self.stop_with_budget(budget).await.context("stop")?;
+
+        self.cleanup_with_budget(budget)
+            .await
+            .context("do the clean up")?;
+
+        info!(sl!(), "stop monitor");
+        self.monitor.stop().await;
+
+        info!(sl!(), "stop agent");
+        budget
+            .run(
+                ShutdownPhase::AgentStop,
+                async {
+                    self.agent.stop().await;
+                    Ok(())
+                },
+                // nothing to kill, the agent connection is dropped with the shim
+                || async { Ok(()) },
+            )
+            .await
+    }
+
+    async fn check_readiness(&self) -> Result<()> {
+        let config = self.resource_manager.config().await;
+        for check in config.runtime.sandbox_readiness_checks.iter() {
+            info!(sl!(), "check sandbox readiness: {}", check);
+            match check.as_str() {
+                SANDBOX_READINESS_CHECK_AGENT => {
+                    let resp = self
+                        .agent
+                        .check(agent::CheckRequest::new(""))
+                        .await
+                        .context("check agent health")?;
+                    if resp.status != AGENT_HEALTH_SERVING {
+                        return Err(anyhow!("agent is not serving, status {}", resp.status));
+                    }
+                }
+                SANDBOX_READINESS_CHECK_NETWORK => self
+                    .resource_manager
+                    .check_network_ready()
+                    .await
+                    .context("check network")?,
+                _ => warn!(sl!(), "unknown sandbox readiness check {}", check),
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn guest_network(&self) -> Result<String> {
+        let interfaces = self
+            .agent
+            .list_interfaces(agent::Empty::new())
+            .await
+            .context("list interfaces")?;
+        let routes = self
+            .agent
+            .list_routes(agent::Empty::new())
+            .await
+            .context("list routes")?;
+        Ok(format!(
+            "interfaces: {:#?}\nroutes: {:#?}\n",
+            interfaces.interfaces, routes.routes
+        ))
+    }
+
+    async fn agent_health(&self) -> Result<String> {
+        let health = self
+            .agent
+            .check(agent::CheckRequest::new(""))
+            .await
+            .context("check agent health")?;
+        let version = self
+            .agent
+            .version(agent::CheckRequest::new(""))
+            .await
+            .context("get agent version")?;
+        Ok(format!("{:#?}\n{:#?}\n", health, version))
+    }
+
+    // the port of the debug console, allocated with the other vsock ports of
+    // the guest
+    async fn debug_console_vport(&self) -> u32 {
+        self.resource_manager
+            .vsock_port(VSOCK_PORT_DEBUG_CONSOLE)
+            .await
+            .unwrap_or(DEFAULT_AGENT_DBG_CONSOLE_PORT)
+    }
 }
 
 #[async_trait]
@@ -252,6 +551,15 @@ impl Sandbox for VirtSandbox {
             return Ok(());
         }
 
+        // fail fast rather than letting the VM run the host out of memory while booting
+        let config = self.resource_manager.config().await;
+        if config.runtime.host_resource_check {
+            let hypervisor_config = self.hypervisor.hypervisor_config().await;
+            let available = HostResources::read(hypervisor_config.memory_info.enable_hugepages)
+                .context("read host resources")?;
+            check_host_resources(&hypervisor_config, &config.runtime, &available)?;
+        }
+
         self.hypervisor
             .prepare_vm(id, network_env.netns.clone())
             .await
@@ -260,7 +568,7 @@ impl Sandbox for VirtSandbox {
         // generate device and setup before start vm
         // should after hypervisor.prepare_vm
         let resources = self
-            .prepare_for_start_sandbox(id, network_env.clone())
+            .prepare_for_start_sandbox(id, spec, network_env.clone())
             .await?;
 
         self.resource_manager
@@ -284,7 +592,6 @@ impl Sandbox for VirtSandbox {
         //    We need to rescan the netns to handle the change.
         // 2. Do not scan the netns if we want no network for the VM.
         // TODO In case of vm factory, scan the netns to hotplug interfaces after the VM is started.
-        let config = self.resource_manager.config().await;
         if self.has_prestart_hooks(prestart_hooks, create_runtime_hooks)
             && !config.runtime.disable_new_netns
             && !dan_config_path(&config, &self.sid).exists()
@@ -300,6 +607,7 @@ impl Sandbox for VirtSandbox {
                         .network_info
                         .network_queues as usize,
                     network_created: network_env.network_created,
+                    bpf_dir: network_bpf_dir(&config, &self.sid),
                 });
                 self.resource_manager
                     .handle_network(network_resource)
@@ -324,7 +632,11 @@ impl Sandbox for VirtSandbox {
 
         // create sandbox in vm
         let agent_config = self.agent.agent_config().await;
-        let kernel_modules = KernelModule::set_kernel_modules(agent_config.kernel_modules)?;
+        let security_info = self.hypervisor.hypervisor_config().await.security_info;
+        let kernel_modules = allowed_kernel_modules(
+            KernelModule::set_kernel_modules(agent_config.kernel_modules)?,
+            &security_info.guest_kernel_modules_blacklist(),
+        );
         let req = agent::CreateSandboxRequest {
             hostname: spec.hostname.clone(),
             dns,
@@ -335,77 +647,94 @@ impl Sandbox for VirtSandbox {
                 .context("get storages for sandbox")?,
             sandbox_pidns: false,
             sandbox_id: id.to_string(),
-            guest_hook_path: self
-                .hypervisor
-                .hypervisor_config()
-                .await
-                .security_info
-                .guest_hook_path,
+            guest_hook_path: security_info.guest_hook_path,
             kernel_modules,
+            dns_cache: agent_config.enable_dns_cache,
         };
 
         self.agent
-            .create_sandbox(req)
+            .create_sandbox(req.clone())
             .await
             .context("create sandbox")?;
 
+        self.check_readiness()
+            .await
+            .context("check sandbox readiness")?;
+
         inner.state = SandboxState::Running;
-        let agent = self.agent.clone();
-        let sender = self.msg_sender.clone();
-        info!(sl!(), "oom watcher start");
-        tokio::spawn(async move {
-            loop {
-                match agent
-                    .get_oom_event(agent::Empty::new())
-                    .await
-                    .context("get oom event")
-                {
-                    Ok(resp) => {
-                        let cid = &resp.container_id;
-                        warn!(sl!(), "send oom event for container {}", &cid);
-                        let event = TaskOOM {
-                            container_id: cid.to_string(),
-                            ..Default::default()
-                        };
-                        let msg = Message::new(Action::Event(Arc::new(event)));
-                        let lock_sender = sender.lock().await;
-                        if let Err(err) = lock_sender.send(msg).await.context("send event") {
-                            error!(
-                                sl!(),
-                                "failed to send oom event for {} error {:?}", cid, err
-                            );
-                        }
-                    }
-                    Err(err) => {
-                        warn!(sl!(), "failed to get oom event error {:?}", err);
-                        break;
-                    }
-                }
-            }
-        });
+        inner.create_sandbox_req = Some(req);
+        {
+            let now = SystemTime::now();
+            let mut boot_record = self.boot_record.write().await;
+            boot_record.created_at = Some(now);
+            boot_record.started_at = Some(now);
+        }
+        self.start_oom_watcher();
+        self.start_storage_event_watcher();
         self.monitor.start(id, self.agent.clone());
+        self.memory_reconciler.start(self.resource_manager.clone());
         self.save().await.context("save state")?;
         Ok(())
     }
 
     async fn stop(&self) -> Result<()> {
-        info!(sl!(), "begin stop sandbox");
-        self.hypervisor.stop_vm().await.context("stop vm")?;
-        Ok(())
+        let budget = ShutdownBudget::new(&self.resource_manager.config().await.runtime);
+        let result = self.stop_with_budget(&budget).await;
+        budget.report("stop sandbox");
+        result
     }
 
-    async fn shutdown(&self) -> Result<()> {
-        info!(sl!(), "shutdown");
+    async fn reboot(&self) -> Result<()> {
+        let inner = self.inner.write().await;
+        if inner.state != SandboxState::Running {
+            return Err(anyhow!("sandbox {} is not running", self.sid));
+        }
+        let req = inner
+            .create_sandbox_req
+            .clone()
+            .context("sandbox is not created in guest")?;
 
-        self.stop().await.context("stop")?;
+        info!(sl!(), "begin reboot sandbox");
+        // the agent is unreachable while the guest is rebooting, stop the
+        // monitor to avoid exiting on the failed health checks.
+        self.monitor.stop().await;
+        self.agent.stop().await;
 
-        self.cleanup().await.context("do the clean up")?;
+        self.hypervisor.reboot_vm().await.context("reboot vm")?;
 
-        info!(sl!(), "stop monitor");
-        self.monitor.stop().await;
+        let address = self
+            .hypervisor
+            .get_agent_socket()
+            .await
+            .context("get agent socket")?;
+        self.agent.start(&address).await.context("connect")?;
 
-        info!(sl!(), "stop agent");
-        self.agent.stop().await;
+        self.resource_manager
+            .setup_after_start_vm()
+            .await
+            .context("setup device after reboot vm")?;
+
+        self.agent
+            .create_sandbox(req)
+            .await
+            .context("create sandbox")?;
+
+        self.boot_record.write().await.started_at = Some(SystemTime::now());
+        self.start_oom_watcher();
+        self.start_storage_event_watcher();
+        self.monitor.start(&self.sid, self.agent.clone());
+        self.save().await.context("save state")?;
+        info!(sl!(), "end reboot sandbox");
+        Ok(())
+    }
+
+    async fn shutdown(&self) -> Result<()> {
+        info!(sl!(), "shutdown");
+
+        let budget = ShutdownBudget::new(&self.resource_manager.config().await.runtime);
+        let result = self.shutdown_with_budget(&budget).await;
+        budget.report("shutdown sandbox");
+        result?;
 
         // stop server
         info!(sl!(), "send shutdown message");
@@ -416,21 +745,19 @@ impl Sandbox for VirtSandbox {
         Ok(())
     }
 
-    async fn cleanup(&self) -> Result<()> {
-        info!(sl!(), "delete hypervisor");
-        self.hypervisor
-            .cleanup()
-            .await
-            .context("delete hypervisor")?;
+    async fn created_at(&self) -> Option<SystemTime> {
+        self.boot_record.read().await.created_at
+    }
 
-        info!(sl!(), "resource clean up");
-        self.resource_manager
-            .cleanup()
-            .await
-            .context("resource clean up")?;
+    async fn started_at(&self) -> Option<SystemTime> {
+        self.boot_record.read().await.started_at
+    }
 
-        // TODO: cleanup other sandbox resource
-        Ok(())
+    async fn cleanup(&self) -> Result<()> {
+        let budget = ShutdownBudget::new(&self.resource_manager.config().await.runtime);
+        let result = self.cleanup_with_budget(&budget).await;
+        budget.report("cleanup sandbox");
+        result
     }
 
     async fn agent_sock(&self) -> Result<String> {
@@ -457,6 +784,59 @@ impl Sandbox for VirtSandbox {
         Ok(())
     }
 
+    async fn copy_file(&self, req: agent::CopyFileRequest) -> Result<()> {
+        self.agent
+            .copy_file(req)
+            .await
+            .context("sandbox: failed to copy file")?;
+        Ok(())
+    }
+
+    async fn read_file(&self, req: agent::ReadFileRequest) -> Result<agent::ReadFileResponse> {
+        self.agent
+            .read_file(req)
+            .await
+            .context("sandbox: failed to read file")
+    }
+
+    async fn dump_guest_memory(&self, path: &str) -> Result<()> {
+        info!(sl!(), "sb: dump_guest_memory invoked, path {}", path);
+        let config = self.resource_manager.config().await;
+        check_memory_dump_path(&config.runtime.memory_dump_dir, path)?;
+        self.hypervisor
+            .dump_guest_memory(path)
+            .await
+            .context("sandbox: failed to dump guest memory")
+    }
+
+    async fn debug_info(&self) -> Result<SandboxDebugInfo> {
+        info!(sl!(), "sb: debug_info invoked");
+        // the sandbox can't change state until the snapshot is taken
+        let inner = self.inner.read().await;
+
+        let state = persist::from_disk::<serde_json::Value>(&self.sid)
+            .and_then(|state| Ok(serde_json::to_string_pretty(&state)?));
+        let command_line = self
+            .hypervisor
+            .get_command_line()
+            .await
+            .map(|args| args.join(" "));
+        let (network, agent_health) = if inner.state == SandboxState::Running {
+            (self.guest_network().await, self.agent_health().await)
+        } else {
+            let err = || Err(anyhow!("sandbox is {:?}", inner.state));
+            (err(), err())
+        };
+
+        Ok(SandboxDebugInfo {
+            state: debug_text(state),
+            hypervisor_command_line: debug_text(command_line),
+            network: debug_text(network),
+            agent_health: debug_text(agent_health),
+            agent_logs: self.agent.recent_logs().await.join("\n"),
+        })
+    }
+
     async fn set_iptables(&self, is_ipv6: bool, data: Vec<u8>) -> Result<Vec<u8>> {
         info!(sl!(), "sb: set_iptables invoked");
         let req = SetIPTablesRequest { is_ipv6, data };
@@ -479,17 +859,122 @@ impl Sandbox for VirtSandbox {
         Ok(resp.data)
     }
 
-    async fn agent_metrics(&self) -> Result<String> {
+    async fn set_debug_console(&self, enable: bool) -> Result<()> {
+        info!(sl!(), "sb: set_debug_console invoked, enable {}", enable);
+        let vport = self.debug_console_vport().await;
+        let req = SetDebugConsoleRequest { enable, vport };
         self.agent
-            .get_metrics(agent::Empty::new())
+            .set_debug_console(req)
+            .await
+            .context("sandbox: failed to set debug console")?;
+        Ok(())
+    }
+
+    async fn connect_debug_console(&self) -> Result<UnixStream> {
+        let vport = self.debug_console_vport().await;
+        info!(sl!(), "sb: connect debug console on vport {}", vport);
+        self.agent
+            .connect_port(vport)
+            .await
+            .context("sandbox: failed to connect debug console, is it enabled?")
+    }
+
+    async fn connect_guest_metrics(&self, port: u32) -> Result<UnixStream> {
+        // only the configured exporters are reachable, not any port of the guest
+        let vport = self
+            .resource_manager
+            .vsock_port(&guest_metrics_port_owner(port))
+            .await
+            .ok_or_else(|| anyhow!("sandbox: guest metrics port {} isn't configured", port))?;
+        debug!(
+            sl!(),
+            "sb: connect guest metrics exporter on vport {}", vport
+        );
+        self.agent
+            .connect_port(vport)
+            .await
+            .context("sandbox: failed to connect guest metrics exporter")
+    }
+
+    async fn agent_metrics(&self) -> Result<String> {
+        self.agent_metrics
+            .get_or_fetch(|| async {
+                self.agent
+                    .get_metrics(agent::Empty::new())
+                    .await
+                    .map_err(|err| anyhow!("failed to get agent metrics {:?}", err))
+                    .map(|resp| resp.metrics)
+            })
             .await
-            .map_err(|err| anyhow!("failed to get agent metrics {:?}", err))
-            .map(|resp| resp.metrics)
     }
 
     async fn hypervisor_metrics(&self) -> Result<String> {
-        self.hypervisor.get_hypervisor_metrics().await
+        self.hypervisor_metrics
+            .get_or_fetch(|| self.hypervisor.get_hypervisor_metrics())
+            .await
+    }
+}
+
+// storage_event converts the storage event reported by the agent into the event
+// published to containerd
+fn storage_event(event: agent::StorageEvent) -> StorageEvent {
+    let r#type = match event.event_type {
+        agent::StorageEventType::NearFull => "near_full",
+        agent::StorageEventType::MountFailed => "mount_failed",
+    };
+    StorageEvent {
+        container_id: event.container_id,
+        r#type: r#type.to_string(),
+        mount_point: event.mount_point,
+        used_bytes: event.used_bytes,
+        capacity_bytes: event.capacity_bytes,
+        message: event.message,
+    }
+}
+
+// allowed_kernel_modules drops the kernel modules blacklisted in the guest, which the agent
+// would fail to load
+fn allowed_kernel_modules(modules: Vec<KernelModule>, blacklist: &[String]) -> Vec<KernelModule> {
+    modules
+        .into_iter()
+        .filter(|m| {
+            let blacklisted = blacklist.contains(&m.name.replace('-', "_"));
+            if blacklisted {
+                warn!(
+                    sl!(),
+                    "kernel module {} is blacklisted in the guest", m.name
+                );
+            }
+            !blacklisted
+        })
+        .collect()
+}
+
+// the part of the debug info which can't be collected holds the error
+fn debug_text(result: Result<String>) -> String {
+    result.unwrap_or_else(|e| format!("failed to collect: {:?}\n", e))
+}
+
+// the guest memory dump is disabled unless the dump directory is configured,
+// and the dump must be a new file right in it
+fn check_memory_dump_path(dump_dir: &str, path: &str) -> Result<()> {
+    if dump_dir.is_empty() {
+        return Err(anyhow!("guest memory dump is disabled"));
     }
+
+    let p = Path::new(path);
+    if !p.is_absolute()
+        || p.parent() != Some(Path::new(dump_dir))
+        || !matches!(p.components().last(), Some(Component::Normal(_)))
+    {
+        return Err(anyhow!(
+            "memory dump path {} is not a file in {}",
+            path,
+            dump_dir
+        ));
+    }
+
+    Ok(())
 }
 
 #[async_trait]
@@ -503,6 +988,7 @@ impl Persist for VirtSandbox {
             sandbox_type: VIRTCONTAINER.to_string(),
             resource: Some(self.resource_manager.save().await?),
             hypervisor: Some(self.hypervisor.save_state().await?),
+            boot_record: *self.boot_record.read().await,
         };
         persist::to_disk(&sandbox_state, &self.sid)?;
         Ok(sandbox_state)
@@ -515,14 +1001,20 @@ impl Persist for VirtSandbox {
         let config = sandbox_args.toml_config;
         let r = sandbox_state.resource.unwrap_or_default();
         let h = sandbox_state.hypervisor.unwrap_or_default();
-        let hypervisor = match h.hypervisor_type.as_str() {
+        let hypervisor: Arc<dyn Hypervisor> = match h.hypervisor_type.as_str() {
             // TODO support other hypervisors
-            HYPERVISOR_DRAGONBALL => Ok(Arc::new(Dragonball::restore((), h).await?)),
-            _ => Err(anyhow!("Unsupported hypervisor {}", &h.hypervisor_type)),
-        }?;
+            HYPERVISOR_DRAGONBALL => Arc::new(Dragonball::restore((), h).await?),
+            #[cfg(feature = "firecracker")]
+            HYPERVISOR_NAME_FIRECRACKER => Arc::new(Firecracker::restore((), h).await?),
+            #[cfg(feature = "stratovirt")]
+            HYPERVISOR_NAME_STRATOVIRT => Arc::new(StratoVirt::restore((), h).await?),
+            _ => return Err(anyhow!("Unsupported hypervisor {}", &h.hypervisor_type)),
+        };
         let agent = Arc::new(KataAgent::new(kata_types::config::Agent::default()));
         let sid = sandbox_args.sid;
         let keep_abnormal = config.runtime.keep_abnormal;
+        let stats_cache_ttl_ms = config.runtime.stats_cache_ttl_ms;
+        let memory_reconcile_interval_ms = config.runtime.memory_reconcile_interval_ms;
         let args = ManagerArgs {
             sid: sid.clone(),
             agent: agent.clone(),
@@ -534,10 +1026,65 @@ impl Persist for VirtSandbox {
             sid: sid.to_string(),
             msg_sender: Arc::new(Mutex::new(sandbox_args.sender)),
             inner: Arc::new(RwLock::new(SandboxInner::new())),
+            boot_record: Arc::new(RwLock::new(sandbox_state.boot_record)),
             agent,
             hypervisor,
             resource_manager,
             monitor: Arc::new(HealthCheck::new(true, keep_abnormal)),
+            memory_reconciler: Arc::new(MemoryReconciler::new(memory_reconcile_interval_ms)),
+            agent_metrics: StatsCache::new(stats_cache_ttl_ms),
+            hypervisor_metrics: StatsCache::new(stats_cache_ttl_ms),
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_memory_dump_path() {
+        assert!(check_memory_dump_path("", "/var/crash/dump").is_err());
+
+        let dir = "/var/crash";
+        assert!(check_memory_dump_path(dir, "/var/crash/dump").is_ok());
+        assert!(check_memory_dump_path(dir, "dump").is_err());
+        assert!(check_memory_dump_path(dir, "/var/crash").is_err());
+        assert!(check_memory_dump_path(dir, "/var/crash/sub/dump").is_err());
+        assert!(check_memory_dump_path(dir, "/var/crash/../dump").is_err());
+        assert!(check_memory_dump_path(dir, "/etc/dump").is_err());
+    }
+
+    #[test]
+    fn test_allowed_kernel_modules() {
+        let modules = KernelModule::set_kernel_modules(vec![
+            "e1000e InterruptThrottleRate=3000".to_string(),
+            "nf-tables".to_string(),
+            "sctp".to_string(),
+        ])
+        .unwrap();
+        let blacklist = vec!["nf_tables".to_string(), "sctp".to_string()];
+
+        let names: Vec<String> = allowed_kernel_modules(modules.clone(), &blacklist)
+            .into_iter()
+            .map(|m| m.name)
+            .collect();
+        assert_eq!(names, vec!["e1000e".to_string()]);
+        assert_eq!(allowed_kernel_modules(modules, &[]).len(), 3);
+    }
+
+    #[test]
+    fn test_storage_event() {
+        let event = storage_event(agent::StorageEvent {
+            event_type: agent::StorageEventType::MountFailed,
+            container_id: "c1".to_string(),
+            mount_point: "/run/kata-containers/sandbox/volumes/v1".to_string(),
+            message: "no such device".to_string(),
+            ..Default::default()
+        });
+        assert_eq!(event.r#type, "mount_failed");
+        assert_eq!(event.container_id, "c1");
+        assert_eq!(event.mount_point, "/run/kata-containers/sandbox/volumes/v1");
+        assert_eq!(event.message, "no such device");
+    }
+}
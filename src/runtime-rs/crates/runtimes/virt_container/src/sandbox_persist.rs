@@ -4,13 +4,55 @@
 // SPDX-License-Identifier: Apache-2.0
 //
 
+use std::time::SystemTime;
+
 use hypervisor::hypervisor_persist::HypervisorState;
 use resource::resource_persist::ResourceState;
 use serde::{Deserialize, Serialize};
 
+/// The times the sandbox VM is created and (re)started at.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+pub struct BootRecord {
+    /// the time the VM is first started at, kept across guest reboots
+    pub created_at: Option<SystemTime>,
+    /// the time the guest is last started or rebooted at
+    pub started_at: Option<SystemTime>,
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct SandboxState {
     pub sandbox_type: String,
     pub resource: Option<ResourceState>,
     pub hypervisor: Option<HypervisorState>,
+    #[serde(default)]
+    pub boot_record: BootRecord,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_restore_state_without_boot_record() {
+        let state: SandboxState =
+            serde_json::from_str(r#"{"sandbox_type":"virt_container"}"#).unwrap();
+        assert_eq!(state.boot_record, BootRecord::default());
+    }
+
+    #[test]
+    fn test_boot_record_round_trip() {
+        let now = SystemTime::now();
+        let state = SandboxState {
+            sandbox_type: "virt_container".to_string(),
+            resource: None,
+            hypervisor: None,
+            boot_record: BootRecord {
+                created_at: Some(now),
+                started_at: Some(now),
+            },
+        };
+        let data = serde_json::to_string(&state).unwrap();
+        let restored: SandboxState = serde_json::from_str(&data).unwrap();
+        assert_eq!(restored.boot_record, state.boot_record);
+    }
 }
@@ -0,0 +1,208 @@
+// Copyright (c) 2023 Kata Containers community
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+use std::fmt;
+use std::future::Future;
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use kata_types::config::Runtime;
+use nix::sys::signal::{kill, Signal};
+use nix::unistd::Pid;
+
+const DEFAULT_GUEST_HOOKS_TIMEOUT_MS: u64 = 30_000;
+const DEFAULT_AGENT_STOP_TIMEOUT_MS: u64 = 3_000;
+const DEFAULT_VM_SHUTDOWN_TIMEOUT_MS: u64 = 10_000;
+const DEFAULT_VIRTIOFSD_EXIT_TIMEOUT_MS: u64 = 3_000;
+const DEFAULT_NETNS_DESTROY_TIMEOUT_MS: u64 = 5_000;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum ShutdownPhase {
+    GuestHooks,
+    AgentStop,
+    VmShutdown,
+    VirtiofsdExit,
+    NetnsDestroy,
+}
+
+impl fmt::Display for ShutdownPhase {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let phase = match self {
+            ShutdownPhase::GuestHooks => "guest hooks",
+            ShutdownPhase::AgentStop => "agent stop",
+            ShutdownPhase::VmShutdown => "vm shutdown",
+            ShutdownPhase::VirtiofsdExit => "virtiofsd exit",
+            ShutdownPhase::NetnsDestroy => "netns destroy",
+        };
+        write!(f, "{}", phase)
+    }
+}
+
+/// Time budget of the sandbox shutdown. Every phase is given its own timeout, and is forced,
+/// e.g. by SIGKILL, if it isn't done in time, so the shutdown completes in a bounded time.
+pub(crate) struct ShutdownBudget {
+    guest_hooks: Duration,
+    agent_stop: Duration,
+    vm_shutdown: Duration,
+    virtiofsd_exit: Duration,
+    netns_destroy: Duration,
+    forced: Mutex<Vec<ShutdownPhase>>,
+}
+
+fn timeout_or_default(ms: u64, default: u64) -> Duration {
+    Duration::from_millis(if ms == 0 { default } else { ms })
+}
+
+impl ShutdownBudget {
+    pub(crate) fn new(runtime: &Runtime) -> Self {
+        Self {
+            guest_hooks: timeout_or_default(
+                runtime.shutdown_guest_hooks_timeout_ms,
+                DEFAULT_GUEST_HOOKS_TIMEOUT_MS,
+            ),
+            agent_stop: timeout_or_default(
+                runtime.shutdown_agent_timeout_ms,
+                DEFAULT_AGENT_STOP_TIMEOUT_MS,
+            ),
+            vm_shutdown: timeout_or_default(
+                runtime.shutdown_vm_timeout_ms,
+                DEFAULT_VM_SHUTDOWN_TIMEOUT_MS,
+            ),
+            virtiofsd_exit: timeout_or_default(
+                runtime.shutdown_virtiofsd_timeout_ms,
+                DEFAULT_VIRTIOFSD_EXIT_TIMEOUT_MS,
+            ),
+            netns_destroy: timeout_or_default(
+                runtime.shutdown_netns_timeout_ms,
+                DEFAULT_NETNS_DESTROY_TIMEOUT_MS,
+            ),
+            forced: Mutex::new(vec![]),
+        }
+    }
+
+    fn timeout(&self, phase: ShutdownPhase) -> Duration {
+        match phase {
+            ShutdownPhase::GuestHooks => self.guest_hooks,
+            ShutdownPhase::AgentStop => self.agent_stop,
+            ShutdownPhase::VmShutdown => self.vm_shutdown,
+            ShutdownPhase::VirtiofsdExit => self.virtiofsd_exit,
+            ShutdownPhase::NetnsDestroy => self.netns_destroy,
+        }
+    }
+
+    /// Run the `phase` within its timeout, `force` is run instead if it times out.
+    pub(crate) async fn run<F, K, KF>(&self, phase: ShutdownPhase, f: F, force: K) -> Result<()>
+    where
+        F: Future<Output = Result<()>>,
+        K: FnOnce() -> KF,
+        KF: Future<Output = Result<()>>,
+    {
+        let timeout = self.timeout(phase);
+        match tokio::time::timeout(timeout, f).await {
+            Ok(result) => result,
+            Err(_) => {
+                warn!(sl!(), "{} isn't done in {:?}, force it", phase, timeout);
+                self.forced.lock().unwrap().push(phase);
+                force().await.with_context(|| format!("force {}", phase))
+            }
+        }
+    }
+
+    /// The phases which needed force.
+    pub(crate) fn forced_phases(&self) -> Vec<ShutdownPhase> {
+        self.forced.lock().unwrap().clone()
+    }
+
+    /// Log the phases which needed force, if any.
+    pub(crate) fn report(&self, action: &str) {
+        let forced = self.forced_phases();
+        if forced.is_empty() {
+            return;
+        }
+
+        let phases: Vec<String> = forced.iter().map(|p| p.to_string()).collect();
+        warn!(sl!(), "{} needed force", action; "forced_phases" => phases.join(", "));
+    }
+}
+
+/// Kill the processes by SIGKILL, the threads of the shim itself are skipped, e.g. the ones
+/// of the in-process VMM.
+pub(crate) fn kill_processes(pids: &[u32]) -> Result<()> {
+    for pid in pids {
+        if Path::new(&format!("/proc/self/task/{}", pid)).exists() {
+            continue;
+        }
+
+        info!(sl!(), "kill process {}", pid);
+        match kill(Pid::from_raw(*pid as i32), Signal::SIGKILL) {
+            Ok(_) | Err(nix::Error::ESRCH) => {}
+            Err(e) => return Err(e).with_context(|| format!("kill process {}", pid)),
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    #[tokio::test]
+    async fn test_shutdown_budget() {
+        let budget = ShutdownBudget::new(&Runtime {
+            shutdown_vm_timeout_ms: 10,
+            ..Default::default()
+        });
+        assert_eq!(
+            budget.timeout(ShutdownPhase::AgentStop),
+            Duration::from_millis(DEFAULT_AGENT_STOP_TIMEOUT_MS)
+        );
+        assert_eq!(
+            budget.timeout(ShutdownPhase::VmShutdown),
+            Duration::from_millis(10)
+        );
+        assert_eq!(
+            budget.timeout(ShutdownPhase::GuestHooks),
+            Duration::from_millis(DEFAULT_GUEST_HOOKS_TIMEOUT_MS)
+        );
+
+        // done in time
+        budget
+            .run(ShutdownPhase::AgentStop, async { Ok(()) }, || async {
+                Err(anyhow::anyhow!("forced"))
+            })
+            .await
+            .unwrap();
+        assert!(budget.forced_phases().is_empty());
+
+        // forced on timeout
+        let forced = AtomicBool::new(false);
+        let forced_ref = &forced;
+        budget
+            .run(
+                ShutdownPhase::VmShutdown,
+                async {
+                    tokio::time::sleep(Duration::from_secs(10)).await;
+                    Ok(())
+                },
+                || async move {
+                    forced_ref.store(true, Ordering::SeqCst);
+                    Ok(())
+                },
+            )
+            .await
+            .unwrap();
+        assert!(forced.load(Ordering::SeqCst));
+        assert_eq!(budget.forced_phases(), vec![ShutdownPhase::VmShutdown]);
+    }
+
+    #[test]
+    fn test_kill_processes_skip_self() {
+        // the shim itself isn't killed
+        assert!(kill_processes(&[std::process::id()]).is_ok());
+    }
+}
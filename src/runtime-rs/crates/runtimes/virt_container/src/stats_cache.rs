@@ -0,0 +1,164 @@
+// Copyright (c) 2023 Kata Containers community
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use tokio::sync::Mutex;
+
+/// Generation of the state some stats depend on, e.g. the resources of a container. It's bumped
+/// when an update of the state starts and when it ends, so it's odd while the state is changing.
+#[derive(Debug, Default)]
+pub(crate) struct Generation(AtomicU64);
+
+impl Generation {
+    pub(crate) fn current(&self) -> u64 {
+        self.0.load(Ordering::SeqCst)
+    }
+
+    /// Mark the state as changing until the returned guard is dropped.
+    pub(crate) fn begin_update(&self) -> GenerationUpdate<'_> {
+        self.0.fetch_add(1, Ordering::SeqCst);
+        GenerationUpdate(self)
+    }
+}
+
+pub(crate) struct GenerationUpdate<'a>(&'a Generation);
+
+impl Drop for GenerationUpdate<'_> {
+    fn drop(&mut self) {
+        (self.0).0.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+/// Cache of the last stats fetched from the agent or the hypervisor, served for `ttl`.
+///
+/// Only the last value is kept, and the requests made while it's being fetched wait for it
+/// instead of asking the agent again. The errors aren't cached.
+pub(crate) struct StatsCache<T> {
+    ttl: Duration,
+    entry: Mutex<Option<(Instant, u64, T)>>,
+}
+
+impl<T: Clone> StatsCache<T> {
+    /// A cache serving the stats for `ttl_ms`, 0 to always fetch them.
+    pub(crate) fn new(ttl_ms: u64) -> Self {
+        Self {
+            ttl: Duration::from_millis(ttl_ms),
+            entry: Mutex::new(None),
+        }
+    }
+
+    pub(crate) async fn get_or_fetch<F, Fut>(&self, fetch: F) -> Result<T>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        self.get_or_fetch_at(&Generation::default(), fetch).await
+    }
+
+    /// Like `get_or_fetch`, for the stats of the state of `generation`. The stats cached for
+    /// another generation aren't served, and the ones fetched while the state changed aren't
+    /// cached, they may mix the old and the new state.
+    pub(crate) async fn get_or_fetch_at<F, Fut>(
+        &self,
+        generation: &Generation,
+        fetch: F,
+    ) -> Result<T>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        if self.ttl.is_zero() {
+            return fetch().await;
+        }
+
+        let mut entry = self.entry.lock().await;
+        let fetched_for = generation.current();
+        if let Some((fetched_at, stats_generation, stats)) = entry.as_ref() {
+            if fetched_at.elapsed() < self.ttl && *stats_generation == fetched_for {
+                return Ok(stats.clone());
+            }
+        }
+        let stats = fetch().await?;
+        *entry = (fetched_for % 2 == 0 && generation.current() == fetched_for)
+            .then(|| (Instant::now(), fetched_for, stats.clone()));
+        Ok(stats)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    async fn fetch_count(cache: &StatsCache<u32>, fetches: &AtomicU32) -> Result<u32> {
+        cache
+            .get_or_fetch(|| async { Ok(fetches.fetch_add(1, Ordering::SeqCst) + 1) })
+            .await
+    }
+
+    #[tokio::test]
+    async fn test_stats_cache_ttl() {
+        let fetches = AtomicU32::new(0);
+        let cache = StatsCache::new(50);
+
+        assert_eq!(fetch_count(&cache, &fetches).await.unwrap(), 1);
+        assert_eq!(fetch_count(&cache, &fetches).await.unwrap(), 1);
+
+        // fetched again once expired
+        tokio::time::sleep(Duration::from_millis(60)).await;
+        assert_eq!(fetch_count(&cache, &fetches).await.unwrap(), 2);
+
+        // the errors aren't cached
+        let cache = StatsCache::new(50);
+        assert!(cache
+            .get_or_fetch(|| async { Err(anyhow::anyhow!("agent")) })
+            .await
+            .is_err());
+        assert_eq!(fetch_count(&cache, &fetches).await.unwrap(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_stats_cache_generation() {
+        let fetches = AtomicU32::new(0);
+        let cache = StatsCache::new(60_000);
+        let generation = Generation::default();
+        let fetch = || async { Ok(fetches.fetch_add(1, Ordering::SeqCst) + 1) };
+
+        assert_eq!(cache.get_or_fetch_at(&generation, fetch).await.unwrap(), 1);
+        assert_eq!(cache.get_or_fetch_at(&generation, fetch).await.unwrap(), 1);
+
+        // the stats aren't cached while the state changes
+        let update = generation.begin_update();
+        assert_eq!(generation.current(), 1);
+        assert_eq!(cache.get_or_fetch_at(&generation, fetch).await.unwrap(), 2);
+        assert_eq!(cache.get_or_fetch_at(&generation, fetch).await.unwrap(), 3);
+        drop(update);
+        assert_eq!(generation.current(), 2);
+
+        // nor the ones of a state changed during the fetch
+        let stats = cache
+            .get_or_fetch_at(&generation, || async {
+                let _update = generation.begin_update();
+                Ok(10)
+            })
+            .await;
+        assert_eq!(stats.unwrap(), 10);
+        assert_eq!(cache.get_or_fetch_at(&generation, fetch).await.unwrap(), 4);
+        assert_eq!(cache.get_or_fetch_at(&generation, fetch).await.unwrap(), 4);
+    }
+
+    #[tokio::test]
+    async fn test_stats_cache_disabled() {
+        let fetches = AtomicU32::new(0);
+        let cache = StatsCache::new(0);
+
+        assert_eq!(fetch_count(&cache, &fetches).await.unwrap(), 1);
+        assert_eq!(fetch_count(&cache, &fetches).await.unwrap(), 2);
+    }
+}
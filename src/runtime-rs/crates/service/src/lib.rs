@@ -11,4 +11,7 @@ logging::logger_with_subsystem!(sl, "service");
 
 mod manager;
 pub use manager::ServiceManager;
+mod nri;
 mod task_service;
+pub use task_service::is_handling_request;
+mod ttrpc_proxy;
@@ -17,8 +17,9 @@ use containerd_shim_protos::{
     protobuf::{well_known_types::any::Any, Message as ProtobufMessage},
     shim_async,
 };
-use kata_types::config::KATA_PATH;
-use runtimes::RuntimeHandlerManager;
+use kata_sys_util::naming::sandbox_dir_name;
+use kata_types::config::{Runtime, TomlConfig, KATA_PATH};
+use runtimes::{peer_cred::PeerCredAuth, RuntimeHandlerManager};
 use tokio::{
     io::AsyncWriteExt,
     process::Command,
@@ -26,15 +27,23 @@ use tokio::{
 };
 use ttrpc::asynchronous::Server;
 
-use crate::task_service::TaskService;
+use crate::{
+    nri::Nri,
+    task_service::TaskService,
+    ttrpc_proxy::{ConnectionLimits, TtrpcProxy},
+};
 
 /// message buffer size
 const MESSAGE_BUFFER_SIZE: usize = 8;
 
+const KATA_CONF_FILE: &str = "KATA_CONF_FILE";
+
 pub struct ServiceManager {
     receiver: Option<Receiver<Message>>,
     handler: Arc<RuntimeHandlerManager>,
     task_server: Option<Server>,
+    task_server_proxy: Option<TtrpcProxy>,
+    nri: Option<Nri>,
     binary: String,
     address: String,
     namespace: String,
@@ -46,6 +55,10 @@ impl std::fmt::Debug for ServiceManager {
         f.debug_struct("ServiceManager")
             .field("receiver", &self.receiver)
             .field("task_server.is_some()", &self.task_server.is_some())
+            .field(
+                "task_server_proxy.is_some()",
+                &self.task_server_proxy.is_some(),
+            )
             .field("binary", &self.binary)
             .field("address", &self.address)
             .field("namespace", &self.namespace)
@@ -65,12 +78,29 @@ impl ServiceManager {
         let (sender, receiver) = channel::<Message>(MESSAGE_BUFFER_SIZE);
         let rt_mgr = RuntimeHandlerManager::new(id, sender).context("new runtime handler")?;
         let handler = Arc::new(rt_mgr);
+
+        // the connections are limited and authorized by a proxy in front of the task server,
+        // if configured
+        let runtime_config = load_runtime_config();
+        let limits = ConnectionLimits::new(&runtime_config);
+        let peer_cred_auth = PeerCredAuth::new(&runtime_config);
+        let (task_server_fd, task_server_proxy) = if limits.is_enabled() || peer_cred_auth.is_some()
+        {
+            let (proxy, fd) = TtrpcProxy::new(id, task_server_fd, limits, peer_cred_auth)
+                .context("new task server proxy")?;
+            (fd, Some(proxy))
+        } else {
+            (task_server_fd, None)
+        };
+
         let mut task_server = unsafe { Server::from_raw_fd(task_server_fd) };
         task_server = task_server.set_domain_unix();
         Ok(Self {
             receiver: Some(receiver),
             handler,
             task_server: Some(task_server),
+            task_server_proxy,
+            nri: Nri::new(id, &runtime_config),
             binary: containerd_binary.to_string(),
             address: address.to_string(),
             namespace: namespace.to_string(),
@@ -121,7 +151,7 @@ impl ServiceManager {
             warn!(sl!(), "failed to clean up runtime state, {}", e);
         }
 
-        let temp_dir = [KATA_PATH, sid].join("/");
+        let temp_dir = [KATA_PATH, &sandbox_dir_name(sid)].join("/");
         if fs::metadata(temp_dir.as_str()).is_ok() {
             // try to remove dir and skip the result
             if let Err(e) = fs::remove_dir_all(temp_dir) {
@@ -134,8 +164,10 @@ impl ServiceManager {
 
     fn registry_service(&mut self) -> Result<()> {
         if let Some(t) = self.task_server.take() {
-            let task_service = Arc::new(Box::new(TaskService::new(self.handler.clone()))
-                as Box<dyn shim_async::Task + Send + Sync>);
+            let task_service = Arc::new(Box::new(TaskService::new(
+                self.handler.clone(),
+                self.nri.take(),
+            )) as Box<dyn shim_async::Task + Send + Sync>);
             let t = t.register_service(shim_async::create_task(task_service));
             self.task_server = Some(t);
         }
@@ -146,10 +178,16 @@ impl ServiceManager {
         if let Some(t) = self.task_server.as_mut() {
             t.start().await.context("task server start")?;
         }
+        if let Some(p) = self.task_server_proxy.as_mut() {
+            p.start();
+        }
         Ok(())
     }
 
     async fn stop_service(&mut self) -> Result<()> {
+        if let Some(p) = self.task_server_proxy.as_mut() {
+            p.stop();
+        }
         if let Some(t) = self.task_server.as_mut() {
             t.stop_listen().await;
         }
@@ -192,3 +230,20 @@ impl ServiceManager {
         Ok(())
     }
 }
+
+/// Load the runtime configuration to set up the ttrpc server with. The server is created before
+/// the sandbox, so only the configuration file given by KATA_CONF_FILE or the default one is
+/// used, the ones given by the annotations or the create task options aren't known yet.
+fn load_runtime_config() -> Runtime {
+    let config_path = std::env::var(KATA_CONF_FILE).unwrap_or_default();
+    match TomlConfig::load_from_file(&config_path) {
+        Ok((config, _)) => config.runtime,
+        Err(e) => {
+            warn!(
+                sl!(),
+                "failed to load config {:?}, use default: {:?}", config_path, e
+            );
+            Runtime::default()
+        }
+    }
+}
@@ -0,0 +1,387 @@
+// Copyright (c) 2023 Kata Containers community
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+// The NRI (Node Resource Interface) hook points of the shim, around the
+// creation of the sandbox and of its containers. The plugins are the
+// executables of the plugin directory, run in the order of their names, e.g.
+// `10-devices` before `20-logger`, as the NRI plugins are. A plugin gets the
+// event and the spec of the container as JSON on its stdin. Before the
+// creation, it may reply on its stdout with the adjustment of the container,
+// mounts, env vars, annotations and resources, applied to the bundle before
+// the next plugin runs. After the creation, its reply is ignored.
+
+use std::{
+    collections::HashMap,
+    os::unix::fs::PermissionsExt,
+    path::{Path, PathBuf},
+    process::Stdio,
+    time::Duration,
+};
+
+use anyhow::{anyhow, Context, Result};
+use common::types::ContainerConfig;
+use kata_types::config::Runtime;
+use serde::{Deserialize, Serialize};
+use tokio::{io::AsyncWriteExt, process::Command};
+
+// the time a plugin has to handle an event, as the default of NRI
+const PLUGIN_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// The events of the lifecycle of the containers sent to the plugins.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum NriEvent {
+    RunPodSandbox,
+    PostRunPodSandbox,
+    CreateContainer,
+    PostCreateContainer,
+}
+
+impl NriEvent {
+    fn as_str(&self) -> &'static str {
+        match self {
+            NriEvent::RunPodSandbox => "RunPodSandbox",
+            NriEvent::PostRunPodSandbox => "PostRunPodSandbox",
+            NriEvent::CreateContainer => "CreateContainer",
+            NriEvent::PostCreateContainer => "PostCreateContainer",
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct PluginRequest<'a> {
+    event: &'a str,
+    sandbox_id: &'a str,
+    container_id: &'a str,
+    spec: &'a oci::Spec,
+}
+
+/// The adjustment of a container by a plugin.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct ContainerAdjustment {
+    /// Mounts added, replacing the ones of the same destination
+    mounts: Vec<oci::Mount>,
+    /// Env vars as `KEY=VALUE`, replacing the ones of the same key
+    env: Vec<String>,
+    annotations: HashMap<String, String>,
+    /// Resources replacing the ones of the container, the devices are added
+    resources: Option<oci::LinuxResources>,
+}
+
+impl ContainerAdjustment {
+    fn is_empty(&self) -> bool {
+        self.mounts.is_empty()
+            && self.env.is_empty()
+            && self.annotations.is_empty()
+            && self.resources.is_none()
+    }
+
+    fn apply(self, spec: &mut oci::Spec) {
+        for mount in self.mounts {
+            spec.mounts.retain(|m| m.destination != mount.destination);
+            spec.mounts.push(mount);
+        }
+
+        if let Some(process) = spec.process.as_mut() {
+            for var in self.env {
+                let key = var.split('=').next().unwrap_or_default();
+                process
+                    .env
+                    .retain(|e| e.split('=').next().unwrap_or_default() != key);
+                process.env.push(var);
+            }
+        }
+
+        spec.annotations.extend(self.annotations);
+
+        if let Some(adjusted) = self.resources {
+            let resources = spec
+                .linux
+                .get_or_insert_with(Default::default)
+                .resources
+                .get_or_insert_with(Default::default);
+            resources.devices.extend(adjusted.devices);
+            if adjusted.memory.is_some() {
+                resources.memory = adjusted.memory;
+            }
+            if adjusted.cpu.is_some() {
+                resources.cpu = adjusted.cpu;
+            }
+            if adjusted.pids.is_some() {
+                resources.pids = adjusted.pids;
+            }
+            if adjusted.block_io.is_some() {
+                resources.block_io = adjusted.block_io;
+            }
+            if !adjusted.hugepage_limits.is_empty() {
+                resources.hugepage_limits = adjusted.hugepage_limits;
+            }
+        }
+    }
+}
+
+pub(crate) struct Nri {
+    sandbox_id: String,
+    plugin_dir: PathBuf,
+}
+
+impl Nri {
+    /// Get the NRI hooks of the sandbox, `None` if no plugin directory is configured.
+    pub(crate) fn new(sandbox_id: &str, config: &Runtime) -> Option<Self> {
+        if config.nri_plugin_dir.is_empty() {
+            return None;
+        }
+        Some(Self {
+            sandbox_id: sandbox_id.to_string(),
+            plugin_dir: PathBuf::from(&config.nri_plugin_dir),
+        })
+    }
+
+    fn event(&self, container_id: &str, post: bool) -> NriEvent {
+        match (container_id == self.sandbox_id, post) {
+            (true, false) => NriEvent::RunPodSandbox,
+            (true, true) => NriEvent::PostRunPodSandbox,
+            (false, false) => NriEvent::CreateContainer,
+            (false, true) => NriEvent::PostCreateContainer,
+        }
+    }
+
+    /// Run the plugins before the creation of the container, the adjustments
+    /// of the plugins are saved to the spec of the bundle. A failed plugin
+    /// fails the creation.
+    pub(crate) async fn before_create(&self, config: &ContainerConfig) -> Result<()> {
+        let event = self.event(&config.container_id, false);
+        let spec_path = spec_path(config);
+        let mut spec = oci::Spec::load(&spec_path).context("load spec")?;
+
+        let mut adjusted = false;
+        for plugin in self.plugins()? {
+            let reply = self
+                .run_plugin(&plugin, event, &config.container_id, &spec)
+                .await
+                .with_context(|| format!("nri plugin {}", plugin.display()))?;
+            let adjustment: ContainerAdjustment = if reply.iter().all(u8::is_ascii_whitespace) {
+                ContainerAdjustment::default()
+            } else {
+                serde_json::from_slice(&reply)
+                    .with_context(|| format!("parse reply of {}", plugin.display()))?
+            };
+            if !adjustment.is_empty() {
+                info!(
+                    sl!(),
+                    "nri plugin {} adjusts container {}: {:?}",
+                    plugin.display(),
+                    config.container_id,
+                    adjustment
+                );
+                adjustment.apply(&mut spec);
+                adjusted = true;
+            }
+        }
+
+        if adjusted {
+            spec.save(&spec_path).context("save adjusted spec")?;
+        }
+        Ok(())
+    }
+
+    /// Notify the plugins of the creation of the container, their failures
+    /// are only logged as the container is created.
+    pub(crate) async fn after_create(&self, config: &ContainerConfig) {
+        let event = self.event(&config.container_id, true);
+        let result = async {
+            let spec = oci::Spec::load(&spec_path(config)).context("load spec")?;
+            for plugin in self.plugins()? {
+                if let Err(e) = self
+                    .run_plugin(&plugin, event, &config.container_id, &spec)
+                    .await
+                {
+                    warn!(
+                        sl!(),
+                        "nri plugin {} failed on {}: {:?}",
+                        plugin.display(),
+                        event.as_str(),
+                        e
+                    );
+                }
+            }
+            Ok::<(), anyhow::Error>(())
+        }
+        .await;
+        if let Err(e) = result {
+            warn!(sl!(), "failed to run nri plugins: {:?}", e);
+        }
+    }
+
+    // plugins returns the executables of the plugin directory, by name.
+    fn plugins(&self) -> Result<Vec<PathBuf>> {
+        let entries = match std::fs::read_dir(&self.plugin_dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(vec![]),
+            Err(e) => return Err(e).with_context(|| format!("read {}", self.plugin_dir.display())),
+        };
+
+        let mut plugins = vec![];
+        for entry in entries {
+            let path = entry?.path();
+            let is_executable = std::fs::metadata(&path)
+                .map(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+                .unwrap_or(false);
+            if is_executable {
+                plugins.push(path);
+            }
+        }
+        plugins.sort();
+        Ok(plugins)
+    }
+
+    async fn run_plugin(
+        &self,
+        plugin: &Path,
+        event: NriEvent,
+        container_id: &str,
+        spec: &oci::Spec,
+    ) -> Result<Vec<u8>> {
+        let request = serde_json::to_vec(&PluginRequest {
+            event: event.as_str(),
+            sandbox_id: &self.sandbox_id,
+            container_id,
+            spec,
+        })?;
+
+        let mut child = Command::new(plugin)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .context("spawn")?;
+        let mut stdin = child.stdin.take().context("no stdin")?;
+
+        let run = async {
+            stdin.write_all(&request).await.context("write request")?;
+            drop(stdin);
+            child.wait_with_output().await.context("wait")
+        };
+        let output = tokio::time::timeout(PLUGIN_TIMEOUT, run)
+            .await
+            .map_err(|_| anyhow!("timed out after {:?}", PLUGIN_TIMEOUT))??;
+        if !output.status.success() {
+            return Err(anyhow!(
+                "{}: {}",
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+
+        Ok(output.stdout)
+    }
+}
+
+fn spec_path(config: &ContainerConfig) -> String {
+    format!("{}/{}", config.bundle, oci::OCI_SPEC_CONFIG_FILE_NAME)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn write_plugin(dir: &Path, name: &str, script: &str) {
+        let path = dir.join(name);
+        fs::write(&path, format!("#!/bin/sh\n{}\n", script)).unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+    }
+
+    #[test]
+    fn test_apply_adjustment() {
+        let mut spec = oci::Spec {
+            process: Some(oci::Process {
+                env: vec!["PATH=/bin".to_string(), "FOO=1".to_string()],
+                ..Default::default()
+            }),
+            mounts: vec![oci::Mount {
+                destination: "/data".to_string(),
+                source: "/old".to_string(),
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+
+        let adjustment: ContainerAdjustment = serde_json::from_str(
+            r#"{
+                "mounts": [{"destination": "/data", "source": "/new", "type": "bind"}],
+                "env": ["FOO=2", "BAR=3"],
+                "annotations": {"nri.io/adjusted": "true"},
+                "resources": {"memory": {"limit": 1073741824}}
+            }"#,
+        )
+        .unwrap();
+        adjustment.apply(&mut spec);
+
+        assert_eq!(spec.mounts.len(), 1);
+        assert_eq!(spec.mounts[0].source, "/new");
+        assert_eq!(
+            spec.process.unwrap().env,
+            vec!["PATH=/bin", "FOO=2", "BAR=3"]
+        );
+        assert_eq!(spec.annotations["nri.io/adjusted"], "true");
+        let resources = spec.linux.unwrap().resources.unwrap();
+        assert_eq!(resources.memory.unwrap().limit, Some(1073741824));
+    }
+
+    #[tokio::test]
+    async fn test_before_create() {
+        let plugin_dir = tempfile::tempdir().unwrap();
+        let bundle = tempfile::tempdir().unwrap();
+
+        // run in the order of the names, the second plugin overrides the first
+        write_plugin(plugin_dir.path(), "20-env", r#"echo '{"env": ["FOO=2"]}'"#);
+        write_plugin(plugin_dir.path(), "10-env", r#"echo '{"env": ["FOO=1"]}'"#);
+        // no adjustment
+        write_plugin(plugin_dir.path(), "30-logger", "cat > /dev/null");
+        // not executable
+        fs::write(plugin_dir.path().join("40-disabled"), "").unwrap();
+
+        let spec = oci::Spec {
+            process: Some(oci::Process::default()),
+            ..Default::default()
+        };
+        let config = ContainerConfig {
+            container_id: "cid".to_string(),
+            bundle: bundle.path().to_str().unwrap().to_string(),
+            rootfs_mounts: vec![],
+            terminal: false,
+            options: None,
+            stdin: None,
+            stdout: None,
+            stderr: None,
+        };
+        spec.save(&spec_path(&config)).unwrap();
+
+        let runtime = Runtime {
+            nri_plugin_dir: plugin_dir.path().to_str().unwrap().to_string(),
+            ..Default::default()
+        };
+        let nri = Nri::new("sid", &runtime).unwrap();
+        assert_eq!(nri.event("sid", false), NriEvent::RunPodSandbox);
+        assert_eq!(nri.event("cid", true), NriEvent::PostCreateContainer);
+        assert_eq!(nri.plugins().unwrap().len(), 3);
+
+        nri.before_create(&config).await.unwrap();
+        let spec = oci::Spec::load(&spec_path(&config)).unwrap();
+        assert_eq!(spec.process.unwrap().env, vec!["FOO=2"]);
+
+        // a failed plugin fails the creation
+        write_plugin(plugin_dir.path(), "50-deny", "echo denied >&2; exit 1");
+        assert!(nri.before_create(&config).await.is_err());
+        nri.after_create(&config).await;
+    }
+
+    #[test]
+    fn test_nri_disabled() {
+        assert!(Nri::new("sid", &Runtime::default()).is_none());
+    }
+}
@@ -5,58 +5,145 @@
 //
 
 use std::{
+    any::Any,
     convert::{TryFrom, TryInto},
+    panic::AssertUnwindSafe,
     sync::Arc,
 };
 
 use async_trait::async_trait;
-use common::types::{Request, Response};
+use common::{
+    error::Error,
+    types::{Request, Response},
+};
 use containerd_shim_protos::{api, shim_async};
+use futures::FutureExt;
 use ttrpc::{self, r#async::TtrpcContext};
 
-use runtimes::RuntimeHandlerManager;
+use runtimes::{PendingRequest, RuntimeHandlerManager};
+
+use crate::nri::Nri;
+
+tokio::task_local! {
+    // set while a request is handled, the panics in handling it are caught
+    static HANDLING_REQUEST: ();
+}
+
+/// Whether the current task is handling a request, whose panics are caught
+/// and turned into errors instead of aborting the shim.
+pub fn is_handling_request() -> bool {
+    HANDLING_REQUEST.try_with(|_| ()).is_ok()
+}
 
 pub(crate) struct TaskService {
     handler: Arc<RuntimeHandlerManager>,
+    nri: Option<Nri>,
 }
 
 impl TaskService {
-    pub(crate) fn new(handler: Arc<RuntimeHandlerManager>) -> Self {
-        Self { handler }
+    pub(crate) fn new(handler: Arc<RuntimeHandlerManager>, nri: Option<Nri>) -> Self {
+        Self { handler, nri }
     }
 
     async fn handler_message<TtrpcReq, TtrpcResp>(
         &self,
+        method: &str,
         ctx: &TtrpcContext,
         req: TtrpcReq,
     ) -> ttrpc::Result<TtrpcResp>
     where
         Request: TryFrom<TtrpcReq>,
-        <Request as TryFrom<TtrpcReq>>::Error: std::fmt::Debug,
+        <Request as TryFrom<TtrpcReq>>::Error: Into<anyhow::Error>,
         TtrpcResp: TryFrom<Response>,
         <TtrpcResp as TryFrom<Response>>::Error: std::fmt::Debug,
     {
-        let r = req.try_into().map_err(|err| {
-            ttrpc::Error::Others(format!("failed to translate from shim {:?}", err))
+        let _pending = PendingRequest::new(method);
+        let r: Request = req.try_into().map_err(|err| {
+            let err = err.into();
+            typed_ttrpc_error(&err).unwrap_or_else(|| {
+                ttrpc::Error::Others(format!("failed to translate from shim {:?}", err))
+            })
         })?;
-        let logger = sl!().new(o!("stream id" =>  ctx.mh.stream_id));
+        // tag all the logs of handling the request with the container id
+        let log_context = match r.container_id() {
+            Some(cid) => slog_scope::logger().new(o!("container_id" => cid.to_string())),
+            None => slog_scope::logger(),
+        };
+        let logger = log_context.new(o!(
+            "subsystem" => "service",
+            "stream id" => ctx.mh.stream_id,
+        ));
         debug!(logger, "====> task service {:?}", &r);
-        let resp =
-            self.handler.handler_message(r).await.map_err(|err| {
-                ttrpc::Error::Others(format!("failed to handler message {:?}", err))
-            })?;
+
+        // the NRI plugins adjust the containers before they're created
+        let nri_create = match (&self.nri, &r) {
+            (Some(nri), Request::CreateContainer(config)) => Some((nri, config.clone())),
+            _ => None,
+        };
+        if let Some((nri, config)) = &nri_create {
+            nri.before_create(config).await.map_err(into_ttrpc_error)?;
+        }
+
+        let handling =
+            logging::context::with_log_context(log_context, self.handler.handler_message(r));
+        let resp = match HANDLING_REQUEST
+            .scope((), AssertUnwindSafe(handling).catch_unwind())
+            .await
+        {
+            Ok(resp) => resp.map_err(into_ttrpc_error)?,
+            Err(panic) => {
+                let reason = format!("panic in handling request: {}", panic_cause(&*panic));
+                error!(logger, "{}", reason);
+                // the state of the sandbox is unknown after the panic
+                self.handler.fail_sandbox(&reason).await;
+                return Err(ttrpc::error::get_rpc_status(ttrpc::Code::INTERNAL, reason));
+            }
+        };
+        if let Some((nri, config)) = &nri_create {
+            nri.after_create(config).await;
+        }
         debug!(logger, "<==== task service {:?}", &resp);
         resp.try_into()
             .map_err(|err| ttrpc::Error::Others(format!("failed to translate to shim {:?}", err)))
     }
 }
 
+// Typed errors are returned with a status code, so that the caller could tell them from the
+// internal errors and show the reason to users.
+fn into_ttrpc_error(err: anyhow::Error) -> ttrpc::Error {
+    typed_ttrpc_error(&err)
+        .unwrap_or_else(|| ttrpc::Error::Others(format!("failed to handler message {:?}", err)))
+}
+
+fn typed_ttrpc_error(err: &anyhow::Error) -> Option<ttrpc::Error> {
+    let (code, e) = match err.chain().find_map(|e| e.downcast_ref::<Error>())? {
+        e @ Error::ImageVerificationFailed(..) | e @ Error::InvalidState(..) => {
+            (ttrpc::Code::FAILED_PRECONDITION, e)
+        }
+        e @ Error::DeadlineExceeded(..) | e @ Error::Timeout(..) => {
+            (ttrpc::Code::DEADLINE_EXCEEDED, e)
+        }
+        e @ Error::ResourceExhausted(..) => (ttrpc::Code::RESOURCE_EXHAUSTED, e),
+        e @ Error::InvalidArgument(..) => (ttrpc::Code::INVALID_ARGUMENT, e),
+        _ => return None,
+    };
+    Some(ttrpc::error::get_rpc_status(code, e.to_string()))
+}
+
+fn panic_cause(panic: &(dyn Any + Send)) -> &str {
+    panic
+        .downcast_ref::<String>()
+        .map(|s| s.as_str())
+        .or_else(|| panic.downcast_ref::<&str>().copied())
+        .unwrap_or("<cause unknown>")
+}
+
 macro_rules! impl_service {
     ($($name: tt | $req: ty | $resp: ty),*) => {
         #[async_trait]
         impl shim_async::Task for TaskService {
             $(async fn $name(&self, ctx: &TtrpcContext, req: $req) -> ttrpc::Result<$resp> {
-                self.handler_message(ctx, req).await
+                self.handler_message(stringify!($name), ctx, req).await
             })*
         }
     };
@@ -70,6 +157,7 @@ impl_service!(
     pids | api::PidsRequest | api::PidsResponse,
     pause | api::PauseRequest | api::Empty,
     resume | api::ResumeRequest | api::Empty,
+    checkpoint | api::CheckpointTaskRequest | api::Empty,
     kill | api::KillRequest | api::Empty,
     exec | api::ExecProcessRequest | api::Empty,
     resize_pty | api::ResizePtyRequest | api::Empty,
@@ -0,0 +1,388 @@
+// Copyright (c) 2023 Kata Containers community
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+use std::{
+    cmp::min,
+    fs,
+    os::unix::{
+        fs::PermissionsExt,
+        io::{FromRawFd, IntoRawFd, RawFd},
+        net::UnixListener as StdUnixListener,
+    },
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+
+use anyhow::{Context, Result};
+use kata_sys_util::naming::sandbox_dir_name;
+use kata_types::config::{Runtime, KATA_PATH};
+use runtimes::peer_cred::PeerCredAuth;
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{UnixListener, UnixStream},
+    sync::Semaphore,
+    task::JoinHandle,
+};
+
+// The ttrpc message header: length(u32), stream id(u32), type(u8) and flags(u8).
+const MESSAGE_HEADER_LENGTH: usize = 10;
+const MESSAGE_TYPE_REQUEST: u8 = 0x1;
+const MESSAGE_TYPE_RESPONSE: u8 = 0x2;
+
+const BACKEND_SOCKET_NAME: &str = "shim-ttrpc.sock";
+const RELAY_BUFFER_SIZE: usize = 8192;
+const ACCEPT_RETRY_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Limits of the connections to the ttrpc server of the shim.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub(crate) struct ConnectionLimits {
+    /// Maximum number of concurrent connections, 0 for no limit.
+    pub max_connections: usize,
+    /// The idle connections are closed after the timeout, if any.
+    pub idle_timeout: Option<Duration>,
+}
+
+impl ConnectionLimits {
+    pub(crate) fn new(runtime: &Runtime) -> Self {
+        Self {
+            max_connections: runtime.ttrpc_max_connections as usize,
+            idle_timeout: match runtime.ttrpc_idle_timeout_secs {
+                0 => None,
+                secs => Some(Duration::from_secs(secs)),
+            },
+        }
+    }
+
+    pub(crate) fn is_enabled(&self) -> bool {
+        self.max_connections > 0 || self.idle_timeout.is_some()
+    }
+}
+
+/// Path of the socket which the ttrpc server listens on behind the proxy, it's in the sandbox
+/// directory so it's removed with the sandbox.
+pub(crate) fn backend_path(sid: &str) -> PathBuf {
+    Path::new(KATA_PATH)
+        .join(sandbox_dir_name(sid))
+        .join(BACKEND_SOCKET_NAME)
+}
+
+/// Proxy in front of the ttrpc server of the shim, which enforces the connection limits and
+/// authorizes the peers.
+///
+/// The ttrpc server doesn't limit or check the connections it accepts, so the proxy takes over
+/// the listening socket of the shim, and relays the allowed connections within the limits to
+/// the ttrpc server listening on a private socket.
+pub(crate) struct TtrpcProxy {
+    listener: Arc<UnixListener>,
+    backend: PathBuf,
+    limits: ConnectionLimits,
+    peer_cred_auth: Option<PeerCredAuth>,
+    permits: Arc<Semaphore>,
+    accept_task: Option<JoinHandle<()>>,
+}
+
+impl TtrpcProxy {
+    /// Take over the listening socket `fd` of the shim, the fd of the private socket to create
+    /// the ttrpc server with is returned as well.
+    pub(crate) fn new(
+        sid: &str,
+        fd: RawFd,
+        limits: ConnectionLimits,
+        peer_cred_auth: Option<PeerCredAuth>,
+    ) -> Result<(Self, RawFd)> {
+        let listener = unsafe { StdUnixListener::from_raw_fd(fd) };
+        listener
+            .set_nonblocking(true)
+            .context("set listener nonblocking")?;
+        let listener = UnixListener::from_std(listener).context("new listener")?;
+
+        let backend = backend_path(sid);
+        let dir = backend.parent().context("backend socket dir")?;
+        fs::create_dir_all(dir).with_context(|| format!("create dir {:?}", dir))?;
+        if backend.exists() {
+            fs::remove_file(&backend).with_context(|| format!("remove {:?}", backend))?;
+        }
+        let backend_listener =
+            StdUnixListener::bind(&backend).with_context(|| format!("bind {:?}", backend))?;
+        // only the shim itself connects to the backend socket
+        fs::set_permissions(&backend, fs::Permissions::from_mode(0o600))
+            .with_context(|| format!("set permissions of {:?}", backend))?;
+
+        let permits = match limits.max_connections {
+            0 => Semaphore::MAX_PERMITS,
+            n => n,
+        };
+        info!(sl!(), "ttrpc connection limits {:?}", limits);
+
+        Ok((
+            Self {
+                listener: Arc::new(listener),
+                backend,
+                limits,
+                peer_cred_auth,
+                permits: Arc::new(Semaphore::new(permits)),
+                accept_task: None,
+            },
+            backend_listener.into_raw_fd(),
+        ))
+    }
+
+    /// Start accepting the connections, the ttrpc server should be started before.
+    pub(crate) fn start(&mut self) {
+        if self.accept_task.is_some() {
+            return;
+        }
+
+        let listener = self.listener.clone();
+        let backend = self.backend.clone();
+        let limits = self.limits;
+        let peer_cred_auth = self.peer_cred_auth.clone();
+        let permits = self.permits.clone();
+        self.accept_task = Some(tokio::spawn(async move {
+            accept_loop(listener, backend, limits, peer_cred_auth, permits).await
+        }));
+    }
+
+    /// Stop accepting the connections, the established ones are kept.
+    pub(crate) fn stop(&mut self) {
+        if let Some(task) = self.accept_task.take() {
+            task.abort();
+        }
+    }
+}
+
+impl Drop for TtrpcProxy {
+    fn drop(&mut self) {
+        self.stop();
+        let _ = fs::remove_file(&self.backend);
+    }
+}
+
+async fn accept_loop(
+    listener: Arc<UnixListener>,
+    backend: PathBuf,
+    limits: ConnectionLimits,
+    peer_cred_auth: Option<PeerCredAuth>,
+    permits: Arc<Semaphore>,
+) {
+    loop {
+        let client = match listener.accept().await {
+            Ok((client, _)) => client,
+            Err(e) => {
+                // e.g. EMFILE, don't spin on it
+                warn!(sl!(), "failed to accept ttrpc connection: {:?}", e);
+                tokio::time::sleep(ACCEPT_RETRY_INTERVAL).await;
+                continue;
+            }
+        };
+
+        if let Some(auth) = peer_cred_auth.as_ref() {
+            if let Err(e) = auth.authorize(&client) {
+                warn!(sl!(), "reject ttrpc connection: {:?}", e);
+                continue;
+            }
+        }
+
+        let permit = match permits.clone().try_acquire_owned() {
+            Ok(permit) => permit,
+            Err(_) => {
+                warn!(
+                    sl!(),
+                    "reject ttrpc connection, the limit {} is reached", limits.max_connections
+                );
+                drop(client);
+                continue;
+            }
+        };
+
+        let backend = backend.clone();
+        tokio::spawn(async move {
+            let result = match UnixStream::connect(&backend).await {
+                Ok(server) => relay(client, server, limits.idle_timeout).await,
+                Err(e) => Err(e).with_context(|| format!("connect {:?}", backend)),
+            };
+            if let Err(e) = result {
+                warn!(sl!(), "ttrpc connection closed: {:?}", e);
+            }
+            drop(permit);
+        });
+    }
+}
+
+/// Counter of the ttrpc messages of a type in a byte stream.
+#[derive(Debug, Default)]
+struct MessageCounter {
+    header: Vec<u8>,
+    remaining: usize,
+}
+
+impl MessageCounter {
+    /// Feed the next bytes of the stream, and return the number of the messages of `msg_type`
+    /// started in them.
+    fn feed(&mut self, mut buf: &[u8], msg_type: u8) -> usize {
+        let mut count = 0;
+        while !buf.is_empty() {
+            if self.remaining > 0 {
+                let n = min(self.remaining, buf.len());
+                self.remaining -= n;
+                buf = &buf[n..];
+                continue;
+            }
+
+            let n = min(MESSAGE_HEADER_LENGTH - self.header.len(), buf.len());
+            self.header.extend_from_slice(&buf[..n]);
+            buf = &buf[n..];
+            if self.header.len() == MESSAGE_HEADER_LENGTH {
+                let mut length = [0u8; 4];
+                length.copy_from_slice(&self.header[..4]);
+                self.remaining = u32::from_be_bytes(length) as usize;
+                if self.header[8] == msg_type {
+                    count += 1;
+                }
+                self.header.clear();
+            }
+        }
+        count
+    }
+}
+
+/// Relay the bytes between the `client` and the ttrpc `server`, until either side closes, or
+/// the connection is idle for `idle_timeout`. A connection with requests in flight, e.g. a
+/// `Wait` on a running process, isn't idle.
+async fn relay(
+    client: UnixStream,
+    server: UnixStream,
+    idle_timeout: Option<Duration>,
+) -> Result<()> {
+    let (mut client_rx, mut client_tx) = client.into_split();
+    let (mut server_rx, mut server_tx) = server.into_split();
+    let mut client_buf = vec![0u8; RELAY_BUFFER_SIZE];
+    let mut server_buf = vec![0u8; RELAY_BUFFER_SIZE];
+    let mut requests = MessageCounter::default();
+    let mut responses = MessageCounter::default();
+    let mut in_flight = 0usize;
+
+    loop {
+        let timeout = if in_flight == 0 { idle_timeout } else { None };
+        let idle = async move {
+            match timeout {
+                Some(timeout) => tokio::time::sleep(timeout).await,
+                None => futures::future::pending().await,
+            }
+        };
+
+        tokio::select! {
+            n = client_rx.read(&mut client_buf) => {
+                let n = n.context("read client")?;
+                if n == 0 {
+                    return Ok(());
+                }
+                in_flight += requests.feed(&client_buf[..n], MESSAGE_TYPE_REQUEST);
+                server_tx.write_all(&client_buf[..n]).await.context("write server")?;
+            }
+            n = server_rx.read(&mut server_buf) => {
+                let n = n.context("read server")?;
+                if n == 0 {
+                    return Ok(());
+                }
+                let done = responses.feed(&server_buf[..n], MESSAGE_TYPE_RESPONSE);
+                in_flight = in_flight.saturating_sub(done);
+                client_tx.write_all(&server_buf[..n]).await.context("write client")?;
+            }
+            _ = idle => {
+                info!(sl!(), "close idle ttrpc connection");
+                return Ok(());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(msg_type: u8, payload: &[u8]) -> Vec<u8> {
+        let mut msg = (payload.len() as u32).to_be_bytes().to_vec();
+        msg.extend_from_slice(&1u32.to_be_bytes());
+        msg.extend_from_slice(&[msg_type, 0]);
+        msg.extend_from_slice(payload);
+        msg
+    }
+
+    #[test]
+    fn test_connection_limits() {
+        assert!(!ConnectionLimits::new(&Runtime::default()).is_enabled());
+
+        let limits = ConnectionLimits::new(&Runtime {
+            ttrpc_max_connections: 8,
+            ttrpc_idle_timeout_secs: 60,
+            ..Default::default()
+        });
+        assert!(limits.is_enabled());
+        assert_eq!(limits.max_connections, 8);
+        assert_eq!(limits.idle_timeout, Some(Duration::from_secs(60)));
+    }
+
+    #[test]
+    fn test_message_counter() {
+        let mut stream = message(MESSAGE_TYPE_REQUEST, b"request");
+        stream.extend(message(MESSAGE_TYPE_RESPONSE, &[MESSAGE_TYPE_REQUEST; 20]));
+        stream.extend(message(MESSAGE_TYPE_REQUEST, b""));
+
+        let mut counter = MessageCounter::default();
+        assert_eq!(counter.feed(&stream, MESSAGE_TYPE_REQUEST), 2);
+
+        // byte by byte, the payload isn't taken as headers
+        let mut counter = MessageCounter::default();
+        let count: usize = stream
+            .iter()
+            .map(|b| counter.feed(&[*b], MESSAGE_TYPE_REQUEST))
+            .sum();
+        assert_eq!(count, 2);
+    }
+
+    #[tokio::test]
+    async fn test_relay_idle_timeout() {
+        let idle_timeout = Some(Duration::from_millis(50));
+
+        // idle connection is closed
+        let (mut client, proxy_client) = UnixStream::pair().unwrap();
+        let (proxy_server, _server) = UnixStream::pair().unwrap();
+        tokio::spawn(relay(proxy_client, proxy_server, idle_timeout));
+        let mut buf = [0u8; 1];
+        let n = tokio::time::timeout(Duration::from_secs(5), client.read(&mut buf))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(n, 0);
+
+        // connection with a request in flight is kept
+        let (mut client, proxy_client) = UnixStream::pair().unwrap();
+        let (proxy_server, mut server) = UnixStream::pair().unwrap();
+        tokio::spawn(relay(proxy_client, proxy_server, idle_timeout));
+        let request = message(MESSAGE_TYPE_REQUEST, b"wait");
+        client.write_all(&request).await.unwrap();
+        let mut received = vec![0u8; request.len()];
+        server.read_exact(&mut received).await.unwrap();
+        assert_eq!(received, request);
+        assert!(
+            tokio::time::timeout(Duration::from_millis(200), client.read(&mut buf))
+                .await
+                .is_err()
+        );
+
+        let response = message(MESSAGE_TYPE_RESPONSE, b"exited");
+        server.write_all(&response).await.unwrap();
+        let mut received = vec![0u8; response.len()];
+        client.read_exact(&mut received).await.unwrap();
+        assert_eq!(received, response);
+        let n = tokio::time::timeout(Duration::from_secs(5), client.read(&mut buf))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(n, 0);
+    }
+}
@@ -0,0 +1,84 @@
+// Copyright (c) 2023 Kata Containers community
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+// The task API served by the service manager, as containerd calls it. No sandbox is created,
+// the requests fail before a VM would be needed.
+
+use std::os::unix::{io::IntoRawFd, net::UnixListener};
+
+use containerd_shim_protos::{api, shim_async::TaskClient};
+use service::ServiceManager;
+use ttrpc::{asynchronous::Client, context};
+
+async fn start_service(dir: &std::path::Path) -> TaskClient {
+    let path = dir.join("task.sock");
+    let listener = UnixListener::bind(&path).unwrap();
+    let manager = ServiceManager::new(
+        "sandbox",
+        "containerd",
+        "/run/containerd/containerd.sock",
+        "default",
+        listener.into_raw_fd(),
+    )
+    .await
+    .unwrap();
+    tokio::spawn(manager.run());
+
+    let client = Client::connect(&format!("unix://{}", path.display())).unwrap();
+    TaskClient::new(client)
+}
+
+#[tokio::test]
+async fn test_request_without_sandbox() {
+    let dir = tempfile::tempdir().unwrap();
+    let client = start_service(dir.path()).await;
+
+    // there is no runtime instance before the sandbox is created
+    let err = client
+        .state(
+            context::with_timeout(0),
+            &api::StateRequest {
+                id: "container".to_string(),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap_err();
+    assert!(format!("{:?}", err).contains("runtime instance"));
+}
+
+#[tokio::test]
+async fn test_create_without_spec() {
+    let dir = tempfile::tempdir().unwrap();
+    let bundle = dir.path().join("bundle");
+    std::fs::create_dir(&bundle).unwrap();
+    let client = start_service(dir.path()).await;
+
+    // the bundle has no config.json
+    let err = client
+        .create(
+            context::with_timeout(0),
+            &api::CreateTaskRequest {
+                id: "container".to_string(),
+                bundle: bundle.display().to_string(),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap_err();
+    assert!(format!("{:?}", err).contains("load spec"));
+
+    // the service is still serving after the failure
+    assert!(client
+        .state(
+            context::with_timeout(0),
+            &api::StateRequest {
+                id: "container".to_string(),
+                ..Default::default()
+            },
+        )
+        .await
+        .is_err());
+}
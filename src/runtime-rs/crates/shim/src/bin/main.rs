@@ -120,9 +120,9 @@ fn get_tokio_runtime() -> Result<tokio::runtime::Runtime> {
         .parse()
         .unwrap_or(DEFAULT_TOKIO_RUNTIME_WORKER_THREADS);
 
-    let rt = tokio::runtime::Builder::new_multi_thread()
-        .worker_threads(worker_threads)
-        .enable_all()
+    let mut builder = tokio::runtime::Builder::new_multi_thread();
+    builder.worker_threads(worker_threads).enable_all();
+    let rt = runtimes::instrument_runtime(&mut builder, worker_threads)
         .build()
         .context("prepare tokio runtime")?;
     Ok(rt)
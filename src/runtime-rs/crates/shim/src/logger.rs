@@ -26,6 +26,8 @@ pub(crate) fn set_logger(path: &str, sid: &str, is_debug: bool) -> Result<slog_a
     };
 
     let (logger, async_guard) = logging::create_logger("kata-runtime", sid, level, fifo);
+    // each shim serves a single sandbox, tag all the logs with the sandbox id
+    let logger = logger.new(o!("sandbox_id" => sid.to_string()));
 
     // not reset global logger when drop
     slog_scope::set_global_logger(logger).cancel_reset();
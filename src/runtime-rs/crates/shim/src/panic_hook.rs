@@ -38,6 +38,12 @@ pub(crate) fn set_panic_hook() {
             "A panic occurred at {}:{}: {}\r\n{:?}", filename, line, cause, bt_data
         );
 
+        // the panics in handling requests are caught by the service, which
+        // fails the sandbox rather than aborting and orphaning the VM
+        if service::is_handling_request() {
+            return;
+        }
+
         // print panic log to dmesg
         // The panic log size is too large to /dev/kmsg, so write by line.
         if let Ok(mut file) = OpenOptions::new().write(true).open(KMESG_DEVICE) {
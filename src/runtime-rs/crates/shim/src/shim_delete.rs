@@ -27,11 +27,11 @@ impl ShimExecutor {
         let mut rsp = api::DeleteResponse::new();
         rsp.set_exit_status(128 + libc::SIGKILL as u32);
         let mut exited_time = protobuf::well_known_types::timestamp::Timestamp::new();
-        let seconds = std::time::SystemTime::now()
+        let now = std::time::SystemTime::now()
             .duration_since(std::time::UNIX_EPOCH)
-            .map_err(Error::SystemTime)?
-            .as_secs() as i64;
-        exited_time.seconds = seconds;
+            .map_err(Error::SystemTime)?;
+        exited_time.seconds = now.as_secs() as i64;
+        exited_time.nanos = now.subsec_nanos() as i32;
         rsp.set_exited_at(exited_time);
 
         let address = self
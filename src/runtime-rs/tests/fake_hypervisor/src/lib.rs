@@ -0,0 +1,256 @@
+// Copyright (c) 2023 Kata Containers community
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+//! A fake hypervisor, to test the runtime without a VM.
+//!
+//! No VM is started, the state of the VM and the devices are only kept in memory, and the
+//! agent socket is the one given, e.g. the address of the mock agent.
+
+use std::sync::Mutex;
+
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use hypervisor::{
+    device::DeviceType, hypervisor_persist::HypervisorState, Hypervisor, VcpuThreadIds,
+};
+use kata_types::capabilities::{Capabilities, CapabilityBits};
+use kata_types::config::hypervisor::Hypervisor as HypervisorConfig;
+
+/// Name of the fake hypervisor.
+pub const HYPERVISOR_FAKE: &str = "fake";
+
+/// State of the fake VM.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum VmState {
+    NotReady,
+    Prepared,
+    Running,
+    Paused,
+    Stopped,
+}
+
+#[derive(Debug)]
+struct FakeHypervisorInner {
+    id: String,
+    agent_socket: String,
+    config: HypervisorConfig,
+    state: VmState,
+    vcpus: u32,
+    memory_mb: u32,
+    devices: Vec<String>,
+}
+
+#[derive(Debug)]
+pub struct FakeHypervisor {
+    inner: Mutex<FakeHypervisorInner>,
+}
+
+impl FakeHypervisor {
+    /// A fake hypervisor whose agent is at `agent_socket`.
+    pub fn new(agent_socket: &str) -> Self {
+        Self::with_config(agent_socket, HypervisorConfig::default())
+    }
+
+    pub fn with_config(agent_socket: &str, config: HypervisorConfig) -> Self {
+        let vcpus = config.cpu_info.default_vcpus.max(1) as u32;
+        let memory_mb = config.memory_info.default_memory;
+        Self {
+            inner: Mutex::new(FakeHypervisorInner {
+                id: String::new(),
+                agent_socket: agent_socket.to_string(),
+                config,
+                state: VmState::NotReady,
+                vcpus,
+                memory_mb,
+                devices: vec![],
+            }),
+        }
+    }
+
+    pub fn state(&self) -> VmState {
+        self.inner.lock().unwrap().state
+    }
+
+    pub fn vcpus(&self) -> u32 {
+        self.inner.lock().unwrap().vcpus
+    }
+
+    pub fn memory_mb(&self) -> u32 {
+        self.inner.lock().unwrap().memory_mb
+    }
+
+    /// The devices attached to the VM, as they're displayed.
+    pub fn devices(&self) -> Vec<String> {
+        self.inner.lock().unwrap().devices.clone()
+    }
+
+    // move the VM to `to` if it's in one of the states `from`
+    fn transition(&self, from: &[VmState], to: VmState) -> Result<()> {
+        let mut inner = self.inner.lock().unwrap();
+        if !from.contains(&inner.state) {
+            return Err(anyhow!("vm is {:?}, can't be {:?}", inner.state, to));
+        }
+        inner.state = to;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Hypervisor for FakeHypervisor {
+    async fn prepare_vm(&self, id: &str, _netns: Option<String>) -> Result<()> {
+        self.transition(&[VmState::NotReady], VmState::Prepared)?;
+        self.inner.lock().unwrap().id = id.to_string();
+        Ok(())
+    }
+
+    async fn start_vm(&self, _timeout: i32) -> Result<()> {
+        self.transition(&[VmState::Prepared], VmState::Running)
+    }
+
+    async fn stop_vm(&self) -> Result<()> {
+        self.inner.lock().unwrap().state = VmState::Stopped;
+        Ok(())
+    }
+
+    async fn reboot_vm(&self) -> Result<()> {
+        self.transition(&[VmState::Running], VmState::Running)
+    }
+
+    async fn pause_vm(&self) -> Result<()> {
+        self.transition(&[VmState::Running], VmState::Paused)
+    }
+
+    async fn save_vm(&self) -> Result<()> {
+        Ok(())
+    }
+
+    async fn resume_vm(&self) -> Result<()> {
+        self.transition(&[VmState::Paused], VmState::Running)
+    }
+
+    async fn resize_vcpu(&self, old_vcpus: u32, new_vcpus: u32) -> Result<(u32, u32)> {
+        self.inner.lock().unwrap().vcpus = new_vcpus;
+        Ok((old_vcpus, new_vcpus))
+    }
+
+    async fn resize_memory(&self, new_mem_mb: u32) -> Result<u32> {
+        self.inner.lock().unwrap().memory_mb = new_mem_mb;
+        Ok(new_mem_mb)
+    }
+
+    async fn add_device(&self, device: DeviceType) -> Result<()> {
+        self.inner.lock().unwrap().devices.push(device.to_string());
+        Ok(())
+    }
+
+    async fn remove_device(&self, device: DeviceType) -> Result<()> {
+        let device = device.to_string();
+        let mut inner = self.inner.lock().unwrap();
+        let index = inner
+            .devices
+            .iter()
+            .position(|d| *d == device)
+            .ok_or_else(|| anyhow!("device {} isn't attached", device))?;
+        inner.devices.remove(index);
+        Ok(())
+    }
+
+    async fn get_agent_socket(&self) -> Result<String> {
+        Ok(self.inner.lock().unwrap().agent_socket.clone())
+    }
+
+    async fn disconnect(&self) {}
+
+    async fn hypervisor_config(&self) -> HypervisorConfig {
+        self.inner.lock().unwrap().config.clone()
+    }
+
+    async fn get_thread_ids(&self) -> Result<VcpuThreadIds> {
+        Ok(VcpuThreadIds::default())
+    }
+
+    // there are no VMM processes to be killed
+    async fn get_pids(&self) -> Result<Vec<u32>> {
+        Ok(vec![])
+    }
+
+    async fn get_vmm_master_tid(&self) -> Result<u32> {
+        Ok(std::process::id())
+    }
+
+    async fn get_ns_path(&self) -> Result<String> {
+        Ok(format!("/proc/{}/ns", std::process::id()))
+    }
+
+    async fn cleanup(&self) -> Result<()> {
+        self.inner.lock().unwrap().devices.clear();
+        Ok(())
+    }
+
+    async fn check(&self) -> Result<()> {
+        match self.state() {
+            VmState::Running | VmState::Paused => Ok(()),
+            state => Err(anyhow!("vm is {:?}", state)),
+        }
+    }
+
+    async fn get_jailer_root(&self) -> Result<String> {
+        Ok(String::new())
+    }
+
+    async fn save_state(&self) -> Result<HypervisorState> {
+        let inner = self.inner.lock().unwrap();
+        Ok(HypervisorState {
+            hypervisor_type: HYPERVISOR_FAKE.to_string(),
+            id: inner.id.clone(),
+            config: inner.config.clone(),
+            ..Default::default()
+        })
+    }
+
+    async fn capabilities(&self) -> Result<Capabilities> {
+        let mut caps = Capabilities::new();
+        caps.set(CapabilityBits::BlockDeviceSupport | CapabilityBits::FsSharingSupport);
+        Ok(caps)
+    }
+
+    async fn get_hypervisor_metrics(&self) -> Result<String> {
+        Ok(String::new())
+    }
+
+    async fn dump_guest_memory(&self, _path: &str) -> Result<()> {
+        Err(anyhow!("the fake hypervisor has no guest memory"))
+    }
+
+    async fn get_command_line(&self) -> Result<Vec<String>> {
+        Err(anyhow!("the fake hypervisor has no process"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_fake_hypervisor_lifecycle() {
+        let hypervisor = FakeHypervisor::new("hvsock:///tmp/kata.hvsock");
+        assert!(hypervisor.start_vm(0).await.is_err());
+
+        hypervisor.prepare_vm("sandbox", None).await.unwrap();
+        hypervisor.start_vm(0).await.unwrap();
+        hypervisor.check().await.unwrap();
+        hypervisor.pause_vm().await.unwrap();
+        assert_eq!(hypervisor.state(), VmState::Paused);
+        hypervisor.resume_vm().await.unwrap();
+        assert_eq!(
+            hypervisor.get_agent_socket().await.unwrap(),
+            "hvsock:///tmp/kata.hvsock"
+        );
+        assert_eq!(hypervisor.save_state().await.unwrap().id, "sandbox");
+
+        hypervisor.stop_vm().await.unwrap();
+        assert!(hypervisor.check().await.is_err());
+    }
+}
@@ -0,0 +1,734 @@
+// Copyright (c) 2023 Kata Containers community
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+//! A mock of the kata-agent, to test the container lifecycle of the runtime without a VM.
+//!
+//! The agent is served over ttrpc behind a hybrid vsock socket, the same way the VMMs mediate
+//! the connections to the agent in the guest: the runtime writes `connect <port>`, and is
+//! relayed to the agent once `OK <port>` is answered. The containers and their processes only
+//! exist in memory, a process exits when it's signaled or when `exit_process` is called, and
+//! echoes its stdin to its stdout like `cat`.
+
+use std::collections::HashMap;
+use std::os::unix::io::{IntoRawFd, RawFd};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use anyhow::{anyhow, Context, Result};
+use async_trait::async_trait;
+use protocols::agent::{
+    CloseStdinRequest, CreateContainerRequest, CreateSandboxRequest, DestroySandboxRequest,
+    ExecProcessRequest, GuestDetailsRequest, GuestDetailsResponse, ReadStreamRequest,
+    ReadStreamResponse, RemoveContainerRequest, SignalProcessRequest, StartContainerRequest,
+    WaitProcessRequest, WaitProcessResponse, WriteStreamRequest, WriteStreamResponse,
+};
+use protocols::empty::Empty;
+use protocols::health::{
+    health_check_response::ServingStatus, CheckRequest, HealthCheckResponse, VersionCheckResponse,
+};
+use protocols::{agent_ttrpc_async as agent_ttrpc, health_ttrpc_async as health_ttrpc};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{UnixListener, UnixStream};
+use tokio::sync::{watch, Notify};
+use tokio::task::JoinHandle;
+use ttrpc::error::get_rpc_status;
+use ttrpc::r#async::{Server, TtrpcContext};
+use ttrpc::Code;
+
+/// Version reported by the mock agent.
+pub const MOCK_AGENT_VERSION: &str = "mock";
+
+const HYBRID_VSOCK_NAME: &str = "kata.hvsock";
+const TTRPC_SOCK_NAME: &str = "agent.sock";
+
+/// Status of a mock container.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ContainerStatus {
+    Created,
+    Running,
+    Stopped,
+}
+
+struct Process {
+    // exit status, none while the process is running
+    exit: watch::Sender<Option<i32>>,
+    // the stdin written and not read from the stdout yet
+    stdout: Mutex<Vec<u8>>,
+    // notified when there is stdout to read or the process exits
+    stdout_ready: Notify,
+}
+
+impl Process {
+    fn new() -> Self {
+        let (exit, _) = watch::channel(None);
+        Self {
+            exit,
+            stdout: Mutex::new(vec![]),
+            stdout_ready: Notify::new(),
+        }
+    }
+
+    fn is_exited(&self) -> bool {
+        self.exit.borrow().is_some()
+    }
+
+    async fn wait(&self) -> ttrpc::Result<i32> {
+        let mut exit = self.exit.subscribe();
+        loop {
+            if let Some(status) = *exit.borrow() {
+                return Ok(status);
+            }
+            exit.changed()
+                .await
+                .map_err(|_| status_error(Code::ABORTED, "agent is stopped".to_string()))?;
+        }
+    }
+}
+
+struct Container {
+    status: ContainerStatus,
+    // the processes by exec id, the init process has an empty exec id
+    processes: HashMap<String, Arc<Process>>,
+}
+
+impl Container {
+    fn new_process(&mut self, exec_id: &str) {
+        self.processes
+            .insert(exec_id.to_string(), Arc::new(Process::new()));
+    }
+}
+
+#[derive(Default)]
+struct AgentState {
+    sandbox_created: bool,
+    containers: HashMap<String, Container>,
+    requests: Vec<String>,
+}
+
+type SharedState = Arc<Mutex<AgentState>>;
+
+fn status_error(code: Code, msg: String) -> ttrpc::Error {
+    get_rpc_status(code, msg)
+}
+
+fn container_mut<'a>(state: &'a mut AgentState, cid: &str) -> ttrpc::Result<&'a mut Container> {
+    state
+        .containers
+        .get_mut(cid)
+        .ok_or_else(|| status_error(Code::NOT_FOUND, format!("container {} not found", cid)))
+}
+
+fn process(state: &mut AgentState, cid: &str, exec_id: &str) -> ttrpc::Result<Arc<Process>> {
+    let container = container_mut(state, cid)?;
+    container.processes.get(exec_id).cloned().ok_or_else(|| {
+        status_error(
+            Code::NOT_FOUND,
+            format!("process {} of container {} not found", exec_id, cid),
+        )
+    })
+}
+
+fn exit(state: &mut AgentState, cid: &str, exec_id: &str, status: i32) -> ttrpc::Result<()> {
+    let process = process(state, cid, exec_id)?;
+    if !process.is_exited() {
+        process.exit.send_replace(Some(status));
+        process.stdout_ready.notify_waiters();
+    }
+    if exec_id.is_empty() {
+        container_mut(state, cid)?.status = ContainerStatus::Stopped;
+    }
+    Ok(())
+}
+
+struct MockAgentService {
+    state: SharedState,
+}
+
+impl MockAgentService {
+    // the requests are recorded in the order they are received
+    fn lock(&self, request: &str) -> MutexGuard<AgentState> {
+        let mut state = self.state.lock().unwrap();
+        state.requests.push(request.to_string());
+        state
+    }
+}
+
+#[async_trait]
+impl agent_ttrpc::AgentService for MockAgentService {
+    async fn create_sandbox(
+        &self,
+        _ctx: &TtrpcContext,
+        _req: CreateSandboxRequest,
+    ) -> ttrpc::Result<Empty> {
+        self.lock("create_sandbox").sandbox_created = true;
+        Ok(Empty::new())
+    }
+
+    async fn destroy_sandbox(
+        &self,
+        _ctx: &TtrpcContext,
+        _req: DestroySandboxRequest,
+    ) -> ttrpc::Result<Empty> {
+        let mut state = self.lock("destroy_sandbox");
+        state.sandbox_created = false;
+        state.containers.clear();
+        Ok(Empty::new())
+    }
+
+    async fn get_guest_details(
+        &self,
+        _ctx: &TtrpcContext,
+        _req: GuestDetailsRequest,
+    ) -> ttrpc::Result<GuestDetailsResponse> {
+        self.lock("get_guest_details");
+        Ok(GuestDetailsResponse::new())
+    }
+
+    async fn create_container(
+        &self,
+        _ctx: &TtrpcContext,
+        req: CreateContainerRequest,
+    ) -> ttrpc::Result<Empty> {
+        let mut state = self.lock("create_container");
+        if state.containers.contains_key(&req.container_id) {
+            return Err(status_error(
+                Code::ALREADY_EXISTS,
+                format!("container {} exists already", req.container_id),
+            ));
+        }
+
+        let mut container = Container {
+            status: ContainerStatus::Created,
+            processes: HashMap::new(),
+        };
+        container.new_process("");
+        state.containers.insert(req.container_id, container);
+        Ok(Empty::new())
+    }
+
+    async fn start_container(
+        &self,
+        _ctx: &TtrpcContext,
+        req: StartContainerRequest,
+    ) -> ttrpc::Result<Empty> {
+        let mut state = self.lock("start_container");
+        let container = container_mut(&mut state, &req.container_id)?;
+        if container.status != ContainerStatus::Created {
+            return Err(status_error(
+                Code::FAILED_PRECONDITION,
+                format!("container {} isn't created", req.container_id),
+            ));
+        }
+        container.status = ContainerStatus::Running;
+        Ok(Empty::new())
+    }
+
+    async fn remove_container(
+        &self,
+        _ctx: &TtrpcContext,
+        req: RemoveContainerRequest,
+    ) -> ttrpc::Result<Empty> {
+        let mut state = self.lock("remove_container");
+        container_mut(&mut state, &req.container_id)?;
+        state.containers.remove(&req.container_id);
+        Ok(Empty::new())
+    }
+
+    async fn exec_process(
+        &self,
+        _ctx: &TtrpcContext,
+        req: ExecProcessRequest,
+    ) -> ttrpc::Result<Empty> {
+        let mut state = self.lock("exec_process");
+        let container = container_mut(&mut state, &req.container_id)?;
+        if container.status != ContainerStatus::Running {
+            return Err(status_error(
+                Code::FAILED_PRECONDITION,
+                format!("container {} isn't running", req.container_id),
+            ));
+        }
+        if req.exec_id.is_empty() || container.processes.contains_key(&req.exec_id) {
+            return Err(status_error(
+                Code::ALREADY_EXISTS,
+                format!("process {} exists already", req.exec_id),
+            ));
+        }
+        container.new_process(&req.exec_id);
+        Ok(Empty::new())
+    }
+
+    async fn signal_process(
+        &self,
+        _ctx: &TtrpcContext,
+        req: SignalProcessRequest,
+    ) -> ttrpc::Result<Empty> {
+        let mut state = self.lock("signal_process");
+        // signal 0 only checks the process exists
+        if req.signal == 0 {
+            process(&mut state, &req.container_id, &req.exec_id)?;
+        } else {
+            exit(
+                &mut state,
+                &req.container_id,
+                &req.exec_id,
+                128 + req.signal as i32,
+            )?;
+        }
+        Ok(Empty::new())
+    }
+
+    async fn wait_process(
+        &self,
+        _ctx: &TtrpcContext,
+        req: WaitProcessRequest,
+    ) -> ttrpc::Result<WaitProcessResponse> {
+        let process = {
+            let mut state = self.lock("wait_process");
+            process(&mut state, &req.container_id, &req.exec_id)?
+        };
+
+        Ok(WaitProcessResponse {
+            status: process.wait().await?,
+            ..Default::default()
+        })
+    }
+
+    // the stream IO isn't recorded, there are too many requests of it
+
+    async fn write_stdin(
+        &self,
+        _ctx: &TtrpcContext,
+        req: WriteStreamRequest,
+    ) -> ttrpc::Result<WriteStreamResponse> {
+        let process = process(
+            &mut self.state.lock().unwrap(),
+            &req.container_id,
+            &req.exec_id,
+        )?;
+        process.stdout.lock().unwrap().extend_from_slice(&req.data);
+        process.stdout_ready.notify_waiters();
+
+        Ok(WriteStreamResponse {
+            len: req.data.len() as u32,
+            ..Default::default()
+        })
+    }
+
+    async fn read_stdout(
+        &self,
+        _ctx: &TtrpcContext,
+        req: ReadStreamRequest,
+    ) -> ttrpc::Result<ReadStreamResponse> {
+        let process = process(
+            &mut self.state.lock().unwrap(),
+            &req.container_id,
+            &req.exec_id,
+        )?;
+
+        loop {
+            // created before the checks, so that no notification is missed
+            let ready = process.stdout_ready.notified();
+            {
+                let mut stdout = process.stdout.lock().unwrap();
+                if !stdout.is_empty() {
+                    let len = stdout.len().min(req.len as usize);
+                    return Ok(ReadStreamResponse {
+                        data: stdout.drain(..len).collect(),
+                        ..Default::default()
+                    });
+                }
+            }
+            if process.is_exited() {
+                return Err(status_error(Code::UNKNOWN, "eof".to_string()));
+            }
+            ready.await;
+        }
+    }
+
+    async fn read_stderr(
+        &self,
+        _ctx: &TtrpcContext,
+        req: ReadStreamRequest,
+    ) -> ttrpc::Result<ReadStreamResponse> {
+        let process = process(
+            &mut self.state.lock().unwrap(),
+            &req.container_id,
+            &req.exec_id,
+        )?;
+
+        // nothing is written to the stderr
+        process.wait().await?;
+        Err(status_error(Code::UNKNOWN, "eof".to_string()))
+    }
+
+    async fn close_stdin(
+        &self,
+        _ctx: &TtrpcContext,
+        req: CloseStdinRequest,
+    ) -> ttrpc::Result<Empty> {
+        let mut state = self.lock("close_stdin");
+        process(&mut state, &req.container_id, &req.exec_id)?;
+        Ok(Empty::new())
+    }
+}
+
+#[derive(Clone)]
+struct MockHealthService;
+
+#[async_trait]
+impl health_ttrpc::Health for MockHealthService {
+    async fn check(
+        &self,
+        _ctx: &TtrpcContext,
+        _req: CheckRequest,
+    ) -> ttrpc::Result<HealthCheckResponse> {
+        let mut resp = HealthCheckResponse::new();
+        resp.set_status(ServingStatus::SERVING);
+        Ok(resp)
+    }
+
+    async fn version(
+        &self,
+        _ctx: &TtrpcContext,
+        _req: CheckRequest,
+    ) -> ttrpc::Result<VersionCheckResponse> {
+        let mut resp = VersionCheckResponse::new();
+        resp.agent_version = MOCK_AGENT_VERSION.to_string();
+        Ok(resp)
+    }
+}
+
+async fn read_connect(stream: &mut UnixStream) -> Result<u32> {
+    // read byte by byte, nothing after the line is consumed
+    let mut line = vec![];
+    loop {
+        match stream.read_u8().await.context("read connect")? {
+            b'\n' => break,
+            b => line.push(b),
+        }
+    }
+
+    let line = String::from_utf8(line).context("connect isn't utf8")?;
+    line.strip_prefix("connect ")
+        .ok_or_else(|| anyhow!("malformed connect {:?}", line))?
+        .trim()
+        .parse()
+        .with_context(|| format!("parse port of {:?}", line))
+}
+
+async fn handle_connection(
+    mut stream: UnixStream,
+    ttrpc_sock: PathBuf,
+    server_port: u32,
+) -> Result<()> {
+    let port = read_connect(&mut stream).await?;
+    stream
+        .write_all(format!("OK {}\n", port).as_bytes())
+        .await
+        .context("write OK")?;
+
+    if port == server_port {
+        let mut agent = UnixStream::connect(&ttrpc_sock)
+            .await
+            .context("connect ttrpc server")?;
+        tokio::io::copy_bidirectional(&mut stream, &mut agent)
+            .await
+            .context("relay")?;
+    } else {
+        // other ports, e.g. the log port, are kept open but nothing is sent
+        let mut buf = [0u8; 1024];
+        while stream.read(&mut buf).await.context("read")? > 0 {}
+    }
+    Ok(())
+}
+
+async fn serve_hybrid_vsock(listener: UnixListener, ttrpc_sock: PathBuf, server_port: u32) {
+    while let Ok((stream, _)) = listener.accept().await {
+        tokio::spawn(handle_connection(stream, ttrpc_sock.clone(), server_port));
+    }
+}
+
+/// The mock agent, it's served until it's stopped.
+pub struct MockAgent {
+    dir: PathBuf,
+    state: SharedState,
+    server: Server,
+    relay: JoinHandle<()>,
+}
+
+impl MockAgent {
+    /// Start the mock agent with its sockets in `dir`, the ttrpc server is on `server_port` of
+    /// the hybrid vsock.
+    pub async fn start(dir: &Path, server_port: u32) -> Result<Self> {
+        let state = SharedState::default();
+        let ttrpc_sock = dir.join(TTRPC_SOCK_NAME);
+
+        let agent_service = Box::new(MockAgentService {
+            state: state.clone(),
+        }) as Box<dyn agent_ttrpc::AgentService + Send + Sync>;
+        let health_service =
+            Box::new(MockHealthService) as Box<dyn health_ttrpc::Health + Send + Sync>;
+        let mut server = Server::new()
+            .bind(&format!("unix://{}", ttrpc_sock.display()))
+            .context("bind ttrpc server")?
+            .register_service(agent_ttrpc::create_agent_service(Arc::new(agent_service)))
+            .register_service(health_ttrpc::create_health(Arc::new(health_service)));
+        server.start().await.context("start ttrpc server")?;
+
+        let listener =
+            UnixListener::bind(dir.join(HYBRID_VSOCK_NAME)).context("bind hybrid vsock")?;
+        let relay = tokio::spawn(serve_hybrid_vsock(listener, ttrpc_sock, server_port));
+
+        Ok(Self {
+            dir: dir.to_path_buf(),
+            state,
+            server,
+            relay,
+        })
+    }
+
+    /// Address of the agent, as it's returned by `Hypervisor::get_agent_socket`.
+    pub fn address(&self) -> String {
+        format!("hvsock://{}", self.dir.join(HYBRID_VSOCK_NAME).display())
+    }
+
+    /// Connect to the ttrpc server directly, without the hybrid vsock handshake.
+    pub async fn connect(&self) -> Result<RawFd> {
+        let stream = UnixStream::connect(self.dir.join(TTRPC_SOCK_NAME))
+            .await
+            .context("connect ttrpc server")?;
+        Ok(stream.into_std().context("into std stream")?.into_raw_fd())
+    }
+
+    pub fn sandbox_created(&self) -> bool {
+        self.state.lock().unwrap().sandbox_created
+    }
+
+    /// Status of the container `cid`, none if it doesn't exist.
+    pub fn container_status(&self, cid: &str) -> Option<ContainerStatus> {
+        self.state
+            .lock()
+            .unwrap()
+            .containers
+            .get(cid)
+            .map(|c| c.status)
+    }
+
+    /// The ids of the existing containers, sorted.
+    pub fn container_ids(&self) -> Vec<String> {
+        let mut ids: Vec<String> = self
+            .state
+            .lock()
+            .unwrap()
+            .containers
+            .keys()
+            .cloned()
+            .collect();
+        ids.sort();
+        ids
+    }
+
+    /// The names of the requests received so far, e.g. "create_container", except the stream
+    /// IO ones.
+    pub fn requests(&self) -> Vec<String> {
+        self.state.lock().unwrap().requests.clone()
+    }
+
+    /// Make the process `exec_id` of the container `cid` exit with `status`, as if it ended by
+    /// itself.
+    pub fn exit_process(&self, cid: &str, exec_id: &str, status: i32) -> Result<()> {
+        exit(&mut self.state.lock().unwrap(), cid, exec_id, status)
+            .map_err(|e| anyhow!("exit process: {:?}", e))
+    }
+
+    pub async fn stop(mut self) {
+        self.relay.abort();
+        let _ = self.server.shutdown().await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ttrpc::context;
+    use ttrpc::r#async::Client;
+
+    const SERVER_PORT: u32 = 1024;
+
+    async fn connect_hybrid_vsock(agent: &MockAgent, port: u32) -> UnixStream {
+        let mut stream = UnixStream::connect(agent.dir.join(HYBRID_VSOCK_NAME))
+            .await
+            .unwrap();
+        stream
+            .write_all(format!("connect {}\n", port).as_bytes())
+            .await
+            .unwrap();
+        let mut resp = vec![0u8; format!("OK {}\n", port).len()];
+        stream.read_exact(&mut resp).await.unwrap();
+        assert_eq!(resp, format!("OK {}\n", port).as_bytes());
+        stream
+    }
+
+    #[tokio::test]
+    async fn test_mock_agent_lifecycle() {
+        let dir = tempfile::tempdir().unwrap();
+        let agent = MockAgent::start(dir.path(), SERVER_PORT).await.unwrap();
+
+        let stream = connect_hybrid_vsock(&agent, SERVER_PORT).await;
+        let client = agent_ttrpc::AgentServiceClient::new(Client::new(
+            stream.into_std().unwrap().into_raw_fd(),
+        ));
+
+        let cid = "c1".to_string();
+        let create = CreateContainerRequest {
+            container_id: cid.clone(),
+            ..Default::default()
+        };
+        client
+            .create_container(context::with_timeout(0), &create)
+            .await
+            .unwrap();
+        assert!(client
+            .create_container(context::with_timeout(0), &create)
+            .await
+            .is_err());
+        assert_eq!(agent.container_status(&cid), Some(ContainerStatus::Created));
+
+        client
+            .start_container(
+                context::with_timeout(0),
+                &StartContainerRequest {
+                    container_id: cid.clone(),
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+        assert_eq!(agent.container_status(&cid), Some(ContainerStatus::Running));
+
+        // the init process is killed while it's waited for
+        let wait = {
+            let client = client.clone();
+            let req = WaitProcessRequest {
+                container_id: cid.clone(),
+                ..Default::default()
+            };
+            tokio::spawn(async move { client.wait_process(context::with_timeout(0), &req).await })
+        };
+        client
+            .signal_process(
+                context::with_timeout(0),
+                &SignalProcessRequest {
+                    container_id: cid.clone(),
+                    signal: 9,
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+        assert_eq!(wait.await.unwrap().unwrap().status, 137);
+        assert_eq!(agent.container_status(&cid), Some(ContainerStatus::Stopped));
+
+        client
+            .remove_container(
+                context::with_timeout(0),
+                &RemoveContainerRequest {
+                    container_id: cid.clone(),
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+        assert!(agent.container_ids().is_empty());
+        // the wait and the signal may be received in any order
+        let requests = agent.requests();
+        assert_eq!(
+            requests[..3],
+            ["create_container", "create_container", "start_container"]
+        );
+        assert_eq!(requests.len(), 6);
+        assert_eq!(requests[5], "remove_container");
+
+        agent.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_mock_agent_stdio() {
+        let dir = tempfile::tempdir().unwrap();
+        let agent = MockAgent::start(dir.path(), SERVER_PORT).await.unwrap();
+        let client =
+            agent_ttrpc::AgentServiceClient::new(Client::new(agent.connect().await.unwrap()));
+
+        let create = CreateContainerRequest {
+            container_id: "c1".to_string(),
+            ..Default::default()
+        };
+        client
+            .create_container(context::with_timeout(0), &create)
+            .await
+            .unwrap();
+
+        // the stdin is echoed to the stdout
+        let resp = client
+            .write_stdin(
+                context::with_timeout(0),
+                &WriteStreamRequest {
+                    container_id: "c1".to_string(),
+                    data: b"kata".to_vec(),
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+        assert_eq!(resp.len, 4);
+        let read = ReadStreamRequest {
+            container_id: "c1".to_string(),
+            len: 3,
+            ..Default::default()
+        };
+        let resp = client
+            .read_stdout(context::with_timeout(0), &read)
+            .await
+            .unwrap();
+        assert_eq!(resp.data, b"kat");
+        let resp = client
+            .read_stdout(context::with_timeout(0), &read)
+            .await
+            .unwrap();
+        assert_eq!(resp.data, b"a");
+
+        // eof once the process exits
+        agent.exit_process("c1", "", 0).unwrap();
+        assert!(client
+            .read_stdout(context::with_timeout(0), &read)
+            .await
+            .is_err());
+
+        agent.stop().await;
+    }
+
+    #[tokio::test]
+    async fn test_mock_agent_unknown_container() {
+        let dir = tempfile::tempdir().unwrap();
+        let agent = MockAgent::start(dir.path(), SERVER_PORT).await.unwrap();
+
+        let client =
+            agent_ttrpc::AgentServiceClient::new(Client::new(agent.connect().await.unwrap()));
+        let err = client
+            .start_container(
+                context::with_timeout(0),
+                &StartContainerRequest {
+                    container_id: "none".to_string(),
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap_err();
+        assert!(matches!(err, ttrpc::Error::RpcStatus(s) if s.code() == Code::NOT_FOUND));
+        assert!(agent.exit_process("none", "", 0).is_err());
+
+        agent.stop().await;
+    }
+}
@@ -38,6 +38,9 @@ pub enum Commands {
     /// Test if system can run Kata Containers
     Check(CheckArgument),
 
+    /// Collect the debug bundle of a sandbox to attach to the bug reports
+    DebugBundle(DebugBundleArguments),
+
     /// Directly assign a volume to Kata Containers to manage
     DirectVolume(DirectVolumeCommand),
 
@@ -91,6 +94,15 @@ pub enum CheckSubCommand {
     List,
 }
 
+#[derive(Debug, Args)]
+pub struct DebugBundleArguments {
+    /// pod sandbox ID.
+    pub sandbox_id: String,
+    /// File to write the tarball to, default is kata-debug-<sandbox ID>.tar.
+    #[arg(short = 'o', long = "output")]
+    pub output: Option<String>,
+}
+
 #[derive(Debug, Args)]
 pub struct EnvArgument {
     /// Format output as JSON
@@ -121,14 +133,73 @@ pub struct IptablesCommand {
 
 #[derive(Debug, Subcommand)]
 pub enum IpTablesArguments {
-    /// Configure iptables
-    Metrics,
+    /// Get the iptables from the guest
+    Get(IptablesGetArgs),
+
+    /// Set the iptables in the guest from a file, in the iptables-save format
+    Set(IptablesSetArgs),
+}
+
+#[derive(Debug, Args)]
+pub struct IptablesGetArgs {
+    /// The target sandbox for getting the iptables
+    #[arg(long)]
+    pub sandbox_id: String,
+    /// Get the ip6tables rather than the iptables
+    #[arg(long)]
+    pub v6: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct IptablesSetArgs {
+    /// The target sandbox for setting the iptables
+    #[arg(long)]
+    pub sandbox_id: String,
+    /// Set the ip6tables rather than the iptables
+    #[arg(long)]
+    pub v6: bool,
+    /// The file holding the rules
+    pub file: String,
 }
 
 #[derive(Debug, Args)]
 pub struct MonitorArgument {
     /// The address to listen on for HTTP requests. (default "127.0.0.1:8090")
     pub address: Option<String>,
+
+    /// The TOML file of the monitor configuration, the other options override it
+    #[arg(short = 'c', long)]
+    pub config: Option<String>,
+
+    /// Interval in seconds between the scrapes of all the sandboxes, 0 disables them and the
+    /// sandboxes are only scraped on demand. (default 0)
+    #[arg(long)]
+    pub scrape_interval: Option<u64>,
+
+    /// The glob of the shim management sockets of the sandboxes to scrape.
+    /// (default "/run/kata/*/shim-monitor.sock")
+    #[arg(long)]
+    pub target_socket_glob: Option<String>,
+
+    /// The CRI endpoint of the container manager, e.g. "/run/containerd/containerd.sock",
+    /// the pod uid, name and namespace queried from it are attached to the sandbox metrics
+    /// through the labels cri_uid, cri_name and cri_namespace
+    #[arg(long)]
+    pub runtime_endpoint: Option<String>,
+
+    /// The TCP port of an in-guest metrics exporter to scrape along with the sandbox metrics,
+    /// e.g. 9400 for the DCGM exporter of the GPUs, it must be one of the guest_metrics_ports
+    /// of the agent configuration. It may be repeated
+    #[arg(long = "guest-metrics-port")]
+    pub guest_metrics_ports: Vec<u32>,
+
+    /// The PEM certificate chain to serve HTTPS with, along with --tls-key
+    #[arg(long)]
+    pub tls_cert: Option<String>,
+
+    /// The PEM private key of --tls-cert
+    #[arg(long)]
+    pub tls_key: Option<String>,
 }
 
 #[derive(Debug, Args)]
@@ -181,4 +252,7 @@ pub struct ExecArguments {
     #[clap(short = 'p', long = "kata-debug-port", default_value_t = 1026)]
     /// kata debug console vport same as configuration, default is 1026.
     pub vport: u32,
+    #[clap(long = "through-shim")]
+    /// attach through the shim management socket of runtime-rs, which knows the vport.
+    pub through_shim: bool,
 }
@@ -25,10 +25,12 @@ use std::process::exit;
 use args::{Commands, KataCtlCli};
 
 use ops::check_ops::{
-    handle_check, handle_factory, handle_iptables, handle_metrics, handle_monitor, handle_version,
+    handle_check, handle_factory, handle_metrics, handle_monitor, handle_version,
 };
+use ops::debug_ops::handle_debug_bundle;
 use ops::env_ops::handle_env;
 use ops::exec_ops::handle_exec;
+use ops::iptables_ops::handle_iptables;
 use ops::volume_ops::handle_direct_volume;
 use slog::{error, o};
 
@@ -53,6 +55,7 @@ fn real_main() -> Result<()> {
 
     let res = match args.command {
         Commands::Check(args) => handle_check(args),
+        Commands::DebugBundle(args) => handle_debug_bundle(args),
         Commands::DirectVolume(args) => handle_direct_volume(args),
         Commands::Exec(args) => handle_exec(args),
         Commands::Env(args) => handle_env(args),
@@ -0,0 +1,202 @@
+// Copyright 2022-2023 Ant Group
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+// The configuration of the monitor, read from a TOML file and overridden by
+// the options of the command line, e.g. for a DaemonSet:
+//
+//   listen_address = "0.0.0.0:8090"
+//   scrape_interval_secs = 15
+//   target_socket_glob = "/run/kata/*/shim-monitor.sock"
+//   runtime_endpoint = "/run/containerd/containerd.sock"
+//   guest_metrics_ports = [9400]
+//   tls_cert = "/etc/kata-monitor/tls.crt"
+//   tls_key = "/etc/kata-monitor/tls.key"
+
+use crate::args::MonitorArgument;
+
+use anyhow::{anyhow, Context, Result};
+use serde::Deserialize;
+use shim_interface::{sb_storage_path, SHIM_MGMT_SOCK_NAME};
+use std::fs;
+use std::net::SocketAddr;
+use std::path::Path;
+
+pub const DEFAULT_LISTEN_ADDRESS: &str = "127.0.0.1:8090";
+
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct MonitorConfig {
+    /// The address to listen on for HTTP requests
+    pub listen_address: String,
+    /// Interval in seconds between the scrapes of the sandboxes matched by
+    /// `target_socket_glob`, 0 disables them and the sandboxes are only
+    /// scraped on demand with "/metrics?sandbox=<id>"
+    pub scrape_interval_secs: u64,
+    /// The glob of the shim management sockets of the sandboxes to scrape,
+    /// the sandbox id is the name of the directory of the socket
+    pub target_socket_glob: String,
+    /// The CRI endpoint of the container manager, see
+    /// `MonitorArgument::runtime_endpoint`
+    pub runtime_endpoint: Option<String>,
+    /// The TCP ports of the in-guest metrics exporters scraped through the
+    /// shims along with the sandbox metrics, see
+    /// `MonitorArgument::guest_metrics_ports`
+    pub guest_metrics_ports: Vec<u32>,
+    /// The PEM certificate chain to serve HTTPS with, along with `tls_key`
+    pub tls_cert: Option<String>,
+    /// The PEM private key of `tls_cert`
+    pub tls_key: Option<String>,
+}
+
+impl Default for MonitorConfig {
+    fn default() -> Self {
+        MonitorConfig {
+            listen_address: DEFAULT_LISTEN_ADDRESS.to_string(),
+            scrape_interval_secs: 0,
+            target_socket_glob: Path::new(&sb_storage_path())
+                .join("*")
+                .join(SHIM_MGMT_SOCK_NAME)
+                .display()
+                .to_string(),
+            runtime_endpoint: None,
+            guest_metrics_ports: vec![],
+            tls_cert: None,
+            tls_key: None,
+        }
+    }
+}
+
+impl MonitorConfig {
+    /// the configuration from the file of `--config` if any, overridden by
+    /// the other options of the command line
+    pub fn new(args: &MonitorArgument) -> Result<Self> {
+        let mut config = match args.config.as_ref() {
+            Some(path) => {
+                let content = fs::read_to_string(path).with_context(|| format!("read {}", path))?;
+                toml::from_str(&content).with_context(|| format!("parse {}", path))?
+            }
+            None => MonitorConfig::default(),
+        };
+
+        if let Some(address) = args.address.as_ref() {
+            config.listen_address = address.clone();
+        }
+        if let Some(interval) = args.scrape_interval {
+            config.scrape_interval_secs = interval;
+        }
+        if let Some(pattern) = args.target_socket_glob.as_ref() {
+            config.target_socket_glob = pattern.clone();
+        }
+        if args.runtime_endpoint.is_some() {
+            config.runtime_endpoint = args.runtime_endpoint.clone();
+        }
+        if !args.guest_metrics_ports.is_empty() {
+            config.guest_metrics_ports = args.guest_metrics_ports.clone();
+        }
+        if args.tls_cert.is_some() {
+            config.tls_cert = args.tls_cert.clone();
+        }
+        if args.tls_key.is_some() {
+            config.tls_key = args.tls_key.clone();
+        }
+
+        config.validate()?;
+        Ok(config)
+    }
+
+    fn validate(&self) -> Result<()> {
+        self.listen_address
+            .parse::<SocketAddr>()
+            .with_context(|| format!("invalid listen address {}", self.listen_address))?;
+        glob::Pattern::new(&self.target_socket_glob)
+            .with_context(|| format!("invalid target socket glob {}", self.target_socket_glob))?;
+        if let Some(port) = self
+            .guest_metrics_ports
+            .iter()
+            .find(|p| **p == 0 || **p > u16::MAX as u32)
+        {
+            return Err(anyhow!("invalid guest metrics port {}", port));
+        }
+        if self.tls_cert.is_some() != self.tls_key.is_some() {
+            return Err(anyhow!("tls cert and tls key must be set together"));
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    fn args() -> MonitorArgument {
+        MonitorArgument {
+            address: None,
+            config: None,
+            scrape_interval: None,
+            target_socket_glob: None,
+            runtime_endpoint: None,
+            guest_metrics_ports: vec![],
+            tls_cert: None,
+            tls_key: None,
+        }
+    }
+
+    #[test]
+    fn test_monitor_config() {
+        let config = MonitorConfig::new(&args()).unwrap();
+        assert_eq!(config, MonitorConfig::default());
+        assert_eq!(config.listen_address, DEFAULT_LISTEN_ADDRESS);
+        assert_eq!(config.target_socket_glob, "/run/kata/*/shim-monitor.sock");
+
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        file.write_all(
+            br#"
+listen_address = "0.0.0.0:9090"
+scrape_interval_secs = 15
+guest_metrics_ports = [9400]
+tls_cert = "/etc/kata-monitor/tls.crt"
+tls_key = "/etc/kata-monitor/tls.key"
+"#,
+        )
+        .unwrap();
+        let mut args = args();
+        args.config = Some(file.path().display().to_string());
+        args.scrape_interval = Some(30);
+        let config = MonitorConfig::new(&args).unwrap();
+        assert_eq!(config.listen_address, "0.0.0.0:9090");
+        // the command line overrides the file
+        assert_eq!(config.scrape_interval_secs, 30);
+        assert_eq!(config.guest_metrics_ports, vec![9400]);
+        assert_eq!(
+            config.tls_cert.as_deref(),
+            Some("/etc/kata-monitor/tls.crt")
+        );
+
+        args.tls_key = None;
+        args.config = None;
+        args.tls_cert = Some("/etc/kata-monitor/tls.crt".to_string());
+        assert!(MonitorConfig::new(&args).is_err());
+
+        let mut args = self::args();
+        args.address = Some("localhost".to_string());
+        assert!(MonitorConfig::new(&args).is_err());
+
+        let mut args = self::args();
+        args.guest_metrics_ports = vec![9400, 0];
+        assert!(MonitorConfig::new(&args).is_err());
+
+        let mut args = self::args();
+        args.target_socket_glob = Some("/run/kata/[*/shim-monitor.sock".to_string());
+        assert!(MonitorConfig::new(&args).is_err());
+
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        file.write_all(b"scrape_interval = 15\n").unwrap();
+        let mut args = self::args();
+        args.config = Some(file.path().display().to_string());
+        assert!(MonitorConfig::new(&args).is_err());
+    }
+}
@@ -0,0 +1,419 @@
+// Copyright 2022-2023 Ant Group
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+// A minimal CRI client of the monitor, it queries the pods of the container
+// manager to attach their metadata to the metrics of the sandboxes, so that
+// the dashboards show the pod name and namespace rather than the sandbox id.
+//
+// Only ListPodSandbox is needed, so the gRPC call is made with the http2
+// client of hyper and the few protobuf fields of the response are decoded by
+// hand, rather than pulling a gRPC stack and the whole CRI api in.
+
+use crate::sl;
+
+use anyhow::{anyhow, Context, Result};
+use hyper::body::HttpBody;
+use hyper::{client::conn, Body, Method, Request};
+use slog::{debug, warn};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::net::UnixStream;
+
+const UNIX_SOCKET_PREFIX: &str = "unix://";
+const CRI_TIMEOUT: Duration = Duration::from_secs(5);
+
+// the CRI api versions tried in order, v1alpha2 is for the container
+// managers which do not serve v1 yet
+const LIST_POD_SANDBOX_PATHS: &[&str] = &[
+    "/runtime.v1.RuntimeService/ListPodSandbox",
+    "/runtime.v1alpha2.RuntimeService/ListPodSandbox",
+];
+
+// ListPodSandboxRequest { filter: PodSandboxFilter { state: { SANDBOX_READY } } },
+// encoded once for all as it never changes
+const LIST_READY_POD_SANDBOX_REQUEST: &[u8] = &[0x0a, 0x02, 0x12, 0x00];
+
+const GRPC_STATUS_OK: &str = "0";
+const GRPC_STATUS_UNIMPLEMENTED: &str = "12";
+const GRPC_FRAME_HEADER_LEN: usize = 5;
+
+const PROTOBUF_WIRE_VARINT: u64 = 0;
+const PROTOBUF_WIRE_FIXED64: u64 = 1;
+const PROTOBUF_WIRE_LEN: u64 = 2;
+const PROTOBUF_WIRE_FIXED32: u64 = 5;
+
+/// The CRI metadata of the pod of a sandbox
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PodMetadata {
+    pub uid: String,
+    pub name: String,
+    pub namespace: String,
+}
+
+impl PodMetadata {
+    /// the labels attached to the metrics of the sandbox, named as the
+    /// ones of the go kata-monitor
+    pub fn labels(&self) -> Vec<(&str, &str)> {
+        vec![
+            ("cri_uid", self.uid.as_str()),
+            ("cri_name", self.name.as_str()),
+            ("cri_namespace", self.namespace.as_str()),
+        ]
+    }
+}
+
+/// CriClient maps the sandbox ids to the metadata of their pods, the pods
+/// are listed again only when a sandbox is not known yet
+pub struct CriClient {
+    endpoint: PathBuf,
+    pods: Mutex<HashMap<String, PodMetadata>>,
+}
+
+impl CriClient {
+    pub fn new(endpoint: &str) -> Self {
+        let endpoint = endpoint
+            .strip_prefix(UNIX_SOCKET_PREFIX)
+            .unwrap_or(endpoint);
+        CriClient {
+            endpoint: PathBuf::from(endpoint),
+            pods: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// the metadata of the pod of the sandbox, None if the container manager
+    /// can't be queried or doesn't know the sandbox, the metrics are still
+    /// served without the pod labels then
+    pub async fn pod_metadata(&self, sandbox_id: &str) -> Option<PodMetadata> {
+        if let Some(pod) = self.cached(sandbox_id) {
+            return Some(pod);
+        }
+
+        match self.list_pod_sandboxes().await {
+            Ok(pods) => {
+                debug!(sl!(), "monitor: synced {} pods from the CRI", pods.len());
+                // replaced rather than merged, to forget the removed pods
+                *self.pods.lock().unwrap() = pods;
+            }
+            Err(e) => {
+                warn!(
+                    sl!(),
+                    "monitor: failed to list pods from {}: {:?}",
+                    self.endpoint.display(),
+                    e
+                );
+                return None;
+            }
+        }
+
+        self.cached(sandbox_id)
+    }
+
+    fn cached(&self, sandbox_id: &str) -> Option<PodMetadata> {
+        self.pods.lock().unwrap().get(sandbox_id).cloned()
+    }
+
+    async fn list_pod_sandboxes(&self) -> Result<HashMap<String, PodMetadata>> {
+        for path in LIST_POD_SANDBOX_PATHS {
+            match tokio::time::timeout(CRI_TIMEOUT, self.grpc_call(path)).await {
+                Ok(Ok(Some(resp))) => return parse_list_pod_sandbox_response(&resp),
+                // not served by the container manager, try the older version
+                Ok(Ok(None)) => continue,
+                Ok(Err(e)) => return Err(e),
+                Err(_) => return Err(anyhow!("timeout calling {}", path)),
+            }
+        }
+
+        Err(anyhow!("no supported CRI api version"))
+    }
+
+    // the unary gRPC call of the path, None if the method is unimplemented
+    async fn grpc_call(&self, path: &str) -> Result<Option<Vec<u8>>> {
+        let stream = UnixStream::connect(&self.endpoint)
+            .await
+            .context("connect CRI endpoint")?;
+        let (mut sender, connection) = conn::Builder::new()
+            .http2_only(true)
+            .handshake::<_, Body>(stream)
+            .await
+            .context("http2 handshake")?;
+        tokio::spawn(async move {
+            if let Err(e) = connection.await {
+                debug!(sl!(), "monitor: CRI connection closed: {:?}", e);
+            }
+        });
+
+        let req = Request::builder()
+            .method(Method::POST)
+            .uri(format!("http://localhost{}", path))
+            .header("content-type", "application/grpc")
+            .header("te", "trailers")
+            .body(Body::from(grpc_frame(LIST_READY_POD_SANDBOX_REQUEST)))
+            .context("build request")?;
+        let mut resp = sender.send_request(req).await.context("send request")?;
+
+        let mut body = Vec::new();
+        while let Some(chunk) = resp.body_mut().data().await {
+            body.extend_from_slice(&chunk.context("read response")?);
+        }
+        // the status is in the headers of a trailers only response
+        let trailers = resp.body_mut().trailers().await.context("read trailers")?;
+        let status = trailers
+            .as_ref()
+            .and_then(|t| t.get("grpc-status"))
+            .or_else(|| resp.headers().get("grpc-status"))
+            .and_then(|s| s.to_str().ok())
+            .unwrap_or(GRPC_STATUS_OK);
+        match status {
+            GRPC_STATUS_OK => Ok(Some(grpc_message(&body)?.to_vec())),
+            GRPC_STATUS_UNIMPLEMENTED => Ok(None),
+            _ => {
+                let message = trailers
+                    .as_ref()
+                    .and_then(|t| t.get("grpc-message"))
+                    .or_else(|| resp.headers().get("grpc-message"))
+                    .and_then(|m| m.to_str().ok())
+                    .unwrap_or_default();
+                Err(anyhow!("{} failed, status {}: {}", path, status, message))
+            }
+        }
+    }
+}
+
+// a gRPC length prefixed message, uncompressed
+fn grpc_frame(message: &[u8]) -> Vec<u8> {
+    let mut frame = Vec::with_capacity(GRPC_FRAME_HEADER_LEN + message.len());
+    frame.push(0);
+    frame.extend_from_slice(&(message.len() as u32).to_be_bytes());
+    frame.extend_from_slice(message);
+    frame
+}
+
+fn grpc_message(frame: &[u8]) -> Result<&[u8]> {
+    if frame.len() < GRPC_FRAME_HEADER_LEN {
+        return Err(anyhow!("truncated gRPC frame"));
+    }
+    if frame[0] != 0 {
+        return Err(anyhow!("compressed gRPC message not supported"));
+    }
+    let mut len = [0u8; 4];
+    len.copy_from_slice(&frame[1..GRPC_FRAME_HEADER_LEN]);
+    frame
+        .get(GRPC_FRAME_HEADER_LEN..GRPC_FRAME_HEADER_LEN + u32::from_be_bytes(len) as usize)
+        .ok_or_else(|| anyhow!("truncated gRPC message"))
+}
+
+// ListPodSandboxResponse { repeated PodSandbox items = 1; }
+// PodSandbox { string id = 1; PodSandboxMetadata metadata = 2; ... }
+// PodSandboxMetadata { string name = 1; string uid = 2; string namespace = 3; ... }
+fn parse_list_pod_sandbox_response(message: &[u8]) -> Result<HashMap<String, PodMetadata>> {
+    let mut pods = HashMap::new();
+    for_each_len_field(message, |field, item| {
+        if field != 1 {
+            return Ok(());
+        }
+        let mut id = String::new();
+        let mut pod = PodMetadata::default();
+        for_each_len_field(item, |field, value| {
+            match field {
+                1 => id = string_field(value)?,
+                2 => for_each_len_field(value, |field, value| {
+                    match field {
+                        1 => pod.name = string_field(value)?,
+                        2 => pod.uid = string_field(value)?,
+                        3 => pod.namespace = string_field(value)?,
+                        _ => {}
+                    }
+                    Ok(())
+                })?,
+                _ => {}
+            }
+            Ok(())
+        })?;
+        pods.insert(id, pod);
+        Ok(())
+    })?;
+
+    Ok(pods)
+}
+
+fn string_field(value: &[u8]) -> Result<String> {
+    String::from_utf8(value.to_vec()).context("invalid string field")
+}
+
+// call f with the number and the value of the length delimited fields of the
+// protobuf message, the other fields are skipped
+fn for_each_len_field<F>(mut message: &[u8], mut f: F) -> Result<()>
+where
+    F: FnMut(u64, &[u8]) -> Result<()>,
+{
+    while !message.is_empty() {
+        let key = read_varint(&mut message)?;
+        match key & 0x7 {
+            PROTOBUF_WIRE_VARINT => {
+                read_varint(&mut message)?;
+            }
+            PROTOBUF_WIRE_FIXED64 => message = skip(message, 8)?,
+            PROTOBUF_WIRE_FIXED32 => message = skip(message, 4)?,
+            PROTOBUF_WIRE_LEN => {
+                let len = read_varint(&mut message)? as usize;
+                let value = message
+                    .get(..len)
+                    .ok_or_else(|| anyhow!("truncated protobuf field"))?;
+                f(key >> 3, value)?;
+                message = &message[len..];
+            }
+            wire_type => return Err(anyhow!("unsupported protobuf wire type {}", wire_type)),
+        }
+    }
+
+    Ok(())
+}
+
+fn skip(message: &[u8], len: usize) -> Result<&[u8]> {
+    message
+        .get(len..)
+        .ok_or_else(|| anyhow!("truncated protobuf field"))
+}
+
+fn read_varint(message: &mut &[u8]) -> Result<u64> {
+    let mut value = 0u64;
+    for (i, byte) in message.iter().enumerate().take(10) {
+        value |= u64::from(byte & 0x7f) << (7 * i);
+        if byte & 0x80 == 0 {
+            *message = &message[i + 1..];
+            return Ok(value);
+        }
+    }
+
+    Err(anyhow!("invalid protobuf varint"))
+}
+
+/// add the labels to every sample of the metrics in the prometheus text format
+pub fn add_labels(metrics: &str, labels: &[(&str, &str)]) -> String {
+    if labels.is_empty() {
+        return metrics.to_string();
+    }
+
+    let labels = labels
+        .iter()
+        .map(|(name, value)| format!("{}=\"{}\"", name, escape_label_value(value)))
+        .collect::<Vec<_>>()
+        .join(",");
+
+    let mut result = String::with_capacity(metrics.len());
+    for line in metrics.lines() {
+        if line.is_empty() || line.starts_with('#') {
+            result.push_str(line);
+        } else if let Some(pos) = line.find(|c: char| c == '{' || c.is_whitespace()) {
+            let (name, rest) = line.split_at(pos);
+            result.push_str(name);
+            match rest.strip_prefix('{') {
+                Some(rest) if rest.starts_with('}') => {
+                    result.push_str(&format!("{{{}{}", labels, rest))
+                }
+                Some(rest) => result.push_str(&format!("{{{},{}", labels, rest)),
+                None => result.push_str(&format!("{{{}}}{}", labels, rest)),
+            }
+        } else {
+            result.push_str(line);
+        }
+        result.push('\n');
+    }
+
+    result
+}
+
+fn escape_label_value(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn len_field(field: u8, value: &[u8]) -> Vec<u8> {
+        let mut buf = vec![(field << 3) | PROTOBUF_WIRE_LEN as u8, value.len() as u8];
+        buf.extend_from_slice(value);
+        buf
+    }
+
+    #[test]
+    fn test_parse_list_pod_sandbox_response() {
+        let mut metadata = len_field(1, b"nginx");
+        metadata.extend(len_field(2, b"uid-1"));
+        metadata.extend(len_field(3, b"default"));
+        // attempt, a varint field to be skipped
+        metadata.extend(vec![4 << 3, 1]);
+
+        let mut pod = len_field(1, b"sandbox-1");
+        pod.extend(len_field(2, &metadata));
+        // state and created_at
+        pod.extend(vec![3 << 3, 0, 4 << 3, 0x80, 0x01]);
+
+        let mut resp = len_field(1, &pod);
+        resp.extend(len_field(1, &len_field(1, b"sandbox-2")));
+
+        let frame = grpc_frame(&resp);
+        let pods = parse_list_pod_sandbox_response(grpc_message(&frame).unwrap()).unwrap();
+        assert_eq!(pods.len(), 2);
+        assert_eq!(
+            pods.get("sandbox-1"),
+            Some(&PodMetadata {
+                uid: "uid-1".to_string(),
+                name: "nginx".to_string(),
+                namespace: "default".to_string(),
+            })
+        );
+        assert_eq!(pods.get("sandbox-2"), Some(&PodMetadata::default()));
+
+        assert!(parse_list_pod_sandbox_response(&[0x0a, 0x05, 0x01]).is_err());
+        assert!(grpc_message(&frame[..frame.len() - 1]).is_err());
+    }
+
+    #[test]
+    fn test_add_labels() {
+        let metrics = "# HELP kata_shim_fds Open FDs\n\
+                       # TYPE kata_shim_fds gauge\n\
+                       kata_shim_fds 12\n\
+                       kata_shim_netdev{interface=\"eth0\"} 1\n\
+                       kata_shim_up{} 1\n";
+        let pod = PodMetadata {
+            uid: "uid-1".to_string(),
+            name: "nginx".to_string(),
+            namespace: "ns\"1".to_string(),
+        };
+
+        let labels = "cri_uid=\"uid-1\",cri_name=\"nginx\",cri_namespace=\"ns\\\"1\"";
+        assert_eq!(
+            add_labels(metrics, &pod.labels()),
+            format!(
+                "# HELP kata_shim_fds Open FDs\n\
+                 # TYPE kata_shim_fds gauge\n\
+                 kata_shim_fds{{{0}}} 12\n\
+                 kata_shim_netdev{{{0},interface=\"eth0\"}} 1\n\
+                 kata_shim_up{{{0}}} 1\n",
+                labels
+            )
+        );
+        assert_eq!(add_labels(metrics, &[]), metrics);
+    }
+
+    #[test]
+    fn test_cri_client_endpoint() {
+        assert_eq!(
+            CriClient::new("unix:///run/containerd/containerd.sock").endpoint,
+            PathBuf::from("/run/containerd/containerd.sock")
+        );
+        assert_eq!(
+            CriClient::new("/run/crio/crio.sock").endpoint,
+            PathBuf::from("/run/crio/crio.sock")
+        );
+    }
+}
@@ -3,23 +3,43 @@
 // SPDX-License-Identifier: Apache-2.0
 //
 
+use crate::monitor::config::MonitorConfig;
+use crate::monitor::cri::{add_labels, CriClient};
 use crate::monitor::metrics::get_monitor_metrics;
+use crate::monitor::scraper::{add_guest_metrics, Scraper};
 use crate::sl;
 use crate::utils::TIMEOUT;
 
 use anyhow::{anyhow, Context, Result};
 use hyper::body;
+use hyper::server::conn::Http;
 use hyper::service::{make_service_fn, service_fn};
 use hyper::{Body, Method, Request, Response, Server, StatusCode};
 use shim_interface::shim_mgmt::client::MgmtClient;
-use slog::{self, info};
+use slog::{self, info, warn};
 use std::collections::HashMap;
+use std::fs::File;
+use std::io::BufReader;
 use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::net::TcpListener;
+use tokio_rustls::rustls::{Certificate, PrivateKey, ServerConfig};
+use tokio_rustls::TlsAcceptor;
 
 const ROOT_URI: &str = "/";
 const METRICS_URI: &str = "/metrics";
 
-async fn handler_mux(req: Request<Body>) -> Result<Response<Body>> {
+// the state shared by the requests
+#[derive(Default)]
+struct MonitorState {
+    cri: Option<CriClient>,
+    // None if the sandboxes are only scraped on demand
+    scraper: Option<Scraper>,
+    guest_metrics_ports: Vec<u32>,
+}
+
+async fn handler_mux(state: Arc<MonitorState>, req: Request<Body>) -> Result<Response<Body>> {
     info!(
         sl!(),
         "mgmt-svr(mux): recv req, method: {}, uri: {}",
@@ -29,7 +49,7 @@ async fn handler_mux(req: Request<Body>) -> Result<Response<Body>> {
 
     match (req.method(), req.uri().path()) {
         (&Method::GET, ROOT_URI) => root_uri_handler(req).await,
-        (&Method::GET, METRICS_URI) => metrics_uri_handler(req).await,
+        (&Method::GET, METRICS_URI) => metrics_uri_handler(&state, req).await,
         _ => not_found_uri_handler(req).await,
     }
     .map_or_else(
@@ -43,50 +63,173 @@ async fn handler_mux(req: Request<Body>) -> Result<Response<Body>> {
     )
 }
 
-pub async fn http_server_setup(socket_addr: &str) -> Result<()> {
-    let addr: SocketAddr = socket_addr
+pub async fn http_server_setup(config: MonitorConfig) -> Result<()> {
+    let addr: SocketAddr = config
+        .listen_address
         .parse()
         .context("failed to parse http socket address")?;
+    let acceptor = tls_acceptor(&config).context("failed to load tls config")?;
+
+    let state = Arc::new(MonitorState {
+        cri: config.runtime_endpoint.as_deref().map(CriClient::new),
+        scraper: (config.scrape_interval_secs > 0)
+            .then(|| Scraper::new(&config.target_socket_glob, &config.guest_metrics_ports)),
+        guest_metrics_ports: config.guest_metrics_ports.clone(),
+    });
+    if state.scraper.is_some() {
+        let interval = Duration::from_secs(config.scrape_interval_secs);
+        let state = state.clone();
+        tokio::spawn(async move {
+            if let Some(scraper) = state.scraper.as_ref() {
+                scraper.run(interval, state.cri.as_ref()).await;
+            }
+        });
+    }
 
-    let make_svc =
-        make_service_fn(|_conn| async { Ok::<_, anyhow::Error>(service_fn(handler_mux)) });
+    match acceptor {
+        Some(acceptor) => serve_tls(addr, acceptor, state).await?,
+        None => {
+            let make_svc = make_service_fn(move |_conn| {
+                let state = state.clone();
+                async move {
+                    Ok::<_, anyhow::Error>(service_fn(move |req| handler_mux(state.clone(), req)))
+                }
+            });
 
-    Server::bind(&addr).serve(make_svc).await?;
+            Server::bind(&addr).serve(make_svc).await?;
+        }
+    }
 
     Ok(())
 }
 
+// serve HTTPS, the TLS handshake of each connection is made in its own task
+// so that a slow client doesn't block the others
+async fn serve_tls(
+    addr: SocketAddr,
+    acceptor: TlsAcceptor,
+    state: Arc<MonitorState>,
+) -> Result<()> {
+    let listener = TcpListener::bind(addr)
+        .await
+        .context("failed to bind tls address")?;
+    loop {
+        let (stream, peer) = listener.accept().await?;
+        let acceptor = acceptor.clone();
+        let state = state.clone();
+        tokio::spawn(async move {
+            let stream = match acceptor.accept(stream).await {
+                Ok(stream) => stream,
+                Err(e) => {
+                    warn!(
+                        sl!(),
+                        "monitor: tls handshake with {} failed: {:?}", peer, e
+                    );
+                    return;
+                }
+            };
+            if let Err(e) = Http::new()
+                .serve_connection(
+                    stream,
+                    service_fn(move |req| handler_mux(state.clone(), req)),
+                )
+                .await
+            {
+                warn!(sl!(), "monitor: failed to serve {}: {:?}", peer, e);
+            }
+        });
+    }
+}
+
+fn tls_acceptor(config: &MonitorConfig) -> Result<Option<TlsAcceptor>> {
+    let (cert, key) = match (config.tls_cert.as_ref(), config.tls_key.as_ref()) {
+        (Some(cert), Some(key)) => (cert, key),
+        _ => return Ok(None),
+    };
+
+    let certs = read_pem(cert)?
+        .into_iter()
+        .filter_map(|item| match item {
+            rustls_pemfile::Item::X509Certificate(cert) => Some(Certificate(cert)),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    let key = read_pem(key)?
+        .into_iter()
+        .find_map(|item| match item {
+            rustls_pemfile::Item::PKCS8Key(key)
+            | rustls_pemfile::Item::RSAKey(key)
+            | rustls_pemfile::Item::ECKey(key) => Some(PrivateKey(key)),
+            _ => None,
+        })
+        .ok_or_else(|| anyhow!("no private key in {}", key))?;
+
+    let server_config = ServerConfig::builder()
+        .with_safe_defaults()
+        .with_no_client_auth()
+        .with_single_cert(certs, key)
+        .context("invalid tls cert or key")?;
+
+    Ok(Some(TlsAcceptor::from(Arc::new(server_config))))
+}
+
+fn read_pem(path: &str) -> Result<Vec<rustls_pemfile::Item>> {
+    let file = File::open(path).with_context(|| format!("open {}", path))?;
+    rustls_pemfile::read_all(&mut BufReader::new(file)).with_context(|| format!("read {}", path))
+}
+
 async fn root_uri_handler(_req: Request<Body>) -> Result<Response<Body>> {
     Response::builder()
         .status(StatusCode::OK)
         .body(Body::from(
             r#"Available HTTP endpoints:
     /metrics : Get metrics from sandboxes.
+    /metrics?sandbox=<sandbox id> : Get metrics from the sandbox.
 "#,
         ))
         .map_err(|e| anyhow!("Failed to Build Response {:?}", e))
 }
 
-async fn metrics_uri_handler(req: Request<Body>) -> Result<Response<Body>> {
+async fn metrics_uri_handler(state: &MonitorState, req: Request<Body>) -> Result<Response<Body>> {
     let mut response_body = String::new();
 
     response_body += &get_monitor_metrics().context("Failed to Get Monitor Metrics")?;
 
     if let Some(uri_query) = req.uri().query() {
         if let Ok(sandbox_id) = parse_sandbox_id(uri_query) {
-            response_body += &get_runtime_metrics(sandbox_id)
+            let runtime_metrics = get_runtime_metrics(sandbox_id, &state.guest_metrics_ports)
                 .await
                 .context(format!("{}\nFailed to Get Runtime Metrics", response_body))?;
+
+            // attach the metadata of the pod, if it's known to the container manager
+            let pod = match state.cri.as_ref() {
+                Some(cri) => cri.pod_metadata(sandbox_id).await,
+                None => None,
+            };
+            response_body += &match pod {
+                Some(pod) => add_labels(&runtime_metrics, &pod.labels()),
+                None => runtime_metrics,
+            };
+
+            return Response::builder()
+                .status(StatusCode::OK)
+                .body(Body::from(response_body))
+                .map_err(|e| anyhow!("Failed to Build Response {:?}", e));
         }
     }
 
+    // the metrics of all the sandboxes of the last background scrape
+    if let Some(scraper) = state.scraper.as_ref() {
+        response_body += &scraper.metrics();
+    }
+
     Response::builder()
         .status(StatusCode::OK)
         .body(Body::from(response_body))
         .map_err(|e| anyhow!("Failed to Build Response {:?}", e))
 }
 
-async fn get_runtime_metrics(sandbox_id: &str) -> Result<String> {
+async fn get_runtime_metrics(sandbox_id: &str, guest_metrics_ports: &[u32]) -> Result<String> {
     // build shim client
     let shim_client =
         MgmtClient::new(sandbox_id, Some(TIMEOUT)).context("failed to build shim mgmt client")?;
@@ -101,7 +244,7 @@ async fn get_runtime_metrics(sandbox_id: &str) -> Result<String> {
     let runtime_metrics = String::from_utf8(body::to_bytes(shim_response).await?.to_vec())
         .context("failed to get runtime_metrics")?;
 
-    Ok(runtime_metrics)
+    Ok(add_guest_metrics(&shim_client, runtime_metrics, guest_metrics_ports).await)
 }
 
 async fn not_found_uri_handler(_req: Request<Body>) -> Result<Response<Body>> {
@@ -137,6 +280,7 @@ mod tests {
     #[tokio::test]
     async fn test_root_uri_handler() {
         let root_resp = handler_mux(
+            Arc::new(MonitorState::default()),
             Request::builder()
                 .method("GET")
                 .uri("/")
@@ -152,6 +296,7 @@ mod tests {
     #[tokio::test]
     async fn test_metrics_uri_handler() {
         let metrics_resp = handler_mux(
+            Arc::new(MonitorState::default()),
             Request::builder()
                 .method("GET")
                 .uri("/metrics?sandbox=demo_sandbox")
@@ -167,6 +312,7 @@ mod tests {
     #[tokio::test]
     async fn test_not_found_uri_handler() {
         let not_found_resp = handler_mux(
+            Arc::new(MonitorState::default()),
             Request::builder()
                 .method("POST")
                 .uri("/metrics?sandbox=demo_sandbox")
@@ -3,6 +3,9 @@
 // SPDX-License-Identifier: Apache-2.0
 //
 
+mod cri;
 mod metrics;
+mod scraper;
 
+pub mod config;
 pub mod http_server;
@@ -0,0 +1,272 @@
+// Copyright 2022-2023 Ant Group
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+// The background scrape of the sandboxes, their shim management sockets are
+// found with a glob and their metrics are kept until the next scrape, so that
+// a single "/metrics" request returns the metrics of all the sandboxes. The
+// in-guest exporters, e.g. the DCGM exporter of the GPUs, are scraped through
+// the shims along with the sandbox metrics, and labelled the same way.
+
+use crate::monitor::cri::{add_labels, CriClient};
+use crate::sl;
+use crate::utils::TIMEOUT;
+
+use anyhow::{anyhow, Context, Result};
+use hyper::{body, StatusCode};
+use shim_interface::shim_mgmt::client::MgmtClient;
+use shim_interface::shim_mgmt::{GUEST_METRICS_PORT_KEY, GUEST_METRICS_URL};
+use slog::{debug, warn};
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::sync::RwLock;
+use std::time::Duration;
+
+const METRICS_URI: &str = "/metrics";
+
+pub struct Scraper {
+    target_socket_glob: String,
+    guest_metrics_ports: Vec<u32>,
+    // the labelled metrics of the sandboxes by sandbox id
+    scraped: RwLock<BTreeMap<String, String>>,
+}
+
+impl Scraper {
+    pub fn new(target_socket_glob: &str, guest_metrics_ports: &[u32]) -> Self {
+        Scraper {
+            target_socket_glob: target_socket_glob.to_string(),
+            guest_metrics_ports: guest_metrics_ports.to_vec(),
+            scraped: RwLock::new(BTreeMap::new()),
+        }
+    }
+
+    /// scrape the sandboxes every interval, forever
+    pub async fn run(&self, interval: Duration, cri: Option<&CriClient>) {
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            let scraped = self.scrape(cri).await;
+            debug!(sl!(), "monitor: scraped {} sandboxes", scraped.len());
+            // replaced rather than merged, to forget the removed sandboxes
+            *self.scraped.write().unwrap() = scraped;
+        }
+    }
+
+    /// the metrics of all the sandboxes of the last scrape
+    pub fn metrics(&self) -> String {
+        merge_metrics(self.scraped.read().unwrap().values().map(String::as_str))
+    }
+
+    async fn scrape(&self, cri: Option<&CriClient>) -> BTreeMap<String, String> {
+        let paths = match glob::glob(&self.target_socket_glob) {
+            Ok(paths) => paths.flatten().collect::<Vec<_>>(),
+            Err(e) => {
+                warn!(sl!(), "monitor: invalid target socket glob: {:?}", e);
+                return BTreeMap::new();
+            }
+        };
+
+        let results = futures::future::join_all(paths.into_iter().map(|path| async move {
+            let result = scrape_target(&path, cri, &self.guest_metrics_ports).await;
+            (result, path)
+        }))
+        .await;
+
+        let mut scraped = BTreeMap::new();
+        for (result, path) in results {
+            match result {
+                Ok((sandbox_id, metrics)) => {
+                    scraped.insert(sandbox_id, metrics);
+                }
+                // e.g. the sandbox is being stopped
+                Err(e) => warn!(
+                    sl!(),
+                    "monitor: failed to scrape {}: {:?}",
+                    path.display(),
+                    e
+                ),
+            }
+        }
+
+        scraped
+    }
+}
+
+// the sandbox id and the labelled metrics of the shim listening on the socket
+async fn scrape_target(
+    path: &Path,
+    cri: Option<&CriClient>,
+    guest_metrics_ports: &[u32],
+) -> Result<(String, String)> {
+    let sandbox_id = sandbox_id_of_socket(path)?;
+
+    let shim_client = MgmtClient::from_socket_path(path, Some(TIMEOUT));
+    let shim_response = shim_client
+        .get(METRICS_URI)
+        .await
+        .context("failed to get METRICS_URI")?;
+    let metrics = String::from_utf8(body::to_bytes(shim_response).await?.to_vec())
+        .context("failed to get runtime_metrics")?;
+    let metrics = add_guest_metrics(&shim_client, metrics, guest_metrics_ports).await;
+
+    let pod = match cri {
+        Some(cri) => cri.pod_metadata(&sandbox_id).await,
+        None => None,
+    };
+    let mut labels = vec![("sandbox_id", sandbox_id.as_str())];
+    if let Some(pod) = pod.as_ref() {
+        labels.extend(pod.labels());
+    }
+    let metrics = add_labels(&metrics, &labels);
+
+    Ok((sandbox_id, metrics))
+}
+
+/// add the metrics of the in-guest exporters of `ports` to the sandbox
+/// metrics, the exporters which can't be scraped are skipped, e.g. the GPU
+/// exporter of a sandbox without GPU
+pub async fn add_guest_metrics(client: &MgmtClient, metrics: String, ports: &[u32]) -> String {
+    let mut guest_metrics = vec![];
+    for port in ports.iter() {
+        match get_guest_metrics(client, *port).await {
+            Ok(m) => guest_metrics.push(m),
+            Err(e) => debug!(
+                sl!(),
+                "monitor: failed to scrape guest port {}: {:?}", port, e
+            ),
+        }
+    }
+    if guest_metrics.is_empty() {
+        return metrics;
+    }
+
+    merge_metrics(std::iter::once(metrics.as_str()).chain(guest_metrics.iter().map(String::as_str)))
+}
+
+async fn get_guest_metrics(client: &MgmtClient, port: u32) -> Result<String> {
+    let uri = format!("{}?{}={}", GUEST_METRICS_URL, GUEST_METRICS_PORT_KEY, port);
+    let response = client
+        .get(&uri)
+        .await
+        .context("failed to get guest metrics")?;
+    let status = response.status();
+    let body = String::from_utf8(body::to_bytes(response).await?.to_vec())
+        .context("failed to get guest metrics")?;
+    if status != StatusCode::OK {
+        return Err(anyhow!("{}: {}", status, body));
+    }
+
+    Ok(body)
+}
+
+fn sandbox_id_of_socket(path: &Path) -> Result<String> {
+    path.parent()
+        .and_then(Path::file_name)
+        .and_then(|name| name.to_str())
+        .map(String::from)
+        .ok_or_else(|| anyhow!("no sandbox directory for {}", path.display()))
+}
+
+#[derive(Default)]
+struct Family {
+    // the HELP and TYPE lines
+    header: Vec<String>,
+    samples: Vec<String>,
+}
+
+/// merge the metrics of several targets in the prometheus text format, the
+/// format doesn't allow a family to be repeated, so the samples of the
+/// families of the same name are put together under a single header
+pub fn merge_metrics<'a, I>(metrics: I) -> String
+where
+    I: Iterator<Item = &'a str>,
+{
+    let mut families: BTreeMap<String, Family> = BTreeMap::new();
+    for text in metrics {
+        // the samples follow the header of their family, and may be named
+        // after it, e.g. the "_bucket" samples of a histogram
+        let mut current: Option<String> = None;
+        for line in text.lines() {
+            let header = line
+                .strip_prefix("# HELP ")
+                .or_else(|| line.strip_prefix("# TYPE "));
+            if let Some(header) = header {
+                let name = header.split_whitespace().next().unwrap_or_default();
+                let family = families.entry(name.to_string()).or_default();
+                // the first HELP and TYPE of the family are kept
+                if !family.header.iter().any(|h| h[..7] == line[..7]) {
+                    family.header.push(line.to_string());
+                }
+                current = Some(name.to_string());
+            } else if !line.is_empty() && !line.starts_with('#') {
+                let sample = line
+                    .split(|c: char| c == '{' || c.is_whitespace())
+                    .next()
+                    .unwrap_or_default();
+                let name = match current.as_ref() {
+                    Some(name) if sample.starts_with(name.as_str()) => name.clone(),
+                    _ => sample.to_string(),
+                };
+                families
+                    .entry(name)
+                    .or_default()
+                    .samples
+                    .push(line.to_string());
+            }
+        }
+    }
+
+    let mut result = String::new();
+    for family in families.values() {
+        for line in family.header.iter().chain(family.samples.iter()) {
+            result.push_str(line);
+            result.push('\n');
+        }
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_merge_metrics() {
+        let shim1 = "# HELP kata_shim_fds Open FDs\n\
+                     # TYPE kata_shim_fds gauge\n\
+                     kata_shim_fds{sandbox_id=\"1\"} 12\n\
+                     # HELP kata_shim_rpc_durations_histogram_milliseconds RPC latency\n\
+                     # TYPE kata_shim_rpc_durations_histogram_milliseconds histogram\n\
+                     kata_shim_rpc_durations_histogram_milliseconds_bucket{sandbox_id=\"1\",le=\"1\"} 3\n\
+                     kata_shim_rpc_durations_histogram_milliseconds_count{sandbox_id=\"1\"} 3\n";
+        let shim2 = "# HELP kata_shim_fds Open FDs\n\
+                     # TYPE kata_shim_fds gauge\n\
+                     kata_shim_fds{sandbox_id=\"2\"} 14\n\
+                     kata_shim_untyped{sandbox_id=\"2\"} 1\n";
+
+        assert_eq!(
+            merge_metrics(vec![shim1, shim2].into_iter()),
+            "# HELP kata_shim_fds Open FDs\n\
+             # TYPE kata_shim_fds gauge\n\
+             kata_shim_fds{sandbox_id=\"1\"} 12\n\
+             kata_shim_fds{sandbox_id=\"2\"} 14\n\
+             # HELP kata_shim_rpc_durations_histogram_milliseconds RPC latency\n\
+             # TYPE kata_shim_rpc_durations_histogram_milliseconds histogram\n\
+             kata_shim_rpc_durations_histogram_milliseconds_bucket{sandbox_id=\"1\",le=\"1\"} 3\n\
+             kata_shim_rpc_durations_histogram_milliseconds_count{sandbox_id=\"1\"} 3\n\
+             kata_shim_untyped{sandbox_id=\"2\"} 1\n"
+        );
+        assert_eq!(merge_metrics(vec![].into_iter()), "");
+    }
+
+    #[test]
+    fn test_sandbox_id_of_socket() {
+        assert_eq!(
+            sandbox_id_of_socket(Path::new("/run/kata/sandbox-1/shim-monitor.sock")).unwrap(),
+            "sandbox-1"
+        );
+        assert!(sandbox_id_of_socket(Path::new("/shim-monitor.sock")).is_err());
+    }
+}
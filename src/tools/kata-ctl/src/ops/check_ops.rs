@@ -5,12 +5,11 @@
 
 use crate::arch::arch_specific::get_checks;
 
-use crate::args::{
-    CheckArgument, CheckSubCommand, IptablesCommand, MetricsCommand, MonitorArgument,
-};
+use crate::args::{CheckArgument, CheckSubCommand, MetricsCommand, MonitorArgument};
 
 use crate::check;
 
+use crate::monitor::config::MonitorConfig;
 use crate::monitor::http_server;
 
 use crate::ops::version;
@@ -19,8 +18,6 @@ use crate::types::*;
 
 use anyhow::{anyhow, Context, Result};
 
-const MONITOR_DEFAULT_SOCK_ADDR: &str = "127.0.0.1:8090";
-
 use slog::{info, o, warn};
 
 const NAME: &str = "kata-ctl";
@@ -126,23 +123,15 @@ pub fn handle_factory() -> Result<()> {
     Ok(())
 }
 
-pub fn handle_iptables(_args: IptablesCommand) -> Result<()> {
-    Ok(())
-}
-
 pub fn handle_metrics(_args: MetricsCommand) -> Result<()> {
     Ok(())
 }
 
 pub fn handle_monitor(monitor_args: MonitorArgument) -> Result<()> {
+    let config = MonitorConfig::new(&monitor_args).context("failed to load monitor config")?;
     tokio::runtime::Runtime::new()
         .context("failed to new runtime for aync http server")?
-        .block_on(http_server::http_server_setup(
-            monitor_args
-                .address
-                .as_deref()
-                .unwrap_or(MONITOR_DEFAULT_SOCK_ADDR),
-        ))
+        .block_on(http_server::http_server_setup(config))
 }
 
 pub fn handle_version() -> Result<()> {
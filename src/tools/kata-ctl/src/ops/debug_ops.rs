@@ -0,0 +1,55 @@
+// Copyright (c) 2023 Kata Containers community
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+use std::fs;
+use std::time::Duration;
+
+use anyhow::{anyhow, Context, Result};
+use hyper::StatusCode;
+use shim_interface::shim_mgmt::{client::MgmtClient, DEBUG_BUNDLE_URL};
+use slog::{info, o};
+
+use crate::args::DebugBundleArguments;
+
+// the bundle is collected from the guest as well, which takes longer than
+// the other requests
+const DEBUG_BUNDLE_TIMEOUT: Duration = Duration::from_secs(30);
+
+macro_rules! sl {
+    () => {
+        slog_scope::logger().new(o!("subsystem" => "debug_ops"))
+    };
+}
+
+pub fn handle_debug_bundle(args: DebugBundleArguments) -> Result<()> {
+    let output = args
+        .output
+        .unwrap_or_else(|| format!("kata-debug-{}.tar", args.sandbox_id));
+    let bundle = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()?
+        .block_on(get_debug_bundle(&args.sandbox_id))
+        .context("get debug bundle")?;
+    fs::write(&output, bundle).with_context(|| format!("write {}", output))?;
+    info!(sl!(), "debug bundle written to {}", output);
+
+    Ok(())
+}
+
+async fn get_debug_bundle(sandbox_id: &str) -> Result<Vec<u8>> {
+    let shim_client = MgmtClient::new(sandbox_id, Some(DEBUG_BUNDLE_TIMEOUT))?;
+    let response = shim_client.get(DEBUG_BUNDLE_URL).await?;
+    let status = response.status();
+    let body = hyper::body::to_bytes(response.into_body()).await?;
+    if status != StatusCode::OK {
+        return Err(anyhow!(
+            "failed to collect debug bundle ({:?}): {}",
+            status,
+            String::from_utf8_lossy(&body)
+        ));
+    }
+
+    Ok(body.to_vec())
+}
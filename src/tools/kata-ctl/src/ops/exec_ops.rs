@@ -23,7 +23,10 @@ use slog::{debug, error, o};
 use vmm_sys_util::terminal::Terminal;
 
 use crate::args::ExecArguments;
-use shim_interface::shim_mgmt::{client::MgmtClient, AGENT_URL};
+use shim_interface::mgmt_socket_addr;
+use shim_interface::shim_mgmt::{
+    client::MgmtClient, AGENT_URL, DEBUG_CONSOLE_ATTACH_URL, DEBUG_CONSOLE_PROTOCOL,
+};
 
 use crate::utils::TIMEOUT;
 
@@ -35,6 +38,10 @@ const SCHEME_HYBRID_VSOCK: &str = "HVSOCK";
 const EPOLL_EVENTS_LEN: usize = 16;
 const KATA_AGENT_VSOCK_TIMEOUT: u64 = 5;
 
+const HTTP_SWITCHING_PROTOCOLS: &str = "101";
+const HTTP_HEAD_END: &[u8] = b"\r\n\r\n";
+const HTTP_HEAD_MAX_LEN: usize = 8192;
+
 type Result<T> = std::result::Result<T, Error>;
 
 // Convenience macro to obtain the scope logger
@@ -285,6 +292,75 @@ impl SockHandler for HvsockConfig {
     }
 }
 
+// The debug console reached through the shim management socket, the shim
+// splices the upgraded connection to the console port of the guest.
+struct ShimConsoleConfig {
+    sock_path: String,
+}
+
+impl ShimConsoleConfig {
+    fn new(sandbox_id: &str) -> anyhow::Result<Self> {
+        let addr = mgmt_socket_addr(sandbox_id).context("get shim management socket")?;
+        let sock_path = addr
+            .strip_prefix("unix://")
+            .ok_or_else(|| anyhow!("invalid shim management socket {:?}", addr))?;
+        Ok(ShimConsoleConfig {
+            sock_path: sock_path.to_string(),
+        })
+    }
+}
+
+impl SockHandler for ShimConsoleConfig {
+    fn setup_sock(&self) -> anyhow::Result<UnixStream> {
+        let mut stream = UnixStream::connect(&self.sock_path)
+            .with_context(|| format!("failed to connect to shim {:?}", self.sock_path))?;
+        stream.set_read_timeout(Some(TIMEOUT))?;
+        stream.set_write_timeout(Some(TIMEOUT))?;
+
+        let request = format!(
+            "GET {} HTTP/1.1\r\nHost: localhost\r\nConnection: Upgrade\r\nUpgrade: {}\r\n\r\n",
+            DEBUG_CONSOLE_ATTACH_URL, DEBUG_CONSOLE_PROTOCOL
+        );
+        stream.write_all(request.as_bytes())?;
+        let head = read_response_head(&mut stream).context("read shim response")?;
+        check_upgrade_response(&head)?;
+
+        stream.set_read_timeout(None)?;
+        stream.set_write_timeout(None)?;
+        Ok(stream)
+    }
+}
+
+// read_response_head reads the status line and the headers of the HTTP
+// response, the console output following them is left in the stream.
+fn read_response_head(reader: &mut impl Read) -> anyhow::Result<String> {
+    let mut head = Vec::new();
+    let mut byte = [0u8; 1];
+    while !head.ends_with(HTTP_HEAD_END) {
+        if head.len() >= HTTP_HEAD_MAX_LEN {
+            return Err(anyhow!("response head exceeds {} bytes", HTTP_HEAD_MAX_LEN));
+        }
+        if reader.read(&mut byte)? == 0 {
+            return Err(anyhow!(
+                "connection closed by the shim, check that the debug console is enabled"
+            ));
+        }
+        head.push(byte[0]);
+    }
+    Ok(String::from_utf8_lossy(&head).into_owned())
+}
+
+fn check_upgrade_response(head: &str) -> anyhow::Result<()> {
+    let status_line = head.lines().next().unwrap_or_default();
+    match status_line.split_whitespace().nth(1) {
+        Some(HTTP_SWITCHING_PROTOCOLS) => Ok(()),
+        _ => Err(anyhow!(
+            "failed to attach to debug console: {:?}",
+            status_line
+        )),
+    }
+}
+
 fn setup_client(server_url: String, dbg_console_port: u32) -> anyhow::Result<UnixStream> {
     // server address format: scheme://[cid|/x/domain.sock]:port
     let url_fields: Vec<&str> = server_url.split("://").collect();
@@ -357,13 +433,19 @@ fn get_server_socket(sandbox_id: &str) -> anyhow::Result<String> {
     Ok(server_url)
 }
 
-fn do_run_exec(sandbox_id: &str, dbg_console_vport: u32) -> anyhow::Result<()> {
+fn do_run_exec(sandbox_id: &str, dbg_console_vport: u32, through_shim: bool) -> anyhow::Result<()> {
     // sandbox_id MUST be a long ID.
-    let server_url = get_server_socket(sandbox_id).context("get debug console socket URL")?;
-    if server_url.is_empty() {
-        return Err(anyhow!("server url is empty."));
-    }
-    let sock_stream = setup_client(server_url, dbg_console_vport)?;
+    let sock_stream = if through_shim {
+        ShimConsoleConfig::new(sandbox_id)?
+            .setup_sock()
+            .context("attach through shim")?
+    } else {
+        let server_url = get_server_socket(sandbox_id).context("get debug console socket URL")?;
+        if server_url.is_empty() {
+            return Err(anyhow!("server url is empty."));
+        }
+        setup_client(server_url, dbg_console_vport)?
+    };
 
     let mut epoll_context = EpollContext::new().expect("create epoll context");
     epoll_context
@@ -386,7 +468,11 @@ fn do_run_exec(sandbox_id: &str, dbg_console_vport: u32) -> anyhow::Result<()> {
 
 // kata-ctl handle exec command starts here.
 pub fn handle_exec(exec_args: ExecArguments) -> anyhow::Result<()> {
-    do_run_exec(exec_args.sandbox_id.as_str(), exec_args.vport)?;
+    do_run_exec(
+        exec_args.sandbox_id.as_str(),
+        exec_args.vport,
+        exec_args.through_shim,
+    )?;
 
     Ok(())
 }
@@ -433,6 +519,26 @@ mod tests {
         std::fs::remove_file(kata_hybrid_addr).unwrap_or_default();
     }
 
+    #[test]
+    fn test_read_response_head() {
+        let response = b"HTTP/1.1 101 Switching Protocols\r\nupgrade: kata-debug-console\r\n\r\n# ";
+        let mut reader = io::Cursor::new(&response[..]);
+        let head = read_response_head(&mut reader).unwrap();
+        assert!(head.starts_with("HTTP/1.1 101"));
+        assert!(check_upgrade_response(&head).is_ok());
+        // the console output is left to the session
+        let mut rest = String::new();
+        reader.read_to_string(&mut rest).unwrap();
+        assert_eq!(rest, "# ");
+
+        let mut reader = io::Cursor::new(&b"HTTP/1.1 404 Not Found\r\n\r\n"[..]);
+        let head = read_response_head(&mut reader).unwrap();
+        assert!(check_upgrade_response(&head).is_err());
+
+        let mut reader = io::Cursor::new(&b"HTTP/1.1 101"[..]);
+        assert!(read_response_head(&mut reader).is_err());
+    }
+
     #[test]
     fn test_setup_vsock_client_failed() {
         let hybrid_sock_addr = "hvsock://8:1024";
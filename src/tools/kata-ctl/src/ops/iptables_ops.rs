@@ -0,0 +1,73 @@
+// Copyright (c) 2023 Kata Containers community
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+use std::fs;
+
+use anyhow::{anyhow, Context, Result};
+use hyper::{Body, Response, StatusCode};
+use shim_interface::shim_mgmt::{client::MgmtClient, IP6_TABLE_URL, IP_TABLE_URL};
+
+use crate::args::{IpTablesArguments, IptablesCommand};
+use crate::utils::TIMEOUT;
+
+pub fn handle_iptables(args: IptablesCommand) -> Result<()> {
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()?;
+    match args.iptables {
+        IpTablesArguments::Get(args) => {
+            let rules = runtime
+                .block_on(get_iptables(&args.sandbox_id, args.v6))
+                .context("get iptables")?;
+            print!("{}", rules);
+        }
+        IpTablesArguments::Set(args) => {
+            let rules = fs::read(&args.file).with_context(|| format!("read {}", args.file))?;
+            runtime
+                .block_on(set_iptables(&args.sandbox_id, args.v6, rules))
+                .with_context(|| format!("set iptables from {}", args.file))?;
+        }
+    }
+
+    Ok(())
+}
+
+fn iptables_url(is_ipv6: bool) -> &'static str {
+    if is_ipv6 {
+        IP6_TABLE_URL
+    } else {
+        IP_TABLE_URL
+    }
+}
+
+async fn get_iptables(sandbox_id: &str, is_ipv6: bool) -> Result<String> {
+    let shim_client = MgmtClient::new(sandbox_id, Some(TIMEOUT))?;
+    let response = shim_client.get(iptables_url(is_ipv6)).await?;
+    let body = response_body(response).await?;
+
+    Ok(String::from_utf8_lossy(&body).to_string())
+}
+
+async fn set_iptables(sandbox_id: &str, is_ipv6: bool, rules: Vec<u8>) -> Result<()> {
+    let shim_client = MgmtClient::new(sandbox_id, Some(TIMEOUT))?;
+    let response = shim_client.put(iptables_url(is_ipv6), rules).await?;
+    response_body(response).await?;
+
+    Ok(())
+}
+
+async fn response_body(response: Response<Body>) -> Result<Vec<u8>> {
+    let status = response.status();
+    let body = hyper::body::to_bytes(response.into_body()).await?;
+    if status != StatusCode::OK {
+        return Err(anyhow!(
+            "shim returned {:?}: {}",
+            status,
+            String::from_utf8_lossy(&body)
+        ));
+    }
+
+    Ok(body.to_vec())
+}
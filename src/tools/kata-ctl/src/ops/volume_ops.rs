@@ -7,6 +7,7 @@ use crate::args::{DirectVolSubcommand, DirectVolumeCommand};
 
 use anyhow::{anyhow, Ok, Result};
 use futures::executor;
+use kata_sys_util::host_lock::{self, HostResource, DEFAULT_LOCK_TIMEOUT};
 use kata_types::mount::{
     get_volume_mount_info, join_path, DirectVolumeMountInfo, KATA_DIRECT_VOLUME_ROOT_PATH,
     KATA_MOUNT_INFO_FILE_NAME,
@@ -71,11 +72,11 @@ async fn resize(volume_path: &str, size: u64) -> Result<Option<String>> {
         .await?;
     let status = response.status();
     if status != StatusCode::OK {
-        let body = format!("{:?}", response.into_body());
+        let body = hyper::body::to_bytes(response.into_body()).await?;
         return Err(anyhow!(
-            "failed to resize volume ({:?}): {:?}",
+            "failed to resize volume ({:?}): {}",
             status,
-            body
+            String::from_utf8_lossy(&body)
         ));
     }
 
@@ -92,14 +93,29 @@ async fn stats(volume_path: &str) -> Result<Option<String>> {
 
     let shim_client = MgmtClient::new(&sandbox_id, Some(TIMEOUT))?;
     let response = shim_client.get(&req_url).await?;
+    let status = response.status();
     // turn body into string
-    let body = format!("{:?}", response.into_body());
+    let body = hyper::body::to_bytes(response.into_body()).await?;
+    let body = String::from_utf8_lossy(&body).to_string();
+    if status != StatusCode::OK {
+        return Err(anyhow!(
+            "failed to get volume stats ({:?}): {}",
+            status,
+            body
+        ));
+    }
 
     Ok(Some(body))
 }
 
 // add writes the mount info (json string) of a direct volume into a filesystem path known to Kata Containers.
 pub fn add(volume_path: &str, mount_info: &str) -> Result<Option<String>> {
+    // the shims read the mount info under the same lock
+    let _guard = host_lock::lock(
+        HostResource::DirectVolume,
+        Some(volume_path),
+        DEFAULT_LOCK_TIMEOUT,
+    )?;
     let mount_info_dir_path = join_path(KATA_DIRECT_VOLUME_ROOT_PATH, volume_path)?;
 
     // create directory if missing
@@ -119,6 +135,11 @@ pub fn add(volume_path: &str, mount_info: &str) -> Result<Option<String>> {
 
 // remove deletes the direct volume path including all the files inside it.
 pub fn remove(volume_path: &str) -> Result<Option<String>> {
+    let _guard = host_lock::lock(
+        HostResource::DirectVolume,
+        Some(volume_path),
+        DEFAULT_LOCK_TIMEOUT,
+    )?;
     let path = join_path(KATA_DIRECT_VOLUME_ROOT_PATH, volume_path)?;
     // removes path and any children it contains.
     fs::remove_dir_all(path)?;
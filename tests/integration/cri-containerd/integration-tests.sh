@@ -237,6 +237,31 @@ function TestKilledVmmCleanup() {
 	info "stop containerd"
 }
 
+function TestNetworkTeardown() {
+	local iterations=${NETWORK_TEARDOWN_ITERATIONS:-5}
+
+	info "test network teardown with ${iterations} sandboxes"
+
+	local links_before=$(ip -o link | wc -l)
+	local netns_before=$(sudo ip netns list | wc -l)
+
+	for i in $(seq 1 "${iterations}"); do
+		testContainerStart
+		testContainerStop
+	done
+
+	local links_after=$(ip -o link | wc -l)
+	local netns_after=$(sudo ip netns list | wc -l)
+	[ "${links_before}" -eq "${links_after}" ] || \
+		die "found leaked links, ${links_before} links before and ${links_after} links after"
+	[ "${netns_before}" -eq "${netns_after}" ] || \
+		die "found leaked netns, ${netns_before} netns before and ${netns_after} netns after"
+
+	# the taps created by the runtime are named with the _kata suffix
+	local taps=$(sudo ip -all netns exec ip -o link show 2>/dev/null | grep "_kata" || true)
+	[ -z "${taps}" ] || die "found leaked taps: ${taps}"
+}
+
 function TestContainerMemoryUpdate() {
 	if [[ "${KATA_HYPERVISOR}" != "qemu" ]] || [[ "${ARCH}" == "ppc64le" ]] || [[ "${ARCH}" == "s390x" ]]; then
 		return
@@ -495,6 +520,8 @@ function main() {
 
 	TestKilledVmmCleanup
 
+	TestNetworkTeardown
+
 	popd
 }
 
@@ -0,0 +1,49 @@
+#!/usr/bin/env bats
+#
+# Copyright (c) 2023 Kata Containers community
+#
+# SPDX-License-Identifier: Apache-2.0
+#
+
+load "${BATS_TEST_DIRNAME}/../../common.bash"
+load "${BATS_TEST_DIRNAME}/tests_common.sh"
+
+# The default masked and readonly paths of containerd and CRI-O are expected
+# to behave as they do with runc:
+#  - masked files read as /dev/null
+#  - masked directories are empty read-only tmpfs
+#  - readonly paths can't be written to
+setup() {
+	pod_name="pod-masked-paths"
+	get_pod_config_dir
+}
+
+@test "Masked and readonly paths of container" {
+	kubectl create -f "${pod_config_dir}/pod-masked-paths.yaml"
+	kubectl wait --for=condition=Ready --timeout=$timeout pod "$pod_name"
+
+	# masked file
+	size=$(kubectl exec "$pod_name" -- sh -c "cat /proc/kcore | wc -c")
+	[ "$size" -eq 0 ]
+
+	# masked directories
+	for dir in /proc/acpi /proc/scsi /sys/firmware; do
+		kubectl exec "$pod_name" -- sh -c "[ ! -d $dir ] || [ -z \"\$(ls -A $dir)\" ]"
+		kubectl exec "$pod_name" -- sh -c "[ ! -d $dir ] || ! touch $dir/kata-test"
+	done
+
+	# readonly paths
+	kubectl exec "$pod_name" -- sh -c "grep -q ' /proc/sys proc ro,' /proc/self/mounts"
+	run kubectl exec "$pod_name" -- sh -c "echo 0 > /proc/sys/kernel/shm_rmid_forced"
+	[ "$status" -ne 0 ]
+	run kubectl exec "$pod_name" -- sh -c "echo h > /proc/sysrq-trigger"
+	[ "$status" -ne 0 ]
+}
+
+teardown() {
+	# Debugging information
+	kubectl describe "pod/$pod_name"
+	kubectl exec "$pod_name" -- sh -c "cat /proc/self/mounts"
+
+	kubectl delete pod "$pod_name"
+}
@@ -0,0 +1,127 @@
+#!/bin/bash
+#
+# Copyright (c) 2023 Kata Containers community
+#
+# SPDX-License-Identifier: Apache-2.0
+
+# Description of the test:
+# This test runs 'fio' on a block device backed container rootfs once for
+# each of the asynchronous IO modes supported by 'block_device_aio'
+# (threads, native and io_uring), and extracts the bandwidth and IOPS
+# of every run so that the modes can be compared.
+
+set -e
+
+# General env
+SCRIPT_PATH=$(dirname "$(readlink -f "$0")")
+source "${SCRIPT_PATH}/../lib/common.bash"
+
+TEST_NAME="block-aio"
+IMAGE="docker.io/library/local-block-aio:latest"
+DOCKERFILE="${SCRIPT_PATH}/block_aio_dockerfile/Dockerfile"
+
+# The snapshotter must provide block devices for the rootfs,
+# otherwise the aio mode of the block backend is not exercised
+SNAPSHOTTER="${SNAPSHOTTER:-devmapper}"
+
+# Asynchronous IO modes to be compared
+AIO_MODES=(${AIO_MODES:-threads native io_uring})
+AIO_ANNOTATION="io.katacontainers.config.hypervisor.block_device_aio"
+CACHE_DIRECT_ANNOTATION="io.katacontainers.config.hypervisor.block_device_cache_direct"
+
+# fio parameters, run inside of the container
+TESTDIR="${TESTDIR:-/tmp}"
+FIO_RW="${FIO_RW:-randrw}"
+FIO_BS="${FIO_BS:-4k}"
+FIO_SIZE="${FIO_SIZE:-512M}"
+FIO_IODEPTH="${FIO_IODEPTH:-32}"
+FIO_RUNTIME="${FIO_RUNTIME:-30}"
+CMD="fio --name=block-aio --directory=${TESTDIR} --ioengine=libaio --direct=1 \
+	--rw=${FIO_RW} --bs=${FIO_BS} --size=${FIO_SIZE} --iodepth=${FIO_IODEPTH} \
+	--runtime=${FIO_RUNTIME} --time_based --group_reporting --output-format=json"
+
+function run_fio() {
+	local aio="$1"
+	local annotations="--annotation ${AIO_ANNOTATION}=${aio}"
+
+	# native aio requires O_DIRECT on the host side
+	if [ "${aio}" == "native" ]; then
+		annotations+=" --annotation ${CACHE_DIRECT_ANNOTATION}=true"
+	fi
+
+	sudo -E ${CTR_EXE} run --rm --runtime=${CTR_RUNTIME} --snapshotter=${SNAPSHOTTER} \
+		${annotations} ${IMAGE} "${TEST_NAME}-${aio}" ${CMD}
+}
+
+function main() {
+	# Check tools/commands dependencies
+	cmds=("jq" "docker")
+
+	init_env
+	check_cmds "${cmds[@]}"
+	check_ctr_images "${IMAGE}" "${DOCKERFILE}"
+	sudo systemctl restart containerd
+	metrics_json_init
+
+	# Save configuration
+	metrics_json_start_array
+
+	local json="$(cat << EOF
+	{
+		"Snapshotter" : "${SNAPSHOTTER}",
+		"Read write mode" : "${FIO_RW}",
+		"Block size" : "${FIO_BS}",
+		"Size" : "${FIO_SIZE}",
+		"IO depth" : ${FIO_IODEPTH},
+		"Runtime" : ${FIO_RUNTIME}
+	}
+EOF
+)"
+	metrics_json_add_array_element "${json}"
+	metrics_json_end_array "Config"
+
+	# Save results
+	info "Running block aio tests"
+	metrics_json_start_array
+
+	for aio in "${AIO_MODES[@]}"; do
+		info "Running fio with block_device_aio=${aio}"
+		local output=$(run_fio "${aio}")
+
+		local read_bw=$(echo "${output}" | jq '.jobs[0].read.bw')
+		local read_iops=$(echo "${output}" | jq '.jobs[0].read.iops')
+		local write_bw=$(echo "${output}" | jq '.jobs[0].write.bw')
+		local write_iops=$(echo "${output}" | jq '.jobs[0].write.iops')
+
+		local json="$(cat << EOF
+	{
+		"${aio}": {
+			"read bw": {
+				"Result" : ${read_bw},
+				"Units"  : "KiB/s"
+			},
+			"read iops": {
+				"Result" : ${read_iops},
+				"Units"  : "iops"
+			},
+			"write bw": {
+				"Result" : ${write_bw},
+				"Units"  : "KiB/s"
+			},
+			"write iops": {
+				"Result" : ${write_iops},
+				"Units"  : "iops"
+			}
+		}
+	}
+EOF
+)"
+		metrics_json_add_array_element "${json}"
+	done
+
+	metrics_json_end_array "Results"
+	metrics_json_save
+	clean_env_ctr
+}
+
+main "$@"